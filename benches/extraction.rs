@@ -0,0 +1,131 @@
+//! Extraction pipeline benchmarks: the previous slice/rescan/re-serialize path
+//! ("before") against the in-place segmented path ("after").
+//!
+//! Run with `cargo bench --bench extraction`. Allocation counts for each path are
+//! printed once per input size before timing starts.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use schemars::JsonSchema;
+use semantic_query::core::{ParsedResponse, ResponseItem};
+use semantic_query::json_utils::{deserialize_stream_map, find_json_structures, ParsedOrUnknown};
+use semantic_query::streaming::TextContent;
+use serde::{Deserialize, Serialize};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct Finding {
+    id: u32,
+    title: String,
+    severity: String,
+    tags: Vec<String>,
+}
+
+/// A long response mixing prose, wrapped arrays of findings and unrelated JSON
+fn response(findings: usize) -> String {
+    let mut raw = String::from("Here is the analysis you asked for.\n\n");
+    for chunk in 0..findings.div_ceil(10) {
+        raw.push_str(&format!("Batch {chunk}: the following issues were found.\n"));
+        raw.push_str("{\"results\": [");
+        for i in 0..10.min(findings - chunk * 10) {
+            if i > 0 {
+                raw.push(',');
+            }
+            let id = chunk * 10 + i;
+            raw.push_str(&format!(
+                "{{\"id\": {id}, \"title\": \"Finding number {id} with a reasonably long title\", \"severity\": \"high\", \"tags\": [\"a\", \"b\", \"c\"]}}"
+            ));
+        }
+        raw.push_str("], \"meta\": {\"page\": 1}}\n\n");
+    }
+    raw.push_str("Let me know if you want more detail.");
+    raw
+}
+
+/// The pipeline as it was: every root is sliced out and rescanned, text is copied
+/// eagerly, and each data item's original text is rebuilt by re-serializing it.
+fn before(raw: &str) -> ParsedResponse<Finding> {
+    let mut items = Vec::new();
+    let mut cursor = 0;
+    for node in find_json_structures(raw) {
+        let text_slice = &raw[cursor..node.start];
+        if !text_slice.trim().is_empty() {
+            items.push(ResponseItem::Text(TextContent { text: text_slice.to_string(), block: None }));
+        }
+        let end = node.end + 1;
+        let json_slice = &raw[node.start..end];
+        let mut any_parsed = false;
+        for item in deserialize_stream_map::<Finding>(json_slice) {
+            match item {
+                ParsedOrUnknown::Parsed(data) => {
+                    any_parsed = true;
+                    let original_text = serde_json::to_string(&data).unwrap();
+                    items.push(ResponseItem::Data { data, original_text, confidence_map: None });
+                }
+                ParsedOrUnknown::Unknown(u) => {
+                    let text = json_slice[u.start..u.end + 1].to_string();
+                    items.push(ResponseItem::Text(TextContent { text, block: None }));
+                }
+            }
+        }
+        if !any_parsed {
+            items.push(ResponseItem::Text(TextContent { text: json_slice.to_string(), block: None }));
+        }
+        cursor = end;
+    }
+    if !raw[cursor..].trim().is_empty() {
+        items.push(ResponseItem::Text(TextContent { text: raw[cursor..].to_string(), block: None }));
+    }
+    ParsedResponse { items, safety: None, truncated: false, error: None, cost: None }
+}
+
+fn after(raw: &str) -> ParsedResponse<Finding> {
+    ParsedResponse::from_raw(raw)
+}
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - start
+}
+
+fn extraction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("extraction");
+    for findings in [100, 1_000, 10_000] {
+        let raw = response(findings);
+        assert_eq!(before(&raw).data_count(), after(&raw).data_count());
+        println!(
+            "{findings} findings ({} bytes): before {} allocations, after {} allocations",
+            raw.len(),
+            allocations(|| drop(before(black_box(&raw)))),
+            allocations(|| drop(after(black_box(&raw)))),
+        );
+
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        group.bench_with_input(BenchmarkId::new("before", findings), &raw, |b, raw| b.iter(|| before(black_box(raw))));
+        group.bench_with_input(BenchmarkId::new("after", findings), &raw, |b, raw| b.iter(|| after(black_box(raw))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, extraction);
+criterion_main!(benches);
@@ -0,0 +1,146 @@
+//! Offline benchmarks for the parsing layer: structure discovery, incremental
+//! feeding, stream segmentation and `extract_all`, across response sizes and JSON
+//! densities. No network or API keys are involved.
+//!
+//! Run with `cargo bench --bench parsing`; filter with e.g.
+//! `cargo bench --bench parsing -- find_json_structures/dense`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use schemars::JsonSchema;
+use semantic_query::json_utils::{extract_all, find_json_structures, JsonStreamParser};
+use semantic_query::streaming::build_parsed_stream;
+use serde::Deserialize;
+
+const SIZES: [(&str, usize); 4] = [("1KB", 1 << 10), ("64KB", 64 << 10), ("1MB", 1 << 20), ("10MB", 10 << 20)];
+
+/// Bytes of JSON per byte of prose, roughly
+#[derive(Clone, Copy)]
+enum Density {
+    /// Prose only; measures the scanner's text fast path
+    Prose,
+    /// Mostly prose with an occasional object
+    Sparse,
+    /// Back-to-back objects with a short line of prose between
+    Dense,
+}
+
+impl Density {
+    const ALL: [Density; 3] = [Density::Prose, Density::Sparse, Density::Dense];
+
+    fn name(self) -> &'static str {
+        match self {
+            Density::Prose => "prose",
+            Density::Sparse => "sparse",
+            Density::Dense => "dense",
+        }
+    }
+
+    fn prose_lines_per_object(self) -> Option<usize> {
+        match self {
+            Density::Prose => None,
+            Density::Sparse => Some(12),
+            Density::Dense => Some(1),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[allow(dead_code)]
+struct Record {
+    id: u64,
+    name: String,
+    score: f64,
+    tags: Vec<String>,
+    owner: Owner,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[allow(dead_code)]
+struct Owner {
+    login: String,
+    admin: bool,
+}
+
+const PROSE: &str = "The model explains its reasoning here, mentioning {braces} and [brackets] in passing.\n";
+
+/// Deterministic response of about `size` bytes at the given density
+fn response(size: usize, density: Density) -> String {
+    let mut raw = String::with_capacity(size + 256);
+    let mut id = 0u64;
+    while raw.len() < size {
+        match density.prose_lines_per_object() {
+            None => raw.push_str(PROSE),
+            Some(lines) => {
+                for _ in 0..lines {
+                    raw.push_str(PROSE);
+                }
+                raw.push_str(&format!(
+                    "{{\"id\": {id}, \"name\": \"record-{id}\", \"score\": {}.5, \"tags\": [\"x\", \"y\"], \"owner\": {{\"login\": \"user{}\", \"admin\": {}}}}}\n",
+                    id % 100,
+                    id % 7,
+                    id % 2 == 0
+                ));
+                id += 1;
+            }
+        }
+    }
+    raw
+}
+
+fn inputs() -> Vec<(String, String)> {
+    let mut inputs = Vec::new();
+    for density in Density::ALL {
+        for (label, size) in SIZES {
+            inputs.push((format!("{}/{label}", density.name()), response(size, density)));
+        }
+    }
+    inputs
+}
+
+fn bench<F>(c: &mut Criterion, name: &str, inputs: &[(String, String)], mut f: F)
+where
+    F: FnMut(&str),
+{
+    let mut group = c.benchmark_group(name);
+    for (id, raw) in inputs {
+        if raw.len() >= 1 << 20 {
+            group.sample_size(10);
+        } else {
+            group.sample_size(50);
+        }
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(id), raw.as_str(), |b, raw| b.iter(|| f(black_box(raw))));
+    }
+    group.finish();
+}
+
+fn parsing(c: &mut Criterion) {
+    let inputs = inputs();
+
+    bench(c, "find_json_structures", &inputs, |raw| {
+        black_box(find_json_structures(raw));
+    });
+    bench(c, "stream_parser_feed", &inputs, |raw| {
+        // Feed in provider-sized chunks, respecting char boundaries
+        let mut parser = JsonStreamParser::new();
+        let mut rest = raw;
+        while !rest.is_empty() {
+            let mut cut = rest.len().min(256);
+            while !rest.is_char_boundary(cut) {
+                cut += 1;
+            }
+            let (chunk, tail) = rest.split_at(cut);
+            black_box(parser.feed(chunk));
+            rest = tail;
+        }
+    });
+    bench(c, "build_parsed_stream", &inputs, |raw| {
+        black_box(build_parsed_stream::<Record>(raw));
+    });
+    bench(c, "extract_all", &inputs, |raw| {
+        black_box(extract_all::<Record>(raw));
+    });
+}
+
+criterion_group!(benches, parsing);
+criterion_main!(benches);
@@ -53,7 +53,7 @@ You are an assistant that chats and emits tool calls when needed.
     let mut n = 0usize;
     while let Some(item) = stream.next().await {
         match item {
-            StreamItem::Text(TextContent { text }) => {
+            StreamItem::Text(TextContent { text, .. }) => {
                 let s = text.trim();
                 if !s.is_empty() { println!("[agent] {}", s); }
             }
@@ -61,6 +61,7 @@ You are an assistant that chats and emits tool calls when needed.
                 n += 1;
                 println!("[toolcall {}] {}\n{}", n, tc.name, serde_json::to_string_pretty(&tc.args).unwrap_or_default());
             }
+            _ => {}
         }
     }
 
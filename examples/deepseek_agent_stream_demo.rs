@@ -207,6 +207,7 @@ Approach: Think step-by-step, chat your thoughts, and propose tool calls.
                 println!("{}{}{}", COLOR_TOOL, pretty_json(&tc.args), COLOR_RESET);
                 last_was_newline = false;
             }
+            Ok(StreamItem::Finished { .. }) => {}
             Err(e) => {
                 eprintln!("\nStream error: {}", e);
                 break;
@@ -70,6 +70,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("   Found {} questions", quiz.questions.len());
                 quiz_data = Some(quiz);
             }
+            StreamItem::Finished { .. } => {}
         }
     }
     
@@ -120,17 +120,17 @@ This covers the main landscape. The ecosystem is quite mature now, with tokio be
 
     println!("=== QueryResolver Legacy vs New API Comparison ===\n");
 
-    // Legacy methods (now deprecated stubs)
+    // Legacy methods (deprecated wrappers over query::<T>().first_required())
     let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
     
     println!("🔍 Legacy method (query_with_schema) - now deprecated:");
+    #[allow(deprecated)]
     match resolver.query_with_schema::<Analysis>("Analyze the Rust async ecosystem".to_string()).await {
         Ok(analysis) => {
             println!("✅ Got analysis: {}", analysis.topic);
         }
         Err(e) => {
-            println!("❌ Legacy method failed (expected - it's now a stub): {}", e);
-            println!("   This demonstrates that old methods are deprecated stubs");
+            println!("❌ Legacy method failed: {}", e);
         }
     }
 
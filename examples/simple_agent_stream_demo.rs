@@ -96,6 +96,7 @@ Go ahead and start thinking through this task.
                 println!("🔧 Tool Call #{}: {}", tool_count, tool_call.name);
                 println!("   Args: {}", serde_json::to_string_pretty(&tool_call.args)?);
             }
+            Ok(StreamItem::Finished { .. }) => {}
             Err(e) => {
                 eprintln!("❌ Stream error: {}", e);
                 break;
@@ -0,0 +1,119 @@
+//! Per-query audit records.
+//!
+//! Every non-streaming query, including conversation turns, produces one `QueryRecord`
+//! when it finishes or fails. The record covers what was asked, against which schema and
+//! model, how many calls it took and what came back. It is delivered as a single event
+//! to the callback registered with `QueryResolver::with_record_callback` and to the
+//! client's interceptors (`Interceptor::save_record`), so audit pipelines don't have to
+//! stitch it together from logs.
+//!
+//! Prompts and extracted data are stored as `content_hash`es; add the prompt text with
+//! `QueryResolver::with_prompts_in_records`.
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default())
+//!     .with_record_callback(|record| {
+//!         println!("{}", serde_json::to_string(record).unwrap());
+//!     });
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::experiments::fnv1a;
+
+/// Everything one query did, as a single event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+    /// Resolver method that ran, e.g. `query` or `conversation`
+    pub operation: String,
+    pub correlation_id: Option<String>,
+    pub tenant: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    /// `content_hash` of the caller's prompt, before schema guidance was added
+    pub prompt_hash: String,
+    /// The prompt itself, with `QueryResolver::with_prompts_in_records`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// `schema_id` of the schema the query asked for, if any
+    pub schema_id: Option<String>,
+    /// Model version the provider reported answering with
+    pub model: Option<String>,
+    pub usage: RecordUsage,
+    /// Model calls made, counting corrections and continuations
+    pub attempts: usize,
+    /// Correction requests sent after the first reply
+    pub retries: usize,
+    pub outcome: RecordOutcome,
+    /// `content_hash` of the extracted data items as a JSON array; None on failure
+    pub data_hash: Option<String>,
+    /// Tags of the resolver that ran the query (see `QueryResolver::with_tag`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tags: HashMap<String, String>,
+}
+
+impl QueryRecord {
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, RecordOutcome::Succeeded { .. })
+    }
+}
+
+/// Token counts of a query: summed over its model calls when the provider reports them
+/// (see `stats::record_usage`), otherwise estimated with `prompt::estimate_tokens` from
+/// the caller's prompt and all text received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordUsage {
+    pub prompt_tokens: u64,
+    /// Prompt tokens served from the provider's prompt cache, included in `prompt_tokens`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub cached_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl RecordUsage {
+    pub(crate) fn add(&mut self, other: RecordUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+/// How a query ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RecordOutcome {
+    Succeeded { items: usize },
+    Failed { error: String },
+    /// Dropped before it finished, e.g. a `query_race` entrant that lost
+    Cancelled,
+}
+
+/// Receives the record of every non-streaming query run through a resolver
+pub type RecordCallback = Arc<dyn Fn(&QueryRecord) + Send + Sync>;
+
+/// 64-bit FNV-1a of `bytes` as 16 hex digits; stable across processes and Rust versions,
+/// so records can be matched against content kept elsewhere. Not a cryptographic hash.
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:016x}", fnv1a(bytes))
+}
+
+/// `title:hash` for a JSON Schema document (just the hash without a title), so records
+/// name the type and change when its schema does
+pub fn schema_id(schema: &Value) -> String {
+    let hash = content_hash(schema.to_string().as_bytes());
+    match schema.get("title").and_then(Value::as_str) {
+        Some(title) => format!("{title}:{hash}"),
+        None => hash,
+    }
+}
@@ -0,0 +1,161 @@
+//! Bulk extraction: run one `T` extraction per input item with bounded concurrency.
+//!
+//! `QueryResolver::map_extract` builds a prompt per item and returns results aligned
+//! with the inputs. With `MapOptions::packing`, several items share one prompt (up to a
+//! rough token budget) and the model answers with a JSON array; packs whose array does
+//! not line up with their items are retried one item at a time.
+
+use crate::core::{LowLevelClient, QueryResolver};
+use crate::error::QueryResolverError;
+use crate::prompt::estimate_tokens;
+use futures_util::stream::{self, StreamExt};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use tracing::{debug, info, warn};
+
+/// Options for `QueryResolver::map_extract_with`
+#[derive(Debug, Clone)]
+pub struct MapOptions {
+    /// Maximum number of prompts in flight at once
+    pub concurrency: usize,
+    /// Pack several items into each prompt; `None` sends one prompt per item
+    pub packing: Option<PackOptions>,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        Self { concurrency: 4, packing: None }
+    }
+}
+
+/// Limits for packing several items into one prompt
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Approximate token budget per packed prompt (estimated at ~4 characters per token)
+    pub token_budget: usize,
+    /// Upper bound on items per prompt regardless of budget
+    pub max_items: usize,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self { token_budget: 2000, max_items: 20 }
+    }
+}
+
+/// Group item prompts into packs of consecutive indices within the limits
+fn pack_indices(prompts: &[String], options: &PackOptions) -> Vec<Vec<usize>> {
+    let mut packs: Vec<Vec<usize>> = Vec::new();
+    let mut current = Vec::new();
+    let mut tokens = 0;
+    for (i, prompt) in prompts.iter().enumerate() {
+        let cost = estimate_tokens(prompt);
+        if !current.is_empty() && (tokens + cost > options.token_budget || current.len() >= options.max_items.max(1)) {
+            packs.push(std::mem::take(&mut current));
+            tokens = 0;
+        }
+        current.push(i);
+        tokens += cost;
+    }
+    if !current.is_empty() {
+        packs.push(current);
+    }
+    packs
+}
+
+fn packed_prompt(prompts: &[&String]) -> String {
+    let mut out = format!(
+        "Process each of the following {} items independently. Respond with a JSON array containing exactly one result per item, in the same order.\n",
+        prompts.len()
+    );
+    for (i, prompt) in prompts.iter().enumerate() {
+        out.push_str(&format!("\n### Item {}\n{}\n", i + 1, prompt));
+    }
+    out
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Extract one `T` per item using `MapOptions::default()` (4 concurrent prompts, no packing).
+    pub async fn map_extract<T, I, F>(&self, items: Vec<I>, prompt_fn: F) -> Vec<Result<T, QueryResolverError>>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+        F: Fn(&I) -> String,
+    {
+        self.map_extract_with(items, prompt_fn, MapOptions::default()).await
+    }
+
+    /// Extract one `T` per item; the result at index `i` corresponds to `items[i]`.
+    pub async fn map_extract_with<T, I, F>(&self, items: Vec<I>, prompt_fn: F, options: MapOptions) -> Vec<Result<T, QueryResolverError>>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+        F: Fn(&I) -> String,
+    {
+        let prompts: Vec<String> = items.iter().map(&prompt_fn).collect();
+        let concurrency = options.concurrency.max(1);
+        info!(items = prompts.len(), concurrency, packed = options.packing.is_some(), "Starting map extraction");
+
+        let Some(packing) = options.packing else {
+            return stream::iter(prompts.iter().map(|p| self.extract_one::<T>(p.clone())))
+                .buffered(concurrency)
+                .collect()
+                .await;
+        };
+
+        let packs = pack_indices(&prompts, &packing);
+        debug!(packs = packs.len(), "Packed map extraction prompts");
+        let packed: Vec<(Vec<usize>, Option<Vec<T>>)> = stream::iter(packs.into_iter().map(|pack| {
+            let prompt = packed_prompt(&pack.iter().map(|&i| &prompts[i]).collect::<Vec<_>>());
+            async move {
+                let results = if pack.len() == 1 {
+                    None
+                } else {
+                    match self.query::<Vec<T>>(prompt).await {
+                        Ok(response) => response.first_required().ok().filter(|r| r.len() == pack.len()),
+                        Err(e) => {
+                            warn!(error = %e, items = pack.len(), "Packed prompt failed; falling back to single items");
+                            None
+                        }
+                    }
+                };
+                (pack, results)
+            }
+        }))
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+        // Fill aligned slots from packs that answered cleanly, re-query the rest individually
+        let mut slots: Vec<Option<Result<T, QueryResolverError>>> = (0..prompts.len()).map(|_| None).collect();
+        let mut retry = Vec::new();
+        for (pack, results) in packed {
+            match results {
+                Some(results) => {
+                    for (i, value) in pack.into_iter().zip(results) {
+                        slots[i] = Some(Ok(value));
+                    }
+                }
+                None => retry.extend(pack),
+            }
+        }
+        let retried: Vec<(usize, Result<T, QueryResolverError>)> = stream::iter(retry.into_iter().map(|i| {
+            let prompt = prompts[i].clone();
+            async move { (i, self.extract_one::<T>(prompt).await) }
+        }))
+        .buffered(concurrency)
+        .collect()
+        .await;
+        for (i, result) in retried {
+            slots[i] = Some(result);
+        }
+
+        slots.into_iter().map(|slot| slot.expect("every index is filled by a pack or a retry")).collect()
+    }
+
+    async fn extract_one<T>(&self, prompt: String) -> Result<T, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        Ok(self.query::<T>(prompt).await?.first_required()?)
+    }
+}
@@ -0,0 +1,526 @@
+//! Offline extraction through the providers' asynchronous batch endpoints.
+//!
+//! `BatchRunner` submits prompts to the Anthropic Message Batches API or the OpenAI
+//! Batch API, polls until the batch ends, downloads the results and parses each one into
+//! a `ParsedResponse<T>` keyed by its request id. With `BatchConfig::with_state_path`
+//! the job is saved after submission and after every poll, so a restarted process picks
+//! the same batch back up instead of paying for it twice.
+//!
+//! Each batch is limited by the provider (100,000 requests for Anthropic, 50,000 for
+//! OpenAI); split larger workloads across runners with separate state files.
+
+use crate::core::{schema_guidance, ParsedResponse};
+use crate::error::{AIError, BatchError, ClaudeError, OpenAIError, ProviderError};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Which batch API to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchProvider {
+    Anthropic,
+    OpenAI,
+}
+
+/// Provider, model and polling settings for a `BatchRunner`
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub provider: BatchProvider,
+    pub api_key: String,
+    /// Model id sent with every request
+    pub model: String,
+    pub max_tokens: u32,
+    /// API base URL; defaults to the provider's public endpoint
+    pub base_url: String,
+    /// Delay between status checks
+    pub poll_interval: Duration,
+    /// Where job state is persisted; `None` keeps it in memory only
+    pub state_path: Option<PathBuf>,
+}
+
+impl BatchConfig {
+    pub fn anthropic(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self::new(BatchProvider::Anthropic, api_key, model, "https://api.anthropic.com")
+    }
+
+    pub fn openai(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self::new(BatchProvider::OpenAI, api_key, model, "https://api.openai.com")
+    }
+
+    fn new(provider: BatchProvider, api_key: impl Into<String>, model: impl Into<String>, base_url: &str) -> Self {
+        Self {
+            provider,
+            api_key: api_key.into(),
+            model: model.into(),
+            max_tokens: 1024,
+            base_url: base_url.to_string(),
+            poll_interval: Duration::from_secs(30),
+            state_path: None,
+        }
+    }
+
+    pub fn with_state_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_path = Some(path.into());
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+}
+
+/// One prompt in a batch. `custom_id` must be unique within the batch; Anthropic
+/// limits it to 64 characters of `[a-zA-Z0-9_-]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub custom_id: String,
+    pub prompt: String,
+}
+
+impl BatchRequest {
+    pub fn new(custom_id: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self { custom_id: custom_id.into(), prompt: prompt.into() }
+    }
+
+    /// Requests with ids `req-0`, `req-1`, ... in input order
+    pub fn indexed<I, S>(prompts: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        prompts.into_iter().enumerate().map(|(i, p)| Self::new(format!("req-{i}"), p)).collect()
+    }
+}
+
+/// Provider-independent batch status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchStatus {
+    /// Accepted and still processing (includes validating and finalizing)
+    InProgress,
+    /// Finished; results are available
+    Ended,
+    Failed,
+    Expired,
+    Cancelled,
+}
+
+impl BatchStatus {
+    pub fn is_terminal(self) -> bool {
+        self != BatchStatus::InProgress
+    }
+
+    fn from_anthropic(status: &str) -> Self {
+        match status {
+            "ended" => BatchStatus::Ended,
+            _ => BatchStatus::InProgress,
+        }
+    }
+
+    fn from_openai(status: &str) -> Self {
+        match status {
+            "completed" => BatchStatus::Ended,
+            "failed" => BatchStatus::Failed,
+            "expired" => BatchStatus::Expired,
+            "cancelled" => BatchStatus::Cancelled,
+            _ => BatchStatus::InProgress,
+        }
+    }
+}
+
+/// Persistable state of a submitted batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub provider: BatchProvider,
+    pub batch_id: String,
+    pub status: BatchStatus,
+    /// Ids of the submitted requests, in submission order
+    pub request_ids: Vec<String>,
+    /// Anthropic: URL of the results file once the batch has ended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub results_url: Option<String>,
+    /// OpenAI: file holding successful responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_file_id: Option<String>,
+    /// OpenAI: file holding per-request errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_file_id: Option<String>,
+}
+
+impl BatchJob {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, BatchError> {
+        let text = crate::runtime::fs::read_to_string(path).await?;
+        serde_json::from_str(&text).map_err(|e| BatchError::State(e.to_string()))
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), BatchError> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| BatchError::State(e.to_string()))?;
+        // Write to a sibling file first so a crash never leaves a truncated state file
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        crate::runtime::fs::write(&tmp, text).await?;
+        crate::runtime::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+/// Per-request outcome as reported by the provider
+pub type RawBatchResults = BTreeMap<String, Result<String, AIError>>;
+
+/// Parsed outcome of every request, keyed by `custom_id`
+pub type BatchResults<T> = BTreeMap<String, Result<ParsedResponse<T>, AIError>>;
+
+/// Submits, polls and collects provider batches
+#[derive(Debug, Clone)]
+pub struct BatchRunner {
+    config: BatchConfig,
+    http: reqwest::Client,
+}
+
+impl BatchRunner {
+    pub fn new(config: BatchConfig) -> Self {
+        Self { config, http: reqwest::Client::new() }
+    }
+
+    pub fn config(&self) -> &BatchConfig {
+        &self.config
+    }
+
+    /// Run a whole extraction: schema guidance for `T` is appended to every prompt, the
+    /// batch is submitted (or resumed from `state_path`), awaited and parsed.
+    pub async fn run<T>(&self, requests: Vec<BatchRequest>) -> Result<BatchResults<T>, BatchError>
+    where
+        T: DeserializeOwned + JsonSchema + Serialize + Clone + 'static,
+    {
+        let requests = requests
+            .into_iter()
+            .map(|r| BatchRequest { prompt: schema_guidance::<T>(r.prompt), ..r })
+            .collect();
+        let mut job = self.resume_or_submit(requests).await?;
+        self.wait(&mut job).await?;
+        self.results::<T>(&job).await
+    }
+
+    /// Load the saved job when `state_path` holds one for the same request ids,
+    /// otherwise submit `requests` as a new batch
+    pub async fn resume_or_submit(&self, requests: Vec<BatchRequest>) -> Result<BatchJob, BatchError> {
+        if let Some(path) = &self.config.state_path {
+            if crate::runtime::fs::try_exists(path).await.unwrap_or(false) {
+                let job = BatchJob::load(path).await?;
+                let saved: BTreeSet<&str> = job.request_ids.iter().map(String::as_str).collect();
+                let wanted: BTreeSet<&str> = requests.iter().map(|r| r.custom_id.as_str()).collect();
+                if job.provider != self.config.provider || saved != wanted {
+                    return Err(BatchError::State(format!(
+                        "{} holds batch {} for different requests; remove it to submit a new batch",
+                        path.display(),
+                        job.batch_id
+                    )));
+                }
+                info!(batch_id = %job.batch_id, status = ?job.status, "Resuming saved batch");
+                return Ok(job);
+            }
+        }
+        self.submit(requests).await
+    }
+
+    /// Submit `requests` as a new batch and persist the job
+    pub async fn submit(&self, requests: Vec<BatchRequest>) -> Result<BatchJob, BatchError> {
+        let mut seen = BTreeSet::new();
+        if let Some(dup) = requests.iter().find(|r| !seen.insert(r.custom_id.as_str())) {
+            return Err(BatchError::State(format!("duplicate custom_id: {}", dup.custom_id)));
+        }
+        info!(provider = ?self.config.provider, requests = requests.len(), "Submitting batch");
+        let job = match self.config.provider {
+            BatchProvider::Anthropic => self.submit_anthropic(&requests).await?,
+            BatchProvider::OpenAI => self.submit_openai(&requests).await?,
+        };
+        self.persist(&job).await?;
+        Ok(job)
+    }
+
+    /// Refresh `job.status` (and result locations) once
+    pub async fn poll(&self, job: &mut BatchJob) -> Result<BatchStatus, BatchError> {
+        let path = match job.provider {
+            BatchProvider::Anthropic => format!("/v1/messages/batches/{}", job.batch_id),
+            BatchProvider::OpenAI => format!("/v1/batches/{}", job.batch_id),
+        };
+        let body = self.get_json(&path).await?;
+        apply_status(job, &body);
+        debug!(batch_id = %job.batch_id, status = ?job.status, "Polled batch");
+        self.persist(job).await?;
+        Ok(job.status)
+    }
+
+    /// Poll every `poll_interval` until the batch ends. Failed, expired and cancelled
+    /// batches are errors; an expired OpenAI batch may still have partial results,
+    /// which `results` can download.
+    pub async fn wait(&self, job: &mut BatchJob) -> Result<(), BatchError> {
+        while !job.status.is_terminal() {
+            crate::runtime::sleep(self.config.poll_interval).await;
+            self.poll(job).await?;
+        }
+        match job.status {
+            BatchStatus::Ended => Ok(()),
+            status => Err(BatchError::Ended { batch_id: job.batch_id.clone(), status }),
+        }
+    }
+
+    /// Download the raw text of every request in an ended batch
+    pub async fn raw_results(&self, job: &BatchJob) -> Result<RawBatchResults, BatchError> {
+        let mut results = RawBatchResults::new();
+        match job.provider {
+            BatchProvider::Anthropic => {
+                let url = job.results_url.clone().unwrap_or_else(|| {
+                    self.config.url(&format!("/v1/messages/batches/{}/results", job.batch_id))
+                });
+                let text = self.get_text(&url).await?;
+                for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                    let (id, result) = parse_anthropic_line(line)?;
+                    results.insert(id, result);
+                }
+            }
+            BatchProvider::OpenAI => {
+                for file_id in job.output_file_id.iter().chain(job.error_file_id.iter()) {
+                    let text = self.get_text(&self.config.url(&format!("/v1/files/{file_id}/content"))).await?;
+                    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                        let (id, result) = parse_openai_line(line)?;
+                        results.insert(id, result);
+                    }
+                }
+            }
+        }
+        for id in &job.request_ids {
+            if !results.contains_key(id) {
+                warn!(custom_id = %id, "Batch returned no result for request");
+                results.insert(id.clone(), Err(provider_error(job.provider, "no result returned".to_string())));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Download and parse the results of an ended batch
+    pub async fn results<T>(&self, job: &BatchJob) -> Result<BatchResults<T>, BatchError>
+    where
+        T: DeserializeOwned + JsonSchema + Serialize + Clone,
+    {
+        let raw = self.raw_results(job).await?;
+        info!(batch_id = %job.batch_id, results = raw.len(), "Parsing batch results");
+        Ok(raw
+            .into_iter()
+            .map(|(id, text)| (id, text.map(|t| ParsedResponse::from_raw(&t))))
+            .collect())
+    }
+
+    async fn submit_anthropic(&self, requests: &[BatchRequest]) -> Result<BatchJob, BatchError> {
+        let body = serde_json::json!({
+            "requests": requests.iter().map(|r| serde_json::json!({
+                "custom_id": r.custom_id,
+                "params": {
+                    "model": self.config.model,
+                    "max_tokens": self.config.max_tokens,
+                    "messages": [{ "role": "user", "content": r.prompt }],
+                },
+            })).collect::<Vec<_>>(),
+        });
+        let response = self.send(self.http.post(self.config.url("/v1/messages/batches")).json(&body)).await?;
+        let value: Value = response.json().await.map_err(|e| provider_error(BatchProvider::Anthropic, e.to_string()))?;
+        Ok(self.new_job(&value, requests))
+    }
+
+    async fn submit_openai(&self, requests: &[BatchRequest]) -> Result<BatchJob, BatchError> {
+        let mut jsonl = String::new();
+        for r in requests {
+            let line = serde_json::json!({
+                "custom_id": r.custom_id,
+                "method": "POST",
+                "url": "/v1/chat/completions",
+                "body": {
+                    "model": self.config.model,
+                    "max_tokens": self.config.max_tokens,
+                    "messages": [{ "role": "user", "content": r.prompt }],
+                },
+            });
+            jsonl.push_str(&line.to_string());
+            jsonl.push('\n');
+        }
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", "batch")
+            .part("file", reqwest::multipart::Part::text(jsonl).file_name("batch.jsonl"));
+        let upload = self.send(self.http.post(self.config.url("/v1/files")).multipart(form)).await?;
+        let file: Value = upload.json().await.map_err(|e| provider_error(BatchProvider::OpenAI, e.to_string()))?;
+        let Some(file_id) = file.get("id").and_then(Value::as_str) else {
+            return Err(provider_error(BatchProvider::OpenAI, "file upload returned no id".to_string()).into());
+        };
+        debug!(file_id, "Uploaded batch input file");
+
+        let body = serde_json::json!({
+            "input_file_id": file_id,
+            "endpoint": "/v1/chat/completions",
+            "completion_window": "24h",
+        });
+        let response = self.send(self.http.post(self.config.url("/v1/batches")).json(&body)).await?;
+        let value: Value = response.json().await.map_err(|e| provider_error(BatchProvider::OpenAI, e.to_string()))?;
+        Ok(self.new_job(&value, requests))
+    }
+
+    fn new_job(&self, value: &Value, requests: &[BatchRequest]) -> BatchJob {
+        let mut job = BatchJob {
+            provider: self.config.provider,
+            batch_id: value.get("id").and_then(Value::as_str).unwrap_or_default().to_string(),
+            status: BatchStatus::InProgress,
+            request_ids: requests.iter().map(|r| r.custom_id.clone()).collect(),
+            results_url: None,
+            output_file_id: None,
+            error_file_id: None,
+        };
+        apply_status(&mut job, value);
+        info!(batch_id = %job.batch_id, "Batch submitted");
+        job
+    }
+
+    async fn persist(&self, job: &BatchJob) -> Result<(), BatchError> {
+        match &self.config.state_path {
+            Some(path) => job.save(path).await,
+            None => Ok(()),
+        }
+    }
+
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.config.provider {
+            BatchProvider::Anthropic => req
+                .header("x-api-key", &self.config.api_key)
+                .header("anthropic-version", "2023-06-01"),
+            BatchProvider::OpenAI => req.bearer_auth(&self.config.api_key),
+        }
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response, AIError> {
+        let provider = self.config.provider;
+        let response = self.authorize(req).send().await.map_err(|e| http_error(provider, e.to_string()))?;
+        if !response.status().is_success() {
+            let err = ProviderError::from_response(response).await;
+            return Err(match provider {
+                BatchProvider::Anthropic => AIError::Claude(err.into()),
+                BatchProvider::OpenAI => AIError::OpenAI(err.into()),
+            });
+        }
+        Ok(response)
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, AIError> {
+        let response = self.send(self.http.get(self.config.url(path))).await?;
+        response.json().await.map_err(|e| http_error(self.config.provider, e.to_string()))
+    }
+
+    async fn get_text(&self, url: &str) -> Result<String, AIError> {
+        let response = self.send(self.http.get(url)).await?;
+        response.text().await.map_err(|e| http_error(self.config.provider, e.to_string()))
+    }
+}
+
+fn apply_status(job: &mut BatchJob, value: &Value) {
+    let str_field = |name: &str| value.get(name).and_then(Value::as_str).map(str::to_string);
+    match job.provider {
+        BatchProvider::Anthropic => {
+            if let Some(status) = str_field("processing_status") {
+                job.status = BatchStatus::from_anthropic(&status);
+            }
+            job.results_url = str_field("results_url").or(job.results_url.take());
+        }
+        BatchProvider::OpenAI => {
+            if let Some(status) = str_field("status") {
+                job.status = BatchStatus::from_openai(&status);
+            }
+            job.output_file_id = str_field("output_file_id").or(job.output_file_id.take());
+            job.error_file_id = str_field("error_file_id").or(job.error_file_id.take());
+        }
+    }
+}
+
+fn provider_error(provider: BatchProvider, message: String) -> AIError {
+    match provider {
+        BatchProvider::Anthropic => AIError::Claude(ClaudeError::Api(message)),
+        BatchProvider::OpenAI => AIError::OpenAI(OpenAIError::Api(message)),
+    }
+}
+
+fn http_error(provider: BatchProvider, message: String) -> AIError {
+    match provider {
+        BatchProvider::Anthropic => AIError::Claude(ClaudeError::Http(message)),
+        BatchProvider::OpenAI => AIError::OpenAI(OpenAIError::Http(message)),
+    }
+}
+
+fn bad_line(line: &str) -> BatchError {
+    BatchError::State(format!("unrecognized batch result line: {line}"))
+}
+
+/// `{"custom_id": .., "result": {"type": "succeeded", "message": {"content": [..]}}}`
+fn parse_anthropic_line(line: &str) -> Result<(String, Result<String, AIError>), BatchError> {
+    let value: Value = serde_json::from_str(line).map_err(|_| bad_line(line))?;
+    let id = value.get("custom_id").and_then(Value::as_str).ok_or_else(|| bad_line(line))?.to_string();
+    let result = &value["result"];
+    let outcome = match result.get("type").and_then(Value::as_str) {
+        Some("succeeded") => {
+            let text: String = result["message"]["content"]
+                .as_array()
+                .map(|blocks| blocks.iter().filter_map(|b| b.get("text").and_then(Value::as_str)).collect())
+                .unwrap_or_default();
+            Ok(text)
+        }
+        Some("errored") => {
+            let message = result["error"]["error"]["message"].as_str()
+                .or_else(|| result["error"]["message"].as_str())
+                .unwrap_or("request errored");
+            Err(provider_error(BatchProvider::Anthropic, message.to_string()))
+        }
+        Some(other) => Err(provider_error(BatchProvider::Anthropic, format!("request {other}"))),
+        None => return Err(bad_line(line)),
+    };
+    Ok((id, outcome))
+}
+
+/// `{"custom_id": .., "response": {"status_code": 200, "request_id": .., "body": {..}}, "error": null}`
+fn parse_openai_line(line: &str) -> Result<(String, Result<String, AIError>), BatchError> {
+    let value: Value = serde_json::from_str(line).map_err(|_| bad_line(line))?;
+    let id = value.get("custom_id").and_then(Value::as_str).ok_or_else(|| bad_line(line))?.to_string();
+    if let Some(message) = value["error"]["message"].as_str() {
+        return Ok((id, Err(provider_error(BatchProvider::OpenAI, message.to_string()))));
+    }
+    let response = &value["response"];
+    let status = response["status_code"].as_u64().unwrap_or(0);
+    let outcome = match response["body"]["choices"][0]["message"]["content"].as_str() {
+        Some(content) if (200..300).contains(&status) => Ok(content.to_string()),
+        _ => {
+            let error = &response["body"]["error"];
+            Err(AIError::OpenAI(ProviderError {
+                status: status as u16,
+                code: error["code"].as_str().or_else(|| error["type"].as_str()).map(str::to_string),
+                message: error["message"].as_str().unwrap_or("request failed").to_string(),
+                retry_after: None,
+                request_id: response["request_id"].as_str().map(str::to_string),
+                correlation_id: None,
+            }.into()))
+        }
+    };
+    Ok((id, outcome))
+}
@@ -1,32 +1,21 @@
 use anyhow::Result;
 use clap::Parser;
 use semantic_query::clients::flexible::FlexibleClient;
-use semantic_query::clients::ClientType;
+use semantic_query::clients::{ClientType, MockClient};
 use semantic_query::core::{QueryResolver, RetryConfig};
 use serde::{Deserialize, Serialize};
 use schemars::JsonSchema;
 use std::env;
 use std::io::{self, Write};
-use std::sync::OnceLock;
 use std::time::Instant;
 use tokio::task::JoinSet;
 use std::str::FromStr;
 
 
 
-/// Global lazy client for tests (set via TEST_CLIENT env var)
-static LAZY_CLIENT: OnceLock<FlexibleClient> = OnceLock::new();
-
-
-fn get_client() -> &'static FlexibleClient {
-    LAZY_CLIENT.get_or_init(|| {
-        let client_type = env::var("TEST_CLIENT")
-            .ok()
-            .and_then(|s| ClientType::from_str(&s).ok())
-            .unwrap_or_default();
-        
-        FlexibleClient::from_type(client_type)
-    })
+/// Whether `client` is the mock, whose benchmarks are expected to fail
+fn is_mock(client: &FlexibleClient) -> bool {
+    client.as_any().is::<MockClient>()
 }
 
  
@@ -163,19 +152,17 @@ async fn main() -> Result<()> {
     println!("🎯 Running benchmarks with {} client", client_type);
     println!();
     
-    // Set TEST_CLIENT environment variable for lazy client
-    env::set_var("TEST_CLIENT", client_type.to_string().to_lowercase());
+    let client = FlexibleClient::new(client_type.build()?);
     
     // Run benchmark tests in parallel
-    run_benchmarks_parallel(args.verbose).await?;
+    run_benchmarks_parallel(client, args.verbose).await?;
     
     Ok(())
 }
 
 /// Individual benchmark functions that can run in parallel
-async fn benchmark_math_query(_verbose: bool) -> Result<String> {
-    let client = get_client().clone();
-    let resolver = QueryResolver::new(client, RetryConfig::default());
+async fn benchmark_math_query(client: FlexibleClient, _verbose: bool) -> Result<String> {
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
     let start = Instant::now();
     let result = resolver.query::<MathResult>("What is 15 + 27? Please provide the result and verify if it's correct.".to_string()).await?.first_required();
     let duration = start.elapsed();
@@ -187,7 +174,7 @@ async fn benchmark_math_query(_verbose: bool) -> Result<String> {
         }
         Err(e) => {
             let mut msg = format!("❌ Math Query failed: {}", e);
-            if env::var("TEST_CLIENT").unwrap_or_default() == "mock" {
+            if is_mock(&client) {
                 msg.push_str("\n   (Expected with Mock client)");
             }
             Ok(msg)
@@ -195,9 +182,8 @@ async fn benchmark_math_query(_verbose: bool) -> Result<String> {
     }
 }
 
-async fn benchmark_code_analysis(verbose: bool) -> Result<String> {
-    let client = get_client().clone();
-    let resolver = QueryResolver::new(client, RetryConfig::default());
+async fn benchmark_code_analysis(client: FlexibleClient, verbose: bool) -> Result<String> {
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
     let code = r#"
 function processData(data) {
     if (data = null) {
@@ -230,7 +216,7 @@ function processData(data) {
         }
         Err(e) => {
             let mut msg = format!("❌ Code Analysis failed: {}", e);
-            if env::var("TEST_CLIENT").unwrap_or_default() == "mock" {
+            if is_mock(&client) {
                 msg.push_str("\n   (Expected with Mock client)");
             }
             Ok(msg)
@@ -238,9 +224,8 @@ function processData(data) {
     }
 }
 
-async fn benchmark_schema_constraints(verbose: bool) -> Result<String> {
-    let client = get_client().clone();
-    let resolver = QueryResolver::new(client, RetryConfig::default());
+async fn benchmark_schema_constraints(client: FlexibleClient, verbose: bool) -> Result<String> {
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
     let start = Instant::now();
     let result = resolver.query::<CodeAnalysis>("Give a high-confidence analysis of this simple function: fn add(a: i32, b: i32) -> i32 { a + b }".to_string()).await?.first_required();
     let duration = start.elapsed();
@@ -270,7 +255,7 @@ async fn benchmark_schema_constraints(verbose: bool) -> Result<String> {
         }
         Err(e) => {
             let mut msg = format!("❌ Schema Constraints failed: {}", e);
-            if env::var("TEST_CLIENT").unwrap_or_default() == "mock" {
+            if is_mock(&client) {
                 msg.push_str("\n   (Expected with Mock client)");
             }
             Ok(msg)
@@ -278,9 +263,8 @@ async fn benchmark_schema_constraints(verbose: bool) -> Result<String> {
     }
 }
 
-async fn benchmark_schema_accuracy(verbose: bool) -> Result<String> {
-    let client = get_client().clone();
-    let resolver = QueryResolver::new(client, RetryConfig::default());
+async fn benchmark_schema_accuracy(client: FlexibleClient, verbose: bool) -> Result<String> {
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
     let start = Instant::now();
     let result = resolver.query::<MathResult>("What is 8 * 7? Return exactly what the schema asks for.".to_string()).await?.first_required();
     let duration = start.elapsed();
@@ -303,7 +287,7 @@ async fn benchmark_schema_accuracy(verbose: bool) -> Result<String> {
         }
         Err(e) => {
             let mut msg = format!("❌ Schema Accuracy failed: {}", e);
-            if env::var("TEST_CLIENT").unwrap_or_default() == "mock" {
+            if is_mock(&client) {
                 msg.push_str("\n   (Expected with Mock client)");
             }
             Ok(msg)
@@ -311,13 +295,12 @@ async fn benchmark_schema_accuracy(verbose: bool) -> Result<String> {
     }
 }
 
-async fn benchmark_advanced_retry(verbose: bool) -> Result<String> {
+async fn benchmark_advanced_retry(client: FlexibleClient, verbose: bool) -> Result<String> {
     let mut retry_config = RetryConfig::default();
     retry_config.max_retries.insert("json_parse_error".to_string(), 3);
     retry_config.default_max_retries = 2;
     
-    let client = get_client().clone();
-    let retry_resolver = QueryResolver::new(client, retry_config);
+    let retry_resolver = QueryResolver::new(client.clone(), retry_config);
     
     let start = Instant::now();
     let result = retry_resolver.query::<MathResult>("Calculate the square root of 144. Be very verbose in your explanation but still return the JSON.".to_string()).await?.first_required();
@@ -342,9 +325,8 @@ async fn benchmark_advanced_retry(verbose: bool) -> Result<String> {
     }
 }
 
-async fn benchmark_empty_prompt(verbose: bool) -> Result<String> {
-    let client = get_client().clone();
-    let resolver = QueryResolver::new(client, RetryConfig::default());
+async fn benchmark_empty_prompt(client: FlexibleClient, verbose: bool) -> Result<String> {
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
     let start = Instant::now();
     let result = resolver.query::<MathResult>("".to_string()).await?.first_required();
     let duration = start.elapsed();
@@ -367,7 +349,7 @@ async fn benchmark_empty_prompt(verbose: bool) -> Result<String> {
                 msg.push_str(&format!("\n   Error: {}", e));
                 msg.push_str("\n   Consider adding more descriptive schema annotations");
             }
-            if env::var("TEST_CLIENT").unwrap_or_default() == "mock" {
+            if is_mock(&client) {
                 msg.push_str("\n   (Expected with Mock client - no actual model inference)");
             }
             Ok(msg)
@@ -375,19 +357,19 @@ async fn benchmark_empty_prompt(verbose: bool) -> Result<String> {
     }
 }
 
-async fn run_benchmarks_parallel(verbose: bool) -> Result<()> {
+async fn run_benchmarks_parallel(client: FlexibleClient, verbose: bool) -> Result<()> {
     println!("📊 Running Benchmark Suite (Parallel)");
     println!("======================================");
     
     let mut join_set = JoinSet::new();
     
-    // Spawn benchmark tasks - each will use the global lazy client
-    join_set.spawn(benchmark_math_query(verbose));
-    join_set.spawn(benchmark_code_analysis(verbose));
-    join_set.spawn(benchmark_schema_constraints(verbose));
-    join_set.spawn(benchmark_schema_accuracy(verbose));
-    join_set.spawn(benchmark_advanced_retry(verbose));
-    join_set.spawn(benchmark_empty_prompt(verbose));
+    // Spawn benchmark tasks - each gets a clone of the same client
+    join_set.spawn(benchmark_math_query(client.clone(), verbose));
+    join_set.spawn(benchmark_code_analysis(client.clone(), verbose));
+    join_set.spawn(benchmark_schema_constraints(client.clone(), verbose));
+    join_set.spawn(benchmark_schema_accuracy(client.clone(), verbose));
+    join_set.spawn(benchmark_advanced_retry(client.clone(), verbose));
+    join_set.spawn(benchmark_empty_prompt(client.clone(), verbose));
     
     // Collect results as they complete
     let mut results = Vec::new();
@@ -0,0 +1,221 @@
+//! `sq`: ad-hoc structured queries from the shell.
+//!
+//! ```text
+//! sq query --schema invoice.schema.json --provider deepseek "Extract the invoice: ..."
+//! cat email.txt | sq query --schema contact.json --all
+//! sq query --schema answer.json --stream --prompt-file question.md
+//! ```
+//!
+//! Extracted JSON goes to stdout (one value per line with `--all`), everything else to
+//! stderr. Exit codes: 0 on success, 1 when the query fails or no JSON was extracted,
+//! 2 for bad arguments, input or configuration.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand};
+use futures_util::StreamExt;
+use semantic_query::clients::ClientType;
+use semantic_query::config::{self, SemanticQueryConfig};
+use semantic_query::core::QueryResolver;
+use semantic_query::streaming::StreamItem;
+use serde_json::Value;
+
+#[derive(Parser)]
+#[command(name = "sq", version, about = "Structured queries against LLM providers")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Ask a question and print the JSON extracted from the answer
+    Query(QueryArgs),
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// Prompt text; read from stdin when omitted or `-`
+    prompt: Option<String>,
+    /// Read the prompt from a file instead
+    #[arg(long, conflicts_with = "prompt")]
+    prompt_file: Option<PathBuf>,
+    /// JSON Schema the answer should match; any JSON is accepted without one
+    #[arg(long)]
+    schema: Option<PathBuf>,
+    /// claude, deepseek, openai, compat or mock; defaults to the configured provider
+    #[arg(long)]
+    provider: Option<String>,
+    /// Configuration file (default: `SEMANTIC_QUERY_CONFIG` or ./semantic-query.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Print tokens to stderr as they arrive and each JSON value as soon as it is parsed
+    #[arg(long)]
+    stream: bool,
+    /// Print every extracted value (one per line) instead of only the first
+    #[arg(long)]
+    all: bool,
+    /// Print JSON on a single line
+    #[arg(long)]
+    compact: bool,
+}
+
+/// Failure with the exit code it maps to
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl Failure {
+    fn usage(message: impl Into<String>) -> Self {
+        Self { code: 2, message: message.into() }
+    }
+
+    fn query(message: impl Into<String>) -> Self {
+        Self { code: 1, message: message.into() }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Query(args) => run_query(args).await,
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("sq: {}", failure.message);
+            ExitCode::from(failure.code)
+        }
+    }
+}
+
+async fn run_query(args: QueryArgs) -> Result<(), Failure> {
+    let prompt = read_prompt(&args)?;
+    let schema = match &args.schema {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| Failure::usage(format!("cannot read {}: {e}", path.display())))?;
+            serde_json::from_str::<Value>(&text)
+                .map_err(|e| Failure::usage(format!("{} is not valid JSON: {e}", path.display())))?
+        }
+        None => Value::Bool(true),
+    };
+
+    let config = load_config(args.config.as_deref())?;
+    let client_type = match &args.provider {
+        Some(name) => ClientType::from_str(name).map_err(Failure::usage)?,
+        None => ClientType::from_config(&config),
+    };
+    let client = client_type.build_with(&config).map_err(|e| Failure::usage(e.to_string()))?;
+    let resolver = QueryResolver::new(client, config.retry_config());
+
+    let values = if args.stream {
+        stream_values(&resolver, prompt, &schema, &args).await?
+    } else {
+        let response = resolver.query_schema(prompt, &schema).await.map_err(|e| Failure::query(e.to_string()))?;
+        let values: Vec<Value> = response.data_only().into_iter().cloned().filter(|v| conforms(v, &schema)).collect();
+        let shown = if args.all { &values[..] } else { &values[..values.len().min(1)] };
+        for value in shown {
+            print_value(value, args.compact)?;
+        }
+        values
+    };
+
+    if values.is_empty() {
+        return Err(Failure::query("no JSON matching the schema in the response"));
+    }
+    Ok(())
+}
+
+/// Stream the response, printing tokens to stderr and values to stdout as they parse
+async fn stream_values<C>(resolver: &QueryResolver<C>, prompt: String, schema: &Value, args: &QueryArgs) -> Result<Vec<Value>, Failure>
+where
+    C: semantic_query::core::LowLevelClient,
+{
+    let mut stream = resolver.stream_query_schema(prompt, schema).await.map_err(|e| Failure::query(e.to_string()))?;
+    let mut values = Vec::new();
+    while let Some(item) = stream.next().await {
+        match item.map_err(|e| Failure::query(e.to_string()))? {
+            StreamItem::Token(token) => {
+                eprint!("{token}");
+                let _ = io::stderr().flush();
+            }
+            StreamItem::Data(value) if conforms(&value, schema) => {
+                if args.all || values.is_empty() {
+                    print_value(&value, args.compact)?;
+                }
+                values.push(value);
+            }
+            StreamItem::Data(_) | StreamItem::Text(_) | StreamItem::Finished { .. } => {}
+        }
+    }
+    eprintln!();
+    Ok(values)
+}
+
+fn read_prompt(args: &QueryArgs) -> Result<String, Failure> {
+    let prompt = match (&args.prompt_file, args.prompt.as_deref()) {
+        (Some(path), _) => std::fs::read_to_string(path)
+            .map_err(|e| Failure::usage(format!("cannot read {}: {e}", path.display())))?,
+        (None, Some(prompt)) if prompt != "-" => prompt.to_string(),
+        (None, _) => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|e| Failure::usage(format!("cannot read stdin: {e}")))?;
+            text
+        }
+    };
+    if prompt.trim().is_empty() {
+        return Err(Failure::usage("empty prompt"));
+    }
+    Ok(prompt)
+}
+
+fn load_config(path: Option<&Path>) -> Result<SemanticQueryConfig, Failure> {
+    let mut config = match path {
+        Some(path) => {
+            let _ = dotenvy::dotenv();
+            config::load_from_path(path)
+        }
+        None => config::load(),
+    }
+    .map_err(|e| Failure::usage(e.to_string()))?;
+    if path.is_some() {
+        config.apply_env_overrides();
+    }
+    Ok(config)
+}
+
+/// Shallow check that `value` has the schema's top-level `type` and `required` properties
+fn conforms(value: &Value, schema: &Value) -> bool {
+    let type_ok = match schema.get("type").and_then(Value::as_str) {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("number") => value.is_number(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+    let required_ok = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .all(|key| value.get(key).is_some());
+    type_ok && required_ok
+}
+
+fn print_value(value: &Value, compact: bool) -> Result<(), Failure> {
+    let text = if compact { serde_json::to_string(value) } else { serde_json::to_string_pretty(value) }
+        .map_err(|e| Failure::query(e.to_string()))?;
+    let mut stdout = io::stdout().lock();
+    // A closed pipe (`sq ... | head`) is not an error worth reporting
+    let _ = writeln!(stdout, "{text}");
+    Ok(())
+}
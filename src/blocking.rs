@@ -0,0 +1,106 @@
+//! Synchronous facade over `QueryResolver` for non-async applications.
+//!
+//! `BlockingQueryResolver` owns a small Tokio runtime and drives the async API on
+//! it, so CLI tools and build scripts can issue queries without setting up a
+//! runtime themselves. Like `reqwest::blocking`, it must not be used from within
+//! an async context (calling it inside a Tokio runtime panics).
+//!
+//! ```no_run
+//! use semantic_query::blocking::BlockingQueryResolver;
+//! use semantic_query::clients::flexible::FlexibleClient;
+//! use semantic_query::core::RetryConfig;
+//! use semantic_query::streaming::StreamItem;
+//! use serde::{Deserialize, Serialize};
+//! use schemars::JsonSchema;
+//!
+//! #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+//! struct Answer { value: i32 }
+//!
+//! # fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let resolver = BlockingQueryResolver::new(FlexibleClient::auto(), RetryConfig::default())?;
+//! let answer = resolver.query::<Answer>("What is 2 + 2?".to_string())?.first_required()?;
+//!
+//! for item in resolver.stream_query::<Answer>("Count to three".to_string())? {
+//!     if let StreamItem::Data(a) = item? { println!("{}", a.value); }
+//! }
+//! # Ok(()) }
+//! ```
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver, RetryConfig};
+use crate::error::QueryResolverError;
+use crate::streaming::StreamItem;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// Blocking wrapper around `QueryResolver` with an internal runtime.
+#[derive(Clone)]
+pub struct BlockingQueryResolver<C: LowLevelClient> {
+    inner: QueryResolver<C>,
+    runtime: Arc<Runtime>,
+}
+
+impl<C: LowLevelClient> BlockingQueryResolver<C> {
+    /// Create a resolver with its own single-threaded runtime
+    pub fn new(client: C, config: RetryConfig) -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self::with_runtime(QueryResolver::new(client, config), Arc::new(runtime)))
+    }
+
+    /// Wrap an existing resolver, driving it on the given runtime
+    pub fn with_runtime(inner: QueryResolver<C>, runtime: Arc<Runtime>) -> Self {
+        Self { inner, runtime }
+    }
+
+    /// The async resolver this facade drives
+    pub fn inner(&self) -> &QueryResolver<C> {
+        &self.inner
+    }
+
+    /// Blocking version of `QueryResolver::query`
+    pub fn query<T>(&self, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        self.runtime.block_on(self.inner.query::<T>(prompt))
+    }
+
+    /// Blocking version of `QueryResolver::query_mixed`
+    pub fn query_mixed<T>(&self, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        self.runtime.block_on(self.inner.query_mixed::<T>(prompt))
+    }
+
+    /// Blocking version of `QueryResolver::stream_query`. Each call to `next()` on
+    /// the returned iterator blocks until the next item arrives.
+    pub fn stream_query<T>(&self, prompt: String) -> Result<BlockingStream<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + 'static,
+    {
+        let stream = self.runtime.block_on(self.inner.stream_query::<T>(prompt))?;
+        Ok(BlockingStream { stream, runtime: self.runtime.clone() })
+    }
+}
+
+/// Iterator over a streaming query, driven by the resolver's runtime.
+pub struct BlockingStream<T: JsonSchema> {
+    stream: Pin<Box<dyn Stream<Item = Result<StreamItem<T>, QueryResolverError>> + Send>>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T: JsonSchema> Iterator for BlockingStream<T> {
+    type Item = Result<StreamItem<T>, QueryResolverError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
@@ -0,0 +1,355 @@
+//! Conformance suite for third-party `LowLevelClient` implementations.
+//!
+//! Implement `ConformanceFixture` to hand the suite clients in known states (typically by
+//! pointing your client at a local stub server), then run every check with
+//! `run_conformance` or generate a test with `client_conformance_suite!`:
+//!
+//! ```ignore
+//! semantic_query::client_conformance_suite!(my_client_conforms, MyFixture::new());
+//! ```
+//!
+//! Checks cover `ask_raw` semantics, `clone_box`, resolver extraction, `stream_raw`
+//! chunking edge cases (split UTF-8, split JSON, empty chunks), `capabilities()`
+//! consistency, and error mapping.
+//! `ScriptedClient` is a reference implementation that passes the whole suite.
+
+use crate::core::{Capabilities, LowLevelClient, QueryResolver, RawByteStream, RetryConfig};
+use std::any::Any;
+use crate::error::{AIError, ClaudeError, DeepSeekError, OpenAIError, ProviderError};
+use crate::streaming::StreamItem;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Backend failure a fixture should simulate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    RateLimit,
+    Authentication,
+}
+
+/// Supplies clients in the states the suite needs
+pub trait ConformanceFixture {
+    type Client: LowLevelClient;
+
+    /// A client whose model answers every prompt with exactly `response`
+    fn replying(&self, response: &str) -> Self::Client;
+
+    /// A client whose `stream_raw` yields `chunks` as the model's text, chunk boundaries
+    /// preserved as far as the transport allows. `None` skips the streaming checks.
+    fn streaming(&self, chunks: &[&[u8]]) -> Option<Self::Client>;
+
+    /// A client whose backend fails with `failure`. `None` skips that error-mapping check.
+    fn failing(&self, failure: Failure) -> Option<Self::Client>;
+}
+
+/// Outcome of a single check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// `None` when the fixture opted out of the check
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+/// Results of `run_conformance`
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    pub fn failures(&self) -> Vec<&CheckResult> {
+        self.checks.iter().filter(|c| c.passed == Some(false)).collect()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().is_empty()
+    }
+
+    /// Panic with every failing check; for use inside tests
+    pub fn assert_passed(&self) {
+        let failures = self.failures();
+        if !failures.is_empty() {
+            panic!("client conformance failed:\n{self}");
+        }
+    }
+
+    fn record(&mut self, name: &'static str, outcome: Option<Result<(), String>>) {
+        let (passed, detail) = match outcome {
+            None => (None, "skipped by fixture".to_string()),
+            Some(Ok(())) => (Some(true), String::new()),
+            Some(Err(detail)) => (Some(false), detail),
+        };
+        self.checks.push(CheckResult { name, passed, detail });
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.passed {
+                Some(true) => "ok",
+                Some(false) => "FAILED",
+                None => "skipped",
+            };
+            write!(f, "{status:>8}  {}", check.name)?;
+            if check.passed == Some(false) {
+                write!(f, ": {}", check.detail)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Probe {
+    id: u32,
+    label: String,
+}
+
+const MIXED_RESPONSE: &str = "Here is the probe — ünïcödé text first.\n{\"id\": 7, \"label\": \"naïve ✓\"}\nAnd a closing remark.";
+
+/// Run every check against `fixture`
+pub async fn run_conformance<F: ConformanceFixture>(fixture: &F) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+    report.record("ask_raw returns the model text verbatim", Some(check_verbatim(fixture).await));
+    report.record("clone_box behaves like the original", Some(check_clone_box(fixture).await));
+    report.record("resolver extracts data from ask_raw", Some(check_resolver(fixture).await));
+    report.record("stream_raw: JSON split across chunks", check_stream(fixture, &split_json_chunks()).await);
+    report.record("stream_raw: UTF-8 split inside a character", check_stream(fixture, &split_utf8_chunks()).await);
+    report.record("stream_raw: empty chunks are tolerated", check_stream(fixture, &empty_chunk_chunks()).await);
+    report.record("capabilities() agrees with the client's methods", check_capabilities(fixture));
+    report.record("rate limits map to a RateLimit error", check_failure(fixture, Failure::RateLimit).await);
+    report.record("auth failures map to an Authentication error", check_failure(fixture, Failure::Authentication).await);
+    report
+}
+
+/// Generate a `#[tokio::test]` that runs the conformance suite against a fixture
+#[macro_export]
+macro_rules! client_conformance_suite {
+    ($name:ident, $fixture:expr) => {
+        #[tokio::test]
+        async fn $name() {
+            let fixture = $fixture;
+            $crate::client_testkit::run_conformance(&fixture).await.assert_passed();
+        }
+    };
+}
+
+async fn check_verbatim<F: ConformanceFixture>(fixture: &F) -> Result<(), String> {
+    for expected in [MIXED_RESPONSE, "", "   leading and trailing whitespace \n"] {
+        let got = fixture.replying(expected).ask_raw("probe".to_string()).await
+            .map_err(|e| format!("ask_raw failed: {e}"))?;
+        if got != expected {
+            return Err(format!("expected {expected:?}, got {got:?}"));
+        }
+    }
+    Ok(())
+}
+
+async fn check_clone_box<F: ConformanceFixture>(fixture: &F) -> Result<(), String> {
+    let client = fixture.replying(MIXED_RESPONSE);
+    let boxed = client.clone_box();
+    drop(client);
+    let got = boxed.ask_raw("probe".to_string()).await.map_err(|e| format!("boxed ask_raw failed: {e}"))?;
+    if got != MIXED_RESPONSE {
+        return Err(format!("boxed clone returned {got:?}"));
+    }
+    Ok(())
+}
+
+async fn check_resolver<F: ConformanceFixture>(fixture: &F) -> Result<(), String> {
+    let resolver = QueryResolver::new(fixture.replying(MIXED_RESPONSE), RetryConfig::default());
+    let response = resolver.query::<Probe>("probe".to_string()).await.map_err(|e| e.to_string())?;
+    match response.first() {
+        Some(probe) if probe.id == 7 && probe.label == "naïve ✓" => Ok(()),
+        other => Err(format!("expected Probe {{ id: 7 }}, got {other:?}")),
+    }
+}
+
+fn split_json_chunks() -> Vec<Vec<u8>> {
+    ["Intro text. {\"id\": 7, \"la", "bel\": \"naïve ✓\"", "} trailing"]
+        .iter()
+        .map(|s| s.as_bytes().to_vec())
+        .collect()
+}
+
+fn split_utf8_chunks() -> Vec<Vec<u8>> {
+    let text = "ü {\"id\": 7, \"label\": \"naïve ✓\"}";
+    let bytes = text.as_bytes();
+    // Split inside the two-byte "ü" and inside the three-byte "✓"
+    let check = text.find('✓').expect("probe contains a check mark") + 1;
+    vec![bytes[..1].to_vec(), bytes[1..check].to_vec(), bytes[check..].to_vec()]
+}
+
+fn empty_chunk_chunks() -> Vec<Vec<u8>> {
+    vec![Vec::new(), b"{\"id\": 7, ".to_vec(), Vec::new(), "\"label\": \"naïve ✓\"}".as_bytes().to_vec(), Vec::new()]
+}
+
+async fn check_stream<F: ConformanceFixture>(fixture: &F, chunks: &[Vec<u8>]) -> Option<Result<(), String>> {
+    let slices: Vec<&[u8]> = chunks.iter().map(Vec::as_slice).collect();
+    let client = fixture.streaming(&slices)?;
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    Some(async {
+        let mut stream = resolver.stream_query::<Probe>("probe".to_string()).await.map_err(|e| e.to_string())?;
+        let mut tokens = String::new();
+        let mut data = Vec::new();
+        while let Some(item) = stream.next().await {
+            match item.map_err(|e| format!("stream error: {e}"))? {
+                StreamItem::Token(t) => tokens.push_str(&t),
+                StreamItem::Data(d) => data.push(d),
+                StreamItem::Text(_) | StreamItem::Finished { .. } => {}
+            }
+        }
+        let expected: Vec<u8> = chunks.concat();
+        if tokens.as_bytes() != expected.as_slice() {
+            return Err(format!("tokens reassemble to {tokens:?}, expected {:?}", String::from_utf8_lossy(&expected)));
+        }
+        match data.as_slice() {
+            [probe] if probe.id == 7 && probe.label == "naïve ✓" => Ok(()),
+            other => Err(format!("expected exactly one Probe, got {other:?}")),
+        }
+    }.await)
+}
+
+fn check_capabilities<F: ConformanceFixture>(fixture: &F) -> Option<Result<(), String>> {
+    let client = fixture.streaming(&[b"{}"])?;
+    let capabilities = client.capabilities();
+    Some(if !capabilities.supports_streaming {
+        Err("stream_raw works but capabilities() reports supports_streaming: false".to_string())
+    } else if capabilities.supports_json_mode != client.with_json_mode().is_some() {
+        Err(format!("supports_json_mode is {} but with_json_mode() disagrees", capabilities.supports_json_mode))
+    } else {
+        Ok(())
+    })
+}
+
+async fn check_failure<F: ConformanceFixture>(fixture: &F, failure: Failure) -> Option<Result<(), String>> {
+    let client = fixture.failing(failure)?;
+    Some(match client.ask_raw("probe".to_string()).await {
+        Ok(text) => Err(format!("expected an error, got Ok({text:?})")),
+        Err(e) if matches_failure(&e, failure) => Ok(()),
+        Err(e) => Err(format!("expected {failure:?}, got {e:?}")),
+    })
+}
+
+/// Whether `error` is the provider-agnostic equivalent of `failure`
+pub fn matches_failure(error: &AIError, failure: Failure) -> bool {
+    match failure {
+        Failure::RateLimit => matches!(
+            error,
+            AIError::Claude(ClaudeError::RateLimit(_))
+                | AIError::OpenAI(OpenAIError::RateLimit(_))
+                | AIError::DeepSeek(DeepSeekError::RateLimit(_))
+        ),
+        Failure::Authentication => matches!(
+            error,
+            AIError::Claude(ClaudeError::Authentication(_))
+                | AIError::OpenAI(OpenAIError::Authentication(_))
+                | AIError::DeepSeek(DeepSeekError::Authentication(_))
+                | AIError::Configuration(_)
+        ),
+    }
+}
+
+/// Encode model text as the OpenAI-style SSE stream `stream_query` consumes
+pub fn sse_body(chunks: &[&[u8]]) -> Vec<Bytes> {
+    // Deltas must be strings, so decode the concatenation and re-split at char boundaries
+    // closest to the requested byte boundaries; the bytes on the wire are split exactly.
+    let mut text_chunks = Vec::new();
+    let mut pending = Vec::new();
+    for chunk in chunks {
+        pending.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if valid > 0 {
+            text_chunks.push(String::from_utf8_lossy(&pending[..valid]).into_owned());
+            pending.drain(..valid);
+        }
+    }
+    let mut body = String::new();
+    for text in text_chunks {
+        let event = serde_json::json!({ "choices": [{ "delta": { "content": text }, "finish_reason": null }] });
+        body.push_str(&format!("data: {event}\n\n"));
+    }
+    body.push_str("data: [DONE]\n\n");
+
+    // Re-chunk the wire bytes at the caller's boundaries, scaled to the body length
+    let bytes = body.into_bytes();
+    let pieces = chunks.len().max(1);
+    let step = bytes.len().div_ceil(pieces).max(1);
+    bytes.chunks(step).map(Bytes::copy_from_slice).collect()
+}
+
+/// Reference client that replays scripted text, SSE chunks, or an error
+#[derive(Debug, Clone)]
+pub enum ScriptedClient {
+    Reply(String),
+    Stream(Vec<Vec<u8>>),
+    Fail(AIError),
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for ScriptedClient {
+    async fn ask_raw(&self, _prompt: String) -> Result<String, AIError> {
+        match self {
+            ScriptedClient::Reply(text) => Ok(text.clone()),
+            ScriptedClient::Stream(chunks) => Ok(String::from_utf8_lossy(&chunks.concat()).into_owned()),
+            ScriptedClient::Fail(e) => Err(e.clone()),
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+
+    fn stream_raw(&self, _prompt: String) -> Option<RawByteStream> {
+        let ScriptedClient::Stream(chunks) = self else { return None };
+        let slices: Vec<&[u8]> = chunks.iter().map(Vec::as_slice).collect();
+        let body: Vec<Result<Bytes, AIError>> = sse_body(&slices).into_iter().map(Ok).collect();
+        Some(Box::pin(futures_util::stream::iter(body)))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { supports_streaming: matches!(self, ScriptedClient::Stream(_)), ..Capabilities::default() }
+    }
+}
+
+/// Fixture for `ScriptedClient`; also a template for writing your own
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedFixture;
+
+impl ConformanceFixture for ScriptedFixture {
+    type Client = ScriptedClient;
+
+    fn replying(&self, response: &str) -> ScriptedClient {
+        ScriptedClient::Reply(response.to_string())
+    }
+
+    fn streaming(&self, chunks: &[&[u8]]) -> Option<ScriptedClient> {
+        Some(ScriptedClient::Stream(chunks.iter().map(|c| c.to_vec()).collect()))
+    }
+
+    fn failing(&self, failure: Failure) -> Option<ScriptedClient> {
+        Some(ScriptedClient::Fail(match failure {
+            Failure::RateLimit => AIError::OpenAI(ProviderError::new(429, "Rate limit reached").into()),
+            Failure::Authentication => AIError::OpenAI(ProviderError::new(401, "Invalid API key").into()),
+        }))
+    }
+}
@@ -28,4 +28,24 @@ impl OpenAIModel {
             OpenAIModel::Override(s) => s.as_str(),
         }
     }
+
+    /// Context window in tokens; unknown for `Override`
+    pub fn context_window(&self) -> Option<u32> {
+        match self {
+            OpenAIModel::Gpt5 => Some(400_000),
+            OpenAIModel::Gpt4o | OpenAIModel::Gpt4oMini | OpenAIModel::O1Mini => Some(128_000),
+            OpenAIModel::Gpt4_1 | OpenAIModel::Gpt4_1Mini => Some(1_047_576),
+            OpenAIModel::Gpt35Turbo => Some(16_385),
+            OpenAIModel::O3Mini | OpenAIModel::O1 => Some(200_000),
+            OpenAIModel::Override(_) => None,
+        }
+    }
+
+    /// Whether the model accepts image input; false for `Override`
+    pub fn supports_vision(&self) -> bool {
+        matches!(
+            self,
+            OpenAIModel::Gpt5 | OpenAIModel::Gpt4o | OpenAIModel::Gpt4oMini | OpenAIModel::Gpt4_1 | OpenAIModel::Gpt4_1Mini | OpenAIModel::O1
+        )
+    }
 }
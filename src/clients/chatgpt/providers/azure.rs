@@ -1,13 +1,19 @@
-use crate::core::LowLevelClient;
+use crate::core::{Capabilities, LowLevelClient, RawByteStream};
+use std::any::Any;
 use crate::clients::chatgpt::models::OpenAIModel;
-use crate::error::{AIError, OpenAIError};
+use crate::correlation::Correlated;
+use crate::refusal::choice_refusal;
+use crate::confidence::TokenLogprob;
+use crate::stats::{record_finish_reason, record_logprobs, record_provenance, record_usage, ChatUsage, Provenance};
+use crate::streaming::FinishReason;
+use crate::error::{AIError, OpenAIError, ProviderError};
 use async_trait::async_trait;
-use bytes::Bytes;
-use futures_core::Stream;
 use futures_util::{StreamExt, TryStreamExt};
 use serde::Deserialize;
 use tracing::instrument;
 
+use super::tools::{tools_json, FunctionTool};
+
 /// Azure OpenAI client (ChatGPT family) with streaming support.
 #[derive(Debug, Clone)]
 pub struct AzureOpenAIConfig {
@@ -18,6 +24,12 @@ pub struct AzureOpenAIConfig {
     pub model: OpenAIModel,               // used only for logging
     pub max_tokens: u32,
     pub temperature: f32,
+    /// Sent as `seed` for best-effort deterministic sampling
+    pub seed: Option<u64>,
+    /// Sent as `user`, as for `OpenAIConfig::user`
+    pub user: Option<String>,
+    /// Functions offered to the model, as for `OpenAIConfig::tools`
+    pub tools: Vec<FunctionTool>,
 }
 
 impl Default for AzureOpenAIConfig {
@@ -30,6 +42,9 @@ impl Default for AzureOpenAIConfig {
             model: OpenAIModel::Gpt4oMini,
             max_tokens: 1024,
             temperature: 0.2,
+            seed: None,
+            user: None,
+            tools: Vec::new(),
         }
     }
 }
@@ -38,10 +53,29 @@ impl Default for AzureOpenAIConfig {
 pub struct AzureOpenAIClient {
     config: AzureOpenAIConfig,
     http: reqwest::Client,
+    json_mode: bool,
+    /// Sent as `stop` (see `with_stop_sequences`)
+    stop: Vec<String>,
+    /// Sent as `logprobs` (see `with_logprobs`)
+    logprobs: bool,
 }
 
 impl AzureOpenAIClient {
-    pub fn new(config: AzureOpenAIConfig) -> Self { Self { config, http: reqwest::Client::new() } }
+    pub fn new(config: AzureOpenAIConfig) -> Self { Self { config, http: reqwest::Client::new(), json_mode: false, stop: Vec::new(), logprobs: false } }
+
+    /// Build a client from the `AZURE_OPENAI_*` variables, returning a configuration
+    /// error if the endpoint or key is missing.
+    pub fn try_default() -> Result<Self, AIError> {
+        let _ = dotenvy::dotenv();
+        let config = AzureOpenAIConfig::default();
+        if config.endpoint.is_empty() {
+            return Err(AIError::Configuration("AZURE_OPENAI_ENDPOINT is not set in the environment or .env".into()));
+        }
+        if config.api_key.is_empty() {
+            return Err(AIError::Configuration("AZURE_OPENAI_API_KEY is not set in the environment or .env".into()));
+        }
+        Ok(Self::new(config))
+    }
 
     fn url(&self) -> String {
         format!(
@@ -53,64 +87,158 @@ impl AzureOpenAIClient {
     }
 
     fn body(&self, prompt: String, stream: bool) -> serde_json::Value {
-        serde_json::json!({
+        let mut body = serde_json::json!({
             "max_tokens": self.config.max_tokens,
             "temperature": self.config.temperature,
             "stream": stream,
             "messages": [
                 {"role": "user", "content": prompt}
             ]
-        })
+        });
+        if let Some(seed) = self.config.seed {
+            body["seed"] = seed.into();
+        }
+        if let Some(user) = &self.config.user {
+            body["user"] = user.as_str().into();
+        }
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::json!(self.stop);
+        }
+        if self.logprobs {
+            body["logprobs"] = true.into();
+        }
+        if self.json_mode {
+            body["response_format"] = serde_json::json!({ "type": "json_object" });
+        }
+        if !self.config.tools.is_empty() {
+            body["tools"] = tools_json(&self.config.tools);
+        }
+        body
     }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl LowLevelClient for AzureOpenAIClient {
-    #[instrument(skip(self, prompt), fields(model = %self.config.model.id()))]
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "azure", model = %self.config.model.id(), status = tracing::field::Empty))]
     async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
         let resp = self.http
             .post(self.url())
+            .correlated()
             .header("api-key", &self.config.api_key)
             .json(&self.body(prompt, false))
             .send().await
             .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+        tracing::Span::current().record("status", resp.status().as_u16());
 
-        if resp.status() == 401 { return Err(AIError::OpenAI(OpenAIError::Authentication)); }
-        if resp.status() == 429 { return Err(AIError::OpenAI(OpenAIError::RateLimit)); }
         if !resp.status().is_success() {
-            let txt = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AIError::OpenAI(OpenAIError::Api(txt)));
+            return Err(AIError::OpenAI(ProviderError::from_response(resp).await.into()));
         }
 
         #[derive(Deserialize)]
-        struct Choices { choices: Vec<Choice> }
+        struct Choices { choices: Vec<Choice>, model: Option<String>, system_fingerprint: Option<String>, usage: Option<ChatUsage> }
+        #[derive(Deserialize)]
+        struct Choice { message: Msg, finish_reason: Option<String>, logprobs: Option<Logprobs> }
+        #[derive(Deserialize)]
+        struct Logprobs { content: Option<Vec<TokenLogprob>> }
         #[derive(Deserialize)]
-        struct Choice { message: Msg }
+        struct Msg { content: Option<String>, refusal: Option<String>, #[serde(default)] tool_calls: Vec<Call> }
         #[derive(Deserialize)]
-        struct Msg { content: String }
+        struct Call { function: Function }
+        #[derive(Deserialize)]
+        struct Function { arguments: String }
 
         let parsed: Choices = resp.json().await
             .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
-        let content = parsed.choices.first()
-            .map(|c| c.message.content.clone())
+        record_provenance(Provenance { model: parsed.model, system_fingerprint: parsed.system_fingerprint, seed: self.config.seed });
+        if let Some(usage) = parsed.usage {
+            record_usage(usage.into());
+        }
+        let choice = parsed.choices.into_iter().next()
             .ok_or_else(|| AIError::OpenAI(OpenAIError::Api("No choices".into())))?;
-        Ok(content)
+        if let Some(reason) = choice.finish_reason.as_deref().and_then(FinishReason::from_provider) {
+            record_finish_reason(reason);
+        }
+        if let Some(tokens) = choice.logprobs.and_then(|logprobs| logprobs.content) {
+            record_logprobs(tokens);
+        }
+        if let Some(message) = choice_refusal(choice.finish_reason.as_deref(), choice.message.refusal) {
+            return Err(AIError::Refused(message));
+        }
+        let calls = choice.message.tool_calls.into_iter().map(|call| call.function.arguments);
+        Ok(choice.message.content.into_iter().chain(calls).collect::<Vec<_>>().join("\n\n"))
     }
 
     fn clone_box(&self) -> Box<dyn LowLevelClient> { Box::new(self.clone()) }
 
-    fn stream_raw(&self, prompt: String) -> Option<std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>> {
+    fn provider(&self) -> Option<&'static str> { Some("azure") }
+
+    fn as_any(&self) -> Option<&dyn Any> { Some(self) }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { Some(self) }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.temperature = temperature;
+        Some(Box::new(client))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.config.max_tokens)
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = max_tokens;
+        Some(Box::new(client))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.seed = Some(seed);
+        Some(Box::new(client))
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.user = Some(user.to_string());
+        Some(Box::new(client))
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { json_mode: true, ..self.clone() }))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { stop: stop.to_vec(), ..self.clone() }))
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { logprobs: true, ..self.clone() }))
+    }
+
+    /// Assumes `config.model` matches the deployment
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_native_tools: true,
+            supports_json_mode: true,
+            supports_vision: self.config.model.supports_vision(),
+            max_context_tokens: self.config.model.context_window(),
+            ..Capabilities::default()
+        }
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
         let req = self.http
             .post(self.url())
+            .correlated()
             .header("api-key", &self.config.api_key)
             .json(&self.body(prompt, true));
         let fut = async move {
             let resp = req.send().await.map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
-            if resp.status() == 401 { return Err(AIError::OpenAI(OpenAIError::Authentication)); }
-            if resp.status() == 429 { return Err(AIError::OpenAI(OpenAIError::RateLimit)); }
             if !resp.status().is_success() {
-                let txt = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(AIError::OpenAI(OpenAIError::Api(txt)));
+                return Err(AIError::OpenAI(ProviderError::from_response(resp).await.into()));
             }
             Ok(resp.bytes_stream().map(|r| r.map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))))
         };
@@ -1,6 +1,11 @@
+mod tools;
+#[cfg(feature = "openai")]
 pub mod openai;
+#[cfg(feature = "azure")]
 pub mod azure;
 
+pub use tools::FunctionTool;
+#[cfg(feature = "openai")]
 pub use openai::*;
+#[cfg(feature = "azure")]
 pub use azure::*;
-
@@ -1,28 +1,62 @@
-use crate::core::LowLevelClient;
+use crate::core::{Capabilities, ChatMessage, LowLevelClient, RawByteStream};
+use std::any::Any;
 use crate::clients::chatgpt::models::OpenAIModel;
-use crate::error::{AIError, OpenAIError};
+use crate::config::KeyFromEnv;
+use crate::correlation::Correlated;
+use crate::refusal::choice_refusal;
+use crate::request::{ModelRequest, ResponseFormat};
+use crate::confidence::TokenLogprob;
+use crate::stats::{record_finish_reason, record_logprobs, record_provenance, record_usage, ChatUsage, Provenance};
+use crate::streaming::FinishReason;
+use crate::error::{AIError, OpenAIError, ProviderError};
 use async_trait::async_trait;
-use bytes::Bytes;
-use futures_core::Stream;
 use futures_util::{StreamExt, TryStreamExt};
 use serde::Deserialize;
 use tracing::instrument;
 
+use super::tools::{tools_json, FunctionTool};
+
 #[derive(Debug, Clone)]
 pub struct OpenAIConfig {
     pub api_key: String,
     pub model: OpenAIModel,
     pub max_tokens: u32,
     pub temperature: f32,
+    /// Sent as `seed` for best-effort deterministic sampling
+    pub seed: Option<u64>,
+    /// Sent as `user`: an id of the end user the request is made for, which OpenAI uses
+    /// to monitor abuse
+    pub user: Option<String>,
+    /// Functions offered to the model; streamed `tool_calls` are reassembled into
+    /// `Data(T)` items (see `streaming::ToolCall`)
+    pub tools: Vec<FunctionTool>,
+    /// OpenAI API base URL, e.g. a proxy in front of it
+    pub base_url: String,
+}
+
+impl OpenAIConfig {
+    /// Enable native function calling with `tool` (in addition to any already added)
+    pub fn with_tool(mut self, tool: FunctionTool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+}
+
+impl KeyFromEnv for OpenAIConfig {
+    const KEY_NAME: &'static str = "OPENAI_API_KEY";
 }
 
 impl Default for OpenAIConfig {
     fn default() -> Self {
         Self {
-            api_key: std::env::var("OPENAI_API_KEY").unwrap_or_default(),
+            api_key: Self::find_key().unwrap_or_default(),
             model: OpenAIModel::Gpt4oMini,
             max_tokens: 1024,
             temperature: 0.2,
+            seed: None,
+            user: None,
+            tools: Vec::new(),
+            base_url: "https://api.openai.com".to_string(),
         }
     }
 }
@@ -31,60 +65,224 @@ impl Default for OpenAIConfig {
 pub struct OpenAIClient {
     config: OpenAIConfig,
     http: reqwest::Client,
+    json_mode: bool,
+    /// Sent as `stop` (see `with_stop_sequences`)
+    stop: Vec<String>,
+    /// Sent as `logprobs` (see `with_logprobs`)
+    logprobs: bool,
 }
 
 impl OpenAIClient {
-    pub fn new(config: OpenAIConfig) -> Self { Self { config, http: reqwest::Client::new() } }
+    pub fn new(config: OpenAIConfig) -> Self { Self { config, http: reqwest::Client::new(), json_mode: false, stop: Vec::new(), logprobs: false } }
+
+    /// Build a client from `OPENAI_API_KEY`, returning a configuration error if missing.
+    pub fn try_default() -> Result<Self, AIError> {
+        let api_key = OpenAIConfig::require_key()?;
+        Ok(Self::new(OpenAIConfig { api_key, ..OpenAIConfig::default() }))
+    }
+
+    /// The configuration this client sends requests with
+    pub fn config(&self) -> &OpenAIConfig { &self.config }
+
+    fn completions_url(&self) -> String {
+        format!("{}/v1/chat/completions", self.config.base_url.trim_end_matches('/'))
+    }
+
+    /// Mutable configuration; changes apply to subsequent requests
+    pub fn config_mut(&mut self) -> &mut OpenAIConfig { &mut self.config }
+
+    /// Switch the model used for subsequent requests
+    pub fn set_model(&mut self, model: OpenAIModel) { self.config.model = model; }
+
+    /// Change the sampling temperature for subsequent requests
+    pub fn set_temperature(&mut self, temperature: f32) { self.config.temperature = temperature; }
 
     fn messages_body(&self, prompt: String) -> serde_json::Value {
-        serde_json::json!({
+        self.chat_body(&[ChatMessage::user(prompt)])
+    }
+
+    fn chat_body(&self, messages: &[ChatMessage]) -> serde_json::Value {
+        let mut body = serde_json::json!({
             "model": self.config.model.id(),
             "max_tokens": self.config.max_tokens,
             "temperature": self.config.temperature,
-            "messages": [
-                {"role": "user", "content": prompt}
-            ]
-        })
+            "messages": messages
+        });
+        if let Some(seed) = self.config.seed {
+            body["seed"] = seed.into();
+        }
+        if let Some(user) = &self.config.user {
+            body["user"] = user.as_str().into();
+        }
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::json!(self.stop);
+        }
+        if self.logprobs {
+            body["logprobs"] = true.into();
+        }
+        if self.json_mode {
+            body["response_format"] = serde_json::json!({ "type": "json_object" });
+        }
+        if !self.config.tools.is_empty() {
+            body["tools"] = tools_json(&self.config.tools);
+        }
+        body
     }
-}
 
-#[async_trait]
-impl LowLevelClient for OpenAIClient {
-    #[instrument(skip(self, prompt), fields(model = %self.config.model.id()))]
-    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
-        let body = self.messages_body(prompt);
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "openai", model = %self.config.model.id(), status = tracing::field::Empty))]
+    async fn complete(&self, body: serde_json::Value) -> Result<String, AIError> {
         let resp = self.http
-            .post("https://api.openai.com/v1/chat/completions")
+            .post(self.completions_url())
+            .correlated()
             .bearer_auth(&self.config.api_key)
             .json(&body)
             .send().await
             .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+        tracing::Span::current().record("status", resp.status().as_u16());
 
-        if resp.status() == 401 { return Err(AIError::OpenAI(OpenAIError::Authentication)); }
-        if resp.status() == 429 { return Err(AIError::OpenAI(OpenAIError::RateLimit)); }
         if !resp.status().is_success() {
-            let txt = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AIError::OpenAI(OpenAIError::Api(txt)));
+            return Err(AIError::OpenAI(ProviderError::from_response(resp).await.into()));
         }
 
         #[derive(Deserialize)]
-        struct Choices { choices: Vec<Choice> }
+        struct Choices { choices: Vec<Choice>, model: Option<String>, system_fingerprint: Option<String>, usage: Option<ChatUsage> }
+        #[derive(Deserialize)]
+        struct Choice { message: Msg, finish_reason: Option<String>, logprobs: Option<Logprobs> }
         #[derive(Deserialize)]
-        struct Choice { message: Msg }
+        struct Logprobs { content: Option<Vec<TokenLogprob>> }
         #[derive(Deserialize)]
-        struct Msg { content: String }
+        struct Msg { content: Option<String>, refusal: Option<String>, #[serde(default)] tool_calls: Vec<Call> }
+        #[derive(Deserialize)]
+        struct Call { function: Function }
+        #[derive(Deserialize)]
+        struct Function { arguments: String }
 
         let parsed: Choices = resp.json().await
             .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
-        let content = parsed.choices.first()
-            .map(|c| c.message.content.clone())
+        record_provenance(Provenance { model: parsed.model, system_fingerprint: parsed.system_fingerprint, seed: self.config.seed });
+        if let Some(usage) = parsed.usage {
+            record_usage(usage.into());
+        }
+        let choice = parsed.choices.into_iter().next()
             .ok_or_else(|| AIError::OpenAI(OpenAIError::Api("No choices".into())))?;
-        Ok(content)
+        if let Some(reason) = choice.finish_reason.as_deref().and_then(FinishReason::from_provider) {
+            record_finish_reason(reason);
+        }
+        if let Some(tokens) = choice.logprobs.and_then(|logprobs| logprobs.content) {
+            record_logprobs(tokens);
+        }
+        if let Some(message) = choice_refusal(choice.finish_reason.as_deref(), choice.message.refusal) {
+            return Err(AIError::Refused(message));
+        }
+        // Function calls are rendered as their arguments JSON, after any text
+        let calls = choice.message.tool_calls.into_iter().map(|call| call.function.arguments);
+        Ok(choice.message.content.into_iter().chain(calls).collect::<Vec<_>>().join("\n\n"))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for OpenAIClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.complete(self.messages_body(prompt)).await
+    }
+
+    fn supports_system_role(&self) -> bool { true }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        self.complete(self.chat_body(&messages)).await
+    }
+
+    /// Request parameters override the config, request tools are sent after the
+    /// configured ones, and `JsonSchema` maps to structured outputs
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        let mut client = self.clone();
+        let params = request.params;
+        client.config.temperature = params.temperature.unwrap_or(client.config.temperature);
+        client.config.max_tokens = params.max_tokens.unwrap_or(client.config.max_tokens);
+        client.config.seed = params.seed.or(client.config.seed);
+        client.config.user = params.user.or(client.config.user);
+        if !params.stop.is_empty() {
+            client.stop = params.stop;
+        }
+        client.config.tools.extend(request.tools.into_iter().map(FunctionTool::from));
+
+        let mut body = client.chat_body(&request.messages);
+        match request.response_format {
+            ResponseFormat::Text => {}
+            ResponseFormat::JsonObject => body["response_format"] = serde_json::json!({ "type": "json_object" }),
+            ResponseFormat::JsonSchema { name, schema } => {
+                body["response_format"] = serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": { "name": name, "schema": schema }
+                });
+            }
+        }
+        client.complete(body).await
     }
 
     fn clone_box(&self) -> Box<dyn LowLevelClient> { Box::new(self.clone()) }
 
-    fn stream_raw(&self, prompt: String) -> Option<std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>> {
+    fn provider(&self) -> Option<&'static str> { Some("openai") }
+
+    fn as_any(&self) -> Option<&dyn Any> { Some(self) }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { Some(self) }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.temperature = temperature;
+        Some(Box::new(client))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.config.max_tokens)
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = max_tokens;
+        Some(Box::new(client))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.seed = Some(seed);
+        Some(Box::new(client))
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.user = Some(user.to_string());
+        Some(Box::new(client))
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { json_mode: true, ..self.clone() }))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { stop: stop.to_vec(), ..self.clone() }))
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { logprobs: true, ..self.clone() }))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_native_tools: true,
+            supports_json_mode: true,
+            supports_grammar: false,
+            supports_system_role: true,
+            supports_prefill: false,
+            supports_vision: self.config.model.supports_vision(),
+            max_context_tokens: self.config.model.context_window(),
+        }
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
         let body = {
             let mut v = self.messages_body(prompt);
             if let Some(obj) = v.as_object_mut() {
@@ -93,16 +291,14 @@ impl LowLevelClient for OpenAIClient {
             v
         };
         let req = self.http
-            .post("https://api.openai.com/v1/chat/completions")
+            .post(self.completions_url())
+            .correlated()
             .bearer_auth(&self.config.api_key)
             .json(&body);
         let fut = async move {
             let resp = req.send().await.map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
-            if resp.status() == 401 { return Err(AIError::OpenAI(OpenAIError::Authentication)); }
-            if resp.status() == 429 { return Err(AIError::OpenAI(OpenAIError::RateLimit)); }
             if !resp.status().is_success() {
-                let txt = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(AIError::OpenAI(OpenAIError::Api(txt)));
+                return Err(AIError::OpenAI(ProviderError::from_response(resp).await.into()));
             }
             Ok(resp.bytes_stream().map(|r| r.map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))))
         };
@@ -0,0 +1,37 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::request::ToolSpec;
+use crate::schema::schema_value;
+
+/// A function definition for OpenAI-style native tool calling
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionTool {
+    pub name: String,
+    pub description: String,
+    /// JSON Schema of the function's arguments
+    pub parameters: serde_json::Value,
+}
+
+impl FunctionTool {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: serde_json::Value) -> Self {
+        Self { name: name.into(), description: description.into(), parameters }
+    }
+
+    /// A function whose arguments are `T`, so its calls stream as `Data(T)`
+    pub fn for_type<T: JsonSchema>(name: impl Into<String>, description: impl Into<String>) -> Self {
+        let parameters = schema_value::<T>();
+        Self::new(name, description, parameters)
+    }
+}
+
+impl From<ToolSpec> for FunctionTool {
+    fn from(tool: ToolSpec) -> Self {
+        Self::new(tool.name, tool.description, tool.schema)
+    }
+}
+
+/// The `tools` request field for `tools`
+pub(crate) fn tools_json(tools: &[FunctionTool]) -> serde_json::Value {
+    tools.iter().map(|tool| serde_json::json!({ "type": "function", "function": tool })).collect()
+}
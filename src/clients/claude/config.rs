@@ -1,3 +1,6 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+
 use crate::config::KeyFromEnv;
 
 use super::models::ClaudeModel;
@@ -11,9 +14,15 @@ pub enum Provider {
 }
 
 impl Default for Provider {
+    #[cfg(feature = "anthropic")]
     fn default() -> Self {
         Self::Anthropic
     }
+
+    #[cfg(not(feature = "anthropic"))]
+    fn default() -> Self {
+        Self::AwsBedrock
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -23,8 +32,23 @@ pub struct ClaudeConfig {
     pub model: ClaudeModel,
     pub api_key: String,
     pub max_tokens: u32,
+    /// Sent as `temperature`; the provider's default when `None`
+    pub temperature: Option<f32>,
+    /// Anthropic API base URL, e.g. a proxy in front of it; ignored by Bedrock
+    pub base_url: String,
     pub enable_caching: bool,
     pub cache_threshold: usize,
+    /// Tools offered to the model with every request; its `tool_use` blocks are parsed
+    /// by `ClaudeResponse::parse` and by streaming queries
+    pub tools: Vec<ClaudeTool>,
+    /// Text every reply starts with (assistant prefill), e.g. `{` to force JSON. Sent as
+    /// a trailing assistant message and put back in front of the reply.
+    pub prefill: Option<String>,
+    /// Sent as `stop_sequences`: generation ends before any of them
+    pub stop_sequences: Vec<String>,
+    /// Sent as `metadata.user_id`: an opaque id of the end user the request is made
+    /// for, which Anthropic uses to detect abuse. Not a name or email address.
+    pub user_id: Option<String>,
     // AWS Bedrock specific
     pub aws_region: Option<String>,
     pub aws_access_key_id: Option<String>,
@@ -39,8 +63,14 @@ impl Default for ClaudeConfig {
             api_key: Self::find_key().unwrap_or(String::new()),
 
             max_tokens: 4096,
+            temperature: None,
+            base_url: "https://api.anthropic.com".to_string(),
             enable_caching: true,
             cache_threshold: 3000,
+            tools: Vec::new(),
+            prefill: None,
+            stop_sequences: Vec::new(),
+            user_id: None,
             aws_region: None,
             aws_access_key_id: None,
             aws_secret_access_key: None,
@@ -96,4 +126,47 @@ impl ClaudeConfig {
         self.model = model;
         self
     }
+
+    /// Enable native tool calling with `tool` (in addition to any already added)
+    #[must_use]
+    pub fn with_tool(mut self, tool: ClaudeTool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Start every reply with `prefill`; see `prefill`
+    #[must_use]
+    pub fn with_prefill(mut self, prefill: impl Into<String>) -> Self {
+        self.prefill = Some(prefill.into());
+        self
+    }
+}
+
+/// A tool definition for Anthropic's native tool calling
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClaudeTool {
+    pub name: String,
+    pub description: String,
+    /// JSON Schema of the tool's input
+    pub input_schema: serde_json::Value,
+}
+
+impl ClaudeTool {
+    #[must_use]
+    pub fn new(name: impl Into<String>, description: impl Into<String>, input_schema: serde_json::Value) -> Self {
+        Self { name: name.into(), description: description.into(), input_schema }
+    }
+
+    /// A tool whose input is `T`, so its calls stream as `Data(T)`
+    #[must_use]
+    pub fn for_type<T: JsonSchema>(name: impl Into<String>, description: impl Into<String>) -> Self {
+        let input_schema = crate::schema::schema_value::<T>();
+        Self::new(name, description, input_schema)
+    }
+}
+
+impl From<crate::request::ToolSpec> for ClaudeTool {
+    fn from(tool: crate::request::ToolSpec) -> Self {
+        Self::new(tool.name, tool.description, tool.schema)
+    }
 }
@@ -2,17 +2,15 @@ pub mod providers;
 pub mod models;
 pub mod config;
 
-// Ensure at least one provider is enabled at compile time for Claude
-#[cfg(all(not(feature = "anthropic"), not(feature = "bedrock")))]
-compile_error!("No Claude providers are enabled. Enable at least one feature: 'anthropic' or 'bedrock'.");
-
 pub use providers::*;
 pub use models::*;
 pub use config::*;
 
-use crate::core::LowLevelClient;
+use crate::core::{Capabilities, ChatMessage, LowLevelClient, RawByteStream};
+use std::any::Any;
 use futures_util::{StreamExt, TryStreamExt};
 use crate::error::AIError;
+use crate::request::ModelRequest;
 use crate::config::KeyFromEnv;
 use async_trait::async_trait;
 
@@ -21,7 +19,7 @@ use async_trait::async_trait;
 pub enum ClaudeClientProvider {
     #[cfg(feature = "anthropic")] 
     Anthropic(AnthropicProvider),
-    #[cfg(feature = "bedrock")] 
+    #[cfg(all(feature = "bedrock", feature = "aws-bedrock-sdk"))] 
     Bedrock(BedrockProvider),
 }
 
@@ -30,16 +28,16 @@ impl ClaudeClientProvider {
         match self {
             #[cfg(feature = "anthropic")] 
             Self::Anthropic(provider) => provider.call_api(request).await,
-            #[cfg(feature = "bedrock")] 
+            #[cfg(all(feature = "bedrock", feature = "aws-bedrock-sdk"))] 
             Self::Bedrock(provider) => provider.call_api(request).await,
         }
     }
 
-    async fn stream_api(&self, request: &ClaudeRequest) -> Result<std::pin::Pin<Box<dyn futures_core::Stream<Item = Result<bytes::Bytes, AIError>> + Send>>, AIError> {
+    async fn stream_api(&self, request: &ClaudeRequest) -> Result<RawByteStream, AIError> {
         match self {
             #[cfg(feature = "anthropic")] 
             Self::Anthropic(provider) => provider.stream_api(request).await,
-            #[cfg(feature = "bedrock")] 
+            #[cfg(all(feature = "bedrock", feature = "aws-bedrock-sdk"))] 
             Self::Bedrock(_) => Err(AIError::Claude(crate::error::ClaudeError::Api("Bedrock streaming not implemented".into()))),
         }
     }
@@ -56,6 +54,7 @@ impl KeyFromEnv for ClaudeConfig {
     const KEY_NAME: &'static str = "ANTHROPIC_API_KEY";
 }
 
+#[cfg(feature = "anthropic")]
 impl Default for ClaudeClient {
     fn default() -> Self {
         let config = ClaudeConfig::anthropic(ClaudeConfig::find_key().unwrap_or(String::new()), ClaudeModel::Haiku35);
@@ -65,34 +64,128 @@ impl Default for ClaudeClient {
 
 impl ClaudeClient {
     #[must_use]
-    #[allow(clippy::missing_panics_doc)]
     pub fn new(config: ClaudeConfig) -> Self {
+        // `Provider` variants are gated by the same features as the provider
+        // implementations, so this match is exhaustive for any feature set.
         let provider = match config.provider {
             #[cfg(feature = "anthropic")] 
             Provider::Anthropic => ClaudeClientProvider::Anthropic(AnthropicProvider::new(config.clone())),
             #[cfg(all(feature = "bedrock", feature = "aws-bedrock-sdk"))] 
             Provider::AwsBedrock => ClaudeClientProvider::Bedrock(BedrockProvider::new(config.clone())),
-            #[allow(unreachable_patterns)]
-            _ => panic!("Requested provider is not enabled via features"),
         };
 
         Self { provider, config }
     }
+
+    /// Build a client from `ANTHROPIC_API_KEY`, returning a configuration error
+    /// if the key is missing.
+    #[cfg(feature = "anthropic")]
+    pub fn try_default() -> Result<Self, AIError> {
+        let api_key = ClaudeConfig::require_key()?;
+        Ok(Self::new(ClaudeConfig::anthropic(api_key, ClaudeModel::Haiku35)))
+    }
+
+    /// The configuration this client sends requests with
+    pub fn config(&self) -> &ClaudeConfig {
+        &self.config
+    }
+
+    /// Switch the model used for subsequent requests
+    pub fn set_model(&mut self, model: ClaudeModel) {
+        self.config.model = model;
+    }
+
+    /// Change the response token limit for subsequent requests
+    pub fn set_max_tokens(&mut self, max_tokens: u32) {
+        self.config.max_tokens = max_tokens;
+    }
+
+    /// Call the API, putting the request's prefill back in front of the reply
+    async fn send(&self, request: ClaudeRequest) -> Result<String, AIError> {
+        let reply = self.provider.call_api(&request).await?;
+        Ok(match request.prefill() {
+            Some(prefill) => format!("{prefill}{reply}"),
+            None => reply,
+        })
+    }
+}
+
+/// Anthropic SSE event streaming `text` as the first text delta
+fn prefill_event(text: &str) -> bytes::Bytes {
+    let data = serde_json::json!({
+        "type": "content_block_delta",
+        "index": 0,
+        "delta": { "type": "text_delta", "text": text },
+    });
+    bytes::Bytes::from(format!("event: content_block_delta\ndata: {data}\n\n"))
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl LowLevelClient for ClaudeClient {
     async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
-        let request = ClaudeRequest::new(prompt, &self.config);
-        self.provider.call_api(&request).await
+        self.send(ClaudeRequest::new(prompt, &self.config)).await
+    }
+
+    fn supports_system_role(&self) -> bool { true }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        self.send(ClaudeRequest::from_messages(messages, &self.config)).await
+    }
+
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        self.send(ClaudeRequest::from_model_request(request, &self.config)).await
     }
 
-    fn stream_raw(&self, prompt: String) -> Option<std::pin::Pin<Box<dyn futures_core::Stream<Item = Result<bytes::Bytes, AIError>> + Send>>> {
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.config.max_tokens)
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = max_tokens;
+        Some(Box::new(client))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.stop_sequences = stop.to_vec();
+        Some(Box::new(client))
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.user_id = Some(user.to_string());
+        Some(Box::new(client))
+    }
+
+    fn with_prefill(&self, prefill: &str) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.prefill = Some(prefill.to_string());
+        Some(Box::new(client))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_native_tools: true,
+            supports_system_role: true,
+            supports_prefill: true,
+            supports_vision: self.config.model.supports_vision(),
+            max_context_tokens: Some(self.config.model.context_window()),
+            ..Capabilities::default()
+        }
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
         let config = self.config.clone();
         let provider = self.provider.clone();
         let s = async_stream::try_stream! {
             let request = ClaudeRequest::new(prompt, &config);
             let mut bs = provider.stream_api(&request).await?;
+            if let Some(prefill) = request.prefill() {
+                yield prefill_event(prefill);
+            }
             while let Some(chunk) = bs.next().await {
                 let b = chunk?;
                 yield b;
@@ -104,4 +197,16 @@ impl LowLevelClient for ClaudeClient {
     fn clone_box(&self) -> Box<dyn LowLevelClient> {
         Box::new(self.clone())
     }
+
+    fn provider(&self) -> Option<&'static str> {
+        Some("claude")
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
 }
@@ -25,6 +25,18 @@ impl Default for ClaudeModel {
 }
 
 impl ClaudeModel {
+    /// Every known model, newest first
+    pub const ALL: [Self; 9] = [
+        Self::Opus4, Self::Sonnet4, Self::Sonnet37, Self::Haiku35, Self::Sonnet35V2,
+        Self::Sonnet35, Self::Opus3, Self::Sonnet3, Self::Haiku3,
+    ];
+
+    /// Look up a model by its Anthropic or Bedrock model id
+    #[must_use]
+    pub fn from_model_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.anthropic_model_id() == id || m.bedrock_model_id() == id)
+    }
+
     #[must_use]
     pub const fn anthropic_model_id(&self) -> &'static str {
         match self {
@@ -60,7 +72,7 @@ impl ClaudeModel {
         match provider {
             #[cfg(feature = "anthropic")] 
             super::config::Provider::Anthropic => self.anthropic_model_id(),
-            #[cfg(feature = "bedrock")] 
+            #[cfg(all(feature = "bedrock", feature = "aws-bedrock-sdk"))] 
             super::config::Provider::AwsBedrock => self.bedrock_model_id(),
         }
     }
@@ -79,4 +91,16 @@ impl ClaudeModel {
             Self::Haiku3 => "Claude 3 Haiku",
         }
     }
+
+    /// Context window in tokens
+    #[must_use]
+    pub const fn context_window(&self) -> u32 {
+        200_000
+    }
+
+    /// Whether the model accepts image input
+    #[must_use]
+    pub const fn supports_vision(&self) -> bool {
+        !matches!(self, Self::Haiku35)
+    }
 }
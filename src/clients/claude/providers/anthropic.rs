@@ -1,13 +1,16 @@
 use crate::config::KeyFromEnv;
-use crate::error::{AIError, ClaudeError};
+use crate::correlation::Correlated;
+use crate::refusal::CONTENT_FILTERED;
+use crate::stats::{record_finish_reason, record_provenance, record_usage, Provenance};
+use crate::streaming::FinishReason;
+use crate::error::{AIError, ClaudeError, ProviderError};
 use async_trait::async_trait;
 use reqwest::Client;
 use tracing::{debug, error, info, instrument, warn};
 
 use super::{ClaudeProvider, ClaudeRequest, ClaudeResponse};
 use crate::clients::claude::config::ClaudeConfig;
-use bytes::Bytes;
-use futures_core::Stream;
+use crate::core::RawByteStream;
 use futures_util::StreamExt;
 
 #[allow(clippy::module_name_repetitions)]
@@ -30,23 +33,39 @@ impl AnthropicProvider {
         }
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     #[must_use]
     pub fn default_with_key() -> Self {
         let api_key = Self::find_key_with_user();
         let config = ClaudeConfig { api_key, ..ClaudeConfig::default() };
         Self::new(config)
     }
+
+    /// Like `default_with_key`, but returns a configuration error instead of
+    /// panicking when no key is provided.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn try_default_with_key() -> Result<Self, AIError> {
+        let api_key = Self::try_find_key_with_user()?;
+        let config = ClaudeConfig { api_key, ..ClaudeConfig::default() };
+        Ok(Self::new(config))
+    }
+
+    fn messages_url(&self) -> String {
+        format!("{}/v1/messages", self.config.base_url.trim_end_matches('/'))
+    }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl ClaudeProvider for AnthropicProvider {
-    #[instrument(skip(self, request), fields(model = %request.model))]
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "anthropic", model = %request.model, status = tracing::field::Empty))]
     async fn call_api(&self, request: &ClaudeRequest) -> Result<String, AIError> {
         debug!(model = %request.model, "Preparing Anthropic API request");
 
         let response = self
             .client
-            .post("https://api.anthropic.com/v1/messages")
+            .post(self.messages_url())
+            .correlated()
             .header("x-api-key", &self.config.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
@@ -58,26 +77,17 @@ impl ClaudeProvider for AnthropicProvider {
                 AIError::Claude(ClaudeError::Http(e.to_string()))
             })?;
 
+        tracing::Span::current().record("status", response.status().as_u16());
         debug!(status = %response.status(), "Received response from Anthropic API");
 
-        if response.status() == 429 {
-            warn!("Anthropic API rate limit exceeded");
-            return Err(AIError::Claude(ClaudeError::RateLimit));
-        }
-
-        if response.status() == 401 {
-            error!("Anthropic API authentication failed");
-            return Err(AIError::Claude(ClaudeError::Authentication));
-        }
-
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            error!(status = %status, error = %error_text, "Anthropic API error");
-            return Err(AIError::Claude(ClaudeError::Api(error_text)));
+            let err = ProviderError::from_response(response).await;
+            if err.status == 429 {
+                warn!(request_id = ?err.request_id, retry_after = ?err.retry_after, "Anthropic API rate limit exceeded");
+            } else {
+                error!(status = err.status, code = ?err.code, request_id = ?err.request_id, error = %err.message, "Anthropic API error");
+            }
+            return Err(AIError::Claude(err.into()));
         }
 
         let claude_response: ClaudeResponse = response.json().await.map_err(|e| {
@@ -86,11 +96,23 @@ impl ClaudeProvider for AnthropicProvider {
         })?;
 
         debug!(content_count = claude_response.content.len(), "Parsed Anthropic response");
+        record_provenance(Provenance { model: claude_response.model.clone(), ..Provenance::default() });
+        if let Some(usage) = claude_response.usage {
+            record_usage(usage.into());
+        }
+        if let Some(reason) = claude_response.stop_reason.as_deref().and_then(FinishReason::from_provider) {
+            record_finish_reason(reason);
+        }
+
+        if claude_response.stop_reason.as_deref() == Some("refusal") {
+            warn!("Anthropic model declined to answer");
+            let text = claude_response.text();
+            return Err(AIError::Refused(if text.trim().is_empty() { CONTENT_FILTERED.to_string() } else { text }));
+        }
 
-        let result = claude_response
-            .content
-            .first()
-            .map(|content| content.text.clone())
+        // Tool calls are rendered as their input JSON; `ClaudeResponse::parse` maps them directly
+        let result = Some(claude_response.text())
+            .filter(|_| !claude_response.content.is_empty())
             .ok_or_else(|| {
                 error!("No content in Anthropic response");
                 AIError::Claude(ClaudeError::Api("No content in response".to_string()))
@@ -104,28 +126,39 @@ impl ClaudeProvider for AnthropicProvider {
         result
     }
 
-    async fn stream_api(&self, request: &ClaudeRequest) -> Result<std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>, AIError> {
+    async fn stream_api(&self, request: &ClaudeRequest) -> Result<RawByteStream, AIError> {
+        let mut body = serde_json::json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens,
+            "messages": request.messages,
+            "stream": true
+        });
+        if let Some(system) = &request.system {
+            body["system"] = serde_json::json!(system);
+        }
+        if !request.tools.is_empty() {
+            body["tools"] = serde_json::json!(request.tools);
+        }
+        if !request.stop_sequences.is_empty() {
+            body["stop_sequences"] = serde_json::json!(request.stop_sequences);
+        }
+        if let Some(metadata) = &request.metadata {
+            body["metadata"] = serde_json::json!(metadata);
+        }
         let resp = self
             .client
-            .post("https://api.anthropic.com/v1/messages")
+            .post(self.messages_url())
+            .correlated()
             .header("x-api-key", &self.config.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&serde_json::json!({
-                "model": request.model,
-                "max_tokens": request.max_tokens,
-                "messages": request.messages,
-                "stream": true
-            }))
+            .json(&body)
             .send()
             .await
             .map_err(|e| AIError::Claude(crate::error::ClaudeError::Http(e.to_string())))?;
 
-        if resp.status() == 401 { return Err(AIError::Claude(crate::error::ClaudeError::Authentication)); }
-        if resp.status() == 429 { return Err(AIError::Claude(crate::error::ClaudeError::RateLimit)); }
         if !resp.status().is_success() {
-            let txt = resp.text().await.unwrap_or_else(|_| "Unknown error".into());
-            return Err(AIError::Claude(crate::error::ClaudeError::Api(txt)));
+            return Err(AIError::Claude(ProviderError::from_response(resp).await.into()));
         }
         let s = async_stream::try_stream! {
             let mut bs = resp.bytes_stream().map(|r| r.map_err(|e| AIError::Claude(crate::error::ClaudeError::Http(e.to_string()))));
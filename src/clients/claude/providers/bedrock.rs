@@ -4,6 +4,8 @@ use tracing::{debug, error, info, instrument};
 
 use super::{ClaudeProvider, ClaudeRequest};
 use crate::clients::claude::config::ClaudeConfig;
+use crate::core::RawByteStream;
+use bytes::Bytes;
 #[cfg(feature = "aws-bedrock-sdk")]
 use aws_sdk_bedrockruntime as bedrockrt;
 #[cfg(feature = "aws-bedrock-sdk")]
@@ -48,11 +50,20 @@ impl BedrockProvider {
                 serde_json::json!({"role": m.role, "content": content_blocks})
             }).collect();
 
-            let payload = serde_json::json!({
+            let mut payload = serde_json::json!({
                 "anthropic_version": "bedrock-2023-05-31",
                 "max_tokens": request.max_tokens,
                 "messages": messages
             });
+            if let Some(temperature) = request.temperature {
+                payload["temperature"] = serde_json::json!(temperature);
+            }
+            if !request.stop_sequences.is_empty() {
+                payload["stop_sequences"] = serde_json::json!(request.stop_sequences);
+            }
+            if let Some(system) = &request.system {
+                payload["system"] = serde_json::json!(system);
+            }
 
             let resp = client
                 .invoke_model()
@@ -76,9 +87,10 @@ impl BedrockProvider {
 
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl ClaudeProvider for BedrockProvider {
-    #[instrument(skip(self, request), fields(model = %request.model, region = ?self.config.aws_region))]
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "bedrock", model = %request.model, region = ?self.config.aws_region))]
     async fn call_api(&self, request: &ClaudeRequest) -> Result<String, AIError> {
         debug!(
             model = %request.model,
@@ -101,7 +113,7 @@ impl ClaudeProvider for BedrockProvider {
         self.call_bedrock_api(request).await
     }
 
-    async fn stream_api(&self, request: &ClaudeRequest) -> Result<std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>, AIError> {
+    async fn stream_api(&self, request: &ClaudeRequest) -> Result<RawByteStream, AIError> {
         #[cfg(not(feature = "aws-bedrock-sdk"))]
         {
             return Err(AIError::Claude(ClaudeError::Api(
@@ -128,12 +140,21 @@ impl ClaudeProvider for BedrockProvider {
                 serde_json::json!({"role": m.role, "content": content_blocks})
             }).collect();
 
-            let payload = serde_json::json!({
+            let mut payload = serde_json::json!({
                 "anthropic_version": "bedrock-2023-05-31",
                 "max_tokens": request.max_tokens,
                 "messages": messages,
                 "stream": true
             });
+            if let Some(temperature) = request.temperature {
+                payload["temperature"] = serde_json::json!(temperature);
+            }
+            if !request.stop_sequences.is_empty() {
+                payload["stop_sequences"] = serde_json::json!(request.stop_sequences);
+            }
+            if let Some(system) = &request.system {
+                payload["system"] = serde_json::json!(system);
+            }
 
             // Try InvokeModelWithResponseStream first; if unsupported by model, fallback to one-shot
             let try_stream = client
@@ -222,11 +243,14 @@ impl BedrockProvider {
     }
 
     async fn call_bedrock_api(&self, request: &ClaudeRequest) -> Result<String, AIError> {
-        let body = json!({
+        let mut body = json!({
             "anthropic_version": "bedrock-2023-05-31",
             "max_tokens": request.max_tokens,
             "messages": request.messages
         });
+        if let Some(system) = &request.system {
+            body["system"] = json!(system);
+        }
 
         let response = self.client
             .invoke_model()
@@ -10,16 +10,36 @@ pub use bedrock::*;
 
 use crate::error::AIError;
 use async_trait::async_trait;
-use bytes::Bytes;
-use futures_core::Stream;
+use crate::audit::RecordUsage;
+use crate::core::{ChatMessage, ChatRole, ParsedResponse, RawByteStream, ResponseItem};
+use crate::request::ModelRequest;
+use crate::streaming::{tool_call_data, TextContent};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use super::config::ClaudeConfig;
+use super::config::{ClaudeConfig, ClaudeTool};
 
 #[derive(Debug, Serialize)]
 pub struct ClaudeRequest {
     pub model: String,
     pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
     pub messages: Vec<ClaudeMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ClaudeTool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ClaudeMetadata>,
+}
+
+/// Request metadata; `user_id` identifies the end user to Anthropic's abuse detection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClaudeMetadata {
+    pub user_id: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -53,11 +73,80 @@ pub struct CacheControl {
 #[derive(Debug, Deserialize)]
 pub struct ClaudeResponse {
     pub content: Vec<ClaudeContent>,
+    /// Model version that answered, e.g. `claude-3-5-haiku-20241022`
+    #[serde(default)]
+    pub model: Option<String>,
+    /// `"refusal"` when the model declined to answer
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+    #[serde(default)]
+    pub usage: Option<ClaudeUsage>,
+}
+
+/// Tokens billed for a response; `input_tokens` excludes the cache reads and writes
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct ClaudeUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl From<ClaudeUsage> for RecordUsage {
+    fn from(usage: ClaudeUsage) -> Self {
+        RecordUsage {
+            prompt_tokens: usage.input_tokens + usage.cache_creation_input_tokens + usage.cache_read_input_tokens,
+            cached_tokens: usage.cache_read_input_tokens,
+            completion_tokens: usage.output_tokens,
+        }
+    }
 }
 
+/// A content block of a response
 #[derive(Debug, Deserialize)]
-pub struct ClaudeContent {
-    pub text: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaudeContent {
+    Text { text: String },
+    /// A native tool call, when the request carried `tools`
+    ToolUse { id: String, name: String, input: serde_json::Value },
+    /// Block types this crate does not read (e.g. `thinking`)
+    #[serde(other)]
+    Other,
+}
+
+impl ClaudeResponse {
+    /// The blocks as one string, each `tool_use` block as its `input` JSON
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.content.iter().filter_map(|block| match block {
+            ClaudeContent::Text { text } => Some(text.clone()),
+            ClaudeContent::ToolUse { input, .. } => Some(input.to_string()),
+            ClaudeContent::Other => None,
+        }).collect::<Vec<_>>().join("\n\n")
+    }
+
+    /// Parse the blocks in order: text blocks as `ParsedResponse::from_raw` does, and
+    /// `tool_use` blocks directly into `Data(T)` (see `ToolCall::to_data`), without
+    /// JSON extraction
+    pub fn parse<T>(&self) -> ParsedResponse<T>
+    where
+        T: DeserializeOwned + JsonSchema + Serialize + Clone,
+    {
+        let items = self.content.iter().flat_map(|block| match block {
+            ClaudeContent::Text { text } => ParsedResponse::<T>::from_raw(text).items,
+            ClaudeContent::ToolUse { id, name, input } => {
+                let original_text = input.to_string();
+                vec![match tool_call_data::<T>(id.clone(), name.clone(), &original_text) {
+                    Ok(data) => ResponseItem::Data { data, original_text, confidence_map: None },
+                    Err(text) => ResponseItem::Text(TextContent { text, block: None }),
+                }]
+            }
+            ClaudeContent::Other => Vec::new(),
+        }).collect();
+        ParsedResponse { items, safety: None, truncated: false, error: None, cost: None }
+    }
 }
 
 impl ClaudeRequest {
@@ -80,18 +169,106 @@ impl ClaudeRequest {
         Self {
             model: config.get_model_for_provider(),
             max_tokens: config.max_tokens,
+            temperature: config.temperature,
+            system: None,
             messages: vec![ClaudeMessage {
                 role: "user".to_string(),
                 content,
             }],
+            tools: config.tools.clone(),
+            stop_sequences: config.stop_sequences.clone(),
+            metadata: config.user_id.clone().map(|user_id| ClaudeMetadata { user_id }),
+        }.with_config_prefill(config)
+    }
+
+    /// Request for a multi-turn history; system messages are joined into the
+    /// top-level `system` field, which the Messages API takes instead of a role.
+    /// A final assistant message is sent as a prefill the reply continues from.
+    #[must_use]
+    pub fn from_messages(messages: Vec<ChatMessage>, config: &ClaudeConfig) -> Self {
+        let (system, turns): (Vec<_>, Vec<_>) = messages.into_iter().partition(|m| m.role == ChatRole::System);
+        let system = (!system.is_empty()).then(|| {
+            system.into_iter().map(|m| m.content).collect::<Vec<_>>().join("\n\n")
+        });
+        let mut messages: Vec<_> = turns.into_iter().map(|m| ClaudeMessage {
+            role: if m.role == ChatRole::Assistant { "assistant" } else { "user" }.to_string(),
+            content: ClaudeMessageContent::Simple(m.content),
+        }).collect();
+        // The API rejects a final assistant message ending in whitespace
+        if let Some(ClaudeMessage { role, content: ClaudeMessageContent::Simple(text) }) = messages.last_mut() {
+            if role == "assistant" {
+                text.truncate(text.trim_end().len());
+            }
+        }
+
+        Self {
+            model: config.get_model_for_provider(),
+            max_tokens: config.max_tokens,
+            temperature: config.temperature,
+            system,
+            messages,
+            tools: config.tools.clone(),
+            stop_sequences: config.stop_sequences.clone(),
+            metadata: config.user_id.clone().map(|user_id| ClaudeMetadata { user_id }),
+        }.with_config_prefill(config)
+    }
+
+    /// End the messages with an assistant turn holding `prefill`, which the reply then
+    /// continues, unless they already end with one. Trailing whitespace is dropped, as
+    /// the API requires.
+    #[must_use]
+    pub fn with_prefill(mut self, prefill: &str) -> Self {
+        let prefill = prefill.trim_end();
+        if self.prefill().is_none() && !prefill.is_empty() {
+            self.messages.push(ClaudeMessage {
+                role: "assistant".to_string(),
+                content: ClaudeMessageContent::Simple(prefill.to_string()),
+            });
+        }
+        self
+    }
+
+    fn with_config_prefill(self, config: &ClaudeConfig) -> Self {
+        match &config.prefill {
+            Some(prefill) => self.with_prefill(prefill),
+            None => self,
+        }
+    }
+
+    /// The final assistant message the reply will continue, if any
+    #[must_use]
+    pub fn prefill(&self) -> Option<&str> {
+        match self.messages.last()? {
+            ClaudeMessage { role, content: ClaudeMessageContent::Simple(text) } if role == "assistant" => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Request for a provider-neutral `ModelRequest`. Its parameters override the
+    /// config (the Messages API has no `seed`), its tools are sent after the configured
+    /// ones, its stop sequences and user replace the configured ones, and its response format
+    /// is left to the prompt.
+    #[must_use]
+    pub fn from_model_request(request: ModelRequest, config: &ClaudeConfig) -> Self {
+        let mut claude = Self::from_messages(request.messages, config);
+        claude.max_tokens = request.params.max_tokens.unwrap_or(claude.max_tokens);
+        claude.temperature = request.params.temperature.or(claude.temperature);
+        if !request.params.stop.is_empty() {
+            claude.stop_sequences = request.params.stop;
+        }
+        if let Some(user_id) = request.params.user {
+            claude.metadata = Some(ClaudeMetadata { user_id });
         }
+        claude.tools.extend(request.tools.into_iter().map(ClaudeTool::from));
+        claude
     }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait ClaudeProvider: Send + Sync {
     async fn call_api(&self, request: &ClaudeRequest) -> Result<String, AIError>;
-    async fn stream_api(&self, _request: &ClaudeRequest) -> Result<std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>, AIError> {
+    async fn stream_api(&self, _request: &ClaudeRequest) -> Result<RawByteStream, AIError> {
         Err(AIError::Claude(crate::error::ClaudeError::Api("Streaming not implemented for this provider".into())))
     }
 }
@@ -1,10 +1,13 @@
-use crate::core::LowLevelClient;
+use crate::core::{Capabilities, ChatMessage, LowLevelClient, RawByteStream};
+use std::any::Any;
 use crate::clients::deepseek::models::DeepSeekModel;
-use bytes::Bytes;
-use futures_core::Stream;
 use futures_util::{StreamExt, TryStreamExt};
 use crate::config::KeyFromEnv;
-use crate::error::{AIError, DeepSeekError};
+use crate::correlation::Correlated;
+use crate::refusal::choice_refusal;
+use crate::stats::{record_finish_reason, record_provenance, Provenance};
+use crate::streaming::FinishReason;
+use crate::error::{AIError, DeepSeekError, ProviderError};
 use async_trait::async_trait;
 use async_stream;
 use reqwest::Client;
@@ -14,25 +17,26 @@ use tracing::{info, warn, error, debug, instrument};
 #[derive(Debug, Serialize)]
 struct DeepSeekRequest {
     model: String,
-    messages: Vec<DeepSeekMessage>,
+    messages: Vec<ChatMessage>,
     max_tokens: u32,
     temperature: f32,
-}
-
-#[derive(Debug, Serialize)]
-struct DeepSeekMessage {
-    role: String,
-    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct DeepSeekResponse {
     choices: Vec<DeepSeekChoice>,
+    model: Option<String>,
+    system_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct DeepSeekChoice {
     message: DeepSeekResponseMessage,
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +51,8 @@ pub struct DeepSeekConfig {
     pub model: DeepSeekModel,
     pub max_tokens: u32,
     pub temperature: f32,
+    /// DeepSeek API base URL, e.g. a proxy in front of it
+    pub base_url: String,
 }
 
 impl Default for DeepSeekConfig {
@@ -56,6 +62,7 @@ impl Default for DeepSeekConfig {
             model: DeepSeekModel::default(),
             max_tokens: 4096,
             temperature: 0.3,
+            base_url: "https://api.deepseek.com".to_string(),
         }
     }
 }
@@ -64,6 +71,9 @@ impl Default for DeepSeekConfig {
 pub struct DeepSeekClient {
     config: DeepSeekConfig,
     client: Client,
+    json_mode: bool,
+    /// Sent as `stop` (see `with_stop_sequences`)
+    stop: Vec<String>,
 }
 
 impl KeyFromEnv for DeepSeekConfig {
@@ -78,6 +88,8 @@ impl Default for DeepSeekClient {
         Self {
             config,
             client: Client::new(),
+            json_mode: false,
+            stop: Vec::new(),
         }
     }
 }
@@ -90,31 +102,41 @@ impl DeepSeekClient {
         Self {
             config,
             client: Client::new(),
+            json_mode: false,
+            stop: Vec::new(),
         }
     }
-    
-}
 
-#[async_trait]
-impl LowLevelClient for DeepSeekClient {
-    #[instrument(skip(self, prompt), fields(prompt_len = prompt.len(), model = %self.config.model.id()))]
-    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
-        debug!(model = %self.config.model.id(), prompt_len = prompt.len(), "Preparing DeepSeek API request");
-        
+    /// Build a client from `DEEPSEEK_API_KEY`, returning a configuration error
+    /// if the key is missing.
+    pub fn try_default() -> Result<Self, AIError> {
+        let api_key = DeepSeekConfig::require_key()?;
+        Ok(Self::new(DeepSeekConfig { api_key, ..DeepSeekConfig::default() }))
+    }
+
+    /// The configuration this client sends requests with
+    pub fn config(&self) -> &DeepSeekConfig { &self.config }
+
+    fn completions_url(&self) -> String {
+        format!("{}/v1/chat/completions", self.config.base_url.trim_end_matches('/'))
+    }
+
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "deepseek", model = %self.config.model.id(), status = tracing::field::Empty))]
+    async fn complete(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
         let request = DeepSeekRequest {
             model: self.config.model.id().to_string(),
-            messages: vec![DeepSeekMessage {
-                role: "user".to_string(),
-                content: prompt,
-            }],
+            messages,
             max_tokens: self.config.max_tokens,
             temperature: self.config.temperature,
+            response_format: self.json_mode.then(|| serde_json::json!({ "type": "json_object" })),
+            stop: self.stop.clone(),
         };
-        
+
         debug!("Sending request to DeepSeek API");
         let response = self
             .client
-            .post("https://api.deepseek.com/v1/chat/completions")
+            .post(self.completions_url())
+            .correlated()
             .header("Authorization", format!("Bearer {}", self.config.api_key))
             .header("Content-Type", "application/json")
             .json(&request)
@@ -125,24 +147,17 @@ impl LowLevelClient for DeepSeekClient {
                 AIError::DeepSeek(DeepSeekError::Http(e.to_string()))
             })?;
             
+        tracing::Span::current().record("status", response.status().as_u16());
         debug!(status = %response.status(), "Received response from DeepSeek API");
             
-        if response.status() == 429 {
-            warn!("DeepSeek API rate limit exceeded");
-            return Err(AIError::DeepSeek(DeepSeekError::RateLimit));
-        }
-        
-        if response.status() == 401 {
-            error!("DeepSeek API authentication failed");
-            return Err(AIError::DeepSeek(DeepSeekError::Authentication));
-        }
-        
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            error!(status = %status, error = %error_text, "DeepSeek API error");
-            return Err(AIError::DeepSeek(DeepSeekError::Api(error_text)));
+            let err = ProviderError::from_response(response).await;
+            if err.status == 429 {
+                warn!(request_id = ?err.request_id, retry_after = ?err.retry_after, "DeepSeek API rate limit exceeded");
+            } else {
+                error!(status = err.status, code = ?err.code, request_id = ?err.request_id, error = %err.message, "DeepSeek API error");
+            }
+            return Err(AIError::DeepSeek(err.into()));
         }
         
         let deepseek_response: DeepSeekResponse = response
@@ -154,14 +169,27 @@ impl LowLevelClient for DeepSeekClient {
             })?;
             
         debug!(choices_count = deepseek_response.choices.len(), "Parsed DeepSeek response");
+        record_provenance(Provenance {
+            model: deepseek_response.model.clone(),
+            system_fingerprint: deepseek_response.system_fingerprint.clone(),
+            seed: None,
+        });
             
         let result = deepseek_response
             .choices
             .first()
-            .map(|choice| choice.message.content.clone())
             .ok_or_else(|| {
                 error!("No choices in DeepSeek response");
                 AIError::DeepSeek(DeepSeekError::Api("No choices in response".to_string()))
+            })
+            .inspect(|choice| {
+                if let Some(reason) = choice.finish_reason.as_deref().and_then(FinishReason::from_provider) {
+                    record_finish_reason(reason);
+                }
+            })
+            .and_then(|choice| match choice_refusal(choice.finish_reason.as_deref(), None) {
+                Some(message) => Err(AIError::Refused(message)),
+                None => Ok(choice.message.content.clone()),
             });
             
         match &result {
@@ -171,31 +199,94 @@ impl LowLevelClient for DeepSeekClient {
         
         result
     }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for DeepSeekClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        debug!(model = %self.config.model.id(), prompt_len = prompt.len(), "Preparing DeepSeek API request");
+        self.complete(vec![ChatMessage::user(prompt)]).await
+    }
+
+    fn supports_system_role(&self) -> bool { true }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        self.complete(messages).await
+    }
     
     fn clone_box(&self) -> Box<dyn LowLevelClient> {
         Box::new(self.clone())
     }
 
-    fn stream_raw(&self, prompt: String) -> Option<std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>> {
-        let body = serde_json::json!({
+    fn provider(&self) -> Option<&'static str> {
+        Some("deepseek")
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.temperature = temperature;
+        Some(Box::new(client))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.config.max_tokens)
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = max_tokens;
+        Some(Box::new(client))
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { json_mode: true, ..self.clone() }))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { stop: stop.to_vec(), ..self.clone() }))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_native_tools: self.config.model != DeepSeekModel::Reasoner,
+            supports_json_mode: true,
+            supports_system_role: true,
+            max_context_tokens: self.config.model.context_window(),
+            ..Capabilities::default()
+        }
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        let mut body = serde_json::json!({
             "model": self.config.model.id(),
             "max_tokens": self.config.max_tokens,
             "temperature": self.config.temperature,
             "stream": true,
             "messages": [{"role":"user","content": prompt}],
         });
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::json!(self.stop);
+        }
         let req = self.client
-            .post("https://api.deepseek.com/v1/chat/completions")
+            .post(self.completions_url())
+            .correlated()
             .header("Authorization", format!("Bearer {}", self.config.api_key))
             .header("Content-Type", "application/json")
             .json(&body);
         let fut = async move {
             let resp = req.send().await.map_err(|e| AIError::DeepSeek(DeepSeekError::Http(e.to_string())))?;
-            if resp.status() == 429 { return Err(AIError::DeepSeek(DeepSeekError::RateLimit)); }
-            if resp.status() == 401 { return Err(AIError::DeepSeek(DeepSeekError::Authentication)); }
             if !resp.status().is_success() {
-                let txt = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(AIError::DeepSeek(DeepSeekError::Api(txt)));
+                return Err(AIError::DeepSeek(ProviderError::from_response(resp).await.into()));
             }
             Ok(resp.bytes_stream().map(|r| r.map_err(|e| AIError::DeepSeek(DeepSeekError::Http(e.to_string())))))
         };
@@ -15,4 +15,13 @@ impl DeepSeekModel {
             Self::Override(s) => s.as_str(),
         }
     }
+
+    /// Context window in tokens; unknown for `Override`
+    #[must_use]
+    pub fn context_window(&self) -> Option<u32> {
+        match self {
+            Self::Chat | Self::Reasoner => Some(65_536),
+            Self::Override(_) => None,
+        }
+    }
 }
@@ -1,80 +1,70 @@
-use crate::clients::claude::ClaudeConfig;
+#[cfg(feature = "openai")]
+use crate::clients::chatgpt::OpenAIClient;
+#[cfg(feature = "anthropic")]
+use crate::clients::claude::{ClaudeClient, ClaudeConfig};
+#[cfg(feature = "deepseek")]
 use crate::clients::deepseek::DeepSeekConfig;
-use crate::core::{LowLevelClient};
+use crate::audit::QueryRecord;
+use crate::core::{render_transcript, Capabilities, ChatMessage, LowLevelClient, RawByteStream};
+use crate::grammar::OutputConstraint;
+use crate::request::ModelRequest;
+#[cfg(feature = "ollama")]
+use crate::clients::ollama::OllamaConfig;
+#[cfg(feature = "openai-compatible")]
+use crate::clients::openai_compatible::CompatConfig;
 use bytes::Bytes;
 use futures_util::StreamExt;
+use crate::config::SemanticQueryConfig;
+use crate::secrets::SecretProvider;
 use crate::error::{AIError};
-use crate::interceptors::{FileInterceptor, Interceptor};
+use crate::interceptors::policy::Exchange;
+use crate::interceptors::stack::ExchangeContext;
+use crate::interceptors::{GuardedInterceptor, Interceptor, InterceptorStack};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::interceptors::FileInterceptor;
 use async_trait::async_trait;
+use std::any::Any;
 use std::env;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, OnceLock};
+use arc_swap::ArcSwap;
 use tokio_util::io::StreamReader;
 use tokio::io::AsyncRead;
 use std::pin::Pin;
 use std::str::FromStr;
 
 
-/// Client type for lazy initialization
+/// Client type for lazy initialization. Provider variants exist only when their
+/// feature is enabled; `Mock` is always available.
 #[derive(Debug, Clone)]
 pub enum ClientType {
+    #[cfg(feature = "anthropic")]
     Claude,
+    #[cfg(feature = "deepseek")]
     DeepSeek,
+    /// OpenAI, or Azure OpenAI when an Azure endpoint is configured
+    #[cfg(any(feature = "openai", feature = "azure"))]
     ChatGPT,
+    /// Any OpenAI-compatible endpoint, configured via `OPENAI_COMPAT_BASE_URL`
+    #[cfg(feature = "openai-compatible")]
+    OpenAICompatible,
     Mock,
 }
 
-impl From<ClientType> for Box<dyn LowLevelClient> {
-    fn from(val: ClientType) -> Self {
-        match val {
-            ClientType::Claude => {
-                use super::claude::ClaudeClient;
-                Box::new(ClaudeClient::default())
-            }
-            ClientType::DeepSeek => {
-                use super::deepseek::DeepSeekClient;
-                Box::new(DeepSeekClient::default())
-            }
-            ClientType::ChatGPT => {
-                // Prefer Azure OpenAI if endpoint/key present; else plain OpenAI
-                let use_azure = env::var("AZURE_OPENAI_ENDPOINT").is_ok() ||
-                    std::fs::read_to_string(".env").map_or(false, |c| c.contains("AZURE_OPENAI_ENDPOINT"));
-                if use_azure {
-                    use super::chatgpt::AzureOpenAIClient;
-                    Box::new(AzureOpenAIClient::new(super::chatgpt::AzureOpenAIConfig::default()))
-                } else {
-                    use super::chatgpt::OpenAIClient;
-                    Box::new(OpenAIClient::new(super::chatgpt::OpenAIConfig::default()))
-                }
-            }
-            ClientType::Mock => {
-                // Note: This creates a mock without a controllable handle
-                // Use FlexibleClient::new_mock() if you need to control the mock
-                use super::mock::MockClient;
-                let (mock_client, _handle) = MockClient::new();
-                // The handle is dropped here, making this mock uncontrollable
-                Box::new(mock_client)
-            }
-        }
+impl TryFrom<ClientType> for Box<dyn LowLevelClient> {
+    type Error = AIError;
+
+    /// Build the provider client from `config::load()`, surfacing
+    /// `AIError::Configuration` when the required API keys are missing instead of panicking.
+    fn try_from(val: ClientType) -> Result<Self, Self::Error> {
+        val.build_with(&crate::config::load()?)
     }
 }
 
 impl Default for ClientType {
-      /// Get the default client type based on available API keys
-      fn default() -> Self {
-        // Check for API keys in order of preference
-        if env::var("ANTHROPIC_API_KEY").is_ok() || 
-           std::fs::read_to_string(".env").map_or(false, |content| content.contains("ANTHROPIC_API_KEY")) {
-            Self::Claude
-        } else if env::var("DEEPSEEK_API_KEY").is_ok() || 
-                 std::fs::read_to_string(".env").map_or(false, |content| content.contains("DEEPSEEK_API_KEY")) {
-            Self::DeepSeek
-        } else if env::var("OPENAI_API_KEY").is_ok() || 
-                 std::fs::read_to_string(".env").map_or(false, |content| content.contains("OPENAI_API_KEY")) {
-            Self::ChatGPT
-        } else {
-            Self::Mock
-        }
+    /// Get the default client type from the config file / environment
+    fn default() -> Self {
+        Self::from_config(&crate::config::load().unwrap_or_default())
     }
 }
 impl FromStr for ClientType {
@@ -82,17 +72,151 @@ impl FromStr for ClientType {
     
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
+            #[cfg(feature = "anthropic")]
             "claude" => Ok(Self::Claude),
+            #[cfg(feature = "deepseek")]
             "deepseek" => Ok(Self::DeepSeek),
+            #[cfg(any(feature = "openai", feature = "azure"))]
             "openai" | "chatgpt" => Ok(Self::ChatGPT),
+            #[cfg(feature = "openai-compatible")]
+            "compat" | "openai-compatible" | "openai_compatible" | "openaicompatible" => Ok(Self::OpenAICompatible),
             "mock" => Ok(Self::Mock),
-            _ => Err(format!("Unknown client type: '{s}'. Supported: claude, deepseek, mock"))
+            _ => Err(format!("Unknown client type: '{s}'. Supported: {}", Self::supported().join(", ")))
         }
     }
 }
 
 impl ClientType {
-    
+    /// Environment variable consulted by `FlexibleClient::lazy()` to pick a provider
+    pub const ENV_VAR: &'static str = "SEMANTIC_QUERY_CLIENT";
+
+    /// Names `from_str` accepts in this build, one per enabled provider
+    #[must_use]
+    pub fn supported() -> Vec<&'static str> {
+        let mut names = Vec::new();
+        #[cfg(feature = "anthropic")]
+        names.push("claude");
+        #[cfg(feature = "deepseek")]
+        names.push("deepseek");
+        #[cfg(any(feature = "openai", feature = "azure"))]
+        names.push("openai");
+        #[cfg(feature = "openai-compatible")]
+        names.push("compat");
+        names.push("mock");
+        names
+    }
+
+    /// This type's name in `supported()`
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "anthropic")]
+            Self::Claude => "claude",
+            #[cfg(feature = "deepseek")]
+            Self::DeepSeek => "deepseek",
+            #[cfg(any(feature = "openai", feature = "azure"))]
+            Self::ChatGPT => "openai",
+            #[cfg(feature = "openai-compatible")]
+            Self::OpenAICompatible => "compat",
+            Self::Mock => "mock",
+        }
+    }
+
+    /// Resolve the client type from `SEMANTIC_QUERY_CLIENT`, falling back to the
+    /// config file and key detection
+    #[must_use]
+    pub fn from_env() -> Self {
+        env::var(Self::ENV_VAR)
+            .ok()
+            .and_then(|s| Self::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Pick a client type from loaded configuration: an explicit `default_provider`
+    /// wins, otherwise the first provider with a key in order of preference.
+    #[must_use]
+    pub fn from_config(config: &SemanticQueryConfig) -> Self {
+        if let Some(explicit) = config.default_provider.as_deref().and_then(|s| Self::from_str(s).ok()) {
+            return explicit;
+        }
+        #[cfg(feature = "anthropic")]
+        if config.anthropic.api_key.is_some() {
+            return Self::Claude;
+        }
+        #[cfg(feature = "deepseek")]
+        if config.deepseek.api_key.is_some() {
+            return Self::DeepSeek;
+        }
+        #[cfg(any(feature = "openai", feature = "azure"))]
+        if config.openai.api_key.is_some() || config.azure.api_key.is_some() {
+            return Self::ChatGPT;
+        }
+        #[cfg(feature = "openai-compatible")]
+        if config.openai_compatible.endpoint.is_some() {
+            return Self::OpenAICompatible;
+        }
+        Self::Mock
+    }
+
+    /// Build the boxed client, returning a configuration error instead of panicking
+    /// when the provider's API key is missing.
+    pub fn build(&self) -> Result<Box<dyn LowLevelClient>, AIError> {
+        self.clone().try_into()
+    }
+
+    /// Build the boxed client from an already-loaded configuration
+    #[cfg_attr(
+        not(any(feature = "anthropic", feature = "deepseek", feature = "openai", feature = "azure", feature = "openai-compatible")),
+        allow(unused_variables)
+    )]
+    pub fn build_with(&self, config: &SemanticQueryConfig) -> Result<Box<dyn LowLevelClient>, AIError> {
+        let client: Box<dyn LowLevelClient> = match self {
+            #[cfg(feature = "anthropic")]
+            ClientType::Claude => Box::new(ClaudeClient::new(config.claude_config()?)),
+            #[cfg(feature = "deepseek")]
+            ClientType::DeepSeek => {
+                use super::deepseek::DeepSeekClient;
+                Box::new(DeepSeekClient::new(config.deepseek_config()?))
+            }
+            #[cfg(any(feature = "openai", feature = "azure"))]
+            ClientType::ChatGPT => Self::chatgpt_client(config)?,
+            #[cfg(feature = "openai-compatible")]
+            ClientType::OpenAICompatible => {
+                use super::openai_compatible::CompatClient;
+                Box::new(CompatClient::new(config.compat_config()?))
+            }
+            ClientType::Mock => {
+                // Note: This creates a mock without a controllable handle
+                // Use FlexibleClient::mock() if you need to control the mock
+                use super::mock::MockClient;
+                let (mock_client, _handle) = MockClient::new();
+                // The handle is dropped here, making this mock uncontrollable
+                Box::new(mock_client)
+            }
+        };
+        Ok(client)
+    }
+
+    /// Prefer Azure OpenAI if an endpoint is configured (and the `azure` feature is
+    /// enabled); else plain OpenAI
+    #[cfg(any(feature = "openai", feature = "azure"))]
+    fn chatgpt_client(config: &SemanticQueryConfig) -> Result<Box<dyn LowLevelClient>, AIError> {
+        #[cfg(feature = "azure")]
+        if config.prefers_azure() || cfg!(not(feature = "openai")) {
+            use super::chatgpt::AzureOpenAIClient;
+            return Ok(Box::new(AzureOpenAIClient::new(config.azure_config()?)));
+        }
+        #[cfg(feature = "openai")]
+        {
+            use super::chatgpt::OpenAIClient;
+            Ok(Box::new(OpenAIClient::new(config.openai_config()?)))
+        }
+        #[cfg(not(feature = "openai"))]
+        {
+            unreachable!("without the `openai` feature every ChatGPT client is Azure")
+        }
+    }
+
     /// Create a mock variant that returns both the client type and a handle
     #[must_use]
     pub fn mock_with_handle() -> (Self, Arc<super::mock::MockHandle>) {
@@ -106,99 +230,378 @@ impl ClientType {
 impl std::fmt::Display for ClientType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            #[cfg(feature = "anthropic")]
             ClientType::Claude => write!(f, "Claude"),
+            #[cfg(feature = "deepseek")]
             ClientType::DeepSeek => write!(f, "DeepSeek"),
+            #[cfg(any(feature = "openai", feature = "azure"))]
             ClientType::ChatGPT => write!(f, "ChatGPT"),
+            #[cfg(feature = "openai-compatible")]
+            ClientType::OpenAICompatible => write!(f, "OpenAICompatible"),
             ClientType::Mock => write!(f, "Mock"),
         }
     }
 }
 
 
+/// Client that defers construction of the provider until first use.
+///
+/// Construction errors (e.g. missing API keys) surface as `AIError` from the first
+/// call rather than at startup. Once built, the client is cached for all clones.
+#[derive(Debug, Clone)]
+pub struct LazyClient {
+    client_type: ClientType,
+    cell: Arc<OnceLock<Box<dyn LowLevelClient>>>,
+}
+
+impl LazyClient {
+    #[must_use]
+    pub fn new(client_type: ClientType) -> Self {
+        Self { client_type, cell: Arc::new(OnceLock::new()) }
+    }
+
+    /// The provider this client will build on first use
+    pub fn client_type(&self) -> &ClientType {
+        &self.client_type
+    }
+
+    /// Whether the underlying client has been constructed yet
+    pub fn is_initialized(&self) -> bool {
+        self.cell.get().is_some()
+    }
+
+    fn get(&self) -> Result<&dyn LowLevelClient, AIError> {
+        if let Some(client) = self.cell.get() {
+            return Ok(client.as_ref());
+        }
+        let built = self.client_type.build()?;
+        // A concurrent initializer may have won the race; either value is equivalent
+        let _ = self.cell.set(built);
+        Ok(self.cell.get().expect("cell initialized above").as_ref())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for LazyClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        let client = self.get()?.clone_box();
+        client.ask_raw(prompt).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> {
+        Box::new(self.clone())
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        match self.get() {
+            Ok(client) => client.stream_raw(prompt),
+            Err(e) => Some(Box::pin(futures_util::stream::once(async move { Err(e) }))),
+        }
+    }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_temperature(temperature)
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_seed(seed)
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.get().ok()?.max_tokens()
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_max_tokens(max_tokens)
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_json_mode()
+    }
+
+    fn with_prefill(&self, prefill: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_prefill(prefill)
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_stop_sequences(stop)
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_user(user)
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.get().ok()?.with_logprobs()
+    }
+
+    fn supports_grammar(&self) -> bool {
+        self.get().map(|c| c.supports_grammar()).unwrap_or(false)
+    }
+
+    async fn ask_raw_constrained(&self, prompt: String, constraint: &OutputConstraint) -> Result<String, AIError> {
+        let client = self.get()?.clone_box();
+        client.ask_raw_constrained(prompt, constraint).await
+    }
+
+    fn supports_system_role(&self) -> bool {
+        self.get().map(|c| c.supports_system_role()).unwrap_or(false)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.get().map(|c| c.capabilities()).unwrap_or_default()
+    }
+
+    /// Known before the client is built
+    fn provider(&self) -> Option<&'static str> {
+        self.cell.get().and_then(|c| c.provider()).or(Some(self.client_type.name()))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.get().ok()?.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        // Detach from clones sharing the cell so changes only affect this client
+        let client = self.get().ok()?.clone_box();
+        self.cell = Arc::new(OnceLock::from(client));
+        Arc::get_mut(&mut self.cell)?.get_mut()?.as_any_mut()
+    }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        let client = self.get()?.clone_box();
+        client.ask_messages(messages).await
+    }
+
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        let client = self.get()?.clone_box();
+        client.ask_request(request).await
+    }
+
+    async fn record_query(&self, record: &QueryRecord) {
+        // No client has been built if the query never reached one
+        if let Some(client) = self.cell.get() {
+            client.record_query(record).await
+        }
+    }
+
+    async fn flush(&self) -> Result<(), String> {
+        match self.cell.get() {
+            Some(client) => client.flush().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reader over raw model output returned by `FlexibleClient::stream_raw_reader`
+#[cfg(not(target_arch = "wasm32"))]
+pub type RawReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Reader over raw model output returned by `FlexibleClient::stream_raw_reader`
+#[cfg(target_arch = "wasm32")]
+pub type RawReader = Pin<Box<dyn AsyncRead>>;
+
+/// Process-wide client used by `FlexibleClient::lazy()`
+static GLOBAL_LAZY: OnceLock<FlexibleClient> = OnceLock::new();
+
 #[derive(Debug)]
-/// Flexible client that wraps any `LowLevelClient` and provides factory functions
+/// Flexible client that wraps any `LowLevelClient` and provides factory functions.
+///
+/// The wrapped client lives behind an `ArcSwap`, so it can be replaced at runtime
+/// with `swap`/`swap_with`. Clones share the same slot: swapping on one handle is
+/// observed by every resolver built from it.
 pub struct FlexibleClient {
-    inner: Arc<Mutex<Box<dyn LowLevelClient>>>,
-    interceptor: Option<Arc<dyn Interceptor>>,
+    inner: Arc<ArcSwap<Box<dyn LowLevelClient>>>,
+    interceptors: InterceptorStack,
 }
 
 
 impl FlexibleClient {
-    /// Create a new `FlexibleClient` from a client type (lazy-initialized boxed impl)
+    /// Create a new `FlexibleClient` from a client type (lazy-initialized boxed impl).
+    ///
+    /// Never panics: configuration problems are reported by the first request.
+    /// Use `try_from_type` to validate eagerly.
     #[must_use]
     pub fn from_type(client_type: ClientType) -> Self {
-       
-        Self { 
-            inner: Arc::new(Mutex::new(client_type.into())),
-            interceptor: None,
-        }
+        Self::from_type_lazy(client_type)
+    }
+
+    /// Create a new `FlexibleClient` from a client type, building it immediately
+    pub fn try_from_type(client_type: ClientType) -> Result<Self, AIError> {
+        Ok(Self::new(client_type.build()?))
     }
     
+    /// Create a `FlexibleClient` whose provider is only constructed on first use.
+    /// Missing keys are reported as `AIError::Configuration` from that first call.
+    #[must_use]
+    pub fn from_type_lazy(client_type: ClientType) -> Self {
+        Self::new(Box::new(LazyClient::new(client_type)))
+    }
+
+    /// Global lazily-initialized client shared across the process.
+    ///
+    /// The provider is chosen from `SEMANTIC_QUERY_CLIENT` (falling back to key
+    /// detection) the first time this is called, and the client itself is built on
+    /// the first request.
+    pub fn lazy() -> &'static FlexibleClient {
+        GLOBAL_LAZY.get_or_init(|| Self::from_type_lazy(ClientType::from_env()))
+    }
+
     /// Create a new `FlexibleClient` wrapping the given client
     #[must_use]
     pub fn new(client: Box<dyn LowLevelClient>) -> Self {
         Self { 
-            inner: Arc::new(Mutex::new(client)),
-            interceptor: None,
+            inner: Arc::new(ArcSwap::from_pointee(client)),
+            interceptors: InterceptorStack::new(),
         }
     }
     
-    /// Create a new `FlexibleClient` with an interceptor
+    /// Create a new `FlexibleClient` with an interceptor under the default error
+    /// policy (`InterceptorErrorPolicy::WarnOnce`)
     #[must_use]
     pub fn with_interceptor(&self, interceptor: Arc<dyn Interceptor>) -> Self {
+        self.with_guarded_interceptor(GuardedInterceptor::new(interceptor))
+    }
+
+    /// Create a new `FlexibleClient` with an interceptor and its own error policy
+    #[must_use]
+    pub fn with_guarded_interceptor(&self, interceptor: GuardedInterceptor) -> Self {
+        self.with_interceptors(InterceptorStack::new().push_guarded(interceptor))
+    }
+
+    /// Create a new `FlexibleClient` with a chain of interceptors, replacing any it had
+    #[must_use]
+    pub fn with_interceptors(&self, interceptors: InterceptorStack) -> Self {
         Self {
             inner: self.inner.clone(),
-            interceptor: Some(interceptor),
+            interceptors,
         }
     }
+
+    /// The interceptors exchanges are handed to
+    pub fn interceptors(&self) -> &InterceptorStack {
+        &self.interceptors
+    }
     
-       /// Create a new `FlexibleClient` with an interceptor
-       #[must_use]
-       pub fn with_file_interceptor(&self, path: PathBuf) -> Self {
-        Self {
-            inner: self.inner.clone(),
-            interceptor: Some(Arc::new(FileInterceptor::new(path))),
-        }
+    /// Create a new `FlexibleClient` that writes each exchange to a file under `path`
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_file_interceptor(&self, path: PathBuf) -> Self {
+        self.with_interceptor(Arc::new(FileInterceptor::new(path)))
     }
     /// Create a `FlexibleClient` with a Claude client (explicit config)
+    #[cfg(feature = "anthropic")]
     #[must_use]
     pub fn claude_with(config: ClaudeConfig) -> Self {
-        use super::claude::ClaudeClient;
         Self::new(Box::new(ClaudeClient::new(config)))
     }
 
-    /// Create a `FlexibleClient` with a Claude client using default config from env
+    /// Create a `FlexibleClient` with a Claude client configured by `config::load()`.
+    ///
+    /// Without an Anthropic API key the client is unauthenticated and every request
+    /// fails; `ClientType::Claude.build()` reports the missing key up front instead.
+    #[cfg(feature = "anthropic")]
     #[must_use]
     pub fn claude() -> Self {
-        use super::claude::ClaudeClient;
-        Self::new(Box::new(ClaudeClient::default()))
+        let client = crate::config::load()
+            .and_then(|config| config.claude_config())
+            .map_or_else(|_| ClaudeClient::default(), ClaudeClient::new);
+        Self::new(Box::new(client))
     }
     
     /// Create a `FlexibleClient` with a `DeepSeek` client (explicit config)
+    #[cfg(feature = "deepseek")]
     #[must_use]
     pub fn deepseek_with(config: DeepSeekConfig) -> Self {
         use super::deepseek::DeepSeekClient;
         Self::new(Box::new(DeepSeekClient::new(config)))
     }
 
-    /// Create a `FlexibleClient` with a `DeepSeek` client using default config from env
+    /// Create a `FlexibleClient` with a `DeepSeek` client configured by `config::load()`.
+    ///
+    /// Without a `DeepSeek` API key the client is unauthenticated and every request
+    /// fails; `ClientType::DeepSeek.build()` reports the missing key up front instead.
+    #[cfg(feature = "deepseek")]
     #[must_use]
     pub fn deepseek() -> Self {
         use super::deepseek::DeepSeekClient;
-        Self::new(Box::new(DeepSeekClient::default()))
+        let client = crate::config::load()
+            .and_then(|config| config.deepseek_config())
+            .map_or_else(|_| DeepSeekClient::default(), DeepSeekClient::new);
+        Self::new(Box::new(client))
+    }
+
+    /// Create a `FlexibleClient` with a local Ollama / llama.cpp client (explicit config)
+    #[cfg(feature = "ollama")]
+    #[must_use]
+    pub fn ollama_with(config: OllamaConfig) -> Self {
+        use super::ollama::OllamaClient;
+        Self::new(Box::new(OllamaClient::new(config)))
+    }
+
+    /// Create a `FlexibleClient` with a local Ollama client using `OLLAMA_HOST` / `OLLAMA_MODEL`
+    #[cfg(feature = "ollama")]
+    #[must_use]
+    pub fn ollama() -> Self {
+        use super::ollama::OllamaClient;
+        Self::new(Box::new(OllamaClient::default()))
+    }
+
+    /// Create a `FlexibleClient` for an OpenAI-compatible endpoint (explicit config)
+    #[cfg(feature = "openai-compatible")]
+    #[must_use]
+    pub fn openai_compatible_with(config: CompatConfig) -> Self {
+        use super::openai_compatible::CompatClient;
+        Self::new(Box::new(CompatClient::new(config)))
+    }
+
+    /// Create a `FlexibleClient` for the endpoint in `OPENAI_COMPAT_BASE_URL`, failing if it is unset
+    #[cfg(feature = "openai-compatible")]
+    pub fn try_openai_compatible() -> Result<Self, AIError> {
+        use super::openai_compatible::CompatClient;
+        Ok(Self::new(Box::new(CompatClient::try_default()?)))
     }
 
     /// Create a `FlexibleClient` with a ChatGPT-family client (OpenAI/Azure) based on env
+    #[cfg(any(feature = "openai", feature = "azure"))]
     #[must_use]
     pub fn chatgpt() -> Self {
         // Reuse the same selection logic as in ClientType::ChatGPT
-        Self::new(ClientType::ChatGPT.into())
+        Self::from_type(ClientType::ChatGPT)
     }
 
-    /// Create a `FlexibleClient` by auto-selecting provider based on available env keys
+    /// Create a `FlexibleClient` by auto-selecting provider based on `semantic-query.toml`
+    /// and available env keys
     #[must_use]
     pub fn auto() -> Self {
-        Self::new(ClientType::default().into())
+        Self::from_type(ClientType::default())
+    }
+
+    /// Create a `FlexibleClient` from an already-loaded configuration
+    pub fn from_config(config: &SemanticQueryConfig) -> Result<Self, AIError> {
+        Ok(Self::new(ClientType::from_config(config).build_with(config)?))
+    }
+
+    /// Create a `FlexibleClient` whose API key is resolved from a secret store
+    pub async fn from_secrets(client_type: ClientType, secrets: &dyn SecretProvider) -> Result<Self, AIError> {
+        let mut config = crate::config::load()?;
+        config.resolve_secrets(secrets).await?;
+        Ok(Self::new(client_type.build_with(&config)?))
+    }
+
+    /// Re-fetch keys from the secret store and swap in a freshly built client.
+    /// Clones of this client pick up the new credentials without being rebuilt.
+    pub async fn rotate_keys(&self, client_type: ClientType, secrets: &dyn SecretProvider) -> Result<(), AIError> {
+        let mut config = crate::config::load()?;
+        config.resolve_secrets(secrets).await?;
+        self.swap_with(client_type.build_with(&config)?);
+        Ok(())
+    }
+
+    /// Like `auto`, but fails fast if the selected provider is missing its keys
+    pub fn try_auto() -> Result<Self, AIError> {
+        Self::try_from_type(ClientType::default())
     }
     
     
@@ -218,21 +621,76 @@ impl FlexibleClient {
         (flexible, handle)
     }
     
+    /// Atomically replace the underlying client with one built from `client_type`.
+    ///
+    /// In-flight requests keep using the client they started with; subsequent calls
+    /// (from this handle or any clone of it) use the new one.
+    ///
+    /// Fails (leaving the current client in place) if the new provider can't be built.
+    pub fn swap(&self, client_type: ClientType) -> Result<(), AIError> {
+        self.swap_with(client_type.build()?);
+        Ok(())
+    }
+
+    /// Atomically replace the underlying client with the given boxed client.
+    pub fn swap_with(&self, client: Box<dyn LowLevelClient>) {
+        self.inner.store(Arc::new(client));
+    }
+
+    /// Snapshot of the currently installed client
+    fn current(&self) -> Box<dyn LowLevelClient> {
+        self.inner.load().as_ref().clone_box()
+    }
+
+    /// Snapshot of the current client for downcasting to a concrete provider.
+    /// Later `swap`/`configure` calls don't affect the snapshot.
+    pub fn as_any(&self) -> ClientSnapshot {
+        ClientSnapshot(self.inner.load_full())
+    }
+
+    /// Copy of the current client if it is a `ClaudeClient`
+    #[cfg(feature = "anthropic")]
+    pub fn as_claude(&self) -> Option<ClaudeClient> {
+        self.as_any().downcast_ref::<ClaudeClient>().cloned()
+    }
+
+    /// Copy of the current client if it is an `OpenAIClient`
+    #[cfg(feature = "openai")]
+    pub fn as_openai(&self) -> Option<OpenAIClient> {
+        self.as_any().downcast_ref::<OpenAIClient>().cloned()
+    }
+
+    /// Modify the current client in place if it is a `C`, e.g. to change model or
+    /// temperature without rebuilding it. Returns whether `f` ran.
+    ///
+    /// The change is visible to every clone of this handle, like `swap_with`. A `swap`
+    /// racing with `configure` may be overwritten.
+    pub fn configure<C: LowLevelClient + 'static>(&self, f: impl FnOnce(&mut C)) -> bool {
+        let mut client = self.current();
+        let Some(concrete) = client.as_any_mut().and_then(|any| any.downcast_mut::<C>()) else {
+            return false;
+        };
+        f(concrete);
+        self.swap_with(client);
+        true
+    }
+
     /// Convert into the inner boxed client (initializes if needed)
     pub fn into_inner(self) -> Result<Box<dyn LowLevelClient>, AIError> {
-        let inner = self.inner.lock().unwrap().clone_box();
-        Ok(inner)
+        Ok(self.current())
+    }
+
+    /// `stream_raw_reader` as a futures-io `AsyncRead`, for async-std and smol applications
+    pub fn stream_raw_futures_reader(&self, prompt: String) -> crate::runtime::compat::Compat<RawReader> {
+        crate::runtime::compat::into_futures_read(self.stream_raw_reader(prompt))
     }
 
     /// Get a streaming reader for the raw model output.
     /// If the underlying client does not support true streaming, this will
     /// fallback to a one-shot response written into a duplex stream.
-    pub fn stream_raw_reader(&self, prompt: String) -> Pin<Box<dyn AsyncRead + Send>> {
+    pub fn stream_raw_reader(&self, prompt: String) -> RawReader {
         // Try streaming first
-        let client = {
-            let inner = self.inner.lock().unwrap();
-            inner.as_ref().clone_box()
-        };
+        let client = self.current();
         if let Some(stream) = client.stream_raw(prompt.clone()) {
             // Map AIError to io::Error
             let io_stream = stream.map(|res| match res {
@@ -245,7 +703,7 @@ impl FlexibleClient {
 
         // Fallback: one-shot ask_raw() written to a duplex
         let (mut tx, rx) = tokio::io::duplex(8 * 1024);
-        tokio::spawn(async move {
+        crate::runtime::spawn(async move {
             if let Ok(text) = client.ask_raw(prompt).await {
                 use tokio::io::AsyncWriteExt;
                 let _ = tx.write_all(text.as_bytes()).await;
@@ -255,48 +713,192 @@ impl FlexibleClient {
     }
 }
 
+/// Client installed in a `FlexibleClient` at the time of `FlexibleClient::as_any`
+#[derive(Debug, Clone)]
+pub struct ClientSnapshot(Arc<Box<dyn LowLevelClient>>);
+
+impl ClientSnapshot {
+    /// The client as `Any`, or None if it doesn't support downcasting
+    pub fn as_any(&self) -> Option<&dyn Any> {
+        self.0.as_any()
+    }
+
+    /// The client as a `C`, if that is its concrete type
+    pub fn downcast_ref<C: 'static>(&self) -> Option<&C> {
+        self.as_any()?.downcast_ref::<C>()
+    }
+
+    /// Whether the client is a `C`
+    pub fn is<C: 'static>(&self) -> bool {
+        self.downcast_ref::<C>().is_some()
+    }
+}
+
+impl FlexibleClient {
+    /// Hand a reply, or the provider's error, to the interceptors. Fails only under
+    /// `FailRequest`; the provider's error wins over an interceptor's.
+    async fn intercept_reply(&self, prompt: String, reply: Result<String, AIError>) -> Result<String, AIError> {
+        if self.interceptors.is_empty() {
+            return reply;
+        }
+        let context = ExchangeContext { provider: self.provider(), streaming: false };
+        match reply {
+            Ok(response) => {
+                self.interceptors.deliver(context, Exchange::Prompt { prompt, response: response.clone() }).await?;
+                Ok(response)
+            }
+            Err(e) => {
+                let _ = self.interceptors.deliver(context, Exchange::Error { prompt, error: e.to_string() }).await;
+                Err(e)
+            }
+        }
+    }
+}
+
 impl Clone for FlexibleClient {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
-            interceptor: self.interceptor.clone(),
+            interceptors: self.interceptors.clone(),
         }
     }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl LowLevelClient for FlexibleClient {
     async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
         
-        // Clone the client to avoid holding the mutex across await
-        let client = {
-            let inner = self.inner.lock().unwrap();
-            inner.as_ref().clone_box()
-        };
-        
-        let response = client.ask_raw(prompt.clone()).await?;
+        // Snapshot the client so a concurrent swap doesn't affect this request
+        let client = self.current();
         
-        // Save to interceptor if present
-        if let Some(interceptor) = &self.interceptor {
-            if let Err(e) = interceptor.save(&prompt, &response).await {
-                // Log error but don't fail the request
-                eprintln!("Interceptor save failed: {}", e);
-            }
-        }
-        
-        Ok(response)
+        let reply = client.ask_raw(prompt.clone()).await;
+        self.intercept_reply(prompt, reply).await
     }
     
     fn clone_box(&self) -> Box<dyn LowLevelClient> {
         Box::new(self.clone())
     }
 
-    fn stream_raw(&self, prompt: String) -> Option<Pin<Box<dyn futures_core::stream::Stream<Item = Result<Bytes, AIError>> + Send>>> {
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
         // Delegate to underlying client's streaming capability
-        let client = {
-            let inner = self.inner.lock().unwrap();
-            inner.as_ref().clone_box()
-        };
-        client.stream_raw(prompt)
+        if self.interceptors.is_empty() {
+            return self.current().stream_raw(prompt);
+        }
+        let stream = self.current().stream_raw(prompt.clone())?;
+        let context = ExchangeContext { provider: self.provider(), streaming: true };
+        Some(self.interceptors.tap_stream(context, prompt, stream))
+    }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        // Keep the interceptors; the variant is detached from future swaps
+        let client = self.current().with_temperature(temperature)?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_seed(seed)?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.current().max_tokens()
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_max_tokens(max_tokens)?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_json_mode()?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn with_prefill(&self, prefill: &str) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_prefill(prefill)?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_stop_sequences(stop)?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_user(user)?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        let client = self.current().with_logprobs()?;
+        Some(Box::new(FlexibleClient { interceptors: self.interceptors.clone(), ..FlexibleClient::new(client) }))
+    }
+
+    fn supports_grammar(&self) -> bool {
+        self.current().supports_grammar()
+    }
+
+    async fn ask_raw_constrained(&self, prompt: String, constraint: &OutputConstraint) -> Result<String, AIError> {
+        let client = self.current();
+        let reply = client.ask_raw_constrained(prompt.clone(), constraint).await;
+        self.intercept_reply(prompt, reply).await
+    }
+
+    fn supports_system_role(&self) -> bool {
+        self.current().supports_system_role()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.current().capabilities()
+    }
+
+    fn provider(&self) -> Option<&'static str> {
+        self.current().provider()
+    }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        let client = self.current();
+        // Interceptors key on a single prompt; record the flattened history
+        let transcript = (!self.interceptors.is_empty()).then(|| render_transcript(&messages));
+        let reply = client.ask_messages(messages).await;
+        match transcript {
+            Some(transcript) => self.intercept_reply(transcript, reply).await,
+            None => reply,
+        }
+    }
+
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        let client = self.current();
+        let recorded = (!self.interceptors.is_empty()).then(|| request.clone());
+        let reply = client.ask_request(request).await;
+        let Some(request) = recorded else { return reply };
+        let context = ExchangeContext { provider: self.provider(), streaming: false };
+        match reply {
+            Ok(response) => {
+                self.interceptors.deliver(context, Exchange::Request { request, response: response.clone() }).await?;
+                Ok(response)
+            }
+            Err(e) => {
+                let exchange = Exchange::Error { prompt: render_transcript(&request.messages), error: e.to_string() };
+                let _ = self.interceptors.deliver(context, exchange).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn record_query(&self, record: &QueryRecord) {
+        self.current().record_query(record).await;
+        if !self.interceptors.is_empty() {
+            let context = ExchangeContext { provider: self.provider(), streaming: false };
+            if let Err(e) = self.interceptors.deliver(context, Exchange::Record(record.clone())).await {
+                tracing::warn!(error = %e, "Interceptor could not save the query record");
+            }
+        }
+    }
+
+    async fn flush(&self) -> Result<(), String> {
+        self.current().flush().await?;
+        self.interceptors.flush().await
     }
 }
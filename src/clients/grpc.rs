@@ -0,0 +1,286 @@
+//! gRPC client for in-house model servers.
+//!
+//! The server implements a small text-generation protocol; the messages are defined
+//! here with `prost`, so no `protoc` step is needed on the client side:
+//!
+//! ```proto
+//! syntax = "proto3";
+//! package semantic_query.v1;
+//!
+//! service TextGeneration {
+//!   rpc Generate(GenerateRequest) returns (GenerateReply);
+//!   rpc GenerateStream(GenerateRequest) returns (stream GenerateReply);
+//! }
+//!
+//! message GenerateRequest {
+//!   string prompt = 1;
+//!   string model = 2;
+//!   optional float temperature = 3;
+//!   optional uint32 max_tokens = 4;
+//!   repeated string stop = 5;
+//!   optional uint64 seed = 6;
+//! }
+//!
+//! // A whole reply from Generate, or one chunk of it from GenerateStream
+//! message GenerateReply {
+//!   string text = 1;
+//!   // "stop", "length", ...; empty until the last chunk
+//!   string finish_reason = 2;
+//! }
+//! ```
+//!
+//! Servers may use another package or service name (`GrpcConfig::service`) as long as
+//! the messages match. Streamed replies feed the same pipeline as SSE providers.
+
+use std::any::Any;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{MetadataKey, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tracing::{debug, error, info, instrument};
+
+use crate::core::{Capabilities, LowLevelClient, RawByteStream};
+use crate::correlation;
+use crate::error::{AIError, GrpcError};
+use crate::stats::record_finish_reason;
+use crate::streaming::{sse_delta, FinishReason, SSE_DONE};
+
+/// Service name of the protocol in the module docs
+pub const DEFAULT_SERVICE: &str = "semantic_query.v1.TextGeneration";
+
+/// Input of `Generate` and `GenerateStream`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GenerateRequest {
+    #[prost(string, tag = "1")]
+    pub prompt: String,
+    #[prost(string, tag = "2")]
+    pub model: String,
+    #[prost(float, optional, tag = "3")]
+    pub temperature: Option<f32>,
+    #[prost(uint32, optional, tag = "4")]
+    pub max_tokens: Option<u32>,
+    #[prost(string, repeated, tag = "5")]
+    pub stop: Vec<String>,
+    #[prost(uint64, optional, tag = "6")]
+    pub seed: Option<u64>,
+}
+
+/// A whole reply, or one streamed chunk of it
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GenerateReply {
+    #[prost(string, tag = "1")]
+    pub text: String,
+    /// Empty until the last chunk
+    #[prost(string, tag = "2")]
+    pub finish_reason: String,
+}
+
+/// Where and how to reach the model server
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    /// Server URI, e.g. `http://models.internal:50051`
+    pub endpoint: String,
+    /// Fully-qualified service name, `DEFAULT_SERVICE` unless the server renamed it
+    pub service: String,
+    /// Passed through as `GenerateRequest::model`; servers hosting one model may ignore it
+    pub model: String,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub seed: Option<u64>,
+    /// Sent as the `grpc-timeout` deadline of each call
+    pub timeout: Option<Duration>,
+    /// Request metadata on every call, e.g. `authorization`
+    pub metadata: Vec<(String, String)>,
+}
+
+impl GrpcConfig {
+    pub fn new(endpoint: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            service: DEFAULT_SERVICE.to_string(),
+            model: model.into(),
+            temperature: None,
+            max_tokens: None,
+            seed: None,
+            timeout: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_service(mut self, service: impl Into<String>) -> Self {
+        self.service = service.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send `key: value` metadata with every call; keys are lowercase ASCII
+    #[must_use]
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    /// Send `authorization: Bearer <token>`
+    #[must_use]
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        let value = format!("Bearer {}", token.as_ref());
+        self.with_metadata("authorization", value)
+    }
+}
+
+/// Client for servers implementing the protocol in the module docs
+#[derive(Debug, Clone)]
+pub struct GrpcClient {
+    config: GrpcConfig,
+    /// Opened on first use, since connecting needs a running Tokio runtime
+    channel: Arc<OnceLock<Channel>>,
+    stop: Vec<String>,
+}
+
+impl GrpcClient {
+    pub fn new(config: GrpcConfig) -> Self {
+        info!(endpoint = %config.endpoint, model = %config.model, "Creating new gRPC client");
+        Self { config, channel: Arc::new(OnceLock::new()), stop: Vec::new() }
+    }
+
+    pub fn config(&self) -> &GrpcConfig {
+        &self.config
+    }
+
+    async fn grpc(&self) -> Result<tonic::client::Grpc<Channel>, AIError> {
+        let channel = match self.channel.get() {
+            Some(channel) => channel.clone(),
+            None => {
+                let endpoint = Endpoint::from_shared(self.config.endpoint.clone())
+                    .map_err(|e| AIError::Configuration(format!("invalid gRPC endpoint '{}': {e}", self.config.endpoint)))?;
+                self.channel.get_or_init(|| endpoint.connect_lazy()).clone()
+            }
+        };
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready().await.map_err(|e| GrpcError::Transport(e.to_string()))?;
+        Ok(grpc)
+    }
+
+    fn request(&self, prompt: String) -> Result<tonic::Request<GenerateRequest>, AIError> {
+        let mut request = tonic::Request::new(GenerateRequest {
+            prompt,
+            model: self.config.model.clone(),
+            temperature: self.config.temperature,
+            max_tokens: self.config.max_tokens,
+            stop: self.stop.clone(),
+            seed: self.config.seed,
+        });
+        let correlation_id = correlation::current().map(|id| (correlation::HEADER.to_string(), id.as_str().to_string()));
+        for (key, value) in self.config.metadata.iter().cloned().chain(correlation_id) {
+            let key = MetadataKey::from_bytes(key.as_bytes()).map_err(|e| AIError::Configuration(format!("invalid metadata key '{key}': {e}")))?;
+            let value = MetadataValue::try_from(value.as_str()).map_err(|e| AIError::Configuration(format!("invalid metadata value: {e}")))?;
+            request.metadata_mut().append(key, value);
+        }
+        if let Some(timeout) = self.config.timeout {
+            request.set_timeout(timeout);
+        }
+        Ok(request)
+    }
+
+    fn path(&self, method: &str) -> Result<PathAndQuery, AIError> {
+        PathAndQuery::try_from(format!("/{}/{method}", self.config.service))
+            .map_err(|e| AIError::Configuration(format!("invalid gRPC service '{}': {e}", self.config.service)))
+    }
+
+    #[instrument(name = "grpc", target = "semantic_query::http", skip_all, fields(provider = "grpc", endpoint = %self.config.endpoint, model = %self.config.model))]
+    async fn generate(&self, prompt: String) -> Result<String, AIError> {
+        let request = self.request(prompt)?;
+        let path = self.path("Generate")?;
+        let mut grpc = self.grpc().await?;
+        debug!(path = %path, "Sending gRPC request");
+        let reply = grpc.unary(request, path, ProstCodec::<GenerateRequest, GenerateReply>::default()).await
+            .map_err(status_error)?
+            .into_inner();
+        if let Some(reason) = FinishReason::from_provider(&reply.finish_reason) {
+            record_finish_reason(reason);
+        }
+        Ok(reply.text)
+    }
+}
+
+fn status_error(status: tonic::Status) -> AIError {
+    error!(code = ?status.code(), message = %status.message(), "gRPC call failed");
+    AIError::Grpc(GrpcError::Status { code: format!("{:?}", status.code()), message: status.message().to_string() })
+}
+
+#[async_trait]
+impl LowLevelClient for GrpcClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.generate(prompt).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> { Box::new(self.clone()) }
+
+    fn provider(&self) -> Option<&'static str> { Some("grpc") }
+
+    fn as_any(&self) -> Option<&dyn Any> { Some(self) }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { Some(self) }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.temperature = Some(temperature);
+        Some(Box::new(client))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.config.max_tokens
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = Some(max_tokens);
+        Some(Box::new(client))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.seed = Some(seed);
+        Some(Box::new(client))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { stop: stop.to_vec(), ..self.clone() }))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { supports_streaming: true, ..Capabilities::default() }
+    }
+
+    /// Calls `GenerateStream`, turning each chunk's text into a delta
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        let client = self.clone();
+        let s = async_stream::try_stream! {
+            let request = client.request(prompt)?;
+            let path = client.path("GenerateStream")?;
+            let mut grpc = client.grpc().await?;
+            let mut chunks = grpc.server_streaming(request, path, ProstCodec::<GenerateRequest, GenerateReply>::default()).await
+                .map_err(status_error)?
+                .into_inner();
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.map_err(status_error)?;
+                if !chunk.text.is_empty() {
+                    yield sse_delta(&chunk.text);
+                }
+            }
+            yield bytes::Bytes::from_static(SSE_DONE);
+        };
+        Some(Box::pin(s))
+    }
+}
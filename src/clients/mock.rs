@@ -1,13 +1,295 @@
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex, Weak};
-use std::collections::VecDeque;
-use crate::{core::LowLevelClient, error::AIError};
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+use crate::audit::RecordUsage;
+use crate::confidence::TokenLogprob;
+use crate::core::{render_transcript, ChatMessage, LowLevelClient, RawByteStream};
+use crate::grammar::OutputConstraint;
+use crate::correlation::{self, CorrelationId};
+use crate::error::{AIError, OpenAIError};
+use crate::stats;
+use crate::streaming::FinishReason;
+use bytes::Bytes;
+use serde_json::{json, Map, Value};
+use std::any::Any;
 
 /// Mock responses that can be configured
 #[derive(Debug, Clone)]
 pub enum MockResponse {
     Success(String),
     Error(AIError),
+    /// A reply cut off at the token limit, reported with `FinishReason::Length`
+    Truncated(String),
+}
+
+/// A `LowLevelClient::with_*` setting a `MockClient` can be made to accept with
+/// `MockHandle::accept`. It accepts none by default, like a provider without them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockSetting {
+    Temperature,
+    Seed,
+    MaxTokens,
+    JsonMode,
+    StopSequences,
+    User,
+    Prefill,
+    Logprobs,
+    /// Not a `with_*` method: `supports_grammar` reports true and constrained calls are
+    /// recorded with their constraint
+    Grammar,
+    /// Not a `with_*` method: `supports_system_role` reports true and `ask_messages`
+    /// calls are recorded with their messages
+    SystemRole,
+}
+
+/// Request settings a `MockClient` copy was made with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockSettings {
+    pub temperature: Option<f32>,
+    pub seed: Option<u64>,
+    pub max_tokens: Option<u32>,
+    pub json_mode: bool,
+    /// Replies end before the first of these
+    pub stop_sequences: Vec<String>,
+    pub user: Option<String>,
+    /// Replies start with this
+    pub prefill: Option<String>,
+    pub logprobs: bool,
+}
+
+impl MockSettings {
+    /// `response` as a provider with these settings would return it
+    fn shape(&self, mut response: String) -> String {
+        if let Some(end) = self.stop_sequences.iter().filter_map(|stop| response.find(stop.as_str())).min() {
+            response.truncate(end);
+        }
+        match &self.prefill {
+            Some(prefill) => format!("{prefill}{response}"),
+            None => response,
+        }
+    }
+}
+
+/// A call a `MockClient` received
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockCall {
+    pub prompt: String,
+    pub settings: MockSettings,
+    /// Set for `ask_raw_constrained` calls with `MockSetting::Grammar` accepted
+    pub constraint: Option<OutputConstraint>,
+    /// Set for `ask_messages` calls with `MockSetting::SystemRole` accepted; `prompt` is
+    /// then their transcript
+    pub messages: Option<Vec<ChatMessage>>,
+    /// `correlation::current()` during the call
+    pub correlation_id: Option<CorrelationId>,
+    /// `tenancy::current()` during the call
+    pub tenant: Option<String>,
+}
+
+/// Delay injected before each mock response
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Latency {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// Uniformly distributed between the bounds
+    Uniform { min: Duration, max: Duration },
+    /// `base`, plus `spike` with probability `rate`
+    Spiky { base: Duration, spike: Duration, rate: f64 },
+}
+
+/// Failures a `MockHandle` injects into `MockClient` calls. Rates are probabilities
+/// from 0.0 to 1.0, drawn from a generator seeded with `seed`, so a run is reproducible.
+///
+/// Injected errors happen before a queued response is consumed, so a retry gets it.
+/// Truncation and malformed JSON alter the response; disconnects and stalls only affect
+/// streams (see `MockHandle::stream_in_chunks`).
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    pub error_rate: f64,
+    /// Returned by injected failures; defaults to a retryable transport error
+    pub error: Option<AIError>,
+    pub latency: Latency,
+    /// Cut the response off partway
+    pub truncate_rate: f64,
+    /// Corrupt the response's JSON: drop its last closing bracket, add a trailing
+    /// comma, or switch to single quotes
+    pub malform_rate: f64,
+    /// End a stream with a transport error partway through
+    pub disconnect_rate: f64,
+    /// Leave a stream open after its last chunk, without ending it
+    pub stall_rate: f64,
+    pub seed: u64,
+}
+
+impl FaultConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_error_rate(mut self, rate: f64) -> Self {
+        self.error_rate = rate;
+        self
+    }
+
+    #[must_use]
+    pub fn with_error(mut self, error: AIError) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    #[must_use]
+    pub fn with_latency(mut self, latency: Latency) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    #[must_use]
+    pub fn with_truncation(mut self, rate: f64) -> Self {
+        self.truncate_rate = rate;
+        self
+    }
+
+    #[must_use]
+    pub fn with_malformed_json(mut self, rate: f64) -> Self {
+        self.malform_rate = rate;
+        self
+    }
+
+    #[must_use]
+    pub fn with_disconnects(mut self, rate: f64) -> Self {
+        self.disconnect_rate = rate;
+        self
+    }
+
+    #[must_use]
+    pub fn with_stalls(mut self, rate: f64) -> Self {
+        self.stall_rate = rate;
+        self
+    }
+
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// How many calls a `MockHandle` has seen and how many of them had faults injected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    pub calls: usize,
+    pub errors: usize,
+    pub truncated: usize,
+    pub malformed: usize,
+    pub disconnects: usize,
+    pub stalls: usize,
+}
+
+/// Active `FaultConfig` with its generator state
+#[derive(Debug)]
+struct FaultInjector {
+    config: FaultConfig,
+    /// SplitMix64 state
+    state: u64,
+}
+
+impl FaultInjector {
+    fn new(config: FaultConfig) -> Self {
+        let state = config.seed;
+        Self { config, state }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Draw every decision for one call, in a fixed order so runs replay exactly
+    fn plan(&mut self, stats: &mut FaultStats, streaming: bool) -> FaultPlan {
+        let latency = self.config.latency;
+        let delay = match latency {
+            Latency::None => Duration::ZERO,
+            Latency::Fixed(delay) => delay,
+            Latency::Uniform { min, max } => min + max.saturating_sub(min).mul_f64(self.next_f64()),
+            Latency::Spiky { base, spike, rate } => if self.next_f64() < rate { base + spike } else { base },
+        };
+        let error = (self.next_f64() < self.config.error_rate).then(|| {
+            self.config.error.clone().unwrap_or_else(|| AIError::OpenAI(OpenAIError::Http("mock: injected failure".to_string())))
+        });
+        let truncate_at = (self.next_f64() < self.config.truncate_rate).then(|| 0.2 + 0.6 * self.next_f64());
+        let malform = (self.next_f64() < self.config.malform_rate).then(|| self.next_u64());
+        let disconnect_at = (self.next_f64() < self.config.disconnect_rate).then(|| 0.2 + 0.6 * self.next_f64())
+            .filter(|_| streaming);
+        // Drawn only when configured, so seeded runs without stalls replay as before
+        let stall = streaming && disconnect_at.is_none() && self.config.stall_rate > 0.0 && self.next_f64() < self.config.stall_rate;
+
+        stats.errors += usize::from(error.is_some());
+        if error.is_none() {
+            stats.truncated += usize::from(truncate_at.is_some());
+            stats.malformed += usize::from(malform.is_some());
+            stats.disconnects += usize::from(disconnect_at.is_some());
+            stats.stalls += usize::from(stall);
+        }
+        FaultPlan { delay, error, truncate_at, malform, disconnect_at, stall }
+    }
+}
+
+/// The faults drawn for one call
+#[derive(Debug, Default)]
+struct FaultPlan {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    delay: Duration,
+    error: Option<AIError>,
+    /// Fraction of the response to keep
+    truncate_at: Option<f64>,
+    /// Selects the corruption
+    malform: Option<u64>,
+    /// Fraction of the stream's chunks sent before disconnecting
+    disconnect_at: Option<f64>,
+    /// Keep the stream open after its last chunk
+    stall: bool,
+}
+
+impl FaultPlan {
+    /// No-op on wasm32, which has no timer here
+    async fn delay(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.delay.is_zero() {
+            crate::runtime::sleep(self.delay).await;
+        }
+    }
+
+    fn apply(&self, response: String) -> String {
+        let mut response = match self.malform {
+            Some(kind) => malform(&response, kind),
+            None => response,
+        };
+        if let Some(keep) = self.truncate_at {
+            let chars = response.chars().count();
+            response = response.chars().take((chars as f64 * keep) as usize).collect();
+        }
+        response
+    }
+}
+
+fn malform(text: &str, kind: u64) -> String {
+    let last_close = text.rfind(['}', ']']);
+    match (kind % 3, last_close) {
+        (0, Some(i)) => format!("{}{}", &text[..i], &text[i + 1..]),
+        (1, Some(i)) => format!("{},{}", &text[..i], &text[i..]),
+        _ => text.replace('"', "'"),
+    }
 }
 
 /// Shared state for mock responses
@@ -15,6 +297,13 @@ pub enum MockResponse {
 pub struct MockState {
     responses: VecDeque<MockResponse>,
     fail_on_empty: bool,
+    faults: Option<FaultInjector>,
+    stats: FaultStats,
+    stream_chunk_chars: Option<usize>,
+    calls: Vec<MockCall>,
+    accepted: HashSet<MockSetting>,
+    usage: Option<RecordUsage>,
+    logprobs: Option<Vec<TokenLogprob>>,
 }
 
 impl MockState {
@@ -22,6 +311,7 @@ impl MockState {
         Self {
             responses: VecDeque::new(),
             fail_on_empty,
+            ..Self::default()
         }
     }
 
@@ -98,6 +388,11 @@ impl MockHandle {
         self.add_responses(responses);
     }
 
+    /// Add a response cut off at the token limit
+    pub fn add_truncated_response(&self, text: &str) {
+        self.add_response(MockResponse::Truncated(text.to_string()));
+    }
+
     /// Add an error response
     pub fn add_error(&self, error: AIError) {
         self.add_response(MockResponse::Error(error));
@@ -126,12 +421,97 @@ impl MockHandle {
         let mut state = self.state.lock().unwrap();
         state.next_response()
     }
+
+    /// Next queued response, if any
+    fn pop_response(&self) -> Option<MockResponse> {
+        self.state.lock().unwrap().responses.pop_front()
+    }
+
+    /// Inject `faults` into subsequent calls, restarting from its seed and resetting
+    /// `fault_stats`
+    pub fn inject_faults(&self, faults: FaultConfig) {
+        let mut state = self.state.lock().unwrap();
+        state.faults = Some(FaultInjector::new(faults));
+        state.stats = FaultStats::default();
+    }
+
+    /// Stop injecting faults
+    pub fn clear_faults(&self) {
+        self.state.lock().unwrap().faults = None;
+    }
+
+    /// Calls seen and faults injected since the last `inject_faults`
+    pub fn fault_stats(&self) -> FaultStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Make `stream_raw` available, sending each response as OpenAI-style SSE deltas of
+    /// up to `chars` characters
+    pub fn stream_in_chunks(&self, chars: usize) {
+        self.state.lock().unwrap().stream_chunk_chars = Some(chars.max(1));
+    }
+
+    /// Make the client's `with_*` hooks for `settings` return a configured copy instead
+    /// of None. Stop sequences cut replies short and a prefill starts them; the other
+    /// settings are only recorded in `calls`.
+    pub fn accept(&self, settings: &[MockSetting]) {
+        self.state.lock().unwrap().accepted.extend(settings.iter().copied());
+    }
+
+    fn accepts(&self, setting: MockSetting) -> bool {
+        self.state.lock().unwrap().accepted.contains(&setting)
+    }
+
+    /// Calls received so far, oldest first, faulted ones included
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Prompts of `calls`
+    pub fn prompts(&self) -> Vec<String> {
+        self.state.lock().unwrap().calls.iter().map(|call| call.prompt.clone()).collect()
+    }
+
+    /// Report `usage` for every reply from now on, as a provider reports what it billed
+    pub fn report_usage(&self, usage: RecordUsage) {
+        self.state.lock().unwrap().usage = Some(usage);
+    }
+
+    fn usage(&self) -> Option<RecordUsage> {
+        self.state.lock().unwrap().usage
+    }
+
+    /// Report `tokens` as the logprobs of every reply to a call made with logprobs on
+    /// (see `MockSetting::Logprobs`)
+    pub fn report_logprobs(&self, tokens: Vec<TokenLogprob>) {
+        self.state.lock().unwrap().logprobs = Some(tokens);
+    }
+
+    fn logprobs(&self) -> Option<Vec<TokenLogprob>> {
+        self.state.lock().unwrap().logprobs.clone()
+    }
+
+    /// Count and record a call and draw its faults
+    fn plan(&self, call: MockCall, streaming: bool) -> FaultPlan {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+        let MockState { faults, stats, .. } = &mut *state;
+        stats.calls += 1;
+        faults.as_mut().map(|faults| faults.plan(stats, streaming)).unwrap_or_default()
+    }
+
+    fn stream_chunk_chars(&self) -> Option<usize> {
+        self.state.lock().unwrap().stream_chunk_chars
+    }
 }
 
-/// Mock client that fails when no responses are available
+/// Mock client that fails when no responses are available, unless created with
+/// `auto_schema`
 #[derive(Debug)]
 pub struct MockClient {
     handle: Weak<MockHandle>,
+    auto: Option<Arc<AutoSchema>>,
+    settings: MockSettings,
 }
 
 impl MockClient {
@@ -143,6 +523,8 @@ impl MockClient {
         
         let client = Self {
             handle: weak_handle,
+            auto: None,
+            settings: MockSettings::default(),
         };
         
         (client, handle)
@@ -155,6 +537,98 @@ impl MockClient {
         (client, handle)
     }
 
+    /// Create a MockClient that answers each prompt with a valid instance of the schema
+    /// in it (see `AutoSchema`). Responses queued on the handle are served first; the
+    /// handle may be dropped.
+    pub fn auto_schema() -> (Self, Arc<MockHandle>) {
+        Self::auto_schema_with(AutoSchema::default())
+    }
+
+    /// `auto_schema` with prose wrapping or variation configured
+    pub fn auto_schema_with(auto: AutoSchema) -> (Self, Arc<MockHandle>) {
+        let (mut client, handle) = Self::new();
+        client.auto = Some(Arc::new(auto));
+        (client, handle)
+    }
+
+    /// Start from `settings`, as if made with them by the provider's config; the handle
+    /// still decides which `with_*` methods change them
+    #[must_use]
+    pub fn with_settings(mut self, settings: MockSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Next reply: a queued response, else a fabricated one in `auto_schema` mode
+    fn reply(&self, prompt: &str) -> Result<String, AIError> {
+        let reply = self.next_reply(prompt)?;
+        if let Some(handle) = self.handle.upgrade() {
+            if let Some(usage) = handle.usage() {
+                stats::record_usage(usage);
+            }
+            if let Some(tokens) = handle.logprobs().filter(|_| self.settings.logprobs) {
+                stats::record_logprobs(tokens);
+            }
+        }
+        Ok(reply)
+    }
+
+    fn next_reply(&self, prompt: &str) -> Result<String, AIError> {
+        let next = match &self.auto {
+            Some(auto) => match self.handle.upgrade().and_then(|handle| handle.pop_response()) {
+                Some(response) => response,
+                None => return auto.reply(prompt),
+            },
+            None => self.try_next_response()?,
+        };
+        match next {
+            MockResponse::Success(response) => Ok(response),
+            MockResponse::Error(error) => Err(error),
+            MockResponse::Truncated(response) => {
+                stats::record_finish_reason(FinishReason::Length);
+                Ok(response)
+            }
+        }
+    }
+
+    /// `prompt` as a call made with this copy's settings
+    fn call(&self, prompt: &str) -> MockCall {
+        MockCall {
+            prompt: prompt.to_string(),
+            settings: self.settings.clone(),
+            constraint: None,
+            messages: None,
+            correlation_id: correlation::current(),
+            tenant: crate::tenancy::current(),
+        }
+    }
+
+    /// Count and record the call and draw its faults; none once the handle is dropped
+    fn plan(&self, call: MockCall, streaming: bool) -> FaultPlan {
+        self.handle.upgrade().map(|handle| handle.plan(call, streaming)).unwrap_or_default()
+    }
+
+    /// Answer `call`, with faults injected and settings applied
+    async fn ask(&self, call: MockCall) -> Result<String, AIError> {
+        let prompt = call.prompt.clone();
+        let plan = self.plan(call, false);
+        plan.delay().await;
+        if let Some(error) = plan.error {
+            return Err(error);
+        }
+        self.reply(&prompt).map(|response| self.settings.shape(plan.apply(response)))
+    }
+
+    /// A copy with `apply` made to its settings, if the handle accepts `setting`
+    fn configured(&self, setting: MockSetting, apply: impl FnOnce(&mut MockSettings)) -> Option<Box<dyn LowLevelClient>> {
+        if !self.handle.upgrade()?.accepts(setting) {
+            return None;
+        }
+        let mut client = self.clone();
+        apply(&mut client.settings);
+        Some(Box::new(client))
+    }
+
     /// Try to get the next response, failing if handle is dropped or no responses available
     fn try_next_response(&self) -> Result<MockResponse, AIError> {
         match self.handle.upgrade() {
@@ -170,29 +644,381 @@ impl Clone for MockClient {
     fn clone(&self) -> Self {
         Self {
             handle: self.handle.clone(),
+            auto: self.auto.clone(),
+            settings: self.settings.clone(),
         }
     }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl LowLevelClient for MockClient {
-    async fn ask_raw(&self, _prompt: String) -> Result<String, AIError> {
-        match self.try_next_response()? {
-            MockResponse::Success(response) => Ok(response),
-            MockResponse::Error(error) => Err(error),
-        }
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.ask(self.call(&prompt)).await
     }
 
     fn clone_box(&self) -> Box<dyn LowLevelClient> {
         Box::new(self.clone())
     }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::Temperature, |settings| settings.temperature = Some(temperature))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::Seed, |settings| settings.seed = Some(seed))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.settings.max_tokens
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::MaxTokens, |settings| settings.max_tokens = Some(max_tokens))
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::JsonMode, |settings| settings.json_mode = true)
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::StopSequences, |settings| settings.stop_sequences = stop.to_vec())
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::User, |settings| settings.user = Some(user.to_string()))
+    }
+
+    fn with_prefill(&self, prefill: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::Prefill, |settings| settings.prefill = Some(prefill.to_string()))
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.configured(MockSetting::Logprobs, |settings| settings.logprobs = true)
+    }
+
+    fn supports_grammar(&self) -> bool {
+        self.handle.upgrade().is_some_and(|handle| handle.accepts(MockSetting::Grammar))
+    }
+
+    async fn ask_raw_constrained(&self, prompt: String, constraint: &OutputConstraint) -> Result<String, AIError> {
+        if !self.supports_grammar() {
+            return self.ask_raw(prompt).await;
+        }
+        self.ask(MockCall { constraint: Some(constraint.clone()), ..self.call(&prompt) }).await
+    }
+
+    fn supports_system_role(&self) -> bool {
+        self.handle.upgrade().is_some_and(|handle| handle.accepts(MockSetting::SystemRole))
+    }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        let call = self.call(&render_transcript(&messages));
+        if !self.supports_system_role() {
+            return self.ask(call).await;
+        }
+        self.ask(MockCall { messages: Some(messages), ..call }).await
+    }
+
+    fn provider(&self) -> Option<&'static str> {
+        Some("mock")
+    }
+
+    /// Only after `MockHandle::stream_in_chunks`
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        let chunk_chars = self.handle.upgrade()?.stream_chunk_chars()?;
+        let mut plan = self.plan(self.call(&prompt), true);
+        // Errors injected before the stream starts leave the queued response in place
+        let reply = match plan.error.take() {
+            Some(error) => Err(error),
+            None => self.reply(&prompt).map(|response| self.settings.shape(plan.apply(response))),
+        };
+        Some(Box::pin(async_stream::stream! {
+            plan.delay().await;
+            match reply {
+                Err(error) => yield Err(error),
+                Ok(text) => {
+                    let chars: Vec<char> = text.chars().collect();
+                    let chunks: Vec<String> = chars.chunks(chunk_chars).map(|chunk| chunk.iter().collect()).collect();
+                    let sent = plan.disconnect_at.map_or(chunks.len(), |at| (chunks.len() as f64 * at) as usize);
+                    for chunk in &chunks[..sent] {
+                        let event = json!({ "choices": [{ "delta": { "content": chunk }, "finish_reason": null }] });
+                        yield Ok(Bytes::from(format!("data: {event}\n\n")));
+                    }
+                    if plan.disconnect_at.is_some() {
+                        yield Err(AIError::OpenAI(OpenAIError::Http("mock: stream disconnected".to_string())));
+                    } else if plan.stall {
+                        std::future::pending::<()>().await;
+                    } else {
+                        yield Ok(Bytes::from_static(b"data: [DONE]\n\n"));
+                    }
+                }
+            }
+        }))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+}
+
+/// Arrays and optional values are kept minimal from this depth on, so recursive types end
+const SHALLOW_DEPTH: usize = 4;
+/// Depth at which generation gives up and emits `null`
+const MAX_DEPTH: usize = 32;
+
+/// Fabricates JSON that fits a JSON Schema, for `MockClient::auto_schema`.
+///
+/// Values come from `const`, the first `enum` entry, `default` or `examples` when the
+/// schema has them, and otherwise from placeholders per type that respect formats,
+/// length and range bounds: strings echo their property name, numbers start at 1 and
+/// booleans are `true`. Object properties are all filled; arrays and maps get one entry.
+/// With the `mock-variation` feature, `with_seed` varies choices, counts and numbers
+/// reproducibly.
+#[derive(Debug, Default)]
+pub struct AutoSchema {
+    prose: bool,
+    #[cfg(feature = "mock-variation")]
+    rng: Option<Mutex<rand::rngs::StdRng>>,
+}
+
+impl AutoSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap the JSON in a sentence and a code fence, like a chat model would
+    #[must_use]
+    pub fn with_prose(mut self) -> Self {
+        self.prose = true;
+        self
+    }
+
+    /// Vary enum choices, array lengths, numbers and strings from `seed`
+    #[cfg(feature = "mock-variation")]
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Some(Mutex::new(rand::SeedableRng::seed_from_u64(seed)));
+        self
+    }
+
+    /// A reply to `prompt`: an instance of the last JSON Schema block in it. Fails with
+    /// `AIError::Mock` when the prompt has none (e.g. plain `ask_raw` calls).
+    pub fn reply(&self, prompt: &str) -> Result<String, AIError> {
+        let schema = prompt_schema(prompt)
+            .ok_or_else(|| AIError::Mock("auto_schema mock found no JSON schema block in the prompt".to_string()))?;
+        let json = serde_json::to_string_pretty(&self.instance(&schema)).unwrap_or_default();
+        Ok(if self.prose {
+            format!("Here is what I found:\n\n```json\n{json}\n```\n\nLet me know if you need anything else.")
+        } else {
+            json
+        })
+    }
+
+    /// An instance of `schema`; `$ref`s resolve against `schema` itself
+    pub fn instance(&self, schema: &Value) -> Value {
+        self.generate(schema, schema, None, 0)
+    }
+
+    /// An index below `n`: 0 unless variation is enabled
+    #[cfg(feature = "mock-variation")]
+    fn pick(&self, n: usize) -> usize {
+        use rand::Rng;
+        self.rng.as_ref().map_or(0, |rng| rng.lock().unwrap().gen_range(0..n.max(1)))
+    }
+
+    #[cfg(not(feature = "mock-variation"))]
+    fn pick(&self, _n: usize) -> usize {
+        0
+    }
+
+    fn generate(&self, root: &Value, schema: &Value, name: Option<&str>, depth: usize) -> Value {
+        // `true` and other non-object schemas accept anything
+        let Some(obj) = schema.as_object() else { return json!(name.unwrap_or("example")) };
+        if depth > MAX_DEPTH {
+            return Value::Null;
+        }
+        if let Some(reference) = obj.get("$ref").and_then(Value::as_str) {
+            return resolve_ref(root, reference)
+                .map_or(Value::Null, |target| self.generate(root, target, name, depth + 1));
+        }
+        if let Some(value) = obj.get("const") {
+            return value.clone();
+        }
+        if let Some(options) = obj.get("enum").and_then(Value::as_array).filter(|o| !o.is_empty()) {
+            return options[self.pick(options.len())].clone();
+        }
+        if let Some(value) = obj.get("default") {
+            return value.clone();
+        }
+        if let Some(example) = obj.get("examples").and_then(Value::as_array).and_then(|e| e.first()) {
+            return example.clone();
+        }
+        if let Some(variants) = obj.get("oneOf").or_else(|| obj.get("anyOf")).and_then(Value::as_array) {
+            let (nulls, variants): (Vec<&Value>, Vec<&Value>) = variants.iter().partition(|v| is_null_schema(root, v));
+            if variants.is_empty() || (depth >= SHALLOW_DEPTH && !nulls.is_empty()) {
+                return Value::Null;
+            }
+            return self.generate(root, variants[self.pick(variants.len())], name, depth + 1);
+        }
+        if let Some(parts) = obj.get("allOf").and_then(Value::as_array) {
+            let mut merged = Map::new();
+            let mut other = None;
+            for part in parts {
+                match self.generate(root, part, name, depth + 1) {
+                    Value::Object(map) => merged.extend(map),
+                    value => other = other.or(Some(value)),
+                }
+            }
+            return if merged.is_empty() { other.unwrap_or(Value::Null) } else { Value::Object(merged) };
+        }
+
+        match schema_type(obj, depth) {
+            Some("object") => self.object(root, obj, depth),
+            Some("array") => self.array(root, obj, name, depth),
+            Some("string") => Value::String(self.string(obj, name)),
+            Some("integer") => {
+                let (min, max) = bounds(obj);
+                let preferred = 1 + self.pick(100) as i64;
+                let (min, max) = (min.map_or(i64::MIN, |m| m.ceil() as i64), max.map_or(i64::MAX, |m| m.floor() as i64));
+                json!(if min > max { min } else { preferred.clamp(min, max) })
+            }
+            Some("number") => {
+                let (min, max) = bounds(obj);
+                let preferred = 1.5 + self.pick(100) as f64;
+                let (min, max) = (min.unwrap_or(f64::MIN), max.unwrap_or(f64::MAX));
+                json!(if min > max { min } else { preferred.clamp(min, max) })
+            }
+            Some("boolean") => json!(self.pick(2) == 0),
+            Some("null") => Value::Null,
+            _ => json!(name.unwrap_or("example")),
+        }
+    }
+
+    fn object(&self, root: &Value, obj: &Map<String, Value>, depth: usize) -> Value {
+        let mut map = Map::new();
+        if let Some(properties) = obj.get("properties").and_then(Value::as_object) {
+            let required: Vec<&str> = obj.get("required").and_then(Value::as_array)
+                .map(|r| r.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            for (key, property) in properties {
+                if depth >= SHALLOW_DEPTH && !required.contains(&key.as_str()) {
+                    continue;
+                }
+                map.insert(key.clone(), self.generate(root, property, Some(key), depth + 1));
+            }
+        } else if let Some(values) = obj.get("additionalProperties").filter(|v| v.is_object()) {
+            let count = if depth >= SHALLOW_DEPTH { 0 } else { 1 + self.pick(3) };
+            for i in 1..=count {
+                map.insert(format!("key{i}"), self.generate(root, values, None, depth + 1));
+            }
+        }
+        Value::Object(map)
+    }
+
+    fn array(&self, root: &Value, obj: &Map<String, Value>, name: Option<&str>, depth: usize) -> Value {
+        // Tuples: `prefixItems` (2020-12) or an `items` array (draft 7)
+        if let Some(prefix) = obj.get("prefixItems").or_else(|| obj.get("items")).and_then(Value::as_array) {
+            return prefix.iter().map(|item| self.generate(root, item, name, depth + 1)).collect();
+        }
+        let min = obj.get("minItems").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let max = obj.get("maxItems").and_then(Value::as_u64).map_or(usize::MAX, |m| m as usize);
+        let wanted = if depth >= SHALLOW_DEPTH { min } else { min.max(1 + self.pick(3)) };
+        let items = obj.get("items").unwrap_or(&Value::Bool(true));
+        (0..wanted.min(max)).map(|_| self.generate(root, items, name, depth + 1)).collect()
+    }
+
+    fn string(&self, obj: &Map<String, Value>, name: Option<&str>) -> String {
+        let mut text = match obj.get("format").and_then(Value::as_str) {
+            Some("date-time") => "2024-01-15T09:30:00Z".to_string(),
+            Some("date") => "2024-01-15".to_string(),
+            Some("time") => "09:30:00".to_string(),
+            Some("email") => "user@example.com".to_string(),
+            Some("uri") | Some("url") => "https://example.com".to_string(),
+            Some("uuid") => "123e4567-e89b-12d3-a456-426614174000".to_string(),
+            Some("ipv4") => "192.0.2.1".to_string(),
+            Some("ipv6") => "2001:db8::1".to_string(),
+            _ => {
+                let word = name.unwrap_or("example");
+                match self.pick(100) {
+                    0 => word.to_string(),
+                    n => format!("{word} {n}"),
+                }
+            }
+        };
+        let min = obj.get("minLength").and_then(Value::as_u64).unwrap_or(0) as usize;
+        while text.chars().count() < min {
+            text.push('x');
+        }
+        if let Some(max) = obj.get("maxLength").and_then(Value::as_u64) {
+            text = text.chars().take(max as usize).collect();
+        }
+        text
+    }
+}
+
+/// The last fenced ```json block in `prompt` that parses as a JSON Schema object
+fn prompt_schema(prompt: &str) -> Option<Value> {
+    const SCHEMA_KEYS: [&str; 8] = ["$schema", "type", "properties", "$ref", "anyOf", "oneOf", "allOf", "enum"];
+    prompt.split("```json").skip(1)
+        .filter_map(|block| block.split("```").next())
+        .filter_map(|body| serde_json::from_str::<Value>(body.trim()).ok())
+        .filter(|value| value.as_object().is_some_and(|obj| SCHEMA_KEYS.iter().any(|key| obj.contains_key(*key))))
+        .last()
+}
+
+/// Resolve a local `#/...` JSON pointer reference (`#/$defs/X`, `#/definitions/X`)
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    root.pointer(reference.strip_prefix('#')?)
+}
+
+fn is_null_schema(root: &Value, schema: &Value) -> bool {
+    let schema = match schema.get("$ref").and_then(Value::as_str) {
+        Some(reference) => resolve_ref(root, reference).unwrap_or(schema),
+        None => schema,
+    };
+    schema.get("type").and_then(Value::as_str) == Some("null")
+}
+
+/// The schema's type: the first non-null entry of a type list (or `null` once past
+/// `SHALLOW_DEPTH`), else inferred from object/array keywords
+fn schema_type(obj: &Map<String, Value>, depth: usize) -> Option<&str> {
+    match obj.get("type") {
+        Some(Value::String(ty)) => Some(ty.as_str()),
+        Some(Value::Array(types)) => {
+            let types: Vec<&str> = types.iter().filter_map(Value::as_str).collect();
+            if depth >= SHALLOW_DEPTH && types.contains(&"null") {
+                return Some("null");
+            }
+            types.iter().copied().find(|ty| *ty != "null").or(types.first().copied())
+        }
+        _ if obj.contains_key("properties") || obj.contains_key("additionalProperties") => Some("object"),
+        _ if obj.contains_key("items") || obj.contains_key("prefixItems") => Some("array"),
+        _ => None,
+    }
+}
+
+/// Inclusive numeric bounds; exclusive bounds (numeric, or draft 4 booleans) are nudged inwards
+fn bounds(obj: &Map<String, Value>) -> (Option<f64>, Option<f64>) {
+    let number = |key: &str| obj.get(key).and_then(Value::as_f64);
+    let exclusive = |key: &str| obj.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let min = number("exclusiveMinimum").map(|m| m + 1.0)
+        .or_else(|| number("minimum").map(|m| if exclusive("exclusiveMinimum") { m + 1.0 } else { m }));
+    let max = number("exclusiveMaximum").map(|m| m - 1.0)
+        .or_else(|| number("maximum").map(|m| if exclusive("exclusiveMaximum") { m - 1.0 } else { m }));
+    (min, max)
 }
 
 /// Mock client for testing that returns empty responses (legacy)
 #[derive(Debug, Clone, Default)]
 pub struct MockVoid;
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl LowLevelClient for MockVoid {
     async fn ask_raw(&self, _prompt: String) -> Result<String, AIError> {
         Ok("{}".to_string())
@@ -201,6 +1027,14 @@ impl LowLevelClient for MockVoid {
     fn clone_box(&self) -> Box<dyn LowLevelClient> {
         Box::new(self.clone())
     }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
 }
 
 #[cfg(test)]
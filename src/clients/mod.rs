@@ -1,15 +1,50 @@
+// Each provider is behind its own feature; `flexible` and `mock` are always available,
+// `process` natively with `rt-tokio`
+#[cfg(any(feature = "anthropic", all(feature = "bedrock", feature = "aws-bedrock-sdk")))]
 pub mod claude;
+#[cfg(feature = "deepseek")]
 pub mod deepseek;
 pub mod flexible;
+#[cfg(all(feature = "grpc", not(target_arch = "wasm32")))]
+pub mod grpc;
 pub mod mock;
+#[cfg(feature = "ollama")]
+pub mod ollama;
+#[cfg(feature = "openai-compatible")]
+pub mod openai_compatible;
+#[cfg(all(feature = "rt-tokio", not(target_arch = "wasm32")))]
+pub mod process;
+#[cfg(any(feature = "openai", feature = "azure"))]
 pub mod chatgpt;
+#[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+pub mod websocket;
 
 // Re-export only the public surface needed by consumers to avoid ambiguous glob re-exports
+#[cfg(any(feature = "anthropic", all(feature = "bedrock", feature = "aws-bedrock-sdk")))]
 pub use claude::{ClaudeClient, ClaudeConfig};
+#[cfg(any(feature = "anthropic", all(feature = "bedrock", feature = "aws-bedrock-sdk")))]
 pub use claude::models::ClaudeModel;
+#[cfg(feature = "deepseek")]
 pub use deepseek::DeepSeekClient;
+#[cfg(feature = "deepseek")]
 pub use deepseek::models::DeepSeekModel;
-pub use flexible::{FlexibleClient, ClientType};
-pub use mock::{MockClient, MockHandle, MockResponse, MockVoid};
-pub use chatgpt::{OpenAIClient, OpenAIConfig, AzureOpenAIClient, AzureOpenAIConfig};
+pub use flexible::{FlexibleClient, ClientType, ClientSnapshot};
+#[cfg(all(feature = "grpc", not(target_arch = "wasm32")))]
+pub use grpc::{GrpcClient, GrpcConfig};
+#[cfg(feature = "ollama")]
+pub use ollama::{OllamaClient, OllamaConfig, LocalBackend};
+#[cfg(feature = "openai-compatible")]
+pub use openai_compatible::{CompatClient, CompatConfig};
+pub use mock::{AutoSchema, FaultConfig, FaultStats, Latency, MockCall, MockClient, MockHandle, MockResponse, MockSetting, MockSettings, MockVoid};
+#[cfg(feature = "openai")]
+pub use chatgpt::{OpenAIClient, OpenAIConfig};
+#[cfg(feature = "azure")]
+pub use chatgpt::{AzureOpenAIClient, AzureOpenAIConfig};
+#[cfg(any(feature = "openai", feature = "azure"))]
+pub use chatgpt::FunctionTool;
+#[cfg(any(feature = "openai", feature = "azure"))]
 pub use chatgpt::models::OpenAIModel;
+#[cfg(all(feature = "rt-tokio", not(target_arch = "wasm32")))]
+pub use process::{ProcessClient, ProcessConfig, PromptInput};
+#[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+pub use websocket::WsClient;
@@ -0,0 +1,282 @@
+use crate::core::{Capabilities, LowLevelClient};
+use std::any::Any;
+use crate::correlation::Correlated;
+use crate::error::{AIError, OllamaError, ProviderError};
+use crate::grammar::OutputConstraint;
+use crate::stats::{record_provenance, Provenance};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, error, debug, instrument};
+
+/// Which local server API the client talks to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalBackend {
+    /// Ollama `/api/generate`; constraints are sent as a JSON schema in `format`
+    #[default]
+    Ollama,
+    /// llama.cpp server `/completion`; constraints are sent as a GBNF `grammar`
+    LlamaCpp,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest {
+    model: String,
+    prompt: String,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Value>,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    num_predict: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    response: String,
+    model: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct LlamaCppRequest {
+    prompt: String,
+    n_predict: u32,
+    temperature: f32,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grammar: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LlamaCppResponse {
+    content: String,
+    model: Option<String>,
+}
+
+/// Configuration for local model servers (Ollama / llama.cpp)
+#[derive(Debug, Clone)]
+pub struct OllamaConfig {
+    /// Base URL of the server, e.g. `http://localhost:11434`
+    pub host: String,
+    /// Model name (ignored by llama.cpp, which serves a single model)
+    pub model: String,
+    pub backend: LocalBackend,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub seed: Option<u64>,
+}
+
+impl OllamaConfig {
+    pub const HOST_ENV: &'static str = "OLLAMA_HOST";
+    pub const MODEL_ENV: &'static str = "OLLAMA_MODEL";
+
+    /// Configuration for a llama.cpp server at `host`
+    pub fn llama_cpp(host: impl Into<String>) -> Self {
+        Self { host: host.into(), backend: LocalBackend::LlamaCpp, ..Self::default() }
+    }
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        let host = std::env::var(Self::HOST_ENV)
+            .map(|h| if h.starts_with("http") { h } else { format!("http://{h}") })
+            .unwrap_or_else(|_| "http://localhost:11434".to_string());
+        Self {
+            host,
+            model: std::env::var(Self::MODEL_ENV).unwrap_or_else(|_| "llama3.1".to_string()),
+            backend: LocalBackend::default(),
+            max_tokens: 4096,
+            temperature: 0.3,
+            seed: None,
+        }
+    }
+}
+
+/// Client for locally hosted models. Supports grammar-constrained generation,
+/// so `QueryResolver::with_grammar_constraints(true)` yields strictly valid JSON.
+#[derive(Clone, Debug)]
+pub struct OllamaClient {
+    config: OllamaConfig,
+    client: Client,
+    json_mode: bool,
+    /// Sent as `stop` (see `with_stop_sequences`)
+    stop: Vec<String>,
+}
+
+impl Default for OllamaClient {
+    fn default() -> Self {
+        Self::new(OllamaConfig::default())
+    }
+}
+
+impl OllamaClient {
+    pub fn new(config: OllamaConfig) -> Self {
+        info!(host = %config.host, model = %config.model, backend = ?config.backend, "Creating new Ollama client");
+        Self {
+            config,
+            client: Client::new(),
+            json_mode: false,
+            stop: Vec::new(),
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        let path = match self.config.backend {
+            LocalBackend::Ollama => "/api/generate",
+            LocalBackend::LlamaCpp => "/completion",
+        };
+        format!("{}{}", self.config.host.trim_end_matches('/'), path)
+    }
+
+    // Non-streaming body only; `stream_raw` keeps the default since Ollama streams NDJSON, not SSE
+    fn request_body(&self, prompt: String, constraint: Option<&OutputConstraint>) -> Value {
+        let body = match self.config.backend {
+            LocalBackend::Ollama => serde_json::to_value(OllamaRequest {
+                model: self.config.model.clone(),
+                prompt,
+                stream: false,
+                format: constraint
+                    .map(|c| c.json_schema.clone())
+                    .or_else(|| self.json_mode.then(|| Value::String("json".into()))),
+                options: OllamaOptions { temperature: self.config.temperature, num_predict: self.config.max_tokens, seed: self.config.seed, stop: self.stop.clone() },
+            }),
+            LocalBackend::LlamaCpp => serde_json::to_value(LlamaCppRequest {
+                prompt,
+                n_predict: self.config.max_tokens,
+                temperature: self.config.temperature,
+                stream: false,
+                grammar: constraint.map(|c| c.gbnf.clone()),
+                seed: self.config.seed,
+                stop: self.stop.clone(),
+            }),
+        };
+        body.unwrap_or(Value::Null)
+    }
+
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "ollama", model = %self.config.model, constrained = constraint.is_some(), status = tracing::field::Empty))]
+    async fn generate(&self, prompt: String, constraint: Option<&OutputConstraint>) -> Result<String, AIError> {
+        let body = self.request_body(prompt, constraint);
+
+        debug!(endpoint = %self.endpoint(), "Sending request to local model server");
+        let response = self
+            .client
+            .post(self.endpoint())
+            .correlated()
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                error!(error = %e, "HTTP request failed");
+                AIError::Ollama(OllamaError::Http(e.to_string()))
+            })?;
+        tracing::Span::current().record("status", response.status().as_u16());
+
+        if response.status() == 404 {
+            error!(model = %self.config.model, "Local model not found");
+            return Err(AIError::Ollama(OllamaError::ModelNotFound(self.config.model.clone())));
+        }
+
+        if !response.status().is_success() {
+            let err = ProviderError::from_response(response).await;
+            error!(status = err.status, error = %err.message, "Local model server error");
+            return Err(AIError::Ollama(OllamaError::Status(Box::new(err))));
+        }
+
+        let (text, model) = match self.config.backend {
+            LocalBackend::Ollama => response.json::<OllamaResponse>().await.map(|r| (r.response, r.model)),
+            LocalBackend::LlamaCpp => response.json::<LlamaCppResponse>().await.map(|r| (r.content, r.model)),
+        }
+        .map_err(|e| {
+            error!(error = %e, "Failed to parse local model response JSON");
+            AIError::Ollama(OllamaError::Http(e.to_string()))
+        })?;
+        record_provenance(Provenance { model, system_fingerprint: None, seed: self.config.seed });
+
+        info!(response_len = text.len(), "Successfully received local model response");
+        Ok(text)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for OllamaClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.generate(prompt, None).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> {
+        Box::new(self.clone())
+    }
+
+    fn provider(&self) -> Option<&'static str> {
+        Some("ollama")
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.temperature = temperature;
+        Some(Box::new(client))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.config.max_tokens)
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = max_tokens;
+        Some(Box::new(client))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.seed = Some(seed);
+        Some(Box::new(client))
+    }
+
+    /// Ollama's `format: "json"`; llama.cpp has no JSON mode without a grammar
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        (self.config.backend == LocalBackend::Ollama).then(|| Box::new(Self { json_mode: true, ..self.clone() }) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { stop: stop.to_vec(), ..self.clone() }))
+    }
+
+    fn supports_grammar(&self) -> bool {
+        true
+    }
+
+    async fn ask_raw_constrained(&self, prompt: String, constraint: &OutputConstraint) -> Result<String, AIError> {
+        self.generate(prompt, Some(constraint)).await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_json_mode: self.config.backend == LocalBackend::Ollama,
+            supports_grammar: true,
+            ..Capabilities::default()
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Client for third-party servers that speak the OpenAI chat-completions protocol
+//! (Together, Groq, vLLM, LM Studio, ...) at a custom base URL.
+
+use crate::config::KeyFromEnv;
+use crate::core::{Capabilities, ChatMessage, LowLevelClient, RawByteStream};
+use std::any::Any;
+use crate::correlation::Correlated;
+use crate::refusal::choice_refusal;
+use crate::confidence::TokenLogprob;
+use crate::stats::{record_finish_reason, record_logprobs, record_provenance, record_usage, ChatUsage, Provenance};
+use crate::streaming::FinishReason;
+use crate::error::{AIError, OpenAIError, ProviderError};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tracing::{debug, error, info, instrument};
+
+/// Configuration for an OpenAI-compatible endpoint
+#[derive(Debug, Clone)]
+pub struct CompatConfig {
+    /// Server base URL, e.g. `https://api.groq.com/openai` or `http://localhost:1234`
+    pub base_url: String,
+    /// Chat-completions path appended to `base_url`
+    pub path: String,
+    /// Header carrying the API key. `Authorization` sends `Bearer <key>`; any other
+    /// header (e.g. `api-key`) sends the key as-is.
+    pub auth_header: String,
+    /// API key; local servers usually need none
+    pub api_key: Option<String>,
+    /// Model string passed through verbatim
+    pub model: String,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    /// Sent as `seed`; servers without seed support usually ignore it
+    pub seed: Option<u64>,
+    /// Sent as `user`, the end user the request is made for; servers without it
+    /// usually ignore it
+    pub user: Option<String>,
+}
+
+impl KeyFromEnv for CompatConfig {
+    const KEY_NAME: &'static str = "OPENAI_COMPAT_API_KEY";
+}
+
+impl CompatConfig {
+    pub const BASE_URL_ENV: &'static str = "OPENAI_COMPAT_BASE_URL";
+    pub const MODEL_ENV: &'static str = "OPENAI_COMPAT_MODEL";
+    pub const PATH_ENV: &'static str = "OPENAI_COMPAT_PATH";
+    pub const AUTH_HEADER_ENV: &'static str = "OPENAI_COMPAT_AUTH_HEADER";
+
+    /// Configuration for `base_url` serving `model`, with the remaining fields at their defaults
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self { base_url: base_url.into(), model: model.into(), ..Self::default() }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    pub fn with_auth_header(mut self, header: impl Into<String>) -> Self {
+        self.auth_header = header.into();
+        self
+    }
+
+    /// Build from `OPENAI_COMPAT_BASE_URL`, returning a configuration error if it is missing
+    pub fn from_env() -> Result<Self, AIError> {
+        let config = Self::default();
+        if config.base_url.is_empty() {
+            return Err(AIError::Configuration(format!("{} is not set in the environment or .env", Self::BASE_URL_ENV)));
+        }
+        Ok(config)
+    }
+
+    fn endpoint(&self) -> String {
+        let path = self.path.trim_start_matches('/');
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+}
+
+impl Default for CompatConfig {
+    /// Read `OPENAI_COMPAT_*` variables; `base_url` is empty when unset
+    fn default() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self {
+            base_url: var(Self::BASE_URL_ENV).unwrap_or_default(),
+            path: var(Self::PATH_ENV).unwrap_or_else(|| "/v1/chat/completions".to_string()),
+            auth_header: var(Self::AUTH_HEADER_ENV).unwrap_or_else(|| "Authorization".to_string()),
+            api_key: Self::find_key().filter(|k| !k.is_empty()),
+            model: var(Self::MODEL_ENV).unwrap_or_default(),
+            max_tokens: 1024,
+            temperature: 0.2,
+            seed: None,
+            user: None,
+        }
+    }
+}
+
+/// Client for any endpoint implementing OpenAI chat completions, with SSE streaming.
+/// Errors are reported as `AIError::OpenAI` since the wire protocol is the same.
+#[derive(Clone, Debug)]
+pub struct CompatClient {
+    config: CompatConfig,
+    http: reqwest::Client,
+    json_mode: bool,
+    /// Sent as `stop` (see `with_stop_sequences`)
+    stop: Vec<String>,
+    /// Sent as `logprobs` (see `with_logprobs`)
+    logprobs: bool,
+}
+
+impl CompatClient {
+    pub fn new(config: CompatConfig) -> Self {
+        info!(base_url = %config.base_url, model = %config.model, "Creating new OpenAI-compatible client");
+        Self { config, http: reqwest::Client::new(), json_mode: false, stop: Vec::new(), logprobs: false }
+    }
+
+    /// Build a client from `OPENAI_COMPAT_*` environment variables
+    pub fn try_default() -> Result<Self, AIError> {
+        Ok(Self::new(CompatConfig::from_env()?))
+    }
+
+    pub fn config(&self) -> &CompatConfig {
+        &self.config
+    }
+
+    fn messages_body(&self, prompt: String, stream: bool) -> serde_json::Value {
+        self.chat_body(&[ChatMessage::user(prompt)], stream)
+    }
+
+    fn chat_body(&self, messages: &[ChatMessage], stream: bool) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": self.config.model,
+            "max_tokens": self.config.max_tokens,
+            "temperature": self.config.temperature,
+            "messages": messages
+        });
+        if stream {
+            body["stream"] = serde_json::Value::Bool(true);
+        }
+        if let Some(seed) = self.config.seed {
+            body["seed"] = seed.into();
+        }
+        if let Some(user) = &self.config.user {
+            body["user"] = user.as_str().into();
+        }
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::json!(self.stop);
+        }
+        if self.logprobs {
+            body["logprobs"] = true.into();
+        }
+        if self.json_mode {
+            body["response_format"] = serde_json::json!({ "type": "json_object" });
+        }
+        body
+    }
+
+    #[instrument(name = "http", target = "semantic_query::http", skip_all, fields(provider = "openai-compatible", model = %self.config.model, base_url = %self.config.base_url, status = tracing::field::Empty))]
+    async fn complete(&self, body: serde_json::Value) -> Result<String, AIError> {
+        debug!(endpoint = %self.config.endpoint(), "Sending request to OpenAI-compatible endpoint");
+        let resp = self.request(&body)
+            .send().await
+            .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+        tracing::Span::current().record("status", resp.status().as_u16());
+        let resp = check_status(resp).await?;
+
+        #[derive(Deserialize)]
+        struct Choices { choices: Vec<Choice>, model: Option<String>, system_fingerprint: Option<String>, usage: Option<ChatUsage> }
+        #[derive(Deserialize)]
+        struct Choice { message: Msg, finish_reason: Option<String>, logprobs: Option<Logprobs> }
+        #[derive(Deserialize)]
+        struct Logprobs { content: Option<Vec<TokenLogprob>> }
+        #[derive(Deserialize)]
+        struct Msg { content: Option<String>, refusal: Option<String> }
+
+        let parsed: Choices = resp.json().await
+            .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+        record_provenance(Provenance { model: parsed.model, system_fingerprint: parsed.system_fingerprint, seed: self.config.seed });
+        if let Some(usage) = parsed.usage {
+            record_usage(usage.into());
+        }
+        let choice = parsed.choices.into_iter().next()
+            .ok_or_else(|| AIError::OpenAI(OpenAIError::Api("No choices".into())))?;
+        if let Some(reason) = choice.finish_reason.as_deref().and_then(FinishReason::from_provider) {
+            record_finish_reason(reason);
+        }
+        if let Some(tokens) = choice.logprobs.and_then(|logprobs| logprobs.content) {
+            record_logprobs(tokens);
+        }
+        if let Some(message) = choice_refusal(choice.finish_reason.as_deref(), choice.message.refusal) {
+            return Err(AIError::Refused(message));
+        }
+        Ok(choice.message.content.unwrap_or_default())
+    }
+
+    fn request(&self, body: &serde_json::Value) -> reqwest::RequestBuilder {
+        let mut req = self.http.post(self.config.endpoint()).correlated().json(body);
+        if let Some(key) = &self.config.api_key {
+            let value = if self.config.auth_header.eq_ignore_ascii_case("authorization") {
+                format!("Bearer {key}")
+            } else {
+                key.clone()
+            };
+            req = req.header(self.config.auth_header.as_str(), value);
+        }
+        req
+    }
+}
+
+async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, AIError> {
+    if !resp.status().is_success() {
+        let err = ProviderError::from_response(resp).await;
+        error!(status = err.status, code = ?err.code, request_id = ?err.request_id, error = %err.message, "OpenAI-compatible API error");
+        return Err(AIError::OpenAI(err.into()));
+    }
+    Ok(resp)
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for CompatClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.complete(self.messages_body(prompt, false)).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> { Box::new(self.clone()) }
+
+    fn provider(&self) -> Option<&'static str> { Some("compat") }
+
+    fn as_any(&self) -> Option<&dyn Any> { Some(self) }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { Some(self) }
+
+    fn supports_system_role(&self) -> bool { true }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        self.complete(self.chat_body(&messages, false)).await
+    }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.temperature = temperature;
+        Some(Box::new(client))
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.config.max_tokens)
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.max_tokens = max_tokens;
+        Some(Box::new(client))
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.seed = Some(seed);
+        Some(Box::new(client))
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        let mut client = self.clone();
+        client.config.user = Some(user.to_string());
+        Some(Box::new(client))
+    }
+
+    /// Sends `response_format: {"type": "json_object"}`; most compatible servers accept it
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { json_mode: true, ..self.clone() }))
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { stop: stop.to_vec(), ..self.clone() }))
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        Some(Box::new(Self { logprobs: true, ..self.clone() }))
+    }
+
+    /// Tools, vision and the context window depend on the server and model, so they
+    /// are reported as unsupported/unknown
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_json_mode: true,
+            supports_system_role: true,
+            ..Capabilities::default()
+        }
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        let req = self.request(&self.messages_body(prompt, true));
+        let s = async_stream::try_stream! {
+            let resp = req.send().await.map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+            let resp = check_status(resp).await?;
+            let mut bytes_stream = resp.bytes_stream();
+            while let Some(chunk) = bytes_stream.next().await {
+                yield chunk.map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+            }
+        };
+        Some(Box::pin(s))
+    }
+}
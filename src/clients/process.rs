@@ -0,0 +1,295 @@
+//! Client that runs a local command per prompt, for air-gapped setups and CLI-only
+//! model tools (llama.cpp's `llama-cli`, the `claude` CLI, shell wrappers).
+//!
+//! The prompt goes to the command's stdin, or into its arguments; the reply is its
+//! stdout. Streamed queries read stdout line by line as the command writes it.
+
+use std::any::Any;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::time::Instant;
+use tracing::{debug, info, instrument, warn};
+
+use crate::core::{Capabilities, LowLevelClient, RawByteStream};
+use crate::error::{AIError, ProcessError};
+use crate::streaming::{sse_delta, SSE_DONE};
+
+/// Bytes of stderr kept for `ProcessError::Exit`
+const STDERR_TAIL: usize = 2048;
+
+/// Placeholder in `ProcessConfig::args` replaced by the prompt under `PromptInput::Argument`
+pub const PROMPT_PLACEHOLDER: &str = "{prompt}";
+
+/// How the prompt reaches the command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PromptInput {
+    /// Written to stdin, which is then closed
+    #[default]
+    Stdin,
+    /// Substituted for `{prompt}` in the arguments, or appended as the last argument
+    /// when none contains it
+    Argument,
+}
+
+/// Command line and environment for a `ProcessClient`
+#[derive(Debug, Clone)]
+pub struct ProcessConfig {
+    pub program: String,
+    pub args: Vec<String>,
+    pub prompt_input: PromptInput,
+    /// Variables set for the command, on top of the inherited environment
+    pub env: Vec<(String, String)>,
+    /// Variables removed from the inherited environment
+    pub env_remove: Vec<String>,
+    /// Start from an empty environment instead of inheriting this process's
+    pub env_clear: bool,
+    pub current_dir: Option<PathBuf>,
+    /// Kill the command if it has not finished after this long
+    pub timeout: Option<Duration>,
+    /// Stream stdout line by line from `stream_raw`; when false the client does not stream
+    pub streaming: bool,
+}
+
+impl ProcessConfig {
+    /// Run `program` with no arguments, the prompt on stdin
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            prompt_input: PromptInput::Stdin,
+            env: Vec::new(),
+            env_remove: Vec::new(),
+            env_clear: false,
+            current_dir: None,
+            timeout: None,
+            streaming: true,
+        }
+    }
+
+    /// The `claude` CLI in print mode (`claude -p`), prompt on stdin
+    pub fn claude_cli() -> Self {
+        Self::new("claude").with_arg("-p")
+    }
+
+    /// llama.cpp's `llama-cli` on the GGUF model at `model_path`, printing only the
+    /// completion
+    pub fn llama_cpp(model_path: impl Into<String>) -> Self {
+        Self::new("llama-cli")
+            .with_args(["-m".to_string(), model_path.into()])
+            .with_args(["--no-display-prompt", "-no-cnv", "-p", PROMPT_PLACEHOLDER])
+            .with_prompt_input(PromptInput::Argument)
+    }
+
+    #[must_use]
+    pub fn with_arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    #[must_use]
+    pub fn with_prompt_input(mut self, input: PromptInput) -> Self {
+        self.prompt_input = input;
+        self
+    }
+
+    #[must_use]
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn without_env(mut self, key: impl Into<String>) -> Self {
+        self.env_remove.push(key.into());
+        self
+    }
+
+    /// Pass only the variables set with `with_env`
+    #[must_use]
+    pub fn with_env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self
+    }
+
+    #[must_use]
+    pub fn with_current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// The command for `prompt`, with stdout and stderr piped
+    fn command(&self, prompt: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        match self.prompt_input {
+            PromptInput::Stdin => {
+                command.args(&self.args).stdin(Stdio::piped());
+            }
+            PromptInput::Argument => {
+                if self.args.iter().any(|arg| arg.contains(PROMPT_PLACEHOLDER)) {
+                    command.args(self.args.iter().map(|arg| arg.replace(PROMPT_PLACEHOLDER, prompt)));
+                } else {
+                    command.args(&self.args).arg(prompt);
+                }
+                command.stdin(Stdio::null());
+            }
+        }
+        if self.env_clear {
+            command.env_clear();
+        }
+        for key in &self.env_remove {
+            command.env_remove(key);
+        }
+        command.envs(self.env.iter().map(|(k, v)| (k, v)));
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        command.stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true);
+        command
+    }
+}
+
+/// Runs a local command per prompt; see the module docs
+#[derive(Debug, Clone)]
+pub struct ProcessClient {
+    config: ProcessConfig,
+}
+
+impl ProcessClient {
+    pub fn new(config: ProcessConfig) -> Self {
+        info!(program = %config.program, "Creating new process client");
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ProcessConfig {
+        &self.config
+    }
+
+    /// Start the command and hand it the prompt. Stdin is written from a separate task so
+    /// a command that answers before reading all of it cannot deadlock.
+    fn spawn(&self, prompt: String) -> Result<Child, AIError> {
+        let mut child = self.config.command(&prompt).spawn().map_err(|e| ProcessError::Spawn {
+            program: self.config.program.clone(),
+            message: e.to_string(),
+        })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            tokio::spawn(async move {
+                // A command that ignores stdin closes the pipe early; that is not an error
+                let _ = stdin.write_all(prompt.as_bytes()).await;
+            });
+        }
+        Ok(child)
+    }
+
+    #[instrument(name = "process", target = "semantic_query::http", skip_all, fields(provider = "process", program = %self.config.program))]
+    async fn run(&self, prompt: String) -> Result<String, AIError> {
+        let child = self.spawn(prompt)?;
+        let output = match self.config.timeout {
+            Some(limit) => tokio::time::timeout(limit, child.wait_with_output()).await
+                .map_err(|_| ProcessError::Timeout(limit))?,
+            None => child.wait_with_output().await,
+        }
+        .map_err(|e| ProcessError::Io(e.to_string()))?;
+        debug!(status = ?output.status.code(), stdout_len = output.stdout.len(), "Command finished");
+        if !output.status.success() {
+            return Err(exit_error(output.status.code(), &output.stderr).into());
+        }
+        String::from_utf8(output.stdout).map_err(|e| ProcessError::Io(format!("stdout is not UTF-8: {e}")).into())
+    }
+}
+
+fn exit_error(code: Option<i32>, stderr: &[u8]) -> ProcessError {
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim();
+    let start = stderr.len().saturating_sub(STDERR_TAIL);
+    let start = (start..stderr.len()).find(|&i| stderr.is_char_boundary(i)).unwrap_or(stderr.len());
+    warn!(code = ?code, "Command failed");
+    ProcessError::Exit { code, stderr: stderr[start..].to_string() }
+}
+
+#[async_trait]
+impl LowLevelClient for ProcessClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.run(prompt).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> { Box::new(self.clone()) }
+
+    fn provider(&self) -> Option<&'static str> { Some("process") }
+
+    fn as_any(&self) -> Option<&dyn Any> { Some(self) }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { Some(self) }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { supports_streaming: self.config.streaming, ..Capabilities::default() }
+    }
+
+    /// Each stdout line becomes a text delta as soon as the command writes it; the stream
+    /// ends with an error if the command fails or exceeds its timeout
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        if !self.config.streaming {
+            return None;
+        }
+        let client = self.clone();
+        let s = async_stream::try_stream! {
+            let deadline = client.config.timeout.map(|limit| (Instant::now() + limit, limit));
+            let mut child = client.spawn(prompt)?;
+            let stdout = child.stdout.take().ok_or_else(|| ProcessError::Io("stdout not captured".into()))?;
+            let mut stderr = child.stderr.take().ok_or_else(|| ProcessError::Io("stderr not captured".into()))?;
+            let errors = tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let _ = stderr.read_to_end(&mut buf).await;
+                buf
+            });
+
+            let mut lines = BufReader::new(stdout);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let read = lines.read_line(&mut line);
+                let read = match deadline {
+                    Some((at, limit)) => tokio::time::timeout_at(at, read).await.map_err(|_| ProcessError::Timeout(limit))?,
+                    None => read.await,
+                };
+                if read.map_err(|e| ProcessError::Io(e.to_string()))? == 0 {
+                    break;
+                }
+                yield sse_delta(&line);
+            }
+            let status = match deadline {
+                Some((at, limit)) => tokio::time::timeout_at(at, child.wait()).await.map_err(|_| ProcessError::Timeout(limit))?,
+                None => child.wait().await,
+            }
+            .map_err(|e| ProcessError::Io(e.to_string()))?;
+            if !status.success() {
+                let stderr = errors.await.unwrap_or_default();
+                Err(AIError::from(exit_error(status.code(), &stderr)))?;
+            }
+            yield bytes::Bytes::from_static(SSE_DONE);
+        };
+        Some(Box::pin(s))
+    }
+}
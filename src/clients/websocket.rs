@@ -0,0 +1,136 @@
+//! Client for models that stream over a WebSocket, such as the OpenAI Realtime API.
+//!
+//! The socket protocol is a `streaming::ws::WsDecoder`; streamed responses go through
+//! `sse_from_ws_frames`, so `stream_query` and friends behave as with SSE providers.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures_core::stream::Stream;
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, instrument};
+
+use crate::config::KeyFromEnv;
+use crate::core::{Capabilities, LowLevelClient, RawByteStream};
+use crate::correlation;
+use crate::error::AIError;
+use crate::streaming::ws::{sse_from_ws_frames, OpenAIRealtimeDecoder, WsDecoder, WsEvent};
+
+/// Reads `OPENAI_API_KEY` for `WsClient::openai_realtime_from_env`
+struct RealtimeKey;
+
+impl KeyFromEnv for RealtimeKey {
+    const KEY_NAME: &'static str = "OPENAI_API_KEY";
+}
+
+/// Streams responses over a WebSocket, one connection per request
+#[derive(Debug, Clone)]
+pub struct WsClient {
+    url: String,
+    headers: Vec<(String, String)>,
+    decoder: Arc<dyn WsDecoder>,
+}
+
+impl WsClient {
+    /// A client for the `ws://` or `wss://` endpoint at `url` speaking `decoder`'s protocol
+    pub fn new(url: impl Into<String>, decoder: impl WsDecoder + 'static) -> Self {
+        Self { url: url.into(), headers: Vec::new(), decoder: Arc::new(decoder) }
+    }
+
+    /// The OpenAI Realtime API's text channel for `model`, e.g. `gpt-4o-realtime-preview`
+    pub fn openai_realtime(api_key: impl AsRef<str>, model: impl AsRef<str>) -> Self {
+        info!(model = model.as_ref(), "Creating new OpenAI Realtime client");
+        Self::new(format!("wss://api.openai.com/v1/realtime?model={}", model.as_ref()), OpenAIRealtimeDecoder::default())
+            .with_header("Authorization", format!("Bearer {}", api_key.as_ref()))
+            .with_header("OpenAI-Beta", "realtime=v1")
+    }
+
+    /// `openai_realtime` with the key from `OPENAI_API_KEY`
+    pub fn openai_realtime_from_env(model: impl AsRef<str>) -> Result<Self, AIError> {
+        Ok(Self::openai_realtime(RealtimeKey::require_key()?, model))
+    }
+
+    /// Send `name: value` with the upgrade request
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Connect, send the decoder's opening frames for `prompt`, and yield the server's
+    /// text frames until it closes the socket
+    fn frames(&self, prompt: String) -> impl Stream<Item = Result<String, AIError>> + Send + 'static {
+        let (url, headers, decoder) = (self.url.clone(), self.headers.clone(), self.decoder.clone());
+        async_stream::try_stream! {
+            let mut request = url.as_str().into_client_request().map_err(ws_error)?;
+            let correlation_id = correlation::current().map(|id| (correlation::HEADER.to_string(), id.as_str().to_string()));
+            for (name, value) in headers.into_iter().chain(correlation_id) {
+                let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| AIError::Configuration(e.to_string()))?;
+                let value = HeaderValue::from_str(&value).map_err(|e| AIError::Configuration(e.to_string()))?;
+                request.headers_mut().append(name, value);
+            }
+            let (mut socket, _) = tokio_tungstenite::connect_async(request).await.map_err(ws_error)?;
+            debug!(url = %url, "WebSocket connected");
+            for frame in decoder.open(&prompt) {
+                socket.send(Message::Text(frame)).await.map_err(ws_error)?;
+            }
+            while let Some(message) = socket.next().await {
+                match message.map_err(ws_error)? {
+                    Message::Text(text) => yield text,
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    #[instrument(name = "ws", target = "semantic_query::http", skip_all, fields(provider = "websocket", url = %self.url))]
+    async fn complete(&self, prompt: String) -> Result<String, AIError> {
+        let mut frames = Box::pin(self.frames(prompt));
+        let mut reply = String::new();
+        while let Some(frame) = frames.next().await {
+            match self.decoder.decode(&frame?) {
+                WsEvent::Delta(text) => reply.push_str(&text),
+                WsEvent::Done => break,
+                WsEvent::Error(error) => return Err(error),
+                WsEvent::Skip => {}
+            }
+        }
+        Ok(reply)
+    }
+}
+
+fn ws_error(error: tokio_tungstenite::tungstenite::Error) -> AIError {
+    AIError::Mock(format!("WebSocket error: {error}"))
+}
+
+#[async_trait]
+impl LowLevelClient for WsClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.complete(prompt).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> { Box::new(self.clone()) }
+
+    fn provider(&self) -> Option<&'static str> { Some("websocket") }
+
+    fn as_any(&self) -> Option<&dyn Any> { Some(self) }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { Some(self) }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { supports_streaming: true, ..Capabilities::default() }
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        Some(sse_from_ws_frames(self.frames(prompt), self.decoder.clone()))
+    }
+}
@@ -0,0 +1,186 @@
+//! Per-field confidence from token logprobs.
+//!
+//! With `QueryResolver::with_field_confidence(true)`, clients that support it (OpenAI,
+//! Azure OpenAI and OpenAI-compatible servers) are asked for token logprobs, and every
+//! extracted data item gets a `confidence_map`: for each field, keyed by JSON pointer
+//! (`/title`, `/tags/0`), `exp` of the mean logprob of the tokens that produced its
+//! value. That is a probability-like score in `0..=1`, suited to thresholding:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, ResponseItem, RetryConfig};
+//! # async fn demo(resolver: QueryResolver<semantic_query::clients::CompatClient>) -> Result<(), semantic_query::error::QueryResolverError> {
+//! # #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema, Clone, Debug)] struct Invoice { total: f64 }
+//! let response = resolver.with_field_confidence(true).query::<Invoice>("...".into()).await?;
+//! for item in &response.items {
+//!     if let ResponseItem::Data { confidence_map: Some(confidence), .. } = item {
+//!         if confidence.get("/total").is_some_and(|c| *c < 0.8) {
+//!             // send to review
+//!         }
+//!     }
+//! }
+//! # Ok(()) }
+//! ```
+//!
+//! Clients without logprobs, streamed queries, and replies rewritten before extraction
+//! (normalizers, merged maps) leave `confidence_map` empty. Custom clients report
+//! logprobs through `stats::record_logprobs`.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{ParsedResponse, ResponseItem};
+
+/// Field confidence by JSON pointer into a data item
+pub type ConfidenceMap = BTreeMap<String, f64>;
+
+/// One generated token and its log probability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+}
+
+/// Confidence for every value inside the JSON at `json` (a byte range of `reply`), from
+/// the `tokens` that produced `reply`. `None` when the tokens do not spell out `reply`
+/// or the JSON cannot be walked.
+pub fn field_confidence(reply: &str, json: Range<usize>, tokens: &[TokenLogprob]) -> Option<ConfidenceMap> {
+    let mut offsets = Vec::with_capacity(tokens.len());
+    let mut end = 0;
+    for token in tokens {
+        let start = end;
+        end += token.token.len();
+        offsets.push((start..end, token.logprob));
+    }
+    if end != reply.len() || tokens.iter().map(|t| t.token.as_str()).collect::<String>() != reply {
+        return None;
+    }
+
+    let text = reply.get(json.clone())?;
+    let mut spans = Vec::new();
+    let mut walker = Walker { text: text.as_bytes(), pos: 0 };
+    walker.value(String::new(), &mut spans)?;
+
+    let mut map = ConfidenceMap::new();
+    for (pointer, span) in spans.into_iter().filter(|(pointer, _)| !pointer.is_empty()) {
+        let (start, end) = (json.start + span.start, json.start + span.end);
+        let logprobs: Vec<f64> = offsets.iter()
+            .filter(|(range, _)| range.start < end && range.end > start)
+            .map(|(_, logprob)| *logprob)
+            .collect();
+        if !logprobs.is_empty() {
+            let mean = logprobs.iter().sum::<f64>() / logprobs.len() as f64;
+            map.insert(pointer, mean.exp());
+        }
+    }
+    Some(map)
+}
+
+/// Fill `confidence_map` on the data items of `response` that lie in the reply starting
+/// at byte `reply_start` of `raw`
+pub(crate) fn annotate<T>(response: &mut ParsedResponse<T>, raw: &str, reply_start: usize, tokens: &[TokenLogprob]) {
+    let Some(reply) = raw.get(reply_start..) else { return };
+    let mut cursor = 0;
+    for item in &mut response.items {
+        let text = match item {
+            ResponseItem::Data { original_text, .. } => original_text.as_str(),
+            ResponseItem::Text(text) => text.text.as_str(),
+        };
+        // Items are in reply order, so each one is found after the previous
+        let Some(found) = raw.get(cursor..).and_then(|rest| rest.find(text)) else { continue };
+        let start = cursor + found;
+        cursor = start + text.len();
+        if let ResponseItem::Data { confidence_map, .. } = item {
+            if start >= reply_start {
+                let json = start - reply_start..cursor - reply_start;
+                *confidence_map = field_confidence(reply, json, tokens);
+            }
+        }
+    }
+}
+
+/// Records the byte span of every value in a JSON document by JSON pointer
+struct Walker<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Walker<'_> {
+    fn skip_ws(&mut self) {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, pointer: String, spans: &mut Vec<(String, Range<usize>)>) -> Option<()> {
+        self.skip_ws();
+        let start = self.pos;
+        match *self.text.get(self.pos)? {
+            b'{' => {
+                self.pos += 1;
+                loop {
+                    self.skip_ws();
+                    match *self.text.get(self.pos)? {
+                        b'}' => break,
+                        b',' => self.pos += 1,
+                        b'"' => {
+                            let key = self.string()?;
+                            self.skip_ws();
+                            (self.text.get(self.pos)? == &b':').then_some(())?;
+                            self.pos += 1;
+                            let key = key.replace('~', "~0").replace('/', "~1");
+                            self.value(format!("{pointer}/{key}"), spans)?;
+                        }
+                        _ => return None,
+                    }
+                }
+                self.pos += 1;
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut index = 0;
+                loop {
+                    self.skip_ws();
+                    match *self.text.get(self.pos)? {
+                        b']' => break,
+                        b',' => self.pos += 1,
+                        _ => {
+                            self.value(format!("{pointer}/{index}"), spans)?;
+                            index += 1;
+                        }
+                    }
+                }
+                self.pos += 1;
+            }
+            b'"' => {
+                self.string()?;
+            }
+            _ => {
+                while self.text.get(self.pos).is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace()) {
+                    self.pos += 1;
+                }
+                (self.pos > start).then_some(())?;
+            }
+        }
+        spans.push((pointer, start..self.pos));
+        Some(())
+    }
+
+    /// The string starting at `pos`, unescaped well enough for a pointer segment
+    fn string(&mut self) -> Option<String> {
+        let start = self.pos + 1;
+        self.pos = start;
+        loop {
+            match *self.text.get(self.pos)? {
+                b'\\' => self.pos += 2,
+                b'"' => break,
+                _ => self.pos += 1,
+            }
+        }
+        let raw = std::str::from_utf8(&self.text[start..self.pos]).ok()?;
+        self.pos += 1;
+        Some(serde_json::from_str(&format!("\"{raw}\"")).unwrap_or_else(|_| raw.to_string()))
+    }
+}
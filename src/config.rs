@@ -1,8 +1,17 @@
+use std::collections::HashMap;
 use std::env;
-use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::Duration;
+use serde::Deserialize;
+use crate::error::AIError;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{self, Write};
+#[cfg(not(target_arch = "wasm32"))]
 use std::thread;
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs::OpenOptions;
+#[cfg(not(target_arch = "wasm32"))]
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent},
     terminal,
@@ -23,15 +32,33 @@ pub trait KeyFromEnv {
         env::var(Self::KEY_NAME).ok()
     }
     
+    /// Find the API key, returning a configuration error naming the missing variable
+    fn require_key() -> Result<String, AIError> {
+        Self::find_key()
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| AIError::Configuration(format!("{} is not set in the environment or .env", Self::KEY_NAME)))
+    }
+
     /// Find the API key with user fallback - waits 15 seconds for user input then panics
+    #[cfg(not(target_arch = "wasm32"))]
     fn find_key_with_user() -> String {
+        match Self::try_find_key_with_user() {
+            Ok(key) => key,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Find the API key with user fallback - waits 15 seconds for user input and
+    /// returns a configuration error on timeout
+    #[cfg(not(target_arch = "wasm32"))]
+    fn try_find_key_with_user() -> Result<String, AIError> {
         if let Some(key) = Self::find_key() {
-            return key;
+            return Ok(key);
         }
         
         // Prompt user for input with timeout
         print!("Environment variable {} not found. Please enter the API key (15 second timeout): ", Self::KEY_NAME);
-        io::stdout().flush().unwrap();
+        let _ = io::stdout().flush();
         
         // Create a channel for communication between threads
         let (sender, receiver) = std::sync::mpsc::channel();
@@ -47,7 +74,7 @@ pub trait KeyFromEnv {
         // Wait for input with timeout
         let api_key = match receiver.recv_timeout(Duration::from_secs(15)) {
             Ok(input) if !input.is_empty() => input,
-            _ => panic!("Timeout waiting for {} input after 15 seconds", Self::KEY_NAME),
+            _ => return Err(AIError::Configuration(format!("Timeout waiting for {} input after 15 seconds", Self::KEY_NAME))),
         };
         
         // Ask if user wants to save to .env file
@@ -59,11 +86,12 @@ pub trait KeyFromEnv {
             }
         }
         
-        api_key
+        Ok(api_key)
     }
     
     /// Prompt user if they want to save the API key to .env file
     /// Uses single keystroke detection with fallback to Enter
+    #[cfg(not(target_arch = "wasm32"))]
     fn prompt_save_to_env() -> bool {
         print!("Add {} to .env file? (y/N): ", Self::KEY_NAME);
         io::stdout().flush().unwrap();
@@ -84,6 +112,7 @@ pub trait KeyFromEnv {
     }
     
     /// Attempt to read a single keystroke
+    #[cfg(not(target_arch = "wasm32"))]
     fn read_single_key() -> Result<String, Box<dyn std::error::Error>> {
         // Enable raw mode temporarily
         terminal::enable_raw_mode()?;
@@ -108,6 +137,7 @@ pub trait KeyFromEnv {
     }
     
     /// Save the API key to .env file
+    #[cfg(not(target_arch = "wasm32"))]
     fn save_to_env_file(api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
         let env_line = format!("{}={}\n", Self::KEY_NAME, api_key);
         
@@ -131,3 +161,252 @@ pub trait KeyFromEnv {
         Ok(())
     }
 }
+
+
+// =============== Configuration file (semantic-query.toml) ===============
+
+/// Environment variable pointing at an explicit configuration file
+pub const CONFIG_PATH_ENV: &str = "SEMANTIC_QUERY_CONFIG";
+
+/// File names searched (in order) in the working directory when `SEMANTIC_QUERY_CONFIG` is unset
+pub const CONFIG_FILE_NAMES: &[&str] = &["semantic-query.toml", "semantic-query.yaml", "semantic-query.yml"];
+
+/// Per-provider section of the configuration file.
+///
+/// ```toml
+/// [deepseek]
+/// api_key = "sk-..."
+/// model = "deepseek-chat"
+/// max_tokens = 2048
+/// temperature = 0.2
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProviderSection {
+    pub api_key: Option<String>,
+    /// Base URL: the Azure resource endpoint or OpenAI-compatible server, and an
+    /// override of the public API (e.g. a proxy) for `anthropic`, `deepseek` and `openai`
+    pub endpoint: Option<String>,
+    /// Provider model id (e.g. `claude-3-5-haiku-20241022`, `gpt-4o-mini`)
+    pub model: Option<String>,
+    /// Azure deployment name
+    pub deployment: Option<String>,
+    /// Azure API version
+    pub api_version: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    /// Sampling seed, for providers that accept one (OpenAI, Azure, OpenAI-compatible)
+    pub seed: Option<u64>,
+}
+
+/// Retry policy
+///
+/// ```toml
+/// [retry]
+/// default_max_retries = 1
+/// [retry.max_retries]
+/// rate_limit = 3
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RetrySection {
+    pub default_max_retries: Option<usize>,
+    /// Per error class limits, keyed like `RetryConfig::max_retries`
+    pub max_retries: HashMap<String, usize>,
+}
+
+impl RetrySection {
+    /// Overlay this section on top of `RetryConfig::default()`
+    #[must_use]
+    pub fn to_retry_config(&self) -> crate::core::RetryConfig {
+        let mut config = crate::core::RetryConfig::default();
+        if let Some(n) = self.default_max_retries {
+            config.default_max_retries = n;
+        }
+        config.max_retries.extend(self.max_retries.iter().map(|(k, v)| (k.clone(), *v)));
+        config
+    }
+}
+
+/// Settings loaded from `semantic-query.toml` (or `.yaml` with the `config-yaml`
+/// feature), merged with environment overrides.
+///
+/// Environment variables always win over the file so that deployments can inject
+/// secrets without editing it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SemanticQueryConfig {
+    /// Preferred provider (`claude`, `deepseek`, `openai`, `compat`, `mock`)
+    pub default_provider: Option<String>,
+    pub anthropic: ProviderSection,
+    pub deepseek: ProviderSection,
+    pub openai: ProviderSection,
+    pub azure: ProviderSection,
+    /// Any OpenAI-compatible server; `endpoint` is its base URL
+    pub openai_compatible: ProviderSection,
+    pub retry: RetrySection,
+    /// Path the settings were read from, if any
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+/// Load configuration from `SEMANTIC_QUERY_CONFIG` or the first of `CONFIG_FILE_NAMES`
+/// found in the working directory, then apply environment overrides.
+///
+/// A missing file is not an error: the result then reflects the environment only.
+pub fn load() -> Result<SemanticQueryConfig, AIError> {
+    let _ = dotenvy::dotenv();
+    let path = match env::var(CONFIG_PATH_ENV) {
+        Ok(p) => Some(PathBuf::from(p)),
+        Err(_) => CONFIG_FILE_NAMES.iter().map(PathBuf::from).find(|p| p.exists()),
+    };
+    let mut config = match path {
+        Some(p) => load_from_path(&p)?,
+        None => SemanticQueryConfig::default(),
+    };
+    config.apply_env_overrides();
+    Ok(config)
+}
+
+/// Parse a configuration file without applying environment overrides
+pub fn load_from_path(path: &Path) -> Result<SemanticQueryConfig, AIError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| AIError::Configuration(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut config = match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => parse_yaml(&content)?,
+        _ => toml::from_str::<SemanticQueryConfig>(&content)
+            .map_err(|e| AIError::Configuration(format!("Invalid config {}: {}", path.display(), e)))?,
+    };
+    config.source = Some(path.to_path_buf());
+    Ok(config)
+}
+
+#[cfg(feature = "config-yaml")]
+fn parse_yaml(content: &str) -> Result<SemanticQueryConfig, AIError> {
+    serde_yaml::from_str(content).map_err(|e| AIError::Configuration(format!("Invalid YAML config: {e}")))
+}
+
+#[cfg(not(feature = "config-yaml"))]
+fn parse_yaml(_content: &str) -> Result<SemanticQueryConfig, AIError> {
+    Err(AIError::Configuration("YAML configuration requires the `config-yaml` feature".into()))
+}
+
+impl SemanticQueryConfig {
+    /// Overlay well-known environment variables on top of the file values
+    pub fn apply_env_overrides(&mut self) {
+        fn set(slot: &mut Option<String>, var: &str) {
+            if let Ok(v) = env::var(var) {
+                if !v.is_empty() { *slot = Some(v); }
+            }
+        }
+        set(&mut self.default_provider, "SEMANTIC_QUERY_CLIENT");
+        set(&mut self.anthropic.api_key, "ANTHROPIC_API_KEY");
+        set(&mut self.deepseek.api_key, "DEEPSEEK_API_KEY");
+        set(&mut self.openai.api_key, "OPENAI_API_KEY");
+        set(&mut self.azure.api_key, "AZURE_OPENAI_API_KEY");
+        set(&mut self.azure.endpoint, "AZURE_OPENAI_ENDPOINT");
+        set(&mut self.azure.deployment, "AZURE_OPENAI_DEPLOYMENT");
+        set(&mut self.azure.api_version, "AZURE_OPENAI_API_VERSION");
+        set(&mut self.openai_compatible.api_key, "OPENAI_COMPAT_API_KEY");
+        set(&mut self.openai_compatible.endpoint, "OPENAI_COMPAT_BASE_URL");
+        set(&mut self.openai_compatible.model, "OPENAI_COMPAT_MODEL");
+    }
+
+    /// Retry policy declared in the `[retry]` section
+    #[must_use]
+    pub fn retry_config(&self) -> crate::core::RetryConfig {
+        self.retry.to_retry_config()
+    }
+
+    #[cfg(any(feature = "anthropic", feature = "deepseek", feature = "openai", feature = "azure"))]
+    fn required_key(section: &ProviderSection, name: &str) -> Result<String, AIError> {
+        section.api_key.clone().filter(|k| !k.is_empty()).ok_or_else(|| {
+            AIError::Configuration(format!("{name} is not set in the environment, .env, or config file"))
+        })
+    }
+
+    /// Claude configuration from the `[anthropic]` section
+    #[cfg(feature = "anthropic")]
+    pub fn claude_config(&self) -> Result<crate::clients::ClaudeConfig, AIError> {
+        use crate::clients::{ClaudeConfig, ClaudeModel};
+        let section = &self.anthropic;
+        let api_key = Self::required_key(section, "ANTHROPIC_API_KEY")?;
+        let model = match section.model.as_deref() {
+            Some(id) => ClaudeModel::from_model_id(id)
+                .ok_or_else(|| AIError::Configuration(format!("Unknown Claude model: {id}")))?,
+            None => ClaudeModel::default(),
+        };
+        let mut config = ClaudeConfig::anthropic(api_key, model);
+        if let Some(endpoint) = &section.endpoint { config.base_url = endpoint.clone(); }
+        if let Some(max_tokens) = section.max_tokens { config.max_tokens = max_tokens; }
+        config.temperature = section.temperature;
+        Ok(config)
+    }
+
+    /// DeepSeek configuration from the `[deepseek]` section
+    #[cfg(feature = "deepseek")]
+    pub fn deepseek_config(&self) -> Result<crate::clients::deepseek::DeepSeekConfig, AIError> {
+        use crate::clients::deepseek::{DeepSeekConfig, models::DeepSeekModel};
+        let section = &self.deepseek;
+        let mut config = DeepSeekConfig { api_key: Self::required_key(section, "DEEPSEEK_API_KEY")?, ..DeepSeekConfig::default() };
+        if let Some(model) = &section.model { config.model = DeepSeekModel::Override(model.clone()); }
+        if let Some(endpoint) = &section.endpoint { config.base_url = endpoint.clone(); }
+        if let Some(max_tokens) = section.max_tokens { config.max_tokens = max_tokens; }
+        if let Some(temperature) = section.temperature { config.temperature = temperature; }
+        Ok(config)
+    }
+
+    /// OpenAI configuration from the `[openai]` section
+    #[cfg(feature = "openai")]
+    pub fn openai_config(&self) -> Result<crate::clients::OpenAIConfig, AIError> {
+        use crate::clients::{OpenAIConfig, OpenAIModel};
+        let section = &self.openai;
+        let mut config = OpenAIConfig { api_key: Self::required_key(section, "OPENAI_API_KEY")?, ..OpenAIConfig::default() };
+        if let Some(model) = &section.model { config.model = OpenAIModel::Override(model.clone()); }
+        if let Some(endpoint) = &section.endpoint { config.base_url = endpoint.clone(); }
+        if let Some(max_tokens) = section.max_tokens { config.max_tokens = max_tokens; }
+        if let Some(temperature) = section.temperature { config.temperature = temperature; }
+        config.seed = section.seed;
+        Ok(config)
+    }
+
+    /// Azure OpenAI configuration from the `[azure]` section
+    #[cfg(feature = "azure")]
+    pub fn azure_config(&self) -> Result<crate::clients::AzureOpenAIConfig, AIError> {
+        use crate::clients::AzureOpenAIConfig;
+        let section = &self.azure;
+        let mut config = AzureOpenAIConfig { api_key: Self::required_key(section, "AZURE_OPENAI_API_KEY")?, ..AzureOpenAIConfig::default() };
+        config.endpoint = section.endpoint.clone()
+            .ok_or_else(|| AIError::Configuration("AZURE_OPENAI_ENDPOINT is not set in the environment, .env, or config file".into()))?;
+        if let Some(deployment) = &section.deployment { config.deployment = deployment.clone(); }
+        if let Some(api_version) = &section.api_version { config.api_version = api_version.clone(); }
+        if let Some(max_tokens) = section.max_tokens { config.max_tokens = max_tokens; }
+        if let Some(temperature) = section.temperature { config.temperature = temperature; }
+        config.seed = section.seed;
+        Ok(config)
+    }
+
+    /// OpenAI-compatible configuration from the `[openai_compatible]` section.
+    /// Only the base URL is required; path and auth header come from `OPENAI_COMPAT_PATH`
+    /// and `OPENAI_COMPAT_AUTH_HEADER` when set.
+    #[cfg(feature = "openai-compatible")]
+    pub fn compat_config(&self) -> Result<crate::clients::CompatConfig, AIError> {
+        use crate::clients::CompatConfig;
+        let section = &self.openai_compatible;
+        let mut config = CompatConfig::default();
+        config.base_url = section.endpoint.clone().filter(|u| !u.is_empty())
+            .ok_or_else(|| AIError::Configuration("OPENAI_COMPAT_BASE_URL is not set in the environment, .env, or config file".into()))?;
+        if let Some(api_key) = section.api_key.clone().filter(|k| !k.is_empty()) { config.api_key = Some(api_key); }
+        if let Some(model) = &section.model { config.model = model.clone(); }
+        if let Some(max_tokens) = section.max_tokens { config.max_tokens = max_tokens; }
+        if let Some(temperature) = section.temperature { config.temperature = temperature; }
+        config.seed = section.seed;
+        Ok(config)
+    }
+
+    /// Whether Azure should be preferred over plain OpenAI for the ChatGPT client
+    #[must_use]
+    pub fn prefers_azure(&self) -> bool {
+        self.azure.endpoint.is_some()
+    }
+}
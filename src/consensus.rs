@@ -0,0 +1,454 @@
+//! Merging repeated extractions of the same schema.
+//!
+//! `ParsedResponse::merge_with` combines two responses field by field,
+//! `QueryResolver::query_consensus` samples a prompt N times and votes per field
+//! (self-consistency), reporting how strongly the samples agreed,
+//! `QueryResolver::query_best_of` keeps the single highest-scoring sample, and
+//! `QueryResolver::compare` runs one prompt on two clients and diffs the results.
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver, ResponseItem};
+use crate::error::{DataExtractionError, QueryResolverError};
+use futures_util::future::{join, join_all};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use tracing::{info, warn, instrument};
+
+/// How `ParsedResponse::merge_with` combines two responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Keep every item from both responses, `self` first
+    #[default]
+    Append,
+    /// Pair data items by position; fields missing or null in `self` are taken from `other`
+    FillMissing,
+    /// Pair data items by position; non-null fields in `other` replace those in `self`
+    Overwrite,
+}
+
+impl<T> ParsedResponse<T>
+where
+    T: DeserializeOwned + JsonSchema + serde::Serialize + Clone,
+{
+    /// Merge `other` into this response.
+    ///
+    /// For the field-level strategies, text items from `self` are kept, data items are
+    /// merged pairwise, and unpaired data items from `other` are appended. A merged value
+    /// that no longer deserializes as `T` falls back to the item from `self`.
+    pub fn merge_with(self, other: ParsedResponse<T>, strategy: MergeStrategy) -> ParsedResponse<T> {
+        let safety = self.safety.or(other.safety);
+        let truncated = self.truncated || other.truncated;
+        let error = self.error.or(other.error);
+        let cost = self.cost.or(other.cost);
+        if strategy == MergeStrategy::Append {
+            let mut items = self.items;
+            items.extend(other.items);
+            return ParsedResponse { items, safety, truncated, error, cost };
+        }
+
+        let mut theirs = other.items.into_iter().filter_map(|item| match item {
+            ResponseItem::Data { data, .. } => Some(data),
+            ResponseItem::Text(_) => None,
+        });
+        let mut items: Vec<ResponseItem<T>> = self.items.into_iter().map(|item| match item {
+            ResponseItem::Data { data, original_text, confidence_map } => match theirs.next() {
+                Some(other_data) => {
+                    let merged = merge_data(&data, &other_data, strategy).unwrap_or(data);
+                    let original_text = serde_json::to_string(&merged).unwrap_or(original_text);
+                    ResponseItem::Data { data: merged, original_text, confidence_map: None }
+                }
+                None => ResponseItem::Data { data, original_text, confidence_map },
+            },
+            text => text,
+        }).collect();
+        items.extend(theirs.map(|data| {
+            let original_text = serde_json::to_string(&data).unwrap_or_default();
+            ResponseItem::Data { data, original_text, confidence_map: None }
+        }));
+        ParsedResponse { items, safety, truncated, error, cost }
+    }
+}
+
+fn merge_data<T: DeserializeOwned + serde::Serialize>(ours: &T, theirs: &T, strategy: MergeStrategy) -> Option<T> {
+    let mut base = serde_json::to_value(ours).ok()?;
+    let overlay = serde_json::to_value(theirs).ok()?;
+    merge_values(&mut base, overlay, strategy);
+    serde_json::from_value(base).ok()
+}
+
+fn merge_values(base: &mut Value, overlay: Value, strategy: MergeStrategy) {
+    match (base, overlay) {
+        (Value::Object(ours), Value::Object(theirs)) => {
+            for (key, value) in theirs {
+                match ours.get_mut(&key) {
+                    Some(existing) if !existing.is_null() => merge_values(existing, value, strategy),
+                    _ => { ours.insert(key, value); }
+                }
+            }
+        }
+        (slot, value) => {
+            if strategy == MergeStrategy::Overwrite && !value.is_null() {
+                *slot = value;
+            }
+        }
+    }
+}
+
+/// Outcome of `QueryResolver::query_consensus`
+#[derive(Debug, Clone)]
+pub struct Consensus<T> {
+    /// The per-field majority value
+    pub value: T,
+    /// Share of samples agreeing with the chosen value, keyed by JSON pointer ("" for the root)
+    pub field_agreement: BTreeMap<String, f64>,
+    /// Share of samples whose whole value equals `value`
+    pub agreement: f64,
+    /// Every sample that produced data, for inspection
+    pub samples: Vec<ParsedResponse<T>>,
+}
+
+impl<T> Consensus<T> {
+    /// Fields whose agreement falls below `threshold`
+    pub fn disputed(&self, threshold: f64) -> Vec<&str> {
+        self.field_agreement.iter()
+            .filter(|(_, score)| **score < threshold)
+            .map(|(path, _)| path.as_str())
+            .collect()
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Run `query<T>()` `n` times concurrently and vote per field on the first data item
+    /// of each sample. Failed or empty samples are skipped; the query fails only if no
+    /// sample yields data.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len()))]
+    pub async fn query_consensus<T>(&self, prompt: String, n: usize) -> Result<Consensus<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        let runs = join_all((0..n.max(1)).map(|_| self.query::<T>(prompt.clone()))).await;
+
+        let mut samples = Vec::new();
+        let mut last_error = None;
+        for run in runs {
+            match run {
+                Ok(response) if response.has_data() => samples.push(response),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(error = %e, "Consensus sample failed");
+                    last_error = Some(e);
+                }
+            }
+        }
+        if samples.is_empty() {
+            return Err(last_error.unwrap_or(QueryResolverError::DataExtraction(DataExtractionError::NoDataFound)));
+        }
+
+        let values: Vec<Value> = samples.iter()
+            .filter_map(|s| s.first().and_then(|d| serde_json::to_value(d).ok()))
+            .collect();
+        let refs: Vec<&Value> = values.iter().collect();
+        let mut field_agreement = BTreeMap::new();
+        let voted = vote(&refs, String::new(), &mut field_agreement);
+
+        // Per-field voting can assemble an invalid combination; fall back to the whole-value winner
+        let value: T = match serde_json::from_value(voted.clone()) {
+            Ok(v) => v,
+            Err(_) => {
+                let (winner, _) = majority(&refs);
+                serde_json::from_value(winner.clone())
+                    .map_err(|e| QueryResolverError::JsonDeserialization(e, winner.to_string()))?
+            }
+        };
+        let chosen = serde_json::to_value(&value).unwrap_or(voted);
+        let agreement = values.iter().filter(|v| **v == chosen).count() as f64 / values.len() as f64;
+
+        info!(samples = samples.len(), requested = n, agreement, "Consensus query completed");
+        Ok(Consensus { value, field_agreement, agreement, samples })
+    }
+}
+
+/// One sample from `query_best_of`
+#[derive(Debug)]
+pub struct Candidate<T> {
+    /// Sampling temperature, when the sample was drawn at an explicit one
+    pub temperature: Option<f32>,
+    /// Scorer output; `None` for failed samples
+    pub score: Option<f64>,
+    pub result: Result<ParsedResponse<T>, QueryResolverError>,
+}
+
+/// Outcome of `QueryResolver::query_best_of`
+#[derive(Debug)]
+pub struct BestOf<T> {
+    /// Index into `candidates` of the winning sample
+    pub best_index: usize,
+    pub candidates: Vec<Candidate<T>>,
+}
+
+impl<T> BestOf<T> {
+    /// The winning response
+    pub fn best(&self) -> &ParsedResponse<T> {
+        match &self.candidates[self.best_index].result {
+            Ok(response) => response,
+            Err(_) => unreachable!("best_index always points at a successful candidate"),
+        }
+    }
+
+    /// Take the winning response, discarding the other candidates
+    pub fn into_best(mut self) -> ParsedResponse<T> {
+        match self.candidates.swap_remove(self.best_index).result {
+            Ok(response) => response,
+            Err(_) => unreachable!("best_index always points at a successful candidate"),
+        }
+    }
+
+    pub fn best_score(&self) -> f64 {
+        self.candidates[self.best_index].score.unwrap_or_default()
+    }
+}
+
+/// Built-in scorer: 0 without data, otherwise the share of non-null top-level fields
+/// in the first data item (1.0 for non-object data).
+pub fn schema_compliance<T: serde::Serialize>(response: &ParsedResponse<T>) -> f64 {
+    let Some(first) = response.items.iter().find_map(|item| match item {
+        ResponseItem::Data { data, .. } => Some(data),
+        ResponseItem::Text(_) => None,
+    }) else {
+        return 0.0;
+    };
+    match serde_json::to_value(first) {
+        Ok(Value::Object(map)) if !map.is_empty() => {
+            map.values().filter(|v| !v.is_null()).count() as f64 / map.len() as f64
+        }
+        Ok(_) => 1.0,
+        Err(_) => 0.0,
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Run `query<T>()` `n` times concurrently and keep the sample `scorer` ranks highest.
+    /// Use `schema_compliance` as the scorer when there is no domain-specific one.
+    pub async fn query_best_of<T, S>(&self, prompt: String, n: usize, scorer: S) -> Result<BestOf<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+        S: Fn(&ParsedResponse<T>) -> f64,
+    {
+        let runs = join_all((0..n.max(1)).map(|_| self.query::<T>(prompt.clone()))).await;
+        pick_best(runs.into_iter().map(|r| (None, r)).collect(), scorer)
+    }
+
+    /// Like `query_best_of`, drawing one sample per temperature. Clients that cannot
+    /// change temperature (see `LowLevelClient::with_temperature`) sample at their default.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt, scorer), fields(prompt_len = prompt.len()))]
+    pub async fn query_best_of_with_temperatures<T, S>(&self, prompt: String, temperatures: &[f32], scorer: S) -> Result<BestOf<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+        S: Fn(&ParsedResponse<T>) -> f64,
+    {
+        let resolvers: Vec<(Option<f32>, QueryResolver<Box<dyn LowLevelClient>>)> = temperatures.iter()
+            .map(|&t| match self.client().with_temperature(t) {
+                Some(client) => (Some(t), self.with_client(client)),
+                None => {
+                    warn!(temperature = t, "Client does not support temperature overrides; using its default");
+                    (None, self.with_client(self.client().clone_box()))
+                }
+            })
+            .collect();
+        let runs = join_all(resolvers.iter().map(|(_, r)| r.query::<T>(prompt.clone()))).await;
+        let temps = resolvers.iter().map(|(t, _)| *t);
+        pick_best(temps.zip(runs).collect(), scorer)
+    }
+}
+
+fn pick_best<T, S>(runs: Vec<(Option<f32>, Result<ParsedResponse<T>, QueryResolverError>)>, scorer: S) -> Result<BestOf<T>, QueryResolverError>
+where
+    S: Fn(&ParsedResponse<T>) -> f64,
+{
+    let mut candidates: Vec<Candidate<T>> = runs.into_iter()
+        .map(|(temperature, result)| Candidate { temperature, score: result.as_ref().ok().map(&scorer), result })
+        .collect();
+
+    let best_index = candidates.iter().enumerate()
+        .filter_map(|(i, c)| c.score.map(|s| (i, s)))
+        .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(i, _)| i);
+
+    match best_index {
+        Some(best_index) => {
+            info!(candidates = candidates.len(), best_index, "Best-of query completed");
+            Ok(BestOf { best_index, candidates })
+        }
+        None => Err(candidates.pop()
+            .and_then(|c| c.result.err())
+            .unwrap_or(QueryResolverError::DataExtraction(DataExtractionError::NoDataFound))),
+    }
+}
+
+static NULL: Value = Value::Null;
+
+/// Vote recursively through objects; any other value is voted on as a whole
+fn vote(values: &[&Value], path: String, agreement: &mut BTreeMap<String, f64>) -> Value {
+    if !values.is_empty() && values.iter().all(|v| v.is_object()) {
+        let mut keys: Vec<&String> = Vec::new();
+        for v in values {
+            for key in v.as_object().into_iter().flat_map(Map::keys) {
+                if !keys.contains(&key) { keys.push(key); }
+            }
+        }
+        let mut out = Map::new();
+        for key in keys {
+            let field: Vec<&Value> = values.iter()
+                .map(|v| v.get(key.as_str()).unwrap_or(&NULL))
+                .collect();
+            let child_path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+            out.insert(key.clone(), vote(&field, child_path, agreement));
+        }
+        return Value::Object(out);
+    }
+
+    let (winner, count) = majority(values);
+    agreement.insert(path, count as f64 / values.len().max(1) as f64);
+    winner.clone()
+}
+
+/// Most common value and its count; ties go to the earliest sample
+fn majority<'a>(values: &[&'a Value]) -> (&'a Value, usize) {
+    let mut best: (&Value, usize) = (&NULL, 0);
+    for candidate in values {
+        let count = values.iter().filter(|v| *v == candidate).count();
+        if count > best.1 {
+            best = (candidate, count);
+        }
+    }
+    best
+}
+
+/// How a field compares between two extractions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum FieldStatus {
+    Equal,
+    Different,
+    /// Absent or null in `a`, set in `b`
+    MissingInA,
+    /// Set in `a`, absent or null in `b`
+    MissingInB,
+}
+
+/// One leaf field of a `compare` diff
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FieldDiff {
+    /// JSON pointer to the field ("" when `T` is not an object)
+    pub path: String,
+    pub status: FieldStatus,
+    pub a: Option<Value>,
+    pub b: Option<Value>,
+}
+
+/// Outcome of `QueryResolver::compare`
+#[derive(Debug)]
+pub struct Comparison<T> {
+    pub a: Result<ParsedResponse<T>, QueryResolverError>,
+    pub b: Result<ParsedResponse<T>, QueryResolverError>,
+    /// Field-level diff of the first data item of each side; a side that failed or
+    /// returned no data counts as missing every field
+    pub diff: Vec<FieldDiff>,
+}
+
+impl<T: JsonSchema + serde::Serialize + Clone> Comparison<T> {
+    /// Whether both sides produced data and every field is equal
+    pub fn is_identical(&self) -> bool {
+        first_data(&self.a).is_some() && first_data(&self.b).is_some()
+            && self.diff.iter().all(|d| d.status == FieldStatus::Equal)
+    }
+
+    /// Fields that are not equal
+    pub fn differences(&self) -> impl Iterator<Item = &FieldDiff> {
+        self.diff.iter().filter(|d| d.status != FieldStatus::Equal)
+    }
+
+    /// Share of fields that are equal (1.0 when there are no fields)
+    pub fn agreement(&self) -> f64 {
+        if self.diff.is_empty() {
+            return 1.0;
+        }
+        self.diff.iter().filter(|d| d.status == FieldStatus::Equal).count() as f64 / self.diff.len() as f64
+    }
+}
+
+/// Field-level diff of two values of the same schema. Objects and arrays are compared
+/// member by member; fields that are absent or null on both sides are left out.
+pub fn diff_fields<T: serde::Serialize>(a: Option<&T>, b: Option<&T>) -> Vec<FieldDiff> {
+    let a = a.and_then(|v| serde_json::to_value(v).ok()).unwrap_or(Value::Null);
+    let b = b.and_then(|v| serde_json::to_value(v).ok()).unwrap_or(Value::Null);
+    let mut out = Vec::new();
+    diff_value(&a, &b, String::new(), &mut out);
+    out
+}
+
+fn diff_value(a: &Value, b: &Value, path: String, out: &mut Vec<FieldDiff>) {
+    match (a, b) {
+        (Value::Object(ours), Value::Object(theirs)) => {
+            let keys: std::collections::BTreeSet<&String> = ours.keys().chain(theirs.keys()).collect();
+            for key in keys {
+                let child = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                diff_value(ours.get(key).unwrap_or(&Value::Null), theirs.get(key).unwrap_or(&Value::Null), child, out);
+            }
+        }
+        (Value::Array(ours), Value::Array(theirs)) => {
+            for i in 0..ours.len().max(theirs.len()) {
+                diff_value(ours.get(i).unwrap_or(&Value::Null), theirs.get(i).unwrap_or(&Value::Null), format!("{path}/{i}"), out);
+            }
+        }
+        // A container on one side only: report each of its fields as missing on the other
+        (Value::Object(_) | Value::Array(_), Value::Null) => diff_container(a, &path, FieldStatus::MissingInB, out),
+        (Value::Null, Value::Object(_) | Value::Array(_)) => diff_container(b, &path, FieldStatus::MissingInA, out),
+        (Value::Null, Value::Null) => {}
+        (_, Value::Null) => out.push(FieldDiff { path, status: FieldStatus::MissingInB, a: Some(a.clone()), b: None }),
+        (Value::Null, _) => out.push(FieldDiff { path, status: FieldStatus::MissingInA, a: None, b: Some(b.clone()) }),
+        _ => {
+            let status = if a == b { FieldStatus::Equal } else { FieldStatus::Different };
+            out.push(FieldDiff { path, status, a: Some(a.clone()), b: Some(b.clone()) });
+        }
+    }
+}
+
+fn first_data<T: JsonSchema + serde::Serialize + Clone>(side: &Result<ParsedResponse<T>, QueryResolverError>) -> Option<&T> {
+    side.as_ref().ok().and_then(ParsedResponse::first)
+}
+
+fn diff_container(present: &Value, path: &str, status: FieldStatus, out: &mut Vec<FieldDiff>) {
+    let mut fields = Vec::new();
+    diff_value(present, present, path.to_string(), &mut fields);
+    out.extend(fields.into_iter().map(|field| {
+        let value = field.a;
+        match status {
+            FieldStatus::MissingInA => FieldDiff { status, a: None, b: value, ..field },
+            _ => FieldDiff { status, a: value, b: None, ..field },
+        }
+    }));
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Answer `prompt` as a `T` with two clients concurrently and diff the first data
+    /// item of each, e.g. to A/B test providers or prompt changes. Both runs use this
+    /// resolver's configuration; a failure on one side is reported in `Comparison`
+    /// rather than returned.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt, client_a, client_b), fields(prompt_len = prompt.len()))]
+    pub async fn compare<T, A, B>(&self, prompt: String, client_a: A, client_b: B) -> Comparison<T>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+        A: LowLevelClient,
+        B: LowLevelClient,
+    {
+        let (resolver_a, resolver_b) = (self.with_client(client_a), self.with_client(client_b));
+        let (a, b) = join(resolver_a.query::<T>(prompt.clone()), resolver_b.query::<T>(prompt)).await;
+
+        let diff = diff_fields(first_data(&a), first_data(&b));
+        let comparison = Comparison { a, b, diff };
+        info!(fields = comparison.diff.len(), agreement = comparison.agreement(), "Comparison completed");
+        comparison
+    }
+}
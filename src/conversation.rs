@@ -0,0 +1,178 @@
+//! Multi-turn conversations over a `QueryResolver`.
+//!
+//! A `Conversation` keeps the message history between queries and sends the schema
+//! guidance for each target type once: the first query for `T` carries the full
+//! schema, later ones only reference it by name. With `SchemaPlacement::System` (and a
+//! client that honors system messages) the schemas live in a single system message.
+//! `with_prefill` starts every reply with fixed text, e.g. `{` to force JSON from Claude.
+
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use tracing::{debug, instrument};
+
+use crate::core::{schema_guidance, schema_instructions, ChatMessage, LowLevelClient, ParsedResponse, QueryResolver};
+use crate::error::QueryResolverError;
+use crate::correlation;
+use crate::schema::SchemaCache;
+use crate::shutdown;
+use crate::stats::QueryProbe;
+
+/// Message history plus the schemas already shown to the model
+pub struct Conversation<'r, C: LowLevelClient> {
+    resolver: &'r QueryResolver<C>,
+    history: Vec<ChatMessage>,
+    /// Schema ids already sent, inline or in the system message
+    sent_schemas: BTreeSet<String>,
+    /// Schema instructions carried by the system message, in first-use order
+    system_schemas: Vec<String>,
+    dedup_schemas: bool,
+    prefill: Option<String>,
+}
+
+impl<'r, C: LowLevelClient> Conversation<'r, C> {
+    pub fn new(resolver: &'r QueryResolver<C>) -> Self {
+        Self {
+            resolver,
+            history: Vec::new(),
+            sent_schemas: BTreeSet::new(),
+            system_schemas: Vec::new(),
+            dedup_schemas: true,
+            prefill: None,
+        }
+    }
+
+    /// Send the full schema only on the first query for each type (default: true).
+    /// Disable to repeat it inline on every turn.
+    pub fn with_schema_dedup(mut self, enabled: bool) -> Self {
+        self.dedup_schemas = enabled;
+        self
+    }
+
+    /// Start each reply with `prefill` (e.g. `{`), sent as the beginning of the
+    /// assistant's turn for the model to continue. Only clients reporting
+    /// `Capabilities::supports_prefill` use it; others send the prompt as usual.
+    pub fn with_prefill(mut self, prefill: impl Into<String>) -> Self {
+        self.prefill = Some(prefill.into());
+        self
+    }
+
+    /// User and assistant turns so far, as sent to the client
+    pub fn history(&self) -> &[ChatMessage] {
+        &self.history
+    }
+
+    /// Forget the history and which schemas were sent
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.sent_schemas.clear();
+        self.system_schemas.clear();
+    }
+
+    /// Ask a follow-up and extract `T` from the reply, like `QueryResolver::query`
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), turn = self.history.len() / 2, correlation_id = tracing::field::Empty))]
+    pub async fn query<T>(&mut self, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.resolver.enter()?;
+            let (sent_before, system_before) = (self.sent_schemas.clone(), self.system_schemas.len());
+            let mut probe = QueryProbe::start("conversation", &prompt).with_schema(&SchemaCache::global().get::<T>());
+            let prompt = self.with_schema::<T>(prompt);
+            let context = self.context();
+            let outcome = shutdown::track(in_flight.as_ref(), async {
+                match self.prefilled() {
+                    Some(resolver) => Self::exchange::<T, _>(&resolver, context, prompt.clone(), &mut probe).await,
+                    None => Self::exchange::<T, C>(self.resolver, context, prompt.clone(), &mut probe).await,
+                }
+            }).await;
+            let mut result = match outcome {
+                Ok((response, raw)) => {
+                    self.record(prompt, raw);
+                    Ok(response)
+                }
+                Err(e) => {
+                    // The schema never reached the model, so send it again next time
+                    self.sent_schemas = sent_before;
+                    self.system_schemas.truncate(system_before);
+                    Err(e)
+                }
+            };
+            self.resolver.report(probe, &mut result).await;
+            result
+        }).await
+    }
+
+    /// Ask a follow-up without schema guidance and return the raw reply
+    pub async fn ask(&mut self, prompt: String) -> Result<String, QueryResolverError> {
+        let (raw, _) = match self.prefilled() {
+            Some(resolver) => resolver.ask_moderated_in(self.context(), prompt.clone(), None).await?,
+            None => self.resolver.ask_moderated_in(self.context(), prompt.clone(), None).await?,
+        };
+        self.record(prompt, raw.clone());
+        Ok(raw)
+    }
+
+    /// The resolver over a client applying `prefill`, when one is set and supported
+    fn prefilled(&self) -> Option<QueryResolver<Box<dyn LowLevelClient>>> {
+        let client = self.resolver.client().with_prefill(self.prefill.as_deref()?);
+        if client.is_none() {
+            debug!("Client does not support assistant prefill; sending the prompt as is");
+        }
+        client.map(|client| self.resolver.with_client(client))
+    }
+
+    /// Send `prompt` after `context` through `resolver` and extract `T` from the reply.
+    /// Post-processor corrections happen out of band; the accepted reply is what's recorded.
+    async fn exchange<T, D: LowLevelClient>(
+        resolver: &QueryResolver<D>,
+        context: Vec<ChatMessage>,
+        prompt: String,
+        probe: &mut QueryProbe,
+    ) -> Result<(ParsedResponse<T>, String), QueryResolverError>
+    where
+        T: DeserializeOwned + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        let (raw, safety) = resolver.ask_moderated_in(context.clone(), prompt.clone(), None).await?;
+        probe.received(&raw);
+        resolver.finish::<T>(context, prompt, raw, safety, probe).await
+    }
+
+    /// Attach guidance for `T` to `prompt`: the full schema the first time, a reference after
+    fn with_schema<T: JsonSchema + 'static>(&mut self, prompt: String) -> String {
+        let name = T::schema_name();
+        let first_use = self.sent_schemas.insert(T::schema_id().into_owned());
+        if self.resolver.schema_in_system_role() {
+            if first_use {
+                self.system_schemas.push(format!("Schema `{}`:\n{}", name, schema_instructions::<T>()));
+            }
+            return format!("{}\n\nRespond with JSON matching the `{}` schema from the system message.", prompt, name);
+        }
+        if first_use || !self.dedup_schemas {
+            return schema_guidance::<T>(prompt);
+        }
+        debug!(schema = %name, "Referencing schema sent earlier in the conversation");
+        format!(
+            "{}\n\n## Response Format\nInclude valid JSON matching the `{}` schema given earlier in this conversation.",
+            prompt, name
+        )
+    }
+
+    /// Messages preceding the next prompt: the schema system message, then the history
+    fn context(&self) -> Vec<ChatMessage> {
+        let mut context = Vec::with_capacity(self.history.len() + 1);
+        if !self.system_schemas.is_empty() {
+            context.push(ChatMessage::system(self.system_schemas.join("\n\n")));
+        }
+        context.extend(self.history.iter().cloned());
+        context
+    }
+
+    fn record(&mut self, prompt: String, reply: String) {
+        self.history.push(ChatMessage::user(prompt));
+        self.history.push(ChatMessage::assistant(reply));
+    }
+}
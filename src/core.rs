@@ -5,29 +5,122 @@
 //! - **Recommended**: Use `QueryResolver::query<T>()` for schema-guided queries with mixed content
 //! - **Advanced**: Use `QueryResolver::query_mixed<T>()` for raw mixed content without schema  
 //! - **Streaming**: Use `QueryResolver::stream_query<T>()` for real-time token streaming
-//! - **Legacy methods** (`query_deserialized`, `query_with_schema`) are deprecated stubs
+//! - **JSON only**: Use `QueryResolver::query_typed<T>()` to get `T` directly from a JSON-only response
+//! - **Legacy methods** (`query_deserialized`, `query_with_schema`) are deprecated wrappers over `query<T>()`
 
 use crate::error::{QueryResolverError, AIError, DataExtractionError};
-use crate::streaming::{StreamItem, TextContent, build_parsed_stream};
+use crate::grammar::OutputConstraint;
+use crate::moderation::{Moderator, ModerationAction, ModerationTarget, SafetyReport};
+use std::sync::Arc;
+use std::any::Any;
+use crate::conversation::Conversation;
+use crate::correlation;
+use crate::cost::{self, CostBreakdown, PriceTable, Session};
+use crate::json_utils::{parse_candidate, DetectionScope};
+use crate::jsonpath::JsonPath;
+use crate::postprocess::{PostProcessor, PostProcessors};
+use crate::language::{self, Language, LanguageDetector, NgramDetector};
+use crate::refusal::{PhraseRefusalDetector, RefusalDetector};
+use crate::tables;
+use crate::request::ModelRequest;
+use crate::retry::{self, FailedAttempt, LengthRecovery, RetryBudget, RetryStrategy};
+use crate::audit::{QueryRecord, RecordCallback, RecordOutcome};
+use crate::stats::{observe_stream, QueryProbe, QueryStats, StatsCallback};
+use crate::shutdown::{self, InFlight, ShutdownHandle};
+use crate::spool::{SpoolConfig, SpooledResponse, SpooledText, Spooler};
+use crate::tenancy::Tenancy;
+use crate::normalize::Normalizers;
+use crate::confidence::ConfidenceMap;
+use crate::streaming::{FinishReason, Segment, Sourced, StreamItem, StreamOptions, TextContent, TextFidelity, Timed, segment_response, segment_response_with, stream_merge};
+use crate::streaming::markdown::MarkdownSegmenter;
 use std::fmt;
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::pin::Pin;
 use async_trait::async_trait;
-use tracing::{info, warn, debug, instrument};
-use schemars::{JsonSchema, schema_for};
+use tracing::{field, info, info_span, warn, debug, instrument, Instrument};
+use schemars::JsonSchema;
+use crate::schema::SchemaCache;
 use futures_core::Stream;
 use bytes::Bytes;
 
+/// Seed used by `QueryResolver::reproducible(true)`
+pub const REPRODUCIBLE_SEED: u64 = 42;
+
 /// Type alias for raw byte streams from AI providers
+#[cfg(not(target_arch = "wasm32"))]
 pub type RawByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, AIError>> + Send>>;
 
+/// Type alias for raw byte streams from AI providers (fetch streams are not `Send` on wasm)
+#[cfg(target_arch = "wasm32")]
+pub type RawByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, AIError>>>>;
+
 /// Type alias for parsed streaming results
+#[cfg(not(target_arch = "wasm32"))]
 pub type ParsedStreamResult<T> = Result<Pin<Box<dyn Stream<Item = Result<StreamItem<T>, QueryResolverError>> + Send>>, QueryResolverError>;
 
+/// Type alias for parsed streaming results
+#[cfg(target_arch = "wasm32")]
+pub type ParsedStreamResult<T> = Result<Pin<Box<dyn Stream<Item = Result<StreamItem<T>, QueryResolverError>>>>, QueryResolverError>;
+
+/// Type alias for timed streaming results
+#[cfg(not(target_arch = "wasm32"))]
+pub type TimedStreamResult<T> = Result<Pin<Box<dyn Stream<Item = Result<Timed<StreamItem<T>>, QueryResolverError>> + Send>>, QueryResolverError>;
+
+/// Type alias for timed streaming results
+#[cfg(target_arch = "wasm32")]
+pub type TimedStreamResult<T> = Result<Pin<Box<dyn Stream<Item = Result<Timed<StreamItem<T>>, QueryResolverError>>>>, QueryResolverError>;
+
+/// Type alias for the live half of `StreamCollect`
+#[cfg(not(target_arch = "wasm32"))]
+pub type CollectedItems<T> = Pin<Box<dyn Stream<Item = Result<StreamItem<T>, QueryResolverError>> + Send>>;
+
+/// Type alias for the live half of `StreamCollect`
+#[cfg(target_arch = "wasm32")]
+pub type CollectedItems<T> = Pin<Box<dyn Stream<Item = Result<StreamItem<T>, QueryResolverError>>>>;
+
+/// Type alias for the aggregate half of `StreamCollect`
+#[cfg(not(target_arch = "wasm32"))]
+pub type CollectedResponse<T> = Pin<Box<dyn std::future::Future<Output = Result<ParsedResponse<T>, QueryResolverError>> + Send>>;
+
+/// Type alias for the aggregate half of `StreamCollect`
+#[cfg(target_arch = "wasm32")]
+pub type CollectedResponse<T> = Pin<Box<dyn std::future::Future<Output = Result<ParsedResponse<T>, QueryResolverError>>>>;
+
+/// Live items and the complete response from a single streaming pass, returned by
+/// `QueryResolver::query_stream_collect`.
+///
+/// `response` resolves once `stream` has been driven to the end, so poll the stream
+/// first (or concurrently). If the stream fails or is dropped early, `response`
+/// resolves to `QueryResolverError::StreamInterrupted`, unless the stream hit its
+/// deadline with `StreamOptions::partial_on_deadline` set: then it holds the items
+/// completed so far, with `truncated` set.
+pub struct StreamCollect<T: JsonSchema> {
+    /// Tokens, text and data as they arrive
+    pub stream: CollectedItems<T>,
+    /// The `Text` and `Data` items of `stream`, in order. `original_text` holds each
+    /// item re-serialized, since streams do not keep source spans.
+    pub response: CollectedResponse<T>,
+}
+
+impl<T: JsonSchema> StreamCollect<T> {
+    /// Drive `stream` to the end, discarding the live items, and return `response`
+    pub async fn finish(self) -> Result<ParsedResponse<T>, QueryResolverError> {
+        let mut stream = self.stream;
+        while futures_util::StreamExt::next(&mut stream).await.is_some() {}
+        self.response.await
+    }
+}
+
 /// A single item in an LLM response - either structured data or explanatory text
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Serialized with the same adjacently tagged layout as `StreamItem`:
+/// `{"kind": "Data", "content": {"data": <T>, "original_text": "..."}}` or
+/// `{"kind": "Text", "content": {"text": "..."}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", content = "content")]
 pub enum ResponseItem<T> {
     /// Structured data that was successfully parsed from JSON
     /// Contains both the parsed data and the original JSON string
@@ -35,20 +128,59 @@ pub enum ResponseItem<T> {
         /// The parsed structured data
         data: T, 
         /// The original JSON string that was parsed
-        original_text: String 
+        original_text: String,
+        /// Confidence per field, by JSON pointer, when the resolver asked for it and the
+        /// client reported logprobs (see `confidence`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        confidence_map: Option<ConfidenceMap>,
     },
     /// Explanatory text content from the LLM
     Text(TextContent),
 }
 
+/// How `QueryResolver` combines multiple data items found in one response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtractionPolicy {
+    /// Keep every data item separately (the default)
+    #[default]
+    KeepAll,
+    /// Union all emitted JSON objects into one `T`; useful for map targets like
+    /// `HashMap<String, V>` when the model splits records across several objects
+    MergeMaps { on_conflict: KeyConflict },
+}
+
+/// Which value wins when merged maps share a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyConflict {
+    #[default]
+    KeepLast,
+    KeepFirst,
+}
+
 /// Complete LLM response with mixed content (text + structured data)
-#[derive(Debug, Clone)]
+///
+/// Serialized as `{"items": [ResponseItem...], "safety": {...}}`; `safety` is omitted
+/// when no moderator ran.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParsedResponse<T> {
     /// All items in order (text and data)
     pub items: Vec<ResponseItem<T>>,
+    /// Moderation verdicts, present when the resolver has a `Moderator`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety: Option<SafetyReport>,
+    /// The response was cut short and holds only the items completed before; see
+    /// `StreamOptions::partial_on_deadline`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Why a truncated response was cut short
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// What the query cost, when the resolver has a `PriceTable` (see `cost`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CostBreakdown>,
 }
 
-impl<T: JsonSchema + serde::Serialize + Clone> ParsedResponse<T> {
+impl<T> ParsedResponse<T> {
     /// Get only the structured data items
     pub fn data_only(&self) -> Vec<&T> {
         self.items.iter().filter_map(|item| match item {
@@ -106,20 +238,116 @@ impl<T: JsonSchema + serde::Serialize + Clone> ParsedResponse<T> {
         self.data_only().len()
     }
     
+    /// Merge all object-shaped data items into one, placed where the first data item was.
+    ///
+    /// Leaves the response unchanged when there are fewer than two data items, when an
+    /// item is not a JSON object, or when the union does not deserialize as `T`.
+    pub fn merge_maps(self, on_conflict: KeyConflict) -> Self
+    where
+        T: DeserializeOwned + serde::Serialize,
+    {
+        if self.data_count() < 2 {
+            return self;
+        }
+        let values: Vec<serde_json::Value> = self.data_only().into_iter()
+            .filter_map(|data| serde_json::to_value(data).ok())
+            .collect();
+        if values.len() != self.data_count() || !values.iter().all(serde_json::Value::is_object) {
+            return self;
+        }
+        let mut merged = serde_json::Map::new();
+        for value in values {
+            let serde_json::Value::Object(map) = value else { continue };
+            for (key, value) in map {
+                if on_conflict == KeyConflict::KeepLast || !merged.contains_key(&key) {
+                    merged.insert(key, value);
+                }
+            }
+        }
+        let merged_value = serde_json::Value::Object(merged);
+        let Ok(data) = serde_json::from_value::<T>(merged_value.clone()) else {
+            warn!("Merged maps do not deserialize as the target type; keeping separate items");
+            return self;
+        };
+
+        let mut data = Some(data);
+        let items = self.items.into_iter().filter_map(|item| match item {
+            ResponseItem::Data { .. } => data.take().map(|data| ResponseItem::Data { data, original_text: merged_value.to_string(), confidence_map: None }),
+            text => Some(text),
+        }).collect();
+        Self { items, safety: self.safety, truncated: self.truncated, error: self.error, cost: self.cost }
+    }
+
+    /// Build a response from segments of `raw`, keeping each data item's source text
+    /// verbatim instead of re-serializing it
+    pub(crate) fn from_segments(raw: &str, segments: Vec<Segment<T>>) -> Self {
+        let items = segments.into_iter().map(|segment| match segment {
+            Segment::Data(data, range) => ResponseItem::Data { data, original_text: raw[range].to_string(), confidence_map: None },
+            Segment::Text(range) => ResponseItem::Text(TextContent { text: raw[range].to_string(), block: None }),
+        }).collect();
+
+        Self { items, safety: None, truncated: false, error: None, cost: None }
+    }
+
+    /// Parse a raw model response exactly as `QueryResolver::query_mixed` does
+    pub fn from_raw(raw: &str) -> Self
+    where
+        T: DeserializeOwned,
+    {
+        Self::from_raw_with(raw, TextFidelity::default())
+    }
+
+    /// `from_raw`, keeping text as `fidelity` says. With `TextFidelity::Exact` the text
+    /// items and the data items' `original_text` concatenate back to `raw`.
+    pub fn from_raw_with(raw: &str, fidelity: TextFidelity) -> Self
+    where
+        T: DeserializeOwned,
+    {
+        Self::from_raw_scoped(raw, fidelity, &DetectionScope::Anywhere)
+    }
+
+    /// `from_raw_with`, taking data only from JSON within `scope`; JSON outside it is text
+    pub fn from_raw_scoped(raw: &str, fidelity: TextFidelity, scope: &DetectionScope) -> Self
+    where
+        T: DeserializeOwned,
+    {
+        Self::from_segments(raw, segment_response::<T>(raw, fidelity, scope))
+    }
+
+    /// Split each run of text between data items into markdown blocks, as
+    /// `StreamOptions::markdown_blocks` does for streams
+    pub fn split_markdown(self, fidelity: TextFidelity) -> Self {
+        let mut segmenter = MarkdownSegmenter::new(fidelity);
+        let mut items = Vec::with_capacity(self.items.len());
+        for item in self.items {
+            match item {
+                ResponseItem::Text(text) => items.extend(segmenter.push(&text.text).into_iter().map(ResponseItem::Text)),
+                data => {
+                    items.extend(segmenter.flush().into_iter().map(ResponseItem::Text));
+                    items.push(data);
+                }
+            }
+        }
+        items.extend(segmenter.flush().into_iter().map(ResponseItem::Text));
+        Self { items, safety: self.safety, truncated: self.truncated, error: self.error, cost: self.cost }
+    }
+}
+
+impl<T: JsonSchema + serde::Serialize> ParsedResponse<T> {
     /// Convert StreamItems to ResponseItems
-    fn from_stream_items(stream_items: Vec<StreamItem<T>>) -> Self {
+    pub(crate) fn from_stream_items(stream_items: Vec<StreamItem<T>>) -> Self {
         let items = stream_items.into_iter().filter_map(|item| match item {
             StreamItem::Data(data) => {
                 // Fallback: re-serialize the data since we don't have original text
                 let original_text = serde_json::to_string(&data)
                     .unwrap_or_else(|_| "[serialization failed]".to_string());
-                Some(ResponseItem::Data { data, original_text })
+                Some(ResponseItem::Data { data, original_text, confidence_map: None })
             },
             StreamItem::Text(text) => Some(ResponseItem::Text(text)),
-            StreamItem::Token(_) => None, // Tokens not relevant for non-streaming
+            StreamItem::Token(_) | StreamItem::Finished { .. } => None, // Tokens not relevant for non-streaming
         }).collect();
         
-        Self { items }
+        Self { items, safety: None, truncated: false, error: None, cost: None }
     }
 }
 
@@ -129,7 +357,7 @@ impl<T: fmt::Display> fmt::Display for ParsedResponse<T> {
             if i > 0 { writeln!(f)?; }
             match item {
                 ResponseItem::Text(text) => write!(f, "[Text] {}", text.text)?,
-                ResponseItem::Data { data, original_text } => {
+                ResponseItem::Data { data, original_text, .. } => {
                     write!(f, "[Data] {} (original: {})", data, original_text)?
                 },
             }
@@ -138,12 +366,144 @@ impl<T: fmt::Display> fmt::Display for ParsedResponse<T> {
     }
 }
 
+/// Speaker of a `ChatMessage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+/// One turn of a multi-turn exchange. Serializes as `{"role": "user", "content": "..."}`,
+/// the shape OpenAI-style chat endpoints accept directly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::Assistant, content: content.into() }
+    }
+}
+
+/// Flatten `messages` into one prompt for clients without a native message API.
+///
+/// A lone user message is passed through unchanged; otherwise system content comes
+/// first, followed by the turns labelled `User:` / `Assistant:`.
+pub fn render_transcript(messages: &[ChatMessage]) -> String {
+    if let [only] = messages {
+        if only.role == ChatRole::User {
+            return only.content.clone();
+        }
+    }
+    let mut sections: Vec<String> = messages.iter()
+        .filter(|m| m.role == ChatRole::System)
+        .map(|m| m.content.clone())
+        .collect();
+    for message in messages {
+        match message.role {
+            ChatRole::System => {}
+            ChatRole::User => sections.push(format!("User: {}", message.content)),
+            ChatRole::Assistant => sections.push(format!("Assistant: {}", message.content)),
+        }
+    }
+    sections.join("\n\n")
+}
+
+/// Where `query<T>()` puts the schema guidance for `T`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaPlacement {
+    /// Appended to the user prompt (works with every client)
+    #[default]
+    Prompt,
+    /// Sent as a system message when the client reports `supports_system_role()`,
+    /// falling back to `Prompt` otherwise
+    System,
+}
+
+/// What `query<T>()` asks the model to answer with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ResponseMode {
+    /// Prose with JSON anywhere in it (the default)
+    #[default]
+    Mixed,
+    /// A single JSON value and nothing else. The prompt says so, the provider's JSON
+    /// mode is enabled when it has one (`with_json_mode`), and text around the JSON
+    /// is handled by `ProsePolicy`.
+    JsonOnly,
+}
+
+/// Stop sequences JSON-only replies end at under `StopSequences::Auto`: the fence
+/// closing the JSON block and a markdown heading after it. Neither can occur inside
+/// JSON, where newlines are escaped.
+pub const JSON_ONLY_STOPS: [&str; 2] = ["\n```\n", "\n\n## "];
+
+/// Stop sequences the resolver sends with model calls, on clients that support them
+/// (`with_stop_sequences`). The reply then ends where the stop sequence would begin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StopSequences {
+    /// None (the default)
+    #[default]
+    Off,
+    /// Derived from the prompt format: `JSON_ONLY_STOPS` for JSON-only queries
+    /// (`ResponseMode::JsonOnly`, `query_typed`), none for mixed content, where more
+    /// text and JSON may follow the first block
+    Auto,
+    /// These, for every query including streams
+    Custom(Vec<String>),
+}
+
+/// How `ResponseMode::JsonOnly` treats text outside the JSON. Markdown code fences
+/// around the JSON are not counted as prose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProsePolicy {
+    /// Fail with `QueryResolverError::UnexpectedProse`
+    #[default]
+    Reject,
+    /// Drop the text and keep the data
+    Ignore,
+}
+
+/// What a client supports, from `LowLevelClient::capabilities`. Lets callers pick a
+/// strategy up front instead of probing with requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// `stream_raw` returns a stream
+    pub supports_streaming: bool,
+    /// The provider API has native tool/function calling
+    pub supports_native_tools: bool,
+    /// `with_json_mode` returns a client
+    pub supports_json_mode: bool,
+    /// `ask_raw_constrained` enforces the constraint (`supports_grammar`)
+    pub supports_grammar: bool,
+    /// `ask_messages` sends system messages in a real system role (`supports_system_role`)
+    pub supports_system_role: bool,
+    /// `with_prefill` returns a client
+    pub supports_prefill: bool,
+    /// The model accepts image input
+    pub supports_vision: bool,
+    /// Context window in tokens, when known
+    pub max_context_tokens: Option<u32>,
+}
+
 /// Low-level model client abstraction.
 ///
 /// Implementors provide `ask_raw`, which executes a prompt and returns the raw
 /// model text. Higher-level parsing and schema handling is performed by
 /// `QueryResolver` using stream-first JSON extraction.
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait LowLevelClient: Send + Sync + Debug{
     /// The only method that implementations must provide
     async fn ask_raw(&self, prompt: String) -> Result<String, AIError>;
@@ -154,6 +514,106 @@ pub trait LowLevelClient: Send + Sync + Debug{
     /// Optional: provide a streaming raw response as chunks of bytes.
     /// Default is None; providers can override to implement true streaming.
     fn stream_raw(&self, _prompt: String) -> Option<RawByteStream> { None }
+
+    /// Optional: a copy of this client sampling at `temperature`.
+    /// Default is None for providers whose temperature is not configurable.
+    fn with_temperature(&self, _temperature: f32) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: a copy of this client sending `seed` for best-effort deterministic sampling.
+    /// Default is None for providers without a seed parameter.
+    fn with_seed(&self, _seed: u64) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: the response token limit this client requests with.
+    /// Default is None when unknown.
+    fn max_tokens(&self) -> Option<u32> { None }
+
+    /// Optional: a copy of this client requesting up to `max_tokens` response tokens,
+    /// used by `LengthRecovery::GrowMaxTokens`. Default is None.
+    fn with_max_tokens(&self, _max_tokens: u32) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: a copy of this client with the provider's JSON output mode enabled
+    /// (e.g. OpenAI `response_format`), used by `ResponseMode::JsonOnly`.
+    /// Default is None for providers without one.
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: a copy of this client that stops generating before any of `stop`
+    /// (OpenAI `stop`, Anthropic `stop_sequences`), replacing earlier ones.
+    /// Default is None.
+    fn with_stop_sequences(&self, _stop: &[String]) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: a copy of this client that names `user` as the end user its requests
+    /// are made for (OpenAI `user`, Anthropic `metadata.user_id`), for the provider's
+    /// abuse monitoring. Default is None for providers without such a field.
+    fn with_user(&self, _user: &str) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: write out anything buffered (e.g. an interceptor's pending records)
+    /// before the process exits; called by `ShutdownHandle::shutdown`.
+    /// Default does nothing; `FlexibleClient` flushes its interceptor.
+    async fn flush(&self) -> Result<(), String> { Ok(()) }
+
+    /// Optional: a copy of this client whose replies start with `prefill` (e.g. `{` to
+    /// force JSON), continued by the model and included in the returned text.
+    /// Default is None for providers without assistant prefill.
+    fn with_prefill(&self, _prefill: &str) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: a copy of this client that asks for token logprobs and reports them
+    /// through `stats::record_logprobs`, used by `QueryResolver::with_field_confidence`.
+    /// Default is None for providers without logprobs.
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> { None }
+
+    /// Optional: whether this client enforces an `OutputConstraint` at generation time.
+    /// Default is false; local backends (Ollama, llama.cpp) override this.
+    fn supports_grammar(&self) -> bool { false }
+
+    /// Optional: ask with the output constrained to a schema/grammar.
+    /// Default ignores the constraint and falls back to `ask_raw`.
+    async fn ask_raw_constrained(&self, prompt: String, _constraint: &OutputConstraint) -> Result<String, AIError> {
+        self.ask_raw(prompt).await
+    }
+
+    /// Optional: whether `ask_messages` sends system messages in a real system role.
+    /// Default is false; the default `ask_messages` inlines them into the prompt.
+    fn supports_system_role(&self) -> bool { false }
+
+    /// Optional: ask with a full message history (system, user and assistant turns).
+    /// Default flattens the history with `render_transcript` and calls `ask_raw`.
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        self.ask_raw(render_transcript(&messages)).await
+    }
+
+    /// Optional: send a provider-neutral `ModelRequest`. Default applies its parameters
+    /// and response format with `ModelRequest::configure` and calls `ask_messages`;
+    /// tools are dropped. Providers with native tools or structured outputs override it.
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        request.configure(self.clone_box()).ask_messages(request.messages).await
+    }
+
+    /// Optional: receive the `QueryRecord` of a finished resolver query. Default does
+    /// nothing; `FlexibleClient` hands it to its interceptor.
+    async fn record_query(&self, _record: &QueryRecord) {}
+
+    /// Optional: what this client supports. Default reports grammar, system-role and
+    /// JSON-mode support from the methods above and nothing else; providers override it.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_json_mode: self.with_json_mode().is_some(),
+            supports_prefill: self.with_prefill("{").is_some(),
+            supports_grammar: self.supports_grammar(),
+            supports_system_role: self.supports_system_role(),
+            ..Capabilities::default()
+        }
+    }
+
+    /// Optional: the provider behind this client, named as in `ClientType` (e.g.
+    /// `"claude"`, `"openai"`), for `InterceptorFilter::provider`. Default is None.
+    fn provider(&self) -> Option<&'static str> { None }
+
+    /// Optional: this client as `Any`, so `FlexibleClient::as_any`/`configure` can reach
+    /// the concrete type. Default is None; built-in clients return `Some(self)`.
+    fn as_any(&self) -> Option<&dyn Any> { None }
+
+    /// Optional: mutable counterpart of `as_any`
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> { None }
 }
 
 // Implement Clone for Box<dyn LowLevelClient>
@@ -164,7 +624,8 @@ impl Clone for Box<dyn LowLevelClient> {
 }
 
 // Implement LowLevelClient for Box<dyn LowLevelClient>
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl LowLevelClient for Box<dyn LowLevelClient> {
     async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
         self.as_ref().ask_raw(prompt).await
@@ -177,6 +638,86 @@ impl LowLevelClient for Box<dyn LowLevelClient> {
     fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
         self.as_ref().stream_raw(prompt)
     }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_temperature(temperature)
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_seed(seed)
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.as_ref().max_tokens()
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_max_tokens(max_tokens)
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_json_mode()
+    }
+
+    fn with_prefill(&self, prefill: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_prefill(prefill)
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_logprobs()
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_stop_sequences(stop)
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.as_ref().with_user(user)
+    }
+
+    async fn flush(&self) -> Result<(), String> {
+        self.as_ref().flush().await
+    }
+
+    fn supports_grammar(&self) -> bool {
+        self.as_ref().supports_grammar()
+    }
+
+    async fn ask_raw_constrained(&self, prompt: String, constraint: &OutputConstraint) -> Result<String, AIError> {
+        self.as_ref().ask_raw_constrained(prompt, constraint).await
+    }
+
+    fn supports_system_role(&self) -> bool {
+        self.as_ref().supports_system_role()
+    }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        self.as_ref().ask_messages(messages).await
+    }
+
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        self.as_ref().ask_request(request).await
+    }
+
+    async fn record_query(&self, record: &QueryRecord) {
+        self.as_ref().record_query(record).await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.as_ref().capabilities()
+    }
+
+    fn provider(&self) -> Option<&'static str> {
+        self.as_ref().provider()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.as_ref().as_any()
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        self.as_mut().as_any_mut()
+    }
 }
 
 
@@ -185,6 +726,11 @@ impl LowLevelClient for Box<dyn LowLevelClient> {
 pub struct RetryConfig {
     pub max_retries: HashMap<String, usize>,
     pub default_max_retries: usize,
+    /// How to rewrite the prompt per error class (`retry::POST_PROCESS`, `retry::NO_DATA`)
+    pub strategies: HashMap<String, Arc<dyn RetryStrategy>>,
+    /// Recovery for replies cut off at `max_tokens` without data (class `retry::LENGTH`);
+    /// off by default
+    pub length_recovery: Option<LengthRecovery>,
 }
 
 impl Default for RetryConfig {
@@ -198,10 +744,42 @@ impl Default for RetryConfig {
         Self {
             max_retries,
             default_max_retries: 1,
+            strategies: HashMap::new(),
+            length_recovery: None,
         }
     }
 }
 
+impl RetryConfig {
+    /// Retries allowed for `error`, looked up by `AIError::retry_key`; zero when the
+    /// error isn't worth retrying
+    pub fn max_retries_for(&self, error: &AIError) -> usize {
+        if !error.is_retryable() {
+            return 0;
+        }
+        self.max_retries.get(error.retry_key()).copied().unwrap_or(self.default_max_retries)
+    }
+
+    /// Retry failures of `class` with `strategy` instead of a follow-up correction
+    #[must_use]
+    pub fn with_strategy(mut self, class: impl Into<String>, strategy: Arc<dyn RetryStrategy>) -> Self {
+        self.strategies.insert(class.into(), strategy);
+        self
+    }
+
+    /// Recover replies the provider cut off at `max_tokens` with `recovery`, when the
+    /// client reports `FinishReason::Length` and no data was extracted
+    #[must_use]
+    pub fn with_length_recovery(mut self, recovery: LengthRecovery) -> Self {
+        self.length_recovery = Some(recovery);
+        self
+    }
+
+    fn retries_for_class(&self, class: &str) -> usize {
+        self.max_retries.get(class).copied().unwrap_or(self.default_max_retries)
+    }
+}
+
 
 #[derive(Clone)]
 /// Query resolver that wraps a LowLevelClient and provides all generic methods.
@@ -210,12 +788,67 @@ impl Default for RetryConfig {
 pub struct QueryResolver<C: LowLevelClient> {
     client: C,
     config: RetryConfig,
+    grammar_constrained: bool,
+    moderator: Option<Arc<dyn Moderator>>,
+    moderate_prompts: bool,
+    extraction_policy: ExtractionPolicy,
+    table_fallback: bool,
+    stream_options: StreamOptions,
+    schema_placement: SchemaPlacement,
+    post_processors: PostProcessors,
+    response_mode: ResponseMode,
+    prose_policy: ProsePolicy,
+    stop_sequences: StopSequences,
+    normalizers: Normalizers,
+    field_confidence: bool,
+    stats_callback: Option<StatsCallback>,
+    record_callback: Option<RecordCallback>,
+    prompts_in_records: bool,
+    refusal_detector: Arc<dyn RefusalDetector>,
+    expected_language: Option<Language>,
+    language_detector: Arc<dyn LanguageDetector>,
+    tenancy: Option<Tenancy>,
+    pricing: Option<Arc<PriceTable>>,
+    cost_session: Option<Session>,
+    tags: HashMap<String, String>,
+    retry_budget: Option<RetryBudget>,
+    shutdown: Option<ShutdownHandle>,
+    spool: SpoolConfig,
 }
 
 impl<C: LowLevelClient> QueryResolver<C> {
     pub fn new(client: C, config: RetryConfig) -> Self {
         info!(default_max_retries = config.default_max_retries, "Creating new QueryResolver");
-        Self { client, config }
+        Self {
+            client,
+            config,
+            grammar_constrained: false,
+            moderator: None,
+            moderate_prompts: false,
+            extraction_policy: ExtractionPolicy::default(),
+            table_fallback: false,
+            stream_options: StreamOptions::default(),
+            schema_placement: SchemaPlacement::default(),
+            post_processors: PostProcessors::default(),
+            response_mode: ResponseMode::default(),
+            prose_policy: ProsePolicy::default(),
+            stop_sequences: StopSequences::default(),
+            normalizers: Normalizers::default(),
+            field_confidence: false,
+            stats_callback: None,
+            record_callback: None,
+            prompts_in_records: false,
+            refusal_detector: Arc::new(PhraseRefusalDetector::default()),
+            expected_language: None,
+            language_detector: Arc::new(NgramDetector::default()),
+            tenancy: None,
+            pricing: None,
+            cost_session: None,
+            tags: HashMap::new(),
+            retry_budget: None,
+            shutdown: None,
+            spool: SpoolConfig::default(),
+        }
     }
     
     /// Get a reference to the underlying client
@@ -234,20 +867,711 @@ impl<C: LowLevelClient> QueryResolver<C> {
         self
     }
 
+    /// Constrain `query<T>()` output with a grammar derived from `T`'s schema.
+    ///
+    /// Only takes effect when the client reports `supports_grammar()`. The response is
+    /// then deserialized strictly as a single `T`, skipping lenient extraction, and a
+    /// mismatch surfaces as `QueryResolverError::JsonDeserialization`.
+    pub fn with_grammar_constraints(mut self, enabled: bool) -> Self {
+        self.grammar_constrained = enabled;
+        self
+    }
+
+    /// What the underlying client supports
+    pub fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+
+    /// Whether `query<T>()` will use grammar-constrained generation with this client
+    pub fn grammar_enforced(&self) -> bool {
+        self.grammar_constrained && self.client.supports_grammar()
+    }
+
+    /// The same resolver settings over a different client
+    pub(crate) fn with_client<D: LowLevelClient>(&self, client: D) -> QueryResolver<D> {
+        QueryResolver {
+            client,
+            config: self.config.clone(),
+            grammar_constrained: self.grammar_constrained,
+            moderator: self.moderator.clone(),
+            moderate_prompts: self.moderate_prompts,
+            extraction_policy: self.extraction_policy,
+            table_fallback: self.table_fallback,
+            stream_options: self.stream_options.clone(),
+            schema_placement: self.schema_placement,
+            post_processors: self.post_processors.clone(),
+            response_mode: self.response_mode,
+            prose_policy: self.prose_policy,
+            stop_sequences: self.stop_sequences.clone(),
+            normalizers: self.normalizers.clone(),
+            field_confidence: self.field_confidence,
+            stats_callback: self.stats_callback.clone(),
+            record_callback: self.record_callback.clone(),
+            prompts_in_records: self.prompts_in_records,
+            refusal_detector: self.refusal_detector.clone(),
+            expected_language: self.expected_language,
+            language_detector: self.language_detector.clone(),
+            tenancy: self.tenancy.clone(),
+            pricing: self.pricing.clone(),
+            cost_session: self.cost_session.clone(),
+            tags: self.tags.clone(),
+            retry_budget: self.retry_budget.clone(),
+            shutdown: self.shutdown.clone(),
+            spool: self.spool.clone(),
+        }
+    }
+
+    /// With `enabled`, a resolver whose client samples at temperature 0 with
+    /// `REPRODUCIBLE_SEED`, for regression-testable extraction; see `reproducible_with_seed`.
+    /// Without, the same client boxed.
+    pub fn reproducible(&self, enabled: bool) -> QueryResolver<Box<dyn LowLevelClient>> {
+        if enabled {
+            self.reproducible_with_seed(REPRODUCIBLE_SEED)
+        } else {
+            self.with_client(self.client.clone_box())
+        }
+    }
+
+    /// A resolver whose client samples at temperature 0 and sends `seed`. Providers
+    /// without a seed parameter (Anthropic, DeepSeek) only get the temperature pinned.
+    /// `QueryStats` then carries the seed and the model version and fingerprint the
+    /// provider reports, so drift between runs can be traced to a backend change.
+    pub fn reproducible_with_seed(&self, seed: u64) -> QueryResolver<Box<dyn LowLevelClient>> {
+        let client = self.client.with_temperature(0.0).unwrap_or_else(|| {
+            warn!("Client does not support temperature overrides; using its default");
+            self.client.clone_box()
+        });
+        let client = client.with_seed(seed).unwrap_or_else(|| {
+            debug!(seed, "Client has no seed parameter; only the temperature is pinned");
+            client
+        });
+        self.with_client(client)
+    }
+
+    /// Options for `stream_query` and `query_stream`, e.g. whether elements of a
+    /// top-level array are emitted as they close
+    pub fn with_stream_options(mut self, options: StreamOptions) -> Self {
+        self.stream_options = options;
+        self
+    }
+
+    /// Whitespace handling of `Text` items, streamed or not. Sets
+    /// `StreamOptions::text_fidelity`.
+    pub fn with_text_fidelity(mut self, fidelity: TextFidelity) -> Self {
+        self.stream_options.text_fidelity = fidelity;
+        self
+    }
+
+    /// Split `Text` items into tagged markdown blocks, streamed or not. Sets
+    /// `StreamOptions::markdown_blocks`.
+    pub fn with_markdown_blocks(mut self, enabled: bool) -> Self {
+        self.stream_options.markdown_blocks = enabled;
+        self
+    }
+
+    /// Where in replies JSON is looked for, streamed or not. Sets
+    /// `StreamOptions::detection_scope`.
+    pub fn with_detection_scope(mut self, scope: DetectionScope) -> Self {
+        self.stream_options.detection_scope = scope;
+        self
+    }
+
+    /// How multiple data items in one response are combined (non-streaming queries only)
+    pub fn with_extraction_policy(mut self, policy: ExtractionPolicy) -> Self {
+        self.extraction_policy = policy;
+        self
+    }
+
+    /// Read `T`s from markdown or CSV tables in replies without JSON matching `T` (see
+    /// `tables`; non-streaming queries only)
+    pub fn with_table_fallback(mut self, enabled: bool) -> Self {
+        self.table_fallback = enabled;
+        self
+    }
+
+    /// Where `query<T>()` and conversations put schema guidance
+    pub fn with_schema_placement(mut self, placement: SchemaPlacement) -> Self {
+        self.schema_placement = placement;
+        self
+    }
+
+    /// Whether schema guidance goes into a system message with this client
+    pub fn schema_in_system_role(&self) -> bool {
+        self.schema_placement == SchemaPlacement::System && self.client.supports_system_role()
+    }
+
+    /// Whether `query<T>()` expects mixed content or JSON only
+    pub fn with_response_mode(mut self, mode: ResponseMode) -> Self {
+        self.response_mode = mode;
+        self
+    }
+
+    /// How JSON-only queries treat text around the JSON
+    pub fn with_prose_policy(mut self, policy: ProsePolicy) -> Self {
+        self.prose_policy = policy;
+        self
+    }
+
+    /// Stop sequences to send with model calls (default: `StopSequences::Off`)
+    pub fn with_stop_sequences(mut self, stop: StopSequences) -> Self {
+        self.stop_sequences = stop;
+        self
+    }
+
+    /// Rewrite replies and streamed chunks with `normalizers` before scanning them for
+    /// JSON, e.g. `Normalizers::standard()` to strip ANSI codes and BOMs (default: none)
+    pub fn with_normalizers(mut self, normalizers: Normalizers) -> Self {
+        self.normalizers = normalizers;
+        self
+    }
+
+    /// With `enabled`, ask the client for token logprobs and fill `confidence_map` on the
+    /// data items of non-streaming queries (see `confidence`). Clients without logprobs
+    /// are used as they are.
+    pub fn with_field_confidence(mut self, enabled: bool) -> Self {
+        self.field_confidence = enabled;
+        self
+    }
+
+    /// The client for one non-streaming call: `stopping_client`, asking for logprobs
+    /// when field confidence is on and the client supports them
+    fn request_client(&self) -> Option<Box<dyn LowLevelClient>> {
+        let stopping = self.stopping_client();
+        if !self.field_confidence {
+            return stopping;
+        }
+        let base: &dyn LowLevelClient = match &stopping {
+            Some(client) => client.as_ref(),
+            None => &self.client,
+        };
+        let client = base.with_logprobs();
+        if client.is_none() {
+            debug!("Client does not report logprobs; field confidence is unavailable");
+        }
+        client.or(stopping)
+    }
+
+    /// The client with the `Custom` stop sequences applied, when set and supported
+    fn stopping_client(&self) -> Option<Box<dyn LowLevelClient>> {
+        let StopSequences::Custom(stop) = &self.stop_sequences else { return None };
+        if stop.is_empty() {
+            return None;
+        }
+        let client = self.client.with_stop_sequences(stop);
+        if client.is_none() {
+            debug!("Client does not support stop sequences; sending without them");
+        }
+        client
+    }
+
+    /// Run `processor` on every `T` extracted by non-streaming queries. Processors for the
+    /// same type run in registration order; a rejection triggers a correction request,
+    /// up to `max_retries["post_process"]` times (else `default_max_retries`).
+    pub fn with_post_processor<T: 'static>(mut self, processor: impl PostProcessor<T> + 'static) -> Self {
+        self.post_processors.register::<T>(Arc::new(processor));
+        self
+    }
+
+    /// Call `callback` with the `QueryStats` of every query, streaming or not, once it
+    /// finishes or fails. Streams report when they end or are dropped.
+    pub fn with_stats_callback(mut self, callback: impl Fn(&QueryStats) + Send + Sync + 'static) -> Self {
+        self.stats_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` with the `QueryRecord` of every non-streaming query once it
+    /// finishes or fails (see `audit`)
+    pub fn with_record_callback(mut self, callback: impl Fn(&QueryRecord) + Send + Sync + 'static) -> Self {
+        self.record_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Whether `QueryRecord`s carry the prompt text in addition to its hash (default false)
+    pub fn with_prompts_in_records(mut self, enabled: bool) -> Self {
+        self.prompts_in_records = enabled;
+        self
+    }
+
+    /// Classifier `query_outcome` uses to recognize refusals in replies without data
+    /// (default: `PhraseRefusalDetector`)
+    pub fn with_refusal_detector(mut self, detector: Arc<dyn RefusalDetector>) -> Self {
+        self.refusal_detector = detector;
+        self
+    }
+
+    pub fn refusal_detector(&self) -> &dyn RefusalDetector {
+        self.refusal_detector.as_ref()
+    }
+
+    /// Retry replies not in `language` (error class `retry::LANGUAGE`); see
+    /// `crate::language`. Non-streaming queries only.
+    pub fn with_expected_language(mut self, language: Language) -> Self {
+        self.expected_language = Some(language);
+        self
+    }
+
+    pub fn expected_language(&self) -> Option<Language> {
+        self.expected_language
+    }
+
+    /// Classifier checking replies against `with_expected_language` (default:
+    /// `NgramDetector`)
+    pub fn with_language_detector(mut self, detector: Arc<dyn LanguageDetector>) -> Self {
+        self.language_detector = detector;
+        self
+    }
+
+    /// Hand the stats and record of a finished non-streaming query to the callbacks, then
+    /// the record to the client, and price it into `result`. Everything but the client
+    /// call happens before the returned future is polled, so it does not borrow `result`
+    /// (which would need `T: Sync`).
+    pub(crate) fn report<T: Serialize>(&self, probe: QueryProbe, result: &mut Result<ParsedResponse<T>, QueryResolverError>) -> impl std::future::Future<Output = ()> + '_ {
+        let (cost, report) = self.report_data(probe, result.as_ref().map(ParsedResponse::data_only));
+        if let Ok(response) = result {
+            response.cost = cost;
+        }
+        report
+    }
+
+    /// `report` for a query that extracted `data`, with what the query cost
+    fn report_data<T: Serialize>(&self, probe: QueryProbe, result: Result<Vec<&T>, &QueryResolverError>) -> (Option<CostBreakdown>, impl std::future::Future<Output = ()> + '_) {
+        let probe = probe.with_tags(&self.tags);
+        let (record, stats) = match result {
+            Ok(items) => {
+                let data = serde_json::to_vec(&items).unwrap_or_default();
+                let outcome = RecordOutcome::Succeeded { items: items.len() };
+                (probe.record(outcome, Some(&data), self.prompts_in_records), probe.completed(items.len()))
+            }
+            Err(e) => {
+                let outcome = RecordOutcome::Failed { error: e.to_string() };
+                (probe.record(outcome, None, self.prompts_in_records), probe.failed())
+            }
+        };
+        if let Some(callback) = &self.stats_callback {
+            callback(&stats);
+        }
+        if let Some(callback) = &self.record_callback {
+            callback(&record);
+        }
+        (self.charge(&record), async move { self.client.record_query(&record).await })
+    }
+
+    /// `report` for a query dropped before it finished. Its prompt was sent, so the
+    /// record still counts the prompt tokens, and the session pays for them.
+    pub(crate) async fn report_cancelled(&self, probe: QueryProbe) {
+        let probe = probe.with_tags(&self.tags);
+        let record = probe.record(RecordOutcome::Cancelled, None, self.prompts_in_records);
+        let stats = probe.failed();
+        if let Some(callback) = &self.stats_callback {
+            callback(&stats);
+        }
+        if let Some(callback) = &self.record_callback {
+            callback(&record);
+        }
+        self.charge(&record);
+        self.client.record_query(&record).await;
+    }
+
+    /// Price `record`, adding it to the session and the global cost report
+    fn charge(&self, record: &QueryRecord) -> Option<CostBreakdown> {
+        let cost = CostBreakdown::of_record(record, self.pricing.as_deref()?)?;
+        cost::charge(self.cost_session.as_ref(), &cost);
+        Some(cost)
+    }
+
+    /// Attach `key: value` to the `QueryStats` and `QueryRecord` of every query this
+    /// resolver runs, e.g. the feature a query serves, for attribution in metrics
+    #[must_use]
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// `with_tag` for each of `tags`
+    #[must_use]
+    pub fn with_tags<K: Into<String>, V: Into<String>>(mut self, tags: impl IntoIterator<Item = (K, V)>) -> Self {
+        self.tags.extend(tags.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    pub fn tags(&self) -> &HashMap<String, String> {
+        &self.tags
+    }
+
+    /// A resolver whose client names `user` as the end user of its requests (OpenAI
+    /// `user`, Anthropic `metadata.user_id`), for the provider's abuse monitoring. Use
+    /// an opaque id rather than a name or email. Clients without such a field send
+    /// requests as before.
+    pub fn for_user(&self, user: &str) -> QueryResolver<Box<dyn LowLevelClient>> {
+        let client = self.client.with_user(user).unwrap_or_else(|| {
+            debug!("Client has no end-user field; sending requests without it");
+            self.client.clone_box()
+        });
+        self.with_client(client)
+    }
+
+    /// Tenants `for_tenant` can select
+    #[must_use]
+    pub fn with_tenancy(mut self, tenancy: Tenancy) -> Self {
+        self.tenancy = Some(tenancy);
+        self
+    }
+
+    pub fn tenancy(&self) -> Option<&Tenancy> {
+        self.tenancy.as_ref()
+    }
+
+    /// Price every non-streaming query at `prices` into `ParsedResponse::cost`, the
+    /// cost session and `cost::global_cost_report`
+    #[must_use]
+    pub fn with_pricing(mut self, prices: PriceTable) -> Self {
+        self.pricing = Some(Arc::new(prices));
+        self
+    }
+
+    pub fn pricing(&self) -> Option<&PriceTable> {
+        self.pricing.as_deref()
+    }
+
+    /// Add the costs of this resolver's queries (and those of resolvers derived from it)
+    /// to `session`; needs `with_pricing`
+    #[must_use]
+    pub fn with_cost_session(mut self, session: Session) -> Self {
+        self.cost_session = Some(session);
+        self
+    }
+
+    pub fn cost_session(&self) -> Option<&Session> {
+        self.cost_session.as_ref()
+    }
+
+    /// Spend every extraction retry of this resolver (and its clones) from `budget`, on
+    /// top of any budget in `retry::budget_scope`
+    #[must_use]
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_budget = Some(budget);
+        self
+    }
+
+    pub fn retry_budget(&self) -> Option<&RetryBudget> {
+        self.retry_budget.as_ref()
+    }
+
+    /// Track this resolver's queries (and those of resolvers derived from it) on
+    /// `handle`, and flush its client when `handle` shuts down
+    #[must_use]
+    pub fn with_shutdown(mut self, handle: ShutdownHandle) -> Self {
+        handle.register(self.client.clone_box());
+        self.shutdown = Some(handle);
+        self
+    }
+
+    pub fn shutdown_handle(&self) -> Option<&ShutdownHandle> {
+        self.shutdown.as_ref()
+    }
+
+    /// When and where `query_spooled` moves replies to disk
+    #[must_use]
+    pub fn with_spool(mut self, config: SpoolConfig) -> Self {
+        self.spool = config;
+        self
+    }
+
+    /// Count a query as in flight on the shutdown handle, if any; fails once it is
+    /// shutting down
+    pub(crate) fn enter(&self) -> Result<Option<InFlight>, QueryResolverError> {
+        self.shutdown.as_ref().map(ShutdownHandle::enter).transpose()
+    }
+
+    /// Start a multi-turn conversation over this resolver
+    pub fn conversation(&self) -> Conversation<'_, C> {
+        Conversation::new(self)
+    }
+
+    /// Run `moderator` on raw responses before parsing (non-streaming queries only)
+    pub fn with_moderator(mut self, moderator: Arc<dyn Moderator>) -> Self {
+        self.moderator = Some(moderator);
+        self
+    }
+
+    /// Also moderate prompts before they are sent
+    pub fn with_prompt_moderation(mut self, enabled: bool) -> Self {
+        self.moderate_prompts = enabled;
+        self
+    }
+
+    /// Send `prompt` through the client, applying the moderator on either side
+    async fn ask_moderated(&self, prompt: String, constraint: Option<&OutputConstraint>) -> Result<(String, Option<SafetyReport>), QueryResolverError> {
+        self.ask_moderated_in(Vec::new(), prompt, constraint).await
+    }
+
+    /// `ask_moderated` with earlier messages before `prompt`. Only the new prompt is
+    /// moderated; a constraint is only used for single-turn requests (empty `context`).
+    pub(crate) async fn ask_moderated_in(&self, context: Vec<ChatMessage>, prompt: String, constraint: Option<&OutputConstraint>) -> Result<(String, Option<SafetyReport>), QueryResolverError> {
+        Box::pin(async move {
+            let Some(moderator) = &self.moderator else {
+                let raw = self.send(context, prompt, constraint).await?;
+                return Ok((raw, None));
+            };
+
+            let mut report = SafetyReport::default();
+            let prompt = if self.moderate_prompts {
+                let verdict = moderator.moderate(&prompt, ModerationTarget::Prompt).await?;
+                if verdict.action == ModerationAction::Block {
+                    warn!(categories = ?verdict.categories, "Prompt blocked by moderation");
+                    return Err(QueryResolverError::ModerationBlocked(verdict));
+                }
+                let prompt = verdict.apply(&prompt);
+                report.prompt = Some(verdict);
+                prompt
+            } else {
+                prompt
+            };
+
+            let raw = self.send(context, prompt, constraint).await?;
+            let verdict = moderator.moderate(&raw, ModerationTarget::Response).await?;
+            if verdict.action == ModerationAction::Block {
+                warn!(categories = ?verdict.categories, "Response blocked by moderation");
+                return Err(QueryResolverError::ModerationBlocked(verdict));
+            }
+            if verdict.flagged {
+                info!(categories = ?verdict.categories, action = ?verdict.action, "Response flagged by moderation");
+            }
+            let raw = verdict.apply(&raw);
+            report.response = Some(verdict);
+            Ok((raw, Some(report)))
+        }).await
+    }
+
+    /// One model call, in its own `attempt` span (see `telemetry`)
+    async fn send(&self, mut context: Vec<ChatMessage>, prompt: String, constraint: Option<&OutputConstraint>) -> Result<String, AIError> {
+        let attempt = crate::stats::next_attempt();
+        let span = info_span!(
+            target: "semantic_query::attempt",
+            "attempt",
+            attempt,
+            turns = context.len() + 1,
+            constrained = constraint.is_some(),
+            status = field::Empty,
+            reply_len = field::Empty,
+        );
+        let configured = self.request_client();
+        let client: &dyn LowLevelClient = match &configured {
+            Some(client) => client.as_ref(),
+            None => &self.client,
+        };
+        let result = async {
+            match constraint {
+                Some(c) => client.ask_raw_constrained(prompt, c).await,
+                None if context.is_empty() => client.ask_raw(prompt).await,
+                None => {
+                    context.push(ChatMessage::user(prompt));
+                    client.ask_messages(context).await
+                }
+            }
+        }.instrument(span.clone()).await;
+        match &result {
+            Ok(raw) => {
+                span.record("status", "ok");
+                span.record("reply_len", raw.len());
+            }
+            Err(e) => {
+                span.record("status", e.retry_key());
+            }
+        }
+        result.map(|raw| self.normalizers.apply_owned(raw))
+    }
+
+    /// Parse the reply `raw` to `prompt` (sent after `context`), running `T`'s post-processors
+    /// and asking for corrections while they reject items. Returns the response together
+    /// with the raw text it was finally parsed from.
+    pub(crate) async fn finish<T>(
+        &self,
+        context: Vec<ChatMessage>,
+        prompt: String,
+        mut raw: String,
+        mut safety: Option<SafetyReport>,
+        probe: &mut QueryProbe,
+    ) -> Result<(ParsedResponse<T>, String), QueryResolverError>
+    where
+        T: DeserializeOwned + serde::Serialize + Clone + 'static,
+    {
+        // Boxed, as are the model call and spooled stream loop, so the futures of the query
+        // methods stay small enough for the stack of debug builds
+        Box::pin(async move {
+            let processors = self.post_processors.for_type::<T>();
+            let mut history = context.clone();
+            history.push(ChatMessage::user(prompt.clone()));
+            let mut attempts: HashMap<&str, usize> = HashMap::new();
+            // `raw` holds several replies once continuations are stitched on; the latest starts here
+            let mut reply_start = 0;
+            let mut max_tokens = self.client.max_tokens();
+            let mut pass = 0usize;
+            loop {
+                let finish_reason = probe.take_finish_reason();
+                let logprobs = probe.take_logprobs();
+                pass += 1;
+                let span = info_span!(target: "semantic_query::extract", "extract", pass, items_emitted = field::Empty);
+                let (mut response, rejections) = span.in_scope(|| if processors.is_empty() {
+                    (ParsedResponse::from_raw_scoped(&raw, self.stream_options.text_fidelity, &self.stream_options.detection_scope), Vec::new())
+                } else {
+                    post_process::<T>(&raw, processors, self.stream_options.text_fidelity, &self.stream_options.detection_scope)
+                });
+                if self.table_fallback && !response.has_data() {
+                    if let Some(segments) = tables::segment_tables::<T>(&raw, self.stream_options.text_fidelity) {
+                        response = ParsedResponse::from_segments(&raw, segments);
+                    }
+                }
+                if self.stream_options.markdown_blocks {
+                    response = response.split_markdown(self.stream_options.text_fidelity);
+                }
+                span.record("items_emitted", response.data_count());
+                let wrong_language = self.expected_language
+                    .and_then(|expected| Some((expected, language::mismatch(&response, expected, self.language_detector.as_ref())?)));
+                let failure = if !rejections.is_empty() {
+                    Some((retry::POST_PROCESS, rejections.join("; ")))
+                } else if let Some((expected, found)) = wrong_language {
+                    Some((retry::LANGUAGE, format!("the answer is in {found}, not {expected}")))
+                } else if !response.has_data() && finish_reason == Some(FinishReason::Length) && self.config.length_recovery.is_some() {
+                    Some((retry::LENGTH, "the answer was cut off at the token limit".to_string()))
+                } else if !response.has_data() && self.config.strategies.contains_key(retry::NO_DATA) {
+                    Some((retry::NO_DATA, "no JSON matching the schema was found in the answer".to_string()))
+                } else {
+                    None
+                };
+                let Some((class, error)) = failure else {
+                    response.safety = safety;
+                    if let ExtractionPolicy::MergeMaps { on_conflict } = self.extraction_policy {
+                        response = response.merge_maps(on_conflict);
+                    }
+                    if let Some(tokens) = logprobs.filter(|_| self.field_confidence) {
+                        crate::confidence::annotate(&mut response, &raw, reply_start, &tokens);
+                    }
+                    return Ok((response, raw));
+                };
+                // A response without data is still returned as it is once retries run out
+                let give_up = |response: ParsedResponse<T>, raw: String| if class == retry::POST_PROCESS {
+                    Err(QueryResolverError::PostProcessing(error.clone()))
+                } else {
+                    Ok((response, raw))
+                };
+                let attempt = attempts.entry(class).or_insert(0);
+                if *attempt >= self.config.retries_for_class(class) {
+                    warn!(class, attempts = *attempt, "Extraction still failing; giving up");
+                    response.safety = safety;
+                    return give_up(response, raw);
+                }
+                if !retry::spend(self.retry_budget.as_ref()) {
+                    warn!(class, attempts = *attempt, "Retry budget exhausted; giving up");
+                    response.safety = safety;
+                    return give_up(response, raw);
+                }
+                *attempt += 1;
+                let attempt = *attempt;
+
+                if class == retry::LENGTH {
+                    probe.retry();
+                    let grown = match self.config.length_recovery {
+                        Some(LengthRecovery::GrowMaxTokens { factor, cap }) => max_tokens
+                            .map(|limit| limit.saturating_mul(factor).min(cap))
+                            .filter(|&next| Some(next) > max_tokens)
+                            .and_then(|next| Some((next, self.client.with_max_tokens(next)?))),
+                        _ => None,
+                    };
+                    if let Some((limit, client)) = grown {
+                        warn!(class, attempt, max_tokens = limit, "Reply cut off at the token limit; retrying with a higher limit");
+                        let (next, next_safety) = self.with_client(client).ask_moderated_in(context.clone(), prompt.clone(), None).await?;
+                        probe.received(&next);
+                        max_tokens = Some(limit);
+                        reply_start = 0;
+                        raw = next;
+                        safety = next_safety;
+                    } else {
+                        warn!(class, attempt, "Reply cut off at the token limit; asking the model to continue");
+                        history.push(ChatMessage::assistant(&raw[reply_start..]));
+                        let (next, next_safety) = self.ask_moderated_in(history.clone(), retry::CONTINUE_INSTRUCTION.to_string(), None).await?;
+                        probe.received(&next);
+                        history.push(ChatMessage::user(retry::CONTINUE_INSTRUCTION));
+                        reply_start = raw.len();
+                        raw.push_str(&next);
+                        safety = next_safety;
+                    }
+                    continue;
+                }
+
+                if let Some(strategy) = self.config.strategies.get(class) {
+                    let failed = FailedAttempt { class, prompt: &prompt, output: &raw, error: &error, attempt };
+                    let Some(plan) = strategy.next_attempt(&failed) else {
+                        warn!(class, attempt, "Retry strategy gave up");
+                        response.safety = safety;
+                        return give_up(response, raw);
+                    };
+                    warn!(class, attempt, escalated = plan.client.is_some(), "Extraction failed; retrying with a rewritten prompt");
+                    probe.retry();
+                    let (next, next_safety) = match plan.client {
+                        Some(client) => self.with_client(client).ask_moderated_in(context.clone(), plan.prompt.clone(), None).await?,
+                        None => self.ask_moderated_in(context.clone(), plan.prompt.clone(), None).await?,
+                    };
+                    probe.received(&next);
+                    history = context.clone();
+                    history.push(ChatMessage::user(plan.prompt));
+                    reply_start = 0;
+                    raw = next;
+                    safety = next_safety;
+                    continue;
+                }
+                let correction = match wrong_language {
+                    Some((expected, _)) if class == retry::LANGUAGE => {
+                        warn!(attempt, %error, "Answer in the wrong language; asking for a correction");
+                        language::correction(expected)
+                    }
+                    _ => {
+                        warn!(attempt, rejections = rejections.len(), "Post-processors rejected data; asking for a correction");
+                        format!(
+                            "Some of the JSON in your previous answer was rejected:\n- {}\n\nReply again with corrected JSON for every item.",
+                            rejections.join("\n- ")
+                        )
+                    }
+                };
+                history.push(ChatMessage::assistant(&raw[reply_start..]));
+                probe.retry();
+                let (next, next_safety) = self.ask_moderated_in(history.clone(), correction.clone(), None).await?;
+                probe.received(&next);
+                history.push(ChatMessage::user(correction));
+                reply_start = 0;
+                raw = next;
+                safety = next_safety;
+            }
+        }).await
+    }
+
     /// Query expecting mixed content (text + structured data)
     /// 
     /// This is the main API - it returns exactly what LLMs actually produce:
     /// a mix of explanatory text and structured data, preserving order and context.
-    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len()))]
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
     pub async fn query_mixed<T>(&self, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
     where
-        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone,
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
     {
-        info!(prompt_len = prompt.len(), "Starting mixed content query");
-        
-        let raw_response = self.client.ask_raw(prompt).await?;
-        let stream_items = build_parsed_stream::<T>(&raw_response);
-        let response = ParsedResponse::from_stream_items(stream_items);
+        correlation::ensure(async move {
+            info!(prompt_len = prompt.len(), "Starting mixed content query");
+
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_mixed", &prompt);
+            let mut result = shutdown::track(in_flight.as_ref(), self.query_mixed_in(prompt, &mut probe)).await;
+            self.report(probe, &mut result).await;
+            result
+        }).await
+    }
+
+    async fn query_mixed_in<T>(&self, prompt: String, probe: &mut QueryProbe) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        let (raw_response, safety) = self.ask_moderated(prompt.clone(), None).await?;
+        probe.received(&raw_response);
+        let (response, _) = self.finish::<T>(Vec::new(), prompt, raw_response, safety, probe).await?;
         
         info!(data_count = response.data_count(), text_length = response.text_content().len(), 
               "Mixed content query completed");
@@ -255,75 +1579,209 @@ impl<C: LowLevelClient> QueryResolver<C> {
         Ok(response)
     }
     
+    /// `query_mixed` for types that only implement serde's traits (`serde_json::Value`,
+    /// types from other crates, ...): the prompt is sent as is, without schema guidance,
+    /// and any JSON in the reply that deserializes as `T` becomes a data item.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn query_untyped<T>(&self, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_untyped", &prompt);
+            let mut result = shutdown::track(in_flight.as_ref(), self.query_mixed_in(prompt, &mut probe)).await;
+            self.report(probe, &mut result).await;
+            result
+        }).await
+    }
+
+    /// `query_untyped` with a hand-written description of `T` as guidance: a JSON Schema
+    /// document, or any text the model can follow (e.g. a TypeScript type or an example)
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt, schema), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn query_mixed_unschema<T>(&self, prompt: String, schema: &str) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_mixed_unschema", &prompt);
+            let prompt = format!("{}\n\n{}", prompt, schema_text_instructions(schema));
+            let mut result = shutdown::track(in_flight.as_ref(), self.query_mixed_in(prompt, &mut probe)).await;
+            self.report(probe, &mut result).await;
+            result
+        }).await
+    }
+
+    /// Every JSON structure in the reply to `prompt` as a `serde_json::Value`, for
+    /// exploring output whose shape is not known ahead of time. With `path` (see
+    /// `jsonpath`), each structure is replaced by the values the path selects in it.
+    ///
+    /// The prompt is sent as is. An invalid `path` fails before anything is sent.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn query_values(&self, prompt: String, path: Option<&str>) -> Result<Vec<serde_json::Value>, QueryResolverError> {
+        let path = path.map(JsonPath::parse).transpose()?;
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_values", &prompt);
+            let mut result = shutdown::track(in_flight.as_ref(), self.query_mixed_in::<serde_json::Value>(prompt, &mut probe)).await;
+            self.report(probe, &mut result).await;
+            let values = result?.data_only().into_iter().cloned().collect::<Vec<_>>();
+            Ok(match &path {
+                Some(path) => values.iter().flat_map(|value| path.select(value)).cloned().collect(),
+                None => values,
+            })
+        }).await
+    }
+
     /// Query with automatic JSON Schema guidance - the main recommended method
     /// 
     /// Automatically adds schema guidance and returns mixed content with context preserved.
-    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len()))]
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
     pub async fn query<T>(&self, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
     where
-        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone,
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
     {
-        info!(prompt_len = prompt.len(), "Starting query");
-        
-        let schema_prompt = self.add_schema_guidance::<T>(prompt);
-        self.query_mixed(schema_prompt).await
+        correlation::ensure(async move {
+            info!(prompt_len = prompt.len(), mode = ?self.response_mode, "Starting query");
+
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query", &prompt).with_schema(&SchemaCache::global().get::<T>());
+            let mut result = shutdown::track(in_flight.as_ref(), async {
+                match self.response_mode {
+                    ResponseMode::Mixed => self.query_guided(prompt, ResponseMode::Mixed, &mut probe).await,
+                    ResponseMode::JsonOnly => self.query_json_only(prompt, &mut probe).await,
+                }
+            }).await;
+            self.report(probe, &mut result).await;
+            result
+        }).await
+    }
+
+    /// Query in JSON-only mode and return the data directly, whatever `ResponseMode`
+    /// the resolver is configured with.
+    ///
+    /// ```no_run
+    /// # use semantic_query::core::{QueryResolver, RetryConfig};
+    /// # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+    /// # struct Verdict { approved: bool }
+    /// # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default());
+    /// let verdict: Verdict = resolver.query_typed("Approve this change?".to_string()).await?;
+    /// # Ok(()) }
+    /// ```
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn query_typed<T>(&self, prompt: String) -> Result<T, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_typed", &prompt).with_schema(&SchemaCache::global().get::<T>());
+            let mut result = shutdown::track(in_flight.as_ref(), self.query_json_only::<T>(prompt, &mut probe)).await;
+            self.report(probe, &mut result).await;
+            result.and_then(|response| response.first_required().map_err(QueryResolverError::from))
+        }).await
+    }
+
+    /// JSON-only query through the client's JSON mode, when it has one
+    async fn query_json_only<T>(&self, prompt: String, probe: &mut QueryProbe) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        let mut resolver = self.with_client(self.client.with_json_mode().unwrap_or_else(|| self.client.clone_box()));
+        if resolver.stop_sequences == StopSequences::Auto {
+            resolver.stop_sequences = StopSequences::Custom(JSON_ONLY_STOPS.map(String::from).to_vec());
+        }
+        let response = resolver.query_guided::<T>(prompt, ResponseMode::JsonOnly, probe).await?;
+        match self.prose_policy {
+            ProsePolicy::Ignore => Ok(ParsedResponse {
+                items: response.items.into_iter().filter(|item| matches!(item, ResponseItem::Data { .. })).collect(),
+                ..response
+            }),
+            ProsePolicy::Reject => match stray_prose(&response) {
+                Some(prose) => {
+                    warn!(prose_len = prose.len(), "JSON-only response contains prose");
+                    Err(QueryResolverError::UnexpectedProse(prose))
+                }
+                None => Ok(response),
+            },
+        }
+    }
+
+    /// Send `prompt` with schema guidance for `mode` (grammar, system message or inline)
+    async fn query_guided<T>(&self, prompt: String, mode: ResponseMode, probe: &mut QueryProbe) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        if self.grammar_enforced() {
+            return self.query_constrained(response_guidance::<T>(prompt, mode), probe).await;
+        }
+        if self.schema_in_system_role() {
+            let context = vec![ChatMessage::system(response_instructions::<T>(mode).to_string())];
+            let (raw_response, safety) = self.ask_moderated_in(context.clone(), prompt.clone(), None).await?;
+            probe.received(&raw_response);
+            return Ok(self.finish(context, prompt, raw_response, safety, probe).await?.0);
+        }
+        self.query_mixed_in(response_guidance::<T>(prompt, mode), probe).await
+    }
+
+    /// Grammar-constrained path: the backend guarantees well-formed JSON, so parse strictly
+    async fn query_constrained<T>(&self, prompt: String, probe: &mut QueryProbe) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        let constraint = OutputConstraint::for_type::<T>()
+            .map_err(|e| AIError::Configuration(e.to_string()))?;
+        debug!(grammar_len = constraint.gbnf.len(), "Querying with grammar constraint");
+
+        let (raw_response, safety) = self.ask_moderated(prompt, Some(&constraint)).await?;
+        probe.received(&raw_response);
+        let mut data: T = serde_json::from_str(raw_response.trim())
+            .map_err(|e| QueryResolverError::JsonDeserialization(e, raw_response.clone()))?;
+        for processor in self.post_processors.for_type::<T>() {
+            data = processor.process(data).map_err(QueryResolverError::PostProcessing)?;
+        }
+
+        info!(response_len = raw_response.len(), "Grammar-constrained query completed");
+        Ok(ParsedResponse { items: vec![ResponseItem::Data { data, original_text: raw_response, confidence_map: None }], safety, truncated: false, error: None, cost: None })
     }
     
     /// Add JSON schema guidance to a prompt
     fn add_schema_guidance<T>(&self, prompt: String) -> String
     where
-        T: JsonSchema,
+        T: JsonSchema + 'static,
     {
-        let schema = schema_for!(T);
-        let schema_json = serde_json::to_string_pretty(&schema)
-            .unwrap_or_else(|_| "Schema serialization failed".to_string());
-            
-        format!(
-            "{}\n\n## Response Format\nPlease include valid JSON matching this schema somewhere in your response:\n```json\n{}\n```",
-            prompt, schema_json
-        )
+        schema_guidance::<T>(prompt)
     }
     
     // =============================================================================
-    // DEPRECATED METHODS - Stubs only, use query() and query_mixed() instead
+    // DEPRECATED METHODS - Thin wrappers kept for the deprecation window
     // =============================================================================
     
-    /// Return a deserialized value `T` using retry + stream-based JSON extraction.
+    /// Return the first `T` in the response to `prompt`.
     ///
     /// # Deprecated
-    /// This method is deprecated and returns a stub error. 
-    /// Use `query<T>().first()` for similar functionality with better mixed content handling.
-    #[deprecated(since = "0.2.0", note = "Use query<T>().first() instead")]
-    pub async fn query_deserialized<T>(&self, _prompt: String) -> Result<T, QueryResolverError>
+    /// Equivalent to `query<T>().first_required()`; use that (or `query_typed<T>()`) instead.
+    #[deprecated(since = "0.2.0", note = "Use query<T>().first_required() instead")]
+    pub async fn query_deserialized<T>(&self, prompt: String) -> Result<T, QueryResolverError>
     where
-        T: DeserializeOwned + Send,
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
     {
-        Err(QueryResolverError::JsonDeserialization(
-            serde_json::Error::io(std::io::Error::new(
-                std::io::ErrorKind::Other, 
-                "query_deserialized is deprecated - use query<T>().first() instead"
-            )),
-            "deprecated method called".to_string(),
-        ))
+        Ok(self.query::<T>(prompt).await?.first_required()?)
     }
     
-    /// Return a deserialized value `T` with automatic JSON Schema guidance.
+    /// Return the first `T` in the response to `prompt`, with automatic JSON Schema guidance.
     ///
-    /// # Deprecated  
-    /// This method is deprecated and returns a stub error.
-    /// Use `query<T>().first()` for the same functionality with better context preservation.
-    #[deprecated(since = "0.2.0", note = "Use query<T>().first() instead")]
-    pub async fn query_with_schema<T>(&self, _prompt: String) -> Result<T, QueryResolverError>
+    /// # Deprecated
+    /// Equivalent to `query<T>().first_required()`. Prefer `query_typed<T>()`, which
+    /// returns `T` directly from a JSON-only response.
+    #[deprecated(since = "0.2.0", note = "Use query_typed<T>() instead")]
+    pub async fn query_with_schema<T>(&self, prompt: String) -> Result<T, QueryResolverError>
     where
-        T: DeserializeOwned + JsonSchema + Send,
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
     {
-        Err(QueryResolverError::JsonDeserialization(
-            serde_json::Error::io(std::io::Error::new(
-                std::io::ErrorKind::Other, 
-                "query_with_schema is deprecated - use query<T>().first() instead"
-            )),
-            "deprecated method called".to_string(),
-        ))
+        Ok(self.query::<T>(prompt).await?.first_required()?)
     }
 
     /// Generate a JSON schema for the return type and append it to the prompt
@@ -369,33 +1827,253 @@ impl<C: LowLevelClient> QueryResolver<C> {
     ///         Ok(StreamItem::Token(tok)) => print!("{}", tok), // Real-time tokens
     ///         Ok(StreamItem::Text(t)) => println!("[chat] {}", t.text),
     ///         Ok(StreamItem::Data(d)) => println!("[tool] {}", d.name),
+    ///         Ok(StreamItem::Finished { .. }) => {}
     ///         Err(e) => eprintln!("Stream error: {}", e),
     ///     }
     /// }
     /// # Ok(()) }
     /// ```
-    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len()))]
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
     pub async fn stream_query<T>(&self, prompt: String) -> ParsedStreamResult<T>
     where
         T: DeserializeOwned + JsonSchema + Send + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let probe = QueryProbe::start("stream_query", &prompt);
+            let stream = self.open_stream::<T>(prompt)?;
+
+            // Convert SSE bytes stream to stream items and box it
+            let items = crate::streaming::stream_from_sse_bytes_normalized::<T>(stream, self.stream_options.clone(), self.normalizers.clone());
+            let items = futures_util::StreamExt::map(items, |item| item.map(|timed| timed.item));
+            ParsedStreamResult::<T>::Ok(match &self.stats_callback {
+                Some(callback) => Box::pin(shutdown::track_stream(in_flight, observe_stream(items, probe.with_tags(&self.tags), callback.clone()))),
+                None => Box::pin(shutdown::track_stream(in_flight, items)),
+            })
+        }).await
+    }
+
+    /// `stream_query` with each item wrapped in `Timed` metadata (arrival time, elapsed
+    /// time, token count, provider event id), e.g. for `StreamLatency::measure`
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn stream_query_timed<T>(&self, prompt: String) -> TimedStreamResult<T>
+    where
+        T: DeserializeOwned + JsonSchema + Send + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let probe = QueryProbe::start("stream_query_timed", &prompt);
+            let stream = self.open_stream::<T>(prompt)?;
+            let items = crate::streaming::stream_from_sse_bytes_normalized::<T>(stream, self.stream_options.clone(), self.normalizers.clone());
+            TimedStreamResult::<T>::Ok(match &self.stats_callback {
+                Some(callback) => Box::pin(shutdown::track_stream(in_flight, observe_stream(items, probe.with_tags(&self.tags), callback.clone()))),
+                None => Box::pin(shutdown::track_stream(in_flight, items)),
+            })
+        }).await
+    }
+
+    /// Query against a JSON Schema only known at runtime (e.g. loaded from a file),
+    /// extracting the JSON the reply contains as `serde_json::Value`s.
+    ///
+    /// Data items are not validated against `schema`; post-processors registered for
+    /// `serde_json::Value` run as usual.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt, schema), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn query_schema(&self, prompt: String, schema: &serde_json::Value) -> Result<ParsedResponse<serde_json::Value>, QueryResolverError> {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_schema", &prompt).with_schema(schema);
+            let prompt = format!("{}\n\n{}", prompt, schema_value_instructions(schema));
+            let mut result = shutdown::track(in_flight.as_ref(), self.query_schema_in(prompt, &mut probe)).await;
+            self.report(probe, &mut result).await;
+            result
+        }).await
+    }
+
+    async fn query_schema_in(&self, prompt: String, probe: &mut QueryProbe) -> Result<ParsedResponse<serde_json::Value>, QueryResolverError> {
+        let (raw, safety) = self.ask_moderated(prompt.clone(), None).await?;
+        probe.received(&raw);
+        let (response, _) = self.finish::<serde_json::Value>(Vec::new(), prompt, raw, safety, probe).await?;
+        Ok(response)
+    }
+
+    /// Query for replies too large to hold in memory (see `spool`): the reply moves to a
+    /// temp file once it passes the resolver's `SpoolConfig::threshold`, and data is
+    /// extracted by scanning the file one JSON structure at a time.
+    ///
+    /// Streams when the client can, so the reply is never whole in memory; otherwise the
+    /// reply from `ask_raw` is spooled when it arrives. There are no correction retries,
+    /// moderation or post-processing.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn query_spooled<T>(&self, prompt: String) -> Result<SpooledResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let mut probe = QueryProbe::start("query_spooled", &prompt).with_schema(&SchemaCache::global().get::<T>());
+            let result = shutdown::track(in_flight.as_ref(), self.query_spooled_in::<T>(prompt, &mut probe)).await;
+            self.report_data(probe, result.as_ref().map(|response| response.data.iter().collect())).1.await;
+            result
+        }).await
+    }
+
+    async fn query_spooled_in<T>(&self, prompt: String, probe: &mut QueryProbe) -> Result<SpooledResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + 'static,
+    {
+        Box::pin(async move {
+            let spool_error = |e: std::io::Error| QueryResolverError::Spool(e.to_string());
+            let prompt = self.add_schema_guidance::<T>(prompt);
+            let raw = match self.client.stream_raw(prompt.clone()) {
+                Some(stream) => {
+                    let mut spooler = Spooler::new(self.spool.clone());
+                    let mut deltas = std::pin::pin!(crate::streaming::sse_text_deltas(stream));
+                    while let Some(delta) = futures_util::StreamExt::next(&mut deltas).await {
+                        let delta = delta?;
+                        probe.received(&delta);
+                        spooler.push(&delta).map_err(spool_error)?;
+                    }
+                    spooler.finish()
+                }
+                None => {
+                    let raw = self.client.ask_raw(prompt).await?;
+                    probe.received(&raw);
+                    SpooledText::from_string(raw, &self.spool)
+                }
+            }.map_err(spool_error)?;
+            info!(bytes = raw.len(), spooled = raw.is_spooled(), "Spooled query received its reply");
+            let data = raw.extract_all::<T>(self.stream_options.limits, &self.stream_options.detection_scope).map_err(spool_error)?;
+            Ok(SpooledResponse { data, raw })
+        }).await
+    }
+
+    /// `stream_query` against a JSON Schema only known at runtime; see `query_schema`
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt, schema), fields(prompt_len = prompt.len(), correlation_id = tracing::field::Empty))]
+    pub async fn stream_query_schema(&self, prompt: String, schema: &serde_json::Value) -> ParsedStreamResult<serde_json::Value> {
+        correlation::ensure(async move {
+            let in_flight = self.enter()?;
+            let probe = QueryProbe::start("stream_query_schema", &prompt);
+            let stream = self.open_guided_stream(format!("{}\n\n{}", prompt, schema_value_instructions(schema)))?;
+            let items = crate::streaming::stream_from_sse_bytes_normalized::<serde_json::Value>(stream, self.stream_options.clone(), self.normalizers.clone());
+            let items = futures_util::StreamExt::map(items, |item| item.map(|timed| timed.item));
+            ParsedStreamResult::<serde_json::Value>::Ok(match &self.stats_callback {
+                Some(callback) => Box::pin(shutdown::track_stream(in_flight, observe_stream(items, probe.with_tags(&self.tags), callback.clone()))),
+                None => Box::pin(shutdown::track_stream(in_flight, items)),
+            })
+        }).await
+    }
+
+    /// Stream responses to several prompts concurrently (e.g. sharded sub-prompts),
+    /// merged into one stream tagged by each prompt's key; see `stream_merge`.
+    ///
+    /// A prompt whose stream can't be opened yields a single `Err` under its key
+    /// instead of failing the others. To compare models, call `stream_query` on one
+    /// resolver per model and pass the streams to `stream_merge` directly.
+    pub async fn stream_query_merged<T, K>(&self, prompts: impl IntoIterator<Item = (K, String)>) -> impl Stream<Item = Sourced<K, Result<StreamItem<T>, QueryResolverError>>>
+    where
+        T: DeserializeOwned + JsonSchema + Send + 'static,
+        K: Clone,
+    {
+        let mut sources = Vec::new();
+        for (key, prompt) in prompts {
+            let items: CollectedItems<T> = match self.stream_query::<T>(prompt).await {
+                Ok(items) => items,
+                Err(e) => Box::pin(futures_util::stream::once(async move { Err(e) })),
+            };
+            sources.push((key, items));
+        }
+        stream_merge(sources)
+    }
+
+    /// Start a streaming response to `prompt` with schema guidance for `T`
+    fn open_stream<T>(&self, prompt: String) -> Result<RawByteStream, QueryResolverError>
+    where
+        T: JsonSchema + 'static,
     {
         info!(prompt_len = prompt.len(), "Starting streaming query");
         
         // For streaming, we add schema guidance to help the model generate proper JSON
-        let augmented_prompt = self.add_schema_guidance::<T>(prompt);
+        self.open_guided_stream(self.add_schema_guidance::<T>(prompt))
+    }
+
+    /// Start a streaming response to a prompt that already carries schema guidance
+    fn open_guided_stream(&self, augmented_prompt: String) -> Result<RawByteStream, QueryResolverError> {
         debug!(prompt_len = augmented_prompt.len(), "Using schema-augmented prompt for streaming");
         
         // Get streaming response
-        let stream = self.client.stream_raw(augmented_prompt)
+        let stream = match self.stopping_client() {
+            Some(client) => client.stream_raw(augmented_prompt),
+            None => self.client.stream_raw(augmented_prompt),
+        }
             .ok_or_else(|| {
                 warn!("Client does not support streaming");
                 crate::error::QueryResolverError::Ai(crate::error::AIError::Mock("Client does not support streaming".to_string()))
             })?;
         
         info!("Successfully initiated streaming response");
-        
-        // Convert SSE bytes stream to stream items and box it
-        Ok(Box::pin(crate::streaming::stream_from_sse_bytes::<T>(stream)))
+        Ok(stream)
+    }
+
+    /// Stream a live response and also collect it into a `ParsedResponse<T>`, parsing once.
+    ///
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    /// # use semantic_query::core::{QueryResolver, RetryConfig};
+    /// # use semantic_query::streaming::StreamItem;
+    /// # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+    /// # struct ToolCall { name: String }
+    /// # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let resolver = QueryResolver::new(semantic_query::clients::flexible::FlexibleClient::mock().0, RetryConfig::default());
+    /// let mut collect = resolver.query_stream_collect::<ToolCall>("Plan the trip".to_string()).await?;
+    /// while let Some(item) = collect.stream.next().await {
+    ///     if let Ok(StreamItem::Token(tok)) = item { print!("{tok}"); }
+    /// }
+    /// let response = collect.response.await?; // store it
+    /// # Ok(()) }
+    /// ```
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt), fields(prompt_len = prompt.len()))]
+    pub async fn query_stream_collect<T>(&self, prompt: String) -> Result<StreamCollect<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + serde::Serialize + Clone + Send + 'static,
+    {
+        let mut live = self.stream_query::<T>(prompt).await?;
+        let partial_on_deadline = self.stream_options.partial_on_deadline;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let stream = async_stream::stream! {
+            let mut items = Vec::new();
+            while let Some(next) = futures_util::StreamExt::next(&mut live).await {
+                match &next {
+                    Ok(StreamItem::Token(_) | StreamItem::Finished { .. }) => {}
+                    Ok(StreamItem::Text(text)) => items.push(ResponseItem::Text(text.clone())),
+                    Ok(StreamItem::Data(data)) => items.push(ResponseItem::Data {
+                        data: data.clone(),
+                        original_text: serde_json::to_string(data).unwrap_or_default(),
+                        confidence_map: None,
+                    }),
+                    Err(QueryResolverError::DeadlineExceeded(_)) if partial_on_deadline => {
+                        debug!(items = items.len(), "Deadline hit; returning the partial response");
+                        let error = next.as_ref().err().map(ToString::to_string);
+                        let _ = tx.send(Ok(ParsedResponse { items, safety: None, truncated: true, error, cost: None }));
+                        yield next;
+                        return;
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(QueryResolverError::StreamInterrupted(e.to_string())));
+                        yield next;
+                        return;
+                    }
+                }
+                yield next;
+            }
+            debug!(items = items.len(), "Streaming response collected");
+            let _ = tx.send(Ok(ParsedResponse { items, safety: None, truncated: false, error: None, cost: None }));
+        };
+        let response = async move {
+            rx.await.unwrap_or_else(|_| Err(QueryResolverError::StreamInterrupted(
+                "stream dropped before the response completed".to_string(),
+            )))
+        };
+        Ok(StreamCollect { stream: Box::pin(stream), response: Box::pin(response) })
     }
 
     /// Stream `StreamItem<T>` from any `AsyncRead` of model output.
@@ -425,7 +2103,7 @@ impl<C: LowLevelClient> QueryResolver<C> {
     /// let s = resolver.query_stream::<Finding,_>(rx, 1024);
     /// pin_mut!(s);
     /// while let Some(item) = s.next().await {
-    ///     match item { StreamItem::Text(t) => println!("text: {}", t.text), StreamItem::Data(d) => println!("data: {}", d.message), }
+    ///     match item { StreamItem::Text(t) => println!("text: {}", t.text), StreamItem::Data(d) => println!("data: {}", d.message), _ => {} }
     /// }
     /// # Ok(()) }
     /// ```
@@ -434,6 +2112,143 @@ impl<C: LowLevelClient> QueryResolver<C> {
         T: DeserializeOwned + JsonSchema + Send + 'static,
         R: tokio::io::AsyncRead + Unpin + Send + 'static,
     {
-        crate::streaming::stream_from_async_read::<R, T>(reader, buf_size)
+        crate::streaming::stream_from_async_read_normalized::<R, T>(reader, buf_size, self.stream_options.clone(), self.normalizers.clone())
     }
 }
+
+/// Append the JSON schema of `T` and answer-format instructions to `prompt`
+pub(crate) fn schema_guidance<T>(prompt: String) -> String
+where
+    T: JsonSchema + 'static,
+{
+    format!("{}\n\n{}", prompt, schema_instructions::<T>())
+}
+
+/// Append the answer-format instructions for `mode` to `prompt`
+fn response_guidance<T>(prompt: String, mode: ResponseMode) -> String
+where
+    T: JsonSchema + 'static,
+{
+    format!("{}\n\n{}", prompt, response_instructions::<T>(mode))
+}
+
+/// Answer-format instructions for `T` in `mode`, from `SchemaCache::global()`
+fn response_instructions<T>(mode: ResponseMode) -> Arc<str>
+where
+    T: JsonSchema + 'static,
+{
+    SchemaCache::global().rendered::<T>(mode)
+}
+
+/// Answer-format instructions for `schema` in `mode`
+pub(crate) fn render_instructions(schema: &serde_json::Value, mode: ResponseMode) -> String {
+    match mode {
+        ResponseMode::Mixed => schema_value_instructions(schema),
+        ResponseMode::JsonOnly => json_only_instructions(schema),
+    }
+}
+
+/// Instructions for answering with a single JSON value matching `schema` and nothing else
+fn json_only_instructions(schema: &serde_json::Value) -> String {
+    let schema_json = serde_json::to_string_pretty(schema)
+        .unwrap_or_else(|_| "Schema serialization failed".to_string());
+    format!(
+        "## Response Format\nRespond with JSON only: a single JSON value matching this schema, with no explanation, comments or text before or after it.\n```json\n{}\n```{}",
+        schema_json, recursion_note(schema)
+    )
+}
+
+/// Text outside the data items of `response`, ignoring whitespace and code fences
+fn stray_prose<T>(response: &ParsedResponse<T>) -> Option<String> {
+    let prose: Vec<&str> = response.items.iter()
+        .filter_map(|item| match item {
+            ResponseItem::Text(t) => Some(t.text.trim()),
+            ResponseItem::Data { .. } => None,
+        })
+        .map(|text| text.trim_start_matches("```json").trim_matches('`').trim())
+        .filter(|text| !text.is_empty())
+        .collect();
+    (!prose.is_empty()).then(|| prose.join(" ... "))
+}
+
+/// The answer-format instructions and JSON schema for `T`, without a prompt
+pub(crate) fn schema_instructions<T>() -> Arc<str>
+where
+    T: JsonSchema + 'static,
+{
+    response_instructions::<T>(ResponseMode::Mixed)
+}
+
+/// `schema_instructions` for a JSON Schema only known at runtime
+pub(crate) fn schema_value_instructions(schema_value: &serde_json::Value) -> String {
+    let schema_json = serde_json::to_string_pretty(schema_value)
+        .unwrap_or_else(|_| "Schema serialization failed".to_string());
+
+    // Map targets (`HashMap<String, V>` etc.) have no fixed properties; say so explicitly
+    let is_map = schema_value.get("additionalProperties").is_some_and(|v| v.is_object())
+        && schema_value.get("properties").is_none();
+    let map_note = if is_map {
+        "\nThe top-level object is a map: choose the keys yourself from the content (free-form strings, one entry per record); every value must match the `additionalProperties` schema. Emit a single object containing all entries."
+    } else {
+        ""
+    };
+        
+    format!(
+        "## Response Format\nPlease include valid JSON matching this schema somewhere in your response:\n```json\n{}\n```{}{}",
+        schema_json, map_note, recursion_note(schema_value)
+    )
+}
+
+/// Recursive types (`$ref` back to `#` or to an enclosing definition) confuse models into
+/// flattening the tree or inventing reference objects; spell out what nesting means
+fn recursion_note(schema: &serde_json::Value) -> &'static str {
+    if crate::schema::is_recursive(schema) {
+        "\nThe schema is recursive: a `$ref` means \"a nested value of that shape\", so write the nested objects out in full (`#` is the top-level shape itself). Nest as deep as the content requires and use an empty array or null where the nesting stops; never emit `$ref` objects."
+    } else {
+        ""
+    }
+}
+
+/// Instructions for a hand-written schema: JSON Schema documents get the same wording as
+/// generated ones, anything else is passed on verbatim
+pub(crate) fn schema_text_instructions(schema: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(schema) {
+        Ok(value) if value.is_object() => schema_value_instructions(&value),
+        _ => format!(
+            "## Response Format\nPlease include valid JSON matching this description somewhere in your response:\n```\n{}\n```",
+            schema.trim()
+        ),
+    }
+}
+
+/// Segment `raw` with `processors` normalizing each candidate's JSON, then run their typed
+/// checks on every data item. Rejected items are dropped and their messages returned.
+fn post_process<T>(raw: &str, processors: &[Arc<dyn PostProcessor<T>>], fidelity: TextFidelity, scope: &DetectionScope) -> (ParsedResponse<T>, Vec<String>)
+where
+    T: DeserializeOwned,
+{
+    let parse = |candidate: &str| {
+        let mut value = parse_candidate::<serde_json::Value>(candidate)?;
+        for processor in processors {
+            processor.normalize_json(&mut value);
+        }
+        serde_json::from_value::<T>(value).ok()
+    };
+    let mut rejections = Vec::new();
+    let segments = segment_response_with(raw, &parse, fidelity, scope)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Data(data, range) => {
+                match processors.iter().try_fold(data, |data, processor| processor.process(data)) {
+                    Ok(data) => Some(Segment::Data(data, range)),
+                    Err(rejection) => {
+                        rejections.push(rejection);
+                        None
+                    }
+                }
+            }
+            text => Some(text),
+        })
+        .collect();
+    (ParsedResponse::from_segments(raw, segments), rejections)
+}
@@ -0,0 +1,114 @@
+//! Correlation ids for tracing one extraction across services.
+//!
+//! Every resolver query runs under a correlation id: the caller's, when the query is
+//! awaited inside `correlation::scope`, or a fresh one. While the query runs, the id is
+//! recorded on its tracing span, sent to providers as the `x-correlation-id` header,
+//! captured in `ProviderError::correlation_id` and `QueryStats::correlation_id`, and
+//! available to clients and interceptors through `correlation::current()`:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::correlation::{self, CorrelationId};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Invoice { total: f64 }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>, incoming: String) {
+//! // Reuse the id of the inbound request so logs line up across services
+//! let id = CorrelationId::from(incoming);
+//! let result = correlation::scope(id, resolver.query::<Invoice>("Extract the invoice".into())).await;
+//! # }
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Header provider requests carry the current id in
+pub const HEADER: &str = "x-correlation-id";
+
+tokio::task_local! {
+    static CURRENT: CorrelationId;
+}
+
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+/// Identifier shared by everything one query does
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CorrelationId(String);
+
+impl CorrelationId {
+    /// A fresh id, unique within the process and unlikely to collide across processes
+    pub fn new() -> Self {
+        let seq = NEXT.fetch_add(1, Ordering::Relaxed);
+        let salt = RandomState::new().hash_one(seq) as u32;
+        Self(format!("sq-{:x}-{:08x}", Utc::now().timestamp_micros(), salt))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for CorrelationId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<String> for CorrelationId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<&str> for CorrelationId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Id of the query running on this task, if any
+pub fn current() -> Option<CorrelationId> {
+    CURRENT.try_with(Clone::clone).ok()
+}
+
+/// Run `future` with `id` as the current correlation id
+pub async fn scope<F: Future>(id: CorrelationId, future: F) -> F::Output {
+    CURRENT.scope(id, future).await
+}
+
+/// Run `future` under the current id, or a fresh one recorded on the current span's
+/// `correlation_id` field. Every resolver entry point goes through here, so it also
+/// opens the slot providers report model provenance into for `QueryStats`.
+pub(crate) async fn ensure<F: Future>(future: F) -> F::Output {
+    let id = current().unwrap_or_default();
+    tracing::Span::current().record("correlation_id", id.as_str());
+    scope(id, crate::stats::capture_provenance(future)).await
+}
+
+/// Adds the current id, if any, to provider requests
+#[cfg(feature = "http")]
+pub(crate) trait Correlated {
+    fn correlated(self) -> Self;
+}
+
+#[cfg(feature = "http")]
+impl Correlated for reqwest::RequestBuilder {
+    fn correlated(self) -> Self {
+        match current() {
+            Some(id) => self.header(HEADER, id.as_str()),
+            None => self,
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! What queries cost, per response and rolled up for reporting.
+//!
+//! Give a resolver a `PriceTable` with `QueryResolver::with_pricing` and every
+//! non-streaming query prices its `RecordUsage` at the rate of the model that answered.
+//! The result is a `CostBreakdown` on `ParsedResponse::cost`. It is also added to the
+//! resolver's `Session` (see `QueryResolver::with_cost_session`) and to the process-wide
+//! report returned by `global_cost_report`, filed under the session's name:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::cost::{self, ModelPrice, PriceTable, Session};
+//! let prices = PriceTable::new()
+//!     .with_model("gpt-4o-mini", ModelPrice::new(0.00015, 0.0006).with_cached(0.000075))
+//!     .with_default(ModelPrice::new(0.0025, 0.01));
+//! let session = Session::new("nightly-import");
+//! let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default())
+//!     .with_pricing(prices)
+//!     .with_cost_session(session.clone());
+//! // later
+//! println!("{}", session.cost_summary().to_csv());
+//! std::fs::write("costs.json", cost::global_cost_report().to_json()).unwrap();
+//! ```
+//!
+//! Token counts are the ones providers report (see `stats::record_usage`), otherwise
+//! estimates. Queries whose model has no price, and streamed queries, are not priced.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{QueryRecord, RecordUsage};
+
+/// Session name of queries run without `QueryResolver::with_cost_session`
+pub const DEFAULT_SESSION: &str = "default";
+
+/// `CostSummary` key of queries whose provider did not report a model
+pub const UNKNOWN_MODEL: &str = "unknown";
+
+/// Prices per 1000 tokens of one model, in whatever currency the table is kept in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_1k: f64,
+    /// Prompt tokens served from the provider's prompt cache
+    pub cached_per_1k: f64,
+    pub output_per_1k: f64,
+}
+
+impl ModelPrice {
+    /// Cached prompt tokens cost as much as others until `with_cached`
+    pub fn new(input_per_1k: f64, output_per_1k: f64) -> Self {
+        Self { input_per_1k, cached_per_1k: input_per_1k, output_per_1k }
+    }
+
+    #[must_use]
+    pub fn with_cached(mut self, cached_per_1k: f64) -> Self {
+        self.cached_per_1k = cached_per_1k;
+        self
+    }
+}
+
+/// Prices by model name, with a fallback for models not listed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceTable {
+    /// Model name prefixes and their prices, so `gpt-4o` also prices `gpt-4o-2024-08-06`
+    pub models: Vec<(String, ModelPrice)>,
+    /// Price of models no prefix matches, and of replies that named no model
+    pub default: Option<ModelPrice>,
+}
+
+impl PriceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_model(mut self, prefix: impl Into<String>, price: ModelPrice) -> Self {
+        self.models.push((prefix.into(), price));
+        self
+    }
+
+    #[must_use]
+    pub fn with_default(mut self, price: ModelPrice) -> Self {
+        self.default = Some(price);
+        self
+    }
+
+    /// The price of the longest prefix of `model`, else the default
+    pub fn price_for(&self, model: Option<&str>) -> Option<ModelPrice> {
+        model
+            .and_then(|model| {
+                self.models.iter()
+                    .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map(|(_, price)| *price)
+            })
+            .or(self.default)
+    }
+}
+
+/// What one query cost. Prompt tokens are split into `input_tokens` and
+/// `cached_tokens`, each billed at its own rate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CostBreakdown {
+    /// Model the provider reported answering with
+    pub model: Option<String>,
+    /// Prompt tokens not served from the cache
+    pub input_tokens: u64,
+    pub cached_tokens: u64,
+    pub output_tokens: u64,
+    pub input_per_1k: f64,
+    pub cached_per_1k: f64,
+    pub output_per_1k: f64,
+    pub total: f64,
+}
+
+impl CostBreakdown {
+    /// `usage` of a reply from `model`, priced at `price`
+    pub fn new(model: Option<String>, usage: RecordUsage, price: ModelPrice) -> Self {
+        let cached_tokens = usage.cached_tokens.min(usage.prompt_tokens);
+        let input_tokens = usage.prompt_tokens - cached_tokens;
+        let total = (input_tokens as f64 * price.input_per_1k
+            + cached_tokens as f64 * price.cached_per_1k
+            + usage.completion_tokens as f64 * price.output_per_1k)
+            / 1000.0;
+        Self {
+            model,
+            input_tokens,
+            cached_tokens,
+            output_tokens: usage.completion_tokens,
+            input_per_1k: price.input_per_1k,
+            cached_per_1k: price.cached_per_1k,
+            output_per_1k: price.output_per_1k,
+            total,
+        }
+    }
+
+    /// The cost of `record`, if `prices` has a price for its model
+    pub fn of_record(record: &QueryRecord, prices: &PriceTable) -> Option<Self> {
+        let price = prices.price_for(record.model.as_deref())?;
+        Some(Self::new(record.model.clone(), record.usage, price))
+    }
+}
+
+/// Queries, tokens and cost added up
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostTotals {
+    pub queries: u64,
+    pub input_tokens: u64,
+    pub cached_tokens: u64,
+    pub output_tokens: u64,
+    pub total: f64,
+}
+
+impl CostTotals {
+    fn add(&mut self, cost: &CostBreakdown) {
+        self.queries += 1;
+        self.input_tokens += cost.input_tokens;
+        self.cached_tokens += cost.cached_tokens;
+        self.output_tokens += cost.output_tokens;
+        self.total += cost.total;
+    }
+
+    fn merge(&mut self, other: &CostTotals) {
+        self.queries += other.queries;
+        self.input_tokens += other.input_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total += other.total;
+    }
+
+    fn csv_fields(&self) -> String {
+        format!("{},{},{},{},{}", self.queries, self.input_tokens, self.cached_tokens, self.output_tokens, self.total)
+    }
+}
+
+/// Costs of a session, by model (`UNKNOWN_MODEL` when the provider named none)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostSummary {
+    pub by_model: BTreeMap<String, CostTotals>,
+}
+
+impl CostSummary {
+    pub fn add(&mut self, cost: &CostBreakdown) {
+        let model = cost.model.clone().unwrap_or_else(|| UNKNOWN_MODEL.to_string());
+        self.by_model.entry(model).or_default().add(cost);
+    }
+
+    /// All models together
+    pub fn total(&self) -> CostTotals {
+        let mut total = CostTotals::default();
+        self.by_model.values().for_each(|totals| total.merge(totals));
+        total
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// One row per model, after a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("model,queries,input_tokens,cached_tokens,output_tokens,total\n");
+        for (model, totals) in &self.by_model {
+            let _ = writeln!(csv, "{},{}", csv_field(model), totals.csv_fields());
+        }
+        csv
+    }
+}
+
+/// Costs of every session, as returned by `global_cost_report`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostReport {
+    pub sessions: BTreeMap<String, CostSummary>,
+}
+
+impl CostReport {
+    /// All sessions and models together
+    pub fn total(&self) -> CostTotals {
+        let mut total = CostTotals::default();
+        self.sessions.values().for_each(|summary| total.merge(&summary.total()));
+        total
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// One row per session and model, after a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("session,model,queries,input_tokens,cached_tokens,output_tokens,total\n");
+        for (session, summary) in &self.sessions {
+            for (model, totals) in &summary.by_model {
+                let _ = writeln!(csv, "{},{},{}", csv_field(session), csv_field(model), totals.csv_fields());
+            }
+        }
+        csv
+    }
+}
+
+/// `value` quoted when it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A named group of queries whose costs add up, e.g. one job run or one user's visit.
+/// Clones share the totals.
+#[derive(Debug, Clone)]
+pub struct Session {
+    name: Arc<str>,
+    summary: Arc<Mutex<CostSummary>>,
+}
+
+impl Session {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into().into(), summary: Arc::default() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Costs of the queries run in this session so far
+    pub fn cost_summary(&self) -> CostSummary {
+        self.summary.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+fn registry() -> &'static Mutex<CostReport> {
+    static REGISTRY: OnceLock<Mutex<CostReport>> = OnceLock::new();
+    REGISTRY.get_or_init(Mutex::default)
+}
+
+/// Costs of every priced query in this process so far, by session
+pub fn global_cost_report() -> CostReport {
+    registry().lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Add `cost` to `session` (if any) and to the global report
+pub(crate) fn charge(session: Option<&Session>, cost: &CostBreakdown) {
+    if let Some(session) = session {
+        session.summary.lock().unwrap_or_else(|e| e.into_inner()).add(cost);
+    }
+    let name = session.map_or(DEFAULT_SESSION, Session::name);
+    registry().lock().unwrap_or_else(|e| e.into_inner()).sessions.entry(name.to_string()).or_default().add(cost);
+}
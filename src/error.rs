@@ -1,3 +1,6 @@
+use std::fmt;
+use std::time::Duration;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,6 +13,103 @@ pub enum QueryResolverError {
     MaxRetriesExceeded,
     #[error("Data extraction error: {0}")]
     DataExtraction(#[from] DataExtractionError),
+    #[error("Blocked by moderation: {}", .0.categories.join(", "))]
+    ModerationBlocked(crate::moderation::ModerationVerdict),
+    #[error("Post-processing rejected data: {0}")]
+    PostProcessing(String),
+    #[error("JSON-only response contains prose: {0}")]
+    UnexpectedProse(String),
+    #[error("Stream ended before the response completed: {0}")]
+    StreamInterrupted(String),
+    /// A streaming query ran past `StreamOptions::deadline`
+    #[error("Streaming query exceeded its {0:?} deadline")]
+    DeadlineExceeded(std::time::Duration),
+    #[error("Retrieval failed: {0}")]
+    Retrieval(String),
+    #[error("Model refused: {0}")]
+    Refused(String),
+    #[error("Schema registry error: {0}")]
+    SchemaRegistry(#[from] SchemaRegistryError),
+    #[error("Experiment error: {0}")]
+    Experiment(String),
+    #[error("Review failed: {0}")]
+    Review(String),
+    #[error("Rejected in review: {0}")]
+    ReviewRejected(String),
+    #[error("Task failed: {0}")]
+    Task(String),
+    #[error("Tenancy error: {0}")]
+    Tenancy(String),
+    #[error("JSONPath error: {0}")]
+    JsonPath(#[from] JsonPathError),
+    /// Writing or reading a reply spooled to disk failed
+    #[error("Spooling failed: {0}")]
+    Spool(String),
+    /// The resolver's `ShutdownHandle` is shutting down and takes no new queries
+    #[error("Shutting down; not accepting new queries")]
+    ShuttingDown,
+    /// Still running at the shutdown deadline, or cancelled through
+    /// `spawn::QueryHandle::cancel`
+    #[error("Query cancelled")]
+    Cancelled,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SchemaRegistryError {
+    #[error("Invalid schema reference '{0}' (expected name or name@vN)")]
+    InvalidReference(String),
+    #[error("Unknown schema '{0}'")]
+    UnknownSchema(String),
+    #[error("Schema '{name}' has no version {version}")]
+    UnknownVersion { name: String, version: u32 },
+    #[error("No migration from {name}@v{from} to the next version")]
+    MissingMigration { name: String, from: u32 },
+    #[error("Migrating {name}@v{from} failed: {message}")]
+    Migration { name: String, from: u32, message: String },
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum JsonPathError {
+    #[error("Invalid JSONPath '{path}' at offset {offset}: {message}")]
+    Syntax { path: String, offset: usize, message: String },
+}
+
+/// A built-in or registered tool could not run a call
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ToolError {
+    #[error("Unknown tool '{0}'")]
+    UnknownTool(String),
+    #[error("Invalid arguments for '{tool}': {message}")]
+    InvalidArguments { tool: String, message: String },
+    #[error("Tool '{tool}' failed: {message}")]
+    Failed { tool: String, message: String },
+}
+
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    #[error("Pipeline step {index} ({name}) failed: {source}")]
+    Step {
+        index: usize,
+        name: String,
+        #[source]
+        source: QueryResolverError,
+    },
+}
+
+#[cfg(all(feature = "batch-api", not(target_arch = "wasm32")))]
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("AI error: {0}")]
+    Ai(#[from] AIError),
+    #[error("Batch state I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Batch state error: {0}")]
+    State(String),
+    #[error("Batch {batch_id} ended as {status:?}")]
+    Ended {
+        batch_id: String,
+        status: crate::batch_api::BatchStatus,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -18,6 +118,19 @@ pub enum DataExtractionError {
     NoDataFound,
     #[error("Data extraction failed: {0}")]
     ExtractionFailed(String),
+    #[error("Response exceeds parse limits: {0}")]
+    LimitExceeded(#[from] ParseLimitError),
+}
+
+/// A JSON structure in a response exceeded `json_utils::ParseLimits`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseLimitError {
+    #[error("nesting deeper than {limit} levels at byte {offset}")]
+    Depth { limit: usize, offset: usize },
+    #[error("more than {limit} nested structures at byte {offset}")]
+    Nodes { limit: usize, offset: usize },
+    #[error("structure starting at byte {start} is larger than {limit} bytes")]
+    Size { limit: usize, start: usize },
 }
 
 #[derive(Error, Debug, Clone)]
@@ -28,42 +141,281 @@ pub enum AIError {
     OpenAI(#[from] OpenAIError),
     #[error("DeepSeek API error: {0}")]
     DeepSeek(#[from] DeepSeekError),
+    #[error("Ollama error: {0}")]
+    Ollama(#[from] OllamaError),
+    #[error("Process error: {0}")]
+    Process(#[from] ProcessError),
+    #[error("gRPC error: {0}")]
+    Grpc(#[from] GrpcError),
     #[error("Mock error: {0}")]
     Mock(String),
+    #[error("Configuration error: {0}")]
+    Configuration(String),
+    /// The provider reported that the model declined to answer or the response was filtered
+    #[error("Model refused: {0}")]
+    Refused(String),
+    /// A tenant's request was refused client-side; `limit` names the `TenantLimits` field
+    #[error("Tenant '{tenant}' exceeded {limit}")]
+    TenantLimit { tenant: String, limit: String },
+    /// An interceptor under `InterceptorErrorPolicy::FailRequest` could not save the exchange
+    #[error("Interceptor failed: {0}")]
+    Interceptor(String),
+}
+
+impl AIError {
+    /// Details of the non-success HTTP response behind this error, if there was one
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            Self::Claude(ClaudeError::Status(e) | ClaudeError::RateLimit(e) | ClaudeError::Authentication(e))
+            | Self::OpenAI(OpenAIError::Status(e) | OpenAIError::RateLimit(e) | OpenAIError::Authentication(e))
+            | Self::DeepSeek(DeepSeekError::Status(e) | DeepSeekError::RateLimit(e) | DeepSeekError::Authentication(e))
+            | Self::Ollama(OllamaError::Status(e)) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// HTTP status returned by the provider
+    pub fn status(&self) -> Option<u16> {
+        self.provider_error().map(|e| e.status)
+    }
+
+    /// Provider request id, for support tickets
+    pub fn request_id(&self) -> Option<&str> {
+        self.provider_error().and_then(|e| e.request_id.as_deref())
+    }
+
+    /// Correlation id of the query that got this response
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.provider_error().and_then(|e| e.correlation_id.as_deref())
+    }
+
+    /// How long the provider asked us to wait before retrying
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.provider_error().and_then(|e| e.retry_after)
+    }
+
+    /// `RetryConfig::max_retries` key for this error: `"rate_limit"` (including gRPC
+    /// `ResourceExhausted`), `"http_error"` (transport failures, 5xx/408 responses, process
+    /// timeouts and gRPC `Unavailable` / `DeadlineExceeded` / `Aborted`) or `"api_error"`
+    /// (anything else)
+    pub fn retry_key(&self) -> &'static str {
+        match self {
+            Self::Claude(ClaudeError::RateLimit(_))
+            | Self::OpenAI(OpenAIError::RateLimit(_))
+            | Self::DeepSeek(DeepSeekError::RateLimit(_)) => "rate_limit",
+            Self::Grpc(GrpcError::Status { code, .. }) if code == "ResourceExhausted" => "rate_limit",
+            Self::Claude(ClaudeError::Http(_))
+            | Self::OpenAI(OpenAIError::Http(_))
+            | Self::DeepSeek(DeepSeekError::Http(_))
+            | Self::Ollama(OllamaError::Http(_))
+            | Self::Process(ProcessError::Timeout(_))
+            | Self::Grpc(GrpcError::Transport(_)) => "http_error",
+            Self::Grpc(GrpcError::Status { code, .. }) if matches!(code.as_str(), "Unavailable" | "DeadlineExceeded" | "Aborted") => "http_error",
+            _ if self.provider_error().is_some_and(ProviderError::is_server_error) => "http_error",
+            _ => "api_error",
+        }
+    }
+
+    /// Whether sending the same request again may succeed: rate limits, transport
+    /// failures, 5xx/408 responses, timeouts and unavailable gRPC servers
+    pub fn is_retryable(&self) -> bool {
+        self.retry_key() != "api_error"
+    }
+}
+
+/// A non-success HTTP response from a provider. Provider errors hold it boxed, which
+/// keeps `AIError` (and every `Result` carrying one) small.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProviderError {
+    pub status: u16,
+    /// Provider error code or type, e.g. `rate_limit_error` or `context_length_exceeded`
+    pub code: Option<String>,
+    pub message: String,
+    /// From the `retry-after` / `retry-after-ms` headers
+    pub retry_after: Option<Duration>,
+    /// From the `request-id`, `x-request-id` or `apim-request-id` headers
+    pub request_id: Option<String>,
+    /// Correlation id of the query that made the request (see `correlation`)
+    pub correlation_id: Option<String>,
+}
+
+fn current_correlation_id() -> Option<String> {
+    crate::correlation::current().map(|id| id.to_string())
+}
+
+#[cfg(feature = "http")]
+const REQUEST_ID_HEADERS: [&str; 3] = ["request-id", "x-request-id", "apim-request-id"];
+
+impl ProviderError {
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        Self { status, message: message.into(), correlation_id: current_correlation_id(), ..Self::default() }
+    }
+
+    /// Read status, headers and body from a non-success response
+    #[cfg(feature = "http")]
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = response.text().await.unwrap_or_default();
+        Self::from_parts(status, &headers, &body)
+    }
+
+    /// Build from a response's parts. Understands `{"error": {"type" | "code", "message"}}`
+    /// bodies (Anthropic, OpenAI and compatibles) and `{"error": "..."}` (Ollama); other
+    /// bodies become the message verbatim.
+    #[cfg(feature = "http")]
+    pub fn from_parts(status: u16, headers: &reqwest::header::HeaderMap, body: &str) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+        // Seconds or milliseconds; HTTP-date values are ignored
+        let seconds = |name: &str, scale: f64| header(name)
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .map(|v| Duration::from_secs_f64(v / scale));
+        let retry_after = seconds("retry-after-ms", 1000.0).or_else(|| seconds("retry-after", 1.0));
+        let request_id = REQUEST_ID_HEADERS.iter().find_map(|name| header(name)).map(str::to_string);
+
+        let (code, message) = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(json) => {
+                let error = json.get("error").unwrap_or(&json);
+                let text = |key: &str| error.get(key).and_then(|v| match v {
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    serde_json::Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                });
+                let code = text("code").or_else(|| text("type"));
+                let message = text("message").or_else(|| error.as_str().map(str::to_string));
+                (code, message)
+            }
+            Err(_) => (None, None),
+        };
+        let message = message.unwrap_or_else(|| match body.trim() {
+            "" => "Unknown error".to_string(),
+            text => text.to_string(),
+        });
+        Self { status, code, message, retry_after, request_id, correlation_id: current_correlation_id() }
+    }
+
+    /// 5xx and 408 responses, which are usually transient
+    pub fn is_server_error(&self) -> bool {
+        self.status >= 500 || self.status == 408
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {}", self.status)?;
+        if let Some(code) = &self.code {
+            write!(f, " ({code})")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(id) = &self.request_id {
+            write!(f, " [request id {id}]")?;
+        }
+        if let Some(id) = &self.correlation_id {
+            write!(f, " [correlation id {id}]")?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Error, Debug, Clone)]
 pub enum ClaudeError {
+    /// The request failed before a response arrived, or its body couldn't be read
     #[error("HTTP error: {0}")]
     Http(String),
+    /// A successful response without usable content
     #[error("API error: {0}")]
     Api(String),
-    #[error("Rate limit exceeded")]
-    RateLimit,
-    #[error("Authentication failed")]
-    Authentication,
+    #[error("API error: {0}")]
+    Status(Box<ProviderError>),
+    #[error("Rate limit exceeded: {0}")]
+    RateLimit(Box<ProviderError>),
+    #[error("Authentication failed: {0}")]
+    Authentication(Box<ProviderError>),
 }
 
 #[derive(Error, Debug, Clone)]
 pub enum OpenAIError {
+    /// The request failed before a response arrived, or its body couldn't be read
     #[error("HTTP error: {0}")]
     Http(String),
+    /// A successful response without usable content
     #[error("API error: {0}")]
     Api(String),
-    #[error("Rate limit exceeded")]
-    RateLimit,
-    #[error("Authentication failed")]
-    Authentication,
+    #[error("API error: {0}")]
+    Status(Box<ProviderError>),
+    #[error("Rate limit exceeded: {0}")]
+    RateLimit(Box<ProviderError>),
+    #[error("Authentication failed: {0}")]
+    Authentication(Box<ProviderError>),
 }
 
 #[derive(Error, Debug, Clone)]
 pub enum DeepSeekError {
+    /// The request failed before a response arrived, or its body couldn't be read
     #[error("HTTP error: {0}")]
     Http(String),
+    /// A successful response without usable content
     #[error("API error: {0}")]
     Api(String),
-    #[error("Rate limit exceeded")]
-    RateLimit,
-    #[error("Authentication failed")]
-    Authentication,
-}
\ No newline at end of file
+    #[error("API error: {0}")]
+    Status(Box<ProviderError>),
+    #[error("Rate limit exceeded: {0}")]
+    RateLimit(Box<ProviderError>),
+    #[error("Authentication failed: {0}")]
+    Authentication(Box<ProviderError>),
+}
+
+/// 401/403 become `Authentication`, 429 `RateLimit`, anything else `Status`
+macro_rules! impl_from_provider_error {
+    ($($name:ident),*) => {$(
+        impl From<ProviderError> for $name {
+            fn from(error: ProviderError) -> Self {
+                match error.status {
+                    401 | 403 => Self::Authentication(Box::new(error)),
+                    429 => Self::RateLimit(Box::new(error)),
+                    _ => Self::Status(Box::new(error)),
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_provider_error!(ClaudeError, OpenAIError, DeepSeekError);
+
+#[derive(Error, Debug, Clone)]
+pub enum OllamaError {
+    #[error("HTTP error: {0}")]
+    Http(String),
+    #[error("API error: {0}")]
+    Api(String),
+    #[error("API error: {0}")]
+    Status(Box<ProviderError>),
+    #[error("Model not found: {0}")]
+    ModelNotFound(String),
+}
+
+/// A `clients::process::ProcessClient` command failed
+#[derive(Error, Debug, Clone)]
+pub enum ProcessError {
+    #[error("Failed to start '{program}': {message}")]
+    Spawn { program: String, message: String },
+    #[error("Command timed out after {0:?}")]
+    Timeout(Duration),
+    /// The command exited unsuccessfully; `stderr` holds the end of its error output
+    #[error("Command exited with {}: {stderr}", .code.map_or("a signal".to_string(), |c| format!("status {c}")))]
+    Exit { code: Option<i32>, stderr: String },
+    #[error("I/O error: {0}")]
+    Io(String),
+}
+
+/// A `clients::grpc::GrpcClient` call failed
+#[derive(Error, Debug, Clone)]
+pub enum GrpcError {
+    /// The channel could not be opened or broke mid-call
+    #[error("Transport error: {0}")]
+    Transport(String),
+    /// The server answered with a non-OK status; `code` is the status name, e.g. `Unavailable`
+    #[error("{code}: {message}")]
+    Status { code: String, message: String },
+}
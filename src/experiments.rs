@@ -0,0 +1,264 @@
+//! Prompt A/B experiments.
+//!
+//! An `Experiment` holds weighted variants, each a prompt template plus the resolver it
+//! runs on (so variants can differ in model, schema placement, response mode, ...).
+//! Every query is assigned a variant deterministically from a caller-supplied key (a user
+//! or document id), so the same key always sees the same variant. Outcomes (schema
+//! compliance, latency, estimated cost) go to an optional callback, like
+//! `QueryResolver::with_stats_callback`, and are tallied for `Experiment::report`:
+//!
+//! ```no_run
+//! # use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+//! # use semantic_query::experiments::{Experiment, Variant};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Ticket { priority: u8 }
+//! # async fn run(resolver: QueryResolver<Box<dyn LowLevelClient>>, ticket_id: &str, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+//! let experiment = Experiment::new("triage-prompt")
+//!     .with_variant(Variant::new("control", "Triage this ticket:\n{input}", resolver.clone()))
+//!     .with_variant(Variant::new("terse", "Priority 1-5 for:\n{input}", resolver).with_weight(1));
+//! let run = experiment.query::<Ticket>(ticket_id, text).await?;
+//! println!("{} -> {:?}", run.outcome.variant, run.response.first());
+//! println!("{:#?}", experiment.report());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver};
+use crate::error::QueryResolverError;
+use crate::prompt::estimate_tokens;
+
+/// Placeholder replaced by the query input in variant templates
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+
+/// One arm of an experiment
+#[derive(Clone)]
+pub struct Variant<C: LowLevelClient> {
+    pub name: String,
+    /// Prompt with `{input}` where the query input goes; without one, the input is appended
+    pub template: String,
+    /// Relative share of traffic (default 1; 0 disables the variant)
+    pub weight: u32,
+    /// Estimated cost per 1000 tokens, prompt and reply combined
+    pub cost_per_1k_tokens: Option<f64>,
+    pub resolver: QueryResolver<C>,
+}
+
+impl<C: LowLevelClient> Variant<C> {
+    pub fn new(name: impl Into<String>, template: impl Into<String>, resolver: QueryResolver<C>) -> Self {
+        Self { name: name.into(), template: template.into(), weight: 1, cost_per_1k_tokens: None, resolver }
+    }
+
+    #[must_use]
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    #[must_use]
+    pub fn with_cost_per_1k_tokens(mut self, cost: f64) -> Self {
+        self.cost_per_1k_tokens = Some(cost);
+        self
+    }
+
+    /// The prompt this variant sends for `input`
+    pub fn render(&self, input: &str) -> String {
+        if self.template.contains(INPUT_PLACEHOLDER) {
+            self.template.replace(INPUT_PLACEHOLDER, input)
+        } else {
+            format!("{}\n\n{}", self.template, input)
+        }
+    }
+}
+
+/// What happened to one experiment query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentOutcome {
+    pub experiment: String,
+    pub variant: String,
+    pub key: String,
+    /// The query succeeded with at least one data item matching the schema
+    pub compliant: bool,
+    pub latency: Duration,
+    /// Estimated prompt plus reply tokens (~4 characters per token)
+    pub tokens: usize,
+    /// `tokens` priced at the variant's `cost_per_1k_tokens`, if set
+    pub cost: Option<f64>,
+    /// The query error, if it failed
+    pub error: Option<String>,
+}
+
+/// Response and outcome of `Experiment::query`
+#[derive(Debug, Clone)]
+pub struct ExperimentRun<T> {
+    pub response: ParsedResponse<T>,
+    pub outcome: ExperimentOutcome,
+}
+
+/// Aggregated outcomes of one variant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VariantSummary {
+    pub variant: String,
+    pub runs: usize,
+    pub compliant: usize,
+    pub errors: usize,
+    pub mean_latency: Duration,
+    pub total_tokens: usize,
+    pub total_cost: Option<f64>,
+}
+
+impl VariantSummary {
+    /// Share of runs that were compliant (0 without runs)
+    pub fn compliance_rate(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.compliant as f64 / self.runs as f64 }
+    }
+
+    fn add(&mut self, outcome: &ExperimentOutcome) {
+        let total_latency = self.mean_latency * self.runs as u32 + outcome.latency;
+        self.runs += 1;
+        self.mean_latency = total_latency / self.runs as u32;
+        self.compliant += usize::from(outcome.compliant);
+        self.errors += usize::from(outcome.error.is_some());
+        self.total_tokens += outcome.tokens;
+        if let Some(cost) = outcome.cost {
+            *self.total_cost.get_or_insert(0.0) += cost;
+        }
+    }
+}
+
+/// Per-variant summaries, in variant order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentReport {
+    pub experiment: String,
+    pub variants: Vec<VariantSummary>,
+}
+
+impl ExperimentReport {
+    /// The variant with the highest compliance rate among those with runs
+    pub fn leader(&self) -> Option<&VariantSummary> {
+        self.variants.iter()
+            .filter(|v| v.runs > 0)
+            .max_by(|a, b| a.compliance_rate().total_cmp(&b.compliance_rate()))
+    }
+}
+
+/// Receives every experiment outcome
+pub type OutcomeCallback = Arc<dyn Fn(&ExperimentOutcome) + Send + Sync>;
+
+/// Weighted prompt variants with deterministic assignment and outcome tallies
+#[derive(Clone)]
+pub struct Experiment<C: LowLevelClient = Box<dyn LowLevelClient>> {
+    name: String,
+    variants: Vec<Variant<C>>,
+    callback: Option<OutcomeCallback>,
+    tallies: Arc<Mutex<HashMap<String, VariantSummary>>>,
+}
+
+impl<C: LowLevelClient> Experiment<C> {
+    /// An experiment without variants; the name also salts assignment, so different
+    /// experiments split the same keys independently
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), variants: Vec::new(), callback: None, tallies: Arc::default() }
+    }
+
+    #[must_use]
+    pub fn with_variant(mut self, variant: Variant<C>) -> Self {
+        self.variants.push(variant);
+        self
+    }
+
+    /// Called with each outcome, e.g. to forward it to a metrics backend
+    #[must_use]
+    pub fn with_outcome_callback(mut self, callback: impl Fn(&ExperimentOutcome) + Send + Sync + 'static) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The variant `key` is assigned to; `None` without variants of positive weight.
+    /// Stable across processes and releases as long as the variants and weights are.
+    pub fn assign(&self, key: &str) -> Option<&Variant<C>> {
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut bucket = fnv1a(format!("{}\u{0}{}", self.name, key).as_bytes()) % total;
+        self.variants.iter().find(|v| {
+            let weight = u64::from(v.weight);
+            if bucket < weight {
+                true
+            } else {
+                bucket -= weight;
+                false
+            }
+        })
+    }
+
+    /// Run `input` through the variant assigned to `key` and record the outcome. Query
+    /// errors are recorded too before being returned.
+    pub async fn query<T>(&self, key: &str, input: &str) -> Result<ExperimentRun<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+    {
+        let variant = self.assign(key)
+            .ok_or_else(|| QueryResolverError::Experiment(format!("experiment '{}' has no active variants", self.name)))?;
+        let prompt = variant.render(input);
+        let prompt_tokens = estimate_tokens(&prompt);
+        let started = Utc::now();
+        let result = variant.resolver.query::<T>(prompt).await;
+        let latency = (Utc::now() - started).to_std().unwrap_or_default();
+
+        let tokens = prompt_tokens + result.as_ref().map(|r| estimate_tokens(&r.text_content())).unwrap_or(0);
+        let outcome = ExperimentOutcome {
+            experiment: self.name.clone(),
+            variant: variant.name.clone(),
+            key: key.to_string(),
+            compliant: result.as_ref().is_ok_and(ParsedResponse::has_data),
+            latency,
+            tokens,
+            cost: variant.cost_per_1k_tokens.map(|price| price * tokens as f64 / 1000.0),
+            error: result.as_ref().err().map(ToString::to_string),
+        };
+        self.record(&outcome);
+        result.map(|response| ExperimentRun { response, outcome })
+    }
+
+    /// Tally an outcome measured elsewhere (e.g. a streamed query run on `assign`'s variant)
+    pub fn record(&self, outcome: &ExperimentOutcome) {
+        if let Ok(mut tallies) = self.tallies.lock() {
+            let summary = tallies.entry(outcome.variant.clone())
+                .or_insert_with(|| VariantSummary { variant: outcome.variant.clone(), ..VariantSummary::default() });
+            summary.add(outcome);
+        }
+        if let Some(callback) = &self.callback {
+            callback(outcome);
+        }
+    }
+
+    /// Outcomes so far, one summary per variant (including variants without runs)
+    pub fn report(&self) -> ExperimentReport {
+        let tallies = self.tallies.lock().map(|t| t.clone()).unwrap_or_default();
+        let variants = self.variants.iter()
+            .map(|v| tallies.get(&v.name).cloned()
+                .unwrap_or_else(|| VariantSummary { variant: v.name.clone(), ..VariantSummary::default() }))
+            .collect();
+        ExperimentReport { experiment: self.name.clone(), variants }
+    }
+}
+
+/// 64-bit FNV-1a; unlike `DefaultHasher`, fixed across Rust versions
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3))
+}
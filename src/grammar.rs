@@ -0,0 +1,260 @@
+//! JSON Schema → GBNF conversion for grammar-constrained generation.
+//!
+//! Local backends such as llama.cpp accept a GBNF grammar that restricts sampling
+//! to strings the grammar accepts. Converting the schemars schema for `T` lets
+//! those backends emit only valid JSON for `T`, at which point lenient extraction
+//! is unnecessary (see `QueryResolver::with_grammar_constraints`).
+//!
+//! Supported keywords: `type` (including type arrays), `properties`/`required`,
+//! `items`, `enum`, `const`, `anyOf`/`oneOf`, and local `$ref`s to the root (`#`) or
+//! into `$defs`/`definitions` (recursive definitions are fine). Unknown or open-ended
+//! schemas fall back to "any JSON value".
+
+use schemars::JsonSchema;
+use crate::schema::SchemaCache;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum GrammarError {
+    #[error("Unsupported $ref: {0}")]
+    UnsupportedRef(String),
+    #[error("Unknown JSON type: {0}")]
+    UnknownType(String),
+}
+
+/// Constraint passed to clients that can enforce output structure at generation time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputConstraint {
+    /// JSON Schema for the expected output (for backends with native schema support)
+    pub json_schema: Value,
+    /// Equivalent GBNF grammar (for llama.cpp-style backends)
+    pub gbnf: String,
+}
+
+impl OutputConstraint {
+    /// Build the constraint for `T` from its schemars schema
+    pub fn for_type<T: JsonSchema + 'static>() -> Result<Self, GrammarError> {
+        let json_schema = Value::clone(&SchemaCache::global().get::<T>());
+        let gbnf = schema_to_gbnf(&json_schema)?;
+        Ok(Self { json_schema, gbnf })
+    }
+}
+
+/// Convert a JSON Schema into a GBNF grammar whose `root` rule matches instances.
+pub fn schema_to_gbnf(schema: &Value) -> Result<String, GrammarError> {
+    let mut builder = GbnfBuilder::new(schema);
+    let root = builder.visit(schema, "root")?;
+    if root != "root" {
+        builder.add_rule("root", root);
+    }
+    Ok(builder.render())
+}
+
+const PRIMITIVES: &[(&str, &str)] = &[
+    ("ws", r#"[ \t\n]*"#),
+    ("string", r#""\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] ) )* "\"" ws"#),
+    ("number", r#""-"? ( [0-9] | [1-9] [0-9]* ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9]+ )? ws"#),
+    ("integer", r#""-"? ( [0-9] | [1-9] [0-9]* ) ws"#),
+    ("boolean", r#"( "true" | "false" ) ws"#),
+    ("null", r#""null" ws"#),
+    ("value", r#"( object | array | string | number | boolean | null )"#),
+    ("object", r#""{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws"#),
+    ("array", r#""[" ws ( value ( "," ws value )* )? "]" ws"#),
+];
+
+struct GbnfBuilder<'a> {
+    root_schema: &'a Value,
+    rules: Vec<(String, String)>,
+    names: HashSet<String>,
+    refs: HashMap<String, String>,
+}
+
+impl<'a> GbnfBuilder<'a> {
+    fn new(root_schema: &'a Value) -> Self {
+        let mut builder = Self { root_schema, rules: Vec::new(), names: HashSet::new(), refs: HashMap::new() };
+        builder.use_primitive("ws");
+        builder
+    }
+
+    fn render(&self) -> String {
+        // `root` first for readability; GBNF itself is order-independent
+        let mut out = String::new();
+        for (name, body) in self.rules.iter().filter(|(n, _)| n == "root").chain(self.rules.iter().filter(|(n, _)| n != "root")) {
+            out.push_str(&format!("{name} ::= {body}\n"));
+        }
+        out
+    }
+
+    fn add_rule(&mut self, name: &str, body: String) {
+        self.names.insert(name.to_string());
+        self.rules.push((name.to_string(), body));
+    }
+
+    fn use_primitive(&mut self, name: &str) -> String {
+        if !self.names.contains(name) {
+            let body = PRIMITIVES.iter().find(|(n, _)| *n == name).map(|(_, b)| *b).unwrap_or("value");
+            self.add_rule(name, body.to_string());
+            // Composite primitives depend on the generic value grammar
+            if matches!(name, "value" | "object" | "array") {
+                for dep in ["value", "object", "array", "string", "number", "boolean", "null"] {
+                    self.use_primitive(dep);
+                }
+            }
+            if name != "ws" { self.use_primitive("ws"); }
+        }
+        name.to_string()
+    }
+
+    fn fresh_name(&mut self, hint: &str) -> String {
+        let base: String = hint
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let base = if base.is_empty() || PRIMITIVES.iter().any(|(n, _)| *n == base) { format!("{base}-rule") } else { base };
+        let mut name = base.clone();
+        let mut n = 1;
+        while self.names.contains(&name) {
+            n += 1;
+            name = format!("{base}-{n}");
+        }
+        self.names.insert(name.clone());
+        name
+    }
+
+    /// Return the name of a rule matching `schema`, creating rules as needed
+    fn visit(&mut self, schema: &Value, hint: &str) -> Result<String, GrammarError> {
+        let obj = match schema {
+            Value::Object(obj) => obj,
+            // `true` / `{}` accept anything; `false` is unsatisfiable but we stay permissive
+            _ => return Ok(self.use_primitive("value")),
+        };
+
+        if let Some(Value::String(reference)) = obj.get("$ref") {
+            return self.visit_ref(reference);
+        }
+        if let Some(values) = obj.get("const").map(std::slice::from_ref).or_else(|| obj.get("enum").and_then(Value::as_array).map(Vec::as_slice)) {
+            let alts: Vec<String> = values.iter().map(json_literal).collect();
+            self.use_primitive("ws");
+            let name = self.fresh_name(hint);
+            self.rules.push((name.clone(), format!("( {} ) ws", alts.join(" | "))));
+            return Ok(name);
+        }
+        if let Some(Value::Array(variants)) = obj.get("anyOf").or_else(|| obj.get("oneOf")) {
+            let name = self.fresh_name(hint);
+            let mut alts = Vec::new();
+            for (i, v) in variants.iter().enumerate() {
+                alts.push(self.visit(v, &format!("{name}-{i}"))?);
+            }
+            self.rules.push((name.clone(), alts.join(" | ")));
+            return Ok(name);
+        }
+        // schemars wraps documented `$ref` fields as a single-element `allOf`
+        if let Some(Value::Array(parts)) = obj.get("allOf") {
+            if let [only] = parts.as_slice() {
+                return self.visit(only, hint);
+            }
+        }
+
+        match obj.get("type") {
+            Some(Value::String(t)) => self.visit_typed(t, obj, hint),
+            Some(Value::Array(types)) => {
+                let name = self.fresh_name(hint);
+                let mut alts = Vec::new();
+                for t in types.iter().filter_map(Value::as_str) {
+                    alts.push(self.visit_typed(t, obj, &format!("{name}-{t}"))?);
+                }
+                self.rules.push((name.clone(), alts.join(" | ")));
+                Ok(name)
+            }
+            _ if obj.contains_key("properties") => self.visit_typed("object", obj, hint),
+            _ => Ok(self.use_primitive("value")),
+        }
+    }
+
+    fn visit_ref(&mut self, reference: &str) -> Result<String, GrammarError> {
+        if let Some(name) = self.refs.get(reference) {
+            return Ok(name.clone());
+        }
+        // Self-referencing types (`Vec<Self>`, `Option<Box<Self>>`) point at the document
+        // root, which `schema_to_gbnf` always emits as `root`
+        if reference == "#" {
+            return Ok("root".to_string());
+        }
+        let def_name = reference
+            .strip_prefix("#/$defs/")
+            .or_else(|| reference.strip_prefix("#/definitions/"))
+            .ok_or_else(|| GrammarError::UnsupportedRef(reference.to_string()))?;
+        let target = self.root_schema
+            .get("$defs")
+            .or_else(|| self.root_schema.get("definitions"))
+            .and_then(|defs| defs.get(def_name))
+            .ok_or_else(|| GrammarError::UnsupportedRef(reference.to_string()))?;
+        // Register an alias before descending so recursive references terminate
+        let alias = self.fresh_name(def_name);
+        self.refs.insert(reference.to_string(), alias.clone());
+        let body = self.visit(target, &format!("{alias}-def"))?;
+        self.rules.push((alias.clone(), body));
+        Ok(alias)
+    }
+
+    fn visit_typed(&mut self, t: &str, obj: &serde_json::Map<String, Value>, hint: &str) -> Result<String, GrammarError> {
+        match t {
+            "string" | "number" | "integer" | "boolean" | "null" => Ok(self.use_primitive(t)),
+            "array" => {
+                let item = match obj.get("items") {
+                    Some(items) => self.visit(items, &format!("{hint}-item"))?,
+                    None => self.use_primitive("value"),
+                };
+                let name = self.fresh_name(hint);
+                self.rules.push((name.clone(), format!(r#""[" ws ( {item} ( "," ws {item} )* )? "]" ws"#)));
+                Ok(name)
+            }
+            "object" => {
+                let name = self.fresh_name(hint);
+                let properties = match obj.get("properties").and_then(Value::as_object) {
+                    Some(p) if !p.is_empty() => p,
+                    _ => {
+                        let generic = self.use_primitive("object");
+                        self.rules.push((name.clone(), generic));
+                        return Ok(name);
+                    }
+                };
+                let required: HashSet<&str> = obj
+                    .get("required")
+                    .and_then(Value::as_array)
+                    .map(|r| r.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+
+                let mut required_kvs = Vec::new();
+                let mut optional_kvs = Vec::new();
+                for (key, prop_schema) in properties {
+                    let value = self.visit(prop_schema, &format!("{name}-{key}"))?;
+                    let kv = format!(r#"{} ":" ws {value}"#, json_literal(&Value::String(key.clone())));
+                    if required.contains(key.as_str()) { required_kvs.push(kv) } else { optional_kvs.push(kv) }
+                }
+
+                let body = if required_kvs.is_empty() {
+                    // No fixed anchor for commas: accept any sequence of the known members
+                    let any = optional_kvs.join(" | ");
+                    format!(r#""{{" ws ( ( {any} ) ( "," ws ( {any} ) )* )? "}}" ws"#)
+                } else {
+                    let mut parts = vec![required_kvs.join(r#" "," ws "#)];
+                    parts.extend(optional_kvs.iter().map(|kv| format!(r#"( "," ws {kv} )?"#)));
+                    format!(r#""{{" ws {} "}}" ws"#, parts.join(" "))
+                };
+                self.rules.push((name.clone(), body));
+                Ok(name)
+            }
+            other => Err(GrammarError::UnknownType(other.to_string())),
+        }
+    }
+}
+
+/// GBNF literal matching the JSON encoding of `value`
+fn json_literal(value: &Value) -> String {
+    let json = value.to_string();
+    let escaped = json.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{escaped}\"")
+}
@@ -1,8 +1,9 @@
-use super::Interceptor;
+use super::{Interceptor, StreamTranscript};
 use async_trait::async_trait;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::path::PathBuf;
-use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use crate::runtime::fs;
 use chrono::Utc;
 
 #[derive(Debug)]
@@ -16,9 +17,9 @@ impl FileInterceptor {
     }
 }
 
-#[async_trait]
-impl Interceptor for FileInterceptor {
-    async fn save(&self, prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+impl FileInterceptor {
+    /// A new transcript file under `base_path`, named by the current time
+    async fn create(&self) -> std::io::Result<PathBuf> {
         let timestamp = Utc::now();
         let filename = format!("query_{}.md", timestamp.format("%Y%m%d_%H%M%S_%3f"));
         let file_path = self.base_path.join(filename);
@@ -27,17 +28,67 @@ impl Interceptor for FileInterceptor {
         if let Some(parent) = file_path.parent() {
             fs::create_dir_all(parent).await?;
         }
-        
+        Ok(file_path)
+    }
+}
+
+/// Correlation id and tenant lines, if any
+fn header() -> String {
+    let mut header = String::new();
+    if let Some(id) = crate::correlation::current() {
+        header.push_str(&format!("Correlation id: {}\n", id));
+    }
+    if let Some(tenant) = crate::tenancy::current() {
+        header.push_str(&format!("Tenant: {}\n", tenant));
+    }
+    if !header.is_empty() {
+        header.push('\n');
+    }
+    header
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl Interceptor for FileInterceptor {
+    async fn save(&self, prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file_path = self.create().await?;
         let content = format!(
-            "# Prompt\n\n{}\n\n# Response\n\n{}\n",
+            "{}# Prompt\n\n{}\n\n# Response\n\n{}\n",
+            header(),
             prompt,
             response
         );
         
-        let mut file = fs::File::create(&file_path).await?;
-        file.write_all(content.as_bytes()).await?;
-        file.flush().await?;
+        fs::write(&file_path, content).await?;
         
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Writes the same layout as `save`, appending the response as it streams
+    async fn start_stream(&self, prompt: &str) -> Result<Option<Box<dyn StreamTranscript>>, Box<dyn std::error::Error>> {
+        let file_path = self.create().await?;
+        fs::write(&file_path, format!("{}# Prompt\n\n{}\n\n# Response\n\n", header(), prompt)).await?;
+        let file = OpenOptions::new().append(true).open(&file_path)?;
+        Ok(Some(Box::new(FileTranscript { file })))
+    }
+}
+
+/// A streamed reply appended to its transcript file chunk by chunk
+#[derive(Debug)]
+struct FileTranscript {
+    file: File,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StreamTranscript for FileTranscript {
+    async fn on_chunk(&mut self, chunk: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.file.write_all(chunk.as_bytes())?;
+        Ok(())
+    }
+
+    async fn on_stream_end(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.file.write_all(b"\n")?;
+        Ok(())
+    }
+}
@@ -1,10 +1,108 @@
 use async_trait::async_trait;
 use std::fmt::Debug;
+use std::sync::Arc;
 
-#[async_trait]
+use crate::audit::QueryRecord;
+use crate::core::render_transcript;
+use crate::request::ModelRequest;
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait Interceptor: Send + Sync + Debug {
+    /// Record one exchange. Runs inside the query, so `correlation::current()` returns
+    /// its correlation id, and `tenancy::current()` its tenant when it runs for one.
     async fn save(&self, prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Record an exchange sent with `ask_request`. Default saves the flattened messages
+    /// (`render_transcript`) through `save`; override to keep parameters and tools.
+    async fn save_request(&self, request: &ModelRequest, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.save(&render_transcript(&request.messages), response).await
+    }
+
+    /// Record the `QueryRecord` of a resolver query that ran over this interceptor's
+    /// client, once it finishes or fails. Default does nothing.
+    async fn save_record(&self, _record: &QueryRecord) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Record a request the provider failed, with the error's message. Default does
+    /// nothing.
+    async fn save_error(&self, _prompt: &str, _error: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Open a transcript of a streamed reply to `prompt`. `FlexibleClient` hands it each
+    /// text chunk as it arrives and ends it with the stream, instead of calling `save`
+    /// once the stream is over. Default is None, which keeps the `save` call.
+    async fn start_stream(&self, _prompt: &str) -> Result<Option<Box<dyn StreamTranscript>>, Box<dyn std::error::Error>> {
+        Ok(None)
+    }
+
+    /// Write out anything buffered; called by `ShutdownHandle::shutdown` through
+    /// `FlexibleClient`. Default does nothing, for interceptors that write as they go.
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Rewrite each text (prompt, response, message or error) before the interceptors
+    /// after this one in an `InterceptorStack` see it, e.g. to redact secrets. Default
+    /// leaves it unchanged.
+    fn rewrite(&self, _text: &mut String) {}
+}
+
+/// One streamed reply being recorded, from `Interceptor::start_stream`
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait StreamTranscript: Send {
+    /// The next text chunk of the reply
+    async fn on_chunk(&mut self, chunk: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// The stream ended, or failed (the error then also goes to `Interceptor::save_error`)
+    async fn on_stream_end(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+// Lets shared interceptors go into an `InterceptorStack` while the caller keeps a handle
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<I: Interceptor + ?Sized> Interceptor for Arc<I> {
+    async fn save(&self, prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().save(prompt, response).await
+    }
+
+    async fn save_request(&self, request: &ModelRequest, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().save_request(request, response).await
+    }
+
+    async fn save_record(&self, record: &QueryRecord) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().save_record(record).await
+    }
+
+    async fn save_error(&self, prompt: &str, error: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().save_error(prompt, error).await
+    }
+
+    async fn start_stream(&self, prompt: &str) -> Result<Option<Box<dyn StreamTranscript>>, Box<dyn std::error::Error>> {
+        self.as_ref().start_stream(prompt).await
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_ref().flush().await
+    }
+
+    fn rewrite(&self, text: &mut String) {
+        self.as_ref().rewrite(text);
+    }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub mod file;
-pub use file::FileInterceptor;
\ No newline at end of file
+pub mod policy;
+pub mod stack;
+#[cfg(not(target_arch = "wasm32"))]
+pub use file::FileInterceptor;
+#[cfg(not(target_arch = "wasm32"))]
+pub use policy::InterceptorRetry;
+pub use policy::{GuardedInterceptor, InterceptorErrorPolicy};
+pub use stack::{InterceptorFilter, InterceptorStack, Redactor};
\ No newline at end of file
@@ -0,0 +1,336 @@
+//! What happens when an interceptor fails.
+//!
+//! `FlexibleClient` runs each interceptor through a `GuardedInterceptor`. A failed save is
+//! handled by the guard's `InterceptorErrorPolicy`: ignored, logged (once), or turned into
+//! a failed request. With `GuardedInterceptor::with_retry`, failed saves are first queued
+//! and retried in the background with backoff, so a sink that is briefly down loses
+//! nothing; the policy applies only when the queue is full.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use semantic_query::clients::FlexibleClient;
+//! # use semantic_query::interceptors::{FileInterceptor, GuardedInterceptor, InterceptorErrorPolicy, InterceptorRetry};
+//! let audit = GuardedInterceptor::new(Arc::new(FileInterceptor::new("audit".into())))
+//!     .on_error(InterceptorErrorPolicy::FailRequest)
+//!     .with_retry(InterceptorRetry::default());
+//! let client = FlexibleClient::mock().0.with_guarded_interceptor(audit.clone());
+//! // later: audit.pending(), audit.dropped()
+//! ```
+//!
+//! Saves still failing after `InterceptorRetry::max_attempts` are logged as errors and
+//! counted in `dropped`. `FlexibleClient::flush` (and so `ShutdownHandle::shutdown`)
+//! retries the queue once more and fails if saves remain undelivered or were dropped
+//! since the last flush.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+use tracing::{debug, warn};
+#[cfg(not(target_arch = "wasm32"))]
+use tracing::error;
+
+use super::Interceptor;
+use crate::audit::QueryRecord;
+use crate::correlation::{self, CorrelationId};
+use crate::error::AIError;
+use crate::request::ModelRequest;
+use crate::tenancy;
+
+/// How a failed save affects the request that produced it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InterceptorErrorPolicy {
+    /// Drop the failure without a trace
+    Ignore,
+    /// Log the first failure as a warning and later ones at debug level
+    #[default]
+    WarnOnce,
+    /// Fail the request with `AIError::Interceptor`, discarding the response. Query
+    /// records are saved after the query returns, so their failures are logged instead.
+    FailRequest,
+}
+
+/// Background retries for failed saves
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg(not(target_arch = "wasm32"))]
+pub struct InterceptorRetry {
+    /// Saves waiting at most; when full, the error policy applies to new failures
+    pub capacity: usize,
+    /// Attempts per save, counting the one made during the request
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each one after
+    pub backoff: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for InterceptorRetry {
+    fn default() -> Self {
+        Self { capacity: 1024, max_attempts: 5, backoff: Duration::from_millis(500) }
+    }
+}
+
+/// One save, owned so it can wait in the retry queue
+#[derive(Debug, Clone)]
+pub(crate) enum Exchange {
+    Prompt { prompt: String, response: String },
+    Request { request: ModelRequest, response: String },
+    Record(QueryRecord),
+    Error { prompt: String, error: String },
+}
+
+impl Exchange {
+    async fn send(&self, interceptor: &dyn Interceptor) -> Result<(), String> {
+        let result = match self {
+            Self::Prompt { prompt, response } => interceptor.save(prompt, response).await,
+            Self::Request { request, response } => interceptor.save_request(request, response).await,
+            Self::Record(record) => interceptor.save_record(record).await,
+            Self::Error { prompt, error } => interceptor.save_error(prompt, error).await,
+        };
+        result.map_err(|e| e.to_string())
+    }
+
+    /// A failed request, or the record of a failed query
+    pub(crate) fn is_failure(&self) -> bool {
+        match self {
+            Self::Error { .. } => true,
+            Self::Record(record) => !record.succeeded(),
+            Self::Prompt { .. } | Self::Request { .. } => false,
+        }
+    }
+
+    /// Pass every text through `interceptor.rewrite`
+    pub(crate) fn rewrite(&mut self, interceptor: &dyn Interceptor) {
+        match self {
+            Self::Prompt { prompt, response } => {
+                interceptor.rewrite(prompt);
+                interceptor.rewrite(response);
+            }
+            Self::Request { request, response } => {
+                request.messages.iter_mut().for_each(|message| interceptor.rewrite(&mut message.content));
+                interceptor.rewrite(response);
+            }
+            Self::Record(record) => {
+                if let Some(prompt) = &mut record.prompt {
+                    interceptor.rewrite(prompt);
+                }
+            }
+            Self::Error { prompt, error } => {
+                interceptor.rewrite(prompt);
+                interceptor.rewrite(error);
+            }
+        }
+    }
+}
+
+/// An interceptor with its error policy and optional retry queue (see the module docs).
+/// Clones share the queue and counters.
+#[derive(Debug, Clone)]
+pub struct GuardedInterceptor {
+    inner: Arc<dyn Interceptor>,
+    policy: InterceptorErrorPolicy,
+    warned: Arc<AtomicBool>,
+    queue: Option<Arc<RetryQueue>>,
+}
+
+impl GuardedInterceptor {
+    /// `interceptor` under the default policy, `WarnOnce`, without retries
+    pub fn new(interceptor: Arc<dyn Interceptor>) -> Self {
+        Self { inner: interceptor, policy: InterceptorErrorPolicy::default(), warned: Arc::default(), queue: None }
+    }
+
+    #[must_use]
+    pub fn on_error(mut self, policy: InterceptorErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Queue failed saves for retry instead of applying the policy right away
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn with_retry(mut self, retry: InterceptorRetry) -> Self {
+        self.queue = Some(Arc::new(RetryQueue::new(self.inner.clone(), retry)));
+        self
+    }
+
+    pub fn interceptor(&self) -> &Arc<dyn Interceptor> {
+        &self.inner
+    }
+
+    pub fn policy(&self) -> InterceptorErrorPolicy {
+        self.policy
+    }
+
+    /// Saves waiting for a retry
+    pub fn pending(&self) -> usize {
+        self.queue.as_ref().map_or(0, |queue| queue.state.lock().unwrap().entries.len())
+    }
+
+    /// Saves given up on after `InterceptorRetry::max_attempts`
+    pub fn dropped(&self) -> u64 {
+        self.queue.as_ref().map_or(0, |queue| queue.dropped.load(Ordering::SeqCst))
+    }
+
+    /// Save `exchange`; `Err` only under `FailRequest`
+    pub(crate) async fn deliver(&self, exchange: Exchange) -> Result<(), AIError> {
+        let error = match exchange.send(self.inner.as_ref()).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        if let Some(queue) = &self.queue {
+            if queue.push(exchange) {
+                debug!(error = %error, "Interceptor save failed; queued for retry");
+                return Ok(());
+            }
+            return self.fail(format!("{error} (retry queue full)"));
+        }
+        self.fail(error)
+    }
+
+    /// Apply the policy to a failure that is not retried
+    pub(crate) fn fail(&self, error: String) -> Result<(), AIError> {
+        match self.policy {
+            InterceptorErrorPolicy::Ignore => Ok(()),
+            InterceptorErrorPolicy::WarnOnce if !self.warned.swap(true, Ordering::SeqCst) => {
+                warn!(error = %error, "Interceptor save failed; further failures are logged at debug level");
+                Ok(())
+            }
+            InterceptorErrorPolicy::WarnOnce => {
+                debug!(error = %error, "Interceptor save failed");
+                Ok(())
+            }
+            InterceptorErrorPolicy::FailRequest => Err(AIError::Interceptor(error)),
+        }
+    }
+
+    /// Retry the queue once, then flush the interceptor
+    pub(crate) async fn flush(&self) -> Result<(), String> {
+        if let Some(queue) = &self.queue {
+            queue.drain().await;
+        }
+        self.inner.flush().await.map_err(|e| format!("Interceptor flush failed: {e}"))?;
+        match &self.queue {
+            Some(queue) => queue.check(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A save waiting for a retry, with the ids it was made under
+#[derive(Debug)]
+struct Pending {
+    exchange: Exchange,
+    attempts: u32,
+    correlation: Option<CorrelationId>,
+    tenant: Option<String>,
+}
+
+impl Pending {
+    async fn send(&self, interceptor: &dyn Interceptor) -> Result<(), String> {
+        let send = correlation::scope(self.correlation.clone().unwrap_or_default(), self.exchange.send(interceptor));
+        match &self.tenant {
+            Some(tenant) => tenancy::scope(tenant.clone(), send).await,
+            None => send.await,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    entries: VecDeque<Pending>,
+    /// A worker task is draining `entries`
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    working: bool,
+}
+
+#[derive(Debug)]
+struct RetryQueue {
+    inner: Arc<dyn Interceptor>,
+    #[cfg(not(target_arch = "wasm32"))]
+    config: InterceptorRetry,
+    state: Mutex<QueueState>,
+    dropped: AtomicU64,
+    /// `dropped` as of the last flush
+    reported: AtomicU64,
+}
+
+impl RetryQueue {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new(inner: Arc<dyn Interceptor>, config: InterceptorRetry) -> Self {
+        Self { inner, config, state: Mutex::default(), dropped: AtomicU64::new(0), reported: AtomicU64::new(0) }
+    }
+
+    /// Queue a failed save, starting a worker if none runs; `false` when there is no room
+    #[cfg(not(target_arch = "wasm32"))]
+    fn push(self: &Arc<Self>, exchange: Exchange) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if self.config.max_attempts < 2 || state.entries.len() >= self.config.capacity {
+            return false;
+        }
+        state.entries.push_back(Pending { exchange, attempts: 1, correlation: correlation::current(), tenant: tenancy::current() });
+        if !std::mem::replace(&mut state.working, true) {
+            crate::runtime::spawn(self.clone().work());
+        }
+        true
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn push(self: &Arc<Self>, _exchange: Exchange) -> bool {
+        false
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn work(self: Arc<Self>) {
+        loop {
+            let mut pending = {
+                let mut state = self.state.lock().unwrap();
+                match state.entries.pop_front() {
+                    Some(pending) => pending,
+                    None => {
+                        state.working = false;
+                        return;
+                    }
+                }
+            };
+            let backoff = self.config.backoff.saturating_mul(1 << (pending.attempts - 1).min(16));
+            crate::runtime::sleep(backoff).await;
+            match pending.send(self.inner.as_ref()).await {
+                Ok(()) => debug!(attempts = pending.attempts + 1, "Interceptor save delivered on retry"),
+                Err(e) => {
+                    pending.attempts += 1;
+                    if pending.attempts >= self.config.max_attempts {
+                        self.dropped.fetch_add(1, Ordering::SeqCst);
+                        error!(error = %e, attempts = pending.attempts, "Interceptor save dropped after retries");
+                    } else {
+                        self.state.lock().unwrap().entries.push_back(pending);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Try every queued save once, now
+    async fn drain(&self) {
+        let entries = std::mem::take(&mut self.state.lock().unwrap().entries);
+        let mut failed = VecDeque::new();
+        for mut pending in entries {
+            if pending.send(self.inner.as_ref()).await.is_err() {
+                pending.attempts += 1;
+                failed.push_back(pending);
+            }
+        }
+        self.state.lock().unwrap().entries.extend(failed);
+    }
+
+    /// `Err` if saves are still queued or were dropped since the last check
+    fn check(&self) -> Result<(), String> {
+        let pending = self.state.lock().unwrap().entries.len();
+        let dropped = self.dropped.load(Ordering::SeqCst);
+        let new_drops = dropped - self.reported.swap(dropped, Ordering::SeqCst);
+        if pending == 0 && new_drops == 0 {
+            return Ok(());
+        }
+        Err(format!("Interceptor saves undelivered: {pending} still queued, {new_drops} dropped after retries"))
+    }
+}
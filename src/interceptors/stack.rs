@@ -0,0 +1,306 @@
+//! Several interceptors on one client.
+//!
+//! An `InterceptorStack` runs its interceptors in the order they were pushed. Each one
+//! has its own `InterceptorErrorPolicy` (see `policy`) and an optional
+//! `InterceptorFilter` that picks which exchanges it sees. Before the next interceptor
+//! runs, each one may rewrite the texts with `Interceptor::rewrite`, so a `Redactor`
+//! pushed first scrubs what every sink after it stores:
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use semantic_query::clients::FlexibleClient;
+//! # use semantic_query::interceptors::{FileInterceptor, InterceptorFilter, InterceptorStack, Redactor};
+//! let stack = InterceptorStack::new()
+//!     .push(Redactor::new(|text| *text = text.replace("sk-live", "[key]")))
+//!     .push(FileInterceptor::new("transcripts".into()))
+//!     .push_filtered(FileInterceptor::new("failures".into()), InterceptorFilter::default().only_errors());
+//! let client = FlexibleClient::mock().0.with_interceptors(stack);
+//! ```
+//!
+//! `FlexibleClient` hands the stack every exchange: replies to `ask_raw`, `ask_messages`,
+//! `ask_request` and constrained requests, streamed replies (chunk by chunk to
+//! interceptors that open a `StreamTranscript`, whole once the stream ends to the
+//! others), requests the provider failed (`Interceptor::save_error`) and query records.
+//! `QueryResolver::intercepted` puts a stack in front of any resolver's client.
+
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+
+use super::policy::{Exchange, GuardedInterceptor};
+use super::{Interceptor, StreamTranscript};
+use crate::clients::FlexibleClient;
+use crate::core::{LowLevelClient, QueryResolver, RawByteStream};
+use crate::error::AIError;
+use crate::streaming::SseTextDecoder;
+
+/// Which exchanges an interceptor in an `InterceptorStack` sees; the default sees all
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterceptorFilter {
+    /// Only failed requests and records of failed queries
+    pub only_errors: bool,
+    /// Only streamed replies (and failed streams)
+    pub only_streaming: bool,
+    /// Only clients whose `LowLevelClient::provider` is listed; any when empty
+    pub providers: Vec<String>,
+}
+
+impl InterceptorFilter {
+    #[must_use]
+    pub fn only_errors(mut self) -> Self {
+        self.only_errors = true;
+        self
+    }
+
+    #[must_use]
+    pub fn only_streaming(mut self) -> Self {
+        self.only_streaming = true;
+        self
+    }
+
+    /// Add `provider` (e.g. `"claude"`) to `providers`
+    #[must_use]
+    pub fn provider(mut self, provider: impl Into<String>) -> Self {
+        self.providers.push(provider.into());
+        self
+    }
+
+    fn accepts(&self, context: &ExchangeContext, exchange: &Exchange) -> bool {
+        (!self.only_errors || exchange.is_failure())
+            && (!self.only_streaming || context.streaming)
+            && self.accepts_provider(context)
+    }
+
+    /// Whether the layer gets the chunks of a (so far successful) stream
+    fn accepts_stream(&self, context: &ExchangeContext) -> bool {
+        !self.only_errors && self.accepts_provider(context)
+    }
+
+    fn accepts_provider(&self, context: &ExchangeContext) -> bool {
+        self.providers.is_empty() || context.provider.is_some_and(|p| self.providers.iter().any(|name| name == p))
+    }
+}
+
+/// Where an exchange came from
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ExchangeContext {
+    pub provider: Option<&'static str>,
+    pub streaming: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Layer {
+    guard: GuardedInterceptor,
+    filter: InterceptorFilter,
+}
+
+/// Interceptors run in order, each with its policy and filter (see the module docs)
+#[derive(Debug, Clone, Default)]
+pub struct InterceptorStack {
+    layers: Vec<Layer>,
+}
+
+impl InterceptorStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `interceptor` under the default policy, seeing every exchange. Pass an `Arc`
+    /// to keep a handle to it.
+    #[must_use]
+    pub fn push(self, interceptor: impl Interceptor + 'static) -> Self {
+        self.push_guarded(GuardedInterceptor::new(Arc::new(interceptor)))
+    }
+
+    /// Add `interceptor` under the default policy, seeing only what `filter` accepts
+    #[must_use]
+    pub fn push_filtered(self, interceptor: impl Interceptor + 'static, filter: InterceptorFilter) -> Self {
+        self.push_with(GuardedInterceptor::new(Arc::new(interceptor)), filter)
+    }
+
+    /// Add an interceptor with its own policy and retry queue
+    #[must_use]
+    pub fn push_guarded(self, interceptor: GuardedInterceptor) -> Self {
+        self.push_with(interceptor, InterceptorFilter::default())
+    }
+
+    #[must_use]
+    pub fn push_with(mut self, interceptor: GuardedInterceptor, filter: InterceptorFilter) -> Self {
+        self.layers.push(Layer { guard: interceptor, filter });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// The interceptors, in order
+    pub fn interceptors(&self) -> impl Iterator<Item = &GuardedInterceptor> {
+        self.layers.iter().map(|layer| &layer.guard)
+    }
+
+    /// Hand `exchange` to every layer that accepts it. All layers run; the first
+    /// `FailRequest` error is returned.
+    pub(crate) async fn deliver(&self, context: ExchangeContext, exchange: Exchange) -> Result<(), AIError> {
+        self.deliver_except(context, exchange, &[]).await
+    }
+
+    /// `deliver`, skipping the layers marked in `skip`
+    async fn deliver_except(&self, context: ExchangeContext, mut exchange: Exchange, skip: &[bool]) -> Result<(), AIError> {
+        let mut first_error = None;
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !skip.get(index).copied().unwrap_or(false) && layer.filter.accepts(&context, &exchange) {
+                if let Err(e) = layer.guard.deliver(exchange.clone()).await {
+                    first_error.get_or_insert(e);
+                }
+            }
+            exchange.rewrite(layer.guard.interceptor().as_ref());
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Flush every layer, joining their errors
+    pub(crate) async fn flush(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for layer in &self.layers {
+            if let Err(e) = layer.guard.flush().await {
+                errors.push(e);
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors.join("; ")) }
+    }
+
+    /// Pass `stream` through while layers with a `StreamTranscript` get its text as it
+    /// arrives; the others get the whole reply through `save` when it ends. A
+    /// `FailRequest` error ends the stream as its last item.
+    pub(crate) fn tap_stream(&self, context: ExchangeContext, prompt: String, stream: RawByteStream) -> RawByteStream {
+        let stack = self.clone();
+        Box::pin(async_stream::stream! {
+            let mut stream = stream;
+            let mut transcripts = stack.start_transcripts(context, &prompt).await;
+            let mut decoder = SseTextDecoder::default();
+            let mut response = String::new();
+            while let Some(chunk) = stream.next().await {
+                let bytes = match chunk {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        let _ = stack.end_transcripts(&mut transcripts).await;
+                        let _ = stack.deliver(context, Exchange::Error { prompt, error: e.to_string() }).await;
+                        yield Err(e);
+                        return;
+                    }
+                };
+                let mut failed = Ok(());
+                for delta in decoder.push(&bytes) {
+                    if let Err(e) = stack.feed(&mut transcripts, &delta).await {
+                        failed = failed.and(Err(e));
+                    }
+                    response.push_str(&delta);
+                }
+                yield Ok(bytes);
+                if let Err(e) = failed {
+                    let _ = stack.end_transcripts(&mut transcripts).await;
+                    yield Err(e);
+                    return;
+                }
+            }
+            let ended = stack.end_transcripts(&mut transcripts).await;
+            let skip: Vec<bool> = transcripts.iter().map(Option::is_some).collect();
+            let saved = stack.deliver_except(context, Exchange::Prompt { prompt, response }, &skip).await;
+            if let Err(e) = ended.and(saved) {
+                yield Err(e);
+            }
+        })
+    }
+
+    /// A transcript from each layer that takes streams and opens one
+    async fn start_transcripts(&self, context: ExchangeContext, prompt: &str) -> Vec<Option<Box<dyn StreamTranscript>>> {
+        let mut transcripts = Vec::with_capacity(self.layers.len());
+        let mut prompt = prompt.to_string();
+        for layer in &self.layers {
+            let interceptor = layer.guard.interceptor();
+            let transcript = if layer.filter.accepts_stream(&context) {
+                interceptor.start_stream(&prompt).await.map_err(|e| e.to_string())
+            } else {
+                Ok(None)
+            };
+            transcripts.push(transcript.unwrap_or_else(|e| {
+                let _ = layer.guard.fail(format!("Could not start a stream transcript: {e}"));
+                None
+            }));
+            interceptor.rewrite(&mut prompt);
+        }
+        transcripts
+    }
+
+    /// Hand `chunk` to the open transcripts, rewritten by the layers before each
+    async fn feed(&self, transcripts: &mut [Option<Box<dyn StreamTranscript>>], chunk: &str) -> Result<(), AIError> {
+        let mut result = Ok(());
+        let mut chunk = chunk.to_string();
+        for (layer, transcript) in self.layers.iter().zip(transcripts.iter_mut()) {
+            if let Some(transcript) = transcript {
+                if let Err(e) = transcript.on_chunk(&chunk).await.map_err(|e| e.to_string()) {
+                    result = result.and(layer.guard.fail(e));
+                }
+            }
+            layer.guard.interceptor().rewrite(&mut chunk);
+        }
+        result
+    }
+
+    async fn end_transcripts(&self, transcripts: &mut [Option<Box<dyn StreamTranscript>>]) -> Result<(), AIError> {
+        let mut result = Ok(());
+        for (layer, transcript) in self.layers.iter().zip(transcripts.iter_mut()) {
+            if let Some(transcript) = transcript {
+                if let Err(e) = transcript.on_stream_end().await.map_err(|e| e.to_string()) {
+                    result = result.and(layer.guard.fail(e));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// A stack layer that rewrites texts for the layers after it and saves nothing
+pub struct Redactor {
+    rewrite: Box<dyn Fn(&mut String) + Send + Sync>,
+}
+
+impl Redactor {
+    pub fn new(rewrite: impl Fn(&mut String) + Send + Sync + 'static) -> Self {
+        Self { rewrite: Box::new(rewrite) }
+    }
+}
+
+impl fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redactor").finish_non_exhaustive()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl Interceptor for Redactor {
+    async fn save(&self, _prompt: &str, _response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn rewrite(&self, text: &mut String) {
+        (self.rewrite)(text);
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// This resolver with its client wrapped in a `FlexibleClient` running `stack`, so
+    /// the stack sees every query whatever the client. Interceptors the client already
+    /// has still run, before the stack.
+    pub fn intercepted(&self, stack: InterceptorStack) -> QueryResolver<FlexibleClient> {
+        self.with_client(FlexibleClient::new(self.client().clone_box()).with_interceptors(stack))
+    }
+}
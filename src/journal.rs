@@ -0,0 +1,228 @@
+//! Event-sourced record of a streamed response.
+//!
+//! A `Journal` stores every `StreamItem` with the time it arrived. It can be written to
+//! disk as JSON lines while streaming (`record`), loaded back (`load`), replayed at any
+//! speed for demos and UI tests (`replay`), and folded into the `ParsedResponse` the
+//! non-streaming path would have produced (`ParsedResponse::from_journal`).
+
+use crate::core::ParsedResponse;
+use crate::streaming::{FinishReason, StreamItem, TextContent};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Journaled form of a `StreamItem`. Same layout as `StreamItem`, except tokens can be
+/// read back, since they are needed to rebuild the raw response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "content")]
+pub enum JournalItem<T> {
+    Token(String),
+    Text(TextContent),
+    Data(T),
+    Finished { reason: FinishReason },
+}
+
+impl<T: JsonSchema> From<StreamItem<T>> for JournalItem<T> {
+    fn from(item: StreamItem<T>) -> Self {
+        match item {
+            StreamItem::Token(t) => JournalItem::Token(t),
+            StreamItem::Text(t) => JournalItem::Text(t),
+            StreamItem::Data(d) => JournalItem::Data(d),
+            StreamItem::Finished { reason } => JournalItem::Finished { reason },
+        }
+    }
+}
+
+impl<T: JsonSchema> From<JournalItem<T>> for StreamItem<T> {
+    fn from(item: JournalItem<T>) -> Self {
+        match item {
+            JournalItem::Token(t) => StreamItem::Token(t),
+            JournalItem::Text(t) => StreamItem::Text(t),
+            JournalItem::Data(d) => StreamItem::Data(d),
+            JournalItem::Finished { reason } => StreamItem::Finished { reason },
+        }
+    }
+}
+
+/// One journaled item and when it arrived, relative to the start of the stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry<T> {
+    pub elapsed_ms: u64,
+    pub item: JournalItem<T>,
+}
+
+/// Ordered, timestamped record of a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journal<T> {
+    pub entries: Vec<JournalEntry<T>>,
+}
+
+impl<T> Default for Journal<T> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+
+impl<T> Journal<T>
+where
+    T: DeserializeOwned + JsonSchema + Serialize + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, elapsed_ms: u64, item: StreamItem<T>) {
+        self.entries.push(JournalEntry { elapsed_ms, item: item.into() });
+    }
+
+    /// The journaled items, without timestamps
+    pub fn items(&self) -> impl Iterator<Item = StreamItem<T>> + '_ {
+        self.entries.iter().map(|e| e.item.clone().into())
+    }
+
+    /// Rebuild the final response; see `ParsedResponse::from_journal`
+    pub fn to_parsed_response(&self) -> ParsedResponse<T> {
+        ParsedResponse::from_journal(self.items())
+    }
+
+    /// Serialize as JSON lines, one entry per line
+    pub fn to_jsonl(&self) -> Result<String, serde_json::Error> {
+        let mut out = String::new();
+        for entry in &self.entries {
+            out.push_str(&serde_json::to_string(entry)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Parse JSON lines produced by `to_jsonl` or `record`; blank lines are ignored
+    pub fn from_jsonl(text: &str) -> Result<Self, serde_json::Error> {
+        let entries = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { entries })
+    }
+}
+
+impl<T> ParsedResponse<T>
+where
+    T: DeserializeOwned + JsonSchema + Serialize + Clone,
+{
+    /// Rebuild a response from streamed items.
+    ///
+    /// When the items include tokens, the tokens are concatenated back into the raw
+    /// response and parsed exactly as `QueryResolver::query_mixed` would. Streams without
+    /// tokens (e.g. from `stream_from_async_read`) use their Text/Data items in order.
+    pub fn from_journal<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = StreamItem<T>>,
+    {
+        let items: Vec<StreamItem<T>> = items.into_iter().collect();
+        let mut raw = String::new();
+        let mut saw_token = false;
+        for item in &items {
+            if let StreamItem::Token(token) = item {
+                saw_token = true;
+                raw.push_str(token);
+            }
+        }
+        if saw_token {
+            ParsedResponse::from_raw(&raw)
+        } else {
+            ParsedResponse::from_stream_items(items)
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use futures_core::Stream;
+    use futures_util::StreamExt;
+    use std::fs::File;
+    use std::io::{BufWriter, Write};
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+    use tracing::warn;
+
+    impl<T> Journal<T>
+    where
+        T: DeserializeOwned + JsonSchema + Serialize + Clone + Send + 'static,
+    {
+        /// Pass `stream` through unchanged while appending each successful item to `path`
+        /// as a JSON line. Write failures are logged and do not interrupt the stream.
+        pub fn record<S, E>(stream: S, path: impl AsRef<Path>) -> impl Stream<Item = Result<StreamItem<T>, E>>
+        where
+            S: Stream<Item = Result<StreamItem<T>, E>>,
+        {
+            let path = path.as_ref().to_path_buf();
+            async_stream::stream! {
+                let started = Instant::now();
+                // Lines are buffered, so writes rarely reach the disk and stay inline
+                let mut file = match File::create(&path) {
+                    Ok(file) => Some(BufWriter::new(file)),
+                    Err(e) => {
+                        warn!(path = %path.display(), error = %e, "Could not create journal file; streaming without recording");
+                        None
+                    }
+                };
+                let mut stream = std::pin::pin!(stream);
+                while let Some(item) = stream.next().await {
+                    let mut write_failed = false;
+                    if let (Some(f), Ok(stream_item)) = (file.as_mut(), &item) {
+                        let entry = JournalEntry {
+                            elapsed_ms: started.elapsed().as_millis() as u64,
+                            item: JournalItem::from(stream_item.clone()),
+                        };
+                        if let Ok(mut line) = serde_json::to_string(&entry) {
+                            line.push('\n');
+                            if let Err(e) = f.write_all(line.as_bytes()) {
+                                warn!(error = %e, "Journal write failed; recording stopped");
+                                write_failed = true;
+                            }
+                        }
+                    }
+                    if write_failed {
+                        file = None;
+                    }
+                    yield item;
+                }
+                if let Some(mut f) = file {
+                    let _ = f.flush();
+                }
+            }
+        }
+
+        /// Load a journal written by `record` or `save`
+        pub async fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+            let text = crate::runtime::fs::read_to_string(path).await?;
+            Self::from_jsonl(&text).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        }
+
+        pub async fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+            let text = self.to_jsonl().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            crate::runtime::fs::write(path, text).await
+        }
+
+        /// Re-emit the items with their original spacing divided by `speed`
+        /// (2.0 = twice as fast). A non-positive or non-finite speed replays without delays.
+        pub fn replay(&self, speed: f64) -> impl Stream<Item = StreamItem<T>> {
+            let entries = self.entries.clone();
+            async_stream::stream! {
+                let mut previous_ms = 0u64;
+                for entry in entries {
+                    if speed.is_finite() && speed > 0.0 {
+                        let gap = entry.elapsed_ms.saturating_sub(previous_ms) as f64 / speed;
+                        if gap > 0.0 {
+                            crate::runtime::sleep(Duration::from_secs_f64(gap / 1000.0)).await;
+                        }
+                    }
+                    previous_ms = entry.elapsed_ms;
+                    yield entry.item.into();
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::AsyncRead;
 use tokio::sync::mpsc;
 use async_stream::stream;
 use futures_core::stream::Stream;
-use tracing::{debug, trace, instrument};
+use crate::error::ParseLimitError;
+use crate::streaming::{BufferPolicy, ReadBuffer};
+use tracing::{debug, trace, warn, instrument};
 
 // All older sanitization/extraction helpers removed in favor of streaming parser.
 
@@ -38,71 +40,165 @@ struct Frame {
     children: Vec<ObjCoords>,
 }
 
-/// Find all JSON object/array structures in the given text. Coordinates are byte indices.
-#[instrument(target = "semantic_query::json_stream", skip(text))]
-pub fn find_json_structures(text: &str) -> Vec<ObjCoords> {
-    let bytes = text.as_bytes();
-    let mut results: Vec<ObjCoords> = Vec::new();
-    let mut stack: Vec<Frame> = Vec::new();
+/// Bounds on the structures the scanner will track, so degenerate input (thousands of
+/// nested brackets, huge unclosed objects) cannot grow the frame stack without limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Maximum nesting depth of objects/arrays
+    pub max_depth: usize,
+    /// Maximum number of objects/arrays inside a single root structure
+    pub max_nodes: usize,
+    /// Maximum size in bytes of a single root structure
+    pub max_structure_bytes: usize,
+}
 
-    let mut in_string = false;
-    let mut escape = false;
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self { max_depth: 128, max_nodes: 100_000, max_structure_bytes: 16 * 1024 * 1024 }
+    }
+}
 
-    for (i, &b) in bytes.iter().enumerate() {
-        if in_string {
-            if escape {
-                escape = false;
-                continue;
-            }
-            match b {
-                b'\\' => escape = true,
-                b'"' => in_string = false,
-                _ => {}
+impl ParseLimits {
+    /// No limits; only for trusted input
+    pub fn unlimited() -> Self {
+        Self { max_depth: usize::MAX, max_nodes: usize::MAX, max_structure_bytes: usize::MAX }
+    }
+}
+
+/// Where in a text the scanner looks for JSON structures. Braces and brackets outside
+/// the scope are plain text, so prose like "{see above}" cannot be mistaken for data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DetectionScope {
+    /// Any brace or bracket may open a structure
+    #[default]
+    Anywhere,
+    /// Only inside markdown code fences (lines opening with three backticks), whatever
+    /// their language. A structure still open when its fence closes is dropped.
+    FencedOnly,
+    /// Only after the first occurrence of the marker, e.g. `"ANSWER:"`
+    AfterSentinel(String),
+}
+
+/// Follows a `DetectionScope` through a text, byte by byte
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScopeTracker {
+    scope: DetectionScope,
+    /// Past the sentinel, or on a line inside a fence
+    inside: bool,
+    /// Past the indentation and backticks opening the current line
+    mid_line: bool,
+    /// Backticks opening the current line
+    ticks: usize,
+    /// On a fence line: `Some(true)` when it opens a block, `Some(false)` when it closes one
+    marker: Option<bool>,
+    /// The last bytes before the sentinel, which may be split across chunks
+    tail: Vec<u8>,
+}
+
+impl ScopeTracker {
+    pub(crate) fn new(scope: DetectionScope) -> Self {
+        let inside = matches!(&scope, DetectionScope::AfterSentinel(sentinel) if sentinel.is_empty());
+        Self { scope, inside, ..Self::default() }
+    }
+
+    /// Advance over `b`. Returns whether `b` is in scope, and whether a fence just
+    /// closed, ending whatever structure was open inside it.
+    fn step(&mut self, b: u8) -> (bool, bool) {
+        match &self.scope {
+            DetectionScope::Anywhere => (true, false),
+            DetectionScope::AfterSentinel(_) if self.inside => (true, false),
+            DetectionScope::AfterSentinel(sentinel) => {
+                self.tail.push(b);
+                if self.tail.ends_with(sentinel.as_bytes()) {
+                    self.inside = true;
+                    self.tail = Vec::new();
+                } else if self.tail.len() >= 2 * sentinel.len() {
+                    self.tail.drain(..self.tail.len() + 1 - sentinel.len());
+                }
+                (false, false)
             }
-            continue;
+            DetectionScope::FencedOnly => self.fence_step(b),
         }
+    }
 
+    fn fence_step(&mut self, b: u8) -> (bool, bool) {
         match b {
-            b'"' => in_string = true,
-            b'{' => stack.push(Frame { start: i, kind: NodeType::Object, children: Vec::new() }),
-            b'[' => stack.push(Frame { start: i, kind: NodeType::Array, children: Vec::new() }),
-            b'}' => {
-                if let Some(frame) = stack.pop() {
-                    if frame.kind == NodeType::Object {
-                        let node = ObjCoords::new(frame.start, i, NodeType::Object, frame.children);
-                        if let Some(parent) = stack.last_mut() {
-                            parent.children.push(node);
-                        } else {
-                            results.push(node);
-                        }
-                    } else {
-                        // Unbalanced brace
-                    }
+            b'\n' => {
+                let inside = self.inside;
+                if self.marker.take() == Some(true) {
+                    self.inside = true;
                 }
+                self.mid_line = false;
+                self.ticks = 0;
+                (inside, false)
             }
-            b']' => {
-                if let Some(frame) = stack.pop() {
-                    if frame.kind == NodeType::Array {
-                        let node = ObjCoords::new(frame.start, i, NodeType::Array, frame.children);
-                        if let Some(parent) = stack.last_mut() {
-                            parent.children.push(node);
-                        } else {
-                            results.push(node);
-                        }
-                    } else {
-                        // Unbalanced bracket
-                    }
+            b'`' if !self.mid_line => {
+                self.ticks += 1;
+                if self.ticks < 3 {
+                    return (self.inside, false);
                 }
+                let closes = self.inside;
+                self.mid_line = true;
+                self.inside = false;
+                self.marker = Some(!closes);
+                (false, closes)
+            }
+            b' ' | b'\t' if !self.mid_line && self.ticks == 0 => (self.inside, false),
+            _ => {
+                self.mid_line = true;
+                (self.inside, false)
             }
-            _ => {}
         }
     }
 
+    /// Advance over text that is not scanned, e.g. text dropped from the front of a buffer
+    pub(crate) fn advance(&mut self, text: &str) {
+        text.bytes().for_each(|b| {
+            self.step(b);
+        });
+    }
+}
+
+/// Find all JSON object/array structures in the given text. Coordinates are byte indices.
+///
+/// Uses `ParseLimits::default()`; a structure that exceeds them is dropped (with a
+/// warning) and scanning continues after it. Use `try_find_json_structures` to get the error.
+#[instrument(target = "semantic_query::json_stream", skip(text))]
+pub fn find_json_structures(text: &str) -> Vec<ObjCoords> {
+    let mut parser = JsonStreamParser::new();
+    let results = parser.feed(text);
+    if let Some(err) = parser.take_error() {
+        warn!(target = "semantic_query::json_stream", error = %err, "dropped JSON structure exceeding parse limits");
+    }
     debug!(target = "semantic_query::json_stream", count = results.len(), "found root structures");
     results
 }
 
+/// `find_json_structures`, looking only within `scope`
+pub fn find_json_structures_in(text: &str, scope: &DetectionScope) -> Vec<ObjCoords> {
+    let mut parser = JsonStreamParser::new().with_scope(scope.clone());
+    let results = parser.feed(text);
+    if let Some(err) = parser.take_error() {
+        warn!(target = "semantic_query::json_stream", error = %err, "dropped JSON structure exceeding parse limits");
+    }
+    results
+}
+
+/// Like `find_json_structures` with explicit limits, failing on the first structure that exceeds them
+pub fn try_find_json_structures(text: &str, limits: ParseLimits) -> Result<Vec<ObjCoords>, ParseLimitError> {
+    let mut parser = JsonStreamParser::with_limits(limits);
+    let results = parser.feed(text);
+    match parser.take_error() {
+        Some(err) => Err(err),
+        None => Ok(results),
+    }
+}
+
 /// Stateful incremental stream parser that can be fed chunks and yields closed root nodes per feed.
+///
+/// Enforces `ParseLimits` (defaults unless built with `with_limits`). When a structure
+/// exceeds them, its partial state is discarded, the error is kept for `take_error`, and
+/// scanning resumes with the following bytes. Looks everywhere unless built `with_scope`.
 #[derive(Debug, Default)]
 pub struct JsonStreamParser {
     stack: Vec<Frame>,
@@ -110,11 +206,59 @@ pub struct JsonStreamParser {
     escape: bool,
     /// Absolute offset (bytes) from the beginning of the full stream to the start of current chunk
     offset: usize,
+    limits: ParseLimits,
+    /// Objects/arrays opened within the current root structure
+    nodes: usize,
+    error: Option<ParseLimitError>,
+    scope: ScopeTracker,
 }
 
 impl JsonStreamParser {
     pub fn new() -> Self { Self::default() }
 
+    pub fn with_limits(limits: ParseLimits) -> Self {
+        Self { limits, ..Self::default() }
+    }
+
+    /// Only report structures within `scope`
+    #[must_use]
+    pub fn with_scope(self, scope: DetectionScope) -> Self {
+        self.resuming(ScopeTracker::new(scope))
+    }
+
+    /// Scan on from where `scope` left off, for text that continues an earlier one
+    #[must_use]
+    pub(crate) fn resuming(mut self, scope: ScopeTracker) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// The first limit violation since the last call, if any
+    pub fn take_error(&mut self) -> Option<ParseLimitError> {
+        self.error.take()
+    }
+
+    fn trip(&mut self, err: ParseLimitError) {
+        debug!(target = "semantic_query::json_stream", error = %err, "parse limit exceeded; discarding structure");
+        self.stack = Vec::new();
+        self.nodes = 0;
+        self.error.get_or_insert(err);
+    }
+
+    fn open(&mut self, idx: usize, kind: NodeType) {
+        if self.stack.len() >= self.limits.max_depth {
+            return self.trip(ParseLimitError::Depth { limit: self.limits.max_depth, offset: idx });
+        }
+        if self.stack.is_empty() {
+            self.nodes = 0;
+        }
+        self.nodes += 1;
+        if self.nodes > self.limits.max_nodes {
+            return self.trip(ParseLimitError::Nodes { limit: self.limits.max_nodes, offset: idx });
+        }
+        self.stack.push(Frame { start: idx, kind, children: Vec::new() });
+    }
+
     /// Feed a new chunk. Returns any fully-closed root nodes found in this chunk.
     #[instrument(target = "semantic_query::json_stream", skip(self, chunk), fields(chunk_len = chunk.len(), offset = self.offset))]
     pub fn feed(&mut self, chunk: &str) -> Vec<ObjCoords> {
@@ -124,6 +268,27 @@ impl JsonStreamParser {
         for (i, &b) in bytes.iter().enumerate() {
             let idx = self.offset + i;
 
+            let (inside, closed) = self.scope.step(b);
+            if closed && !self.stack.is_empty() {
+                debug!(target = "semantic_query::json_stream", offset = idx, "fence closed inside a structure; discarding it");
+                self.stack = Vec::new();
+                self.nodes = 0;
+            }
+            if closed {
+                self.in_string = false;
+                self.escape = false;
+            }
+            if !inside {
+                continue;
+            }
+
+            if let Some(root) = self.stack.first() {
+                if idx - root.start >= self.limits.max_structure_bytes {
+                    let start = root.start;
+                    self.trip(ParseLimitError::Size { limit: self.limits.max_structure_bytes, start });
+                }
+            }
+
             if self.in_string {
                 if self.escape {
                     self.escape = false;
@@ -139,8 +304,8 @@ impl JsonStreamParser {
 
             match b {
                 b'"' => self.in_string = true,
-                b'{' => self.stack.push(Frame { start: idx, kind: NodeType::Object, children: Vec::new() }),
-                b'[' => self.stack.push(Frame { start: idx, kind: NodeType::Array, children: Vec::new() }),
+                b'{' => self.open(idx, NodeType::Object),
+                b'[' => self.open(idx, NodeType::Array),
                 b'}' => {
                     if let Some(frame) = self.stack.pop() {
                         if frame.kind == NodeType::Object {
@@ -173,6 +338,15 @@ impl JsonStreamParser {
         debug!(target = "semantic_query::json_stream", roots = roots.len(), new_offset = self.offset, "feed complete");
         roots
     }
+
+    /// The root array still open after the last `feed`: its start offset and the direct
+    /// elements (objects/arrays) that have closed so far. Scalars are not tracked.
+    pub fn open_array(&self) -> Option<(usize, &[ObjCoords])> {
+        match self.stack.first() {
+            Some(frame) if frame.kind == NodeType::Array => Some((frame.start, &frame.children)),
+            _ => None,
+        }
+    }
 }
 
 /// A deserialized item or an unknown structure (with coordinates) for upstream handling.
@@ -182,23 +356,53 @@ pub enum ParsedOrUnknown<T> {
     Unknown(ObjCoords),
 }
 
+/// Parse a candidate structure as strict JSON, then (with the `json5` feature) as JSON5,
+/// which accepts single quotes, unquoted keys, comments, and trailing commas.
+pub fn parse_candidate<T: DeserializeOwned>(candidate: &str) -> Option<T> {
+    if let Ok(parsed) = serde_json::from_str::<T>(candidate) {
+        return Some(parsed);
+    }
+    #[cfg(feature = "json5")]
+    if let Ok(parsed) = json5::from_str::<T>(candidate) {
+        trace!(target = "semantic_query::json_stream", len = candidate.len(), "recovered structure via json5 fallback");
+        return Some(parsed);
+    }
+    None
+}
+
 /// Attempt to deserialize a node; if it fails, recursively try children.
 fn descend_deserialize<T: DeserializeOwned>(text: &str, node: &ObjCoords, out: &mut Vec<ParsedOrUnknown<T>>) {
+    descend_spans::<T>(text, node, &parse_candidate::<T>, &mut |parsed, coords| out.push(match parsed {
+        Some(parsed) => ParsedOrUnknown::Parsed(parsed),
+        None => ParsedOrUnknown::Unknown(coords.clone()),
+    }));
+}
+
+/// Walk `node` in place within `text`, reporting each structure that `parse` accepts
+/// (`Some`) or each innermost structure that nothing below parsed from (`None`),
+/// together with its coordinates in `text`. Returns how many spans were reported.
+pub(crate) fn descend_spans<T>(
+    text: &str,
+    node: &ObjCoords,
+    parse: &dyn Fn(&str) -> Option<T>,
+    emit: &mut dyn FnMut(Option<T>, &ObjCoords),
+) -> usize {
     let slice_end = node.end + 1; // end is inclusive
-    let candidate = &text[node.start..slice_end];
-    if let Ok(parsed) = serde_json::from_str::<T>(candidate) {
-        out.push(ParsedOrUnknown::Parsed(parsed));
-        return; // success: do not attempt internals
+    if let Some(parsed) = parse(&text[node.start..slice_end]) {
+        emit(Some(parsed), node);
+        return 1; // success: do not attempt internals
     }
     // Try children
-    let before_len = out.len();
+    let mut emitted = 0;
     for child in &node.children {
-        descend_deserialize::<T>(text, child, out);
+        emitted += descend_spans::<T>(text, child, parse, emit);
     }
     // If none of the children produced anything, surface this unknown
-    if out.len() == before_len {
-        out.push(ParsedOrUnknown::Unknown(node.clone()));
+    if emitted == 0 {
+        emit(None, node);
+        emitted = 1;
     }
+    emitted
 }
 
 /// Produce a flat stream of parsed items or unknown structures from the given text.
@@ -233,11 +437,11 @@ pub fn extract_all<T: DeserializeOwned>(text: &str) -> Vec<T> {
     fn collect_from_node<T: DeserializeOwned>(text: &str, node: &ObjCoords, out: &mut Vec<T>) -> bool {
         let slice_end = node.end + 1;
         let s = &text[node.start..slice_end];
-        if let Ok(vs) = serde_json::from_str::<Vec<T>>(s) {
+        if let Some(vs) = parse_candidate::<Vec<T>>(s) {
             out.extend(vs);
             return true; // consumed node; skip children
         }
-        if let Ok(v) = serde_json::from_str::<T>(s) {
+        if let Some(v) = parse_candidate::<T>(s) {
             out.push(v);
             return true; // consumed node; skip children
         }
@@ -262,29 +466,18 @@ pub fn stream_coords_from_async_read<R>(mut reader: R, buf_size: usize) -> mpsc:
 where
     R: AsyncRead + Send + Unpin + 'static,
 {
-    let (tx, rx) = mpsc::channel(64);
-    tokio::spawn(async move {
+    let (tx, rx) = mpsc::channel(crate::spawn::DEFAULT_CHANNEL_CAPACITY);
+    crate::runtime::spawn(async move {
         tracing::debug!(target = "semantic_query::json_stream", "spawned stream_coords_from_async_read task");
         let mut parser = JsonStreamParser::new();
         let mut accum = String::new();
-        let mut buf = vec![0u8; buf_size.max(1024)];
-        loop {
-            match reader.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Ok(s) = std::str::from_utf8(&buf[..n]) {
-                        accum.push_str(s);
-                        for node in parser.feed(s) {
-                            // Ignore send errors if receiver dropped
-                            trace!(target = "semantic_query::json_stream", start = node.start, end = node.end, kind = ?node.kind, "emitting coords");
-                            let _ = tx.send(node).await;
-                        }
-                    } else {
-                        // Non-UTF8 bytes; skip for now (JSON is UTF-8)
-                        debug!(target = "semantic_query::json_stream", "skipping non-utf8 chunk");
-                    }
-                }
-                Err(e) => { debug!(target = "semantic_query::json_stream", error = %e, "read error"); break },
+        let mut buf = ReadBuffer::new(BufferPolicy::default(), buf_size);
+        while let Some(s) = buf.read(&mut reader).await {
+            accum.push_str(&s);
+            for node in parser.feed(&s) {
+                // Ignore send errors if receiver dropped
+                trace!(target = "semantic_query::json_stream", start = node.start, end = node.end, kind = ?node.kind, "emitting coords");
+                let _ = tx.send(node).await;
             }
         }
         tracing::debug!(target = "semantic_query::json_stream", "stream_coords_from_async_read completed");
@@ -300,35 +493,22 @@ where
     R: AsyncRead + Send + Unpin + 'static,
     T: DeserializeOwned + Send + 'static,
 {
-    let (tx, rx) = mpsc::channel(64);
-    tokio::spawn(async move {
+    let (tx, rx) = mpsc::channel(crate::spawn::DEFAULT_CHANNEL_CAPACITY);
+    crate::runtime::spawn(async move {
         tracing::debug!(target = "semantic_query::json_stream", "spawned stream_deserialized_from_async_read task");
         let mut parser = JsonStreamParser::new();
         let mut accum = String::new();
-        let mut buf = vec![0u8; buf_size.max(1024)];
-        loop {
-            match reader.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Ok(s) = std::str::from_utf8(&buf[..n]) {
-                        let start_len = accum.len();
-                        accum.push_str(s);
-                        for node in parser.feed(s) {
-                            // Attempt parent-first deserialization on each closed root
-                            let mut out = Vec::new();
-                            descend_deserialize::<T>(&accum, &node, &mut out);
-                            for item in out {
-                                trace!(target = "semantic_query::json_stream", "emitting parsed/unknown item");
-                                let _ = tx.send(item).await;
-                            }
-                        }
-                        let _ = start_len; // quiet unused warning if any
-                    } else {
-                        // Non-UTF8 bytes; skip for now
-                        debug!(target = "semantic_query::json_stream", "skipping non-utf8 chunk");
-                    }
+        let mut buf = ReadBuffer::new(BufferPolicy::default(), buf_size);
+        while let Some(s) = buf.read(&mut reader).await {
+            accum.push_str(&s);
+            for node in parser.feed(&s) {
+                // Attempt parent-first deserialization on each closed root
+                let mut out = Vec::new();
+                descend_deserialize::<T>(&accum, &node, &mut out);
+                for item in out {
+                    trace!(target = "semantic_query::json_stream", "emitting parsed/unknown item");
+                    let _ = tx.send(item).await;
                 }
-                Err(e) => { debug!(target = "semantic_query::json_stream", error = %e, "read error"); break },
             }
         }
         tracing::debug!(target = "semantic_query::json_stream", "stream_deserialized_from_async_read completed");
@@ -343,18 +523,10 @@ where
 {
     stream! {
         let mut parser = JsonStreamParser::new();
-        let mut buf = vec![0u8; buf_size.max(1024)];
-        loop {
-            match reader.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Ok(s) = std::str::from_utf8(&buf[..n]) {
-                        for node in parser.feed(s) {
-                            yield node;
-                        }
-                    }
-                }
-                Err(_) => break,
+        let mut buf = ReadBuffer::new(BufferPolicy::default(), buf_size);
+        while let Some(s) = buf.read(&mut reader).await {
+            for node in parser.feed(&s) {
+                yield node;
             }
         }
     }
@@ -369,23 +541,15 @@ where
     stream! {
         let mut parser = JsonStreamParser::new();
         let mut accum = String::new();
-        let mut buf = vec![0u8; buf_size.max(1024)];
-        loop {
-            match reader.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Ok(s) = std::str::from_utf8(&buf[..n]) {
-                        accum.push_str(s);
-                        for node in parser.feed(s) {
-                            let mut out = Vec::new();
-                            descend_deserialize::<T>(&accum, &node, &mut out);
-                            for item in out.into_iter() {
-                                yield item;
-                            }
-                        }
-                    }
+        let mut buf = ReadBuffer::new(BufferPolicy::default(), buf_size);
+        while let Some(s) = buf.read(&mut reader).await {
+            accum.push_str(&s);
+            for node in parser.feed(&s) {
+                let mut out = Vec::new();
+                descend_deserialize::<T>(&accum, &node, &mut out);
+                for item in out.into_iter() {
+                    yield item;
                 }
-                Err(_) => break,
             }
         }
     }
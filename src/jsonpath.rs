@@ -0,0 +1,166 @@
+//! A small JSONPath subset for picking values out of extracted JSON.
+//!
+//! Supported: the root `$`, child names (`.name`, `['name']`, `["name"]`), array
+//! indexes (`[0]`, `[-1]` from the end), wildcards (`.*`, `[*]`) and recursive descent
+//! (`..name`, `..*`, `..[0]`). Filters, slices and unions are not supported.
+//!
+//! ```
+//! use semantic_query::jsonpath::JsonPath;
+//! use serde_json::json;
+//!
+//! let path: JsonPath = "$.orders[*].total".parse().unwrap();
+//! let value = json!({ "orders": [{ "total": 3 }, { "total": 5 }] });
+//! assert_eq!(path.select(&value), vec![&json!(3), &json!(5)]);
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::error::JsonPathError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Selector {
+    Name(String),
+    Index(i64),
+    Wildcard,
+}
+
+impl Selector {
+    /// Push the children of `value` this selector matches onto `out`
+    fn apply<'a>(&self, value: &'a Value, out: &mut Vec<&'a Value>) {
+        match (self, value) {
+            (Selector::Name(name), Value::Object(map)) => out.extend(map.get(name)),
+            (Selector::Index(index), Value::Array(items)) => {
+                let index = if *index < 0 { items.len() as i64 + index } else { *index };
+                out.extend(usize::try_from(index).ok().and_then(|i| items.get(i)));
+            }
+            (Selector::Wildcard, Value::Object(map)) => out.extend(map.values()),
+            (Selector::Wildcard, Value::Array(items)) => out.extend(items),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// Apply the selector to the current values and all their descendants (`..`)
+    recursive: bool,
+    selector: Selector,
+}
+
+/// A parsed JSONPath expression; see the module docs for the supported syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    source: String,
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, JsonPathError> {
+        let source = path.trim();
+        let error = |offset: usize, message: &str| JsonPathError::Syntax {
+            path: source.to_string(),
+            offset,
+            message: message.to_string(),
+        };
+        let Some(rest) = source.strip_prefix('$') else {
+            return Err(error(0, "expected '$'"));
+        };
+
+        let mut steps = Vec::new();
+        let mut pos = source.len() - rest.len();
+        while pos < source.len() {
+            let rest = &source[pos..];
+            let (recursive, skip) = if rest.starts_with("..") {
+                (true, 2)
+            } else if rest.starts_with('.') || rest.starts_with('[') {
+                (false, usize::from(rest.starts_with('.')))
+            } else {
+                return Err(error(pos, "expected '.' or '['"));
+            };
+            pos += skip;
+            let rest = &source[pos..];
+
+            let (selector, len) = if rest.starts_with('[') {
+                parse_bracket(rest).map_err(|(offset, message)| error(pos + offset, message))?
+            } else if rest.starts_with('*') {
+                (Selector::Wildcard, 1)
+            } else {
+                let len = rest.find(['.', '[']).unwrap_or(rest.len());
+                if len == 0 {
+                    return Err(error(pos, "expected a name"));
+                }
+                (Selector::Name(rest[..len].to_string()), len)
+            };
+            steps.push(Step { recursive, selector });
+            pos += len;
+        }
+        Ok(Self { source: source.to_string(), steps })
+    }
+
+    /// Every value in `root` the path matches, in document order
+    #[must_use]
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for value in current {
+                if step.recursive {
+                    visit(value, &mut |node| step.selector.apply(node, &mut next));
+                } else {
+                    step.selector.apply(value, &mut next);
+                }
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+/// Parse a `[...]` selector at the start of `rest`, returning it and its length, or the
+/// offset and reason it is malformed
+fn parse_bracket(rest: &str) -> Result<(Selector, usize), (usize, &'static str)> {
+    let inner = &rest[1..];
+    if let Some(quote) = inner.chars().next().filter(|c| *c == '\'' || *c == '"') {
+        let name_len = inner[1..].find(quote).ok_or((1, "unterminated string"))?;
+        let end = 1 + name_len + 1;
+        if !inner[end..].starts_with(']') {
+            return Err((1 + end, "expected ']'"));
+        }
+        return Ok((Selector::Name(inner[1..1 + name_len].to_string()), 1 + end + 1));
+    }
+    let close = inner.find(']').ok_or((0, "unterminated '['"))?;
+    let content = inner[..close].trim();
+    let selector = if content == "*" {
+        Selector::Wildcard
+    } else {
+        Selector::Index(content.parse().map_err(|_| (1, "expected an index, '*' or a quoted name"))?)
+    };
+    Ok((selector, 1 + close + 1))
+}
+
+/// Call `f` on `value` and then on each of its descendants, depth first
+fn visit<'a>(value: &'a Value, f: &mut impl FnMut(&'a Value)) {
+    f(value);
+    match value {
+        Value::Object(map) => map.values().for_each(|child| visit(child, f)),
+        Value::Array(items) => items.iter().for_each(|child| visit(child, f)),
+        _ => {}
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = JsonPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
@@ -0,0 +1,268 @@
+//! Checking that answers come back in the expected language.
+//!
+//! Given a prompt in one language, models sometimes answer, or fill string fields, in
+//! another (usually English). `QueryResolver::with_expected_language` declares the
+//! language answers must be in; a `LanguageDetector` checks each reply, and a mismatch
+//! is retried as error class `retry::LANGUAGE` with a request to answer in the expected
+//! language:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::language::Language;
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Summary { text: String }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) -> Result<(), Box<dyn std::error::Error>> {
+//! let resolver = resolver.with_expected_language(Language::German);
+//! let summary = resolver.query::<Summary>("Fasse den Vertrag zusammen".into()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The string values of the extracted data are checked when there are any, otherwise
+//! the reply's text. Replies too short to tell are accepted. Only non-streaming queries
+//! are checked.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::core::{ParsedResponse, ResponseItem};
+
+/// A language `NgramDetector` recognizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+    Portuguese,
+    Italian,
+    Dutch,
+    Russian,
+    Ukrainian,
+    Greek,
+    Arabic,
+    Hebrew,
+    Hindi,
+    Chinese,
+    Japanese,
+    Korean,
+    Thai,
+}
+
+impl Language {
+    /// ISO 639-1 code
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Spanish => "es",
+            Self::French => "fr",
+            Self::German => "de",
+            Self::Portuguese => "pt",
+            Self::Italian => "it",
+            Self::Dutch => "nl",
+            Self::Russian => "ru",
+            Self::Ukrainian => "uk",
+            Self::Greek => "el",
+            Self::Arabic => "ar",
+            Self::Hebrew => "he",
+            Self::Hindi => "hi",
+            Self::Chinese => "zh",
+            Self::Japanese => "ja",
+            Self::Korean => "ko",
+            Self::Thai => "th",
+        }
+    }
+
+    /// English name, as used in correction prompts
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::Spanish => "Spanish",
+            Self::French => "French",
+            Self::German => "German",
+            Self::Portuguese => "Portuguese",
+            Self::Italian => "Italian",
+            Self::Dutch => "Dutch",
+            Self::Russian => "Russian",
+            Self::Ukrainian => "Ukrainian",
+            Self::Greek => "Greek",
+            Self::Arabic => "Arabic",
+            Self::Hebrew => "Hebrew",
+            Self::Hindi => "Hindi",
+            Self::Chinese => "Chinese",
+            Self::Japanese => "Japanese",
+            Self::Korean => "Korean",
+            Self::Thai => "Thai",
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Recognizes the language of model text
+pub trait LanguageDetector: Send + Sync + Debug {
+    /// The language of `text`, or None when it cannot tell
+    fn detect(&self, text: &str) -> Option<Language>;
+}
+
+/// Tells languages apart by script, and Latin-script languages by character trigrams
+/// compared against a small built-in profile of each
+#[derive(Debug, Clone)]
+pub struct NgramDetector {
+    /// Texts with fewer letters than this are not classified
+    min_letters: usize,
+}
+
+impl Default for NgramDetector {
+    fn default() -> Self {
+        Self { min_letters: 20 }
+    }
+}
+
+impl NgramDetector {
+    /// Classify texts of at least `letters` letters instead of 20
+    #[must_use]
+    pub fn with_min_letters(mut self, letters: usize) -> Self {
+        self.min_letters = letters;
+        self
+    }
+}
+
+/// Sample text each Latin-script profile is built from. The samples say the same thing,
+/// so what tells them apart is the language rather than the topic.
+const SAMPLES: &[(Language, &str)] = &[
+    (Language::English, "the summary of the report is that the company and its customers have been working with the new system for more than a year. it was not easy, but we think that this is the best way to do it. they would like to know what you have done with their data and when it will be ready. there are many things which we should check before the end of the month, so please tell us if you can help with the invoices from the suppliers."),
+    (Language::Spanish, "el resumen del informe es que la empresa y sus clientes han trabajado con el nuevo sistema durante más de un año. no fue fácil, pero creemos que esta es la mejor manera de hacerlo. ellos quieren saber qué has hecho con sus datos y cuándo estarán listos. hay muchas cosas que debemos revisar antes del fin de mes, por eso te pedimos que nos digas si puedes ayudar con las facturas de los proveedores."),
+    (Language::French, "le résumé du rapport est que l'entreprise et ses clients ont travaillé avec le nouveau système pendant plus d'un an. ce n'était pas facile, mais nous pensons que c'est la meilleure façon de le faire. ils veulent savoir ce que vous avez fait avec leurs données et quand elles seront prêtes. il y a beaucoup de choses que nous devons vérifier avant la fin du mois, donc dites-nous si vous pouvez nous aider avec les factures des fournisseurs."),
+    (Language::German, "die zusammenfassung des berichts ist, dass das unternehmen und seine kunden seit mehr als einem jahr mit dem neuen system arbeiten. es war nicht einfach, aber wir denken, dass dies der beste weg ist. sie möchten wissen, was sie mit ihren daten gemacht haben und wann sie fertig sein werden. es gibt viele dinge, die wir vor dem ende des monats prüfen müssen, also sagen sie uns bitte, ob sie bei den rechnungen der lieferanten helfen können."),
+    (Language::Portuguese, "o resumo do relatório é que a empresa e os seus clientes trabalham com o novo sistema há mais de um ano. não foi fácil, mas achamos que esta é a melhor maneira de fazer isso. eles querem saber o que você fez com os dados deles e quando vão estar prontos. há muitas coisas que devemos verificar antes do fim do mês, então diga-nos se você pode ajudar com as faturas dos fornecedores."),
+    (Language::Italian, "il riassunto del rapporto è che l'azienda e i suoi clienti lavorano con il nuovo sistema da più di un anno. non è stato facile, ma pensiamo che questo sia il modo migliore per farlo. vogliono sapere che cosa avete fatto con i loro dati e quando saranno pronti. ci sono molte cose che dobbiamo controllare prima della fine del mese, quindi diteci se potete aiutare con le fatture dei fornitori."),
+    (Language::Dutch, "de samenvatting van het rapport is dat het bedrijf en zijn klanten al meer dan een jaar met het nieuwe systeem werken. het was niet makkelijk, maar wij denken dat dit de beste manier is om het te doen. ze willen weten wat je met hun gegevens hebt gedaan en wanneer ze klaar zijn. er zijn veel dingen die we voor het einde van de maand moeten controleren, dus laat ons weten of je kunt helpen met de facturen van de leveranciers."),
+];
+
+/// Trigram counts of a sample, and their total
+struct Profile {
+    language: Language,
+    counts: HashMap<String, usize>,
+    total: usize,
+}
+
+fn profiles() -> &'static [Profile] {
+    static PROFILES: OnceLock<Vec<Profile>> = OnceLock::new();
+    PROFILES.get_or_init(|| SAMPLES.iter().map(|&(language, sample)| {
+        let counts = trigrams(sample);
+        let total = counts.values().sum();
+        Profile { language, counts, total }
+    }).collect())
+}
+
+/// Trigrams of the lowercased words of `text`, each padded with a space on both sides
+fn trigrams(text: &str) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for word in text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()) {
+        let padded: Vec<char> = std::iter::once(' ').chain(word.chars().flat_map(char::to_lowercase)).chain([' ']).collect();
+        for gram in padded.windows(3) {
+            *counts.entry(gram.iter().collect()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Script of a letter outside Latin, as the language it implies
+fn script(c: char) -> Option<Language> {
+    Some(match c as u32 {
+        0x0370..=0x03FF => Language::Greek,
+        0x0400..=0x04FF => Language::Russian,
+        0x0590..=0x05FF => Language::Hebrew,
+        0x0600..=0x06FF => Language::Arabic,
+        0x0900..=0x097F => Language::Hindi,
+        0x0E00..=0x0E7F => Language::Thai,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Language::Korean,
+        0x3040..=0x30FF => Language::Japanese,
+        0x4E00..=0x9FFF => Language::Chinese,
+        _ => return None,
+    })
+}
+
+impl LanguageDetector for NgramDetector {
+    fn detect(&self, text: &str) -> Option<Language> {
+        let mut letters = 0;
+        let mut scripts: HashMap<Language, usize> = HashMap::new();
+        for c in text.chars().filter(|c| c.is_alphabetic()) {
+            letters += 1;
+            if let Some(language) = script(c) {
+                *scripts.entry(language).or_insert(0) += 1;
+            }
+        }
+        if letters < self.min_letters {
+            return None;
+        }
+
+        let other: usize = scripts.values().sum();
+        if other * 2 > letters {
+            // Japanese mixes kana with Chinese characters
+            if scripts.contains_key(&Language::Japanese) {
+                return Some(Language::Japanese);
+            }
+            let (&language, _) = scripts.iter().max_by_key(|&(language, count)| (count, language.code()))?;
+            // Letters Russian does not use
+            if language == Language::Russian && text.contains(['і', 'ї', 'є', 'ґ', 'І', 'Ї', 'Є', 'Ґ']) {
+                return Some(Language::Ukrainian);
+            }
+            return Some(language);
+        }
+
+        let grams = trigrams(text);
+        profiles().iter().map(|profile| {
+            // Naive Bayes over trigrams, add-one smoothed
+            let denominator = (profile.total + 4096) as f64;
+            let score: f64 = grams.iter().map(|(gram, &count)| {
+                let seen = profile.counts.get(gram).copied().unwrap_or(0);
+                count as f64 * ((seen + 1) as f64 / denominator).ln()
+            }).sum();
+            (profile.language, score)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(language, _)| language)
+    }
+}
+
+/// Follow-up asking for the previous answer again in `expected`
+pub fn correction(expected: Language) -> String {
+    format!("Your previous answer was not in {expected}. Answer again in {expected}: write all text and every string value in {expected}, keeping the same JSON structure.")
+}
+
+/// The language of `response` when it is not `expected`
+pub(crate) fn mismatch<T: Serialize>(response: &ParsedResponse<T>, expected: Language, detector: &dyn LanguageDetector) -> Option<Language> {
+    let mut strings = String::new();
+    for item in &response.items {
+        if let ResponseItem::Data { data, .. } = item {
+            if let Ok(value) = serde_json::to_value(data) {
+                collect_strings(&value, &mut strings);
+            }
+        }
+    }
+    let text = if strings.is_empty() { response.text_content() } else { strings };
+    detector.detect(&text).filter(|&found| found != expected)
+}
+
+/// Append the string values in `value` to `out`, space-separated
+fn collect_strings(value: &Value, out: &mut String) {
+    match value {
+        Value::String(s) => {
+            if !out.is_empty() { out.push(' '); }
+            out.push_str(s);
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
@@ -1,11 +1,80 @@
+#[cfg(all(feature = "schemars-1", feature = "schemars-0_8"))]
+compile_error!("features `schemars-1` and `schemars-0_8` are mutually exclusive");
+#[cfg(not(any(feature = "schemars-1", feature = "schemars-0_8")))]
+compile_error!("enable one of the features `schemars-1` or `schemars-0_8`");
+#[cfg(all(feature = "rt-tokio", feature = "rt-async-std"))]
+compile_error!("features `rt-tokio` and `rt-async-std` are mutually exclusive; use `default-features = false` with `rt-async-std`");
+#[cfg(all(not(target_arch = "wasm32"), not(any(feature = "rt-tokio", feature = "rt-async-std"))))]
+compile_error!("enable one of the features `rt-tokio` or `rt-async-std`");
+
+// Derive `JsonSchema` through this re-export so it matches the crate's bounds
+#[cfg(feature = "schemars-1")]
+pub extern crate schemars;
+#[cfg(all(feature = "schemars-0_8", not(feature = "schemars-1")))]
+pub extern crate schemars08 as schemars;
+
+pub mod audit;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod batch;
+#[cfg(all(feature = "batch-api", not(target_arch = "wasm32")))]
+pub mod batch_api;
+pub mod client_testkit;
 pub mod clients;
 pub mod config;
+pub mod confidence;
+pub mod consensus;
+pub mod conversation;
+pub mod correlation;
+pub mod cost;
 pub mod error;
+pub mod experiments;
+pub mod grammar;
+pub mod injection;
 pub mod interceptors;
+pub mod journal;
 pub mod json_utils;
+pub mod jsonpath;
+pub mod language;
 pub mod core;
+pub mod moderation;
+pub mod normalize;
+pub mod pipeline;
+pub mod postprocess;
+pub mod prompt;
+pub mod race;
+pub mod refusal;
+pub mod request;
+pub mod retrieval;
+pub mod retry;
+pub mod review;
+pub mod runtime;
+pub mod schema;
+pub mod schema_registry;
+pub mod secrets;
+pub mod semantic;
+pub mod serde_helpers;
+pub mod shutdown;
+pub mod spawn;
+pub mod spool;
+pub mod stats;
+pub mod stream_ext;
 pub mod streaming;
+pub mod tasks;
+pub mod telemetry;
+pub mod tenancy;
+pub mod testing;
+pub mod tables;
+pub mod tools;
+pub mod units;
+#[cfg(all(feature = "web", not(target_arch = "wasm32")))]
+pub mod web;
 
 // Convenient re-exports
 pub use json_utils::extract_all;
-pub use core::{QueryResolver, ParsedResponse, ResponseItem};
+pub use serde_helpers::lenient_enum;
+pub use streaming::{FinishReason, StreamItem, TextContent, Timed};
+pub use core::{QueryResolver, ParsedResponse, ResponseItem, ExtractionPolicy, KeyConflict, SchemaPlacement, ResponseMode, ProsePolicy, StopSequences, StreamCollect, Capabilities};
+pub use conversation::Conversation;
+pub use prompt::PromptBuilder;
+pub use stream_ext::SemanticStreamExt;
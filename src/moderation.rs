@@ -0,0 +1,261 @@
+//! Content-safety hook run on prompts and raw responses before parsing.
+//!
+//! A `Moderator` returns a `ModerationVerdict` whose `action` tells the resolver what
+//! to do: pass the text through, block the query, redact spans, or just annotate.
+//! Verdicts are attached to `ParsedResponse::safety` so callers can inspect them.
+
+use crate::error::AIError;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// What is being moderated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ModerationTarget {
+    Prompt,
+    Response,
+}
+
+/// Action the resolver takes for a verdict
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum ModerationAction {
+    /// Use the text unchanged
+    #[default]
+    Allow,
+    /// Fail the query with `QueryResolverError::ModerationBlocked`
+    Block,
+    /// Replace these spans with `[REDACTED]` before parsing; an empty list redacts everything
+    Redact(Vec<String>),
+    /// Use the text unchanged but record the verdict as flagged
+    Annotate,
+}
+
+/// Result of moderating a single text
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ModerationVerdict {
+    pub flagged: bool,
+    /// Names of the categories that triggered (e.g. "harassment")
+    pub categories: Vec<String>,
+    /// Per-category scores when the moderator provides them
+    pub scores: HashMap<String, f64>,
+    pub action: ModerationAction,
+}
+
+impl ModerationVerdict {
+    /// Verdict for text that raised no concerns
+    pub fn allow() -> Self {
+        Self::default()
+    }
+
+    /// Apply this verdict's redactions to `text`
+    pub fn apply(&self, text: &str) -> String {
+        match &self.action {
+            ModerationAction::Redact(spans) if spans.is_empty() => REDACTED.to_string(),
+            ModerationAction::Redact(spans) => spans
+                .iter()
+                .fold(text.to_string(), |acc, span| acc.replace(span.as_str(), REDACTED)),
+            _ => text.to_string(),
+        }
+    }
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Safety metadata attached to a `ParsedResponse`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SafetyReport {
+    /// Verdict for the prompt, when prompt moderation is enabled
+    pub prompt: Option<ModerationVerdict>,
+    /// Verdict for the raw response
+    pub response: Option<ModerationVerdict>,
+}
+
+impl SafetyReport {
+    /// Whether any moderated text was flagged
+    pub fn flagged(&self) -> bool {
+        self.prompt.iter().chain(self.response.iter()).any(|v| v.flagged)
+    }
+
+    /// Whether the response text was altered before parsing
+    pub fn redacted(&self) -> bool {
+        self.prompt.iter().chain(self.response.iter())
+            .any(|v| matches!(v.action, ModerationAction::Redact(_)))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait Moderator: Send + Sync + Debug {
+    async fn moderate(&self, text: &str, target: ModerationTarget) -> Result<ModerationVerdict, AIError>;
+}
+
+/// Flags texts containing any of a fixed list of terms (case-insensitive).
+#[derive(Debug, Clone)]
+pub struct KeywordModerator {
+    terms: Vec<String>,
+    on_flag: ModerationAction,
+}
+
+impl KeywordModerator {
+    /// Redact matching terms by default
+    pub fn new<I, S>(terms: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { terms: terms.into_iter().map(Into::into).collect(), on_flag: ModerationAction::Redact(Vec::new()) }
+    }
+
+    /// Block instead of redacting when a term matches
+    pub fn blocking(mut self) -> Self {
+        self.on_flag = ModerationAction::Block;
+        self
+    }
+
+    /// Only annotate when a term matches
+    pub fn annotating(mut self) -> Self {
+        self.on_flag = ModerationAction::Annotate;
+        self
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl Moderator for KeywordModerator {
+    async fn moderate(&self, text: &str, _target: ModerationTarget) -> Result<ModerationVerdict, AIError> {
+        let lower = text.to_lowercase();
+        let mut spans = Vec::new();
+        let mut categories = Vec::new();
+        for term in &self.terms {
+            let needle = term.to_lowercase();
+            if needle.is_empty() { continue; }
+            let mut from = 0;
+            while let Some(pos) = lower[from..].find(&needle) {
+                let start = from + pos;
+                // Lowercasing can change byte lengths for some scripts; only redact aligned matches
+                if let Some(original) = text.get(start..start + needle.len()) {
+                    if !spans.iter().any(|s: &String| s == original) {
+                        spans.push(original.to_string());
+                    }
+                }
+                from = start + needle.len();
+            }
+            if lower.contains(&needle) {
+                categories.push(term.clone());
+            }
+        }
+
+        if categories.is_empty() {
+            return Ok(ModerationVerdict::allow());
+        }
+        let action = match &self.on_flag {
+            ModerationAction::Redact(_) => ModerationAction::Redact(spans),
+            other => other.clone(),
+        };
+        Ok(ModerationVerdict { flagged: true, categories, scores: HashMap::new(), action })
+    }
+}
+
+#[cfg(feature = "openai-moderation")]
+pub use openai::OpenAIModerator;
+
+#[cfg(feature = "openai-moderation")]
+mod openai {
+    use super::*;
+    use crate::config::KeyFromEnv;
+    use crate::correlation::Correlated;
+    use crate::error::{OpenAIError, ProviderError};
+    use reqwest::Client;
+    use serde::Deserialize;
+    use tracing::{debug, error};
+
+    #[derive(Debug, Deserialize)]
+    struct ModerationResponse {
+        results: Vec<ModerationResult>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ModerationResult {
+        flagged: bool,
+        categories: HashMap<String, bool>,
+        category_scores: HashMap<String, f64>,
+    }
+
+    /// Moderator backed by OpenAI's `/v1/moderations` endpoint.
+    #[derive(Debug, Clone)]
+    pub struct OpenAIModerator {
+        api_key: String,
+        model: String,
+        on_flag: ModerationAction,
+        client: Client,
+    }
+
+    impl KeyFromEnv for OpenAIModerator {
+        const KEY_NAME: &'static str = "OPENAI_API_KEY";
+    }
+
+    impl OpenAIModerator {
+        /// Blocks flagged content by default; the endpoint reports no spans to redact
+        pub fn new(api_key: impl Into<String>) -> Self {
+            Self {
+                api_key: api_key.into(),
+                model: "omni-moderation-latest".to_string(),
+                on_flag: ModerationAction::Block,
+                client: Client::new(),
+            }
+        }
+
+        /// Build from `OPENAI_API_KEY`
+        pub fn try_default() -> Result<Self, AIError> {
+            Ok(Self::new(Self::require_key()?))
+        }
+
+        pub fn with_model(mut self, model: impl Into<String>) -> Self {
+            self.model = model.into();
+            self
+        }
+
+        /// Action for flagged content (`Redact` with no spans replaces the whole text)
+        pub fn on_flag(mut self, action: ModerationAction) -> Self {
+            self.on_flag = action;
+            self
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    impl Moderator for OpenAIModerator {
+        async fn moderate(&self, text: &str, target: ModerationTarget) -> Result<ModerationVerdict, AIError> {
+            debug!(?target, text_len = text.len(), "Sending moderation request");
+            let response = self.client
+                .post("https://api.openai.com/v1/moderations")
+                .correlated()
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(&serde_json::json!({ "model": self.model, "input": text }))
+                .send()
+                .await
+                .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+
+            if !response.status().is_success() {
+                let err = ProviderError::from_response(response).await;
+                error!(status = err.status, request_id = ?err.request_id, error = %err.message, "Moderation API error");
+                return Err(AIError::OpenAI(err.into()));
+            }
+
+            let parsed: ModerationResponse = response.json().await
+                .map_err(|e| AIError::OpenAI(OpenAIError::Http(e.to_string())))?;
+            let Some(result) = parsed.results.into_iter().next() else {
+                return Err(AIError::OpenAI(OpenAIError::Api("No moderation results".to_string())));
+            };
+
+            let mut categories: Vec<String> = result.categories.into_iter()
+                .filter_map(|(name, hit)| hit.then_some(name))
+                .collect();
+            categories.sort();
+            let action = if result.flagged { self.on_flag.clone() } else { ModerationAction::Allow };
+            Ok(ModerationVerdict { flagged: result.flagged, categories, scores: result.category_scores, action })
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Cleaning model text before it is scanned for JSON.
+//!
+//! Models wrapped by CLIs or terminals can emit ANSI color codes, a byte-order mark, or
+//! typographic quotes, any of which breaks JSON parsing. `Normalizers` registered with
+//! `QueryResolver::with_normalizers` rewrite every reply, and every streamed chunk,
+//! before extraction:
+//!
+//! ```
+//! use semantic_query::normalize::{Normalizers, StripAnsi};
+//!
+//! let normalizers = Normalizers::new().with(StripAnsi);
+//! assert_eq!(normalizers.apply("\x1b[32m{\"ok\": true}\x1b[0m"), "{\"ok\": true}");
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Rewrites model text before JSON scanning
+pub trait TextNormalizer: Send + Sync + Debug {
+    /// `text` rewritten, or borrowed unchanged. Streams apply this to each chunk on its
+    /// own, so a sequence split across chunks is seen as two halves.
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str>;
+}
+
+/// Removes ANSI escape sequences (colors, cursor movement)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripAnsi;
+
+impl TextNormalizer for StripAnsi {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.contains('\x1b') {
+            return Cow::Borrowed(text);
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\x1b' {
+                out.push(c);
+                continue;
+            }
+            match chars.peek() {
+                // CSI: parameters and intermediates up to a final byte in @..~
+                Some('[') => {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ESC \
+                Some(']') => {
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Two-character sequences such as ESC c
+                Some(_) => {
+                    chars.next();
+                }
+                None => {}
+            }
+        }
+        Cow::Owned(out)
+    }
+}
+
+/// Removes byte-order marks (U+FEFF), which also appear mid-text when replies are joined
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripBom;
+
+impl TextNormalizer for StripBom {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if text.contains('\u{feff}') {
+            Cow::Owned(text.replace('\u{feff}', ""))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
+/// Turns typographic double quotes (`“ ” „ ″`) into `"` when the text has no straight
+/// double quote at all, i.e. when every quote was curled. Text that also has straight
+/// quotes is left alone, since curly ones inside JSON strings are content.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StraightenQuotes;
+
+const CURLY_QUOTES: [char; 4] = ['\u{201c}', '\u{201d}', '\u{201e}', '\u{2033}'];
+
+impl TextNormalizer for StraightenQuotes {
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if text.contains('"') || !text.contains(CURLY_QUOTES) {
+            return Cow::Borrowed(text);
+        }
+        Cow::Owned(text.replace(CURLY_QUOTES, "\""))
+    }
+}
+
+/// Normalizers applied in order. Clones share the normalizers.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizers(Vec<Arc<dyn TextNormalizer>>);
+
+impl Normalizers {
+    /// No normalizers (the resolver default)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `StripBom`, `StripAnsi` and `StraightenQuotes`
+    pub fn standard() -> Self {
+        Self::new().with(StripBom).with(StripAnsi).with(StraightenQuotes)
+    }
+
+    /// Run `normalizer` after the ones already added
+    #[must_use]
+    pub fn with(mut self, normalizer: impl TextNormalizer + 'static) -> Self {
+        self.0.push(Arc::new(normalizer));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `text` through every normalizer in turn
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.0.iter().fold(Cow::Borrowed(text), |text, normalizer| match text {
+            Cow::Borrowed(text) => normalizer.normalize(text),
+            Cow::Owned(text) => Cow::Owned(normalizer.normalize(&text).into_owned()),
+        })
+    }
+
+    /// `apply` for owned text, reusing it when nothing changes
+    pub(crate) fn apply_owned(&self, text: String) -> String {
+        let normalized = match self.apply(&text) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(normalized) => Some(normalized),
+        };
+        normalized.unwrap_or(text)
+    }
+}
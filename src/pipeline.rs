@@ -0,0 +1,282 @@
+//! Typed multi-step queries where each step's output builds the next prompt.
+//!
+//! ```ignore
+//! let summary = Pipeline::start::<Outline>("Outline this paper: ...")
+//!     .named("outline")
+//!     .then::<Section, _>(|outline| format!("Expand the first section of {:?}", outline))
+//!     .with_retries(3)
+//!     .then::<Summary, _>(|section| format!("Summarize: {}", section.body))
+//!     .run(&resolver)
+//!     .await?;
+//! ```
+//!
+//! Steps share a `PipelineContext` (a prompt preamble, free-form variables, and the
+//! outputs of earlier steps). `Pipeline::stream` yields each step's output as it completes.
+//! `Pipeline::with_retry_budget` bounds the retries of all steps, and of the queries
+//! inside them, together.
+
+use crate::core::{LowLevelClient, QueryResolver};
+use crate::error::{PipelineError, QueryResolverError};
+use crate::retry::{self, RetryBudget};
+use futures_core::Stream;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+#[cfg(not(target_arch = "wasm32"))]
+type StepFuture<'a, O> = Pin<Box<dyn Future<Output = Result<O, PipelineError>> + Send + 'a>>;
+
+#[cfg(target_arch = "wasm32")]
+type StepFuture<'a, O> = Pin<Box<dyn Future<Output = Result<O, PipelineError>> + 'a>>;
+
+type StepFn<C, O> = Box<dyn for<'a> FnOnce(&'a QueryResolver<C>, &'a mut PipelineContext) -> StepFuture<'a, O> + Send>;
+
+/// Coerce a closure to `StepFn` so its signature is inferred from the higher-ranked bound
+fn step_fn<C, O, F>(f: F) -> StepFn<C, O>
+where
+    C: LowLevelClient + 'static,
+    F: for<'a> FnOnce(&'a QueryResolver<C>, &'a mut PipelineContext) -> StepFuture<'a, O> + Send + 'static,
+{
+    Box::new(f)
+}
+
+/// Output of a completed step, recorded in the context and emitted by `Pipeline::stream`
+#[derive(Debug, Clone)]
+pub struct StepOutput {
+    pub index: usize,
+    pub name: String,
+    pub prompt: String,
+    /// The step's typed output, serialized
+    pub output: Value,
+    /// Number of attempts the step took (1 when it succeeded first time)
+    pub attempts: usize,
+}
+
+/// State shared by every step of a pipeline run
+#[derive(Debug, Default)]
+pub struct PipelineContext {
+    preamble: Option<String>,
+    vars: HashMap<String, Value>,
+    outputs: Vec<StepOutput>,
+    names: Vec<String>,
+    retries: Vec<Option<usize>>,
+    budget: Option<RetryBudget>,
+    events: Option<mpsc::UnboundedSender<StepOutput>>,
+}
+
+impl PipelineContext {
+    /// A shared variable set with `Pipeline::with_var` or `set`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.vars.get(key)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Serialize) {
+        self.vars.insert(key.into(), serde_json::to_value(value).unwrap_or(Value::Null));
+    }
+
+    /// Outputs of the steps completed so far, in order
+    pub fn outputs(&self) -> &[StepOutput] {
+        &self.outputs
+    }
+
+    /// Output of the step with the given name
+    pub fn output(&self, name: &str) -> Option<&StepOutput> {
+        self.outputs.iter().find(|o| o.name == name)
+    }
+}
+
+/// Event yielded by `Pipeline::stream`
+#[derive(Debug, Clone)]
+pub enum PipelineEvent<O> {
+    /// An intermediate (or the final) step completed
+    Step(StepOutput),
+    /// The pipeline finished with this output
+    Done(O),
+}
+
+/// Typed chain of queries; `O` is the output type of the last step.
+pub struct Pipeline<C: LowLevelClient, O> {
+    run: StepFn<C, O>,
+    context: PipelineContext,
+}
+
+impl<C: LowLevelClient + 'static> Pipeline<C, ()> {
+    /// First step: query `prompt` for an `A`
+    pub fn start<A>(prompt: impl Into<String>) -> Pipeline<C, A>
+    where
+        A: DeserializeOwned + JsonSchema + Serialize + Send + Debug + Clone + 'static,
+    {
+        let prompt = prompt.into();
+        let mut context = PipelineContext::default();
+        context.names.push("step-0".to_string());
+        context.retries.push(None);
+        Pipeline {
+            run: step_fn(move |resolver, ctx| Box::pin(run_step::<A, C>(resolver, ctx, 0, prompt))),
+            context,
+        }
+    }
+}
+
+impl<C: LowLevelClient + 'static, O> Pipeline<C, O>
+where
+    O: Send + 'static,
+{
+    /// Next step: build a prompt from the previous output and query it for a `B`
+    pub fn then<B, F>(self, prompt_fn: F) -> Pipeline<C, B>
+    where
+        B: DeserializeOwned + JsonSchema + Serialize + Send + Debug + Clone + 'static,
+        F: FnOnce(&O) -> String + Send + 'static,
+    {
+        self.then_with_context(move |previous, _| prompt_fn(previous))
+    }
+
+    /// Like `then`, with read access to the shared context (variables and earlier outputs)
+    pub fn then_with_context<B, F>(mut self, prompt_fn: F) -> Pipeline<C, B>
+    where
+        B: DeserializeOwned + JsonSchema + Serialize + Send + Debug + Clone + 'static,
+        F: FnOnce(&O, &PipelineContext) -> String + Send + 'static,
+    {
+        let index = self.context.names.len();
+        self.context.names.push(format!("step-{index}"));
+        self.context.retries.push(None);
+        let previous = self.run;
+        Pipeline {
+            run: step_fn(move |resolver, ctx| Box::pin(async move {
+                let output = previous(resolver, ctx).await?;
+                let prompt = prompt_fn(&output, ctx);
+                run_step::<B, C>(resolver, ctx, index, prompt).await
+            })),
+            context: self.context,
+        }
+    }
+
+    /// Name the most recent step (used in errors, events, and `PipelineContext::output`)
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        if let Some(last) = self.context.names.last_mut() {
+            *last = name.into();
+        }
+        self
+    }
+
+    /// Retry the most recent step up to `retries` extra times when it fails or yields no
+    /// data. Steps without an override use the resolver's `default_max_retries`.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        if let Some(last) = self.context.retries.last_mut() {
+            *last = Some(retries);
+        }
+        self
+    }
+
+    /// Spend every retry of the run from `budget`: step retries and the extraction
+    /// retries of each step's query
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.context.budget = Some(budget);
+        self
+    }
+
+    /// Text prepended to every step's prompt
+    pub fn with_shared_context(mut self, preamble: impl Into<String>) -> Self {
+        self.context.preamble = Some(preamble.into());
+        self
+    }
+
+    /// Seed a shared variable readable from `then_with_context`
+    pub fn with_var(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        self.context.set(key, value);
+        self
+    }
+
+    /// Run every step and return the final output
+    pub async fn run(self, resolver: &QueryResolver<C>) -> Result<O, PipelineError> {
+        self.run_with_context(resolver).await.map(|(output, _)| output)
+    }
+
+    /// Run every step, also returning the context with all intermediate outputs
+    pub async fn run_with_context(self, resolver: &QueryResolver<C>) -> Result<(O, PipelineContext), PipelineError> {
+        let mut context = self.context;
+        let budget = context.budget.clone();
+        let output = within_budget(budget, (self.run)(resolver, &mut context)).await?;
+        Ok((output, context))
+    }
+
+    /// Run the pipeline, yielding each step's output as it completes and then the final value
+    pub fn stream(self, resolver: &QueryResolver<C>) -> impl Stream<Item = Result<PipelineEvent<O>, PipelineError>> + '_ {
+        async_stream::stream! {
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let mut context = self.context;
+            context.events = Some(tx);
+            let budget = context.budget.clone();
+            let mut run = std::pin::pin!(within_budget(budget, (self.run)(resolver, &mut context)));
+
+            let result = loop {
+                let next = tokio::select! {
+                    Some(event) = rx.recv() => Ok(event),
+                    result = &mut run => Err(result),
+                };
+                match next {
+                    Ok(event) => yield Ok(PipelineEvent::Step(event)),
+                    Err(result) => break result,
+                }
+            };
+            while let Ok(event) = rx.try_recv() {
+                yield Ok(PipelineEvent::Step(event));
+            }
+            yield result.map(PipelineEvent::Done);
+        }
+    }
+}
+
+async fn within_budget<F: Future>(budget: Option<RetryBudget>, future: F) -> F::Output {
+    match budget {
+        Some(budget) => retry::budget_scope(budget, future).await,
+        None => future.await,
+    }
+}
+
+async fn run_step<T, C>(resolver: &QueryResolver<C>, ctx: &mut PipelineContext, index: usize, prompt: String) -> Result<T, PipelineError>
+where
+    T: DeserializeOwned + JsonSchema + Serialize + Send + Debug + Clone + 'static,
+    C: LowLevelClient,
+{
+    let name = ctx.names[index].clone();
+    let retries = ctx.retries[index].unwrap_or(resolver.config().default_max_retries);
+    let prompt = match &ctx.preamble {
+        Some(preamble) => format!("{preamble}\n\n{prompt}"),
+        None => prompt,
+    };
+
+    let mut attempts = 0;
+    let output = loop {
+        attempts += 1;
+        let result = resolver.query::<T>(prompt.clone()).await
+            .and_then(|response| response.first_required().map_err(QueryResolverError::from));
+        match result {
+            Ok(output) => break output,
+            Err(e) if attempts <= retries && retry::spend(None) => {
+                warn!(step = %name, attempt = attempts, error = %e, "Pipeline step failed, retrying");
+            }
+            Err(source) => return Err(PipelineError::Step { index, name, source }),
+        }
+    };
+
+    info!(step = %name, attempts, "Pipeline step completed");
+    let record = StepOutput {
+        index,
+        name,
+        prompt,
+        output: serde_json::to_value(&output).unwrap_or(Value::Null),
+        attempts,
+    };
+    if let Some(events) = &ctx.events {
+        let _ = events.send(record.clone());
+    }
+    ctx.outputs.push(record);
+    Ok(output)
+}
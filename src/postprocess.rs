@@ -0,0 +1,184 @@
+//! Per-type post-processing of extracted data.
+//!
+//! A `PostProcessor<T>` registered with `QueryResolver::with_post_processor` sees every
+//! `T` extracted by `query`/`query_mixed` (and conversations) before it is returned. It
+//! can repair the raw JSON before deserialization (`normalize_json`, e.g. `"HIGH"` →
+//! `"High"` for an enum) and adjust or reject the typed value (`process`, e.g. trim,
+//! clamp, parse dates). Rejections are sent back to the model as a correction request,
+//! up to `RetryConfig::max_retries["post_process"]` times.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+/// Normalizes or validates extracted items of type `T`
+pub trait PostProcessor<T>: Send + Sync {
+    /// Repair a candidate's JSON before it is deserialized as `T`. Runs on every JSON
+    /// structure in the response, so leave values you don't recognise untouched.
+    fn normalize_json(&self, _value: &mut Value) {}
+
+    /// Adjust a deserialized item, or reject it with a message for the model
+    fn process(&self, value: T) -> Result<T, String> {
+        Ok(value)
+    }
+}
+
+/// Closures act as typed processors: `|t: Task| Ok(Task { title: t.title.trim().into(), ..t })`
+impl<T, F> PostProcessor<T> for F
+where
+    F: Fn(T) -> Result<T, String> + Send + Sync,
+{
+    fn process(&self, value: T) -> Result<T, String> {
+        self(value)
+    }
+}
+
+/// Trims leading/trailing whitespace from every string in the JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrimStrings;
+
+impl<T> PostProcessor<T> for TrimStrings {
+    fn normalize_json(&self, value: &mut Value) {
+        match value {
+            Value::String(s) => {
+                let trimmed = s.trim();
+                if trimmed.len() != s.len() {
+                    *s = trimmed.to_string();
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| PostProcessor::<T>::normalize_json(self, v)),
+            Value::Object(map) => map.values_mut().for_each(|v| PostProcessor::<T>::normalize_json(self, v)),
+            _ => {}
+        }
+    }
+}
+
+/// Clamps the number at a JSON pointer (e.g. `/confidence`) into `[min, max]`
+#[derive(Debug, Clone)]
+pub struct Clamp {
+    pointer: String,
+    min: f64,
+    max: f64,
+}
+
+impl Clamp {
+    pub fn new(pointer: impl Into<String>, min: f64, max: f64) -> Self {
+        Self { pointer: pointer.into(), min, max }
+    }
+}
+
+impl<T> PostProcessor<T> for Clamp {
+    fn normalize_json(&self, value: &mut Value) {
+        let Some(target) = value.pointer_mut(&self.pointer) else { return };
+        let Some(n) = target.as_f64() else { return };
+        if n < self.min || n > self.max {
+            let clamped = n.clamp(self.min, self.max);
+            // Keep integers integral so integer fields still deserialize
+            *target = if target.is_f64() || clamped.fract() != 0.0 {
+                serde_json::json!(clamped)
+            } else {
+                serde_json::json!(clamped as i64)
+            };
+        }
+    }
+}
+
+/// Rewrites the string at a JSON pointer to the canonical spelling it matches
+/// case-insensitively, ignoring `_`, `-` and spaces (`"HIGH"`, `"high"` → `"High"`)
+#[derive(Debug, Clone)]
+pub struct CanonicalEnum {
+    pointer: String,
+    variants: Vec<String>,
+}
+
+impl CanonicalEnum {
+    pub fn new<I, S>(pointer: impl Into<String>, variants: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { pointer: pointer.into(), variants: variants.into_iter().map(Into::into).collect() }
+    }
+
+    fn key(s: &str) -> String {
+        s.chars().filter(|c| !matches!(c, '_' | '-' | ' ')).flat_map(char::to_lowercase).collect()
+    }
+}
+
+impl<T> PostProcessor<T> for CanonicalEnum {
+    fn normalize_json(&self, value: &mut Value) {
+        let Some(Value::String(s)) = value.pointer_mut(&self.pointer) else { return };
+        let key = Self::key(s);
+        if let Some(canonical) = self.variants.iter().find(|v| Self::key(v) == key) {
+            *s = canonical.clone();
+        }
+    }
+}
+
+/// Rewrites quantity strings at JSON pointers (`"2,5 Stunden"`, `"90 min"`) as numbers in
+/// a declared unit, for target types whose fields cannot use the `units` serde modules.
+/// Bare numbers are left as they are; strings in other kinds of unit are left for
+/// deserialization to reject.
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeUnits {
+    fields: Vec<(String, String)>,
+}
+
+impl NormalizeUnits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Convert the quantity at `pointer` (e.g. `/effort`) to `unit` (e.g. `"h"`)
+    #[must_use]
+    pub fn with_field(mut self, pointer: impl Into<String>, unit: impl Into<String>) -> Self {
+        self.fields.push((pointer.into(), unit.into()));
+        self
+    }
+}
+
+impl<T> PostProcessor<T> for NormalizeUnits {
+    fn normalize_json(&self, value: &mut Value) {
+        for (pointer, unit) in &self.fields {
+            let Some(target) = value.pointer_mut(pointer) else { continue };
+            if !target.is_string() {
+                continue;
+            }
+            if let Some(n) = crate::units::to_unit(target, unit) {
+                *target = serde_json::json!(n);
+            }
+        }
+    }
+}
+
+/// Post-processors registered per target type
+#[derive(Clone, Default)]
+pub(crate) struct PostProcessors {
+    by_type: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+type Chain<T> = Vec<Arc<dyn PostProcessor<T>>>;
+
+impl PostProcessors {
+    pub(crate) fn register<T: 'static>(&mut self, processor: Arc<dyn PostProcessor<T>>) {
+        let mut chain: Chain<T> = self.for_type::<T>().to_vec();
+        chain.push(processor);
+        self.by_type.insert(TypeId::of::<T>(), Arc::new(chain));
+    }
+
+    pub(crate) fn for_type<T: 'static>(&self) -> &[Arc<dyn PostProcessor<T>>] {
+        self.by_type
+            .get(&TypeId::of::<T>())
+            .and_then(|chain| chain.downcast_ref::<Chain<T>>())
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+impl fmt::Debug for PostProcessors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostProcessors").field("types", &self.by_type.len()).finish()
+    }
+}
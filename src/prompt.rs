@@ -0,0 +1,321 @@
+//! Multi-part prompts with a token budget.
+//!
+//! `PromptBuilder` assembles named parts (system text, instructions, context documents,
+//! the response schema and the question), each with a priority. When the rendered
+//! prompt would exceed `max_tokens`, the lowest-priority parts are truncated or dropped
+//! until it fits, and `RenderedPrompt::cuts` reports what was removed:
+//!
+//! ```
+//! # use semantic_query::prompt::PromptBuilder;
+//! let rendered = PromptBuilder::new()
+//!     .system("You extract invoices.")
+//!     .context("invoice.txt", "ACME Corp, total $120.00, due 2024-05-01")
+//!     .question("What is the total?")
+//!     .max_tokens(2000)
+//!     .render();
+//! assert!(rendered.cuts.is_empty());
+//! ```
+//!
+//! Token counts are estimated at ~4 characters per token, so leave some headroom below
+//! the model's real context window.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{schema_instructions, ChatMessage};
+use crate::injection::{FlaggedSegment, InjectionGuard};
+
+/// Rough token estimate (~4 characters per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Parts shorter than this after truncation are dropped instead
+const MIN_TRUNCATED_TOKENS: usize = 16;
+
+const TRUNCATION_MARKER: &str = "\n[...truncated]";
+
+/// Role of a prompt part, which decides where and how it is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartKind {
+    /// Rendered as the system message
+    System,
+    Instructions,
+    /// A context document, rendered under a `### <name>` heading
+    Context,
+    /// Answer format and JSON schema
+    Schema,
+    Question,
+}
+
+impl PartKind {
+    /// Priority of parts added through the builder's shorthand methods
+    pub fn default_priority(self) -> u32 {
+        match self {
+            Self::System | Self::Question => PromptPart::PINNED,
+            Self::Schema => 90,
+            Self::Instructions => 80,
+            Self::Context => 50,
+        }
+    }
+}
+
+/// One named section of a prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptPart {
+    pub kind: PartKind,
+    pub name: String,
+    pub content: String,
+    /// Higher priorities are kept longer; `PINNED` parts are never cut
+    pub priority: u32,
+    /// Whether the part may be shortened rather than dropped whole
+    pub truncatable: bool,
+}
+
+impl PromptPart {
+    /// Priority of parts that are never truncated or dropped
+    pub const PINNED: u32 = u32::MAX;
+
+    /// A part with `kind`'s default priority; only context documents are truncatable
+    pub fn new(kind: PartKind, name: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            content: content.into(),
+            priority: kind.default_priority(),
+            truncatable: kind == PartKind::Context,
+        }
+    }
+
+    #[must_use]
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    #[must_use]
+    pub fn truncatable(mut self, truncatable: bool) -> Self {
+        self.truncatable = truncatable;
+        self
+    }
+
+    /// The part as it appears in the prompt
+    fn rendered(&self) -> String {
+        match self.kind {
+            PartKind::Context => format!("### {}\n{}", self.name, self.content),
+            _ => self.content.clone(),
+        }
+    }
+
+    fn tokens(&self) -> usize {
+        estimate_tokens(&self.rendered())
+    }
+}
+
+/// How a part was cut to fit the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CutAction {
+    Truncated { original_tokens: usize, kept_tokens: usize },
+    Dropped { tokens: usize },
+}
+
+/// A part that was shortened or removed by `PromptBuilder::render`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptCut {
+    pub name: String,
+    pub kind: PartKind,
+    pub action: CutAction,
+}
+
+/// Output of `PromptBuilder::render`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedPrompt {
+    /// Content of the `System` parts, if any survived
+    pub system: Option<String>,
+    /// Every other part, in the order added
+    pub prompt: String,
+    /// Estimated tokens of `system` and `prompt` together
+    pub tokens: usize,
+    /// The budget the prompt was rendered for, if any
+    pub max_tokens: Option<usize>,
+    /// Parts that were truncated or dropped, in the order they were cut
+    pub cuts: Vec<PromptCut>,
+}
+
+impl RenderedPrompt {
+    /// Whether the prompt is within budget. False only when pinned parts alone exceed it.
+    pub fn fits(&self) -> bool {
+        !matches!(self.max_tokens, Some(max) if self.tokens > max)
+    }
+
+    /// System message (if any) followed by the prompt as a user message
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        let mut messages = Vec::new();
+        if let Some(system) = &self.system {
+            messages.push(ChatMessage::system(system.clone()));
+        }
+        messages.push(ChatMessage::user(self.prompt.clone()));
+        messages
+    }
+
+    /// Single prompt string with the system text first, for `QueryResolver::query` and
+    /// other single-prompt APIs
+    pub fn into_text(self) -> String {
+        match self.system {
+            Some(system) => format!("{}\n\n{}", system, self.prompt),
+            None => self.prompt,
+        }
+    }
+}
+
+/// Builder for prompts assembled from prioritized parts
+#[derive(Debug, Clone, Default)]
+pub struct PromptBuilder {
+    parts: Vec<PromptPart>,
+    max_tokens: Option<usize>,
+}
+
+impl PromptBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token budget for the whole rendered prompt, system text included
+    #[must_use]
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    #[must_use]
+    pub fn part(mut self, part: PromptPart) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    #[must_use]
+    pub fn system(self, content: impl Into<String>) -> Self {
+        self.part(PromptPart::new(PartKind::System, "system", content))
+    }
+
+    #[must_use]
+    pub fn instructions(self, content: impl Into<String>) -> Self {
+        self.part(PromptPart::new(PartKind::Instructions, "instructions", content))
+    }
+
+    /// A context document titled `name`
+    #[must_use]
+    pub fn context(self, name: impl Into<String>, content: impl Into<String>) -> Self {
+        self.part(PromptPart::new(PartKind::Context, name, content))
+    }
+
+    /// A context document with an explicit priority
+    #[must_use]
+    pub fn context_with_priority(self, name: impl Into<String>, content: impl Into<String>, priority: u32) -> Self {
+        self.part(PromptPart::new(PartKind::Context, name, content).priority(priority))
+    }
+
+    /// The answer-format instructions and JSON schema for `T`. `QueryResolver::query`
+    /// appends these itself; add this part when sending the prompt through `ask_messages`
+    /// or another API that doesn't.
+    #[must_use]
+    pub fn schema<T: JsonSchema + 'static>(self) -> Self {
+        self.part(PromptPart::new(PartKind::Schema, "schema", schema_instructions::<T>().to_string()))
+    }
+
+    #[must_use]
+    pub fn question(self, content: impl Into<String>) -> Self {
+        self.part(PromptPart::new(PartKind::Question, "question", content))
+    }
+
+    /// Run `guard` over the context documents added so far: flagged ones are stripped,
+    /// annotated or removed according to its policy and reported by name
+    #[must_use]
+    pub fn screen_context(mut self, guard: &InjectionGuard) -> (Self, Vec<FlaggedSegment>) {
+        let mut flagged = Vec::new();
+        self.parts.retain_mut(|part| {
+            if part.kind != PartKind::Context {
+                return true;
+            }
+            let (text, report) = guard.screen_named(&part.name, &part.content);
+            flagged.extend(report);
+            match text {
+                Some(text) => {
+                    part.content = text;
+                    true
+                }
+                None => false,
+            }
+        });
+        (self, flagged)
+    }
+
+    /// Render the prompt, cutting parts until it fits `max_tokens`.
+    ///
+    /// The lowest-priority part is cut first; among equal priorities, the one added last.
+    /// Truncatable parts lose just enough text from the end to fit (and are dropped if
+    /// little would remain); other parts are dropped whole.
+    pub fn render(&self) -> RenderedPrompt {
+        let mut parts: Vec<Option<PromptPart>> = self.parts.iter().cloned().map(Some).collect();
+        let mut cuts = Vec::new();
+        let original: Vec<usize> = self.parts.iter().map(PromptPart::tokens).collect();
+
+        if let Some(max) = self.max_tokens {
+            let mut order: Vec<usize> = (0..parts.len())
+                .filter(|&i| self.parts[i].priority != PromptPart::PINNED)
+                .collect();
+            order.sort_by_key(|&i| (self.parts[i].priority, std::cmp::Reverse(i)));
+
+            for i in order {
+                let total = render_parts(&parts).2;
+                if total <= max {
+                    break;
+                }
+                let part = parts[i].as_mut().expect("parts are cut once");
+                let excess = total - max;
+                let kept = if part.truncatable { truncate(part, excess) } else { None };
+                let action = match kept {
+                    Some(kept_tokens) => CutAction::Truncated { original_tokens: original[i], kept_tokens },
+                    None => {
+                        parts[i] = None;
+                        CutAction::Dropped { tokens: original[i] }
+                    }
+                };
+                cuts.push(PromptCut { name: self.parts[i].name.clone(), kind: self.parts[i].kind, action });
+            }
+        }
+
+        let (system, prompt, tokens) = render_parts(&parts);
+        RenderedPrompt { system, prompt, tokens, max_tokens: self.max_tokens, cuts }
+    }
+}
+
+/// Shorten `part` by at least `excess` tokens, returning its new token count, or None if
+/// too little would be left to be worth keeping
+fn truncate(part: &mut PromptPart, excess: usize) -> Option<usize> {
+    // Headings, the marker and estimate rounding (one token of slack)
+    let overhead = part.tokens() - estimate_tokens(&part.content) + estimate_tokens(TRUNCATION_MARKER) + 1;
+    let target = part.tokens().checked_sub(excess + overhead)?;
+    if target < MIN_TRUNCATED_TOKENS {
+        return None;
+    }
+    let mut end = (target * 4).min(part.content.len());
+    while !part.content.is_char_boundary(end) {
+        end -= 1;
+    }
+    part.content.truncate(end);
+    part.content.push_str(TRUNCATION_MARKER);
+    Some(part.tokens())
+}
+
+/// System text, prompt text and their combined estimated tokens
+fn render_parts(parts: &[Option<PromptPart>]) -> (Option<String>, String, usize) {
+    let present = || parts.iter().flatten();
+    let system: Vec<String> = present().filter(|p| p.kind == PartKind::System).map(PromptPart::rendered).collect();
+    let body: Vec<String> = present().filter(|p| p.kind != PartKind::System).map(PromptPart::rendered).collect();
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    let prompt = body.join("\n\n");
+    let tokens = system.as_deref().map_or(0, estimate_tokens) + estimate_tokens(&prompt);
+    (system, prompt, tokens)
+}
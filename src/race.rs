@@ -0,0 +1,94 @@
+//! Racing one query across several clients.
+//!
+//! `QueryResolver::query_race` sends the same prompt to every client at once and keeps
+//! the first response that yields a `T`, for latency-critical paths with flaky
+//! providers. The other requests are dropped as soon as a winner is found.
+
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use tracing::{info, instrument, warn};
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver};
+use crate::correlation;
+use crate::error::{DataExtractionError, QueryResolverError};
+use crate::schema::SchemaCache;
+use crate::stats::QueryProbe;
+
+/// Outcome of `QueryResolver::query_race`
+#[derive(Debug)]
+pub struct RaceWin<T> {
+    /// Index in `clients` of the client that answered first with data
+    pub winner: usize,
+    pub response: ParsedResponse<T>,
+    /// Clients that finished before the winner without data, with their error
+    pub failed: Vec<(usize, QueryResolverError)>,
+    /// Clients still running when the winner answered; their requests were dropped
+    pub cancelled: Vec<usize>,
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Run `query<T>()` on every client in `clients` concurrently and return the first
+    /// response holding a data item, dropping the requests still in flight.
+    ///
+    /// All entrants share the current correlation id and this resolver's configuration.
+    /// Finished entrants report stats and audit records as usual; cancelled ones report a
+    /// failed `QueryStats` and a `RecordOutcome::Cancelled` record, and tenant clients
+    /// keep the request and prompt tokens they admitted. The query fails only if no
+    /// client yields data, with the last error.
+    #[instrument(target = "semantic_query::resolver", skip(self, prompt, clients), fields(prompt_len = prompt.len(), entrants = clients.len()))]
+    pub async fn query_race<T>(&self, prompt: String, clients: Vec<Box<dyn LowLevelClient>>) -> Result<RaceWin<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + serde::Serialize + Clone + 'static,
+    {
+        let id = correlation::current().unwrap_or_default();
+        correlation::scope(id, async move {
+            let schema = SchemaCache::global().get::<T>();
+            let entrants: Vec<_> = clients.into_iter()
+                .map(|client| (self.with_client(client), QueryProbe::start("query", &prompt).with_schema(&schema).detached()))
+                .collect();
+
+            let mut legs: FuturesUnordered<_> = entrants.iter().enumerate()
+                .map(|(index, (resolver, probe))| {
+                    let run = resolver.query::<T>(prompt.clone());
+                    async move { (index, probe.scoped(run).await) }
+                })
+                .collect();
+            let mut pending: BTreeSet<usize> = (0..entrants.len()).collect();
+            let mut failed = Vec::new();
+            let mut winner = None;
+            while let Some((index, result)) = legs.next().await {
+                pending.remove(&index);
+                match result {
+                    Ok(response) if response.has_data() => {
+                        winner = Some((index, response));
+                        break;
+                    }
+                    Ok(_) => failed.push((index, QueryResolverError::DataExtraction(DataExtractionError::NoDataFound))),
+                    Err(e) => {
+                        warn!(entrant = index, error = %e, "Race entrant failed");
+                        failed.push((index, e));
+                    }
+                }
+            }
+            drop(legs);
+
+            for (index, (resolver, probe)) in entrants.into_iter().enumerate() {
+                if pending.contains(&index) {
+                    resolver.report_cancelled(probe).await;
+                }
+            }
+            let cancelled: Vec<usize> = pending.into_iter().collect();
+            match winner {
+                Some((winner, response)) => {
+                    info!(winner, failed = failed.len(), cancelled = cancelled.len(), "Race won");
+                    Ok(RaceWin { winner, response, failed, cancelled })
+                }
+                None => Err(failed.pop().map(|(_, e)| e).unwrap_or(QueryResolverError::DataExtraction(DataExtractionError::NoDataFound))),
+            }
+        }).await
+    }
+}
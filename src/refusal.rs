@@ -0,0 +1,162 @@
+//! Telling refusals apart from extraction failures.
+//!
+//! A model that declines to answer produces no data, which `first_required` reports as
+//! `NoDataFound`. `QueryResolver::query_outcome` distinguishes the two: providers that
+//! flag refusals (`finish_reason: "content_filter"`, OpenAI's `refusal` message,
+//! Anthropic's `stop_reason: "refusal"`) fail with `AIError::Refused`, and replies with
+//! no data are checked by a `RefusalDetector`:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::refusal::QueryOutcome;
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Summary { text: String }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) -> Result<(), Box<dyn std::error::Error>> {
+//! match resolver.query_outcome::<Summary>("Summarize this contract".into()).await? {
+//!     QueryOutcome::Extracted(response) => println!("{:?}", response.first_required()?),
+//!     QueryOutcome::Refused { message } => println!("declined: {message}"),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver};
+use crate::error::{AIError, QueryResolverError};
+
+/// Message used when a provider blocks a response without explaining why
+pub const CONTENT_FILTERED: &str = "Response blocked by the provider's content filter";
+
+/// Result of `QueryResolver::query_outcome`
+#[derive(Debug, Clone)]
+pub enum QueryOutcome<T> {
+    /// The model answered; the response may still hold no data
+    Extracted(ParsedResponse<T>),
+    /// The model or the provider declined to answer
+    Refused { message: String },
+}
+
+impl<T> QueryOutcome<T> {
+    pub fn is_refused(&self) -> bool {
+        matches!(self, Self::Refused { .. })
+    }
+
+    /// The response, or `QueryResolverError::Refused`
+    pub fn into_response(self) -> Result<ParsedResponse<T>, QueryResolverError> {
+        match self {
+            Self::Extracted(response) => Ok(response),
+            Self::Refused { message } => Err(QueryResolverError::Refused(message)),
+        }
+    }
+}
+
+/// Recognizes refusals in model text
+pub trait RefusalDetector: Send + Sync + Debug {
+    /// The refusal message if `text` is one
+    fn detect(&self, text: &str) -> Option<String>;
+}
+
+/// Flags text that opens with a stock refusal phrase ("I can't help with that", ...)
+#[derive(Debug, Clone)]
+pub struct PhraseRefusalDetector {
+    phrases: Vec<String>,
+    /// Only this many leading characters are searched; refusals come first
+    window: usize,
+}
+
+const DEFAULT_PHRASES: &[&str] = &[
+    "i can't help",
+    "i cannot help",
+    "i can't assist",
+    "i cannot assist",
+    "i can't provide",
+    "i cannot provide",
+    "i can't comply",
+    "i cannot comply",
+    "i'm not able to help",
+    "i am not able to help",
+    "i'm unable to help",
+    "i am unable to help",
+    "i'm unable to assist",
+    "i won't be able to",
+    "i must decline",
+    "i have to decline",
+    "i'm sorry, but i can't",
+    "i'm sorry, but i cannot",
+];
+
+impl Default for PhraseRefusalDetector {
+    fn default() -> Self {
+        Self { phrases: DEFAULT_PHRASES.iter().map(|p| p.to_string()).collect(), window: 200 }
+    }
+}
+
+impl PhraseRefusalDetector {
+    /// Also flag text containing `phrase` (matched case-insensitively)
+    #[must_use]
+    pub fn with_phrase(mut self, phrase: impl Into<String>) -> Self {
+        self.phrases.push(normalize(&phrase.into()));
+        self
+    }
+
+    /// Search the first `chars` characters instead of 200
+    #[must_use]
+    pub fn with_window(mut self, chars: usize) -> Self {
+        self.window = chars;
+        self
+    }
+}
+
+/// Lowercase with typographic apostrophes straightened
+fn normalize(text: &str) -> String {
+    text.to_lowercase().replace(['\u{2019}', '\u{2018}'], "'")
+}
+
+impl RefusalDetector for PhraseRefusalDetector {
+    fn detect(&self, text: &str) -> Option<String> {
+        let text = text.trim();
+        let head: String = text.chars().take(self.window).collect();
+        let head = normalize(&head);
+        self.phrases.iter().any(|p| head.contains(p.as_str())).then(|| text.to_string())
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// `query`, reporting refusals as `QueryOutcome::Refused` rather than an error or a
+    /// response without data.
+    ///
+    /// Catches `AIError::Refused` from the provider, and replies without data (or prose
+    /// rejected under `ProsePolicy::Reject`) that the resolver's `RefusalDetector`
+    /// recognizes. Under `ProsePolicy::Ignore` the reply's text is discarded, so only
+    /// provider refusals are caught.
+    pub async fn query_outcome<T>(&self, prompt: String) -> Result<QueryOutcome<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+    {
+        match self.query::<T>(prompt).await {
+            Ok(response) if !response.has_data() => Ok(match self.refusal_detector().detect(&response.text_content()) {
+                Some(message) => QueryOutcome::Refused { message },
+                None => QueryOutcome::Extracted(response),
+            }),
+            Ok(response) => Ok(QueryOutcome::Extracted(response)),
+            Err(QueryResolverError::Ai(AIError::Refused(message))) => Ok(QueryOutcome::Refused { message }),
+            Err(QueryResolverError::UnexpectedProse(prose)) => match self.refusal_detector().detect(&prose) {
+                Some(message) => Ok(QueryOutcome::Refused { message }),
+                None => Err(QueryResolverError::UnexpectedProse(prose)),
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Refusal reported on an OpenAI-style choice: a `refusal` message, or
+/// `finish_reason: "content_filter"`
+#[cfg_attr(not(any(feature = "openai", feature = "azure", feature = "deepseek", feature = "openai-compatible")), allow(dead_code))]
+pub(crate) fn choice_refusal(finish_reason: Option<&str>, refusal: Option<String>) -> Option<String> {
+    refusal.or_else(|| (finish_reason == Some("content_filter")).then(|| CONTENT_FILTERED.to_string()))
+}
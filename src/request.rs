@@ -0,0 +1,175 @@
+//! Provider-neutral request type.
+//!
+//! A `ModelRequest` describes one call (messages, sampling parameters, tools and the
+//! expected response format) independently of any provider's wire format. Clients
+//! map it in `LowLevelClient::ask_request`, so interceptors, caches, routers and
+//! tests can work with one canonical type instead of `ClaudeRequest`, OpenAI bodies
+//! and so on.
+//!
+//! ```
+//! use semantic_query::core::ChatMessage;
+//! use semantic_query::request::{ModelRequest, ResponseFormat};
+//!
+//! let request = ModelRequest::new(vec![ChatMessage::system("Be terse"), ChatMessage::user("Hi")])
+//!     .with_temperature(0.0)
+//!     .with_response_format(ResponseFormat::JsonObject);
+//! let json = serde_json::to_value(&request).unwrap();
+//! assert_eq!(json["response_format"]["type"], "json_object");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::{ChatMessage, LowLevelClient};
+use crate::schema::{schema_value, JsonSchema};
+
+/// Sampling parameters; `None` (or no stop sequences) keeps the client's configured value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Generation ends before any of these
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// End user the request is made for, sent as OpenAI `user` and Anthropic
+    /// `metadata.user_id` for the provider's abuse monitoring. Use an opaque id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// A tool offered to the model, mapped to `FunctionTool` / `ClaudeTool` by providers
+/// with native tool calling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    /// JSON Schema of the tool's input
+    pub schema: Value,
+}
+
+impl ToolSpec {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, schema: Value) -> Self {
+        Self { name: name.into(), description: description.into(), schema }
+    }
+
+    /// A tool whose input is `T`
+    pub fn for_type<T: JsonSchema>(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self::new(name, description, schema_value::<T>())
+    }
+}
+
+/// The shape the reply should take
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Free text (the default)
+    #[default]
+    Text,
+    /// A JSON value, using the provider's JSON mode where it has one
+    JsonObject,
+    /// JSON matching `schema`. Providers without structured outputs treat this as
+    /// `JsonObject`.
+    JsonSchema { name: String, schema: Value },
+}
+
+/// One model call, independent of the provider it is sent to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelRequest {
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub params: RequestParams,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolSpec>,
+    #[serde(default)]
+    pub response_format: ResponseFormat,
+}
+
+impl ModelRequest {
+    pub fn new(messages: Vec<ChatMessage>) -> Self {
+        Self { messages, ..Self::default() }
+    }
+
+    /// A request with a single user message
+    pub fn user(prompt: impl Into<String>) -> Self {
+        Self::new(vec![ChatMessage::user(prompt)])
+    }
+
+    #[must_use]
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.params.temperature = Some(temperature);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.params.max_tokens = Some(max_tokens);
+        self
+    }
+
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.params.seed = Some(seed);
+        self
+    }
+
+    /// Stop generating before any of `stop`
+    #[must_use]
+    pub fn with_stop(mut self, stop: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.params.stop = stop.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Make the request on behalf of end user `user`; see `RequestParams::user`
+    #[must_use]
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.params.user = Some(user.into());
+        self
+    }
+
+    /// Offer `tool` to the model (in addition to any already added)
+    #[must_use]
+    pub fn with_tool(mut self, tool: ToolSpec) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    #[must_use]
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = format;
+        self
+    }
+
+    /// `client` with this request's parameters and JSON mode applied through the
+    /// `with_*` methods, skipping any the client does not support. Used by the default
+    /// `LowLevelClient::ask_request`; tools are not applied.
+    pub fn configure(&self, client: Box<dyn LowLevelClient>) -> Box<dyn LowLevelClient> {
+        let mut client = client;
+        if let Some(configured) = self.params.temperature.and_then(|t| client.with_temperature(t)) {
+            client = configured;
+        }
+        if let Some(configured) = self.params.max_tokens.and_then(|n| client.with_max_tokens(n)) {
+            client = configured;
+        }
+        if let Some(configured) = self.params.seed.and_then(|s| client.with_seed(s)) {
+            client = configured;
+        }
+        if !self.params.stop.is_empty() {
+            if let Some(configured) = client.with_stop_sequences(&self.params.stop) {
+                client = configured;
+            }
+        }
+        if let Some(configured) = self.params.user.as_deref().and_then(|u| client.with_user(u)) {
+            client = configured;
+        }
+        if self.response_format != ResponseFormat::Text {
+            if let Some(configured) = client.with_json_mode() {
+                client = configured;
+            }
+        }
+        client
+    }
+}
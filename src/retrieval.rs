@@ -0,0 +1,303 @@
+//! Grounded extraction over retrieved context.
+//!
+//! A `ContextProvider` returns scored snippets for a question (from a vector store,
+//! search index, ...). `stuff_context` adds them to a `PromptBuilder` under citation
+//! markers (`[S1]`, `[S2]`, ...), keeping the highest-scoring ones when the budget is
+//! tight, and `map_citations` maps the markers the model wrote into the extracted data
+//! back to snippet ids. `QueryResolver::query_grounded` runs all three:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::retrieval::{GroundingOptions, InMemoryContext};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Answer { answer: String }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) -> Result<(), Box<dyn std::error::Error>> {
+//! let docs = InMemoryContext::new([("handbook#leave", "Employees get 25 days of annual leave.")]);
+//! let grounded = resolver
+//!     .query_grounded::<Answer>("How many days of leave do employees get?".into(), &docs, &GroundingOptions::default())
+//!     .await?;
+//! for citation in &grounded.citations {
+//!     println!("{} cites {:?}", citation.path, citation.snippet_id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::{LowLevelClient, QueryResolver};
+use crate::error::QueryResolverError;
+use crate::injection::{FlaggedSegment, InjectionGuard};
+use crate::prompt::{CutAction, PromptBuilder, PromptCut, RenderedPrompt};
+
+/// Instructions added ahead of the snippets by `query_grounded`
+pub const CITATION_INSTRUCTIONS: &str = "Answer using only the sources below. After each statement that relies on a source, write its marker, e.g. [S1] or [S1][S3], inside the same JSON string.";
+
+/// A retrieved piece of context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snippet {
+    /// Stable id in the document store, reported back in citations
+    pub id: String,
+    pub text: String,
+    /// Relevance; higher is better
+    pub score: f32,
+    /// Human-readable origin (file name, URL, ...), shown in the prompt if set
+    pub source: Option<String>,
+}
+
+impl Snippet {
+    pub fn new(id: impl Into<String>, text: impl Into<String>, score: f32) -> Self {
+        Self { id: id.into(), text: text.into(), score, source: None }
+    }
+
+    #[must_use]
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+/// Source of scored snippets for a query
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait ContextProvider: Send + Sync + Debug {
+    /// Up to `limit` snippets relevant to `query`, in any order.
+    /// Report store failures as `QueryResolverError::Retrieval`.
+    async fn retrieve(&self, query: &str, limit: usize) -> Result<Vec<Snippet>, QueryResolverError>;
+}
+
+/// Fixed set of documents scored by the share of query words they contain.
+/// Meant for tests and small corpora; plug a real index in through `ContextProvider`.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryContext {
+    documents: Vec<(String, String)>,
+}
+
+impl InMemoryContext {
+    /// Documents as `(id, text)` pairs
+    pub fn new<I, K, V>(documents: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self { documents: documents.into_iter().map(|(id, text)| (id.into(), text.into())).collect() }
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.len() > 2)
+        .map(str::to_lowercase)
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl ContextProvider for InMemoryContext {
+    async fn retrieve(&self, query: &str, limit: usize) -> Result<Vec<Snippet>, QueryResolverError> {
+        let terms: Vec<String> = words(query).collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut snippets: Vec<Snippet> = self.documents.iter()
+            .filter_map(|(id, text)| {
+                let doc: Vec<String> = words(text).collect();
+                let hits = terms.iter().filter(|t| doc.contains(t)).count();
+                (hits > 0).then(|| Snippet::new(id.clone(), text.clone(), hits as f32 / terms.len() as f32))
+            })
+            .collect();
+        sort_by_score(&mut snippets);
+        snippets.truncate(limit);
+        Ok(snippets)
+    }
+}
+
+/// Highest score first; ties keep their original order
+fn sort_by_score(snippets: &mut [Snippet]) {
+    snippets.sort_by(|a, b| b.score.total_cmp(&a.score));
+}
+
+/// Snippet included in a prompt, with the marker the model cites it by
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CitedSnippet {
+    /// e.g. `[S1]`
+    pub marker: String,
+    pub snippet: Snippet,
+    /// Whether the snippet was shortened to fit the budget
+    pub truncated: bool,
+}
+
+/// Output of `stuff_context`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StuffedContext {
+    pub rendered: RenderedPrompt,
+    /// Snippets that made it into the prompt, best first
+    pub included: Vec<CitedSnippet>,
+}
+
+impl StuffedContext {
+    /// Snippet cited by `marker`, if it was included
+    pub fn snippet(&self, marker: &str) -> Option<&Snippet> {
+        self.included.iter().find(|c| c.marker == marker).map(|c| &c.snippet)
+    }
+}
+
+/// Add `snippets` to `builder` as context documents, best score first, followed by
+/// `question`, and render it.
+///
+/// Snippets share one priority, so when over budget the lowest-scoring ones are
+/// truncated or dropped first; dropped snippets are left out of `included`.
+pub fn stuff_context(mut builder: PromptBuilder, snippets: &[Snippet], question: impl Into<String>) -> StuffedContext {
+    let mut ranked = snippets.to_vec();
+    sort_by_score(&mut ranked);
+
+    let mut markers = Vec::with_capacity(ranked.len());
+    for (i, snippet) in ranked.iter().enumerate() {
+        let marker = format!("[S{}]", i + 1);
+        let title = match &snippet.source {
+            Some(source) => format!("{marker} {source}"),
+            None => marker.clone(),
+        };
+        builder = builder.context(title.clone(), snippet.text.clone());
+        markers.push((marker, title));
+    }
+
+    let rendered = builder.question(question).render();
+    let cut = |title: &str| rendered.cuts.iter().find(|c| c.name == title).map(|c| c.action);
+    let included = ranked.into_iter().zip(markers)
+        .filter_map(|(snippet, (marker, title))| match cut(&title) {
+            Some(CutAction::Dropped { .. }) => None,
+            action => Some(CitedSnippet { marker, snippet, truncated: action.is_some() }),
+        })
+        .collect();
+    StuffedContext { rendered, included }
+}
+
+/// A citation marker found in extracted data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Citation {
+    /// JSON pointer to the string containing the marker, e.g. `/claims/0/text`
+    pub path: String,
+    pub marker: String,
+    /// Id of the cited snippet; None if the marker doesn't match an included snippet
+    pub snippet_id: Option<String>,
+}
+
+/// Find the `[S<n>]` markers in the strings of `data` and map them to snippet ids
+pub fn map_citations<T: Serialize>(data: &T, context: &StuffedContext) -> Vec<Citation> {
+    let mut citations = Vec::new();
+    if let Ok(value) = serde_json::to_value(data) {
+        collect_citations(&value, String::new(), context, &mut citations);
+    }
+    citations
+}
+
+fn collect_citations(value: &Value, path: String, context: &StuffedContext, out: &mut Vec<Citation>) {
+    match value {
+        Value::String(text) => {
+            for marker in find_markers(text) {
+                let snippet_id = context.snippet(&marker).map(|s| s.id.clone());
+                out.push(Citation { path: path.clone(), marker, snippet_id });
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_citations(item, format!("{path}/{i}"), context, out);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, item) in fields {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                collect_citations(item, format!("{path}/{key}"), context, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `[S<digits>]` markers in `text`, in order of appearance
+fn find_markers(text: &str) -> Vec<String> {
+    let mut markers = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[S") {
+        let after = &rest[start + 2..];
+        let digits = after.bytes().take_while(u8::is_ascii_digit).count();
+        if digits > 0 && after[digits..].starts_with(']') {
+            markers.push(rest[start..start + 3 + digits].to_string());
+        }
+        rest = &rest[start + 2..];
+    }
+    markers
+}
+
+/// Limits for `QueryResolver::query_grounded`
+#[derive(Debug, Clone)]
+pub struct GroundingOptions {
+    /// Snippets requested from the provider
+    pub max_snippets: usize,
+    /// Snippets scoring below this are ignored
+    pub min_score: f32,
+    /// Token budget for instructions, snippets and question; the schema guidance the
+    /// resolver appends comes on top
+    pub max_tokens: usize,
+    /// Screens snippets for prompt injection before they are stuffed (default: none)
+    pub injection_guard: Option<InjectionGuard>,
+}
+
+impl Default for GroundingOptions {
+    fn default() -> Self {
+        Self { max_snippets: 8, min_score: 0.0, max_tokens: 4000, injection_guard: None }
+    }
+}
+
+/// Result of `QueryResolver::query_grounded`
+#[derive(Debug, Clone)]
+pub struct Grounded<T> {
+    pub data: T,
+    /// Markers found in `data`, in document order
+    pub citations: Vec<Citation>,
+    /// Snippets the model was shown
+    pub snippets: Vec<CitedSnippet>,
+    /// Snippets (and other parts) cut to fit the budget
+    pub cuts: Vec<PromptCut>,
+    /// Snippets `GroundingOptions::injection_guard` flagged, by snippet id
+    pub flagged: Vec<FlaggedSegment>,
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Retrieve context for `question` from `provider`, answer it as a `T` grounded in
+    /// that context, and map the citation markers in the answer back to snippet ids.
+    pub async fn query_grounded<T>(&self, question: String, provider: &dyn ContextProvider, options: &GroundingOptions) -> Result<Grounded<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+    {
+        let mut snippets: Vec<Snippet> = provider.retrieve(&question, options.max_snippets).await?
+            .into_iter()
+            .filter(|s| s.score >= options.min_score)
+            .collect();
+        let mut flagged = Vec::new();
+        if let Some(guard) = &options.injection_guard {
+            snippets = snippets.into_iter()
+                .filter_map(|snippet| {
+                    let (text, report) = guard.screen_named(&snippet.id, &snippet.text);
+                    flagged.extend(report);
+                    text.map(|text| Snippet { text, ..snippet })
+                })
+                .collect();
+        }
+        let builder = PromptBuilder::new()
+            .instructions(CITATION_INSTRUCTIONS)
+            .max_tokens(options.max_tokens);
+        let stuffed = stuff_context(builder, &snippets, question);
+
+        let data = self.query::<T>(stuffed.rendered.clone().into_text()).await?.first_required()?;
+        let citations = map_citations(&data, &stuffed);
+        Ok(Grounded { data, citations, snippets: stuffed.included, cuts: stuffed.rendered.cuts, flagged })
+    }
+}
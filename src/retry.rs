@@ -0,0 +1,330 @@
+//! Prompt mutation between extraction attempts.
+//!
+//! By default a rejected extraction is answered with a follow-up correction message in
+//! the same conversation. A `RetryStrategy` registered for an error class with
+//! `RetryConfig::with_strategy` replaces that: it sees the failed attempt and plans a
+//! fresh request, with a rewritten prompt and optionally on another client:
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use semantic_query::core::RetryConfig;
+//! # use semantic_query::retry::{self, AppendFailure, Escalate, Sequence, StrictJson};
+//! # fn run(stronger: Box<dyn semantic_query::core::LowLevelClient>) {
+//! let config = RetryConfig::default()
+//!     .with_strategy(retry::NO_DATA, Arc::new(StrictJson))
+//!     .with_strategy(retry::POST_PROCESS, Arc::new(Sequence::new(vec![
+//!         Arc::new(AppendFailure),
+//!         Arc::new(Escalate::new(stronger)),
+//!     ])));
+//! # }
+//! ```
+//!
+//! Strategies apply to `query`, `query_mixed`, `query_typed` and conversations. The
+//! number of attempts per class is still `RetryConfig::max_retries[class]`.
+//!
+//! Replies cut off at `max_tokens` are a separate, opt-in case: see `LengthRecovery`.
+//!
+//! Per-class limits bound each query on its own; a multi-step pipeline retrying at every
+//! level can still multiply them. A `RetryBudget` bounds the total: every retry under it,
+//! at any level, spends from the same allowance:
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use semantic_query::retry::{self, RetryBudget};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Invoice { total: f64 }
+//! # async fn run(resolver: semantic_query::core::QueryResolver<semantic_query::clients::mock::MockVoid>) {
+//! let budget = RetryBudget::attempts(5).with_deadline(Duration::from_secs(30));
+//! let result = retry::budget_scope(budget.clone(), async {
+//!     let first = resolver.query::<Invoice>("Extract the invoice".into()).await;
+//!     let second = resolver.query::<Invoice>("Extract the credit note".into()).await;
+//!     (first, second)
+//! }).await;
+//! println!("{} retries spent", budget.spent());
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::core::LowLevelClient;
+
+/// Error class of replies whose data post-processors rejected
+pub const POST_PROCESS: &str = "post_process";
+/// Error class of replies without any data. Such replies are only retried when a
+/// strategy is registered for this class.
+pub const NO_DATA: &str = "json_parse_error";
+
+/// Error class of replies without data that the provider cut off at `max_tokens`
+/// (`FinishReason::Length`). Only recovered from when `RetryConfig::with_length_recovery`
+/// is set; otherwise these count as `NO_DATA`.
+pub const LENGTH: &str = "length";
+
+/// Error class of replies not in the resolver's expected language
+/// (`QueryResolver::with_expected_language`)
+pub const LANGUAGE: &str = "language";
+
+/// How to recover a reply that hit `max_tokens` before any data was complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthRecovery {
+    /// Resend the prompt with `max_tokens` multiplied by `factor`, up to `cap`. Clients
+    /// that cannot change their limit (`LowLevelClient::with_max_tokens`) get a
+    /// continuation request instead.
+    GrowMaxTokens { factor: u32, cap: u32 },
+    /// Ask the model to continue exactly where it stopped and parse the reply joined
+    /// to the cut-off one, so a JSON value split across both is recovered
+    Continue,
+}
+
+/// Follow-up sent by `LengthRecovery::Continue`
+pub const CONTINUE_INSTRUCTION: &str = "Your answer was cut off. Continue exactly where it stopped, without repeating anything.";
+
+/// An extraction attempt that failed
+#[derive(Debug, Clone, Copy)]
+pub struct FailedAttempt<'a> {
+    /// `POST_PROCESS`, `NO_DATA` or `LANGUAGE`
+    pub class: &'a str,
+    /// The first prompt sent, including schema guidance
+    pub prompt: &'a str,
+    /// What the model answered this time
+    pub output: &'a str,
+    /// Why the answer was rejected
+    pub error: &'a str,
+    /// 1 for the first retry
+    pub attempt: usize,
+}
+
+/// The request a strategy wants sent next
+#[derive(Debug)]
+pub struct RetryPlan {
+    pub prompt: String,
+    /// Send to this client instead of the resolver's
+    pub client: Option<Box<dyn LowLevelClient>>,
+}
+
+impl RetryPlan {
+    pub fn prompt(prompt: impl Into<String>) -> Self {
+        Self { prompt: prompt.into(), client: None }
+    }
+}
+
+/// Plans the next attempt after a failed extraction
+pub trait RetryStrategy: Send + Sync + Debug {
+    /// The next request, or None to give up
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan>;
+}
+
+/// Repeats the prompt with the failed answer and the error appended
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AppendFailure;
+
+impl RetryStrategy for AppendFailure {
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan> {
+        Some(RetryPlan::prompt(format!(
+            "{}\n\nA previous answer was rejected.\nAnswer:\n{}\nProblem: {}\n\nAnswer again, fixing the problem.",
+            failed.prompt, failed.output, failed.error
+        )))
+    }
+}
+
+/// Repeats the prompt with an example of a valid answer
+#[derive(Debug, Clone)]
+pub struct AddExample {
+    example: String,
+}
+
+impl AddExample {
+    pub fn new(example: impl Into<String>) -> Self {
+        Self { example: example.into() }
+    }
+
+    /// Use `value`, serialized, as the example
+    pub fn of<T: Serialize>(value: &T) -> Self {
+        Self::new(serde_json::to_string_pretty(value).unwrap_or_default())
+    }
+}
+
+impl RetryStrategy for AddExample {
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan> {
+        Some(RetryPlan::prompt(format!(
+            "{}\n\nHere is an example of a valid answer (the values are illustrative):\n{}",
+            failed.prompt, self.example
+        )))
+    }
+}
+
+/// Instruction `StrictJson` appends
+pub const STRICT_JSON_INSTRUCTION: &str = "Respond with the JSON only: no explanation, no markdown code fences, nothing before or after it. Every required field must be present.";
+
+/// Repeats the prompt demanding a bare JSON answer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StrictJson;
+
+impl RetryStrategy for StrictJson {
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan> {
+        Some(RetryPlan::prompt(format!("{}\n\n{STRICT_JSON_INSTRUCTION}", failed.prompt)))
+    }
+}
+
+/// Sends the original prompt to another (usually stronger) client
+#[derive(Debug, Clone)]
+pub struct Escalate {
+    client: Box<dyn LowLevelClient>,
+}
+
+impl Escalate {
+    pub fn new(client: Box<dyn LowLevelClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl RetryStrategy for Escalate {
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan> {
+        Some(RetryPlan { prompt: failed.prompt.to_string(), client: Some(self.client.clone_box()) })
+    }
+}
+
+/// Uses the n-th strategy for the n-th retry, repeating the last one
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    strategies: Vec<Arc<dyn RetryStrategy>>,
+}
+
+impl Sequence {
+    pub fn new(strategies: Vec<Arc<dyn RetryStrategy>>) -> Self {
+        Self { strategies }
+    }
+}
+
+impl RetryStrategy for Sequence {
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan> {
+        let index = failed.attempt.saturating_sub(1).min(self.strategies.len().checked_sub(1)?);
+        self.strategies[index].next_attempt(failed)
+    }
+}
+
+tokio::task_local! {
+    static BUDGETS: Vec<RetryBudget>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn deadline_after(limit: Duration) -> Option<Instant> {
+    Some(Instant::now() + limit)
+}
+
+/// `Instant::now` panics on wasm32, so budgets there have no deadline
+#[cfg(target_arch = "wasm32")]
+fn deadline_after(_limit: Duration) -> Option<Instant> {
+    None
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    max_attempts: Option<usize>,
+    deadline: Option<Instant>,
+    spent: AtomicUsize,
+}
+
+/// Total retries allowed across everything it is attached to: by attempts, by wall
+/// clock, or both. Clones share the allowance.
+///
+/// Attach it with `retry::budget_scope`, `QueryResolver::with_retry_budget` or
+/// `Pipeline::with_retry_budget`. Once it is exhausted, failing extractions are given
+/// up as if their class had run out of retries.
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    state: Arc<BudgetState>,
+}
+
+impl RetryBudget {
+    /// At most `max` retries in total
+    pub fn attempts(max: usize) -> Self {
+        Self::new(Some(max), None)
+    }
+
+    /// Retries may start until `limit` after now. Not enforced on wasm32, which has no
+    /// clock.
+    pub fn within(limit: Duration) -> Self {
+        Self::new(None, deadline_after(limit))
+    }
+
+    fn new(max_attempts: Option<usize>, deadline: Option<Instant>) -> Self {
+        Self { state: Arc::new(BudgetState { max_attempts, deadline, spent: AtomicUsize::new(0) }) }
+    }
+
+    /// Also stop retrying `limit` after now (not on wasm32, see `within`)
+    #[must_use]
+    pub fn with_deadline(self, limit: Duration) -> Self {
+        Self::new(self.state.max_attempts, deadline_after(limit))
+    }
+
+    /// Also stop retrying after `max` retries
+    #[must_use]
+    pub fn with_attempts(self, max: usize) -> Self {
+        Self::new(Some(max), self.state.deadline)
+    }
+
+    /// Retries spent so far
+    pub fn spent(&self) -> usize {
+        self.state.spent.load(Ordering::Relaxed)
+    }
+
+    /// Retries left, when limited by attempts
+    pub fn remaining(&self) -> Option<usize> {
+        self.state.max_attempts.map(|max| max.saturating_sub(self.spent()))
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == Some(0) || self.state.deadline.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Take one retry, or return false when none is left
+    pub fn try_spend(&self) -> bool {
+        if self.state.deadline.is_some_and(|at| Instant::now() >= at) {
+            return false;
+        }
+        let max = self.state.max_attempts.unwrap_or(usize::MAX);
+        self.state.spent
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |spent| (spent < max).then_some(spent + 1))
+            .is_ok()
+    }
+
+    fn refund(&self) {
+        self.state.spent.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+/// Run `future` with `budget` bounding every retry inside it, including those of nested
+/// pipelines and queries. Scopes nest: a retry spends from every budget in scope.
+pub async fn budget_scope<F: Future>(budget: RetryBudget, future: F) -> F::Output {
+    let mut budgets = BUDGETS.try_with(Clone::clone).unwrap_or_default();
+    if !budgets.iter().any(|b| b.same(&budget)) {
+        budgets.push(budget);
+    }
+    BUDGETS.scope(budgets, future).await
+}
+
+/// Take one retry from every budget in scope and from `own`. Spends nothing and returns
+/// false when any of them is exhausted.
+pub(crate) fn spend(own: Option<&RetryBudget>) -> bool {
+    let mut budgets = BUDGETS.try_with(Clone::clone).unwrap_or_default();
+    if let Some(own) = own.filter(|own| !budgets.iter().any(|b| b.same(own))) {
+        budgets.push(own.clone());
+    }
+    for (i, budget) in budgets.iter().enumerate() {
+        if !budget.try_spend() {
+            budgets[..i].iter().for_each(RetryBudget::refund);
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,204 @@
+//! Human review of doubtful extractions.
+//!
+//! `QueryResolver::query_reviewed` runs a query and checks each extracted item with a
+//! `ReviewGuard` (low confidence, failed business rule, ...). If one is flagged, the
+//! response is not returned: a `ReviewRequest` with the raw response and the candidate
+//! goes to a `ReviewSink` and the caller gets `ReviewOutcome::Pending`. A reviewer later
+//! answers with a `ReviewResolution`, which `ReviewRequest::resolve` turns into a `T`:
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use semantic_query::core::QueryResolver;
+//! # use semantic_query::review::{InMemoryReviewQueue, ReviewOutcome, ReviewResolution};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Invoice { total: f64, confidence: f64 }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) -> Result<(), Box<dyn std::error::Error>> {
+//! let queue = InMemoryReviewQueue::new();
+//! let guard = |i: &Invoice| (i.confidence < 0.8).then(|| format!("confidence {}", i.confidence));
+//! match resolver.query_reviewed::<Invoice, _>("Extract the invoice".into(), &guard, &queue).await? {
+//!     ReviewOutcome::Accepted(response) => println!("{:?}", response.first()),
+//!     ReviewOutcome::Pending { id, .. } => {
+//!         // ... later, from the review UI
+//!         queue.resolve(&id, ReviewResolution::Correct(serde_json::json!({"total": 99.5, "confidence": 1.0})))?;
+//!         let invoice: Invoice = queue.resolved(&id).expect("resolved")?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver};
+use crate::correlation::{self, CorrelationId};
+use crate::error::QueryResolverError;
+
+/// Decides whether an extracted item needs a human look
+pub trait ReviewGuard<T>: Send + Sync {
+    /// Why `value` needs review, or None to accept it
+    fn check(&self, value: &T) -> Option<String>;
+}
+
+/// Closures act as guards: `|i: &Invoice| (i.confidence < 0.8).then(|| "low confidence".into())`
+impl<T, F> ReviewGuard<T> for F
+where
+    F: Fn(&T) -> Option<String> + Send + Sync,
+{
+    fn check(&self, value: &T) -> Option<String> {
+        self(value)
+    }
+}
+
+/// An extraction waiting for a reviewer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewRequest {
+    pub id: String,
+    pub prompt: String,
+    /// The model's reply, text and JSON
+    pub raw_response: String,
+    /// The flagged item, serialized
+    pub candidate: Value,
+    /// What the guard reported
+    pub reason: String,
+    pub correlation_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ReviewRequest {
+    /// The value a resolution settles on: the candidate if approved, the reviewer's
+    /// correction, or `QueryResolverError::ReviewRejected`
+    pub fn resolve<T: DeserializeOwned>(&self, resolution: &ReviewResolution) -> Result<T, QueryResolverError> {
+        let value = match resolution {
+            ReviewResolution::Approve => self.candidate.clone(),
+            ReviewResolution::Correct(value) => value.clone(),
+            ReviewResolution::Reject(reason) => return Err(QueryResolverError::ReviewRejected(reason.clone())),
+        };
+        serde_json::from_value(value.clone()).map_err(|e| QueryResolverError::JsonDeserialization(e, value.to_string()))
+    }
+}
+
+/// A reviewer's decision
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReviewResolution {
+    /// The candidate is right as extracted
+    Approve,
+    /// Use this value instead
+    Correct(Value),
+    /// Nothing usable; the reason is reported to the caller
+    Reject(String),
+}
+
+/// Result of `QueryResolver::query_reviewed`
+#[derive(Debug, Clone)]
+pub enum ReviewOutcome<T> {
+    /// No item was flagged
+    Accepted(ParsedResponse<T>),
+    /// An item was flagged and sent for review under `id`
+    Pending { id: String, reason: String },
+}
+
+impl<T> ReviewOutcome<T> {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending { .. })
+    }
+}
+
+/// Where flagged extractions are sent (a database table, a ticketing system, ...)
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait ReviewSink: Send + Sync {
+    /// Store `request`; an error fails the query with `QueryResolverError::Review`
+    async fn submit(&self, request: ReviewRequest) -> Result<(), String>;
+}
+
+/// Process-local review queue, for tests and single-instance tools
+#[derive(Debug, Default)]
+pub struct InMemoryReviewQueue {
+    entries: Mutex<BTreeMap<String, (ReviewRequest, Option<ReviewResolution>)>>,
+}
+
+impl InMemoryReviewQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests without a resolution, oldest first
+    pub fn pending(&self) -> Vec<ReviewRequest> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut pending: Vec<_> = entries.values().filter(|(_, r)| r.is_none()).map(|(req, _)| req.clone()).collect();
+        pending.sort_by_key(|r| r.created_at);
+        pending
+    }
+
+    pub fn get(&self, id: &str) -> Option<ReviewRequest> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.get(id).map(|(req, _)| req.clone())
+    }
+
+    /// Record the reviewer's decision for `id`
+    pub fn resolve(&self, id: &str, resolution: ReviewResolution) -> Result<(), QueryResolverError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (_, slot) = entries.get_mut(id).ok_or_else(|| QueryResolverError::Review(format!("unknown review '{id}'")))?;
+        *slot = Some(resolution);
+        Ok(())
+    }
+
+    /// The settled value for `id`, or None while it is unknown or pending
+    pub fn resolved<T: DeserializeOwned>(&self, id: &str) -> Option<Result<T, QueryResolverError>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (request, resolution) = entries.get(id)?;
+        resolution.as_ref().map(|resolution| request.resolve(resolution))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl ReviewSink for InMemoryReviewQueue {
+    async fn submit(&self, request: ReviewRequest) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(request.id.clone(), (request, None));
+        Ok(())
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// `query`, diverting the response to `sink` when `guard` flags any extracted item.
+    ///
+    /// Only the first flagged item is sent; the caller gets `ReviewOutcome::Pending` with
+    /// the review id to look the resolution up by. Responses without data are accepted
+    /// as they are.
+    pub async fn query_reviewed<T, G>(&self, prompt: String, guard: &G, sink: &dyn ReviewSink) -> Result<ReviewOutcome<T>, QueryResolverError>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+        G: ReviewGuard<T> + ?Sized,
+    {
+        let id = correlation::current().unwrap_or_default();
+        let response = correlation::scope(id.clone(), self.query::<T>(prompt.clone())).await?;
+        let flagged = response.data_only().into_iter().find_map(|item| guard.check(item).map(|reason| (item, reason)));
+        let Some((item, reason)) = flagged else {
+            return Ok(ReviewOutcome::Accepted(response));
+        };
+
+        let request = ReviewRequest {
+            id: format!("review-{}", CorrelationId::new()),
+            prompt,
+            raw_response: response.text_content(),
+            candidate: serde_json::to_value(item).unwrap_or_default(),
+            reason: reason.clone(),
+            correlation_id: Some(id.to_string()),
+            created_at: Utc::now(),
+        };
+        let review_id = request.id.clone();
+        sink.submit(request).await.map_err(QueryResolverError::Review)?;
+        Ok(ReviewOutcome::Pending { id: review_id, reason })
+    }
+}
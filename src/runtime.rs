@@ -0,0 +1,151 @@
+//! Async runtime touchpoints.
+//!
+//! The crate runs on any executor except where it spawns tasks, waits on timers,
+//! runs blocking work or touches the file system. Those go through this module:
+//!
+//! - with the `rt-tokio` feature (the default), they use tokio and must run inside a
+//!   tokio runtime;
+//! - with `rt-async-std` (and `default-features = false`), they use async-std, whose
+//!   global executor and timers also work under smol and other `async-io` executors;
+//! - on `wasm32` (browser / edge functions) there is no multi-threaded executor, so
+//!   tasks are run with `wasm_bindgen_futures::spawn_local` and need not be `Send`.
+//!
+//! Native builds enable exactly one of the two features. Without `rt-tokio`, only the
+//! parts of tokio that need no runtime are compiled: the channels, locks, task-locals
+//! and `select!` used elsewhere. The HTTP providers do: `reqwest` needs a tokio reactor, so
+//! non-tokio applications give them one (e.g. with the `async-compat` crate) or bring
+//! their own `LowLevelClient`. `ProcessClient` (compiled with `rt-tokio` only) and the
+//! `websocket`/`grpc` transports are tokio-only.
+//!
+//! `compat` converts between tokio's and futures-io's `AsyncRead`, so the readers taken
+//! and returned by the streaming API work with either.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "rt-async-std"))]
+use async_std as rt;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "rt-async-std")))]
+use tokio as rt;
+
+/// Spawn a detached background task on the current runtime.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    rt::task::spawn(future);
+}
+
+/// Spawn a detached background task on the browser event loop.
+#[cfg(target_arch = "wasm32")]
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Wait for `duration`
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: Duration) {
+    #[cfg(not(feature = "rt-async-std"))]
+    rt::time::sleep(duration).await;
+    #[cfg(feature = "rt-async-std")]
+    rt::task::sleep(duration).await;
+}
+
+/// A `timeout` ran out before its future finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Run `future` for at most `duration`
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    #[cfg(not(feature = "rt-async-std"))]
+    return rt::time::timeout(duration, future).await.map_err(|_| Elapsed);
+    #[cfg(feature = "rt-async-std")]
+    return rt::future::timeout(duration, future).await.map_err(|_| Elapsed);
+}
+
+/// Without a timer on wasm32, `future` runs to completion and never elapses
+#[cfg(target_arch = "wasm32")]
+pub async fn timeout<F: Future>(_duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    Ok(future.await)
+}
+
+/// Run blocking `f` (file or keychain access) off the async worker threads. A panic
+/// in `f` resumes in the caller.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn spawn_blocking<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    #[cfg(not(feature = "rt-async-std"))]
+    return match rt::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    };
+    #[cfg(feature = "rt-async-std")]
+    return rt::task::spawn_blocking(f).await;
+}
+
+/// File-system calls as `std::fs` run through `spawn_blocking`
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fs {
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    pub async fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+        let path = path.as_ref().to_path_buf();
+        super::spawn_blocking(move || std::fs::read_to_string(path)).await
+    }
+
+    pub async fn write(path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> io::Result<()> {
+        let (path, contents) = (path.as_ref().to_path_buf(), contents.into());
+        super::spawn_blocking(move || std::fs::write(path, contents)).await
+    }
+
+    pub async fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+        let (from, to): (PathBuf, PathBuf) = (from.as_ref().into(), to.as_ref().into());
+        super::spawn_blocking(move || std::fs::rename(from, to)).await
+    }
+
+    pub async fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref().to_path_buf();
+        super::spawn_blocking(move || std::fs::create_dir_all(path)).await
+    }
+
+    pub async fn try_exists(path: impl AsRef<Path>) -> io::Result<bool> {
+        let path = path.as_ref().to_path_buf();
+        super::spawn_blocking(move || path.try_exists()).await
+    }
+}
+
+/// Adapters between tokio's and futures-io's `AsyncRead`
+pub mod compat {
+    pub use tokio_util::compat::Compat;
+    use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+
+    /// A futures-io reader (async-std, smol, `futures::io`) as a tokio `AsyncRead`, for
+    /// the `*_async_read` functions in `streaming` and `json_utils`
+    pub fn from_futures_read<R: futures_io::AsyncRead>(reader: R) -> Compat<R> {
+        reader.compat()
+    }
+
+    /// A tokio reader (e.g. `FlexibleClient::stream_raw_reader`) as a futures-io
+    /// `AsyncRead`
+    pub fn into_futures_read<R: tokio::io::AsyncRead>(reader: R) -> Compat<R> {
+        reader.compat()
+    }
+}
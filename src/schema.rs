@@ -0,0 +1,241 @@
+//! Schema generation independent of the schemars major version.
+//!
+//! The `JsonSchema` bounds throughout the crate come from whichever schemars the
+//! `schemars-1` (default) or `schemars-0_8` feature selects. Derive through the
+//! `semantic_query::schemars` re-export, or depend on the same major version, so your
+//! types satisfy them:
+//!
+//! ```
+//! use semantic_query::schema::{schema_value, JsonSchema};
+//!
+//! #[derive(serde::Deserialize, JsonSchema)]
+//! #[schemars(crate = "semantic_query::schemars")]
+//! struct Invoice { total: f64 }
+//!
+//! assert!(schema_value::<Invoice>()["properties"]["total"].is_object());
+//! ```
+//!
+//! The two versions emit different documents for the same type: 1.x targets draft
+//! 2020-12 and puts shared definitions under `$defs`, 0.8 targets draft-07 and uses
+//! `definitions`. Everything in this crate that reads schemas accepts both.
+//!
+//! Queries take their schemas from `SchemaCache::global()`, so `schema_for!` runs once
+//! per type per process. Warm it at startup to keep that work off the first requests:
+//!
+//! ```
+//! # use semantic_query::schema::{JsonSchema, SchemaCache};
+//! # #[derive(serde::Deserialize, JsonSchema)]
+//! # #[schemars(crate = "semantic_query::schemars")]
+//! # struct Invoice { total: f64 }
+//! SchemaCache::global().warm::<Invoice>().warm::<Vec<Invoice>>();
+//! assert!(SchemaCache::global().contains::<Invoice>());
+//! ```
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde_json::Value;
+
+use crate::core::ResponseMode;
+
+pub use schemars::{schema_for, JsonSchema};
+
+/// The schema type `#[schemars(schema_with = "...")]` functions return
+#[cfg(feature = "schemars-1")]
+pub type Schema = schemars::Schema;
+#[cfg(all(feature = "schemars-0_8", not(feature = "schemars-1")))]
+pub type Schema = schemars::schema::Schema;
+
+/// The generator `#[schemars(schema_with = "...")]` functions receive
+#[cfg(feature = "schemars-1")]
+pub type SchemaGenerator = schemars::SchemaGenerator;
+#[cfg(all(feature = "schemars-0_8", not(feature = "schemars-1")))]
+pub type SchemaGenerator = schemars::gen::SchemaGenerator;
+
+/// `T`'s root JSON Schema as a plain value
+#[must_use]
+pub fn schema_value<T: JsonSchema>() -> Value {
+    serde_json::to_value(schema_for!(T)).unwrap_or(Value::Bool(true))
+}
+
+/// `T`'s root JSON Schema from `SchemaCache::global()`
+#[must_use]
+pub fn cached_schema<T: JsonSchema + 'static>() -> Arc<Value> {
+    SchemaCache::global().get::<T>()
+}
+
+type InvalidationHook = Arc<dyn Fn(Option<TypeId>) + Send + Sync>;
+
+/// Generated schemas, and the prompt guidance rendered from them, keyed by `TypeId`.
+///
+/// Entries never go stale for ordinary derived types. Types whose `JsonSchema` impl
+/// reads runtime state (variants loaded from config, `schema_with` functions consulting
+/// a registry) must be invalidated when that state changes.
+#[derive(Default)]
+pub struct SchemaCache {
+    schemas: RwLock<HashMap<TypeId, Arc<Value>>>,
+    rendered: RwLock<HashMap<(TypeId, ResponseMode), Arc<str>>>,
+    hooks: RwLock<Vec<InvalidationHook>>,
+}
+
+impl std::fmt::Debug for SchemaCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SchemaCache").field("schemas", &self.len()).finish_non_exhaustive()
+    }
+}
+
+impl SchemaCache {
+    /// The process-wide cache every query uses
+    pub fn global() -> &'static SchemaCache {
+        static GLOBAL: OnceLock<SchemaCache> = OnceLock::new();
+        GLOBAL.get_or_init(SchemaCache::default)
+    }
+
+    /// `T`'s schema, generated on first use
+    pub fn get<T: JsonSchema + 'static>(&self) -> Arc<Value> {
+        let key = TypeId::of::<T>();
+        if let Some(schema) = self.schemas.read().ok().and_then(|schemas| schemas.get(&key).cloned()) {
+            return schema;
+        }
+        let schema = Arc::new(schema_value::<T>());
+        match self.schemas.write() {
+            Ok(mut schemas) => schemas.entry(key).or_insert(schema).clone(),
+            Err(_) => schema,
+        }
+    }
+
+    /// Generate `T`'s schema and guidance now rather than on the first query
+    pub fn warm<T: JsonSchema + 'static>(&self) -> &Self {
+        for mode in [ResponseMode::Mixed, ResponseMode::JsonOnly] {
+            self.rendered::<T>(mode);
+        }
+        self
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.schemas.read().is_ok_and(|schemas| schemas.contains_key(&TypeId::of::<T>()))
+    }
+
+    /// Number of types with a cached schema
+    pub fn len(&self) -> usize {
+        self.schemas.read().map_or(0, |schemas| schemas.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop `T`'s schema and guidance; the next query regenerates them
+    pub fn invalidate<T: 'static>(&self) {
+        let key = TypeId::of::<T>();
+        if let Ok(mut schemas) = self.schemas.write() {
+            schemas.remove(&key);
+        }
+        if let Ok(mut rendered) = self.rendered.write() {
+            rendered.retain(|(id, _), _| *id != key);
+        }
+        self.notify(Some(key));
+    }
+
+    /// Drop every entry
+    pub fn clear(&self) {
+        if let Ok(mut schemas) = self.schemas.write() {
+            schemas.clear();
+        }
+        if let Ok(mut rendered) = self.rendered.write() {
+            rendered.clear();
+        }
+        self.notify(None);
+    }
+
+    /// Call `hook` after every invalidation, with the type's id, or `None` after `clear`.
+    /// Use it to drop what you derived from cached schemas (compiled validators, tool
+    /// definitions).
+    pub fn on_invalidate(&self, hook: impl Fn(Option<TypeId>) + Send + Sync + 'static) {
+        if let Ok(mut hooks) = self.hooks.write() {
+            hooks.push(Arc::new(hook));
+        }
+    }
+
+    fn notify(&self, key: Option<TypeId>) {
+        let hooks = self.hooks.read().map(|hooks| hooks.clone()).unwrap_or_default();
+        hooks.iter().for_each(|hook| hook(key));
+    }
+
+    /// Answer-format instructions for `T` in `mode`, rendered once and shared by every
+    /// prompt after that
+    pub(crate) fn rendered<T: JsonSchema + 'static>(&self, mode: ResponseMode) -> Arc<str> {
+        let key = (TypeId::of::<T>(), mode);
+        if let Some(text) = self.rendered.read().ok().and_then(|rendered| rendered.get(&key).cloned()) {
+            return text;
+        }
+        let schema = self.get::<T>();
+        let text: Arc<str> = crate::core::render_instructions(&schema, mode).into();
+        match self.rendered.write() {
+            Ok(mut rendered) => rendered.entry(key).or_insert(text).clone(),
+            Err(_) => text,
+        }
+    }
+}
+
+/// A `Schema` from a JSON Schema document, for `schema_with` functions
+///
+/// # Panics
+/// If `value` is neither an object nor a boolean.
+#[must_use]
+pub fn schema_from_json(value: Value) -> Schema {
+    serde_json::from_value(value).expect("a JSON Schema is an object or a boolean")
+}
+
+/// Whether `schema` describes a recursive type: some `$ref` points back at the root
+/// (`#`) or at a definition that, directly or through others, references itself.
+#[must_use]
+pub fn is_recursive(schema: &Value) -> bool {
+    let mut refs = Vec::new();
+    collect_refs(schema, &mut refs);
+    if refs.iter().any(|r| r == "#") {
+        return true;
+    }
+    let Some(defs) = schema.get("$defs").or_else(|| schema.get("definitions")).and_then(Value::as_object) else {
+        return false;
+    };
+    let edges: std::collections::HashMap<&str, Vec<String>> = defs.iter()
+        .map(|(name, def)| {
+            let mut refs = Vec::new();
+            collect_refs(def, &mut refs);
+            (name.as_str(), refs)
+        })
+        .collect();
+    // A definition is recursive when it can reach itself
+    edges.keys().any(|start| {
+        let mut seen = std::collections::HashSet::new();
+        let mut stack: Vec<&str> = edges[start].iter().filter_map(|r| def_name(r)).collect();
+        while let Some(name) = stack.pop() {
+            if name == *start {
+                return true;
+            }
+            if seen.insert(name) {
+                stack.extend(edges.get(name).into_iter().flatten().filter_map(|r| def_name(r)));
+            }
+        }
+        false
+    })
+}
+
+fn collect_refs(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::Object(obj) => {
+            if let Some(Value::String(reference)) = obj.get("$ref") {
+                out.push(reference.clone());
+            }
+            obj.values().for_each(|v| collect_refs(v, out));
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_refs(v, out)),
+        _ => {}
+    }
+}
+
+fn def_name(reference: &str) -> Option<&str> {
+    reference.strip_prefix("#/$defs/").or_else(|| reference.strip_prefix("#/definitions/"))
+}
@@ -0,0 +1,233 @@
+//! Named, versioned schemas and migrations between versions.
+//!
+//! A `SchemaRegistry` stores JSON Schemas as `name@vN`. Queries can target any stored
+//! version ("invoice@v2", or "invoice" for the latest), and migrations registered per
+//! version step upgrade the extracted data to the latest version before it is
+//! deserialized into the current Rust type:
+//!
+//! ```no_run
+//! # use semantic_query::core::QueryResolver;
+//! # use semantic_query::schema_registry::SchemaRegistry;
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct InvoiceV2 { total: f64 }
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Invoice { total: f64, currency: String }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) -> Result<(), Box<dyn std::error::Error>> {
+//! let registry = SchemaRegistry::new()
+//!     .with_type::<InvoiceV2>("invoice", 2)
+//!     .with_type::<Invoice>("invoice", 3)
+//!     .with_migration("invoice", 2, |mut v| {
+//!         v["currency"] = "EUR".into();
+//!         Ok(v)
+//!     });
+//! let response = resolver.query_versioned::<Invoice>(&registry, "invoice@v2", "Extract the invoice".into()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use crate::schema::schema_value;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver, ResponseItem};
+use crate::error::{QueryResolverError, SchemaRegistryError};
+use crate::streaming::TextContent;
+
+/// Upgrades data from one version to the next registered version
+pub type Migration = Arc<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+/// `name@vN` (or `name@N`); without a version, the latest registered one
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SchemaRef {
+    pub name: String,
+    pub version: Option<u32>,
+}
+
+impl FromStr for SchemaRef {
+    type Err = SchemaRegistryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SchemaRegistryError::InvalidReference(s.to_string());
+        let (name, version) = match s.split_once('@') {
+            Some((name, version)) => {
+                let digits = version.strip_prefix('v').unwrap_or(version);
+                (name, Some(digits.parse().map_err(|_| invalid())?))
+            }
+            None => (s, None),
+        };
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { name: name.to_string(), version })
+    }
+}
+
+impl fmt::Display for SchemaRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some(version) => write!(f, "{}@v{version}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// One stored schema, the unit `SchemaRegistry::records` exports and `from_records` loads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaRecord {
+    pub name: String,
+    pub version: u32,
+    pub schema: Value,
+}
+
+#[derive(Default, Clone)]
+struct Versions {
+    schemas: BTreeMap<u32, Value>,
+    /// Keyed by the version migrated from
+    migrations: BTreeMap<u32, Migration>,
+}
+
+/// Named, versioned JSON Schemas with migrations between consecutive versions
+#[derive(Default, Clone)]
+pub struct SchemaRegistry {
+    entries: BTreeMap<String, Versions>,
+}
+
+impl fmt::Debug for SchemaRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, versions) in &self.entries {
+            map.entry(name, &versions.schemas.keys().collect::<Vec<_>>());
+        }
+        map.finish()
+    }
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load schemas exported by `records`
+    pub fn from_records(records: impl IntoIterator<Item = SchemaRecord>) -> Self {
+        let mut registry = Self::new();
+        for record in records {
+            registry.register(record.name, record.version, record.schema);
+        }
+        registry
+    }
+
+    /// Store `schema` as `name@v{version}`, replacing any schema already there
+    pub fn register(&mut self, name: impl Into<String>, version: u32, schema: Value) {
+        self.entries.entry(name.into()).or_default().schemas.insert(version, schema);
+    }
+
+    /// Store `T`'s JSON Schema as `name@v{version}`
+    #[must_use]
+    pub fn with_type<T: JsonSchema>(mut self, name: impl Into<String>, version: u32) -> Self {
+        let schema = schema_value::<T>();
+        self.register(name, version, schema);
+        self
+    }
+
+    #[must_use]
+    pub fn with_schema(mut self, name: impl Into<String>, version: u32, schema: Value) -> Self {
+        self.register(name, version, schema);
+        self
+    }
+
+    /// Upgrade data extracted against `name@v{from}` to the next registered version
+    #[must_use]
+    pub fn with_migration<F>(mut self, name: impl Into<String>, from: u32, migration: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.entries.entry(name.into()).or_default().migrations.insert(from, Arc::new(migration));
+        self
+    }
+
+    /// Registered versions of `name`, oldest first
+    pub fn versions(&self, name: &str) -> Vec<u32> {
+        self.entries.get(name).map(|v| v.schemas.keys().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn latest(&self, name: &str) -> Option<u32> {
+        self.entries.get(name).and_then(|v| v.schemas.keys().next_back().copied())
+    }
+
+    /// The version and schema a reference such as `"invoice@v2"` points at
+    pub fn resolve(&self, reference: &str) -> Result<(u32, &Value), SchemaRegistryError> {
+        let SchemaRef { name, version } = reference.parse()?;
+        let versions = self.entries.get(&name).filter(|v| !v.schemas.is_empty())
+            .ok_or_else(|| SchemaRegistryError::UnknownSchema(name.clone()))?;
+        match version {
+            Some(version) => versions.schemas.get(&version).map(|schema| (version, schema))
+                .ok_or(SchemaRegistryError::UnknownVersion { name, version }),
+            None => versions.schemas.iter().next_back().map(|(v, schema)| (*v, schema))
+                .ok_or(SchemaRegistryError::UnknownSchema(name)),
+        }
+    }
+
+    /// Run the migrations that take `value` from `name@v{from}` to the latest version
+    pub fn migrate(&self, name: &str, from: u32, value: Value) -> Result<Value, SchemaRegistryError> {
+        let versions = self.entries.get(name).ok_or_else(|| SchemaRegistryError::UnknownSchema(name.to_string()))?;
+        if !versions.schemas.contains_key(&from) {
+            return Err(SchemaRegistryError::UnknownVersion { name: name.to_string(), version: from });
+        }
+        let steps = versions.schemas.range(from..).map(|(v, _)| *v).collect::<Vec<_>>();
+        steps.windows(2).try_fold(value, |value, step| {
+            let migration = versions.migrations.get(&step[0])
+                .ok_or_else(|| SchemaRegistryError::MissingMigration { name: name.to_string(), from: step[0] })?;
+            migration(value).map_err(|message| SchemaRegistryError::Migration { name: name.to_string(), from: step[0], message })
+        })
+    }
+
+    /// Every stored schema, for persisting the registry
+    pub fn records(&self) -> Vec<SchemaRecord> {
+        self.entries.iter().flat_map(|(name, versions)| {
+            versions.schemas.iter().map(|(version, schema)| SchemaRecord {
+                name: name.clone(),
+                version: *version,
+                schema: schema.clone(),
+            })
+        }).collect()
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Query against a registered schema version (`"invoice@v2"`; `"invoice"` for the
+    /// latest), migrating each extracted item to the latest version and deserializing
+    /// it into `T`, which should match the latest version.
+    ///
+    /// Items that still do not deserialize after migration are kept as text, as
+    /// `query_mixed` does; a failing or missing migration is an error.
+    pub async fn query_versioned<T>(&self, registry: &SchemaRegistry, reference: &str, prompt: String) -> Result<ParsedResponse<T>, QueryResolverError>
+    where
+        T: DeserializeOwned,
+    {
+        let (version, schema) = registry.resolve(reference)?;
+        let SchemaRef { name, .. } = reference.parse()?;
+        let response = self.query_schema(prompt, schema).await?;
+
+        let mut items = Vec::with_capacity(response.items.len());
+        for item in response.items {
+            items.push(match item {
+                ResponseItem::Data { data, original_text, confidence_map } => {
+                    let migrated = registry.migrate(&name, version, data)?;
+                    match serde_json::from_value::<T>(migrated) {
+                        Ok(data) => ResponseItem::Data { data, original_text, confidence_map },
+                        Err(_) => ResponseItem::Text(TextContent { text: original_text, block: None }),
+                    }
+                }
+                ResponseItem::Text(text) => ResponseItem::Text(text),
+            });
+        }
+        Ok(ParsedResponse { items, safety: response.safety, truncated: response.truncated, error: response.error, cost: response.cost })
+    }
+}
@@ -0,0 +1,293 @@
+//! Pluggable secret resolution for provider API keys.
+//!
+//! `KeyFromEnv` only looks at the process environment. A `SecretProvider` can
+//! resolve the same key names (`ANTHROPIC_API_KEY`, `DEEPSEEK_API_KEY`, ...) from
+//! other stores, asynchronously, so keys can be fetched at startup and re-fetched
+//! later to rotate them without restarting:
+//!
+//! ```no_run
+//! use semantic_query::clients::flexible::{FlexibleClient, ClientType};
+//! use semantic_query::secrets::{ChainedSecrets, EnvSecrets, FileSecrets};
+//!
+//! # async fn demo() -> Result<(), semantic_query::error::AIError> {
+//! let secrets = ChainedSecrets::new()
+//!     .with(EnvSecrets)
+//!     .with(FileSecrets::new("/run/secrets"));
+//! let client = FlexibleClient::from_secrets(ClientType::DeepSeek, &secrets).await?;
+//! // ...later, after the key was rotated in the store
+//! client.rotate_keys(ClientType::DeepSeek, &secrets).await?;
+//! # Ok(()) }
+//! ```
+
+use crate::config::SemanticQueryConfig;
+use crate::error::AIError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+/// Source of named secrets such as provider API keys.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait SecretProvider: Send + Sync + Debug {
+    /// Fetch the secret called `name`. `Ok(None)` means the store has no such secret;
+    /// errors are reserved for failures talking to the store.
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError>;
+}
+
+/// Reads secrets from the process environment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvSecrets;
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for EnvSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        Ok(std::env::var(name).ok().filter(|v| !v.is_empty()))
+    }
+}
+
+/// Reads secrets from a dotenv file without modifying the process environment.
+/// The file is re-read on every lookup so edits are picked up on rotation.
+#[derive(Debug, Clone)]
+pub struct DotenvSecrets {
+    path: PathBuf,
+}
+
+impl DotenvSecrets {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Default for DotenvSecrets {
+    fn default() -> Self {
+        Self::new(".env")
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for DotenvSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        let iter = match dotenvy::from_path_iter(&self.path) {
+            Ok(iter) => iter,
+            Err(e) if e.not_found() => return Ok(None),
+            Err(e) => return Err(AIError::Configuration(format!("Failed to read {}: {}", self.path.display(), e))),
+        };
+        for item in iter {
+            let (key, value) = item
+                .map_err(|e| AIError::Configuration(format!("Invalid line in {}: {}", self.path.display(), e)))?;
+            if key == name {
+                return Ok(Some(value).filter(|v| !v.is_empty()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Reads each secret from a file named after it inside a directory, e.g.
+/// `/run/secrets/DEEPSEEK_API_KEY` as mounted by Docker or Kubernetes.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileSecrets {
+    dir: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl SecretProvider for FileSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        let path = self.dir.join(name);
+        match crate::runtime::fs::read_to_string(&path).await {
+            Ok(content) => Ok(Some(content.trim().to_string()).filter(|v| !v.is_empty())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AIError::Configuration(format!("Failed to read {}: {}", path.display(), e))),
+        }
+    }
+}
+
+/// Fixed in-memory secrets, mainly for tests.
+#[derive(Debug, Clone, Default)]
+pub struct StaticSecrets {
+    values: HashMap<String, String>,
+}
+
+impl StaticSecrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.values.insert(name.into(), value.into());
+        self
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for StaticSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        Ok(self.values.get(name).cloned())
+    }
+}
+
+/// Tries each provider in order and returns the first secret found.
+#[derive(Debug, Default)]
+pub struct ChainedSecrets {
+    providers: Vec<Box<dyn SecretProvider>>,
+}
+
+impl ChainedSecrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for ChainedSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        for provider in &self.providers {
+            if let Some(value) = provider.get_secret(name).await? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Reads secrets from the OS keychain (macOS Keychain, Windows Credential Manager,
+/// Secret Service on Linux). Entries are stored under `service` with the secret
+/// name as the account.
+#[cfg(feature = "keychain")]
+#[derive(Debug, Clone)]
+pub struct KeychainSecrets {
+    service: String,
+}
+
+#[cfg(feature = "keychain")]
+impl KeychainSecrets {
+    pub fn new(service: impl Into<String>) -> Self {
+        Self { service: service.into() }
+    }
+}
+
+#[cfg(feature = "keychain")]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for KeychainSecrets {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        let service = self.service.clone();
+        let name = name.to_string();
+        // Keychain APIs are blocking
+        crate::runtime::spawn_blocking(move || {
+            let entry = keyring::Entry::new(&service, &name)
+                .map_err(|e| AIError::Configuration(format!("Keychain error: {e}")))?;
+            match entry.get_password() {
+                Ok(value) => Ok(Some(value)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(AIError::Configuration(format!("Keychain error: {e}"))),
+            }
+        })
+        .await
+    }
+}
+
+/// Reads secrets from AWS Secrets Manager. `prefix` is prepended to the key name,
+/// e.g. `prod/semantic-query/` + `DEEPSEEK_API_KEY`.
+#[cfg(feature = "aws-secrets")]
+#[derive(Debug, Clone)]
+pub struct AwsSecretsManager {
+    client: aws_sdk_secretsmanager::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "aws-secrets")]
+impl AwsSecretsManager {
+    /// Build from the default AWS configuration chain
+    pub async fn from_env(prefix: impl Into<String>) -> Self {
+        let aws_cfg = aws_config::load_from_env().await;
+        Self { client: aws_sdk_secretsmanager::Client::new(&aws_cfg), prefix: prefix.into() }
+    }
+}
+
+#[cfg(feature = "aws-secrets")]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for AwsSecretsManager {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        let id = format!("{}{}", self.prefix, name);
+        match self.client.get_secret_value().secret_id(&id).send().await {
+            Ok(out) => Ok(out.secret_string().map(str::to_string)),
+            Err(e) if e.as_service_error().map_or(false, |se| se.is_resource_not_found_exception()) => Ok(None),
+            Err(e) => Err(AIError::Configuration(format!("Secrets Manager error for {id}: {e}"))),
+        }
+    }
+}
+
+/// Reads secrets from AWS Systems Manager Parameter Store (decrypting SecureString
+/// parameters). `prefix` is prepended to the key name, e.g. `/semantic-query/`.
+#[cfg(feature = "aws-secrets")]
+#[derive(Debug, Clone)]
+pub struct AwsSsmParameters {
+    client: aws_sdk_ssm::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "aws-secrets")]
+impl AwsSsmParameters {
+    /// Build from the default AWS configuration chain
+    pub async fn from_env(prefix: impl Into<String>) -> Self {
+        let aws_cfg = aws_config::load_from_env().await;
+        Self { client: aws_sdk_ssm::Client::new(&aws_cfg), prefix: prefix.into() }
+    }
+}
+
+#[cfg(feature = "aws-secrets")]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SecretProvider for AwsSsmParameters {
+    async fn get_secret(&self, name: &str) -> Result<Option<String>, AIError> {
+        let id = format!("{}{}", self.prefix, name);
+        match self.client.get_parameter().name(&id).with_decryption(true).send().await {
+            Ok(out) => Ok(out.parameter().and_then(|p| p.value()).map(str::to_string)),
+            Err(e) if e.as_service_error().map_or(false, |se| se.is_parameter_not_found()) => Ok(None),
+            Err(e) => Err(AIError::Configuration(format!("SSM error for {id}: {e}"))),
+        }
+    }
+}
+
+impl SemanticQueryConfig {
+    /// Fill provider API keys from `provider`. Secrets found in the store replace
+    /// values from the file/environment; missing ones leave them untouched.
+    pub async fn resolve_secrets(&mut self, provider: &dyn SecretProvider) -> Result<(), AIError> {
+        let slots = [
+            ("ANTHROPIC_API_KEY", &mut self.anthropic.api_key),
+            ("DEEPSEEK_API_KEY", &mut self.deepseek.api_key),
+            ("OPENAI_API_KEY", &mut self.openai.api_key),
+            ("AZURE_OPENAI_API_KEY", &mut self.azure.api_key),
+            ("OPENAI_COMPAT_API_KEY", &mut self.openai_compatible.api_key),
+        ];
+        for (name, slot) in slots {
+            if let Some(value) = provider.get_secret(name).await? {
+                *slot = Some(value);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+//! Backwards-compatible name for `crate::streaming`.
+//!
+//! `semantic` and `streaming` used to carry near-identical copies of the stream item
+//! types (`SemanticItem` vs `StreamItem`). `streaming` is now the single implementation;
+//! this module re-exports it so existing `semantic_query::semantic::...` paths keep working.
+
+pub use crate::streaming::*;
+
+/// Former name of `StreamItem`
+pub type SemanticItem<T> = StreamItem<T>;
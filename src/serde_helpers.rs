@@ -0,0 +1,423 @@
+//! Lenient serde adapters for field types models often get "almost right".
+//!
+//! Each module works with `#[serde(with = "...")]` and exposes a `schema` function for
+//! `#[schemars(schema_with = "...")]`, so the prompt asks for the canonical format while
+//! extraction still accepts common variants:
+//!
+//! ```
+//! use chrono::{DateTime, NaiveDate, Utc};
+//! use schemars::JsonSchema;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, JsonSchema)]
+//! struct Invoice {
+//!     #[serde(with = "semantic_query::serde_helpers::date")]
+//!     #[schemars(schema_with = "semantic_query::serde_helpers::date::schema")]
+//!     due: NaiveDate, // "2024-01-03", "Jan 3, 2024", "3rd January 2024", "01/03/2024"
+//!     #[serde(with = "semantic_query::serde_helpers::datetime")]
+//!     #[schemars(schema_with = "semantic_query::serde_helpers::datetime::schema")]
+//!     issued: DateTime<Utc>, // RFC 3339, "2024-01-03 10:00", unix seconds
+//!     #[serde(with = "semantic_query::serde_helpers::number")]
+//!     #[schemars(with = "f64")]
+//!     total: f64, // 12.5, "12.5", "$1,234.50"
+//! }
+//! ```
+//!
+//! schemars reads `serde(with)` as a type, so every adapted field also needs a
+//! `schemars(schema_with)` or `schemars(with)` attribute. `uuid` is available with the
+//! `uuid` feature.
+//!
+//! `lenient_enum` (also at `semantic_query::lenient_enum`) matches unit enum variants
+//! regardless of case and separators, and by their `#[serde(alias)]`es in any case:
+//!
+//! ```
+//! use schemars::JsonSchema;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, JsonSchema)]
+//! #[serde(rename_all = "snake_case")]
+//! enum Priority { Low, #[serde(alias = "urgent")] High, InProgress }
+//!
+//! #[derive(Deserialize, JsonSchema)]
+//! struct Ticket {
+//!     #[serde(with = "semantic_query::lenient_enum")]
+//!     #[schemars(schema_with = "semantic_query::lenient_enum::schema::<Priority>")]
+//!     priority: Priority, // "high", "HIGH", "High", "URGENT"
+//! }
+//! ```
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use crate::schema::{schema_from_json, schema_value, JsonSchema, Schema, SchemaGenerator};
+use serde::de::DeserializeOwned;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Date layouts tried in order after ordinal suffixes are removed. `%B` also accepts
+/// abbreviated month names; all-numeric slashes are read month-first.
+const DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d.%m.%Y", "%B %d, %Y", "%B %d %Y", "%d %B %Y", "%d %B, %Y",
+    "%d-%B-%Y", "%A, %B %d, %Y", "%A %d %B %Y",
+];
+
+/// Naive date-time layouts, read as UTC
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M", "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+];
+
+/// Parse a calendar date in any of the common layouts models produce
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let cleaned = strip_ordinals(text.trim());
+    DATE_FORMATS.iter()
+        .find_map(|format| NaiveDate::parse_from_str(&cleaned, format).ok())
+        .or_else(|| parse_datetime(text).map(|dt| dt.date_naive()))
+}
+
+/// Parse a timestamp: RFC 3339 / RFC 2822, common naive layouts (as UTC), or a bare date
+/// (midnight UTC)
+pub fn parse_datetime(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Some(dt) = DATETIME_FORMATS.iter().find_map(|format| NaiveDateTime::parse_from_str(text, format).ok()) {
+        return Some(dt.and_utc());
+    }
+    let cleaned = strip_ordinals(text);
+    DATE_FORMATS.iter()
+        .find_map(|format| NaiveDate::parse_from_str(&cleaned, format).ok())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+/// Parse a number written as a JSON string: surrounding whitespace, currency symbols,
+/// thousands separators (`,` `_` and spaces) and a trailing `.0` on integers are tolerated
+pub fn parse_number<T: FromStr>(text: &str) -> Option<T> {
+    let cleaned: String = text.trim()
+        .trim_start_matches(['$', '€', '£', '¥'])
+        .chars()
+        .filter(|c| !matches!(c, ',' | '_' | ' '))
+        .collect();
+    if let Ok(n) = cleaned.parse() {
+        return Some(n);
+    }
+    // "3.0" for an integer field
+    let (whole, fraction) = cleaned.split_once('.')?;
+    if fraction.chars().all(|c| c == '0') {
+        return whole.parse().ok();
+    }
+    None
+}
+
+/// Parse a unit enum variant of `T` from a model's spelling of it.
+///
+/// Tries, in order: `text` as serde reads it; the variant in `T`'s schema that equals
+/// `text` once case and `_`/`-`/space separators are ignored ("In Progress" for
+/// `in_progress`); and `text` recased to lower, UPPER, snake, SCREAMING_SNAKE, kebab and
+/// Pascal case, which reaches `#[serde(alias)]`es declared in one of those cases.
+pub fn parse_enum<T: DeserializeOwned + JsonSchema>(text: &str) -> Option<T> {
+    let from = |s: &str| T::deserialize(Value::String(s.to_string())).ok();
+    let text = text.trim();
+    if let Some(value) = from(text) {
+        return Some(value);
+    }
+    let key = variant_key(text);
+    let mut variants = Vec::new();
+    collect_variants(&schema_value::<T>(), &mut variants);
+    if let Some(value) = variants.iter().filter(|v| variant_key(v) == key).find_map(|v| from(v)) {
+        return Some(value);
+    }
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let pascal: String = words.iter()
+        .map(|w| w.chars().next().map(|c| c.to_uppercase().chain(w.chars().skip(1)).collect::<String>()).unwrap_or_default())
+        .collect();
+    [text.to_lowercase(), text.to_uppercase(), words.join("_"), words.join("_").to_uppercase(), words.join("-"), pascal]
+        .iter()
+        .find_map(|candidate| from(candidate))
+}
+
+/// Lowercase alphanumerics only, so `IN_PROGRESS`, `in-progress` and `InProgress` agree
+fn variant_key(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// String variants of an enum schema: `enum` lists and `const`s, including those inside
+/// `oneOf`/`anyOf` (documented variants)
+fn collect_variants(schema: &Value, out: &mut Vec<String>) {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        out.extend(values.iter().filter_map(Value::as_str).map(str::to_string));
+    }
+    if let Some(Value::String(value)) = schema.get("const") {
+        out.push(value.clone());
+    }
+    for key in ["oneOf", "anyOf"] {
+        for variant in schema.get(key).and_then(Value::as_array).into_iter().flatten() {
+            collect_variants(variant, out);
+        }
+    }
+}
+
+/// "January 3rd, 2024" -> "January 3, 2024"
+fn strip_ordinals(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        out.push(c);
+        if !c.is_ascii_digit() {
+            continue;
+        }
+        let rest = &text[i + c.len_utf8()..];
+        let is_ordinal = match (rest.get(..2), rest.get(2..)) {
+            (Some(suffix), Some(after)) => {
+                ["st", "nd", "rd", "th"].iter().any(|s| suffix.eq_ignore_ascii_case(s))
+                    && !after.starts_with(|c: char| c.is_alphabetic())
+            }
+            _ => false,
+        };
+        if is_ordinal {
+            chars.next();
+            chars.next();
+        }
+    }
+    out
+}
+
+fn expected<E: de::Error>(what: &str, value: &Value) -> E {
+    E::custom(format!("expected {what}, found {value}"))
+}
+
+/// `NaiveDate` as `YYYY-MM-DD`, accepting the layouts of `parse_date`
+pub mod date {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format("%Y-%m-%d"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        value.as_str().and_then(parse_date).ok_or_else(|| expected("a date", &value))
+    }
+
+    pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+        schema_from_json(serde_json::json!({ "type": "string", "format": "date", "description": "Date as YYYY-MM-DD" }))
+    }
+
+    /// `Option<NaiveDate>`; `null`, a missing field (with `#[serde(default)]`) and `""` are `None`
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(date: &Option<NaiveDate>, serializer: S) -> Result<S::Ok, S::Error> {
+            match date {
+                Some(date) => super::serialize(date, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+            match Value::deserialize(deserializer)? {
+                Value::Null => Ok(None),
+                Value::String(s) if s.trim().is_empty() => Ok(None),
+                value => value.as_str().and_then(parse_date).map(Some).ok_or_else(|| expected("a date", &value)),
+            }
+        }
+
+        pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+            schema_from_json(serde_json::json!({ "type": ["string", "null"], "format": "date", "description": "Date as YYYY-MM-DD" }))
+        }
+    }
+}
+
+/// `DateTime<Utc>` as RFC 3339, accepting the layouts of `parse_datetime` and unix seconds
+pub mod datetime {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&dt.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        from_value(&value).ok_or_else(|| expected("a timestamp", &value))
+    }
+
+    pub(super) fn from_value(value: &Value) -> Option<DateTime<Utc>> {
+        match value {
+            Value::String(s) => parse_datetime(s),
+            Value::Number(n) => n.as_i64().and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            _ => None,
+        }
+    }
+
+    pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+        schema_from_json(serde_json::json!({ "type": "string", "format": "date-time", "description": "RFC 3339 timestamp, e.g. 2024-01-03T10:00:00Z" }))
+    }
+
+    /// `Option<DateTime<Utc>>`; `null` and `""` are `None`
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(dt: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match dt {
+                Some(dt) => super::serialize(dt, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+            match Value::deserialize(deserializer)? {
+                Value::Null => Ok(None),
+                Value::String(s) if s.trim().is_empty() => Ok(None),
+                value => super::from_value(&value).map(Some).ok_or_else(|| expected("a timestamp", &value)),
+            }
+        }
+
+        pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+            schema_from_json(serde_json::json!({ "type": ["string", "null"], "format": "date-time", "description": "RFC 3339 timestamp, e.g. 2024-01-03T10:00:00Z" }))
+        }
+    }
+}
+
+/// Any `FromStr` number (integers, floats, `rust_decimal::Decimal`, ...) given as a JSON
+/// number or a string in the forms `parse_number` accepts. Serializes with `T`'s own
+/// `Serialize`. Pair with `#[schemars(with = "f64")]` (or the field's own type), or use
+/// `schema` for types without a `JsonSchema` impl.
+pub mod number {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Serialize>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+    {
+        let value = Value::deserialize(deserializer)?;
+        from_value(&value).ok_or_else(|| expected("a number", &value))
+    }
+
+    pub(super) fn from_value<T: FromStr>(value: &Value) -> Option<T> {
+        match value {
+            // Go through the literal text so decimals keep their digits
+            Value::Number(n) => parse_number(&n.to_string()),
+            Value::String(s) => parse_number(s),
+            _ => None,
+        }
+    }
+
+    pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+        schema_from_json(serde_json::json!({ "type": "number" }))
+    }
+
+    /// `Option<T>`; `null` and `""` are `None`
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer, T: Serialize>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+            value.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: FromStr,
+        {
+            match Value::deserialize(deserializer)? {
+                Value::Null => Ok(None),
+                Value::String(s) if s.trim().is_empty() => Ok(None),
+                value => super::from_value(&value).map(Some).ok_or_else(|| expected("a number", &value)),
+            }
+        }
+
+        pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+            schema_from_json(serde_json::json!({ "type": ["number", "null"] }))
+        }
+    }
+}
+
+/// Unit enums matched case- and separator-insensitively and by alias (see `parse_enum`).
+/// Serialized as serde would; non-string values are passed to `T` unchanged.
+pub mod lenient_enum {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Serialize>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: DeserializeOwned + JsonSchema,
+    {
+        from_value(Value::deserialize(deserializer)?)
+    }
+
+    pub(super) fn from_value<T: DeserializeOwned + JsonSchema, E: de::Error>(value: Value) -> Result<T, E> {
+        match &value {
+            Value::String(s) => parse_enum(s).ok_or_else(|| expected("one of the enum variants", &value)),
+            _ => T::deserialize(value).map_err(E::custom),
+        }
+    }
+
+    /// `T`'s own schema, so the prompt lists the canonical variants
+    pub fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+        generator.subschema_for::<T>()
+    }
+
+    /// `Option<T>`; `null` and `""` are `None`
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer, T: Serialize>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+            value.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: DeserializeOwned + JsonSchema,
+        {
+            match Value::deserialize(deserializer)? {
+                Value::Null => Ok(None),
+                Value::String(s) if s.trim().is_empty() => Ok(None),
+                value => super::from_value(value).map(Some),
+            }
+        }
+
+        pub fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+            generator.subschema_for::<Option<T>>()
+        }
+    }
+}
+
+/// `uuid::Uuid` in hyphenated form, accepting simple, braced and `urn:uuid:` forms and
+/// surrounding whitespace
+#[cfg(feature = "uuid")]
+pub mod uuid {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(id: &::uuid::Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&id.hyphenated())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<::uuid::Uuid, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        value.as_str()
+            .and_then(|s| ::uuid::Uuid::parse_str(s.trim()).ok())
+            .ok_or_else(|| expected("a UUID", &value))
+    }
+
+    pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+        schema_from_json(serde_json::json!({ "type": "string", "format": "uuid" }))
+    }
+}
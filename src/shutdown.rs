@@ -0,0 +1,229 @@
+//! Graceful shutdown for long-running services.
+//!
+//! A `ShutdownHandle` tracks the queries of every resolver built with
+//! `QueryResolver::with_shutdown(handle)`, streams included. `ShutdownHandle::shutdown`
+//! then:
+//!
+//! 1. stops accepting queries: new ones fail with `QueryResolverError::ShuttingDown`;
+//! 2. waits up to `timeout` for the queries in flight to finish, and cancels those still
+//!    running at the deadline (they fail with `QueryResolverError::Cancelled`);
+//! 3. flushes the resolvers' clients (`LowLevelClient::flush`, which `FlexibleClient`
+//!    passes on to `Interceptor::flush`) and runs the hooks added with `on_flush`, e.g.
+//!    to flush a metrics exporter fed by `with_stats_callback`.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::shutdown::ShutdownHandle;
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) {
+//! let shutdown = ShutdownHandle::new();
+//! let resolver = resolver.with_shutdown(shutdown.clone());
+//! // ... serve queries, then on SIGTERM:
+//! let report = shutdown.shutdown(Duration::from_secs(30)).await;
+//! if !report.is_clean() {
+//!     eprintln!("{report:?}");
+//! }
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::core::LowLevelClient;
+use crate::error::QueryResolverError;
+
+type FlushHook = Box<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+/// Tracks in-flight queries and shuts them down; clones share the same state
+#[derive(Clone, Default)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    closing: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    cancel: CancellationToken,
+    clients: Mutex<Vec<Box<dyn LowLevelClient>>>,
+    hooks: Mutex<Vec<FlushHook>>,
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("closing", &self.is_closing())
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+/// What `ShutdownHandle::shutdown` did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Queries still running at the deadline, which were cancelled
+    pub cancelled: usize,
+    /// Flushes that failed, with their errors
+    pub flush_errors: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Every query finished on its own and every flush succeeded
+    pub fn is_clean(&self) -> bool {
+        self.cancelled == 0 && self.flush_errors.is_empty()
+    }
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `shutdown` has been called
+    pub fn is_closing(&self) -> bool {
+        self.inner.closing.load(Ordering::SeqCst)
+    }
+
+    /// Queries (and open streams) running now
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Run `hook` when shutting down, after the queries have finished
+    pub fn on_flush<F, Fut>(&self, hook: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.inner.hooks.lock().unwrap().push(Box::new(move || Box::pin(hook())));
+    }
+
+    /// Flush `client` when shutting down; done by `QueryResolver::with_shutdown`
+    pub(crate) fn register(&self, client: Box<dyn LowLevelClient>) {
+        self.inner.clients.lock().unwrap().push(client);
+    }
+
+    /// Stop accepting queries, wait up to `timeout` for those in flight, cancel the
+    /// rest, and flush. Calling it again only flushes again. On wasm32, which has no
+    /// timer, it waits for every query in flight.
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        self.inner.closing.store(true, Ordering::SeqCst);
+        info!(in_flight = self.in_flight(), ?timeout, "Shutting down");
+
+        let mut report = ShutdownReport::default();
+        if crate::runtime::timeout(timeout, self.drained()).await.is_err() {
+            report.cancelled = self.in_flight();
+            warn!(cancelled = report.cancelled, "Queries still running at the shutdown deadline; cancelling them");
+            self.inner.cancel.cancel();
+        }
+
+        let clients: Vec<_> = self.inner.clients.lock().unwrap().iter().map(|c| c.clone_box()).collect();
+        for client in clients {
+            if let Err(e) = client.flush().await {
+                report.flush_errors.push(e);
+            }
+        }
+        let flushes: Vec<_> = self.inner.hooks.lock().unwrap().iter().map(|hook| hook()).collect();
+        for flush in flushes {
+            if let Err(e) = flush.await {
+                report.flush_errors.push(e);
+            }
+        }
+        for error in &report.flush_errors {
+            warn!(%error, "Flush failed during shutdown");
+        }
+        report
+    }
+
+    /// Wait until no query is in flight
+    async fn drained(&self) {
+        loop {
+            let idle = self.inner.idle.notified();
+            if self.in_flight() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Count a query in flight until the returned guard drops, unless shutting down
+    pub(crate) fn enter(&self) -> Result<InFlight, QueryResolverError> {
+        self.inner.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight { inner: self.inner.clone() };
+        if self.is_closing() {
+            return Err(QueryResolverError::ShuttingDown);
+        }
+        Ok(guard)
+    }
+}
+
+/// One query in flight; dropping it lets a waiting `shutdown` go on
+pub(crate) struct InFlight {
+    inner: Arc<Inner>,
+}
+
+impl InFlight {
+    async fn cancelled(&self) {
+        self.inner.cancel.cancelled().await
+    }
+}
+
+/// Cancelled at the shutdown deadline of `in_flight`'s handle; never without one
+async fn cancelled(in_flight: Option<&InFlight>) {
+    match in_flight {
+        Some(in_flight) => in_flight.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Run `query`, cancelling it if it is still running at the shutdown deadline
+pub(crate) async fn track<R>(in_flight: Option<&InFlight>, query: impl Future<Output = Result<R, QueryResolverError>>) -> Result<R, QueryResolverError> {
+    tokio::select! {
+        biased;
+        _ = cancelled(in_flight) => Err(QueryResolverError::Cancelled),
+        result = query => result,
+    }
+}
+
+/// `stream`, in flight until it ends or is dropped. At the shutdown deadline it
+/// yields `QueryResolverError::Cancelled` and ends.
+pub(crate) fn track_stream<S, I>(in_flight: Option<InFlight>, stream: S) -> impl Stream<Item = Result<I, QueryResolverError>>
+where
+    S: Stream<Item = Result<I, QueryResolverError>>,
+{
+    async_stream::stream! {
+        let mut stream = std::pin::pin!(stream);
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = cancelled(in_flight.as_ref()) => Some(Err(QueryResolverError::Cancelled)),
+                next = stream.next() => next,
+            };
+            let Some(item) = next else { break };
+            let cancelled = matches!(item, Err(QueryResolverError::Cancelled));
+            yield item;
+            if cancelled {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.inner.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Queries running on their own task.
+//!
+//! `QueryResolver::spawn_query` starts a streaming query on a background task and
+//! returns a bounded channel of its items with a `QueryHandle` that resolves to the
+//! collected response. This suits actor systems: forward the receiver into a mailbox or
+//! `select!` loop and keep the handle for the final result.
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::spawn::SpawnOptions;
+//! # use semantic_query::streaming::StreamItem;
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Step { action: String }
+//! # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! # let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default());
+//! let (mut items, handle) = resolver.spawn_query_with::<Step>("Plan the move".into(), SpawnOptions::default().with_capacity(8));
+//! while let Some(item) = items.recv().await {
+//!     if let StreamItem::Data(step) = item { println!("{}", step.action); }
+//! }
+//! let plan = handle.await?;
+//! # Ok(()) }
+//! ```
+//!
+//! The task waits while the channel is full, so a slow consumer slows the query down
+//! rather than buffering without bound. Dropping the receiver keeps the query running
+//! for the handle; dropping the handle detaches the task. `QueryHandle::cancel` (or the
+//! token passed in `SpawnOptions::with_cancellation`) stops it with
+//! `QueryResolverError::Cancelled`. The task keeps the caller's correlation id and
+//! tenant.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::core::{LowLevelClient, ParsedResponse, QueryResolver, StreamCollect};
+use crate::error::QueryResolverError;
+use crate::streaming::StreamItem;
+use crate::{correlation, runtime, tenancy};
+
+/// Default channel capacity for `spawn_query` and the channel helpers in `json_utils`
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 64;
+
+/// Channel capacity and cancellation for `QueryResolver::spawn_query_with`
+#[derive(Debug, Clone)]
+pub struct SpawnOptions {
+    /// Items buffered before the task waits for the receiver; at least 1
+    pub capacity: usize,
+    /// Cancels the query when cancelled, e.g. an actor's shutdown token
+    pub cancel: Option<CancellationToken>,
+}
+
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self { capacity: DEFAULT_CHANNEL_CAPACITY, cancel: None }
+    }
+}
+
+impl SpawnOptions {
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+}
+
+/// The final result of a spawned query; await it like a `JoinHandle`.
+///
+/// Dropping the handle does not stop the query; call `cancel` for that.
+#[derive(Debug)]
+pub struct QueryHandle<T> {
+    result: oneshot::Receiver<Result<ParsedResponse<T>, QueryResolverError>>,
+    cancel: CancellationToken,
+}
+
+impl<T> QueryHandle<T> {
+    /// Stop the query; the handle then resolves to `QueryResolverError::Cancelled`
+    /// unless it had already finished
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Whether the query has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        !self.result.is_empty() || self.result.is_terminated()
+    }
+}
+
+impl<T> Future for QueryHandle<T> {
+    type Output = Result<ParsedResponse<T>, QueryResolverError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result).poll(cx).map(|result| result.unwrap_or_else(|_| Err(
+            QueryResolverError::StreamInterrupted("query task stopped before the response completed".to_string()),
+        )))
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Run `query_stream_collect::<T>()` on a background task with the default
+    /// `SpawnOptions`; see the module docs
+    pub fn spawn_query<T>(&self, prompt: String) -> (mpsc::Receiver<StreamItem<T>>, QueryHandle<T>)
+    where
+        T: DeserializeOwned + JsonSchema + serde::Serialize + Clone + Send + 'static,
+    {
+        self.spawn_query_with(prompt, SpawnOptions::default())
+    }
+
+    /// `spawn_query` with a channel capacity and an outside cancellation token.
+    ///
+    /// The channel carries the stream's items; a stream error ends it and is returned
+    /// by the handle. Must be called inside the runtime `runtime::spawn` uses.
+    pub fn spawn_query_with<T>(&self, prompt: String, options: SpawnOptions) -> (mpsc::Receiver<StreamItem<T>>, QueryHandle<T>)
+    where
+        T: DeserializeOwned + JsonSchema + serde::Serialize + Clone + Send + 'static,
+    {
+        let (items, receiver) = mpsc::channel(options.capacity.max(1));
+        let (done, result) = oneshot::channel();
+        let cancel = options.cancel.map_or_else(CancellationToken::new, |token| token.child_token());
+        let resolver = self.with_client(self.client().clone_box());
+
+        let token = cancel.clone();
+        let task = correlation::scope(correlation::current().unwrap_or_default(), async move {
+            let run = async {
+                let StreamCollect { mut stream, response } = resolver.query_stream_collect::<T>(prompt).await?;
+                let mut failed = None;
+                while let Some(next) = stream.next().await {
+                    match next {
+                        // A dropped receiver stops the forwarding, not the query
+                        Ok(item) => { let _ = items.send(item).await; }
+                        Err(e) => failed = Some(e),
+                    }
+                }
+                match (response.await, failed) {
+                    // Report the stream's own error rather than `StreamInterrupted`
+                    (Err(_), Some(e)) => Err(e),
+                    (result, _) => result,
+                }
+            };
+            let result = tokio::select! {
+                biased;
+                _ = token.cancelled() => Err(QueryResolverError::Cancelled),
+                result = run => result,
+            };
+            debug!(ok = result.is_ok(), "Spawned query finished");
+            let _ = done.send(result);
+            // Closed only now, so the handle is finished once the receiver sees the end
+            drop(items);
+        });
+        match tenancy::current() {
+            Some(tenant) => runtime::spawn(tenancy::scope(tenant, task)),
+            None => runtime::spawn(task),
+        }
+        (receiver, QueryHandle { result, cancel })
+    }
+}
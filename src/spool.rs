@@ -0,0 +1,273 @@
+//! Replies too large to keep in memory.
+//!
+//! `QueryResolver::query_spooled` writes a reply to a temp file once it grows past
+//! `SpoolConfig::threshold`, extracts data by scanning the file, and returns the reply
+//! as a `SpooledText`: a handle that reads it back from disk on demand and removes the
+//! file when dropped. Replies under the threshold stay in memory behind the same API.
+//!
+//! ```no_run
+//! # use std::io::BufRead;
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! # use semantic_query::spool::SpoolConfig;
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Row { id: u64 }
+//! # async fn demo() -> Result<(), Box<dyn std::error::Error>> {
+//! let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default())
+//!     .with_spool(SpoolConfig::new(256 * 1024).in_dir("/var/tmp"));
+//! let response = resolver.query_spooled::<Row>("Export every row".into()).await?;
+//! println!("{} rows from a {} byte reply", response.data.len(), response.raw.len());
+//! for line in response.raw.reader()?.lines() {
+//!     let _line = line?;
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::de::DeserializeOwned;
+use tracing::{debug, warn};
+
+use crate::json_utils::{self, DetectionScope, JsonStreamParser, ObjCoords, ParseLimits};
+
+/// Default `SpoolConfig::threshold`: 1 MiB
+pub const DEFAULT_SPOOL_THRESHOLD: usize = 1024 * 1024;
+
+/// Bytes read per step when scanning a spooled reply
+const SCAN_CHUNK: usize = 64 * 1024;
+
+/// When and where replies are spooled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpoolConfig {
+    /// Replies longer than this many bytes are moved to a temp file
+    pub threshold: usize,
+    /// Directory for the temp files; `std::env::temp_dir()` when unset
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self { threshold: DEFAULT_SPOOL_THRESHOLD, dir: None }
+    }
+}
+
+impl SpoolConfig {
+    pub fn new(threshold: usize) -> Self {
+        Self { threshold, ..Self::default() }
+    }
+
+    #[must_use]
+    pub fn in_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+}
+
+/// A spool file, removed when dropped
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create(dir: &Path) -> io::Result<(Self, File)> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        loop {
+            let name = format!("semantic-query-{}-{}.spool", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+            let path = dir.join(name);
+            match OpenOptions::new().read(true).write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((Self { path }, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            debug!(path = %self.path.display(), error = %e, "Could not remove spool file");
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Repr {
+    Memory(String),
+    File(TempFile),
+}
+
+/// The text of a reply, in memory or in a temp file (see the module docs)
+#[derive(Debug)]
+pub struct SpooledText {
+    repr: Repr,
+    len: usize,
+}
+
+impl SpooledText {
+    /// `text`, moved to a temp file when it is longer than `config.threshold`
+    pub fn from_string(text: String, config: &SpoolConfig) -> io::Result<Self> {
+        if text.len() <= config.threshold {
+            return Ok(Self { len: text.len(), repr: Repr::Memory(text) });
+        }
+        let mut spooler = Spooler::new(SpoolConfig { threshold: 0, ..config.clone() });
+        spooler.push(&text)?;
+        drop(text);
+        spooler.finish()
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the text lives in a temp file
+    pub fn is_spooled(&self) -> bool {
+        matches!(self.repr, Repr::File(_))
+    }
+
+    /// The temp file holding the text, if spooled. It is removed when `self` drops;
+    /// copy it to keep it.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.repr {
+            Repr::File(file) => Some(&file.path),
+            Repr::Memory(_) => None,
+        }
+    }
+
+    /// The text, read from the start
+    pub fn reader(&self) -> io::Result<Box<dyn BufRead + Send + '_>> {
+        Ok(match &self.repr {
+            Repr::Memory(text) => Box::new(Cursor::new(text.as_bytes())),
+            Repr::File(file) => Box::new(BufReader::new(File::open(&file.path)?)),
+        })
+    }
+
+    /// The bytes in `range`, which must fall on character boundaries
+    pub fn read_range(&self, range: Range<usize>) -> io::Result<String> {
+        if range.start > range.end || range.end > self.len {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("range {range:?} out of bounds for {} bytes", self.len)));
+        }
+        match &self.repr {
+            Repr::Memory(text) => text.get(range.clone())
+                .map(str::to_string)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("range {range:?} splits a character"))),
+            Repr::File(file) => {
+                let mut file = File::open(&file.path)?;
+                file.seek(SeekFrom::Start(range.start as u64))?;
+                let mut bytes = vec![0; range.len()];
+                file.read_exact(&mut bytes)?;
+                String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+        }
+    }
+
+    /// The whole text in memory; defeats the purpose for large replies
+    pub fn read_to_string(&self) -> io::Result<String> {
+        self.read_range(0..self.len)
+    }
+
+    /// Root JSON structures within `scope`, scanned in chunks; a structure that exceeds
+    /// `limits` is dropped (with a warning) like `json_utils::find_json_structures`
+    pub fn find_json(&self, limits: ParseLimits, scope: &DetectionScope) -> io::Result<Vec<ObjCoords>> {
+        let mut reader = self.reader()?;
+        let mut parser = JsonStreamParser::with_limits(limits).with_scope(scope.clone());
+        let mut roots = Vec::new();
+        let mut buf = vec![0; SCAN_CHUNK];
+        // Bytes of a character split by the previous read, kept at the start of `buf`
+        let mut carry = 0;
+        loop {
+            let read = reader.read(&mut buf[carry..])?;
+            if read == 0 {
+                break;
+            }
+            let filled = carry + read;
+            let text = match std::str::from_utf8(&buf[..filled]) {
+                Ok(text) => text,
+                Err(e) if e.error_len().is_none() => std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap_or_default(),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            let valid = text.len();
+            roots.extend(parser.feed(text));
+            buf.copy_within(valid..filled, 0);
+            carry = filled - valid;
+        }
+        if let Some(err) = parser.take_error() {
+            warn!(target = "semantic_query::json_stream", error = %err, "dropped JSON structure exceeding parse limits");
+        }
+        Ok(roots)
+    }
+
+    /// Every `T` in the text, found like `json_utils::extract_all` but reading one root
+    /// structure into memory at a time
+    pub fn extract_all<T: DeserializeOwned>(&self, limits: ParseLimits, scope: &DetectionScope) -> io::Result<Vec<T>> {
+        let mut out = Vec::new();
+        for root in self.find_json(limits, scope)? {
+            let structure = self.read_range(root.start..root.end + 1)?;
+            out.extend(json_utils::extract_all::<T>(&structure));
+        }
+        Ok(out)
+    }
+}
+
+/// Builds a `SpooledText` from chunks, switching to a temp file once they pass the
+/// threshold
+#[derive(Debug)]
+pub struct Spooler {
+    config: SpoolConfig,
+    memory: String,
+    file: Option<(TempFile, BufWriter<File>)>,
+    len: usize,
+}
+
+impl Spooler {
+    pub fn new(config: SpoolConfig) -> Self {
+        Self { config, memory: String::new(), file: None, len: 0 }
+    }
+
+    pub fn push(&mut self, chunk: &str) -> io::Result<()> {
+        self.len += chunk.len();
+        if let Some((_, writer)) = &mut self.file {
+            return writer.write_all(chunk.as_bytes());
+        }
+        self.memory.push_str(chunk);
+        if self.memory.len() > self.config.threshold {
+            let (file, handle) = TempFile::create(&self.config.dir())?;
+            debug!(path = %file.path.display(), bytes = self.memory.len(), "Spooling reply to disk");
+            let mut writer = BufWriter::new(handle);
+            writer.write_all(std::mem::take(&mut self.memory).as_bytes())?;
+            self.file = Some((file, writer));
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<SpooledText> {
+        let repr = match self.file {
+            Some((file, mut writer)) => {
+                writer.flush()?;
+                Repr::File(file)
+            }
+            None => Repr::Memory(self.memory),
+        };
+        Ok(SpooledText { repr, len: self.len })
+    }
+}
+
+/// Data extracted by `QueryResolver::query_spooled`, with the reply it came from
+#[derive(Debug)]
+pub struct SpooledResponse<T> {
+    pub data: Vec<T>,
+    pub raw: SpooledText,
+}
@@ -0,0 +1,427 @@
+//! Per-query performance metrics.
+//!
+//! Register a callback with `QueryResolver::with_stats_callback` to receive a `QueryStats`
+//! after every query, streaming or not, e.g. to feed latency histograms:
+//!
+//! ```no_run
+//! # use semantic_query::core::{QueryResolver, RetryConfig};
+//! let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default())
+//!     .with_stats_callback(|stats| {
+//!         tracing::info!(op = %stats.operation, ttft = ?stats.time_to_first_token, total = ?stats.total, "query stats");
+//!     });
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_stream::stream;
+use chrono::{DateTime, Utc};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::confidence::TokenLogprob;
+use crate::audit::{content_hash, schema_id, QueryRecord, RecordOutcome, RecordUsage};
+use crate::error::QueryResolverError;
+use crate::prompt::estimate_tokens;
+use crate::streaming::{FinishReason, StreamItem, Timed};
+
+/// Timing and volume of one query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryStats {
+    /// Resolver method that ran, e.g. `query`, `query_mixed` or `stream_query`
+    pub operation: String,
+    /// Time until the first token arrived; for non-streaming queries, the first reply
+    pub time_to_first_token: Option<Duration>,
+    /// Time until the first data item was available
+    pub time_to_first_data: Option<Duration>,
+    /// Time from sending the prompt to the final result (or error)
+    pub total: Duration,
+    /// Correction requests sent after the first reply
+    pub retries: usize,
+    /// Bytes of model text received, across retries
+    pub bytes_received: usize,
+    /// Data items returned or emitted
+    pub items: usize,
+    /// Whether the query produced a result rather than an error
+    pub succeeded: bool,
+    /// Correlation id the query ran under (see `correlation`)
+    pub correlation_id: Option<String>,
+    /// Model version the provider reported answering with (non-streaming queries)
+    pub model: Option<String>,
+    /// Backend configuration fingerprint reported by the provider, e.g. OpenAI's
+    /// `system_fingerprint`; a change explains output drift under a fixed seed
+    pub system_fingerprint: Option<String>,
+    /// Seed the request was sent with (see `QueryResolver::reproducible`)
+    pub seed: Option<u64>,
+    /// Tenant the query ran for (see `tenancy`)
+    pub tenant: Option<String>,
+    /// Tags of the resolver that ran the query (see `QueryResolver::with_tag`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tags: HashMap<String, String>,
+}
+
+/// What a provider reported about the model that produced a reply
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Provenance {
+    pub(crate) model: Option<String>,
+    pub(crate) system_fingerprint: Option<String>,
+    pub(crate) seed: Option<u64>,
+}
+
+/// What clients report during one query
+#[derive(Debug, Default)]
+pub(crate) struct Reported {
+    provenance: Provenance,
+    tenant: Option<String>,
+    finish_reason: Option<FinishReason>,
+    logprobs: Option<Vec<TokenLogprob>>,
+    usage: Option<RecordUsage>,
+    attempts: usize,
+}
+
+tokio::task_local! {
+    static PROVENANCE: Arc<Mutex<Reported>>;
+}
+
+/// Run `future` with a slot clients fill through `record_provenance`, unless one is
+/// already in scope
+pub(crate) async fn capture_provenance<F: Future>(future: F) -> F::Output {
+    if PROVENANCE.try_with(|_| ()).is_ok() {
+        future.await
+    } else {
+        PROVENANCE.scope(Arc::default(), future).await
+    }
+}
+
+/// Called by clients after a reply; outside a resolver query this does nothing
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub(crate) fn record_provenance(provenance: Provenance) {
+    let _ = PROVENANCE.try_with(|slot| {
+        if let Ok(mut slot) = slot.lock() {
+            slot.provenance = provenance;
+        }
+    });
+}
+
+/// Called by tenant clients before a request; outside a resolver query this does nothing
+pub(crate) fn record_tenant(tenant: &str) {
+    let _ = PROVENANCE.try_with(|slot| {
+        if let Ok(mut slot) = slot.lock() {
+            slot.tenant = Some(tenant.to_string());
+        }
+    });
+}
+
+/// Called by clients after a reply with why the model stopped (e.g. `Length` when it
+/// hit `max_tokens`); `RetryConfig::with_length_recovery` reads it. Outside a resolver
+/// query this does nothing.
+pub fn record_finish_reason(reason: FinishReason) {
+    let _ = PROVENANCE.try_with(|slot| {
+        if let Ok(mut slot) = slot.lock() {
+            slot.finish_reason = Some(reason);
+        }
+    });
+}
+
+/// Called by clients after a reply with the logprob of every token it is made of;
+/// `QueryResolver::with_field_confidence` reads them. Outside a resolver query this
+/// does nothing.
+pub fn record_logprobs(tokens: Vec<TokenLogprob>) {
+    let _ = PROVENANCE.try_with(|slot| {
+        if let Ok(mut slot) = slot.lock() {
+            slot.logprobs = Some(tokens);
+        }
+    });
+}
+
+/// Called by clients after a reply with the token counts the provider billed for it.
+/// They are summed over the query's model calls and replace the estimate in its
+/// `QueryRecord`. Outside a resolver query this does nothing.
+pub fn record_usage(usage: RecordUsage) {
+    let _ = PROVENANCE.try_with(|slot| {
+        if let Ok(mut slot) = slot.lock() {
+            slot.usage.get_or_insert_with(RecordUsage::default).add(usage);
+        }
+    });
+}
+
+/// The `usage` object of OpenAI-style chat completion replies
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChatUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    #[serde(default)]
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+#[derive(Debug, Deserialize)]
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u64,
+}
+
+impl From<ChatUsage> for RecordUsage {
+    fn from(usage: ChatUsage) -> Self {
+        RecordUsage {
+            prompt_tokens: usage.prompt_tokens,
+            cached_tokens: usage.prompt_tokens_details.map_or(0, |details| details.cached_tokens),
+            completion_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+/// Number the next model call of the current query, from 1; always 1 outside a query
+pub(crate) fn next_attempt() -> usize {
+    PROVENANCE.try_with(|slot| slot.lock().map(|mut slot| {
+        slot.attempts += 1;
+        slot.attempts
+    }).unwrap_or(1)).unwrap_or(1)
+}
+
+/// Receives the stats of every query run through a resolver
+pub type StatsCallback = Arc<dyn Fn(&QueryStats) + Send + Sync>;
+
+/// Collects `QueryStats` while a query runs
+#[derive(Debug)]
+pub(crate) struct QueryProbe {
+    operation: &'static str,
+    prompt: String,
+    schema_id: Option<String>,
+    started: DateTime<Utc>,
+    first_token: Option<Duration>,
+    first_data: Option<Duration>,
+    last_reply: Option<Duration>,
+    retries: usize,
+    bytes: usize,
+    items: usize,
+    correlation_id: Option<String>,
+    tenant: Option<String>,
+    tags: HashMap<String, String>,
+    provenance: Option<Arc<Mutex<Reported>>>,
+}
+
+impl QueryProbe {
+    /// Start timing `operation`, run for the caller's `prompt`
+    pub(crate) fn start(operation: &'static str, prompt: &str) -> Self {
+        Self {
+            operation,
+            prompt: prompt.to_string(),
+            schema_id: None,
+            started: Utc::now(),
+            first_token: None,
+            first_data: None,
+            last_reply: None,
+            retries: 0,
+            bytes: 0,
+            items: 0,
+            correlation_id: crate::correlation::current().map(|id| id.to_string()),
+            tenant: crate::tenancy::current(),
+            tags: HashMap::new(),
+            provenance: PROVENANCE.try_with(Arc::clone).ok(),
+        }
+    }
+
+    /// The query asks for data matching `schema`
+    #[must_use]
+    pub(crate) fn with_schema(mut self, schema: &serde_json::Value) -> Self {
+        self.schema_id = Some(schema_id(schema));
+        self
+    }
+
+    /// Tag the query with `tags`, on top of any it has
+    #[must_use]
+    pub(crate) fn with_tags(mut self, tags: &HashMap<String, String>) -> Self {
+        self.tags.extend(tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self
+    }
+
+    /// Give the probe a report slot of its own, for a query run through `scoped` while
+    /// other queries run on the same task (see `QueryResolver::query_race`)
+    #[must_use]
+    pub(crate) fn detached(mut self) -> Self {
+        self.provenance = Some(Arc::default());
+        self
+    }
+
+    /// Run `future` with this probe's report slot in scope, so the query inside reports
+    /// into it
+    pub(crate) async fn scoped<F: Future>(&self, future: F) -> F::Output {
+        match &self.provenance {
+            Some(slot) => PROVENANCE.scope(slot.clone(), future).await,
+            None => future.await,
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        (Utc::now() - self.started).to_std().unwrap_or_default()
+    }
+
+    /// Why the model stopped its latest reply, if the client said; clears it so the
+    /// next reply starts unknown
+    pub(crate) fn take_finish_reason(&self) -> Option<FinishReason> {
+        self.provenance.as_ref()?.lock().ok()?.finish_reason.take()
+    }
+
+    /// Token logprobs of the latest reply, if the client reported them; clears them like
+    /// `take_finish_reason`
+    pub(crate) fn take_logprobs(&self) -> Option<Vec<TokenLogprob>> {
+        self.provenance.as_ref()?.lock().ok()?.logprobs.take()
+    }
+
+    /// Model text arrived: a whole reply, or one streamed token
+    pub(crate) fn received(&mut self, text: &str) {
+        let now = self.elapsed();
+        self.first_token.get_or_insert(now);
+        self.last_reply = Some(now);
+        self.bytes += text.len();
+    }
+
+    /// A correction request is about to be sent
+    pub(crate) fn retry(&mut self) {
+        self.retries += 1;
+    }
+
+    /// A streamed data item was emitted
+    pub(crate) fn data(&mut self) {
+        let now = self.elapsed();
+        self.first_data.get_or_insert(now);
+        self.items += 1;
+    }
+
+    /// Stats for a query that returned `items` data items. Without streamed items, data
+    /// became available with the last reply.
+    pub(crate) fn completed(mut self, items: usize) -> QueryStats {
+        if items > 0 && self.first_data.is_none() {
+            self.first_data = self.last_reply;
+        }
+        self.items = self.items.max(items);
+        self.stats(true)
+    }
+
+    pub(crate) fn failed(self) -> QueryStats {
+        self.stats(false)
+    }
+
+    /// Stats for a stream that ended, successfully or not
+    pub(crate) fn ended(self, succeeded: bool) -> QueryStats {
+        self.stats(succeeded)
+    }
+
+    /// Audit record of the query so far, ending with `outcome`; `data` is the extracted
+    /// items as a JSON array
+    pub(crate) fn record(&self, outcome: RecordOutcome, data: Option<&[u8]>, include_prompt: bool) -> QueryRecord {
+        let (model, tenant, usage, attempts) = self.provenance.as_ref()
+            .and_then(|slot| slot.lock().ok().map(|r| (r.provenance.model.clone(), r.tenant.clone(), r.usage, r.attempts)))
+            .unwrap_or_default();
+        QueryRecord {
+            operation: self.operation.to_string(),
+            correlation_id: self.correlation_id.clone(),
+            tenant: tenant.or_else(|| self.tenant.clone()),
+            started_at: self.started,
+            duration: self.elapsed(),
+            prompt_hash: content_hash(self.prompt.as_bytes()),
+            prompt: include_prompt.then(|| self.prompt.clone()),
+            schema_id: self.schema_id.clone(),
+            model,
+            usage: usage.unwrap_or(RecordUsage {
+                prompt_tokens: estimate_tokens(&self.prompt) as u64,
+                cached_tokens: 0,
+                // Same estimate as `estimate_tokens`, from the byte count alone
+                completion_tokens: self.bytes.div_ceil(4) as u64,
+            }),
+            attempts,
+            retries: self.retries,
+            outcome,
+            data_hash: data.map(content_hash),
+            tags: self.tags.clone(),
+        }
+    }
+
+    fn stats(self, succeeded: bool) -> QueryStats {
+        let (provenance, tenant) = self.provenance
+            .as_ref()
+            .and_then(|slot| slot.lock().ok().map(|r| (r.provenance.clone(), r.tenant.clone())))
+            .unwrap_or_default();
+        QueryStats {
+            operation: self.operation.to_string(),
+            time_to_first_token: self.first_token,
+            time_to_first_data: self.first_data,
+            total: self.elapsed(),
+            retries: self.retries,
+            bytes_received: self.bytes,
+            items: self.items,
+            succeeded,
+            correlation_id: self.correlation_id,
+            model: provenance.model,
+            system_fingerprint: provenance.system_fingerprint,
+            seed: provenance.seed,
+            tenant: tenant.or(self.tenant),
+            tags: self.tags,
+        }
+    }
+}
+
+/// Stream items the observer can inspect
+pub(crate) trait AsStreamItem<T: JsonSchema> {
+    fn stream_item(&self) -> &StreamItem<T>;
+}
+
+impl<T: JsonSchema> AsStreamItem<T> for StreamItem<T> {
+    fn stream_item(&self) -> &StreamItem<T> {
+        self
+    }
+}
+
+impl<T: JsonSchema> AsStreamItem<T> for Timed<StreamItem<T>> {
+    fn stream_item(&self) -> &StreamItem<T> {
+        &self.item
+    }
+}
+
+/// Reports a stream's stats when it is dropped, whether or not it ran to the end
+struct StreamReport {
+    probe: Option<QueryProbe>,
+    callback: StatsCallback,
+    succeeded: bool,
+}
+
+impl Drop for StreamReport {
+    fn drop(&mut self) {
+        if let Some(probe) = self.probe.take() {
+            (self.callback)(&probe.ended(self.succeeded));
+        }
+    }
+}
+
+/// Pass `items` through unchanged, recording tokens and data into `probe` and handing the
+/// stats to `callback` once the stream ends or is dropped
+pub(crate) fn observe_stream<S, I, T>(items: S, probe: QueryProbe, callback: StatsCallback) -> impl Stream<Item = Result<I, QueryResolverError>>
+where
+    S: Stream<Item = Result<I, QueryResolverError>>,
+    I: AsStreamItem<T>,
+    T: JsonSchema,
+{
+    stream! {
+        let mut report = StreamReport { probe: Some(probe), callback, succeeded: false };
+        let mut failed = false;
+        let mut items = Box::pin(items);
+        while let Some(next) = items.next().await {
+            if let Some(probe) = report.probe.as_mut() {
+                match next.as_ref().map(|item| item.stream_item()) {
+                    Ok(StreamItem::Token(token)) => probe.received(token),
+                    Ok(StreamItem::Data(_)) => probe.data(),
+                    Ok(StreamItem::Text(_) | StreamItem::Finished { .. }) => {}
+                    Err(_) => failed = true,
+                }
+            }
+            yield next;
+        }
+        report.succeeded = !failed;
+    }
+}
@@ -0,0 +1,163 @@
+//! Combinators for semantic streams, i.e. streams of `Result<StreamItem<T>, E>` such as
+//! `QueryResolver::stream_query` returns. Errors always pass through unchanged.
+//!
+//! ```no_run
+//! # use futures_util::StreamExt;
+//! # use semantic_query::core::QueryResolver;
+//! # use semantic_query::stream_ext::{SemanticStreamExt, TextBuffer};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Finding { title: String }
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>) -> Result<(), Box<dyn std::error::Error>> {
+//! let stream = resolver.stream_query::<Finding>("Audit this".into()).await?;
+//! let mut titles = std::pin::pin!(stream.data_only().map(|f| f.map(|f| f.title)));
+//! while let Some(title) = titles.next().await {
+//!     println!("{}", title?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use async_stream::stream;
+use chrono::Utc;
+use futures_core::stream::Stream;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::journal::Journal;
+use crate::streaming::{StreamItem, TextContent};
+
+/// When `SemanticStreamExt::buffer_text` releases buffered text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextBuffer {
+    /// After each newline
+    Lines,
+    /// After `.`, `!` or `?` followed by whitespace
+    Sentences,
+    /// In pieces of this many characters
+    Chars(usize),
+    /// Only before the next non-text item and at the end
+    Whole,
+}
+
+impl TextBuffer {
+    /// Byte offset up to which `buffer` should be released
+    fn flush_point(self, buffer: &str) -> Option<usize> {
+        match self {
+            Self::Lines => buffer.find('\n').map(|i| i + 1),
+            Self::Sentences => buffer.match_indices(['.', '!', '?']).find_map(|(i, mark)| {
+                let after = i + mark.len();
+                let space = buffer[after..].chars().next().filter(|c| c.is_whitespace())?;
+                Some(after + space.len_utf8())
+            }),
+            Self::Chars(n) => buffer.char_indices().nth(n.max(1)).map(|(i, _)| i)
+                .or_else(|| (buffer.chars().count() == n.max(1)).then_some(buffer.len())),
+            Self::Whole => None,
+        }
+    }
+}
+
+/// Combinators for streams of `Result<StreamItem<T>, E>`; implemented for all of them
+pub trait SemanticStreamExt<T: JsonSchema, E>: Stream<Item = Result<StreamItem<T>, E>> + Sized {
+    /// Only the data items
+    fn data_only(self) -> impl Stream<Item = Result<T, E>> {
+        self.filter_map(|item| std::future::ready(match item {
+            Ok(StreamItem::Data(data)) => Some(Ok(data)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        }))
+    }
+
+    /// Only the text of `Text` items (tokens are dropped)
+    fn text_only(self) -> impl Stream<Item = Result<String, E>> {
+        self.filter_map(|item| std::future::ready(match item {
+            Ok(StreamItem::Text(TextContent { text, .. })) => Some(Ok(text)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        }))
+    }
+
+    /// Join consecutive `Text` items and release them per `policy`. Buffered text is
+    /// always released before the next data, finish or error item and at the end of
+    /// the stream; tokens pass through without releasing it.
+    fn buffer_text(self, policy: TextBuffer) -> impl Stream<Item = Result<StreamItem<T>, E>> {
+        stream! {
+            let mut items = std::pin::pin!(self);
+            let mut buffer = String::new();
+            while let Some(item) = items.next().await {
+                match item {
+                    Ok(StreamItem::Text(TextContent { text, .. })) => {
+                        buffer.push_str(&text);
+                        while let Some(at) = policy.flush_point(&buffer) {
+                            let rest = buffer.split_off(at);
+                            yield Ok(StreamItem::Text(TextContent { text: std::mem::replace(&mut buffer, rest), block: None }));
+                        }
+                    }
+                    Ok(token @ StreamItem::Token(_)) => yield Ok(token),
+                    other => {
+                        if !buffer.is_empty() {
+                            yield Ok(StreamItem::Text(TextContent { text: std::mem::take(&mut buffer), block: None }));
+                        }
+                        yield other;
+                    }
+                }
+            }
+            if !buffer.is_empty() {
+                yield Ok(StreamItem::Text(TextContent { text: buffer, block: None }));
+            }
+        }
+    }
+
+    /// Transform each data item, keeping everything else
+    fn map_data<U, F>(self, mut f: F) -> impl Stream<Item = Result<StreamItem<U>, E>>
+    where
+        U: JsonSchema,
+        F: FnMut(T) -> U,
+    {
+        self.map(move |item| item.map(|item| match item {
+            StreamItem::Data(data) => StreamItem::Data(f(data)),
+            StreamItem::Token(token) => StreamItem::Token(token),
+            StreamItem::Text(text) => StreamItem::Text(text),
+            StreamItem::Finished { reason } => StreamItem::Finished { reason },
+        }))
+    }
+
+    /// Everything up to and including the first data item, then end the stream
+    /// (dropping the source, and with it the connection)
+    fn take_until_first_data(self) -> impl Stream<Item = Result<StreamItem<T>, E>> {
+        stream! {
+            let mut items = std::pin::pin!(self);
+            while let Some(item) = items.next().await {
+                let done = matches!(item, Ok(StreamItem::Data(_)));
+                yield item;
+                if done {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Pass items through unchanged while pushing each successful one to `journal`,
+    /// timestamped from the first poll. See `Journal::record` to write to a file instead.
+    fn tee_to(self, journal: Arc<Mutex<Journal<T>>>) -> impl Stream<Item = Result<StreamItem<T>, E>>
+    where
+        T: DeserializeOwned + Serialize + Clone,
+    {
+        stream! {
+            let started = Utc::now();
+            let mut items = std::pin::pin!(self);
+            while let Some(item) = items.next().await {
+                if let (Ok(stream_item), Ok(mut journal)) = (&item, journal.lock()) {
+                    let elapsed_ms = (Utc::now() - started).num_milliseconds().max(0) as u64;
+                    journal.push(elapsed_ms, stream_item.clone());
+                }
+                yield item;
+            }
+        }
+    }
+}
+
+impl<T: JsonSchema, E, S> SemanticStreamExt<T, E> for S where S: Stream<Item = Result<StreamItem<T>, E>> {}
@@ -1,25 +1,43 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::json_utils::{find_json_structures, deserialize_stream_map, ParsedOrUnknown};
-use tracing::{debug, instrument};
+use crate::json_utils::{find_json_structures_in, descend_spans, parse_candidate, DetectionScope, JsonStreamParser, ObjCoords, ParseLimits, ScopeTracker};
+use tracing::{debug, instrument, trace, warn};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncBufReadExt, BufReader};
 use async_stream::stream;
 use futures_core::stream::Stream;
 use futures_util::StreamExt;
 use bytes::Bytes;
-use std::pin::Pin;
+use crate::core::RawByteStream;
+use crate::normalize::Normalizers;
+
+pub mod markdown;
+pub mod ws;
+
+use markdown::{MarkdownBlock, MarkdownSegmenter};
 
 /// Represents a piece of unstructured text content returned by the model.
 ///
 /// Usage:
-/// - `StreamItem::Text(TextContent { text })` preserves non-JSON content in the
+/// - `StreamItem::Text(TextContent { text, .. })` preserves non-JSON content in the
 ///   order it appears, so you never lose commentary or context.
-#[derive(Debug, Clone, Deserialize, JsonSchema, PartialEq)]
+///
+/// Serialized as `{"text": "..."}`, plus `"block": {"kind": ...}` under
+/// `StreamOptions::markdown_blocks`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct TextContent {
     /// Plain text content. Downstream systems can render or log this.
     pub text: String,
+    /// The markdown block `text` is, when split by `StreamOptions::markdown_blocks`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<MarkdownBlock>,
 }
 
 /// An item in the model's response stream: token, text, or typed data `T`.
@@ -27,24 +45,86 @@ pub struct TextContent {
 /// Usage:
 /// - One-shot: `Vec<StreamItem<T>>` via `QueryResolver::query_stream`.
 /// - Streaming: `Stream<Item=StreamItem<T>>` via `stream_from_async_read`.
-#[derive(Debug, Clone, Deserialize, JsonSchema)]
+///
+/// Serde representation (stable; adjacently tagged):
+/// - `{"kind": "Text", "content": {"text": "..."}}`
+/// - `{"kind": "Data", "content": <T as JSON>}`
+/// - `{"kind": "Finished", "content": {"reason": "EarlyStopAfterData"}}`
+/// - `{"kind": "Token", "content": "..."}` — serialize-only; tokens are transient, so
+///   they are rejected on deserialization and omitted from the JSON schema.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "kind", content = "content")]
 pub enum StreamItem<T>
 where
     T: JsonSchema,
 {
     /// Individual token for real-time display
-    #[serde(skip)]
+    #[serde(skip_deserializing)]
     Token(String),
     /// Free-form text emitted by the model.
     Text(TextContent),
     /// Structured data conforming to the user-provided schema.
     Data(T),
+    /// The stream was ended before the model finished, e.g. by `StreamOptions::stop_after_data`.
+    /// Always the last item.
+    Finished { reason: FinishReason },
+}
+
+/// Why a response ended: reported by providers for one-shot replies (see
+/// `stats::record_finish_reason`), or set by this crate when it ends a stream early
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum FinishReason {
+    /// A complete `T` was parsed and `StreamOptions::stop_after_data` is set
+    EarlyStopAfterData,
+    /// The model finished its answer
+    Stop,
+    /// The answer hit the `max_tokens` limit and is cut off
+    Length,
+    /// The model stopped to call tools
+    ToolCalls,
+    /// The provider's content filter cut the answer
+    ContentFilter,
+}
+
+impl FinishReason {
+    /// Map a provider's `finish_reason` / `stop_reason` / `done_reason` string
+    pub fn from_provider(reason: &str) -> Option<Self> {
+        match reason {
+            "stop" | "end_turn" | "stop_sequence" => Some(Self::Stop),
+            "length" | "max_tokens" => Some(Self::Length),
+            "tool_calls" | "tool_use" | "function_call" => Some(Self::ToolCalls),
+            "content_filter" => Some(Self::ContentFilter),
+            _ => None,
+        }
+    }
 }
 
 /// Convenience alias describing the full response as an ordered stream.
 pub type ParsedStream<T> = Vec<StreamItem<T>>;
 
+/// A native tool call (an Anthropic `tool_use` block or an OpenAI `tool_calls` entry),
+/// kept whole with its id.
+///
+/// Provider tool calls become `Data(T)` when their `input` deserializes as `T`;
+/// use `T = ToolCall` to receive the id and tool name as well.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolCall {
+    /// Provider-assigned id, e.g. `toolu_01A...`
+    pub id: String,
+    /// Name of the tool the model called
+    pub name: String,
+    /// Arguments the model passed, as sent by the provider
+    pub input: serde_json::Value,
+}
+
+impl ToolCall {
+    /// The call as `T`: its `input` if that deserializes as `T`, else the whole call
+    pub fn to_data<T: DeserializeOwned>(&self) -> Option<T> {
+        T::deserialize(&self.input).ok()
+            .or_else(|| serde_json::to_value(self).ok().and_then(|call| T::deserialize(call).ok()))
+    }
+}
+
 /// Construct a parsed stream from a raw model response using the streaming
 /// structure parser for segmentation. Any JSON structure that deserializes to `T`
 /// becomes `StreamItem::Data(T)`. Non-matching JSON and all non-JSON text are
@@ -55,138 +135,864 @@ pub fn build_parsed_stream<T>(raw: &str) -> ParsedStream<T>
 where
     T: DeserializeOwned + JsonSchema,
 {
-    let mut items: ParsedStream<T> = Vec::new();
-    let roots = find_json_structures(raw);
+    build_parsed_stream_with(raw, TextFidelity::default())
+}
+
+/// `build_parsed_stream`, emitting text as `fidelity` says
+pub fn build_parsed_stream_with<T>(raw: &str, fidelity: TextFidelity) -> ParsedStream<T>
+where
+    T: DeserializeOwned + JsonSchema,
+{
+    segment_response::<T>(raw, fidelity, &DetectionScope::Anywhere)
+        .into_iter()
+        .map(|segment| segment.into_stream_item(raw))
+        .collect()
+}
+
+/// How text outside data items is emitted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextFidelity {
+    /// Drop whitespace-only text, and trim the text chunks of SSE streams
+    #[default]
+    Trimmed,
+    /// Emit all text verbatim, whitespace included. In a `ParsedResponse` the text items
+    /// and the data items' `original_text` concatenate back to the response; streams
+    /// drop only the data's source text and the separators of streamed array elements.
+    Exact,
+}
+
+impl TextFidelity {
+    /// Whether a text segment is emitted at all
+    pub(crate) fn keeps(self, text: &str) -> bool {
+        match self {
+            Self::Trimmed => !text.trim().is_empty(),
+            Self::Exact => !text.is_empty(),
+        }
+    }
+
+    /// A text chunk as emitted, or None when it is dropped
+    pub(crate) fn chunk(self, text: &str) -> Option<&str> {
+        let text = match self {
+            Self::Trimmed => text.trim(),
+            Self::Exact => text,
+        };
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+/// A piece of a response, held as a byte range into the response buffer so that text
+/// is only copied out when an item is actually materialized.
+#[derive(Debug)]
+pub(crate) enum Segment<T> {
+    Text(Range<usize>),
+    Data(T, Range<usize>),
+}
+
+impl<T: JsonSchema> Segment<T> {
+    pub(crate) fn into_stream_item(self, buf: &str) -> StreamItem<T> {
+        match self {
+            Segment::Text(range) => StreamItem::Text(TextContent { text: buf[range].to_string(), block: None }),
+            Segment::Data(data, _) => StreamItem::Data(data),
+        }
+    }
+}
+
+/// Segment `raw` with a single structure scan: every root within `scope` is deserialized
+/// in place (no re-slicing and re-scanning) and text is recorded as ranges.
+pub(crate) fn segment_response<T: DeserializeOwned>(raw: &str, fidelity: TextFidelity, scope: &DetectionScope) -> Vec<Segment<T>> {
+    segment_response_with(raw, &parse_candidate::<T>, fidelity, scope)
+}
+
+/// `segment_response` with a custom candidate parser
+pub(crate) fn segment_response_with<T>(raw: &str, parse: &dyn Fn(&str) -> Option<T>, fidelity: TextFidelity, scope: &DetectionScope) -> Vec<Segment<T>> {
+    let mut segments = Vec::new();
     let mut cursor = 0usize;
+    for node in find_json_structures_in(raw, scope) {
+        push_text(raw, cursor..node.start, fidelity, &mut segments);
+        segment_node::<T>(raw, &node, parse, fidelity, &mut segments);
+        cursor = node.end + 1;
+    }
+    push_text(raw, cursor..raw.len(), fidelity, &mut segments);
+    segments
+}
+
+/// Record `range` as text unless `fidelity` drops it
+pub(crate) fn push_text<T>(buf: &str, range: Range<usize>, fidelity: TextFidelity, out: &mut Vec<Segment<T>>) {
+    if range.start < range.end && fidelity.keeps(&buf[range.clone()]) {
+        out.push(Segment::Text(range));
+    }
+}
+
+/// Segments for one closed root structure of `buf`: each match of `T` as data and each
+/// unmatched structure as text. When nothing matched, the whole root follows as text
+/// as well so no information is lost. `TextFidelity::Exact` instead covers the root
+/// once: the text between matches, or the whole root when nothing matched.
+fn segment_node<T>(buf: &str, node: &ObjCoords, parse: &dyn Fn(&str) -> Option<T>, fidelity: TextFidelity, out: &mut Vec<Segment<T>>) {
+    if fidelity == TextFidelity::Exact {
+        let mut cursor = node.start;
+        descend_spans::<T>(buf, node, parse, &mut |parsed, coords| {
+            if let Some(data) = parsed {
+                push_text(buf, cursor..coords.start, fidelity, out);
+                out.push(Segment::Data(data, coords.start..coords.end + 1));
+                cursor = coords.end + 1;
+            }
+        });
+        push_text(buf, cursor..node.end + 1, fidelity, out);
+        return;
+    }
+    let mut any_parsed = false;
+    descend_spans::<T>(buf, node, parse, &mut |parsed, coords| {
+        let range = coords.start..coords.end + 1;
+        match parsed {
+            Some(data) => {
+                any_parsed = true;
+                out.push(Segment::Data(data, range));
+            }
+            None => out.push(Segment::Text(range)),
+        }
+    });
+    if !any_parsed {
+        out.push(Segment::Text(node.start..node.end + 1));
+    }
+}
+
+/// Stream items for one closed root structure of `buf`
+fn node_items<T>(buf: &str, node: &ObjCoords, fidelity: TextFidelity) -> Vec<StreamItem<T>>
+where
+    T: DeserializeOwned + JsonSchema,
+{
+    let mut segments = Vec::new();
+    segment_node::<T>(buf, node, &parse_candidate::<T>, fidelity, &mut segments);
+    segments.into_iter().map(|segment| segment.into_stream_item(buf)).collect()
+}
+
+/// A stream item with arrival metadata, from `stream_from_sse_bytes_timed` and
+/// `QueryResolver::stream_query_timed`. Serializable, so a recorded stream can be
+/// replayed with its original timing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timed<I> {
+    pub item: I,
+    /// Wall-clock time the item was produced
+    pub received_at: DateTime<Utc>,
+    /// Time since the stream started
+    pub elapsed: Duration,
+    /// Tokens received so far, including the one that produced this item
+    pub tokens: usize,
+    /// Id of the provider event that produced this item: the SSE `id:` field, else the
+    /// payload's `id` (e.g. OpenAI's `chatcmpl-...`); for tool calls, the tool call id
+    pub event_id: Option<String>,
+}
+
+/// Stamps items with `Timed` metadata as a stream is parsed
+struct StreamClock {
+    started: DateTime<Utc>,
+    tokens: usize,
+    event_id: Option<String>,
+}
+
+impl StreamClock {
+    fn start() -> Self {
+        Self { started: Utc::now(), tokens: 0, event_id: None }
+    }
+
+    fn stamp<I>(&self, item: I) -> Timed<I> {
+        let received_at = Utc::now();
+        Timed {
+            item,
+            received_at,
+            elapsed: (received_at - self.started).to_std().unwrap_or_default(),
+            tokens: self.tokens,
+            event_id: self.event_id.clone(),
+        }
+    }
+}
+
+/// Latency summary of a timed stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamLatency {
+    /// Time to the first `Token`
+    pub first_token: Option<Duration>,
+    /// Time to the first `Data`
+    pub first_data: Option<Duration>,
+    /// Time to the last item
+    pub total: Option<Duration>,
+    pub tokens: usize,
+}
+
+impl StreamLatency {
+    pub fn measure<'a, T: JsonSchema + 'a>(items: impl IntoIterator<Item = &'a Timed<StreamItem<T>>>) -> Self {
+        let mut latency = Self::default();
+        for timed in items {
+            match timed.item {
+                StreamItem::Token(_) => { latency.first_token.get_or_insert(timed.elapsed); }
+                StreamItem::Data(_) => { latency.first_data.get_or_insert(timed.elapsed); }
+                StreamItem::Text(_) | StreamItem::Finished { .. } => {}
+            }
+            latency.total = Some(timed.elapsed);
+            latency.tokens = timed.tokens;
+        }
+        latency
+    }
+}
+
+/// An item from one of the streams combined by `stream_merge`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sourced<K, I> {
+    /// Tag of the stream the item came from
+    pub source: K,
+    /// Position of the item within its source, from 0
+    pub seq: usize,
+    pub item: I,
+}
+
+/// Merge several streams into one, tagging each item with its source.
+///
+/// Items of one source keep their order; items of different sources are interleaved
+/// as they become ready. Sources are polled independently, so an error item or an
+/// early end in one doesn't affect the others. The merged stream ends once every
+/// source has ended.
+pub fn stream_merge<K, S>(sources: impl IntoIterator<Item = (K, S)>) -> impl Stream<Item = Sourced<K, S::Item>>
+where
+    K: Clone,
+    S: Stream,
+{
+    futures_util::stream::select_all(sources.into_iter().map(|(source, items)| {
+        Box::pin(items.enumerate().map(move |(seq, item)| Sourced { source: source.clone(), seq, item }))
+    }))
+}
+
+/// Options for the streaming adapters (`stream_from_*_with`, `QueryResolver::stream_query`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// When the response is a top-level array whose first element deserializes as `T`,
+    /// emit each element as `Data(T)` as soon as it closes instead of waiting for `]`.
+    /// Disable to treat the array as a whole, e.g. when `T` itself accepts arrays.
+    pub stream_array_elements: bool,
+    /// Bounds on nesting depth and structure size; exceeding them ends the stream with
+    /// `DataExtractionError::LimitExceeded`
+    pub limits: ParseLimits,
+    /// End the stream right after the first `Data(T)`, emitting
+    /// `Finished { reason: EarlyStopAfterData }`. The byte stream is dropped, which closes
+    /// the connection so the provider stops generating (and billing) trailing prose.
+    /// Meant for single-object extraction; leave off when several items are expected.
+    pub stop_after_data: bool,
+    /// End SSE streams (`QueryResolver::stream_query` and friends) with
+    /// `QueryResolverError::DeadlineExceeded` once this long has passed since they were
+    /// first polled, dropping the connection. Not enforced on wasm32, which has no timer.
+    pub deadline: Option<Duration>,
+    /// When the deadline hits, let `query_stream_collect` return the items completed so
+    /// far as a `ParsedResponse` with `truncated` set, instead of failing
+    pub partial_on_deadline: bool,
+    /// Read buffer sizing of the `AsyncRead` adapters. Byte streams (SSE and friends)
+    /// arrive in chunks the transport sizes and ignore it.
+    pub buffer: BufferPolicy,
+    /// Whitespace handling of `Text` items. The resolver applies it to non-streaming
+    /// queries as well (`QueryResolver::with_text_fidelity`).
+    pub text_fidelity: TextFidelity,
+    /// Split `Text` items into markdown blocks and tag each with its kind; see
+    /// `streaming::markdown`. The resolver applies it to non-streaming queries as well
+    /// (`QueryResolver::with_markdown_blocks`).
+    pub markdown_blocks: bool,
+    /// Where JSON is looked for, e.g. only inside fenced code blocks. The resolver applies
+    /// it to non-streaming queries as well (`QueryResolver::with_detection_scope`).
+    pub detection_scope: DetectionScope,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            stream_array_elements: true,
+            limits: ParseLimits::default(),
+            stop_after_data: false,
+            deadline: None,
+            partial_on_deadline: false,
+            buffer: BufferPolicy::default(),
+            text_fidelity: TextFidelity::default(),
+            markdown_blocks: false,
+            detection_scope: DetectionScope::Anywhere,
+        }
+    }
+}
+
+/// How the `AsyncRead` adapters size their reads. Reads start at `initial` bytes, so the
+/// first tokens are parsed as soon as they arrive, and double whenever a read fills the
+/// buffer, up to `max`. The `buf_size` argument of the adapters caps `max` further.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferPolicy {
+    pub initial: usize,
+    pub max: usize,
+}
+
+impl Default for BufferPolicy {
+    fn default() -> Self {
+        Self { initial: 256, max: 64 * 1024 }
+    }
+}
+
+impl BufferPolicy {
+    /// Always read up to `size` bytes
+    pub fn fixed(size: usize) -> Self {
+        Self { initial: size, max: size }
+    }
+
+    pub fn adaptive(initial: usize, max: usize) -> Self {
+        Self { initial, max }
+    }
+}
 
-    for node in roots {
-        // Emit text before this node
-        if node.start > cursor {
-            let text_slice = &raw[cursor..node.start];
-            let trimmed = text_slice.trim();
-            if !trimmed.is_empty() {
-                items.push(StreamItem::Text(TextContent { text: text_slice.to_string() }));
+/// Read buffer following a `BufferPolicy`. Hands out text only up to the last complete
+/// UTF-8 character; a character split across reads is completed by the next one.
+pub(crate) struct ReadBuffer {
+    buf: Vec<u8>,
+    /// Bytes of an incomplete character carried over from the last read
+    pending: usize,
+    max: usize,
+}
+
+impl ReadBuffer {
+    /// A buffer for `policy`, never larger than `buf_size` (the adapters' historical
+    /// argument, floored at 1 KiB)
+    pub(crate) fn new(policy: BufferPolicy, buf_size: usize) -> Self {
+        // Room for at least one UTF-8 character
+        let max = policy.max.min(buf_size.max(1024)).max(4);
+        Self { buf: vec![0; policy.initial.clamp(4, max)], pending: 0, max }
+    }
+
+    /// Text of the next read; `None` at the end of `reader` or on a read error. Invalid
+    /// UTF-8 is skipped together with the rest of its read.
+    pub(crate) async fn read<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> Option<String> {
+        let n = match reader.read(&mut self.buf[self.pending..]).await {
+            Ok(0) => return None,
+            Ok(n) => n,
+            Err(e) => {
+                debug!(target = "semantic_query::json_stream", error = %e, "read error");
+                return None;
+            }
+        };
+        let filled = self.pending + n;
+        let text = match std::str::from_utf8(&self.buf[..filled]) {
+            Ok(text) => {
+                self.pending = 0;
+                text.to_string()
             }
+            Err(e) if e.error_len().is_none() => {
+                let valid = e.valid_up_to();
+                let text = String::from_utf8_lossy(&self.buf[..valid]).into_owned();
+                self.buf.copy_within(valid..filled, 0);
+                self.pending = filled - valid;
+                text
+            }
+            Err(_) => {
+                debug!(target = "semantic_query::json_stream", "skipping non-utf8 chunk");
+                self.pending = 0;
+                String::new()
+            }
+        };
+        if filled == self.buf.len() && self.buf.len() < self.max {
+            let grown = (self.buf.len() * 2).min(self.max);
+            trace!(target = "semantic_query::json_stream", from = self.buf.len(), to = grown, "growing read buffer");
+            self.buf.resize(grown, 0);
         }
+        Some(text)
+    }
+}
+
+/// How `markdown_items` treats a stream item
+enum TextPart<I> {
+    /// A `Text` item's content, and how to build an item like it around other text
+    Text(TextContent, Box<dyn Fn(TextContent) -> I + Send + Sync>),
+    /// Passes through without ending the text around it
+    Token(I),
+    /// Ends the text before it, e.g. data, finish and error items
+    Other(I),
+}
 
-        // Try to parse this node or any of its children that match T.
-        let end = node.end + 1; // inclusive -> make end exclusive
-        let json_slice = &raw[node.start..end];
-        let mapped: Vec<ParsedOrUnknown<T>> = deserialize_stream_map::<T>(json_slice);
-        if mapped.is_empty() {
-            // No structures detected inside (unlikely), preserve as text
-            items.push(StreamItem::Text(TextContent { text: json_slice.to_string() }));
-        } else {
-            let mut any_parsed = false;
-            for item in mapped {
-                match item {
-                    ParsedOrUnknown::Parsed(v) => {
-                        any_parsed = true;
-                        items.push(StreamItem::Data(v));
+/// With `StreamOptions::markdown_blocks`, re-split the `Text` items of `items` into
+/// markdown blocks; a block is held until the text after it completes it, or until the
+/// next non-text item
+fn markdown_items<S, I>(items: S, options: StreamOptions, part: impl Fn(I) -> TextPart<I>) -> impl Stream<Item = I>
+where
+    S: Stream<Item = I>,
+{
+    stream! {
+        let mut items = Box::pin(items);
+        let mut segmenter = MarkdownSegmenter::new(options.text_fidelity);
+        let mut rebuild: Option<Box<dyn Fn(TextContent) -> I + Send + Sync>> = None;
+        while let Some(next) = items.next().await {
+            if !options.markdown_blocks {
+                yield next;
+                continue;
+            }
+            match part(next) {
+                TextPart::Text(text, make) => {
+                    for block in segmenter.push(&text.text) { yield make(block); }
+                    rebuild = Some(make);
+                }
+                TextPart::Token(item) => yield item,
+                TextPart::Other(item) => {
+                    if let Some(make) = &rebuild {
+                        for block in segmenter.flush() { yield make(block); }
                     }
-                    ParsedOrUnknown::Unknown(u) => {
-                        // Preserve unknown JSON chunks as text to keep fidelity
-                        let u_end = u.end + 1;
-                        if u_end <= json_slice.len() && u.start < u_end {
-                            let sub = &json_slice[u.start..u_end];
-                            items.push(StreamItem::Text(TextContent { text: sub.to_string() }));
-                        } else {
-                            debug!(target = "semantic_query::json_stream", "Skipping invalid unknown coordinates");
-                        }
+                    yield item;
+                }
+            }
+        }
+        if let Some(make) = &rebuild {
+            for block in segmenter.flush() { yield make(block); }
+        }
+    }
+}
+
+/// Pass `items` through until `finished` returns an item for one of them; yield that as
+/// the last item and drop `items` (and with it the underlying connection)
+fn end_after<S>(items: S, finished: impl Fn(&S::Item) -> Option<S::Item>) -> impl Stream<Item = S::Item>
+where
+    S: Stream,
+{
+    stream! {
+        let mut items = Box::pin(items);
+        while let Some(next) = items.next().await {
+            let end = finished(&next);
+            yield next;
+            if let Some(end) = end {
+                debug!(target = "semantic_query::json_stream", "ending stream after first data item");
+                yield end;
+                return;
+            }
+        }
+    }
+}
+
+/// End `items` with `DeadlineExceeded` once `deadline` has passed since the first poll
+#[cfg(not(target_arch = "wasm32"))]
+fn within_deadline<S, I>(items: S, deadline: Option<Duration>) -> impl Stream<Item = Result<I, crate::error::QueryResolverError>>
+where
+    S: Stream<Item = Result<I, crate::error::QueryResolverError>>,
+{
+    stream! {
+        let mut items = Box::pin(items);
+        let Some(limit) = deadline else {
+            while let Some(item) = items.next().await {
+                yield item;
+            }
+            return;
+        };
+        let expires = std::time::Instant::now() + limit;
+        loop {
+            match crate::runtime::timeout(expires.saturating_duration_since(std::time::Instant::now()), items.next()).await {
+                Ok(Some(item)) => yield item,
+                Ok(None) => return,
+                Err(_) => {
+                    warn!(target = "semantic_query::json_stream", deadline = ?limit, "stream deadline exceeded");
+                    yield Err(crate::error::QueryResolverError::DeadlineExceeded(limit));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Without a timer on wasm32, deadlines are not enforced
+#[cfg(target_arch = "wasm32")]
+fn within_deadline<S, I>(items: S, _deadline: Option<Duration>) -> S
+where
+    S: Stream<Item = Result<I, crate::error::QueryResolverError>>,
+{
+    items
+}
+
+const EARLY_STOP: FinishReason = FinishReason::EarlyStopAfterData;
+
+/// Text delta of an SSE payload: OpenAI-style `choices[0].delta.content`, or an
+/// Anthropic `content_block_delta` carrying a `text_delta`
+/// An SSE event carrying `text` as a chat-completions delta, the shape `delta_text`
+/// reads; for transports that produce plain text
+pub(crate) fn sse_delta(text: &str) -> Bytes {
+    let event = serde_json::json!({"choices": [{"delta": {"content": text}}]});
+    Bytes::from(format!("data: {event}\n\n"))
+}
+
+/// Ends an SSE stream
+pub(crate) const SSE_DONE: &[u8] = b"data: [DONE]\n\n";
+
+fn delta_text(v: &serde_json::Value) -> Option<&str> {
+    v.get("choices").and_then(|c| c.get(0))
+        .and_then(|c0| c0.get("delta")).and_then(|d| d.get("content")).and_then(|c| c.as_str())
+        .or_else(|| {
+            let delta = v.get("delta").filter(|_| v["type"] == "content_block_delta")?;
+            delta.get("text").filter(|_| delta["type"] == "text_delta")?.as_str()
+        })
+}
+
+/// Text deltas of an SSE stream, without scanning them for data; for consumers that
+/// keep the reply elsewhere (see `spool`). Tool-call events are skipped.
+pub(crate) fn sse_text_deltas(byte_stream: RawByteStream) -> impl Stream<Item = Result<String, crate::error::QueryResolverError>> {
+    stream! {
+        use tokio_util::io::StreamReader;
+
+        let io_stream = byte_stream.map(|res| res.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string())));
+        let mut lines = BufReader::new(StreamReader::new(io_stream)).lines();
+        let mut sse_event = String::new();
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    yield Err(crate::error::QueryResolverError::StreamInterrupted(e.to_string()));
+                    break;
+                }
+            };
+            if !line.is_empty() {
+                if line.starts_with("data:") {
+                    if !sse_event.is_empty() { sse_event.push('\n'); }
+                    sse_event.push_str(&line);
+                }
+                continue;
+            }
+            if let Some(payload) = sse_event.strip_prefix("data: ") {
+                if payload.trim() == "[DONE]" {
+                    break;
+                }
+                if let Ok(v) = serde_json::from_str::<serde_json::Value>(payload) {
+                    if let Some(token) = delta_text(&v) {
+                        yield Ok(token.to_string());
+                    }
+                    if matches!(ToolEvent::of(&v), Some(ToolEvent::MessageStop)) {
+                        break;
                     }
                 }
             }
-            if !any_parsed {
-                // Fallback: include full slice to avoid losing info
-                items.push(StreamItem::Text(TextContent { text: json_slice.to_string() }));
+            sse_event.clear();
+        }
+    }
+}
+
+/// `sse_text_deltas` for byte chunks handed over one at a time, e.g. by a stream that
+/// passes them on unchanged (see `interceptors::stack`)
+#[derive(Debug, Default)]
+pub(crate) struct SseTextDecoder {
+    /// Bytes after the last complete line
+    pending: Vec<u8>,
+    event: String,
+    done: bool,
+}
+
+impl SseTextDecoder {
+    /// Text deltas of the events completed by `bytes`
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut deltas = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            if !self.done {
+                let line = String::from_utf8_lossy(&line);
+                self.line(line.trim_end_matches(['\n', '\r']), &mut deltas);
             }
         }
+        deltas
+    }
 
-        cursor = end;
+    fn line(&mut self, line: &str, deltas: &mut Vec<String>) {
+        if !line.is_empty() {
+            if line.starts_with("data:") {
+                if !self.event.is_empty() { self.event.push('\n'); }
+                self.event.push_str(line);
+            }
+            return;
+        }
+        if let Some(payload) = self.event.strip_prefix("data: ") {
+            if payload.trim() == "[DONE]" {
+                self.done = true;
+            } else if let Ok(v) = serde_json::from_str::<serde_json::Value>(payload) {
+                if let Some(token) = delta_text(&v) {
+                    deltas.push(token.to_string());
+                }
+                self.done = matches!(ToolEvent::of(&v), Some(ToolEvent::MessageStop));
+            }
+        }
+        self.event.clear();
     }
+}
+
+/// Anthropic SSE events that frame native tool calls and end the message
+enum ToolEvent {
+    /// `content_block_start` of a `tool_use` block
+    Start { id: String, name: String },
+    /// `input_json_delta` fragment of the open block's input
+    InputDelta(String),
+    /// `content_block_stop`
+    Stop,
+    /// `message_stop`
+    MessageStop,
+}
 
-    // Emit trailing text
-    if cursor < raw.len() {
-        let text_slice = &raw[cursor..];
-        let trimmed = text_slice.trim();
-        if !trimmed.is_empty() {
-            items.push(StreamItem::Text(TextContent { text: text_slice.to_string() }));
+impl ToolEvent {
+    fn of(v: &serde_json::Value) -> Option<Self> {
+        match v.get("type")?.as_str()? {
+            "content_block_start" => {
+                let block = v.get("content_block").filter(|b| b["type"] == "tool_use")?;
+                Some(Self::Start {
+                    id: block.get("id")?.as_str()?.to_string(),
+                    name: block.get("name")?.as_str()?.to_string(),
+                })
+            }
+            "content_block_delta" => {
+                let delta = v.get("delta").filter(|d| d["type"] == "input_json_delta")?;
+                Some(Self::InputDelta(delta.get("partial_json")?.as_str()?.to_string()))
+            }
+            "content_block_stop" => Some(Self::Stop),
+            "message_stop" => Some(Self::MessageStop),
+            _ => None,
         }
     }
+}
 
-    items
+/// `(index, id, name, arguments fragment)` of each `choices[0].delta.tool_calls` entry;
+/// the id and name only come with a call's first fragment
+fn tool_call_deltas(v: &serde_json::Value) -> Vec<(u64, Option<&str>, Option<&str>, &str)> {
+    let Some(deltas) = v.get("choices").and_then(|c| c.get(0)).and_then(|c0| c0.get("delta"))
+        .and_then(|d| d.get("tool_calls")).and_then(|t| t.as_array()) else { return Vec::new() };
+    deltas.iter().filter_map(|delta| {
+        let function = delta.get("function");
+        Some((
+            delta.get("index")?.as_u64()?,
+            delta.get("id").and_then(|id| id.as_str()),
+            function.and_then(|f| f.get("name")).and_then(|n| n.as_str()),
+            function.and_then(|f| f.get("arguments")).and_then(|a| a.as_str()).unwrap_or(""),
+        ))
+    }).collect()
+}
+
+/// A finished tool call as a stream item: `Data(T)`, else `Text` (see `tool_call_data`)
+fn tool_call_item<T: DeserializeOwned + JsonSchema>(id: String, name: String, input: &str) -> StreamItem<T> {
+    match tool_call_data::<T>(id, name, input) {
+        Ok(data) => StreamItem::Data(data),
+        Err(text) => StreamItem::Text(TextContent { text, block: None }),
+    }
+}
+
+/// A finished tool call as `T` (see `ToolCall::to_data`), or the text to keep instead
+/// when its input is not valid JSON or does not fit `T`
+pub(crate) fn tool_call_data<T: DeserializeOwned>(id: String, name: String, input: &str) -> Result<T, String> {
+    let input = if input.trim().is_empty() { "{}" } else { input };
+    let Ok(input) = serde_json::from_str::<serde_json::Value>(input) else {
+        warn!(target = "semantic_query::json_stream", tool = %name, "tool call input is not valid JSON");
+        return Err(input.to_string());
+    };
+    let call = ToolCall { id, name, input };
+    call.to_data::<T>().ok_or_else(|| {
+        debug!(target = "semantic_query::json_stream", tool = %call.name, "tool call does not match the target type");
+        call.input.to_string()
+    })
+}
+
+/// Tracks a root-level array whose elements are being emitted as they close
+#[derive(Debug, Default)]
+struct ArrayProgress {
+    /// Start of the array being streamed
+    streaming: Option<usize>,
+    /// Start of an array whose first element did not match `T`
+    declined: Option<usize>,
+    emitted: usize,
+}
+
+/// Outcome of `ArrayProgress::advance`
+struct ArrayStep<T: JsonSchema> {
+    /// Set when streaming of an array starting here began in this step
+    started_at: Option<usize>,
+    items: Vec<StreamItem<T>>,
+    /// Offset just past the last element emitted in this step
+    emitted_to: Option<usize>,
+}
+
+impl ArrayProgress {
+    fn is_streaming(&self) -> bool {
+        self.streaming.is_some()
+    }
+
+    /// Emit newly closed elements of the still-open root array `open`
+    fn advance<T>(&mut self, text: &str, open: Option<(usize, &[ObjCoords])>) -> ArrayStep<T>
+    where
+        T: DeserializeOwned + JsonSchema,
+    {
+        let mut step = ArrayStep { started_at: None, items: Vec::new(), emitted_to: None };
+        let Some((start, elements)) = open else { return step };
+        if self.streaming != Some(start) && self.declined != Some(start) {
+            let Some(first) = elements.first() else { return step };
+            if parse_candidate::<T>(slice(text, first)).is_none() {
+                self.declined = Some(start);
+                return step;
+            }
+            debug!(target = "semantic_query::json_stream", start, "streaming root array elements");
+            *self = Self { streaming: Some(start), declined: None, emitted: 0 };
+            step.started_at = Some(start);
+        }
+        if self.streaming == Some(start) {
+            let fresh = &elements[self.emitted..];
+            step.items = fresh.iter().map(|e| element_item(text, e)).collect();
+            step.emitted_to = fresh.last().map(|e| e.end + 1);
+            self.emitted = elements.len();
+        }
+        step
+    }
+
+    /// Items still owed for a root that just closed, or `None` if it was not being streamed
+    fn finish<T>(&mut self, text: &str, node: &ObjCoords) -> Option<Vec<StreamItem<T>>>
+    where
+        T: DeserializeOwned + JsonSchema,
+    {
+        if self.declined == Some(node.start) {
+            self.declined = None;
+        }
+        if self.streaming != Some(node.start) {
+            return None;
+        }
+        let rest = node.children.get(self.emitted..).unwrap_or_default();
+        let items = rest.iter().map(|e| element_item(text, e)).collect();
+        *self = Self::default();
+        Some(items)
+    }
+
+    /// Account for `n` bytes removed from the front of the buffer
+    fn shift(&mut self, n: usize) {
+        self.streaming = self.streaming.map(|s| s.saturating_sub(n));
+        self.declined = self.declined.map(|s| s.saturating_sub(n));
+    }
+}
+
+fn slice<'a>(text: &'a str, node: &ObjCoords) -> &'a str {
+    &text[node.start..node.end + 1]
+}
+
+/// An array element as `Data(T)`, or as text when it does not match
+fn element_item<T>(text: &str, node: &ObjCoords) -> StreamItem<T>
+where
+    T: DeserializeOwned + JsonSchema,
+{
+    let json = slice(text, node);
+    match parse_candidate::<T>(json) {
+        Some(v) => StreamItem::Data(v),
+        None => StreamItem::Text(TextContent { text: json.to_string(), block: None }),
+    }
 }
 
 /// Stream `StreamItem<T>` from an `AsyncRead` by incrementally parsing JSON
 /// structures and interleaving free-form text between them.
 ///
-/// Use this for realtime toolcalls or progressive UIs.
-pub fn stream_from_async_read<R, T>(mut reader: R, buf_size: usize) -> impl Stream<Item = StreamItem<T>>
+/// Use this for realtime toolcalls or progressive UIs. Reads grow from 256 bytes as
+/// `BufferPolicy::default()` describes, to at most `buf_size` (at least 1 KiB).
+pub fn stream_from_async_read<R, T>(reader: R, buf_size: usize) -> impl Stream<Item = StreamItem<T>>
 where
     R: AsyncRead + Unpin + Send + 'static,
     T: DeserializeOwned + JsonSchema + Send + 'static,
 {
-    stream! {
-        let mut parser = crate::json_utils::JsonStreamParser::new();
+    stream_from_async_read_with(reader, buf_size, StreamOptions::default())
+}
+
+/// `stream_from_async_read` with explicit `StreamOptions`. This stream carries no errors,
+/// so exceeding `StreamOptions::limits` logs a warning and ends it.
+pub fn stream_from_async_read_with<R, T>(reader: R, buf_size: usize, options: StreamOptions) -> impl Stream<Item = StreamItem<T>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    stream_from_async_read_normalized(reader, buf_size, options, Normalizers::default())
+}
+
+/// `stream_from_async_read` over a futures-io reader (async-std, smol, `futures::io`)
+pub fn stream_from_futures_read<R, T>(reader: R, buf_size: usize) -> impl Stream<Item = StreamItem<T>>
+where
+    R: futures_io::AsyncRead + Unpin + Send + 'static,
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    stream_from_futures_read_with(reader, buf_size, StreamOptions::default())
+}
+
+/// `stream_from_async_read_with` over a futures-io reader
+pub fn stream_from_futures_read_with<R, T>(reader: R, buf_size: usize, options: StreamOptions) -> impl Stream<Item = StreamItem<T>>
+where
+    R: futures_io::AsyncRead + Unpin + Send + 'static,
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    stream_from_async_read_with(crate::runtime::compat::from_futures_read(reader), buf_size, options)
+}
+
+/// `stream_from_async_read_with`, rewriting each chunk read with `normalizers` first
+pub fn stream_from_async_read_normalized<R, T>(mut reader: R, buf_size: usize, options: StreamOptions, normalizers: Normalizers) -> impl Stream<Item = StreamItem<T>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    let fidelity = options.text_fidelity;
+    let stop_after_data = options.stop_after_data;
+    let scope = options.detection_scope.clone();
+    let items = stream! {
+        let mut parser = JsonStreamParser::with_limits(options.limits).with_scope(scope);
+        let mut array = ArrayProgress::default();
         let mut accum = String::new();
         let mut last_offset: usize = 0;
-        let mut buf = vec![0u8; buf_size.max(1024)];
-        loop {
-            match reader.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Ok(s) = std::str::from_utf8(&buf[..n]) {
-                        let old_len = accum.len();
-                        accum.push_str(s);
-                        for node in parser.feed(s) {
-                            // Emit text before node
-                            if node.start > last_offset && node.start <= accum.len() {
-                                let text_slice = &accum[last_offset..node.start];
-                                if !text_slice.trim().is_empty() {
-                                    yield StreamItem::Text(TextContent { text: text_slice.to_string() });
-                                }
-                            }
+        let mut buf = ReadBuffer::new(options.buffer, buf_size);
+        while let Some(s) = buf.read(&mut reader).await {
+            let s = normalizers.apply(&s);
+            accum.push_str(&s);
+            for node in parser.feed(&s) {
+                if let Some(rest) = array.finish::<T>(&accum, &node) {
+                    for item in rest { yield item; }
+                    last_offset = node.end + 1;
+                    continue;
+                }
 
-                            // Process node slice
-                            let end = node.end + 1;
-                            if end <= accum.len() {
-                                let json_slice = &accum[node.start..end];
-                                let mapped: Vec<ParsedOrUnknown<T>> = deserialize_stream_map::<T>(json_slice);
-                                if mapped.is_empty() {
-                                    yield StreamItem::Text(TextContent { text: json_slice.to_string() });
-                                } else {
-                                    let mut any = false;
-                                    for item in mapped {
-                                        match item {
-                                            ParsedOrUnknown::Parsed(v) => { any = true; yield StreamItem::Data(v); }
-                                            ParsedOrUnknown::Unknown(u) => {
-                                                let u_end = u.end + 1;
-                                                if u_end <= json_slice.len() && u.start < u_end {
-                                                    let sub = &json_slice[u.start..u_end];
-                                                    yield StreamItem::Text(TextContent { text: sub.to_string() });
-                                                }
-                                            }
-                                        }
-                                    }
-                                    if !any { yield StreamItem::Text(TextContent { text: json_slice.to_string() }); }
-                                }
-                                last_offset = end;
-                            }
-                        }
-                        let _ = old_len;
+                // Emit text before node
+                if node.start > last_offset && node.start <= accum.len() {
+                    let text_slice = &accum[last_offset..node.start];
+                    if fidelity.keeps(text_slice) {
+                        yield StreamItem::Text(TextContent { text: text_slice.to_string(), block: None });
                     }
                 }
-                Err(_) => break,
+
+                // Process node in place
+                let end = node.end + 1;
+                if end <= accum.len() {
+                    for item in node_items::<T>(&accum, &node, fidelity) { yield item; }
+                    last_offset = end;
+                }
+            }
+
+            if let Some(err) = parser.take_error() {
+                warn!(target = "semantic_query::json_stream", error = %err, "ending stream: response exceeds parse limits");
+                return;
+            }
+
+            if options.stream_array_elements {
+                let step = array.advance::<T>(&accum, parser.open_array());
+                if let Some(start) = step.started_at {
+                    let text_slice = &accum[last_offset.min(start)..start];
+                    if fidelity.keeps(text_slice) {
+                        yield StreamItem::Text(TextContent { text: text_slice.to_string(), block: None });
+                    }
+                    last_offset = start;
+                }
+                for item in step.items { yield item; }
+                if let Some(to) = step.emitted_to { last_offset = to; }
             }
         }
         // Emit trailing text
         if last_offset < accum.len() {
             let text_slice = &accum[last_offset..];
-            if !text_slice.trim().is_empty() {
-                yield StreamItem::Text(TextContent { text: text_slice.to_string() });
+            if fidelity.keeps(text_slice) {
+                yield StreamItem::Text(TextContent { text: text_slice.to_string(), block: None });
             }
         }
-    }
+    };
+    let items = markdown_items(items, options, |item| match item {
+        StreamItem::Text(text) => TextPart::Text(text, Box::new(StreamItem::Text)),
+        token @ StreamItem::Token(_) => TextPart::Token(token),
+        other => TextPart::Other(other),
+    });
+    end_after(items, move |item| match item {
+        StreamItem::Data(_) if stop_after_data => Some(StreamItem::Finished { reason: EARLY_STOP }),
+        _ => None,
+    })
 }
 
 /// Stream `StreamItem<T>` from a bytes stream (such as from an HTTP response).
@@ -195,13 +1001,28 @@ where
 /// with proper error handling. It automatically handles UTF-8 conversion and incremental
 /// JSON parsing without exposing low-level buffer management.
 pub fn stream_from_bytes<T>(
-    byte_stream: Pin<Box<dyn Stream<Item = Result<Bytes, crate::error::AIError>> + Send>>
+    byte_stream: RawByteStream
 ) -> impl Stream<Item = Result<StreamItem<T>, crate::error::QueryResolverError>>
 where
     T: DeserializeOwned + JsonSchema + Send + 'static,
 {
-    stream! {
-        let mut parser = crate::json_utils::JsonStreamParser::new();
+    stream_from_bytes_with(byte_stream, StreamOptions::default())
+}
+
+/// `stream_from_bytes` with explicit `StreamOptions`
+pub fn stream_from_bytes_with<T>(
+    byte_stream: RawByteStream,
+    options: StreamOptions,
+) -> impl Stream<Item = Result<StreamItem<T>, crate::error::QueryResolverError>>
+where
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    let fidelity = options.text_fidelity;
+    let stop_after_data = options.stop_after_data;
+    let scope = options.detection_scope.clone();
+    let items = stream! {
+        let mut parser = JsonStreamParser::with_limits(options.limits).with_scope(scope);
+        let mut array = ArrayProgress::default();
         let mut accum = String::new();
         let mut last_offset: usize = 0;
         
@@ -216,45 +1037,46 @@ where
                             
                             // Process any complete JSON structures
                             for node in parser.feed(s) {
+                                if let Some(rest) = array.finish::<T>(&accum, &node) {
+                                    for item in rest { yield Ok(item); }
+                                    last_offset = node.end + 1;
+                                    continue;
+                                }
+
                                 // Emit text before node
                                 if node.start > last_offset && node.start <= accum.len() {
                                     let text_slice = &accum[last_offset..node.start];
-                                    if !text_slice.trim().is_empty() {
-                                        yield Ok(StreamItem::Text(TextContent { text: text_slice.to_string() }));
+                                    if fidelity.keeps(text_slice) {
+                                        yield Ok(StreamItem::Text(TextContent { text: text_slice.to_string(), block: None }));
                                     }
                                 }
 
-                                // Process node slice
+                                // Process node in place
                                 let end = node.end + 1;
                                 if end <= accum.len() {
-                                    let json_slice = &accum[node.start..end];
-                                    let mapped: Vec<ParsedOrUnknown<T>> = deserialize_stream_map::<T>(json_slice);
-                                    if mapped.is_empty() {
-                                        yield Ok(StreamItem::Text(TextContent { text: json_slice.to_string() }));
-                                    } else {
-                                        let mut any_parsed = false;
-                                        for item in mapped {
-                                            match item {
-                                                ParsedOrUnknown::Parsed(v) => { 
-                                                    any_parsed = true; 
-                                                    yield Ok(StreamItem::Data(v)); 
-                                                }
-                                                ParsedOrUnknown::Unknown(u) => {
-                                                    let u_end = u.end + 1;
-                                                    if u_end <= json_slice.len() && u.start < u_end {
-                                                        let sub = &json_slice[u.start..u_end];
-                                                        yield Ok(StreamItem::Text(TextContent { text: sub.to_string() }));
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        if !any_parsed { 
-                                            yield Ok(StreamItem::Text(TextContent { text: json_slice.to_string() })); 
-                                        }
-                                    }
+                                    for item in node_items::<T>(&accum, &node, fidelity) { yield Ok(item); }
                                     last_offset = end;
                                 }
                             }
+
+                            if let Some(err) = parser.take_error() {
+                                yield Err(crate::error::QueryResolverError::DataExtraction(err.into()));
+                                return;
+                            }
+
+                            // Emit array elements that closed in this chunk
+                            if options.stream_array_elements {
+                                let step = array.advance::<T>(&accum, parser.open_array());
+                                if let Some(start) = step.started_at {
+                                    let text_slice = &accum[last_offset.min(start)..start];
+                                    if fidelity.keeps(text_slice) {
+                                        yield Ok(StreamItem::Text(TextContent { text: text_slice.to_string(), block: None }));
+                                    }
+                                    last_offset = start;
+                                }
+                                for item in step.items { yield Ok(item); }
+                                if let Some(to) = step.emitted_to { last_offset = to; }
+                            }
                         }
                         Err(utf8_err) => {
                             yield Err(crate::error::QueryResolverError::Ai(
@@ -274,11 +1096,20 @@ where
         // Emit any remaining text
         if last_offset < accum.len() {
             let text_slice = &accum[last_offset..];
-            if !text_slice.trim().is_empty() {
-                yield Ok(StreamItem::Text(TextContent { text: text_slice.to_string() }));
+            if fidelity.keeps(text_slice) {
+                yield Ok(StreamItem::Text(TextContent { text: text_slice.to_string(), block: None }));
             }
         }
-    }
+    };
+    let items = markdown_items(items, options, |item| match item {
+        Ok(StreamItem::Text(text)) => TextPart::Text(text, Box::new(|text| Ok(StreamItem::Text(text)))),
+        token @ Ok(StreamItem::Token(_)) => TextPart::Token(token),
+        other => TextPart::Other(other),
+    });
+    end_after(items, move |item| match item {
+        Ok(StreamItem::Data(_)) if stop_after_data => Some(Ok(StreamItem::Finished { reason: EARLY_STOP })),
+        _ => None,
+    })
 }
 
 /// Stream `StreamItem<T>` from an SSE bytes stream with proper token aggregation.
@@ -287,12 +1118,50 @@ where
 /// into stream items. It handles the complexity of SSE parsing and JSON extraction
 /// so users get clean Text/Data events.
 pub fn stream_from_sse_bytes<T>(
-    byte_stream: Pin<Box<dyn Stream<Item = Result<Bytes, crate::error::AIError>> + Send>>
+    byte_stream: RawByteStream
 ) -> impl Stream<Item = Result<StreamItem<T>, crate::error::QueryResolverError>>
 where
     T: DeserializeOwned + JsonSchema + Send + 'static,
 {
-    stream! {
+    stream_from_sse_bytes_with(byte_stream, StreamOptions::default())
+}
+
+/// `stream_from_sse_bytes` with explicit `StreamOptions`
+pub fn stream_from_sse_bytes_with<T>(
+    byte_stream: RawByteStream,
+    options: StreamOptions,
+) -> impl Stream<Item = Result<StreamItem<T>, crate::error::QueryResolverError>>
+where
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    stream_from_sse_bytes_timed(byte_stream, options).map(|item| item.map(|timed| timed.item))
+}
+
+/// `stream_from_sse_bytes_with`, with each item wrapped in `Timed` metadata
+pub fn stream_from_sse_bytes_timed<T>(
+    byte_stream: RawByteStream,
+    options: StreamOptions,
+) -> impl Stream<Item = Result<Timed<StreamItem<T>>, crate::error::QueryResolverError>>
+where
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    stream_from_sse_bytes_normalized(byte_stream, options, Normalizers::default())
+}
+
+/// `stream_from_sse_bytes_timed`, rewriting each text delta with `normalizers` before it
+/// is emitted as a `Token` and scanned
+pub fn stream_from_sse_bytes_normalized<T>(
+    byte_stream: RawByteStream,
+    options: StreamOptions,
+    normalizers: Normalizers,
+) -> impl Stream<Item = Result<Timed<StreamItem<T>>, crate::error::QueryResolverError>>
+where
+    T: DeserializeOwned + JsonSchema + Send + 'static,
+{
+    let fidelity = options.text_fidelity;
+    let stop_after_data = options.stop_after_data;
+    let scope = options.detection_scope.clone();
+    let items = stream! {
         use tokio_util::io::StreamReader;
         
         // Convert bytes stream to AsyncRead
@@ -306,53 +1175,108 @@ where
         let mut br = BufReader::new(reader).lines();
         let mut sse_event = String::new();
         let mut text_buf = String::new();
+        let mut array = ArrayProgress::default();
+        // Detection scope as of the start of `text_buf`
+        let mut scope = ScopeTracker::new(scope);
+        let mut clock = StreamClock::start();
+        let mut event_id: Option<String> = None;
+        let mut tool: Option<(String, String, String)> = None;
+        let mut calls: BTreeMap<u64, (String, String, String)> = BTreeMap::new();
         
         while let Ok(Some(line)) = br.next_line().await {
             if line.is_empty() {
                 // process event
                 if let Some(payload) = sse_event.strip_prefix("data: ") {
                     if payload.trim() == "[DONE]" {
-                        let tail = text_buf.trim();
-                        if !tail.is_empty() { 
-                            yield Ok(StreamItem::Text(TextContent { text: tail.to_string() })); 
-                        }
                         break;
                     }
                     if let Ok(v) = serde_json::from_str::<serde_json::Value>(payload) {
-                        if let Some(token) = v.get("choices").and_then(|c| c.get(0))
-                            .and_then(|c0| c0.get("delta")).and_then(|d| d.get("content")).and_then(|c| c.as_str())
-                        {
-                            // Emit raw token for live rendering and accumulate for parsing
-                            yield Ok(StreamItem::Token(token.to_string()));
-                            text_buf.push_str(token);
+                        clock.event_id = event_id.take()
+                            .or_else(|| v.get("id").and_then(|id| id.as_str()).map(str::to_string));
+                        match ToolEvent::of(&v) {
+                            Some(ToolEvent::Start { id, name }) => {
+                                // Text before the block is complete; keep it ahead of the call
+                                if let Some(tail) = fidelity.chunk(&text_buf) {
+                                    yield Ok(clock.stamp(StreamItem::Text(TextContent { text: tail.to_string(), block: None })));
+                                }
+                                scope.advance(&text_buf);
+                                text_buf.clear();
+                                array = ArrayProgress::default();
+                                tool = Some((id, name, String::new()));
+                            }
+                            Some(ToolEvent::InputDelta(partial)) => {
+                                if let Some((_, _, input)) = tool.as_mut() { input.push_str(&partial); }
+                            }
+                            Some(ToolEvent::Stop) => if let Some((id, name, input)) = tool.take() {
+                                clock.event_id = Some(id.clone());
+                                yield Ok(clock.stamp(tool_call_item::<T>(id, name, &input)));
+                            },
+                            Some(ToolEvent::MessageStop) => break,
+                            None => {}
+                        }
+                        if let Some(token) = delta_text(&v) {
+                            // Emit the token for live rendering and accumulate for parsing
+                            let token = normalizers.apply(token).into_owned();
+                            clock.tokens += 1;
+                            yield Ok(clock.stamp(StreamItem::Token(token.clone())));
+                            text_buf.push_str(&token);
 
                             // detect completed JSON for T
-                            let coords = find_json_structures(&text_buf);
+                            let mut scan = JsonStreamParser::with_limits(options.limits).resuming(scope.clone());
+                            let coords = scan.feed(&text_buf);
+                            if let Some(err) = scan.take_error() {
+                                yield Err(crate::error::QueryResolverError::DataExtraction(err.into()));
+                                return;
+                            }
                             let mut consumed_up_to = 0usize;
                             for node in coords {
                                 let end = node.end.saturating_add(1);
+                                if let Some(rest) = array.finish::<T>(&text_buf, &node) {
+                                    for item in rest { yield Ok(clock.stamp(item)); }
+                                    consumed_up_to = consumed_up_to.max(end);
+                                    continue;
+                                }
                                 let slice = &text_buf[node.start..end];
                                 if let Ok(item) = serde_json::from_str::<T>(slice) {
-                                    if node.start > 0 {
-                                        let chunk = text_buf[..node.start].trim();
-                                        if !chunk.is_empty() { 
-                                            yield Ok(StreamItem::Text(TextContent { text: chunk.to_string() })); 
-                                        }
+                                    if let Some(chunk) = fidelity.chunk(&text_buf[consumed_up_to.min(node.start)..node.start]) {
+                                        yield Ok(clock.stamp(StreamItem::Text(TextContent { text: chunk.to_string(), block: None })));
                                     }
-                                    yield Ok(StreamItem::Data(item));
+                                    yield Ok(clock.stamp(StreamItem::Data(item)));
                                     consumed_up_to = consumed_up_to.max(end);
                                 }
                             }
-                            if consumed_up_to > 0 { text_buf.drain(..consumed_up_to); }
-
-                            // Paragraph flush
-                            if let Some(idx) = text_buf.find("\n\n") {
-                                let (chunk, rest) = text_buf.split_at(idx);
-                                let chunk = chunk.trim();
-                                if !chunk.is_empty() { 
-                                    yield Ok(StreamItem::Text(TextContent { text: chunk.to_string() })); 
+
+                            // Emit elements of a root array that is still open
+                            if options.stream_array_elements {
+                                let step = array.advance::<T>(&text_buf, scan.open_array());
+                                if let Some(start) = step.started_at {
+                                    if let Some(chunk) = fidelity.chunk(&text_buf[consumed_up_to.min(start)..start]) {
+                                        yield Ok(clock.stamp(StreamItem::Text(TextContent { text: chunk.to_string(), block: None })));
+                                    }
+                                    consumed_up_to = consumed_up_to.max(start);
+                                }
+                                for item in step.items { yield Ok(clock.stamp(item)); }
+                            }
+                            if consumed_up_to > 0 {
+                                scope.advance(&text_buf[..consumed_up_to]);
+                                text_buf.drain(..consumed_up_to);
+                                array.shift(consumed_up_to);
+                            }
+
+                            // Paragraph flush (not inside an array being streamed)
+                            if let Some(idx) = text_buf.find("\n\n").filter(|_| !array.is_streaming()) {
+                                // Exact text keeps the blank line with the paragraph it ends
+                                let (chunk, rest) = text_buf.split_at(idx + 2);
+                                let chunk = match fidelity {
+                                    TextFidelity::Exact => fidelity.chunk(chunk),
+                                    TextFidelity::Trimmed => fidelity.chunk(&chunk[..idx]),
+                                };
+                                if let Some(chunk) = chunk {
+                                    yield Ok(clock.stamp(StreamItem::Text(TextContent { text: chunk.to_string(), block: None })));
                                 }
-                                text_buf = rest[2..].to_string();
+                                scope.advance(&text_buf[..idx + 2]);
+                                text_buf = rest.to_string();
+                                array.shift(idx + 2);
                             }
 
                             // Finish flush only when finish_reason is a non-null string
@@ -362,20 +1286,84 @@ where
                                 .and_then(|fr| fr.as_str())
                                 .is_some()
                             {
-                                let tail = text_buf.trim();
-                                if !tail.is_empty() { 
-                                    yield Ok(StreamItem::Text(TextContent { text: tail.to_string() })); 
+                                if let Some(tail) = fidelity.chunk(&text_buf) {
+                                    yield Ok(clock.stamp(StreamItem::Text(TextContent { text: tail.to_string(), block: None })));
                                 }
+                                scope.advance(&text_buf);
                                 text_buf.clear();
+                                array = ArrayProgress::default();
+                            }
+                        }
+
+                        // OpenAI function calls arrive as argument fragments keyed by index
+                        for (index, id, name, fragment) in tool_call_deltas(&v) {
+                            let call = calls.entry(index).or_default();
+                            if let Some(id) = id { call.0 = id.to_string(); }
+                            if let Some(name) = name { call.1 = name.to_string(); }
+                            call.2.push_str(fragment);
+                        }
+                        if !calls.is_empty() && v.get("choices").and_then(|c| c.get(0))
+                            .and_then(|c0| c0.get("finish_reason")).is_some_and(|fr| fr.is_string())
+                        {
+                            if let Some(tail) = fidelity.chunk(&text_buf) {
+                                yield Ok(clock.stamp(StreamItem::Text(TextContent { text: tail.to_string(), block: None })));
+                            }
+                            scope.advance(&text_buf);
+                            text_buf.clear();
+                            array = ArrayProgress::default();
+                            for (_, (id, name, arguments)) in std::mem::take(&mut calls) {
+                                clock.event_id = Some(id.clone());
+                                yield Ok(clock.stamp(tool_call_item::<T>(id, name, &arguments)));
                             }
                         }
                     }
                 }
                 sse_event.clear();
+                event_id = None;
+            } else if let Some(id) = line.strip_prefix("id:") {
+                event_id = Some(id.trim().to_string());
+            } else if line.starts_with("event:") {
+                // Anthropic names each event; the payload's `type` repeats the name
             } else {
                 if !sse_event.is_empty() { sse_event.push('\n'); }
                 sse_event.push_str(&line);
             }
         }
-    }
+        // Ended by `[DONE]`, `message_stop`, or the connection closing without either
+        // (e.g. a server that stops at a stop sequence and hangs up)
+        if let Some(tail) = fidelity.chunk(&text_buf) {
+            yield Ok(clock.stamp(StreamItem::Text(TextContent { text: tail.to_string(), block: None })));
+        }
+        for (_, (id, name, arguments)) in std::mem::take(&mut calls) {
+            clock.event_id = Some(id.clone());
+            yield Ok(clock.stamp(tool_call_item::<T>(id, name, &arguments)));
+        }
+    };
+    let deadline = options.deadline;
+    let items = markdown_items(items, options, |item| match item {
+        Ok(Timed { item: StreamItem::Text(text), received_at, elapsed, tokens, event_id }) => TextPart::Text(text, Box::new(move |text| Ok(Timed {
+            item: StreamItem::Text(text),
+            received_at,
+            elapsed,
+            tokens,
+            event_id: event_id.clone(),
+        }))),
+        token @ Ok(Timed { item: StreamItem::Token(_), .. }) => TextPart::Token(token),
+        other => TextPart::Other(other),
+    });
+    let items = end_after(items, move |item| match item {
+        Ok(timed) if stop_after_data && matches!(timed.item, StreamItem::Data(_)) => Some(Ok(Timed {
+            item: StreamItem::Finished { reason: EARLY_STOP },
+            received_at: timed.received_at,
+            elapsed: timed.elapsed,
+            tokens: timed.tokens,
+            event_id: timed.event_id.clone(),
+        })),
+        _ => None,
+    });
+    let span = tracing::info_span!(target: "semantic_query::sse", "sse_decode", items_emitted = tracing::field::Empty);
+    // Tokens are deltas, not items
+    crate::telemetry::traced_stream(within_deadline(items, deadline), span, |item| {
+        matches!(item, Ok(timed) if !matches!(timed.item, StreamItem::Token(_)))
+    })
 }
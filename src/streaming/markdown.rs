@@ -0,0 +1,236 @@
+//! Markdown-aware segmentation of response text.
+//!
+//! Models answer in markdown, and the stream adapters cut `Text` items wherever a JSON
+//! structure or a paragraph break happens to fall. With `StreamOptions::markdown_blocks`
+//! each `Text` item is one markdown block instead (a heading, a list item, a paragraph,
+//! a fenced code block or a quote), tagged with `TextContent::block`, so a UI can render
+//! each item as it arrives without re-parsing what came before.
+//!
+//! ```
+//! use semantic_query::streaming::markdown::{segment_markdown, MarkdownBlock};
+//! use semantic_query::streaming::TextFidelity;
+//!
+//! let blocks = segment_markdown("# Plan\n\n- one\n- two", TextFidelity::Trimmed);
+//! assert_eq!(blocks[0].block, Some(MarkdownBlock::Heading { level: 1 }));
+//! assert_eq!(blocks[2].text, "- two");
+//! ```
+
+use std::ops::Range;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{TextContent, TextFidelity};
+
+/// Kind of markdown block a `TextContent` holds
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MarkdownBlock {
+    /// `#` to `######`
+    Heading { level: u8 },
+    Paragraph,
+    /// One item of a bulleted (`-`, `*`, `+`) or numbered (`1.`, `1)`) list, with its
+    /// continuation lines
+    ListItem { ordered: bool },
+    /// A fenced code block, fences included; `language` is the fence's info string
+    CodeBlock { language: Option<String> },
+    /// Consecutive `>` lines
+    Quote,
+}
+
+/// Split `text` into markdown blocks. With `TextFidelity::Exact` the blocks keep the
+/// blank lines after them and concatenate back to `text`; otherwise they are trimmed.
+pub fn segment_markdown(text: &str, fidelity: TextFidelity) -> Vec<TextContent> {
+    let mut segmenter = MarkdownSegmenter::new(fidelity);
+    let mut blocks = segmenter.push(text);
+    blocks.extend(segmenter.flush());
+    blocks
+}
+
+/// Incremental `segment_markdown`: text goes in piece by piece, blocks come out once
+/// the text after them shows they are complete
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownSegmenter {
+    buf: String,
+    fidelity: TextFidelity,
+    /// Language of a code block left open by `flush`; text pushed afterwards continues it
+    /// until its closing fence
+    open_code: Option<Option<String>>,
+}
+
+impl MarkdownSegmenter {
+    pub fn new(fidelity: TextFidelity) -> Self {
+        Self { fidelity, ..Self::default() }
+    }
+
+    /// Add `text` and return the blocks it completed. Under `TextFidelity::Trimmed` a
+    /// piece that follows one without a trailing newline starts a new paragraph, since
+    /// trimmed pieces have lost the blank line that separated them.
+    pub fn push(&mut self, text: &str) -> Vec<TextContent> {
+        if self.fidelity == TextFidelity::Trimmed && !self.buf.is_empty() && !self.buf.ends_with('\n') {
+            self.buf.push_str("\n\n");
+        }
+        self.buf.push_str(text);
+        let mut blocks = parse(&self.buf, self.open_code.clone());
+        let ends_blank = self.buf.trim_end_matches([' ', '\t']).ends_with("\n\n");
+        let keep = match blocks.last() {
+            Some(last) if last.open || !ends_blank => 1,
+            _ => 0,
+        };
+        let done = blocks.len().saturating_sub(keep);
+        if done == 0 {
+            return Vec::new();
+        }
+        let end = blocks.get(done).map_or(self.buf.len(), |next| next.span.start);
+        blocks.truncate(done);
+        let out = self.emit(blocks, end);
+        self.buf.drain(..end);
+        self.open_code = None;
+        out
+    }
+
+    /// Return every block still held, complete or not, e.g. before a `Data` item. A code
+    /// block left open here is continued by the next `push`.
+    pub fn flush(&mut self) -> Vec<TextContent> {
+        let blocks = parse(&self.buf, self.open_code.take());
+        self.open_code = match blocks.last() {
+            Some(Block { kind: MarkdownBlock::CodeBlock { language }, open: true, .. }) => Some(language.clone()),
+            _ => None,
+        };
+        let out = self.emit(blocks, self.buf.len());
+        self.buf.clear();
+        out
+    }
+
+    /// Text of `blocks`, the last of which ends at `end`
+    fn emit(&self, blocks: Vec<Block>, end: usize) -> Vec<TextContent> {
+        if blocks.is_empty() {
+            // Only blank lines; exact text keeps them, outside any block
+            return match self.fidelity {
+                TextFidelity::Exact if end > 0 => vec![TextContent { text: self.buf[..end].to_string(), block: None }],
+                _ => Vec::new(),
+            };
+        }
+        let starts: Vec<usize> = blocks.iter().skip(1).map(|b| b.span.start).chain([end]).collect();
+        blocks.into_iter().zip(starts).enumerate().filter_map(|(i, (block, next))| {
+            let text = match self.fidelity {
+                // Blocks tile the text, so leading blank lines go with the first one
+                TextFidelity::Exact => &self.buf[if i == 0 { 0 } else { block.span.start }..next],
+                TextFidelity::Trimmed => {
+                    let text = &self.buf[block.span.clone()];
+                    let text = match block.kind {
+                        // Keep the indentation of the first line of code
+                        MarkdownBlock::CodeBlock { .. } => text.trim_start_matches(['\r', '\n']).trim_end(),
+                        _ => text.trim(),
+                    };
+                    if text.is_empty() { return None; }
+                    text
+                }
+            };
+            Some(TextContent { text: text.to_string(), block: Some(block.kind) })
+        }).collect()
+    }
+}
+
+struct Block {
+    kind: MarkdownBlock,
+    /// From the start of its first line to the end of its last
+    span: Range<usize>,
+    /// A code block whose closing fence has not arrived
+    open: bool,
+}
+
+/// Blocks of `text`, which starts inside a code block of `open_code`'s language if set
+fn parse(text: &str, open_code: Option<Option<String>>) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut current: Option<Block> = open_code.map(|language| Block {
+        kind: MarkdownBlock::CodeBlock { language },
+        span: 0..0,
+        open: true,
+    });
+    // Fence that closes the current code block; a carried-over block closes on either
+    let mut fence: Option<(char, usize)> = current.as_ref().map(|_| (' ', 3));
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let range = offset..offset + line.len();
+        offset = range.end;
+
+        if let Some((ch, len)) = fence {
+            let block = current.as_mut().expect("a fence belongs to a code block");
+            block.span.end = range.end;
+            if fence_of(line).is_some_and(|(c, n, info)| (ch == ' ' || c == ch) && n >= len && info.is_empty()) {
+                block.open = false;
+                fence = None;
+                blocks.extend(current.take());
+            }
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if trimmed.trim_end().is_empty() {
+            blocks.extend(current.take());
+            continue;
+        }
+        let kind = if let Some((ch, len, info)) = fence_of(line) {
+            fence = Some((ch, len));
+            MarkdownBlock::CodeBlock { language: (!info.is_empty()).then(|| info.to_string()) }
+        } else if let Some(level) = heading_level(trimmed) {
+            MarkdownBlock::Heading { level }
+        } else if let Some(ordered) = list_marker(trimmed) {
+            MarkdownBlock::ListItem { ordered }
+        } else if trimmed.starts_with('>') {
+            match &mut current {
+                Some(block) if block.kind == MarkdownBlock::Quote => {
+                    block.span.end = range.end;
+                    continue;
+                }
+                _ => MarkdownBlock::Quote,
+            }
+        } else {
+            // Lazy continuation of a paragraph, list item or quote
+            match &mut current {
+                Some(block) if !matches!(block.kind, MarkdownBlock::Heading { .. }) => {
+                    block.span.end = range.end;
+                    continue;
+                }
+                _ => MarkdownBlock::Paragraph,
+            }
+        };
+        blocks.extend(current.take());
+        let open = matches!(kind, MarkdownBlock::CodeBlock { .. });
+        let block = Block { kind, span: range, open };
+        // A heading is one line; whatever follows starts a new block
+        if matches!(block.kind, MarkdownBlock::Heading { .. }) {
+            blocks.push(block);
+        } else {
+            current = Some(block);
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+/// Fence character, fence length and info string of an opening or closing fence line
+fn fence_of(line: &str) -> Option<(char, usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let ch = rest.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = rest.chars().take_while(|&c| c == ch).count();
+    (indent <= 3 && len >= 3).then(|| (ch, len, rest[len..].trim()))
+}
+
+fn heading_level(line: &str) -> Option<u8> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.trim().is_empty() || rest.starts_with([' ', '\t']))).then_some(level as u8)
+}
+
+/// Whether `line` starts a list item, and if so whether it is numbered
+fn list_marker(line: &str) -> Option<bool> {
+    if line.len() > 1 && line.starts_with(['-', '*', '+']) && line[1..].starts_with([' ', '\t']) {
+        return Some(false);
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let rest = &line[digits..];
+    ((1..=9).contains(&digits) && rest.len() > 1 && rest.starts_with(['.', ')']) && rest[1..].starts_with([' ', '\t'])).then_some(true)
+}
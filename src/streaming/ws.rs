@@ -0,0 +1,118 @@
+//! WebSocket transport for streaming responses.
+//!
+//! Some gateways, and the OpenAI Realtime API, stream over a WebSocket instead of SSE.
+//! A `WsDecoder` knows one such protocol: the frames that start a response and how to
+//! read the server's frames. `sse_from_ws_frames` turns the decoded text deltas into
+//! the SSE bytes `stream_raw` returns, so the rest of the streaming pipeline (tokens,
+//! text, data, stop-after-data) is unchanged.
+//!
+//! `clients::websocket::WsClient` (feature `websocket`) opens the socket; custom
+//! transports can pass their own frames to `sse_from_ws_frames`.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use futures_core::stream::Stream;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+
+use crate::core::RawByteStream;
+use crate::error::{AIError, OpenAIError};
+use crate::streaming::{sse_delta, SSE_DONE};
+
+/// What a server frame means for the response
+#[derive(Debug, Clone)]
+pub enum WsEvent {
+    /// A piece of the response text
+    Delta(String),
+    /// The response is complete
+    Done,
+    /// The server reported an error; the stream ends with it
+    Error(AIError),
+    /// A frame without response text (session updates, audio, rate limits)
+    Skip,
+}
+
+/// One WebSocket streaming protocol
+pub trait WsDecoder: Send + Sync + Debug {
+    /// Text frames to send after connecting to request a response to `prompt`
+    fn open(&self, prompt: &str) -> Vec<String>;
+
+    /// Interpret one text frame from the server
+    fn decode(&self, frame: &str) -> WsEvent;
+}
+
+/// The text channel of the OpenAI Realtime API: sends the prompt as a user message,
+/// asks for a text-only response, and reads `response.text.delta` (or
+/// `response.output_text.delta`) events until `response.done`
+#[derive(Debug, Clone, Default)]
+pub struct OpenAIRealtimeDecoder {
+    /// Session instructions, sent in `session.update`
+    pub instructions: Option<String>,
+}
+
+impl OpenAIRealtimeDecoder {
+    #[must_use]
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+}
+
+impl WsDecoder for OpenAIRealtimeDecoder {
+    fn open(&self, prompt: &str) -> Vec<String> {
+        let mut session = json!({"modalities": ["text"]});
+        if let Some(instructions) = &self.instructions {
+            session["instructions"] = json!(instructions);
+        }
+        vec![
+            json!({"type": "session.update", "session": session}).to_string(),
+            json!({
+                "type": "conversation.item.create",
+                "item": {"type": "message", "role": "user", "content": [{"type": "input_text", "text": prompt}]},
+            }).to_string(),
+            json!({"type": "response.create", "response": {"modalities": ["text"]}}).to_string(),
+        ]
+    }
+
+    fn decode(&self, frame: &str) -> WsEvent {
+        let Ok(event) = serde_json::from_str::<Value>(frame) else { return WsEvent::Skip };
+        match event["type"].as_str().unwrap_or_default() {
+            "response.text.delta" | "response.output_text.delta" => {
+                event["delta"].as_str().map_or(WsEvent::Skip, |delta| WsEvent::Delta(delta.to_string()))
+            }
+            "response.done" => match event["response"]["status"].as_str() {
+                Some("failed") => realtime_error(&event["response"]["status_details"]["error"], "response failed"),
+                _ => WsEvent::Done,
+            },
+            "error" => realtime_error(&event["error"], "unknown error"),
+            _ => WsEvent::Skip,
+        }
+    }
+}
+
+fn realtime_error(error: &Value, fallback: &str) -> WsEvent {
+    let message = error["message"].as_str().unwrap_or(fallback);
+    WsEvent::Error(AIError::OpenAI(OpenAIError::Api(message.to_string())))
+}
+
+/// Decode server text frames into SSE `data:` events carrying the text deltas, ending
+/// with `data: [DONE]` when the decoder reports `Done` or the frames run out
+pub fn sse_from_ws_frames<S>(frames: S, decoder: Arc<dyn WsDecoder>) -> RawByteStream
+where
+    S: Stream<Item = Result<String, AIError>> + Send + 'static,
+{
+    let events = async_stream::try_stream! {
+        let mut frames = Box::pin(frames);
+        while let Some(frame) = frames.next().await {
+            match decoder.decode(&frame?) {
+                WsEvent::Delta(text) => yield sse_delta(&text),
+                WsEvent::Done => break,
+                WsEvent::Error(error) => Err(error)?,
+                WsEvent::Skip => {}
+            }
+        }
+        yield bytes::Bytes::from_static(SSE_DONE);
+    };
+    Box::pin(events)
+}
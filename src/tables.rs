@@ -0,0 +1,523 @@
+//! Extracting rows from markdown and CSV tables, for models that answer with a table
+//! instead of JSON.
+//!
+//! `QueryResolver::with_table_fallback` turns this on for replies without any JSON
+//! matching `T`. Each table's columns are matched to `T`'s fields by header name:
+//! case, spacing and punctuation are ignored, and near spellings ("Qty" for `quantity`,
+//! "Product Name" for `name`) still match. Cells are read leniently, so "1,200" fills
+//! a number field and "yes" a boolean:
+//!
+//! ```
+//! use semantic_query::tables::find_tables;
+//! use serde::Deserialize;
+//!
+//! #[derive(Debug, Deserialize, PartialEq)]
+//! struct Item { name: String, quantity: u32, in_stock: bool }
+//!
+//! let reply = "Here you go:\n\n| Product Name | Qty | In stock? |\n|---|--:|---|\n| Bolt | 1,200 | yes |\n| Nut | 40 | no |\n";
+//! let table = &find_tables(reply)[0];
+//! let items: Vec<Item> = table.extract();
+//! assert_eq!(items[0], Item { name: "Bolt".into(), quantity: 1200, in_stock: true });
+//!
+//! // For a `Vec` target the whole table is one value
+//! let all: Vec<Vec<Item>> = table.extract();
+//! assert_eq!(all[0].len(), 2);
+//! ```
+//!
+//! Fields are found from `T`'s `Deserialize` implementation, so this works for any
+//! struct (or `Vec`/`Option` of one). Targets without declared fields, like
+//! `serde_json::Value` or maps, get one object per row keyed by the header text.
+
+use std::cell::Cell;
+use std::ops::Range;
+
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::serde_helpers::parse_number;
+use crate::streaming::{push_text, Segment, TextFidelity};
+
+/// Headers matching a field less closely than this are left out
+const MIN_SIMILARITY: f64 = 0.6;
+
+/// A table found in model text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    /// Header cells, as written
+    pub headers: Vec<String>,
+    /// Body cells, row by row. Rows may have fewer or more cells than there are headers.
+    pub rows: Vec<Vec<String>>,
+    /// Where the table is in the text, including a code fence around it
+    pub range: Range<usize>,
+    /// Where each row is in the text
+    row_ranges: Vec<Range<usize>>,
+}
+
+/// Markdown pipe tables, and comma- or tab-separated blocks of at least two lines with
+/// the same number of cells, in `text`
+pub fn find_tables(text: &str) -> Vec<Table> {
+    let lines = lines(text);
+    let mut tables = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        match markdown_table(&lines, i).or_else(|| delimited_table(&lines, i)) {
+            Some((mut table, next)) => {
+                // Take in a code fence right around the table
+                let fence = |line: Option<&Line<'_>>| line.is_some_and(|line| line.text.trim_start().starts_with("```"));
+                if i > 0 && fence(lines.get(i - 1)) && fence(lines.get(next)) {
+                    table.range = lines[i - 1].range.start..lines[next].range.end;
+                    i = next + 1;
+                } else {
+                    i = next;
+                }
+                tables.push(table);
+            }
+            None => i += 1,
+        }
+    }
+    tables
+}
+
+impl Table {
+    /// The table as `T`s: one per row that deserializes, or, when `T` is a sequence
+    /// (`Vec<Row>`), the whole table as one value. Empty when nothing fits.
+    pub fn extract<T: DeserializeOwned>(&self) -> Vec<T> {
+        self.extract_spans().into_iter().map(|(data, _)| data).collect()
+    }
+
+    /// `extract`, with the part of the text each value came from
+    fn extract_spans<T: DeserializeOwned>(&self) -> Vec<(T, Range<usize>)> {
+        let shape = probe::<T>();
+        let keys = match shape.fields {
+            Some(fields) => match_headers(&self.headers, fields),
+            None => self.headers.iter().map(|h| Some(h.clone())).collect(),
+        };
+        if keys.iter().all(Option::is_none) {
+            return Vec::new();
+        }
+        if shape.sequence {
+            let rows = SeqDeserializer::<_, Error>::new(self.rows.iter().map(|cells| RowDeserializer { keys: &keys, cells }));
+            return T::deserialize(rows).map(|data| vec![(data, self.range.clone())]).unwrap_or_default();
+        }
+        self.rows.iter().zip(&self.row_ranges)
+            .filter_map(|(cells, range)| Some((T::deserialize(RowDeserializer { keys: &keys, cells }).ok()?, range.clone())))
+            .collect()
+    }
+}
+
+/// Segments for a reply whose tables hold `T`s: the rows (or tables) as data and the
+/// rest as text. None when no table does.
+pub(crate) fn segment_tables<T: DeserializeOwned>(raw: &str, fidelity: TextFidelity) -> Option<Vec<Segment<T>>> {
+    let mut segments = Vec::new();
+    let mut cursor = 0;
+    let mut found = false;
+    for table in find_tables(raw) {
+        let spans = table.extract_spans::<T>();
+        if spans.is_empty() {
+            continue;
+        }
+        found = true;
+        push_text(raw, cursor..table.range.start, fidelity, &mut segments);
+        let covers_table = spans.len() == 1 && spans[0].1 == table.range;
+        if covers_table {
+            let (data, range) = spans.into_iter().next()?;
+            segments.push(Segment::Data(data, range));
+        } else {
+            // Only exact text keeps the header and the rows that did not fit
+            let exact = fidelity == TextFidelity::Exact;
+            let mut row_cursor = table.range.start;
+            for (data, range) in spans {
+                if exact {
+                    push_text(raw, row_cursor..range.start, fidelity, &mut segments);
+                }
+                row_cursor = range.end;
+                segments.push(Segment::Data(data, range));
+            }
+            if exact {
+                push_text(raw, row_cursor..table.range.end, fidelity, &mut segments);
+            }
+        }
+        cursor = table.range.end;
+    }
+    push_text(raw, cursor..raw.len(), fidelity, &mut segments);
+    found.then_some(segments)
+}
+
+struct Line<'a> {
+    /// Without the line break
+    text: &'a str,
+    /// With the line break
+    range: Range<usize>,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let mut start = 0;
+    text.split_inclusive('\n').map(|line| {
+        let range = start..start + line.len();
+        start = range.end;
+        Line { text: line.trim_end_matches(['\n', '\r']), range }
+    }).collect()
+}
+
+/// A pipe table starting at `lines[i]`: header, `|---|:--:|` separator, body rows.
+/// Returns the table and the index of the line after it.
+fn markdown_table(lines: &[Line<'_>], i: usize) -> Option<(Table, usize)> {
+    let header = &lines[i];
+    if !header.text.contains('|') {
+        return None;
+    }
+    let separator = split_pipes(lines.get(i + 1)?.text);
+    let is_rule = |cell: &String| {
+        let rule = cell.trim_start_matches(':').trim_end_matches(':');
+        !rule.is_empty() && rule.chars().all(|c| c == '-')
+    };
+    if separator.is_empty() || !separator.iter().all(is_rule) {
+        return None;
+    }
+    let headers = split_pipes(header.text);
+    let mut end = i + 2;
+    while lines.get(end).is_some_and(|line| line.text.contains('|') && !line.text.trim().is_empty()) {
+        end += 1;
+    }
+    let body = &lines[i + 2..end];
+    Some((table(lines, i, end, headers, body.iter().map(|line| split_pipes(line.text)).collect(), body), end))
+}
+
+/// Cells of a pipe table row; `\|` is a literal pipe
+fn split_pipes(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').filter(|rest| !rest.ends_with('\\')).unwrap_or(line);
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => cells.last_mut().unwrap().push(chars.next().unwrap()),
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.into_iter().map(|cell| clean_cell(&cell)).collect()
+}
+
+/// Lines from `lines[i]` on with the same number (two or more) of comma- or
+/// tab-separated cells. The header cells must be short labels, so that prose with
+/// commas is not taken for a table.
+fn delimited_table(lines: &[Line<'_>], i: usize) -> Option<(Table, usize)> {
+    let delimiter = if lines[i].text.contains('\t') { '\t' } else { ',' };
+    let headers = split_delimited(lines[i].text, delimiter);
+    let label = |cell: &String| !cell.is_empty() && cell.chars().count() <= 40;
+    if headers.len() < 2 || !headers.iter().all(label) {
+        return None;
+    }
+    let mut rows = Vec::new();
+    let mut end = i + 1;
+    while let Some(line) = lines.get(end) {
+        let cells = split_delimited(line.text, delimiter);
+        if cells.len() != headers.len() {
+            break;
+        }
+        rows.push(cells);
+        end += 1;
+    }
+    if rows.is_empty() {
+        return None;
+    }
+    Some((table(lines, i, end, headers, rows, &lines[i + 1..end]), end))
+}
+
+/// Cells of a CSV line, with `"..."` quoting and `""` for a quote inside it
+fn split_delimited(line: &str, delimiter: char) -> Vec<String> {
+    let mut cells = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cells.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.into_iter().map(|cell| clean_cell(&cell)).collect()
+}
+
+/// Trim a cell and drop bold or code markup around it
+fn clean_cell(cell: &str) -> String {
+    let cell = cell.trim();
+    let bare = cell.trim_matches(['*', '`']).trim();
+    if bare.is_empty() { cell.to_string() } else { bare.to_string() }
+}
+
+fn table(lines: &[Line<'_>], start: usize, end: usize, headers: Vec<String>, rows: Vec<Vec<String>>, body: &[Line<'_>]) -> Table {
+    Table {
+        headers,
+        rows,
+        range: lines[start].range.start..lines[end - 1].range.end,
+        row_ranges: body.iter().map(|line| line.range.clone()).collect(),
+    }
+}
+
+/// The field each header fills, best matches first
+fn match_headers(headers: &[String], fields: &'static [&'static str]) -> Vec<Option<String>> {
+    let mut pairs: Vec<(f64, usize, usize)> = Vec::new();
+    for (h, header) in headers.iter().enumerate() {
+        for (f, field) in fields.iter().enumerate() {
+            let score = similarity(&normalize(header), &normalize(field));
+            if score >= MIN_SIMILARITY {
+                pairs.push((score, h, f));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    let mut keys = vec![None; headers.len()];
+    let mut taken = vec![false; fields.len()];
+    for (_, h, f) in pairs {
+        if keys[h].is_none() && !taken[f] {
+            keys[h] = Some(fields[f].to_string());
+            taken[f] = true;
+        }
+    }
+    keys
+}
+
+/// Lowercase letters and digits only: "In stock?" and `in_stock` are both "instock"
+fn normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// 1.0 for equal names, less the further apart they are. One name inside the other, or
+/// an abbreviation of it (its letters in order, starting alike), counts as close.
+fn similarity(a: &str, b: &str) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let (short, long) = if a.chars().count() <= b.chars().count() { (a, b) } else { (b, a) };
+    let ratio = short.chars().count() as f64 / long.chars().count() as f64;
+    let mut score = 1.0 - levenshtein(a, b) as f64 / long.chars().count() as f64;
+    if long.contains(short) {
+        score = score.max(0.5 + 0.5 * ratio);
+    } else if short.chars().next() == long.chars().next() && is_subsequence(short, long) {
+        score = score.max(0.45 + 0.5 * ratio);
+    }
+    score
+}
+
+fn is_subsequence(short: &str, long: &str) -> bool {
+    let mut rest = long.chars();
+    short.chars().all(|c| rest.any(|l| l == c))
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1).min(row[j] + 1).min(diagonal + usize::from(ca != cb));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// What `T` looks like to a table: the fields of its rows, and whether it takes all
+/// rows at once
+struct Shape {
+    fields: Option<&'static [&'static str]>,
+    sequence: bool,
+}
+
+/// Find `T`'s shape by starting to deserialize it from a `Probe`, which notes the
+/// fields a struct asks for and then gives up
+fn probe<T: DeserializeOwned>() -> Shape {
+    let fields = Cell::new(None);
+    let sequence = Cell::new(false);
+    let _ = T::deserialize(Probe { fields: &fields, sequence: &sequence, nested: false });
+    Shape { fields: fields.get(), sequence: sequence.get() }
+}
+
+struct Probe<'a> {
+    fields: &'a Cell<Option<&'static [&'static str]>>,
+    sequence: &'a Cell<bool>,
+    /// Inside the sequence already
+    nested: bool,
+}
+
+impl<'de> de::Deserializer<'de> for Probe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("probe"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], _visitor: V) -> Result<V::Value, Error> {
+        self.fields.set(Some(fields));
+        Err(de::Error::custom("probe"))
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.nested {
+            return Err(de::Error::custom("probe"));
+        }
+        self.sequence.set(true);
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de> SeqAccess<'de> for Probe<'_> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>, Error> {
+        seed.deserialize(Probe { fields: self.fields, sequence: self.sequence, nested: true }).map(Some)
+    }
+}
+
+/// One row as a map from field to cell
+struct RowDeserializer<'a> {
+    keys: &'a [Option<String>],
+    cells: &'a [String],
+}
+
+impl<'de> de::Deserializer<'de> for RowDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries = self.keys.iter().zip(self.cells)
+            .filter_map(|(key, cell)| Some((key.as_deref()?, CellDeserializer(cell))));
+        visitor.visit_map(MapDeserializer::new(entries))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, Error> for RowDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// A cell, read as whatever the field asks for
+struct CellDeserializer<'a>(&'a str);
+
+impl CellDeserializer<'_> {
+    fn blank(&self) -> bool {
+        matches!(self.0.to_lowercase().as_str(), "" | "-" | "—" | "n/a" | "null" | "none")
+    }
+
+    fn boolean(&self) -> Option<bool> {
+        match self.0.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "✓" | "✔" | "✅" => Some(true),
+            "false" | "no" | "n" | "✗" | "✘" | "❌" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! deserialize_numbers {
+    ($($method:ident => $parse:ty, $visit:ident;)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match parse_number::<$parse>(self.0) {
+                Some(n) => visitor.$visit(n),
+                None => visitor.visit_str(self.0),
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for CellDeserializer<'_> {
+    type Error = Error;
+
+    /// Numbers and booleans as such, blanks as null, anything else as a string
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_empty() {
+            return visitor.visit_unit();
+        }
+        match serde_json::from_str::<serde_json::Value>(self.0) {
+            Ok(serde_json::Value::Number(n)) if n.is_i64() => visitor.visit_i64(n.as_i64().unwrap_or_default()),
+            Ok(serde_json::Value::Number(n)) if n.is_u64() => visitor.visit_u64(n.as_u64().unwrap_or_default()),
+            Ok(serde_json::Value::Number(n)) => visitor.visit_f64(n.as_f64().unwrap_or_default()),
+            Ok(serde_json::Value::Bool(b)) => visitor.visit_bool(b),
+            _ => visitor.visit_str(self.0),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.boolean() {
+            Some(b) => visitor.visit_bool(b),
+            None => visitor.visit_str(self.0),
+        }
+    }
+
+    deserialize_numbers! {
+        deserialize_i8 => i64, visit_i64;
+        deserialize_i16 => i64, visit_i64;
+        deserialize_i32 => i64, visit_i64;
+        deserialize_i64 => i64, visit_i64;
+        deserialize_u8 => u64, visit_u64;
+        deserialize_u16 => u64, visit_u64;
+        deserialize_u32 => u64, visit_u64;
+        deserialize_u64 => u64, visit_u64;
+        deserialize_f32 => f64, visit_f64;
+        deserialize_f64 => f64, visit_f64;
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.blank() { visitor.visit_none() } else { visitor.visit_some(self) }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, Error> for CellDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
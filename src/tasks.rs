@@ -0,0 +1,419 @@
+//! Canned prompt and schema bundles for common jobs.
+//!
+//! Each task pairs a tested prompt with a result type and splits inputs that exceed the
+//! chunk budget (`DEFAULT_CHUNK_TOKENS`, change it with `Tasks::with_chunk_tokens`):
+//!
+//! ```no_run
+//! # use semantic_query::core::QueryResolver;
+//! # use semantic_query::tasks::ChangeReport;
+//! # async fn run(resolver: QueryResolver<semantic_query::clients::mock::MockVoid>, old: &str, new: &str) -> Result<(), Box<dyn std::error::Error>> {
+//! let report = resolver.diff::<ChangeReport>(old, new).await?;
+//! for change in &report.changes {
+//!     println!("{:?}: {}", change.kind, change.description);
+//! }
+//! let summary = resolver.tasks().with_chunk_tokens(1500).summarize(new).await?;
+//! let label = resolver.tasks().classify(new, &["contract", "invoice", "letter"]).await?.label;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::core::{LowLevelClient, QueryResolver};
+use crate::error::QueryResolverError;
+use crate::prompt::estimate_tokens;
+
+/// Inputs above this many tokens (estimated) are split into chunks
+pub const DEFAULT_CHUNK_TOKENS: usize = 3000;
+
+/// Result of `Tasks::summarize`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Summary {
+    /// A few sentences covering the whole text
+    pub summary: String,
+    /// The most important facts or claims, one per entry
+    pub key_points: Vec<String>,
+}
+
+/// Result of `Tasks::classify`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Classification {
+    /// Exactly one of the allowed labels
+    pub label: String,
+    /// 0 to 1
+    pub confidence: f64,
+    /// One sentence on why the label fits
+    pub rationale: String,
+}
+
+/// A named thing found by `Tasks::extract_entities`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct Entity {
+    /// The entity as written in the text
+    pub text: String,
+    /// person, organization, location, date, amount, product or other
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct EntityList {
+    entities: Vec<Entity>,
+}
+
+/// An entity located in the source text by `Tasks::extract_entity_spans`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntitySpan {
+    /// The source text between `start` and `end`
+    pub text: String,
+    pub kind: String,
+    /// Character (not byte) offset of the first character
+    pub start: usize,
+    /// Character offset just past the last character
+    pub end: usize,
+    /// The model's offsets were wrong and the span was located by searching the text
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct EntityMention {
+    /// The mention exactly as written in the text
+    text: String,
+    /// person, organization, location, date, amount, product or other
+    kind: String,
+    /// 0-based character offset where the mention starts
+    start: Option<usize>,
+    /// Character offset just past the mention
+    end: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct MentionList {
+    mentions: Vec<EntityMention>,
+}
+
+/// What happened to a passage between two versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// One change reported by `diff`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// What changed in meaning, in one sentence
+    pub description: String,
+    /// The passage in the old version (absent for additions)
+    pub before: Option<String>,
+    /// The passage in the new version (absent for removals)
+    pub after: Option<String>,
+}
+
+/// Default result type of `diff`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChangeReport {
+    /// The changes overall, in one or two sentences; empty if there are none
+    pub summary: String,
+    /// Every change in meaning, in document order; wording-only edits are left out
+    pub changes: Vec<Change>,
+}
+
+/// A report that can be assembled from reports on parts of the input
+pub trait MergeReport: Default {
+    /// Fold the report on a later part into this one
+    fn merge(&mut self, later: Self);
+}
+
+impl MergeReport for ChangeReport {
+    fn merge(&mut self, later: Self) {
+        if self.summary.is_empty() {
+            self.summary = later.summary;
+        } else if !later.summary.is_empty() {
+            self.summary = format!("{} {}", self.summary, later.summary);
+        }
+        self.changes.extend(later.changes);
+    }
+}
+
+const DIFF_INSTRUCTIONS: &str = "Compare the two versions of a text below. Report every change in meaning: added, removed and modified statements, changed numbers, dates, names, obligations and conditions. Ignore changes in wording, formatting or order that keep the meaning. Quote the affected passages briefly.";
+
+const EXCERPT_NOTE: &str = "Only the passages that differ are shown, with a little surrounding context; the rest of the text is identical in both versions.";
+
+/// Split `text` into chunks of at most `max_tokens` (estimated), at paragraph breaks
+/// where possible, then at line breaks and whitespace
+pub fn chunk_text(text: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens.max(1) * 4;
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for piece in pieces(text, max_chars) {
+        if !current.is_empty() && current.len() + 2 + piece.len() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Character offsets of `mention` in `source`: at `claimed_start` if it is there,
+/// otherwise the occurrence nearest to it (the first one without a claim), preferring
+/// exact matches over ones differing in ASCII case. None if `mention` does not occur.
+pub fn locate_span(source: &str, mention: &str, claimed_start: Option<usize>) -> Option<(usize, usize)> {
+    let mention = mention.trim();
+    if mention.is_empty() {
+        return None;
+    }
+    let occurrences = |exact: bool| source.char_indices().enumerate().filter(move |(_, (byte, _))| {
+        source.get(*byte..byte + mention.len()).is_some_and(|s| if exact { s == mention } else { s.eq_ignore_ascii_case(mention) })
+    }).map(|(start, _)| start);
+    let distance = |start: &usize| claimed_start.map_or(*start, |claimed| start.abs_diff(claimed));
+    let start = occurrences(true).min_by_key(distance).or_else(|| occurrences(false).min_by_key(distance))?;
+    Some((start, start + mention.chars().count()))
+}
+
+fn paragraphs(text: &str) -> Vec<&str> {
+    text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).collect()
+}
+
+/// Paragraphs, with those longer than `max_chars` cut down
+fn pieces(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    for paragraph in paragraphs(text) {
+        let mut rest = paragraph;
+        while rest.len() > max_chars {
+            let mut end = max_chars;
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            let window = &rest[..end];
+            let cut = window.rfind('\n').or_else(|| window.rfind(char::is_whitespace)).filter(|&i| i > 0).unwrap_or(end);
+            pieces.push(rest[..cut].trim_end());
+            rest = rest[cut..].trim_start();
+        }
+        if !rest.is_empty() {
+            pieces.push(rest);
+        }
+    }
+    pieces
+}
+
+/// Passages of two versions that differ, as (old, new) pairs with one paragraph of
+/// leading context, from a longest-common-subsequence alignment of the paragraphs
+fn changed_passages(old: &str, new: &str) -> Vec<(String, String)> {
+    let (a, b) = (paragraphs(old), paragraphs(new));
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut passages = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut context: Option<&str> = None;
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            context = Some(a[i]);
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (mut before, mut after): (Vec<&str>, Vec<&str>) = (context.into_iter().collect(), context.into_iter().collect());
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                break;
+            }
+            if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                before.push(a[i]);
+                i += 1;
+            } else {
+                after.push(b[j]);
+                j += 1;
+            }
+        }
+        passages.push((before.join("\n\n"), after.join("\n\n")));
+    }
+    passages
+}
+
+fn diff_prompt(old: &str, new: &str, excerpts: bool) -> String {
+    let note = if excerpts { format!("\n{EXCERPT_NOTE}") } else { String::new() };
+    format!("{DIFF_INSTRUCTIONS}{note}\n\n=== OLD VERSION ===\n{old}\n\n=== NEW VERSION ===\n{new}\n=== END ===")
+}
+
+/// Canned tasks run on a resolver; see the module docs
+pub struct Tasks<'a, C: LowLevelClient> {
+    resolver: &'a QueryResolver<C>,
+    chunk_tokens: usize,
+}
+
+impl<C: LowLevelClient> Tasks<'_, C> {
+    /// Inputs above `tokens` (estimated) are split
+    #[must_use]
+    pub fn with_chunk_tokens(mut self, tokens: usize) -> Self {
+        self.chunk_tokens = tokens.max(1);
+        self
+    }
+
+    /// Summary of `text`. Long texts are summarized chunk by chunk and the partial
+    /// summaries combined in a final query.
+    pub async fn summarize(&self, text: &str) -> Result<Summary, QueryResolverError> {
+        let chunks = chunk_text(text, self.chunk_tokens);
+        if chunks.len() <= 1 {
+            return self.resolver.query_typed(format!("Summarize the following text.\n\n{text}")).await;
+        }
+        let mut partials = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            let summary: Summary = self.resolver.query_typed(format!(
+                "Summarize part {} of {} of a longer text.\n\n{chunk}",
+                index + 1,
+                chunks.len()
+            )).await?;
+            partials.push(format!("Part {}: {}\n- {}", index + 1, summary.summary, summary.key_points.join("\n- ")));
+        }
+        self.resolver.query_typed(format!(
+            "Below are summaries of consecutive parts of one text. Combine them into a summary of the whole text.\n\n{}",
+            partials.join("\n\n")
+        )).await
+    }
+
+    /// Which of `labels` fits `text` best. Long texts are classified by their first chunk.
+    /// A label outside `labels` fails with `QueryResolverError::Task`.
+    pub async fn classify(&self, text: &str, labels: &[&str]) -> Result<Classification, QueryResolverError> {
+        if labels.is_empty() {
+            return Err(QueryResolverError::Task("classify needs at least one label".into()));
+        }
+        let excerpt = chunk_text(text, self.chunk_tokens).into_iter().next().unwrap_or_default();
+        let classification: Classification = self.resolver.query_typed(format!(
+            "Classify the following text with exactly one of these labels: {}.\n\n{excerpt}",
+            labels.join(", ")
+        )).await?;
+        match labels.iter().find(|label| label.eq_ignore_ascii_case(classification.label.trim())) {
+            Some(label) => Ok(Classification { label: label.to_string(), ..classification }),
+            None => Err(QueryResolverError::Task(format!("label '{}' is not one of: {}", classification.label, labels.join(", ")))),
+        }
+    }
+
+    /// Named entities in `text`, in order of first appearance and without duplicates
+    pub async fn extract_entities(&self, text: &str) -> Result<Vec<Entity>, QueryResolverError> {
+        let mut entities: Vec<Entity> = Vec::new();
+        for chunk in chunk_text(text, self.chunk_tokens) {
+            let found: EntityList = self.resolver.query_typed(format!(
+                "List the named entities (people, organizations, locations, dates, amounts, products) in the following text.\n\n{chunk}"
+            )).await?;
+            for entity in found.entities {
+                if !entities.contains(&entity) {
+                    entities.push(entity);
+                }
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Every entity mention in `text` with character offsets checked against `text`.
+    /// Mentions whose offsets do not match are looked up near the claimed position
+    /// (`EntitySpan::repaired`); mentions that do not occur in `text` are dropped.
+    /// Sorted by position, one span per position.
+    pub async fn extract_entity_spans(&self, text: &str) -> Result<Vec<EntitySpan>, QueryResolverError> {
+        let mut spans: Vec<EntitySpan> = Vec::new();
+        let mut offset = 0;
+        for chunk in chunk_text(text, self.chunk_tokens) {
+            // Chunks start with a paragraph copied verbatim from `text`
+            let first = chunk.split("\n\n").next().unwrap_or_default();
+            offset = locate_span(text, first, Some(offset)).map_or(offset, |(start, _)| start);
+            let found: MentionList = self.resolver.query_typed(format!(
+                "List every mention of a named entity (people, organizations, locations, dates, amounts, products) in the text below, with 0-based character offsets into the text; end is exclusive.\n\n{chunk}"
+            )).await?;
+            for mention in found.mentions {
+                let claimed = mention.start.map(|start| start + offset);
+                let Some((start, end)) = locate_span(text, &mention.text, claimed) else {
+                    debug!(mention = %mention.text, "Dropping entity not found in the source text");
+                    continue;
+                };
+                if spans.iter().any(|span| span.start == start && span.end == end) {
+                    continue;
+                }
+                spans.push(EntitySpan {
+                    text: text.chars().skip(start).take(end - start).collect(),
+                    kind: mention.kind,
+                    start,
+                    end,
+                    repaired: claimed != Some(start) || mention.end.map(|e| e + offset) != Some(end),
+                });
+            }
+        }
+        spans.sort_by_key(|span| span.start);
+        Ok(spans)
+    }
+
+    /// Changes in meaning from `old` to `new`. When both together exceed the chunk
+    /// budget, only the differing passages (aligned by paragraph) are sent, in as few
+    /// queries as fit, and the reports are merged. Identical texts skip the model.
+    pub async fn diff<R>(&self, old: &str, new: &str) -> Result<R, QueryResolverError>
+    where
+        R: MergeReport + DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+    {
+        if old == new {
+            return Ok(R::default());
+        }
+        if estimate_tokens(old) + estimate_tokens(new) <= self.chunk_tokens {
+            return self.resolver.query_typed(diff_prompt(old, new, false)).await;
+        }
+
+        // Pack passages into batches; a passage over the budget is split into pairs of chunks
+        let mut batches: Vec<(String, String)> = Vec::new();
+        for (before, after) in changed_passages(old, new) {
+            if estimate_tokens(&before) + estimate_tokens(&after) > self.chunk_tokens {
+                let (before, after) = (chunk_text(&before, self.chunk_tokens / 2), chunk_text(&after, self.chunk_tokens / 2));
+                for index in 0..before.len().max(after.len()) {
+                    batches.push((before.get(index).cloned().unwrap_or_default(), after.get(index).cloned().unwrap_or_default()));
+                }
+                continue;
+            }
+            match batches.last_mut() {
+                Some((old_batch, new_batch))
+                    if estimate_tokens(old_batch) + estimate_tokens(new_batch) + estimate_tokens(&before) + estimate_tokens(&after) <= self.chunk_tokens =>
+                {
+                    old_batch.push_str(&format!("\n\n[...]\n\n{before}"));
+                    new_batch.push_str(&format!("\n\n[...]\n\n{after}"));
+                }
+                _ => batches.push((before, after)),
+            }
+        }
+
+        let mut report = R::default();
+        for (before, after) in batches {
+            let part: R = self.resolver.query_typed(diff_prompt(&before, &after, true)).await?;
+            report.merge(part);
+        }
+        Ok(report)
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// Canned summarize / classify / extract-entities / diff tasks on this resolver
+    pub fn tasks(&self) -> Tasks<'_, C> {
+        Tasks { resolver: self, chunk_tokens: DEFAULT_CHUNK_TOKENS }
+    }
+
+    /// Changes in meaning from `old` to `new` as a typed report, usually `ChangeReport`;
+    /// see `Tasks::diff`
+    pub async fn diff<R>(&self, old: &str, new: &str) -> Result<R, QueryResolverError>
+    where
+        R: MergeReport + DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+    {
+        self.tasks().diff(old, new).await
+    }
+}
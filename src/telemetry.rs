@@ -0,0 +1,60 @@
+//! Tracing targets and span layout.
+//!
+//! The target names below are stable, so they can be used in `RUST_LOG` / `EnvFilter`
+//! directives. A non-streaming query produces this span tree:
+//!
+//! ```text
+//! query / query_mixed / ...   target semantic_query::resolver  (prompt_len, correlation_id)
+//! ├── attempt                 target semantic_query::attempt   (attempt, turns, constrained, status, reply_len)
+//! │   └── http                target semantic_query::http      (provider, model, status)
+//! └── extract                 target semantic_query::extract   (pass, items_emitted)
+//! ```
+//!
+//! Each model call made for the query (the first ask, corrections, continuations and
+//! strategy retries) is its own `attempt`, numbered from 1 within the query; `status`
+//! is `ok` or the error's `AIError::retry_key`. `http` spans are opened by the built-in
+//! HTTP clients and record the response's status code. Each parse of the reply is an
+//! `extract` pass. Streaming queries decode SSE in an `sse_decode` span
+//! (`semantic_query::sse`, with `items_emitted` recorded when the stream ends).
+//! Parser internals log under `semantic_query::json_stream`.
+
+use std::task::Poll;
+
+use futures_core::Stream;
+use tracing::Span;
+
+/// Query-level spans (`query`, `stream_query`, `Conversation::query`, consensus)
+pub const RESOLVER: &str = "semantic_query::resolver";
+/// One model call within a query
+pub const ATTEMPT: &str = "semantic_query::attempt";
+/// Provider HTTP requests
+pub const HTTP: &str = "semantic_query::http";
+/// SSE decoding of streamed replies
+pub const SSE: &str = "semantic_query::sse";
+/// Extraction passes over a reply
+pub const EXTRACT: &str = "semantic_query::extract";
+/// Incremental JSON parser internals
+pub const JSON_STREAM: &str = "semantic_query::json_stream";
+
+/// `stream` polled inside `span`, recording on it how many items `counts` accepted as
+/// `items_emitted` once the stream ends
+pub(crate) fn traced_stream<S, F>(stream: S, span: Span, counts: F) -> impl Stream<Item = S::Item>
+where
+    S: Stream,
+    F: Fn(&S::Item) -> bool,
+{
+    let mut stream = Box::pin(stream);
+    let mut emitted = 0u64;
+    futures_util::stream::poll_fn(move |cx| {
+        let _entered = span.enter();
+        let next = stream.as_mut().poll_next(cx);
+        match &next {
+            Poll::Ready(Some(item)) if counts(item) => emitted += 1,
+            Poll::Ready(None) => {
+                span.record("items_emitted", emitted);
+            }
+            _ => {}
+        }
+        next
+    })
+}
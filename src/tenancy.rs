@@ -0,0 +1,357 @@
+//! Per-tenant credentials, limits and usage for multi-tenant backends.
+//!
+//! A `Tenancy` maps tenant ids to the client (and so the API key) each tenant queries
+//! with, plus optional `TenantLimits`. `QueryResolver::for_tenant` returns a resolver
+//! over that client which enforces the limits, counts usage, and attributes its queries
+//! to the tenant: `QueryStats::tenant` is set, and clients and interceptors see the id
+//! through `tenancy::current()`:
+//!
+//! ```no_run
+//! # use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+//! # use semantic_query::tenancy::{Tenancy, TenantLimits};
+//! # #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+//! # struct Invoice { total: f64 }
+//! # async fn run(acme_client: Box<dyn LowLevelClient>, globex_client: Box<dyn LowLevelClient>) -> Result<(), Box<dyn std::error::Error>> {
+//! let tenancy = Tenancy::new()
+//!     .with_tenant("acme", acme_client, TenantLimits::default().requests_per_minute(60))
+//!     .with_tenant("globex", globex_client, TenantLimits::default().token_budget(1_000_000));
+//! let resolver = QueryResolver::new(semantic_query::clients::mock::MockVoid, RetryConfig::default())
+//!     .with_tenancy(tenancy.clone());
+//!
+//! let invoice = resolver.for_tenant("acme")?.query::<Invoice>("Extract the invoice".into()).await?;
+//! println!("{:?}", tenancy.usage("acme"));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::QueryRecord;
+use crate::core::{Capabilities, ChatMessage, LowLevelClient, QueryResolver, RawByteStream};
+use crate::error::{AIError, QueryResolverError};
+use crate::grammar::OutputConstraint;
+use crate::prompt::estimate_tokens;
+use crate::request::ModelRequest;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// The tenant the running request is made for, if any
+pub fn current() -> Option<String> {
+    CURRENT.try_with(Clone::clone).ok()
+}
+
+/// Run `future` on behalf of `tenant`
+pub async fn scope<F: Future>(tenant: impl Into<String>, future: F) -> F::Output {
+    CURRENT.scope(tenant.into(), future).await
+}
+
+/// Limits on one tenant's requests; unset limits are not enforced. Tokens are
+/// estimated (~4 characters per token) from prompts and replies.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantLimits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u64>,
+    /// Total tokens over the tenant's lifetime in this `Tenancy`
+    pub token_budget: Option<u64>,
+}
+
+impl TenantLimits {
+    #[must_use]
+    pub fn requests_per_minute(mut self, limit: u32) -> Self {
+        self.requests_per_minute = Some(limit);
+        self
+    }
+
+    #[must_use]
+    pub fn tokens_per_minute(mut self, limit: u64) -> Self {
+        self.tokens_per_minute = Some(limit);
+        self
+    }
+
+    #[must_use]
+    pub fn token_budget(mut self, budget: u64) -> Self {
+        self.token_budget = Some(budget);
+        self
+    }
+}
+
+/// What a tenant has used so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantUsage {
+    /// Requests sent to the provider
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    /// Reply tokens; streamed replies are not counted
+    pub completion_tokens: u64,
+    /// Requests refused for exceeding a limit
+    pub rejected: u64,
+}
+
+impl TenantUsage {
+    pub fn tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+#[derive(Debug, Default)]
+struct TenantState {
+    usage: TenantUsage,
+    /// Request times within the last minute
+    requests: VecDeque<DateTime<Utc>>,
+    /// Token counts within the last minute
+    tokens: VecDeque<(DateTime<Utc>, u64)>,
+}
+
+impl TenantState {
+    /// Count a request of `prompt_tokens` if the limits allow it, else name the limit hit
+    fn admit(&mut self, limits: &TenantLimits, prompt_tokens: u64) -> Result<(), &'static str> {
+        let now = Utc::now();
+        let window_start = now - Duration::minutes(1);
+        while self.requests.front().is_some_and(|t| *t <= window_start) {
+            self.requests.pop_front();
+        }
+        while self.tokens.front().is_some_and(|(t, _)| *t <= window_start) {
+            self.tokens.pop_front();
+        }
+
+        let recent_tokens: u64 = self.tokens.iter().map(|(_, n)| n).sum();
+        let exceeded = if limits.requests_per_minute.is_some_and(|limit| self.requests.len() >= limit as usize) {
+            Some("requests_per_minute")
+        } else if limits.tokens_per_minute.is_some_and(|limit| recent_tokens + prompt_tokens > limit) {
+            Some("tokens_per_minute")
+        } else if limits.token_budget.is_some_and(|budget| self.usage.tokens() + prompt_tokens > budget) {
+            Some("token_budget")
+        } else {
+            None
+        };
+        if let Some(limit) = exceeded {
+            self.usage.rejected += 1;
+            return Err(limit);
+        }
+
+        self.requests.push_back(now);
+        self.tokens.push_back((now, prompt_tokens));
+        self.usage.requests += 1;
+        self.usage.prompt_tokens += prompt_tokens;
+        Ok(())
+    }
+
+    fn settle(&mut self, reply_tokens: u64) {
+        self.tokens.push_back((Utc::now(), reply_tokens));
+        self.usage.completion_tokens += reply_tokens;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tenant {
+    client: Box<dyn LowLevelClient>,
+    limits: TenantLimits,
+    state: Arc<Mutex<TenantState>>,
+}
+
+/// Tenants keyed by id, each with its own client, limits and usage. Clones share usage.
+#[derive(Clone, Default)]
+pub struct Tenancy {
+    tenants: BTreeMap<String, Tenant>,
+}
+
+impl fmt::Debug for Tenancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.tenants.iter().map(|(id, tenant)| (id, &tenant.limits))).finish()
+    }
+}
+
+impl Tenancy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add (or replace) `tenant`, querying through `client` (which carries its credentials)
+    #[must_use]
+    pub fn with_tenant(mut self, tenant: impl Into<String>, client: Box<dyn LowLevelClient>, limits: TenantLimits) -> Self {
+        self.tenants.insert(tenant.into(), Tenant { client, limits, state: Arc::default() });
+        self
+    }
+
+    /// Registered tenant ids, sorted
+    pub fn tenants(&self) -> Vec<String> {
+        self.tenants.keys().cloned().collect()
+    }
+
+    pub fn usage(&self, tenant: &str) -> Option<TenantUsage> {
+        let tenant = self.tenants.get(tenant)?;
+        tenant.state.lock().ok().map(|state| state.usage.clone())
+    }
+
+    /// Usage of every tenant, e.g. for billing exports
+    pub fn usage_report(&self) -> BTreeMap<String, TenantUsage> {
+        self.tenants.keys().filter_map(|id| Some((id.clone(), self.usage(id)?))).collect()
+    }
+
+    /// `tenant`'s client, wrapped to enforce its limits and record usage
+    pub fn client(&self, tenant: &str) -> Option<TenantClient> {
+        let entry = self.tenants.get(tenant)?;
+        Some(TenantClient {
+            tenant: tenant.to_string(),
+            inner: entry.client.clone_box(),
+            limits: entry.limits.clone(),
+            state: entry.state.clone(),
+        })
+    }
+}
+
+/// A tenant's client; refuses requests over the tenant's limits with
+/// `AIError::TenantLimit` and counts the rest in `Tenancy::usage`
+#[derive(Debug)]
+pub struct TenantClient {
+    tenant: String,
+    inner: Box<dyn LowLevelClient>,
+    limits: TenantLimits,
+    state: Arc<Mutex<TenantState>>,
+}
+
+impl Clone for TenantClient {
+    fn clone(&self) -> Self {
+        self.wrap(self.inner.clone_box())
+    }
+}
+
+impl TenantClient {
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    fn wrap(&self, inner: Box<dyn LowLevelClient>) -> Self {
+        Self { tenant: self.tenant.clone(), inner, limits: self.limits.clone(), state: self.state.clone() }
+    }
+
+    fn admit(&self, prompt_tokens: usize) -> Result<(), AIError> {
+        crate::stats::record_tenant(&self.tenant);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.admit(&self.limits, prompt_tokens as u64)
+            .map_err(|limit| AIError::TenantLimit { tenant: self.tenant.clone(), limit: limit.to_string() })
+    }
+
+    async fn run<F>(&self, prompt_tokens: usize, request: F) -> Result<String, AIError>
+    where
+        F: Future<Output = Result<String, AIError>>,
+    {
+        self.admit(prompt_tokens)?;
+        let reply = scope(self.tenant.clone(), request).await?;
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).settle(estimate_tokens(&reply) as u64);
+        Ok(reply)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl LowLevelClient for TenantClient {
+    async fn ask_raw(&self, prompt: String) -> Result<String, AIError> {
+        self.run(estimate_tokens(&prompt), self.inner.ask_raw(prompt)).await
+    }
+
+    fn clone_box(&self) -> Box<dyn LowLevelClient> {
+        Box::new(self.clone())
+    }
+
+    fn stream_raw(&self, prompt: String) -> Option<RawByteStream> {
+        if let Err(e) = self.admit(estimate_tokens(&prompt)) {
+            return Some(Box::pin(futures_util::stream::once(async move { Err(e) })));
+        }
+        self.inner.stream_raw(prompt)
+    }
+
+    fn with_temperature(&self, temperature: f32) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_temperature(temperature).map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_seed(&self, seed: u64) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_seed(seed).map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.inner.max_tokens()
+    }
+
+    fn with_max_tokens(&self, max_tokens: u32) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_max_tokens(max_tokens).map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_json_mode(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_json_mode().map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_prefill(&self, prefill: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_prefill(prefill).map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_stop_sequences(&self, stop: &[String]) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_stop_sequences(stop).map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_user(&self, user: &str) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_user(user).map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn with_logprobs(&self) -> Option<Box<dyn LowLevelClient>> {
+        self.inner.with_logprobs().map(|inner| Box::new(self.wrap(inner)) as Box<dyn LowLevelClient>)
+    }
+
+    fn supports_grammar(&self) -> bool {
+        self.inner.supports_grammar()
+    }
+
+    async fn ask_raw_constrained(&self, prompt: String, constraint: &OutputConstraint) -> Result<String, AIError> {
+        self.run(estimate_tokens(&prompt), self.inner.ask_raw_constrained(prompt, constraint)).await
+    }
+
+    fn supports_system_role(&self) -> bool {
+        self.inner.supports_system_role()
+    }
+
+    async fn ask_messages(&self, messages: Vec<ChatMessage>) -> Result<String, AIError> {
+        let prompt_tokens = messages.iter().map(|m| estimate_tokens(&m.content)).sum();
+        self.run(prompt_tokens, self.inner.ask_messages(messages)).await
+    }
+
+    async fn ask_request(&self, request: ModelRequest) -> Result<String, AIError> {
+        let prompt_tokens = request.messages.iter().map(|m| estimate_tokens(&m.content)).sum();
+        self.run(prompt_tokens, self.inner.ask_request(request)).await
+    }
+
+    async fn record_query(&self, record: &QueryRecord) {
+        self.inner.record_query(record).await
+    }
+
+    async fn flush(&self) -> Result<(), String> {
+        self.inner.flush().await
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn provider(&self) -> Option<&'static str> {
+        self.inner.provider()
+    }
+}
+
+impl<C: LowLevelClient> QueryResolver<C> {
+    /// These resolver settings over `tenant`'s client from the `Tenancy` set with
+    /// `with_tenancy`; fails with `QueryResolverError::Tenancy` for unknown tenants
+    pub fn for_tenant(&self, tenant: &str) -> Result<QueryResolver<Box<dyn LowLevelClient>>, QueryResolverError> {
+        let tenancy = self.tenancy().ok_or_else(|| QueryResolverError::Tenancy("no tenancy configured; see with_tenancy".into()))?;
+        let client = tenancy.client(tenant).ok_or_else(|| QueryResolverError::Tenancy(format!("unknown tenant '{tenant}'")))?;
+        Ok(self.with_client(Box::new(client)))
+    }
+}
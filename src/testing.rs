@@ -0,0 +1,422 @@
+//! Regression cases for prompts and schemas, run through the whole resolver pipeline.
+//!
+//! A case pairs a prompt with the reply (or replies, when retries are expected) a mock
+//! model gives, and says what the resolver must extract from it. Cases are kept in JSON
+//! files next to the tests:
+//!
+//! ```json
+//! [
+//!   {
+//!     "name": "invoice in a code fence",
+//!     "prompt": "Extract the invoice",
+//!     "response": "Sure:\n```json\n{\"number\": \"A-1\", \"total\": 12.5}\n```",
+//!     "expect": { "equals": { "number": "A-1", "total": 12.5 } }
+//!   },
+//!   {
+//!     "name": "retried after a reply without data",
+//!     "prompt": "Extract the invoice",
+//!     "responses": ["I found no invoice.", "{\"number\": \"B-2\", \"total\": 3}"],
+//!     "expect": { "matches": { "number": "B-2" }, "items": 1 }
+//!   }
+//! ]
+//! ```
+//!
+//! and turned into a `#[tokio::test]` with `regression_suite!`, which panics with every
+//! failing case. Later replies are only used when the resolver asks again, e.g. with a
+//! `retry::NO_DATA` strategy:
+//!
+//! ```ignore
+//! semantic_query::regression_suite!(invoices_extract, Invoice, "tests/cases/invoices.json");
+//! // or with the resolver settings under test; its client is replaced by the mock
+//! semantic_query::regression_suite!(invoices_extract_strict, Invoice, "tests/cases/invoices.json", my_resolver());
+//! ```
+//!
+//! Expectations (`Expectation`) combine: `equals` compares the first data item exactly,
+//! `matches` only the fields it lists, `items` counts data items, and `error` expects the
+//! query to fail with a message containing the text. A case without any expects the
+//! query to succeed. Cases can also be built in Rust with `RegressionCase::new`.
+//!
+//! # Capturing golden replies
+//!
+//! Replies need not be written by hand. Leave `responses` out, give the macro a resolver
+//! over a real provider, and run the test once with `SEMANTIC_QUERY_CAPTURE=missing`
+//! (`all` to refresh every case). Each case without replies is then sent to the provider,
+//! the replies it gave are stored in the file along with the provider's name, and the
+//! suite replays them from then on without network access. Pass a redaction function to
+//! scrub what is stored:
+//!
+//! ```ignore
+//! semantic_query::regression_suite!(claude_invoices, Invoice, "tests/cases/claude_invoices.json", claude_resolver(),
+//!     redact = |text: &mut String| *text = text.replace(ACCOUNT_ID, "[account]"));
+//! ```
+//!
+//! `RegressionSuite::capture` and `save` do the same from Rust.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::clients::mock::{MockClient, MockResponse};
+use crate::core::{LowLevelClient, QueryResolver};
+use crate::error::AIError;
+use crate::interceptors::{Interceptor, InterceptorStack, Redactor};
+
+/// Environment variable that turns on capture in `regression_suite!` tests; see
+/// `CaptureMode`
+pub const CAPTURE_ENV: &str = "SEMANTIC_QUERY_CAPTURE";
+
+/// What a case requires of the query result; every field set must hold
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// The first data item, serialized, equals this value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equals: Option<Value>,
+    /// The first data item has these fields with these values; nested objects match
+    /// the same way, arrays element by element
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<Value>,
+    /// Number of data items extracted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<usize>,
+    /// The query fails with an error whose message contains this text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One prompt, the mock model's replies, and what must be extracted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegressionCase {
+    pub name: String,
+    pub prompt: String,
+    /// Replies in the order the model gives them; `response` in JSON for just one
+    #[serde(alias = "response", deserialize_with = "one_or_many")]
+    pub responses: Vec<String>,
+    #[serde(default)]
+    pub expect: Expectation,
+    /// Provider the replies were captured from (see `RegressionSuite::capture`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(response) => vec![response],
+        OneOrMany::Many(responses) => responses,
+    })
+}
+
+impl RegressionCase {
+    pub fn new(name: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self { name: name.into(), prompt: prompt.into(), responses: Vec::new(), expect: Expectation::default(), provider: None }
+    }
+
+    /// Add a reply of the mock model
+    #[must_use]
+    pub fn replying(mut self, response: impl Into<String>) -> Self {
+        self.responses.push(response.into());
+        self
+    }
+
+    #[must_use]
+    pub fn expect_equals(mut self, value: Value) -> Self {
+        self.expect.equals = Some(value);
+        self
+    }
+
+    #[must_use]
+    pub fn expect_matches(mut self, value: Value) -> Self {
+        self.expect.matches = Some(value);
+        self
+    }
+
+    #[must_use]
+    pub fn expect_items(mut self, count: usize) -> Self {
+        self.expect.items = Some(count);
+        self
+    }
+
+    #[must_use]
+    pub fn expect_error(mut self, message: impl Into<String>) -> Self {
+        self.expect.error = Some(message.into());
+        self
+    }
+
+    /// Run the case through `resolver`'s settings over a mock client replaying
+    /// `responses`; `Err` says what did not hold
+    pub async fn run<T, C>(&self, resolver: &QueryResolver<C>) -> Result<(), String>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+        C: LowLevelClient,
+    {
+        if self.responses.is_empty() {
+            return Err(format!("no replies to replay; capture them with {CAPTURE_ENV}=missing"));
+        }
+        let (mock, handle) = MockClient::new();
+        handle.add_responses(self.responses.iter().cloned().map(MockResponse::Success).collect());
+        let result = resolver.with_client(mock).query::<T>(self.prompt.clone()).await;
+        drop(handle);
+
+        let expect = &self.expect;
+        let response = match (result, &expect.error) {
+            (Err(e), Some(needle)) if e.to_string().contains(needle.as_str()) => return Ok(()),
+            (Err(e), _) => return Err(format!("query failed: {e}")),
+            (Ok(_), Some(needle)) => return Err(format!("expected an error containing {needle:?}, but the query succeeded")),
+            (Ok(response), None) => response,
+        };
+        let items: Vec<Value> = response.data_only().into_iter()
+            .map(|item| serde_json::to_value(item).map_err(|e| format!("cannot serialize extracted item: {e}")))
+            .collect::<Result<_, _>>()?;
+        if let Some(count) = expect.items {
+            if items.len() != count {
+                return Err(format!("expected {count} data items, got {}: {}", items.len(), Value::Array(items)));
+            }
+        }
+        if expect.equals.is_none() && expect.matches.is_none() {
+            return Ok(());
+        }
+        let first = items.first().ok_or_else(|| format!("no data extracted from {:?}", response.text_content()))?;
+        if let Some(expected) = &expect.equals {
+            if first != expected {
+                return Err(format!("expected {expected}, got {first}"));
+            }
+        }
+        if let Some(expected) = &expect.matches {
+            if let Some(path) = mismatch(expected, first, String::new()) {
+                return Err(format!("{} differs: expected fields {expected}, got {first}", if path.is_empty() { "value" } else { &path }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which cases `RegressionSuite::capture` sends to the provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Cases without replies
+    Missing,
+    /// Every case, replacing the replies it has
+    All,
+}
+
+impl CaptureMode {
+    /// The mode `CAPTURE_ENV` asks for: `missing` (or `1`, `true`) or `all`; None when
+    /// unset, empty or `0`
+    pub fn from_env() -> Option<Self> {
+        match std::env::var(CAPTURE_ENV).ok()?.to_ascii_lowercase().as_str() {
+            "" | "0" | "false" => None,
+            "all" => Some(Self::All),
+            _ => Some(Self::Missing),
+        }
+    }
+
+    fn wants(self, case: &RegressionCase) -> bool {
+        self == Self::All || case.responses.is_empty()
+    }
+}
+
+/// Keeps the (redacted) replies of one captured query
+#[derive(Debug, Default)]
+struct Recorder {
+    replies: Mutex<Vec<String>>,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl Interceptor for Recorder {
+    async fn save(&self, _prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.replies.lock().unwrap_or_else(|e| e.into_inner()).push(response.to_string());
+        Ok(())
+    }
+}
+
+/// Where `actual` does not have what `expected` lists, as a JSON-path-like location
+fn mismatch(expected: &Value, actual: &Value, path: String) -> Option<String> {
+    match (expected, actual) {
+        (Value::Object(fields), Value::Object(actual)) => fields.iter().find_map(|(key, expected)| {
+            let path = format!("{path}.{key}");
+            match actual.get(key) {
+                Some(actual) => mismatch(expected, actual, path),
+                None => Some(path),
+            }
+        }),
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => expected.iter().zip(actual)
+            .enumerate()
+            .find_map(|(index, (expected, actual))| mismatch(expected, actual, format!("{path}[{index}]"))),
+        // 1 and 1.0 are the same number here
+        (Value::Number(expected), Value::Number(actual)) if expected.as_f64() == actual.as_f64() => None,
+        (expected, actual) if expected == actual => None,
+        _ => Some(path),
+    }
+}
+
+/// Outcome of one case
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Results of `RegressionSuite::run`
+#[derive(Debug, Clone, Default)]
+pub struct RegressionReport {
+    pub cases: Vec<CaseResult>,
+}
+
+impl RegressionReport {
+    pub fn failures(&self) -> Vec<&CaseResult> {
+        self.cases.iter().filter(|case| !case.passed).collect()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().is_empty()
+    }
+
+    /// Panic with every failing case; for use inside tests
+    pub fn assert_passed(&self) {
+        if !self.is_success() {
+            panic!("regression cases failed:\n{self}");
+        }
+    }
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for case in &self.cases {
+            write!(f, "{:>8}  {}", if case.passed { "ok" } else { "FAILED" }, case.name)?;
+            if !case.passed {
+                write!(f, ": {}", case.detail)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cases run together, usually loaded from one JSON file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RegressionSuite {
+    pub cases: Vec<RegressionCase>,
+}
+
+impl RegressionSuite {
+    pub fn new(cases: Vec<RegressionCase>) -> Self {
+        Self { cases }
+    }
+
+    /// Parse a JSON array of cases
+    pub fn from_json(json: &str) -> Result<Self, AIError> {
+        serde_json::from_str(json).map_err(|e| AIError::Configuration(format!("Invalid regression cases: {e}")))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AIError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| AIError::Configuration(format!("Failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&json)
+            .map_err(|e| AIError::Configuration(format!("Invalid regression cases in {}: {}", path.display(), e)))
+    }
+
+    /// Write the cases to `path` as pretty-printed JSON
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AIError> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AIError::Configuration(format!("Cannot serialize regression cases: {e}")))?;
+        std::fs::write(path, json + "\n")
+            .map_err(|e| AIError::Configuration(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Send the cases `mode` picks to `resolver`'s (real) client and store the replies
+    /// it gives, passed through `redactor` first, as the cases' `responses`. Returns the
+    /// number of cases captured; stops at the first query that fails, keeping earlier
+    /// captures.
+    pub async fn capture<T, C>(&mut self, resolver: &QueryResolver<C>, mode: CaptureMode, redactor: Option<Redactor>) -> Result<usize, String>
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+        C: LowLevelClient,
+    {
+        let redactor = redactor.map(Arc::new);
+        let provider = resolver.client().provider().map(str::to_string);
+        let mut captured = 0;
+        for case in self.cases.iter_mut().filter(|case| mode.wants(case)) {
+            let recorder = Arc::new(Recorder::default());
+            let mut stack = InterceptorStack::new();
+            if let Some(redactor) = &redactor {
+                stack = stack.push(redactor.clone());
+            }
+            let intercepted = resolver.intercepted(stack.push(recorder.clone()));
+            intercepted.query::<T>(case.prompt.clone()).await
+                .map_err(|e| format!("capturing '{}' failed: {e}", case.name))?;
+            case.responses = std::mem::take(&mut *recorder.replies.lock().unwrap_or_else(|e| e.into_inner()));
+            case.provider.clone_from(&provider);
+            captured += 1;
+        }
+        Ok(captured)
+    }
+
+    /// Run every case, in order, with `resolver`'s settings (see `RegressionCase::run`)
+    pub async fn run<T, C>(&self, resolver: &QueryResolver<C>) -> RegressionReport
+    where
+        T: DeserializeOwned + JsonSchema + Send + Debug + Serialize + Clone + 'static,
+        C: LowLevelClient,
+    {
+        let mut report = RegressionReport::default();
+        for case in &self.cases {
+            let outcome = case.run::<T, C>(resolver).await;
+            report.cases.push(CaseResult { name: case.name.clone(), passed: outcome.is_ok(), detail: outcome.err().unwrap_or_default() });
+        }
+        report
+    }
+}
+
+/// Generate a `#[tokio::test]` running the cases in a JSON file (relative to the
+/// calling crate's manifest) for type `$ty`, with a default resolver or `$resolver`.
+/// With `CAPTURE_ENV` set, replies are first captured from `$resolver`'s client and
+/// written to the file, redacted by `redact` if given (see the module docs).
+#[macro_export]
+macro_rules! regression_suite {
+    ($name:ident, $ty:ty, $path:literal) => {
+        $crate::regression_suite!(
+            $name,
+            $ty,
+            $path,
+            $crate::core::QueryResolver::new($crate::clients::mock::MockVoid, $crate::core::RetryConfig::default())
+        );
+    };
+    ($name:ident, $ty:ty, $path:literal, $resolver:expr) => {
+        $crate::regression_suite!(@test $name, $ty, $path, $resolver, None);
+    };
+    ($name:ident, $ty:ty, $path:literal, $resolver:expr, redact = $redact:expr) => {
+        $crate::regression_suite!(@test $name, $ty, $path, $resolver, Some($crate::interceptors::Redactor::new($redact)));
+    };
+    (@test $name:ident, $ty:ty, $path:literal, $resolver:expr, $redactor:expr) => {
+        #[tokio::test]
+        async fn $name() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/", $path);
+            let mut suite = $crate::testing::RegressionSuite::load(path).unwrap_or_else(|e| panic!("{e}"));
+            let resolver = $resolver;
+            if let Some(mode) = $crate::testing::CaptureMode::from_env() {
+                suite.capture::<$ty, _>(&resolver, mode, $redactor).await.unwrap_or_else(|e| panic!("{e}"));
+                suite.save(path).unwrap_or_else(|e| panic!("{e}"));
+            }
+            suite.run::<$ty, _>(&resolver).await.assert_passed();
+        }
+    };
+}
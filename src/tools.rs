@@ -0,0 +1,642 @@
+//! Built-in tools for agents: expression evaluation, date math and unit conversion.
+//!
+//! A `ToolRegistry` maps tool names to handlers and offers their `ToolSpec`s to the
+//! model. `ToolRegistry::new()` comes with three tools implemented natively in Rust, with
+//! no shelling out, file or network access, so demos and simple agents work without
+//! writing handlers:
+//!
+//! - `calculator`: arithmetic with `+ - * / % ^`, parentheses, `pi`, `e` and functions
+//!   such as `sqrt`, `round`, `min` and `max`
+//! - `date_math`: add days, weeks, months or years to a date, count the days between
+//!   two dates, or name a date's weekday
+//! - `unit_convert`: length, mass, time, volume, speed, data size and temperature
+//!
+//! ```
+//! use semantic_query::tools::ToolRegistry;
+//! use serde_json::json;
+//!
+//! let tools = ToolRegistry::new();
+//! let result = tools.call("calculator", &json!({"expression": "2 * (3 + 4)^2"})).unwrap();
+//! assert_eq!(result, json!({"result": 98.0}));
+//! ```
+//!
+//! Pass `tools.specs()` to `ModelRequest::with_tool` for providers with native tool
+//! calling and run the `streaming::ToolCall`s they return with `ToolRegistry::run`.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use chrono::{Datelike, Months, NaiveDate, Utc};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::ToolError;
+use crate::request::ToolSpec;
+use crate::serde_helpers::parse_date;
+use crate::streaming::ToolCall;
+
+/// A handler the model can call
+pub trait Tool: Send + Sync + Debug {
+    /// Name, description and input schema offered to the model
+    fn spec(&self) -> ToolSpec;
+
+    /// Run the tool on `args`, returning a JSON result for the model
+    fn call(&self, args: &Value) -> Result<Value, ToolError>;
+}
+
+/// Tools by name, in registration order. Clones share the tools.
+#[derive(Debug, Clone)]
+pub struct ToolRegistry(Vec<Arc<dyn Tool>>);
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolRegistry {
+    /// The built-in `calculator`, `date_math` and `unit_convert` tools
+    pub fn new() -> Self {
+        Self::empty().with_tool(Calculator).with_tool(DateMath).with_tool(UnitConvert)
+    }
+
+    /// No tools
+    pub fn empty() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Register `tool`, replacing any tool with the same name
+    #[must_use]
+    pub fn with_tool(mut self, tool: impl Tool + 'static) -> Self {
+        let name = tool.spec().name;
+        self.0.retain(|existing| existing.spec().name != name);
+        self.0.push(Arc::new(tool));
+        self
+    }
+
+    /// Register a closure under `spec`
+    #[must_use]
+    pub fn with_fn<F>(self, spec: ToolSpec, handler: F) -> Self
+    where
+        F: Fn(&Value) -> Result<Value, ToolError> + Send + Sync + 'static,
+    {
+        self.with_tool(FnTool { spec, handler: Arc::new(handler) })
+    }
+
+    /// Drop the tool called `name`
+    #[must_use]
+    pub fn without(mut self, name: &str) -> Self {
+        self.0.retain(|tool| tool.spec().name != name);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.0.iter().find(|tool| tool.spec().name == name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0.iter().map(|tool| tool.spec().name).collect()
+    }
+
+    /// Specs of every tool, for `ModelRequest::with_tool`
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        self.0.iter().map(|tool| tool.spec()).collect()
+    }
+
+    /// Run the tool called `name` on `args`
+    pub fn call(&self, name: &str, args: &Value) -> Result<Value, ToolError> {
+        self.get(name)
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?
+            .call(args)
+    }
+
+    /// Run a native tool call from a streamed or parsed response
+    pub fn run(&self, call: &ToolCall) -> Result<Value, ToolError> {
+        self.call(&call.name, &call.input)
+    }
+}
+
+type Handler = Arc<dyn Fn(&Value) -> Result<Value, ToolError> + Send + Sync>;
+
+struct FnTool {
+    spec: ToolSpec,
+    handler: Handler,
+}
+
+impl Debug for FnTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FnTool").field("name", &self.spec.name).finish_non_exhaustive()
+    }
+}
+
+impl Tool for FnTool {
+    fn spec(&self) -> ToolSpec {
+        self.spec.clone()
+    }
+
+    fn call(&self, args: &Value) -> Result<Value, ToolError> {
+        (self.handler)(args)
+    }
+}
+
+fn parse_args<T: DeserializeOwned>(tool: &str, args: &Value) -> Result<T, ToolError> {
+    T::deserialize(args).map_err(|e| invalid(tool, e))
+}
+
+fn invalid(tool: &str, message: impl ToString) -> ToolError {
+    ToolError::InvalidArguments { tool: tool.to_string(), message: message.to_string() }
+}
+
+fn failed(tool: &str, message: impl ToString) -> ToolError {
+    ToolError::Failed { tool: tool.to_string(), message: message.to_string() }
+}
+
+// ---------------------------------------------------------------------------
+// calculator
+
+/// Longest expression `evaluate` accepts
+pub const MAX_EXPRESSION_LEN: usize = 1024;
+
+/// Deepest nesting of parentheses, function calls and unary operators `evaluate` accepts
+const MAX_DEPTH: usize = 64;
+
+/// Evaluates arithmetic expressions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Calculator;
+
+#[derive(Deserialize, JsonSchema)]
+struct CalculatorArgs {
+    /// Arithmetic expression, e.g. `(12.5 * 4) / 3` or `sqrt(2) ^ 2`
+    expression: String,
+}
+
+impl Tool for Calculator {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec::for_type::<CalculatorArgs>(
+            "calculator",
+            "Evaluate an arithmetic expression. Supports + - * / % ^, parentheses, the constants pi, e and tau, \
+             and the functions sqrt, cbrt, abs, exp, ln, log, log2, log10, sin, cos, tan, asin, acos, atan, \
+             floor, ceil, trunc, round, min, max and pow.",
+        )
+    }
+
+    fn call(&self, args: &Value) -> Result<Value, ToolError> {
+        let args: CalculatorArgs = parse_args("calculator", args)?;
+        Ok(json!({ "result": evaluate(&args.expression)? }))
+    }
+}
+
+/// Evaluate an arithmetic expression, as the `calculator` tool does. Results that are not
+/// finite (division by zero, overflow, `sqrt(-1)`) are errors.
+pub fn evaluate(expression: &str) -> Result<f64, ToolError> {
+    if expression.len() > MAX_EXPRESSION_LEN {
+        return Err(invalid("calculator", format!("expression is longer than {MAX_EXPRESSION_LEN} bytes")));
+    }
+    let mut parser = Expr { text: expression.as_bytes(), pos: 0, depth: 0 };
+    let value = parser.sum()?;
+    parser.skip_ws();
+    if parser.pos < parser.text.len() {
+        return Err(parser.error("unexpected character"));
+    }
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(failed("calculator", "result is not a finite number"))
+    }
+}
+
+/// Recursive-descent evaluator: sum > product > unary minus > power (right-associative) > atom
+struct Expr<'a> {
+    text: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Expr<'_> {
+    fn error(&self, message: &str) -> ToolError {
+        invalid("calculator", format!("{message} at offset {}", self.pos))
+    }
+
+    fn skip_ws(&mut self) {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `token` after any whitespace
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        let matched = self.text[self.pos..].starts_with(token.as_bytes());
+        if matched {
+            self.pos += token.len();
+        }
+        matched
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ToolError>) -> Result<T, ToolError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("expression is nested too deeply"));
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn sum(&mut self) -> Result<f64, ToolError> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat("+") {
+                value += self.product()?;
+            } else if self.eat("-") {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, ToolError> {
+        let mut value = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                '*'
+            } else if self.eat("/") {
+                '/'
+            } else if self.eat("%") {
+                '%'
+            } else {
+                return Ok(value);
+            };
+            let rhs = self.unary()?;
+            if op != '*' && rhs == 0.0 {
+                return Err(failed("calculator", "division by zero"));
+            }
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, ToolError> {
+        if self.eat("-") {
+            return self.nested(|p| p.unary()).map(|v| -v);
+        }
+        if self.eat("+") {
+            return self.nested(|p| p.unary());
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<f64, ToolError> {
+        let base = self.atom()?;
+        if self.eat("^") || self.eat("**") {
+            let exponent = self.nested(|p| p.unary())?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, ToolError> {
+        self.skip_ws();
+        match self.text.get(self.pos) {
+            Some(b'(') => {
+                self.pos += 1;
+                let value = self.nested(|p| p.sum())?;
+                if !self.eat(")") {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || *c == b'.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.text.get(self.pos).is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_') {
+                    self.pos += 1;
+                }
+                let name = std::str::from_utf8(&self.text[start..self.pos]).unwrap_or_default().to_ascii_lowercase();
+                if self.eat("(") {
+                    let args = self.nested(|p| p.arguments())?;
+                    self.function(&name, &args)
+                } else {
+                    match name.as_str() {
+                        "pi" => Ok(std::f64::consts::PI),
+                        "e" => Ok(std::f64::consts::E),
+                        "tau" => Ok(std::f64::consts::TAU),
+                        _ => Err(invalid("calculator", format!("unknown constant '{name}'"))),
+                    }
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    /// Comma-separated arguments up to the closing parenthesis
+    fn arguments(&mut self) -> Result<Vec<f64>, ToolError> {
+        let mut args = Vec::new();
+        if self.eat(")") {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.sum()?);
+            if self.eat(")") {
+                return Ok(args);
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected ',' or ')'"));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, ToolError> {
+        let start = self.pos;
+        while self.text.get(self.pos).is_some_and(|c| c.is_ascii_digit() || *c == b'.') {
+            self.pos += 1;
+        }
+        if matches!(self.text.get(self.pos), Some(b'e' | b'E'))
+            && self.text.get(self.pos + 1).is_some_and(|c| c.is_ascii_digit() || matches!(c, b'+' | b'-'))
+        {
+            self.pos += 2;
+            while self.text.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                self.pos += 1;
+            }
+        }
+        std::str::from_utf8(&self.text[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid("calculator", format!("invalid number at offset {start}")))
+    }
+
+    fn function(&self, name: &str, args: &[f64]) -> Result<f64, ToolError> {
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(invalid("calculator", format!("{name}() takes {n} argument(s), got {}", args.len())))
+            }
+        };
+        let unary: Option<fn(f64) -> f64> = match name {
+            "sqrt" => Some(f64::sqrt),
+            "cbrt" => Some(f64::cbrt),
+            "abs" => Some(f64::abs),
+            "exp" => Some(f64::exp),
+            "ln" => Some(f64::ln),
+            "log2" => Some(f64::log2),
+            "log10" => Some(f64::log10),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "asin" => Some(f64::asin),
+            "acos" => Some(f64::acos),
+            "atan" => Some(f64::atan),
+            "floor" => Some(f64::floor),
+            "ceil" => Some(f64::ceil),
+            "trunc" => Some(f64::trunc),
+            _ => None,
+        };
+        if let Some(f) = unary {
+            arity(1)?;
+            return Ok(f(args[0]));
+        }
+        match name {
+            // log(x) is base 10, log(x, base) any base
+            "log" if args.len() == 2 => Ok(args[0].log(args[1])),
+            "log" => arity(1).map(|_| args[0].log10()),
+            "pow" => arity(2).map(|_| args[0].powf(args[1])),
+            // round(x) to an integer, round(x, digits) to decimal places
+            "round" if args.len() == 2 => {
+                let scale = 10f64.powi(args[1] as i32);
+                Ok((args[0] * scale).round() / scale)
+            }
+            "round" => arity(1).map(|_| args[0].round()),
+            "min" | "max" if args.is_empty() => Err(invalid("calculator", format!("{name}() needs at least one argument"))),
+            "min" => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+            "max" => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+            _ => Err(invalid("calculator", format!("unknown function '{name}'"))),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// date_math
+
+/// Calendar arithmetic on dates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateMath;
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum DateArgs {
+    /// Shift `date` by the given amounts (negative to go back). Month and year shifts
+    /// clamp to the end of shorter months.
+    Add {
+        /// A date such as `2024-03-15`, or `today`
+        date: String,
+        #[serde(default)]
+        years: i32,
+        #[serde(default)]
+        months: i32,
+        #[serde(default)]
+        weeks: i64,
+        #[serde(default)]
+        days: i64,
+    },
+    /// Days from `from` to `to` (negative when `to` is earlier)
+    Diff { from: String, to: String },
+    /// Day of the week of `date`
+    Weekday { date: String },
+}
+
+impl Tool for DateMath {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec::for_type::<DateArgs>(
+            "date_math",
+            "Date arithmetic. op=add shifts a date by years/months/weeks/days; op=diff counts the days between \
+             two dates; op=weekday names the day of the week. Dates are YYYY-MM-DD (other common layouts and \
+             'today' are accepted); results are YYYY-MM-DD.",
+        )
+    }
+
+    fn call(&self, args: &Value) -> Result<Value, ToolError> {
+        match parse_args("date_math", args)? {
+            DateArgs::Add { date, years, months, weeks, days } => {
+                let date = date_arg(&date)?;
+                let months = years.checked_mul(12).and_then(|y| y.checked_add(months))
+                    .ok_or_else(|| failed("date_math", "date out of range"))?;
+                let shifted = if months >= 0 {
+                    date.checked_add_months(Months::new(months.unsigned_abs()))
+                } else {
+                    date.checked_sub_months(Months::new(months.unsigned_abs()))
+                };
+                let shifted = shifted
+                    .and_then(|d| d.checked_add_signed(chrono::Duration::try_weeks(weeks)?))
+                    .and_then(|d| d.checked_add_signed(chrono::Duration::try_days(days)?))
+                    .ok_or_else(|| failed("date_math", "date out of range"))?;
+                Ok(json!({ "date": shifted.to_string(), "weekday": weekday_name(&shifted) }))
+            }
+            DateArgs::Diff { from, to } => {
+                let days = (date_arg(&to)? - date_arg(&from)?).num_days();
+                Ok(json!({ "days": days }))
+            }
+            DateArgs::Weekday { date } => {
+                let date = date_arg(&date)?;
+                Ok(json!({ "date": date.to_string(), "weekday": weekday_name(&date) }))
+            }
+        }
+    }
+}
+
+fn date_arg(text: &str) -> Result<NaiveDate, ToolError> {
+    if text.trim().eq_ignore_ascii_case("today") {
+        return Ok(Utc::now().date_naive());
+    }
+    parse_date(text).ok_or_else(|| invalid("date_math", format!("unrecognized date '{text}'")))
+}
+
+fn weekday_name(date: &NaiveDate) -> &'static str {
+    ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"][date.weekday().num_days_from_monday() as usize]
+}
+
+// ---------------------------------------------------------------------------
+// unit_convert
+
+/// Converts between units of the same dimension
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnitConvert;
+
+#[derive(Deserialize, JsonSchema)]
+struct UnitArgs {
+    value: f64,
+    /// Unit of `value`, e.g. `km`, `lb`, `°F`, `GiB`
+    from: String,
+    /// Unit to convert to
+    to: String,
+}
+
+/// Dimension, size in the dimension's base unit, and accepted names (lowercase)
+const UNITS: &[(&str, f64, &[&str])] = &[
+    ("length", 1.0, &["m", "meter", "metre"]),
+    ("length", 1e3, &["km", "kilometer", "kilometre"]),
+    ("length", 1e-2, &["cm", "centimeter", "centimetre"]),
+    ("length", 1e-3, &["mm", "millimeter", "millimetre"]),
+    ("length", 1609.344, &["mi", "mile"]),
+    ("length", 0.9144, &["yd", "yard"]),
+    ("length", 0.3048, &["ft", "foot", "feet"]),
+    ("length", 0.0254, &["in", "inch", "inches"]),
+    ("length", 1852.0, &["nmi", "nautical mile"]),
+    ("mass", 1.0, &["kg", "kilogram"]),
+    ("mass", 1e-3, &["g", "gram"]),
+    ("mass", 1e-6, &["mg", "milligram"]),
+    ("mass", 1e3, &["t", "tonne", "metric ton"]),
+    ("mass", 0.45359237, &["lb", "lbs", "pound"]),
+    ("mass", 0.028349523125, &["oz", "ounce"]),
+    ("mass", 6.35029318, &["st", "stone"]),
+    ("time", 1.0, &["s", "sec", "second"]),
+    ("time", 1e-3, &["ms", "millisecond"]),
+    ("time", 60.0, &["min", "minute"]),
+    ("time", 3600.0, &["h", "hr", "hour"]),
+    ("time", 86_400.0, &["d", "day"]),
+    ("time", 604_800.0, &["wk", "week"]),
+    ("time", 31_557_600.0, &["yr", "year"]),
+    ("volume", 1.0, &["l", "liter", "litre"]),
+    ("volume", 1e-3, &["ml", "milliliter", "millilitre"]),
+    ("volume", 1e3, &["m3", "m³", "cubic meter", "cubic metre"]),
+    ("volume", 3.785411784, &["gal", "gallon"]),
+    ("volume", 0.946352946, &["qt", "quart"]),
+    ("volume", 0.473176473, &["pt", "pint"]),
+    ("volume", 0.2365882365, &["cup"]),
+    ("volume", 0.0295735295625, &["fl oz", "fluid ounce"]),
+    ("speed", 1.0, &["m/s", "meters per second"]),
+    ("speed", 1.0 / 3.6, &["km/h", "kph", "kmh", "kilometers per hour"]),
+    ("speed", 0.44704, &["mph", "miles per hour"]),
+    ("speed", 1852.0 / 3600.0, &["kn", "kt", "knot"]),
+    ("data", 1.0, &["b", "byte"]),
+    ("data", 0.125, &["bit"]),
+    ("data", 1e3, &["kb", "kilobyte"]),
+    ("data", 1e6, &["mb", "megabyte"]),
+    ("data", 1e9, &["gb", "gigabyte"]),
+    ("data", 1e12, &["tb", "terabyte"]),
+    ("data", 1024.0, &["kib", "kibibyte"]),
+    ("data", 1_048_576.0, &["mib", "mebibyte"]),
+    ("data", 1_073_741_824.0, &["gib", "gibibyte"]),
+    ("data", 1_099_511_627_776.0, &["tib", "tebibyte"]),
+];
+
+/// Temperature scales, which convert through an offset as well as a factor
+#[derive(Clone, Copy)]
+enum Temperature {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl Temperature {
+    fn parse(unit: &str) -> Option<Self> {
+        match unit.trim_start_matches('°') {
+            "c" | "celsius" => Some(Self::Celsius),
+            "f" | "fahrenheit" => Some(Self::Fahrenheit),
+            "k" | "kelvin" | "kelvins" => Some(Self::Kelvin),
+            _ => None,
+        }
+    }
+
+    fn to_kelvin(self, value: f64) -> f64 {
+        match self {
+            Self::Celsius => value + 273.15,
+            Self::Fahrenheit => (value - 32.0) * 5.0 / 9.0 + 273.15,
+            Self::Kelvin => value,
+        }
+    }
+
+    fn in_scale(self, kelvin: f64) -> f64 {
+        match self {
+            Self::Celsius => kelvin - 273.15,
+            Self::Fahrenheit => (kelvin - 273.15) * 9.0 / 5.0 + 32.0,
+            Self::Kelvin => kelvin,
+        }
+    }
+}
+
+/// Dimension and factor of `unit`, ignoring case and a plural `s`
+fn lookup_unit(unit: &str) -> Option<(&'static str, f64)> {
+    let unit = unit.trim().to_lowercase();
+    let find = |name: &str| UNITS.iter().find(|(_, _, names)| names.contains(&name)).map(|(dim, factor, _)| (*dim, *factor));
+    find(&unit).or_else(|| unit.strip_suffix('s').and_then(find))
+}
+
+impl Tool for UnitConvert {
+    fn spec(&self) -> ToolSpec {
+        ToolSpec::for_type::<UnitArgs>(
+            "unit_convert",
+            "Convert a value between units of length (m, km, mi, ft, in...), mass (kg, g, lb, oz...), time \
+             (s, min, h, day...), volume (l, ml, gal, cup...), speed (m/s, km/h, mph, knot), data size \
+             (B, KB, MB, KiB, MiB...) or temperature (C, F, K).",
+        )
+    }
+
+    fn call(&self, args: &Value) -> Result<Value, ToolError> {
+        let args: UnitArgs = parse_args("unit_convert", args)?;
+        let value = convert(args.value, &args.from, &args.to)?;
+        Ok(json!({ "value": value, "unit": args.to }))
+    }
+}
+
+/// Convert `value` from one unit to another, as the `unit_convert` tool does
+pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, ToolError> {
+    let (from_lower, to_lower) = (from.trim().to_lowercase(), to.trim().to_lowercase());
+    if let (Some(from), Some(to)) = (Temperature::parse(&from_lower), Temperature::parse(&to_lower)) {
+        return Ok(to.in_scale(from.to_kelvin(value)));
+    }
+    let unknown = |unit: &str| invalid("unit_convert", format!("unknown unit '{unit}'"));
+    let (from_dim, from_factor) = lookup_unit(from).ok_or_else(|| unknown(from))?;
+    let (to_dim, to_factor) = lookup_unit(to).ok_or_else(|| unknown(to))?;
+    if from_dim != to_dim {
+        return Err(invalid("unit_convert", format!("cannot convert {from_dim} ({from}) to {to_dim} ({to})")));
+    }
+    Ok(value * from_factor / to_factor)
+}
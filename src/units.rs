@@ -0,0 +1,284 @@
+//! Quantities with units, for extractions like "3.5 hours", "2,5 Stunden" or "150 ms"
+//! that a plain numeric field rejects.
+//!
+//! `Quantity` keeps the value and the unit the model wrote. The modules below convert
+//! to one unit instead, with `#[serde(with = "...")]` on an `f64` field; a bare number
+//! is taken to be in that unit already:
+//!
+//! ```
+//! use schemars::JsonSchema;
+//! use semantic_query::units::Quantity;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, JsonSchema)]
+//! struct Estimate {
+//!     #[serde(with = "semantic_query::units::hours")]
+//!     #[schemars(schema_with = "semantic_query::units::hours::schema")]
+//!     effort: f64, // 3.5, "3.5 hours", "2,5 Stunden", "90 min", "1h 30min"
+//!     latency: Quantity, // "150 ms" -> Quantity { value: 150.0, unit: "ms" }
+//! }
+//!
+//! let estimate: Estimate = serde_json::from_str(r#"{"effort": "90 min", "latency": "150 ms"}"#).unwrap();
+//! assert_eq!(estimate.effort, 1.5);
+//! assert_eq!(estimate.latency.to("s"), Some(0.15));
+//! ```
+//!
+//! For types that cannot be annotated, `postprocess::NormalizeUnits` rewrites quantity
+//! strings at JSON pointers before deserialization.
+
+use std::fmt;
+
+use crate::schema::{schema_from_json, Schema, SchemaGenerator};
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Time,
+    Length,
+    Mass,
+    Data,
+}
+
+struct UnitDef {
+    symbol: &'static str,
+    /// Plural English name, for schema descriptions
+    name: &'static str,
+    dimension: Dimension,
+    /// Size in the dimension's base unit: seconds, meters, kilograms, bytes
+    factor: f64,
+    /// Lowercase spellings, in English and the common European languages
+    aliases: &'static [&'static str],
+}
+
+const UNITS: &[UnitDef] = &[
+    UnitDef { symbol: "ms", name: "milliseconds", dimension: Dimension::Time, factor: 0.001, aliases: &["ms", "msec", "millisecond", "milliseconds", "millisekunde", "millisekunden", "milisegundo", "milisegundos", "milliseconde", "millisecondes"] },
+    UnitDef { symbol: "s", name: "seconds", dimension: Dimension::Time, factor: 1.0, aliases: &["s", "sec", "secs", "second", "seconds", "sek", "sekunde", "sekunden", "segundo", "segundos", "seconde", "secondes", "secondo", "secondi", "seconden"] },
+    UnitDef { symbol: "min", name: "minutes", dimension: Dimension::Time, factor: 60.0, aliases: &["min", "mins", "minute", "minutes", "minuten", "minuto", "minutos", "minuti"] },
+    UnitDef { symbol: "h", name: "hours", dimension: Dimension::Time, factor: 3600.0, aliases: &["h", "hr", "hrs", "hour", "hours", "std", "stunde", "stunden", "hora", "horas", "heure", "heures", "ora", "ore", "uur", "uren"] },
+    UnitDef { symbol: "d", name: "days", dimension: Dimension::Time, factor: 86400.0, aliases: &["d", "day", "days", "tag", "tage", "tagen", "día", "días", "dia", "dias", "jour", "jours", "giorno", "giorni", "dag", "dagen"] },
+    UnitDef { symbol: "wk", name: "weeks", dimension: Dimension::Time, factor: 604800.0, aliases: &["w", "wk", "wks", "week", "weeks", "woche", "wochen", "semana", "semanas", "semaine", "semaines", "settimana", "settimane", "weken"] },
+    UnitDef { symbol: "mm", name: "millimeters", dimension: Dimension::Length, factor: 0.001, aliases: &["mm", "millimeter", "millimeters", "millimetre", "millimetres", "milímetros", "millimètres"] },
+    UnitDef { symbol: "cm", name: "centimeters", dimension: Dimension::Length, factor: 0.01, aliases: &["cm", "centimeter", "centimeters", "centimetre", "centimetres", "zentimeter", "centímetros", "centimètres"] },
+    UnitDef { symbol: "m", name: "meters", dimension: Dimension::Length, factor: 1.0, aliases: &["meter", "meters", "metre", "metres", "metro", "metros", "mètre", "mètres", "metri"] },
+    UnitDef { symbol: "km", name: "kilometers", dimension: Dimension::Length, factor: 1000.0, aliases: &["km", "kilometer", "kilometers", "kilometre", "kilometres", "kilómetro", "kilómetros", "kilomètre", "kilomètres", "chilometri"] },
+    UnitDef { symbol: "in", name: "inches", dimension: Dimension::Length, factor: 0.0254, aliases: &["in", "inch", "inches"] },
+    UnitDef { symbol: "ft", name: "feet", dimension: Dimension::Length, factor: 0.3048, aliases: &["ft", "foot", "feet"] },
+    UnitDef { symbol: "mi", name: "miles", dimension: Dimension::Length, factor: 1609.344, aliases: &["mi", "mile", "miles"] },
+    UnitDef { symbol: "mg", name: "milligrams", dimension: Dimension::Mass, factor: 1e-6, aliases: &["mg", "milligram", "milligrams", "milligramm"] },
+    UnitDef { symbol: "g", name: "grams", dimension: Dimension::Mass, factor: 0.001, aliases: &["g", "gram", "grams", "gramm", "gramo", "gramos", "gramme", "grammes", "grammi"] },
+    UnitDef { symbol: "kg", name: "kilograms", dimension: Dimension::Mass, factor: 1.0, aliases: &["kg", "kilo", "kilos", "kilogram", "kilograms", "kilogramm", "kilogramo", "kilogramos", "kilogramme", "kilogrammes"] },
+    UnitDef { symbol: "lb", name: "pounds", dimension: Dimension::Mass, factor: 0.453_592_37, aliases: &["lb", "lbs", "pound", "pounds"] },
+    UnitDef { symbol: "oz", name: "ounces", dimension: Dimension::Mass, factor: 0.028_349_523_125, aliases: &["oz", "ounce", "ounces"] },
+    UnitDef { symbol: "B", name: "bytes", dimension: Dimension::Data, factor: 1.0, aliases: &["b", "byte", "bytes"] },
+    UnitDef { symbol: "KB", name: "kilobytes", dimension: Dimension::Data, factor: 1e3, aliases: &["kb", "kilobyte", "kilobytes"] },
+    UnitDef { symbol: "MB", name: "megabytes", dimension: Dimension::Data, factor: 1e6, aliases: &["mb", "megabyte", "megabytes"] },
+    UnitDef { symbol: "GB", name: "gigabytes", dimension: Dimension::Data, factor: 1e9, aliases: &["gb", "gigabyte", "gigabytes"] },
+    UnitDef { symbol: "TB", name: "terabytes", dimension: Dimension::Data, factor: 1e12, aliases: &["tb", "terabyte", "terabytes"] },
+    UnitDef { symbol: "KiB", name: "kibibytes", dimension: Dimension::Data, factor: 1024.0, aliases: &["kib", "kibibyte", "kibibytes"] },
+    UnitDef { symbol: "MiB", name: "mebibytes", dimension: Dimension::Data, factor: 1_048_576.0, aliases: &["mib", "mebibyte", "mebibytes"] },
+    UnitDef { symbol: "GiB", name: "gibibytes", dimension: Dimension::Data, factor: 1_073_741_824.0, aliases: &["gib", "gibibyte", "gibibytes"] },
+];
+
+/// The unit spelled `name`, by symbol ("MB", "m") or by alias in any case
+fn lookup(name: &str) -> Option<&'static UnitDef> {
+    let name = name.trim().trim_end_matches('.');
+    UNITS.iter().find(|u| u.symbol == name).or_else(|| {
+        let lower = name.to_lowercase();
+        UNITS.iter().find(|u| u.aliases.contains(&lower.as_str()))
+    })
+}
+
+/// Canonical symbol of a unit spelling: `"Stunden"` → `"h"`, `"milliseconds"` → `"ms"`
+pub fn unit_symbol(name: &str) -> Option<&'static str> {
+    lookup(name).map(|u| u.symbol)
+}
+
+/// Convert `value` from one unit to another of the same kind (time, length, mass or
+/// data size); None for unknown or incompatible units
+pub fn convert(value: f64, from: &str, to: &str) -> Option<f64> {
+    let (from, to) = (lookup(from)?, lookup(to)?);
+    (from.dimension == to.dimension).then(|| value * from.factor / to.factor)
+}
+
+/// A number and its unit. The unit is the canonical symbol when the spelling is known
+/// (`unit_symbol`), as written otherwise, and empty for a bare number.
+///
+/// Deserializes from `"3.5 hours"`, `{"value": 3.5, "unit": "h"}` or a bare number;
+/// serializes as `{"value": 3.5, "unit": "h"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: String,
+}
+
+impl Quantity {
+    pub fn new(value: f64, unit: impl Into<String>) -> Self {
+        let unit = unit.into();
+        Self { value, unit: unit_symbol(&unit).map_or(unit, str::to_string) }
+    }
+
+    /// The value in `unit`; None when the units are unknown or of different kinds
+    pub fn to(&self, unit: &str) -> Option<f64> {
+        convert(self.value, &self.unit, unit)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => parse_quantity(s),
+            Value::Number(n) => Some(Self::new(n.as_f64()?, "")),
+            Value::Object(map) => {
+                let number = match map.get("value")? {
+                    Value::Number(n) => n.as_f64()?,
+                    Value::String(s) => parse_decimal(s)?,
+                    _ => return None,
+                };
+                let unit = map.get("unit").and_then(Value::as_str).unwrap_or_default();
+                Some(Self::new(number, unit))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.unit.is_empty() {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{} {}", self.value, self.unit)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Self::from_value(&value).ok_or_else(|| expected("a quantity like \"3.5 hours\"", &value))
+    }
+}
+
+/// Parse a quantity as models write it: `"3.5 hours"`, `"2,5 Stunden"`, `"150ms"`,
+/// `"1.234,5 km"`. Runs of one kind add up in the first unit, so `"1h 30min"` is
+/// 1.5 hours. A bare number has an empty unit.
+pub fn parse_quantity(text: &str) -> Option<Quantity> {
+    let mut rest = text.trim();
+    let mut parts = Vec::new();
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | ',' | ' ' | '_' | '\u{a0}' | '\u{202f}' | '-' | '+'))).unwrap_or(rest.len());
+        let unit_end = rest[number_end..].find(|c: char| c.is_ascii_digit()).map_or(rest.len(), |i| number_end + i);
+        let value = parse_decimal(&rest[..number_end])?;
+        parts.push((value, rest[number_end..unit_end].trim()));
+        rest = rest[unit_end..].trim_start();
+    }
+    let (&(first, unit), more) = parts.split_first()?;
+    if more.is_empty() {
+        return Some(Quantity::new(first, unit));
+    }
+    let mut total = first;
+    for &(value, part_unit) in more {
+        total += convert(value, part_unit, unit)?;
+    }
+    Some(Quantity::new(total, unit))
+}
+
+/// A decimal number with either `.` or `,` as the decimal mark. Where both appear the
+/// last one is the mark; a lone `,` is a thousands separator only before exactly three
+/// digits (`"1,500"`), and repeated `.`s are thousands separators (`"1.234.567"`).
+fn parse_decimal(text: &str) -> Option<f64> {
+    let cleaned: String = text.trim().chars().filter(|c| !matches!(c, ' ' | '_' | '\u{a0}' | '\u{202f}')).collect();
+    let (commas, dots) = (cleaned.matches(',').count(), cleaned.matches('.').count());
+    let normalized = match (commas, dots) {
+        (0, 0) => cleaned,
+        (_, 0) if commas == 1 && cleaned.rsplit(',').next()?.len() != 3 => cleaned.replace(',', "."),
+        (_, 0) => cleaned.replace(',', ""),
+        (0, _) if dots > 1 => cleaned.replace('.', ""),
+        (0, _) => cleaned,
+        _ if cleaned.rfind(',')? > cleaned.rfind('.')? => cleaned.replace('.', "").replace(',', "."),
+        _ => cleaned.replace(',', ""),
+    };
+    normalized.parse().ok()
+}
+
+fn expected<E: de::Error>(what: &str, value: &Value) -> E {
+    E::custom(format!("expected {what}, found {value}"))
+}
+
+/// `value` as a number in `unit`; strings are parsed with `parse_quantity`, and bare
+/// numbers are taken to be in `unit` already
+pub(crate) fn to_unit(value: &Value, unit: &str) -> Option<f64> {
+    let quantity = Quantity::from_value(value)?;
+    if quantity.unit.is_empty() {
+        Some(quantity.value)
+    } else {
+        quantity.to(unit)
+    }
+}
+
+fn deserialize_in<'de, D: Deserializer<'de>>(deserializer: D, unit: &str) -> Result<f64, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    to_unit(&value, unit).ok_or_else(|| expected(&format!("a quantity in {}", lookup(unit).map_or(unit, |u| u.name)), &value))
+}
+
+fn schema_in(unit: &str, nullable: bool) -> Schema {
+    let name = lookup(unit).map_or(unit, |u| u.name);
+    let kind = if nullable { json!(["number", "null"]) } else { json!("number") };
+    schema_from_json(json!({ "type": kind, "description": format!("In {name}") }))
+}
+
+macro_rules! unit_fields {
+    ($($module:ident => $unit:literal),* $(,)?) => {$(
+        #[doc = concat!("`f64` in `", $unit, "`, accepting any quantity of the same kind. Serialized as a bare number.")]
+        pub mod $module {
+            use super::*;
+
+            pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_f64(*value)
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+                deserialize_in(deserializer, $unit)
+            }
+
+            pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+                schema_in($unit, false)
+            }
+
+            /// `Option<f64>`; `null` and `""` are `None`
+            pub mod option {
+                use super::super::*;
+
+                pub fn serialize<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+                    value.serialize(serializer)
+                }
+
+                pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+                    match Value::deserialize(deserializer)? {
+                        Value::Null => Ok(None),
+                        Value::String(s) if s.trim().is_empty() => Ok(None),
+                        value => to_unit(&value, $unit).map(Some).ok_or_else(|| expected(concat!("a quantity in ", $unit), &value)),
+                    }
+                }
+
+                pub fn schema(_generator: &mut SchemaGenerator) -> Schema {
+                    schema_in($unit, true)
+                }
+            }
+        }
+    )*};
+}
+
+unit_fields! {
+    milliseconds => "ms",
+    seconds => "s",
+    minutes => "min",
+    hours => "h",
+    days => "d",
+    meters => "m",
+    kilometers => "km",
+    grams => "g",
+    kilograms => "kg",
+    bytes => "B",
+    megabytes => "MB",
+}
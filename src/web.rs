@@ -0,0 +1,174 @@
+//! Re-emitting semantic streams to browsers over SSE or WebSockets.
+//!
+//! `web_events` turns a `stream_query` stream into `WebEvent`s with stable names
+//! (`token`, `text`, `data`, `finished`, `error`, `ping`, `end`), inserting keep-alive
+//! pings while the model is quiet. `sse_body` encodes them as a `text/event-stream` body
+//! and `websocket_messages` as JSON text frames, so a proxy endpoint is one call:
+//!
+//! ```ignore
+//! // axum
+//! async fn extract(State(resolver): State<Resolver>) -> impl IntoResponse {
+//!     let stream = resolver.stream_query::<Invoice>(prompt).await?;
+//!     ([(CONTENT_TYPE, SSE_CONTENT_TYPE)], Body::from_stream(sse_body(stream, WebOptions::default())))
+//! }
+//! ```
+//!
+//! Every event's data is a single-line JSON object: `{"token": ".."}`, `{"text": ".."}`,
+//! the serialized `T`, `{"reason": ".."}`, `{"message": ".."}`, or `{}` for `ping`/`end`.
+
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::streaming::{FinishReason, StreamItem, TextContent};
+
+/// `Content-Type` of an `sse_body` response
+pub const SSE_CONTENT_TYPE: &str = "text/event-stream";
+
+/// A stream item ready to send to a browser
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebEvent {
+    Token(String),
+    Text(TextContent),
+    /// A parsed `T`, already serialized
+    Data(Value),
+    Finished(FinishReason),
+    /// The source stream yielded an error (or a `T` failed to serialize)
+    Error(String),
+    /// Keep-alive sent after `WebOptions::keep_alive` without other events
+    Ping,
+    /// The source stream ended
+    End,
+}
+
+impl WebEvent {
+    /// Stable event name, used as the SSE `event:` field and the WebSocket `event` key
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Token(_) => "token",
+            Self::Text(_) => "text",
+            Self::Data(_) => "data",
+            Self::Finished(_) => "finished",
+            Self::Error(_) => "error",
+            Self::Ping => "ping",
+            Self::End => "end",
+        }
+    }
+
+    /// JSON payload of the event
+    pub fn data(&self) -> Value {
+        match self {
+            Self::Token(token) => json!({ "token": token }),
+            Self::Text(text) => json!({ "text": text.text }),
+            Self::Data(value) => value.clone(),
+            Self::Finished(reason) => json!({ "reason": reason }),
+            Self::Error(message) => json!({ "message": message }),
+            Self::Ping | Self::End => json!({}),
+        }
+    }
+
+    /// One SSE frame, with an `id:` line if `id` is set
+    pub fn to_sse(&self, id: Option<u64>) -> String {
+        let id = id.map(|id| format!("id: {id}\n")).unwrap_or_default();
+        format!("{id}event: {}\ndata: {}\n\n", self.name(), self.data())
+    }
+
+    /// `{"event": <name>, "data": <payload>}`, for a WebSocket text frame
+    pub fn to_json(&self) -> String {
+        json!({ "event": self.name(), "data": self.data() }).to_string()
+    }
+
+    fn from_item<T: Serialize + JsonSchema>(item: StreamItem<T>) -> Self {
+        match item {
+            StreamItem::Token(token) => Self::Token(token),
+            StreamItem::Text(text) => Self::Text(text),
+            StreamItem::Data(data) => match serde_json::to_value(&data) {
+                Ok(value) => Self::Data(value),
+                Err(e) => Self::Error(format!("could not serialize data: {e}")),
+            },
+            StreamItem::Finished { reason } => Self::Finished(reason),
+        }
+    }
+}
+
+/// How `web_events` frames a stream
+#[derive(Debug, Clone, Copy)]
+pub struct WebOptions {
+    /// Send a `ping` after this long without other events (default 15s; None disables)
+    pub keep_alive: Option<Duration>,
+    /// Send an `end` event when the source stream ends (default true), so clients can
+    /// tell completion from a dropped connection
+    pub end_event: bool,
+    /// Number SSE frames with `id:` lines (default true)
+    pub event_ids: bool,
+}
+
+impl Default for WebOptions {
+    fn default() -> Self {
+        Self { keep_alive: Some(Duration::from_secs(15)), end_event: true, event_ids: true }
+    }
+}
+
+/// Convert a semantic stream into `WebEvent`s. Errors become `error` events; the
+/// stream continues for as long as the source does.
+pub fn web_events<S, T, E>(stream: S, options: WebOptions) -> impl Stream<Item = WebEvent>
+where
+    S: Stream<Item = Result<StreamItem<T>, E>>,
+    T: Serialize + JsonSchema,
+    E: Display,
+{
+    async_stream::stream! {
+        let mut items = std::pin::pin!(stream);
+        loop {
+            let next = match options.keep_alive {
+                Some(period) => match crate::runtime::timeout(period, items.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        yield WebEvent::Ping;
+                        continue;
+                    }
+                },
+                None => items.next().await,
+            };
+            match next {
+                Some(Ok(item)) => yield WebEvent::from_item(item),
+                Some(Err(e)) => yield WebEvent::Error(e.to_string()),
+                None => break,
+            }
+        }
+        if options.end_event {
+            yield WebEvent::End;
+        }
+    }
+}
+
+/// `web_events` encoded as a `text/event-stream` body, e.g. for axum's
+/// `Body::from_stream` or hyper's `Body::wrap_stream`
+pub fn sse_body<S, T, E>(stream: S, options: WebOptions) -> impl Stream<Item = Result<Bytes, Infallible>>
+where
+    S: Stream<Item = Result<StreamItem<T>, E>>,
+    T: Serialize + JsonSchema,
+    E: Display,
+{
+    web_events(stream, options).enumerate().map(move |(i, event)| {
+        let id = options.event_ids.then_some(i as u64);
+        Ok(Bytes::from(event.to_sse(id)))
+    })
+}
+
+/// `web_events` as JSON text frames for a WebSocket
+pub fn websocket_messages<S, T, E>(stream: S, options: WebOptions) -> impl Stream<Item = String>
+where
+    S: Stream<Item = Result<StreamItem<T>, E>>,
+    T: Serialize + JsonSchema,
+    E: Display,
+{
+    web_events(stream, options).map(|event| event.to_json())
+}
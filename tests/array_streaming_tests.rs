@@ -0,0 +1,94 @@
+use bytes::Bytes;
+use futures_util::{pin_mut, StreamExt};
+use schemars::JsonSchema;
+use semantic_query::core::RawByteStream;
+use semantic_query::error::AIError;
+use semantic_query::json_utils::JsonStreamParser;
+use semantic_query::streaming::{stream_from_bytes_with, stream_from_sse_bytes, StreamItem, StreamOptions};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+struct Row {
+    id: u32,
+}
+
+fn channel_stream() -> (mpsc::UnboundedSender<&'static str>, RawByteStream) {
+    let (tx, rx) = mpsc::unbounded_channel::<&'static str>();
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (Ok::<_, AIError>(Bytes::from_static(chunk.as_bytes())), rx))
+    });
+    (tx, Box::pin(stream))
+}
+
+fn sse(tokens: &[&str]) -> RawByteStream {
+    let mut body = String::new();
+    for token in tokens {
+        body.push_str(&format!("data: {}\n\n", serde_json::json!({ "choices": [{ "delta": { "content": token } }] })));
+    }
+    body.push_str("data: [DONE]\n\n");
+    Box::pin(futures_util::stream::iter(vec![Ok::<_, AIError>(Bytes::from(body))]))
+}
+
+#[test]
+fn parser_reports_open_array_elements() {
+    let mut parser = JsonStreamParser::new();
+    assert!(parser.feed("rows: [{\"id\":1}, {\"id\"").is_empty());
+    let (start, elements) = parser.open_array().unwrap();
+    assert_eq!(start, 6);
+    assert_eq!(elements.len(), 1);
+
+    assert_eq!(parser.feed(":2}]").len(), 1);
+    assert!(parser.open_array().is_none());
+}
+
+#[tokio::test]
+async fn elements_arrive_before_the_array_closes() {
+    let (tx, bytes) = channel_stream();
+    let items = stream_from_bytes_with::<Row>(bytes, StreamOptions::default());
+    pin_mut!(items);
+
+    tx.send("Results: [{\"id\": 1},").unwrap();
+    assert!(matches!(items.next().await, Some(Ok(StreamItem::Text(t))) if t.text.trim() == "Results:"));
+    assert!(matches!(items.next().await, Some(Ok(StreamItem::Data(Row { id: 1 })))));
+
+    tx.send(" {\"id\": 2}, {\"oops\": true}").unwrap();
+    assert!(matches!(items.next().await, Some(Ok(StreamItem::Data(Row { id: 2 })))));
+    assert!(matches!(items.next().await, Some(Ok(StreamItem::Text(t))) if t.text == "{\"oops\": true}"));
+
+    tx.send(", {\"id\": 3}] done").unwrap();
+    drop(tx);
+    let rest: Vec<_> = items.collect().await;
+    assert!(matches!(&rest[0], Ok(StreamItem::Data(Row { id: 3 }))));
+    assert!(matches!(&rest[1], Ok(StreamItem::Text(t)) if t.text.trim() == "done"));
+    assert_eq!(rest.len(), 2);
+}
+
+#[tokio::test]
+async fn whole_array_behavior_can_be_kept() {
+    let (tx, bytes) = channel_stream();
+    tx.send("[{\"id\": 1}, ").unwrap();
+    tx.send("{\"id\": 2}]").unwrap();
+    drop(tx);
+
+    let options = StreamOptions { stream_array_elements: false, ..StreamOptions::default() };
+    let items: Vec<_> = stream_from_bytes_with::<serde_json::Value>(bytes, options).collect().await;
+    assert_eq!(items.len(), 1);
+    assert!(matches!(&items[0], Ok(StreamItem::Data(v)) if v.as_array().map(Vec::len) == Some(2)));
+}
+
+#[tokio::test]
+async fn sse_streams_array_elements() {
+    let items: Vec<_> = stream_from_sse_bytes::<Row>(sse(&["Here [", "{\"id\":1}", ",{\"id\":", "2}", "]"]))
+        .filter_map(|item| async move { item.ok() })
+        .collect()
+        .await;
+
+    let data: Vec<u32> = items.iter().filter_map(|i| match i { StreamItem::Data(r) => Some(r.id), _ => None }).collect();
+    assert_eq!(data, vec![1, 2]);
+    // The first element is yielded before the token that closes the array arrives
+    let first_data = items.iter().position(|i| matches!(i, StreamItem::Data(_))).unwrap();
+    let last_token = items.iter().rposition(|i| matches!(i, StreamItem::Token(_))).unwrap();
+    assert!(first_data < last_token);
+    assert!(!items.iter().any(|i| matches!(i, StreamItem::Text(t) if t.text.contains("id"))));
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use semantic_query::clients::{AutoSchema, MockClient, MockResponse};
+use semantic_query::core::{LowLevelClient, QueryResolver, ResponseMode, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::schema::{schema_value, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+enum Status {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Contact {
+    Email { address: String },
+    Phone { number: String, extension: Option<u16> },
+}
+
+/// Recursive, so generation has to stop on its own
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Task {
+    title: String,
+    subtasks: Vec<Task>,
+    parent: Option<Box<Task>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Ticket {
+    id: u32,
+    #[schemars(range(min = 10, max = 20))]
+    priority: i64,
+    score: f64,
+    urgent: bool,
+    #[schemars(length(min = 12))]
+    summary: String,
+    status: Status,
+    contacts: Vec<Contact>,
+    labels: HashMap<String, String>,
+    position: (i32, i32),
+    assignee: Option<String>,
+    task: Task,
+}
+
+#[tokio::test]
+async fn fabricated_replies_parse_as_the_requested_type() {
+    let (client, _) = MockClient::auto_schema();
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let ticket = resolver.query::<Ticket>("Open a ticket".to_string()).await.unwrap().first_required().unwrap();
+
+    assert_eq!(ticket.id, 1);
+    assert_eq!(ticket.priority, 10);
+    assert!(ticket.urgent);
+    assert!(ticket.summary.chars().count() >= 12);
+    assert_eq!(ticket.status, Status::Open);
+    assert_eq!(ticket.contacts.len(), 1);
+    assert_eq!(ticket.labels.len(), 1);
+}
+
+#[tokio::test]
+async fn prose_wrapped_replies_parse_in_mixed_mode() {
+    let (client, _) = MockClient::auto_schema_with(AutoSchema::new().with_prose());
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
+    let response = resolver.query::<Ticket>("Open a ticket".to_string()).await.unwrap();
+    assert_eq!(response.data_count(), 1);
+
+    let raw = client.ask_raw(format!("```json\n{}\n```", schema_value::<Status>())).await.unwrap();
+    assert!(raw.starts_with("Here is what I found:\n\n```json\n\"Open\"\n```"));
+}
+
+#[tokio::test]
+async fn json_only_mode_gets_bare_json() {
+    let (client, _) = MockClient::auto_schema();
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_response_mode(ResponseMode::JsonOnly);
+    assert!(resolver.query::<Ticket>("Open a ticket".to_string()).await.is_ok());
+}
+
+#[tokio::test]
+async fn queued_responses_come_first() {
+    let (client, handle) = MockClient::auto_schema();
+    handle.add_response(MockResponse::Error(AIError::Mock("scripted".to_string())));
+
+    let prompt = format!("```json\n{}\n```", schema_value::<Status>());
+    assert!(matches!(client.ask_raw(prompt.clone()).await, Err(AIError::Mock(m)) if m == "scripted"));
+    assert_eq!(client.ask_raw(prompt.clone()).await.unwrap(), "\"Open\"");
+
+    drop(handle);
+    assert_eq!(client.ask_raw(prompt).await.unwrap(), "\"Open\"");
+}
+
+#[tokio::test]
+async fn prompts_without_a_schema_fail() {
+    let (client, _) = MockClient::auto_schema();
+    let err = client.ask_raw("Hello".to_string()).await.unwrap_err();
+    assert!(matches!(err, AIError::Mock(m) if m.contains("no JSON schema")));
+}
+
+#[test]
+fn keywords_take_precedence_over_placeholders() {
+    let auto = AutoSchema::new();
+    assert_eq!(auto.instance(&json!({ "type": "string", "const": "fixed" })), json!("fixed"));
+    assert_eq!(auto.instance(&json!({ "type": "integer", "default": 42 })), json!(42));
+    assert_eq!(auto.instance(&json!({ "type": "string", "format": "date-time" })), json!("2024-01-15T09:30:00Z"));
+    assert_eq!(auto.instance(&json!({ "type": "integer", "exclusiveMinimum": 5 })), json!(6));
+    assert_eq!(auto.instance(&json!({ "type": ["null", "boolean"] })), json!(true));
+    assert_eq!(
+        auto.instance(&json!({ "type": "object", "properties": { "name": { "type": "string" } } })),
+        json!({ "name": "name" })
+    );
+}
+
+#[cfg(feature = "mock-variation")]
+#[test]
+fn seeded_variation_is_reproducible() {
+    let schema = schema_value::<Ticket>();
+    let first = AutoSchema::new().with_seed(7).instance(&schema);
+    assert_eq!(AutoSchema::new().with_seed(7).instance(&schema), first);
+    assert!(serde_json::from_value::<Ticket>(first).is_ok());
+
+    let seeded = AutoSchema::new().with_seed(7);
+    let samples: Vec<_> = (0..8).map(|_| seeded.instance(&schema)).collect();
+    assert!(samples.iter().any(|sample| *sample != samples[0]));
+}
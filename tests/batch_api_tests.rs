@@ -0,0 +1,145 @@
+#![cfg(feature = "batch-api")]
+
+use schemars::JsonSchema;
+use semantic_query::batch_api::{BatchConfig, BatchJob, BatchProvider, BatchRequest, BatchRunner, BatchStatus};
+use semantic_query::error::BatchError;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Person {
+    name: String,
+}
+
+type Route = Arc<dyn Fn(&str, &str) -> String + Send + Sync>;
+
+/// Minimal HTTP server answering each request with `route(method, path)`;
+/// returns the base URL and the list of `METHOD path` lines received
+async fn stub_server(route: Route) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else { break };
+            let route = route.clone();
+            let log = log.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 8192];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text.lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length { break; }
+                    }
+                    if n == 0 { break; }
+                }
+                let text = String::from_utf8_lossy(&request).to_string();
+                let mut parts = text.split_whitespace();
+                let (method, path) = (parts.next().unwrap_or("").to_string(), parts.next().unwrap_or("").to_string());
+                log.lock().unwrap().push(format!("{method} {path}"));
+                let body = route(&method, &path);
+                let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}", body.len());
+                let _ = socket.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    (base_url, seen)
+}
+
+fn state_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("sq_batch_{}_{}.json", std::process::id(), name))
+}
+
+#[tokio::test]
+async fn anthropic_batch_round_trip() {
+    let results_base = Arc::new(Mutex::new(String::new()));
+    let base_for_route = results_base.clone();
+    let (base_url, seen) = stub_server(Arc::new(move |method, path| match (method, path) {
+        ("POST", "/v1/messages/batches") => r#"{"id":"msgbatch_1","processing_status":"in_progress"}"#.to_string(),
+        ("GET", "/v1/messages/batches/msgbatch_1") => format!(
+            r#"{{"id":"msgbatch_1","processing_status":"ended","results_url":"{}/results/msgbatch_1"}}"#,
+            base_for_route.lock().unwrap()
+        ),
+        ("GET", "/results/msgbatch_1") => [
+            r#"{"custom_id":"req-0","result":{"type":"succeeded","message":{"content":[{"type":"text","text":"{\"name\":\"Ada\"}"}]}}}"#,
+            r#"{"custom_id":"req-1","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"bad prompt"}}}}"#,
+        ].join("\n"),
+        _ => "{}".to_string(),
+    })).await;
+    *results_base.lock().unwrap() = base_url.clone();
+
+    let path = state_path("anthropic");
+    let _ = std::fs::remove_file(&path);
+    let runner = BatchRunner::new(
+        BatchConfig::anthropic("key", "claude-3-5-haiku-20241022")
+            .with_base_url(&base_url)
+            .with_poll_interval(Duration::from_millis(1))
+            .with_state_path(&path),
+    );
+
+    let results = runner.run::<Person>(BatchRequest::indexed(["Ada bio", "broken", "missing"])).await.unwrap();
+    assert_eq!(results["req-0"].as_ref().unwrap().first(), Some(&Person { name: "Ada".into() }));
+    assert!(results["req-1"].as_ref().unwrap_err().to_string().contains("bad prompt"));
+    assert!(results["req-2"].is_err());
+
+    let saved = BatchJob::load(&path).await.unwrap();
+    assert_eq!(saved.status, BatchStatus::Ended);
+    assert_eq!(saved.request_ids, vec!["req-0", "req-1", "req-2"]);
+    assert_eq!(seen.lock().unwrap().first().map(String::as_str), Some("POST /v1/messages/batches"));
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn resumes_saved_job_without_resubmitting() {
+    let (base_url, seen) = stub_server(Arc::new(|_, path| match path {
+        "/v1/batches/batch_9" => r#"{"id":"batch_9","status":"completed","output_file_id":"file_out"}"#.to_string(),
+        "/v1/files/file_out/content" => r#"{"custom_id":"a","response":{"status_code":200,"body":{"choices":[{"message":{"content":"{\"name\":\"Grace\"}"}}]}},"error":null}"#.to_string(),
+        _ => "{}".to_string(),
+    })).await;
+
+    let path = state_path("resume");
+    BatchJob {
+        provider: BatchProvider::OpenAI,
+        batch_id: "batch_9".into(),
+        status: BatchStatus::InProgress,
+        request_ids: vec!["a".into()],
+        results_url: None,
+        output_file_id: None,
+        error_file_id: None,
+    }
+    .save(&path)
+    .await
+    .unwrap();
+
+    let runner = BatchRunner::new(
+        BatchConfig::openai("key", "gpt-4o-mini")
+            .with_base_url(&base_url)
+            .with_poll_interval(Duration::from_millis(1))
+            .with_state_path(&path),
+    );
+    let results = runner.run::<Person>(vec![BatchRequest::new("a", "Grace bio")]).await.unwrap();
+    assert_eq!(results["a"].as_ref().unwrap().first(), Some(&Person { name: "Grace".into() }));
+    assert!(seen.lock().unwrap().iter().all(|r| !r.starts_with("POST")));
+
+    // A state file for other requests is refused rather than silently reused
+    let err = runner.resume_or_submit(vec![BatchRequest::new("b", "x")]).await.unwrap_err();
+    assert!(matches!(err, BatchError::State(_)));
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn rejects_duplicate_ids() {
+    let runner = BatchRunner::new(BatchConfig::openai("key", "gpt-4o-mini").with_base_url("http://127.0.0.1:9"));
+    let err = runner.submit(vec![BatchRequest::new("x", "1"), BatchRequest::new("x", "2")]).await.unwrap_err();
+    assert!(matches!(err, BatchError::State(_)));
+}
@@ -0,0 +1,54 @@
+use schemars::JsonSchema;
+use semantic_query::batch::{MapOptions, PackOptions};
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Label {
+    label: String,
+}
+
+#[tokio::test]
+async fn results_align_with_inputs() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![r#"{"label":"a"}"#, "no data", r#"{"label":"c"}"#]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let results = resolver
+        .map_extract_with::<Label, _, _>(vec!["x", "y", "z"], |s| format!("label {s}"), MapOptions { concurrency: 1, packing: None })
+        .await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().label, "a");
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap().label, "c");
+}
+
+#[tokio::test]
+async fn packed_prompts_split_the_array_response() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"[{"label":"a"},{"label":"b"},{"label":"c"}]"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let options = MapOptions { concurrency: 2, packing: Some(PackOptions::default()) };
+    let results = resolver.map_extract_with::<Label, _, _>(vec![1, 2, 3], |n| format!("item {n}"), options).await;
+    let labels: Vec<_> = results.into_iter().map(|r| r.unwrap().label).collect();
+    assert_eq!(labels, ["a", "b", "c"]);
+    assert!(handle.is_empty());
+}
+
+#[tokio::test]
+async fn misaligned_packs_fall_back_to_single_items() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![
+        r#"[{"label":"only one"}]"#,
+        r#"{"label":"a"}"#,
+        r#"{"label":"b"}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let options = MapOptions { concurrency: 1, packing: Some(PackOptions { token_budget: 10_000, max_items: 5 }) };
+    let results = resolver.map_extract_with::<Label, _, _>(vec!["x", "y"], |s| s.to_string(), options).await;
+    assert_eq!(results[0].as_ref().unwrap().label, "a");
+    assert_eq!(results[1].as_ref().unwrap().label, "b");
+}
@@ -0,0 +1,19 @@
+#![cfg(feature = "blocking")]
+
+use semantic_query::blocking::BlockingQueryResolver;
+use semantic_query::clients::flexible::FlexibleClient;
+use semantic_query::core::RetryConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+struct Answer { value: i32 }
+
+#[test]
+fn blocking_query_without_async_runtime() {
+    let (client, handle) = FlexibleClient::mock();
+    handle.add_json_response(r#"The answer is {"value": 4}."#);
+    let resolver = BlockingQueryResolver::new(client, RetryConfig::default()).unwrap();
+    let answer = resolver.query::<Answer>("2 + 2?".to_string()).unwrap().first_required().unwrap();
+    assert_eq!(answer, Answer { value: 4 });
+}
@@ -0,0 +1,80 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::streaming::{stream_from_async_read, stream_from_async_read_with, BufferPolicy, StreamItem, StreamOptions};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, ReadBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Note {
+    text: String,
+}
+
+/// Hands out `data` in reads as large as the caller's buffer allows, recording the
+/// buffer sizes it was offered
+struct SizedReader {
+    data: Vec<u8>,
+    offered: Arc<Mutex<Vec<usize>>>,
+}
+
+impl SizedReader {
+    fn new(data: impl Into<Vec<u8>>) -> (Self, Arc<Mutex<Vec<usize>>>) {
+        let offered = Arc::new(Mutex::new(Vec::new()));
+        (Self { data: data.into(), offered: offered.clone() }, offered)
+    }
+}
+
+impl AsyncRead for SizedReader {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        self.offered.lock().unwrap().push(buf.remaining());
+        let n = buf.remaining().min(self.data.len());
+        let chunk: Vec<u8> = self.data.drain(..n).collect();
+        buf.put_slice(&chunk);
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn notes(items: &[StreamItem<Note>]) -> Vec<&str> {
+    items.iter().filter_map(|item| match item {
+        StreamItem::Data(note) => Some(note.text.as_str()),
+        _ => None,
+    }).collect()
+}
+
+#[tokio::test]
+async fn reads_start_small_and_grow_to_the_cap() {
+    let body = format!("{}{{\"text\": \"end\"}}", "x".repeat(20_000));
+    let (reader, offered) = SizedReader::new(body);
+    let items: Vec<StreamItem<Note>> = stream_from_async_read(reader, 4096).collect().await;
+    assert_eq!(notes(&items), ["end"]);
+
+    let offered = offered.lock().unwrap();
+    assert_eq!(offered[..6], [256, 512, 1024, 2048, 4096, 4096]);
+    assert!(offered.iter().all(|&size| size <= 4096));
+}
+
+#[tokio::test]
+async fn fixed_policy_keeps_one_size() {
+    let (reader, offered) = SizedReader::new(r#"Note: {"text": "hi"} and more text after it"#);
+    let options = StreamOptions { buffer: BufferPolicy::fixed(8), ..StreamOptions::default() };
+    let items: Vec<StreamItem<Note>> = stream_from_async_read_with(reader, 8192, options).collect().await;
+    assert_eq!(notes(&items), ["hi"]);
+    assert!(offered.lock().unwrap().iter().all(|&size| size == 8));
+}
+
+#[tokio::test]
+async fn characters_split_across_reads_are_kept() {
+    let (reader, _) = SizedReader::new(r#"Voilà: {"text": "café ☕ naïve"} fin"#);
+    let options = StreamOptions { buffer: BufferPolicy::fixed(5), ..StreamOptions::default() };
+    let items: Vec<StreamItem<Note>> = stream_from_async_read_with(reader, 1024, options).collect().await;
+    assert_eq!(notes(&items), ["café ☕ naïve"]);
+
+    let text: String = items.iter().filter_map(|item| match item {
+        StreamItem::Text(t) => Some(t.text.as_str()),
+        _ => None,
+    }).collect();
+    assert!(text.contains("Voilà") && text.contains("fin"), "{text}");
+}
@@ -0,0 +1,50 @@
+#![cfg(all(feature = "anthropic", feature = "openai", feature = "ollama", feature = "openai-compatible"))]
+
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::{
+    ClaudeClient, ClaudeConfig, ClaudeModel, CompatClient, CompatConfig, MockVoid, OllamaClient, OllamaConfig, OpenAIClient,
+    OpenAIConfig, OpenAIModel,
+};
+use semantic_query::core::{Capabilities, LowLevelClient, QueryResolver, RetryConfig};
+
+#[test]
+fn openai_reports_model_details() {
+    let openai = OpenAIClient::new(OpenAIConfig { api_key: "k".into(), model: OpenAIModel::Gpt4o, ..OpenAIConfig::default() });
+    let caps = openai.capabilities();
+    assert!(caps.supports_streaming && caps.supports_json_mode && caps.supports_native_tools && caps.supports_vision);
+    assert_eq!(caps.max_context_tokens, Some(128_000));
+
+    let legacy = OpenAIClient::new(OpenAIConfig { api_key: "k".into(), model: OpenAIModel::Gpt35Turbo, ..OpenAIConfig::default() });
+    assert!(!legacy.capabilities().supports_vision);
+}
+
+#[cfg(feature = "anthropic")]
+#[test]
+fn claude_reports_model_details() {
+    let claude = ClaudeClient::new(ClaudeConfig::anthropic("k".into(), ClaudeModel::Sonnet4));
+    let caps = claude.capabilities();
+    assert!(caps.supports_system_role && caps.supports_vision && caps.supports_prefill && !caps.supports_json_mode);
+    assert_eq!(caps.max_context_tokens, Some(200_000));
+}
+
+#[test]
+fn local_and_compatible_clients() {
+    let ollama = OllamaClient::new(OllamaConfig::default()).capabilities();
+    assert!(ollama.supports_grammar && ollama.supports_json_mode && !ollama.supports_streaming);
+    let llama_cpp = OllamaClient::new(OllamaConfig::llama_cpp("http://localhost:8080")).capabilities();
+    assert!(llama_cpp.supports_grammar && !llama_cpp.supports_json_mode);
+
+    let compat = CompatClient::new(CompatConfig::new("http://localhost:1234", "local")).capabilities();
+    assert!(compat.supports_streaming && compat.supports_json_mode);
+    assert_eq!(compat.max_context_tokens, None);
+}
+
+#[test]
+fn defaults_follow_the_client_methods() {
+    assert_eq!(MockVoid.capabilities(), Capabilities::default());
+    assert!(ScriptedClient::Stream(Vec::new()).capabilities().supports_streaming);
+
+    let boxed: Box<dyn LowLevelClient> = Box::new(ScriptedClient::Stream(Vec::new()));
+    let resolver = QueryResolver::new(boxed, RetryConfig::default());
+    assert!(resolver.capabilities().supports_streaming);
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "anthropic")]
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::clients::claude::{ClaudeConfig, ClaudeRequest, ClaudeResponse, ClaudeTool};
+use semantic_query::core::{RawByteStream, ResponseItem};
+use semantic_query::error::AIError;
+use semantic_query::streaming::{stream_from_sse_bytes, stream_from_sse_bytes_timed, StreamItem, StreamOptions, ToolCall};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Weather {
+    location: String,
+}
+
+/// Anthropic Messages SSE: a text block, then a `tool_use` block whose input arrives in pieces
+fn anthropic_sse() -> RawByteStream {
+    let events = [
+        json!({"type": "message_start", "message": {"id": "msg_1", "content": []}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Checking the "}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "weather."}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": {}}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"location\": \"Par"}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "is\"}"}}),
+        json!({"type": "content_block_stop", "index": 1}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}}),
+        json!({"type": "message_stop"}),
+    ];
+    let body: String = events.iter()
+        .map(|event| format!("event: {}\ndata: {event}\n\n", event["type"].as_str().unwrap()))
+        .collect();
+    Box::pin(futures_util::stream::iter(vec![Ok::<_, AIError>(Bytes::from(body))]))
+}
+
+#[tokio::test]
+async fn streamed_tool_use_becomes_data() {
+    let items: Vec<_> = stream_from_sse_bytes::<Weather>(anthropic_sse()).collect().await;
+    let items: Vec<_> = items.into_iter().map(Result::unwrap).filter(|i| !matches!(i, StreamItem::Token(_))).collect();
+
+    assert_eq!(items.len(), 2);
+    assert!(matches!(&items[0], StreamItem::Text(t) if t.text == "Checking the weather."));
+    assert!(matches!(&items[1], StreamItem::Data(w) if w.location == "Paris"));
+}
+
+#[tokio::test]
+async fn streamed_tool_use_keeps_its_id() {
+    let items: Vec<_> = stream_from_sse_bytes::<ToolCall>(anthropic_sse()).collect().await;
+    let calls: Vec<ToolCall> = items.into_iter().filter_map(|i| match i {
+        Ok(StreamItem::Data(call)) => Some(call),
+        _ => None,
+    }).collect();
+    assert_eq!(calls, vec![ToolCall { id: "toolu_01".into(), name: "get_weather".into(), input: json!({"location": "Paris"}) }]);
+
+    let timed: Vec<_> = stream_from_sse_bytes_timed::<Weather>(anthropic_sse(), StreamOptions::default()).collect().await;
+    let data = timed.into_iter().map(Result::unwrap).find(|t| matches!(t.item, StreamItem::Data(_))).unwrap();
+    assert_eq!(data.event_id.as_deref(), Some("toolu_01"));
+}
+
+#[test]
+fn one_shot_tool_use_blocks_map_to_data() {
+    let response: ClaudeResponse = serde_json::from_value(json!({
+        "model": "claude-3-5-haiku-20241022",
+        "stop_reason": "tool_use",
+        "content": [
+            {"type": "text", "text": "Looking up {\"location\": \"nowhere\"} first."},
+            {"type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": {"location": "Paris"}},
+            {"type": "tool_use", "id": "toolu_02", "name": "get_time", "input": {"zone": "CET"}}
+        ]
+    })).unwrap();
+
+    let parsed = response.parse::<Weather>();
+    let locations: Vec<&str> = parsed.data_only().iter().map(|w| w.location.as_str()).collect();
+    assert_eq!(locations, vec!["nowhere", "Paris"]);
+    assert!(matches!(parsed.items.last(), Some(ResponseItem::Text(t)) if t.text == r#"{"zone":"CET"}"#));
+
+    let calls = response.parse::<ToolCall>();
+    let ids: Vec<&str> = calls.data_only().iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, vec!["toolu_01", "toolu_02"]);
+}
+
+#[test]
+fn unknown_blocks_are_skipped_and_text_joins_blocks() {
+    let response: ClaudeResponse = serde_json::from_value(json!({
+        "content": [
+            {"type": "thinking", "thinking": "hmm"},
+            {"type": "text", "text": "Here:"},
+            {"type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": {"location": "Oslo"}}
+        ]
+    })).unwrap();
+    assert_eq!(response.text(), "Here:\n\n{\"location\":\"Oslo\"}");
+}
+
+#[test]
+fn requests_carry_configured_tools() {
+    let config = ClaudeConfig::default().with_tool(ClaudeTool::for_type::<Weather>("get_weather", "Current weather for a city"));
+    let request = serde_json::to_value(ClaudeRequest::new("Weather in Paris?".into(), &config)).unwrap();
+    assert_eq!(request["tools"][0]["name"], "get_weather");
+    assert_eq!(request["tools"][0]["input_schema"]["required"], json!(["location"]));
+
+    let plain = serde_json::to_value(ClaudeRequest::new("Hi".into(), &ClaudeConfig::default())).unwrap();
+    assert!(plain.get("tools").is_none());
+}
@@ -0,0 +1,76 @@
+use semantic_query::client_testkit::{
+    matches_failure, run_conformance, sse_body, ConformanceFixture, Failure, ScriptedClient, ScriptedFixture,
+};
+use semantic_query::error::{AIError, ClaudeError, ProviderError};
+
+semantic_query::client_conformance_suite!(scripted_client_conforms, ScriptedFixture);
+
+/// Fixture that opts out of streaming and error checks
+struct ReplyOnly;
+
+impl ConformanceFixture for ReplyOnly {
+    type Client = ScriptedClient;
+
+    fn replying(&self, response: &str) -> ScriptedClient {
+        ScriptedClient::Reply(response.to_string())
+    }
+
+    fn streaming(&self, _chunks: &[&[u8]]) -> Option<ScriptedClient> {
+        None
+    }
+
+    fn failing(&self, _failure: Failure) -> Option<ScriptedClient> {
+        None
+    }
+}
+
+/// Fixture whose client mangles responses
+struct Trimming;
+
+impl ConformanceFixture for Trimming {
+    type Client = ScriptedClient;
+
+    fn replying(&self, response: &str) -> ScriptedClient {
+        ScriptedClient::Reply(response.trim().to_string())
+    }
+
+    fn streaming(&self, _chunks: &[&[u8]]) -> Option<ScriptedClient> {
+        None
+    }
+
+    fn failing(&self, _failure: Failure) -> Option<ScriptedClient> {
+        Some(ScriptedClient::Fail(AIError::Configuration("boom".into())))
+    }
+}
+
+#[tokio::test]
+async fn opted_out_checks_are_skipped() {
+    let report = run_conformance(&ReplyOnly).await;
+    report.assert_passed();
+    assert!(report.checks.iter().any(|c| c.passed.is_none()));
+}
+
+#[tokio::test]
+async fn misbehaving_clients_fail_with_details() {
+    let report = run_conformance(&Trimming).await;
+    assert!(!report.is_success());
+    let names: Vec<_> = report.failures().iter().map(|c| c.name).collect();
+    assert!(names.contains(&"ask_raw returns the model text verbatim"));
+    assert!(names.contains(&"rate limits map to a RateLimit error"));
+    assert!(report.to_string().contains("FAILED"));
+}
+
+#[test]
+fn error_mapping_is_provider_agnostic() {
+    let rate_limited = AIError::Claude(ClaudeError::RateLimit(ProviderError::new(429, "slow down").into()));
+    assert!(matches_failure(&rate_limited, Failure::RateLimit));
+    assert!(!matches_failure(&rate_limited, Failure::Authentication));
+}
+
+#[test]
+fn sse_body_ends_with_done() {
+    let body: Vec<u8> = sse_body(&[b"hi"]).concat();
+    let text = String::from_utf8(body).unwrap();
+    assert!(text.starts_with("data: {"));
+    assert!(text.ends_with("data: [DONE]\n\n"));
+}
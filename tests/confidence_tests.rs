@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting};
+use semantic_query::confidence::{field_confidence, TokenLogprob};
+use semantic_query::core::{QueryResolver, ResponseItem, RetryConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Invoice {
+    vendor: String,
+    total: f64,
+    tags: Vec<String>,
+}
+
+const REPLY: &str = r#"Found it: {"vendor": "Acme", "total": 12.5, "tags": ["q3"]} done"#;
+
+/// `REPLY` split into tokens; the tokens of `total` are the uncertain ones
+fn tokens() -> Vec<TokenLogprob> {
+    [
+        ("Found it: ", -0.2), ("{\"vendor\": ", -0.01), ("\"Acme\"", -0.05), (", \"total\": ", -0.01),
+        ("12", -1.2), (".5", -0.8), (", \"tags\": [", -0.01), ("\"q3\"", -0.1), ("]}", 0.0), (" done", -0.3),
+    ]
+    .into_iter()
+    .map(|(token, logprob)| TokenLogprob { token: token.into(), logprob })
+    .collect()
+}
+
+/// A mock that replies `REPLY` and, when asked, reports its logprobs
+fn with_logprobs() -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::Logprobs]);
+    handle.add_json_response(REPLY);
+    handle.report_logprobs(tokens());
+    (client, handle)
+}
+
+fn confidence_of<T>(item: &ResponseItem<T>) -> Option<&semantic_query::confidence::ConfidenceMap> {
+    match item {
+        ResponseItem::Data { confidence_map, .. } => confidence_map.as_ref(),
+        ResponseItem::Text(_) => None,
+    }
+}
+
+#[test]
+fn fields_average_the_tokens_that_produced_them() {
+    let start = REPLY.find('{').unwrap();
+    let end = REPLY.rfind('}').unwrap() + 1;
+    let confidence = field_confidence(REPLY, start..end, &tokens()).unwrap();
+
+    assert!((confidence["/total"] - (-1.0f64).exp()).abs() < 1e-9);
+    assert!((confidence["/vendor"] - (-0.05f64).exp()).abs() < 1e-9);
+    assert!((confidence["/tags/0"] - (-0.1f64).exp()).abs() < 1e-9);
+    assert!(confidence.contains_key("/tags"));
+    assert!(!confidence.contains_key(""));
+}
+
+#[test]
+fn tokens_that_do_not_spell_the_reply_give_nothing() {
+    assert_eq!(field_confidence("{\"a\": 1}", 0..8, &tokens()), None);
+}
+
+#[tokio::test]
+async fn data_items_carry_a_confidence_map() {
+    let (client, _handle) = with_logprobs();
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_field_confidence(true);
+    let response = resolver.query::<Invoice>("Extract".into()).await.unwrap();
+
+    let data = response.items.iter().find(|item| matches!(item, ResponseItem::Data { .. })).unwrap();
+    let confidence = confidence_of(data).unwrap();
+    assert!(confidence["/total"] < confidence["/vendor"]);
+
+    let json = serde_json::to_value(&response).unwrap();
+    assert!(json["items"][1]["content"]["confidence_map"]["/total"].is_number());
+}
+
+#[tokio::test]
+async fn confidence_is_off_by_default_and_without_logprobs() {
+    let (client, _handle) = with_logprobs();
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let response = resolver.query::<Invoice>("Extract".into()).await.unwrap();
+    assert!(response.items.iter().all(|item| confidence_of(item).is_none()));
+
+    let resolver = QueryResolver::new(ScriptedClient::Reply(REPLY.into()), RetryConfig::default()).with_field_confidence(true);
+    let response = resolver.query::<Invoice>("Extract".into()).await.unwrap();
+    assert!(response.has_data());
+    assert!(response.items.iter().all(|item| confidence_of(item).is_none()));
+}
@@ -0,0 +1,115 @@
+#![cfg(all(feature = "anthropic", feature = "deepseek"))]
+
+use semantic_query::clients::flexible::{ClientType, FlexibleClient};
+use semantic_query::config::{load_from_path, SemanticQueryConfig};
+
+fn write_temp(name: &str, content: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("sq_{}_{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn parses_provider_and_retry_sections() {
+    let path = write_temp("semantic-query.toml", r#"
+default_provider = "deepseek"
+
+[deepseek]
+api_key = "sk-file"
+model = "deepseek-reasoner"
+max_tokens = 2048
+temperature = 0.1
+
+[retry]
+default_max_retries = 3
+[retry.max_retries]
+rate_limit = 5
+"#);
+    let config = load_from_path(&path).unwrap();
+    assert!(matches!(ClientType::from_config(&config), ClientType::DeepSeek));
+
+    let deepseek = config.deepseek_config().unwrap();
+    assert_eq!(deepseek.api_key, "sk-file");
+    assert_eq!(deepseek.model.id(), "deepseek-reasoner");
+    assert_eq!(deepseek.max_tokens, 2048);
+
+    let retry = config.retry_config();
+    assert_eq!(retry.default_max_retries, 3);
+    assert_eq!(retry.max_retries.get("rate_limit"), Some(&5));
+    assert_eq!(retry.max_retries.get("json_parse_error"), Some(&2));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn missing_keys_select_mock_and_report_configuration_errors() {
+    let config = SemanticQueryConfig::default();
+    assert!(matches!(ClientType::from_config(&config), ClientType::Mock));
+    assert!(config.claude_config().is_err());
+}
+
+fn build(config: &SemanticQueryConfig, client_type: ClientType) -> FlexibleClient {
+    FlexibleClient::new(client_type.build_with(config).unwrap())
+}
+
+#[test]
+fn every_provider_field_reaches_the_built_client() {
+    use semantic_query::clients::{deepseek::DeepSeekClient, ClaudeClient, ClaudeModel};
+
+    let path = write_temp("fields.toml", r#"
+[anthropic]
+api_key = "sk-ant-file"
+endpoint = "http://localhost:8080/anthropic/"
+model = "claude-3-5-haiku-20241022"
+max_tokens = 512
+temperature = 0.4
+
+[deepseek]
+api_key = "sk-ds-file"
+endpoint = "http://localhost:8080/deepseek"
+model = "deepseek-reasoner"
+max_tokens = 1024
+temperature = 0.6
+
+[openai]
+api_key = "sk-oai-file"
+endpoint = "http://localhost:8080/openai"
+model = "gpt-4o"
+max_tokens = 256
+temperature = 0.8
+seed = 7
+"#);
+    let config = load_from_path(&path).unwrap();
+    let _ = std::fs::remove_file(path);
+
+    let client = build(&config, ClientType::Claude);
+    let claude = client.as_any().downcast_ref::<ClaudeClient>().unwrap().config().clone();
+    assert_eq!(claude.api_key, "sk-ant-file");
+    assert_eq!(claude.base_url, "http://localhost:8080/anthropic/");
+    assert_eq!(claude.model, ClaudeModel::Haiku35);
+    assert_eq!(claude.max_tokens, 512);
+    assert_eq!(claude.temperature, Some(0.4));
+
+    let client = build(&config, ClientType::DeepSeek);
+    let deepseek = client.as_any().downcast_ref::<DeepSeekClient>().unwrap().config().clone();
+    assert_eq!(deepseek.api_key, "sk-ds-file");
+    assert_eq!(deepseek.base_url, "http://localhost:8080/deepseek");
+    assert_eq!(deepseek.model.id(), "deepseek-reasoner");
+    assert_eq!(deepseek.max_tokens, 1024);
+    assert!((deepseek.temperature - 0.6).abs() < f32::EPSILON);
+
+    #[cfg(feature = "openai")]
+    {
+        use semantic_query::clients::OpenAIClient;
+
+        let mut config = config;
+        config.azure.endpoint = None; // an AZURE_OPENAI_ENDPOINT in .env must not pick Azure
+        let client = build(&config, ClientType::ChatGPT);
+        let openai = client.as_any().downcast_ref::<OpenAIClient>().unwrap().config().clone();
+        assert_eq!(openai.api_key, "sk-oai-file");
+        assert_eq!(openai.base_url, "http://localhost:8080/openai");
+        assert_eq!(openai.model.id(), "gpt-4o");
+        assert_eq!(openai.max_tokens, 256);
+        assert!((openai.temperature - 0.8).abs() < f32::EPSILON);
+        assert_eq!(openai.seed, Some(7));
+    }
+}
@@ -0,0 +1,154 @@
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::consensus::MergeStrategy;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Invoice {
+    vendor: String,
+    total: f64,
+    currency: Option<String>,
+}
+
+#[tokio::test]
+async fn consensus_votes_per_field() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![
+        r#"{"vendor":"Acme","total":10.0,"currency":"USD"}"#,
+        r#"{"vendor":"Acme","total":12.0,"currency":"USD"}"#,
+        r#"{"vendor":"ACME Corp","total":10.0,"currency":"USD"}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let consensus = resolver.query_consensus::<Invoice>("extract".to_string(), 3).await.unwrap();
+    assert_eq!(consensus.value, Invoice { vendor: "Acme".into(), total: 10.0, currency: Some("USD".into()) });
+    assert_eq!(consensus.samples.len(), 3);
+    assert_eq!(consensus.field_agreement["/currency"], 1.0);
+    assert!((consensus.field_agreement["/vendor"] - 2.0 / 3.0).abs() < 1e-9);
+    // Only the first sample matches the voted value exactly
+    assert!((consensus.agreement - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(consensus.disputed(0.9), vec!["/total", "/vendor"]);
+}
+
+#[tokio::test]
+async fn consensus_skips_failed_samples() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"vendor":"Acme","total":1.0}"#);
+    handle.add_error(semantic_query::error::AIError::Mock("boom".into()));
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let consensus = resolver.query_consensus::<Invoice>("extract".to_string(), 2).await.unwrap();
+    assert_eq!(consensus.samples.len(), 1);
+    assert_eq!(consensus.agreement, 1.0);
+}
+
+#[tokio::test]
+async fn merge_fills_missing_fields() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![
+        r#"{"vendor":"Acme","total":10.0}"#,
+        r#"{"vendor":"Other","total":99.0,"currency":"EUR"}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let first = resolver.query_mixed::<Invoice>("a".to_string()).await.unwrap();
+    let second = resolver.query_mixed::<Invoice>("b".to_string()).await.unwrap();
+
+    let merged = first.clone().merge_with(second.clone(), MergeStrategy::FillMissing);
+    assert_eq!(merged.first().unwrap(), &Invoice { vendor: "Acme".into(), total: 10.0, currency: Some("EUR".into()) });
+
+    let overwritten = first.clone().merge_with(second.clone(), MergeStrategy::Overwrite);
+    assert_eq!(overwritten.first().unwrap().vendor, "Other");
+
+    let appended = first.merge_with(second, MergeStrategy::Append);
+    assert_eq!(appended.data_count(), 2);
+}
+
+#[tokio::test]
+async fn best_of_keeps_the_highest_scoring_sample() {
+    use semantic_query::consensus::schema_compliance;
+
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![
+        r#"{"vendor":"Acme","total":10.0}"#,
+        r#"{"vendor":"Acme","total":10.0,"currency":"USD"}"#,
+    ]);
+    handle.add_error(semantic_query::error::AIError::Mock("boom".into()));
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let best = resolver.query_best_of::<Invoice, _>("extract".to_string(), 3, schema_compliance).await.unwrap();
+    assert_eq!(best.candidates.len(), 3);
+    assert_eq!(best.best_index, 1);
+    assert_eq!(best.best_score(), 1.0);
+    assert!(best.candidates[2].score.is_none());
+    assert_eq!(best.into_best().first().unwrap().currency.as_deref(), Some("USD"));
+}
+
+#[tokio::test]
+async fn best_of_accepts_custom_scorers() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![
+        r#"{"vendor":"Acme","total":10.0}"#,
+        r#"{"vendor":"Acme","total":99.0}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    // Mocks ignore temperature, so samples fall back to the default client
+    let best = resolver
+        .query_best_of_with_temperatures::<Invoice, _>("extract".to_string(), &[0.0, 0.8], |r| r.first().map_or(0.0, |i| i.total))
+        .await
+        .unwrap();
+    assert_eq!(best.best().first().unwrap().total, 99.0);
+    assert!(best.candidates.iter().all(|c| c.temperature.is_none()));
+}
+
+#[tokio::test]
+async fn compare_diffs_fields_of_two_clients() {
+    use semantic_query::consensus::FieldStatus;
+
+    let (client_a, a) = MockClient::new();
+    a.add_json_response(r#"{"vendor":"Acme","total":10.0,"currency":"USD"}"#);
+    let (client_b, b) = MockClient::new();
+    b.add_json_response(r#"{"vendor":"Acme","total":12.5}"#);
+    let (base, _) = MockClient::new();
+    let resolver = QueryResolver::new(base, RetryConfig::default());
+
+    let comparison = resolver.compare::<Invoice, _, _>("extract".to_string(), client_a, client_b).await;
+    assert!(comparison.a.is_ok() && comparison.b.is_ok());
+    let statuses: Vec<(&str, FieldStatus)> = comparison.diff.iter().map(|d| (d.path.as_str(), d.status)).collect();
+    assert_eq!(statuses, [
+        ("/currency", FieldStatus::MissingInB),
+        ("/total", FieldStatus::Different),
+        ("/vendor", FieldStatus::Equal),
+    ]);
+    assert!(!comparison.is_identical());
+    assert_eq!(comparison.differences().count(), 2);
+    assert!((comparison.agreement() - 1.0 / 3.0).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn compare_reports_a_failed_side_as_missing_fields() {
+    use semantic_query::consensus::FieldStatus;
+
+    let (client_a, a) = MockClient::new();
+    a.add_error(semantic_query::error::AIError::Mock("down".into()));
+    let (client_b, b) = MockClient::new();
+    b.add_json_response(r#"{"vendor":"Acme","total":1.0}"#);
+    let resolver = QueryResolver::new(MockClient::new().0, RetryConfig::default());
+
+    let comparison = resolver.compare::<Invoice, _, _>("extract".to_string(), client_a, client_b).await;
+    assert!(comparison.a.is_err());
+    assert!(comparison.diff.iter().all(|d| d.status == FieldStatus::MissingInA && d.a.is_none()));
+    assert_eq!(comparison.diff.len(), 2);
+}
+
+#[test]
+fn diff_fields_recurses_into_arrays() {
+    use semantic_query::consensus::{diff_fields, FieldStatus};
+
+    let a = serde_json::json!({"lines": [{"sku": "A"}, {"sku": "B"}]});
+    let b = serde_json::json!({"lines": [{"sku": "A"}]});
+    let diff = diff_fields(Some(&a), Some(&b));
+    let statuses: Vec<(&str, FieldStatus)> = diff.iter().map(|d| (d.path.as_str(), d.status)).collect();
+    assert_eq!(statuses, [("/lines/0/sku", FieldStatus::Equal), ("/lines/1/sku", FieldStatus::MissingInB)]);
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting};
+use semantic_query::core::{ChatMessage, ChatRole, QueryResolver, RetryConfig, SchemaPlacement};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Task {
+    title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Estimate {
+    hours: u32,
+}
+
+/// A mock answering `reply` to each of `calls` requests, sending system messages in a
+/// system role if `system_role`
+fn mock(system_role: bool, reply: &str, calls: usize) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    if system_role {
+        handle.accept(&[MockSetting::SystemRole]);
+    }
+    handle.add_json_responses(vec![reply; calls]);
+    (client, handle)
+}
+
+const SCHEMA_MARKER: &str = "## Response Format\nPlease include valid JSON";
+
+#[tokio::test]
+async fn inline_schema_is_sent_once_per_type() {
+    let (client, handle) = mock(false, r#"{"title": "ship", "hours": 3}"#, 3);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let mut conversation = resolver.conversation();
+
+    let first = conversation.query::<Task>("Plan the release".into()).await.unwrap();
+    assert_eq!(first.first().unwrap().title, "ship");
+    conversation.query::<Task>("And the next one?".into()).await.unwrap();
+    conversation.query::<Estimate>("How long?".into()).await.unwrap();
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 3);
+    // Each request carries the history, so count schema blocks in the final request
+    assert_eq!(prompts[2].matches(SCHEMA_MARKER).count(), 2, "{}", prompts[2]);
+    assert!(prompts[2].contains("matching the `Task` schema given earlier"));
+    assert!(prompts[2].starts_with("User: Plan the release"));
+    assert_eq!(conversation.history().len(), 6);
+}
+
+#[tokio::test]
+async fn dedup_can_be_disabled() {
+    let (client, handle) = mock(false, r#"{"title": "x"}"#, 2);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let mut conversation = resolver.conversation().with_schema_dedup(false);
+
+    conversation.query::<Task>("one".into()).await.unwrap();
+    conversation.query::<Task>("two".into()).await.unwrap();
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts[1].matches(SCHEMA_MARKER).count(), 2);
+}
+
+#[tokio::test]
+async fn system_placement_keeps_schemas_in_one_system_message() {
+    let (client, handle) = mock(true, r#"{"hours": 2}"#, 2);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_schema_placement(SchemaPlacement::System);
+    let mut conversation = resolver.conversation();
+
+    conversation.query::<Task>("Plan".into()).await.unwrap();
+    let estimate = conversation.query::<Estimate>("How long?".into()).await.unwrap();
+    assert_eq!(estimate.first().unwrap().hours, 2);
+
+    let histories: Vec<Vec<ChatMessage>> = handle.calls().into_iter().filter_map(|call| call.messages).collect();
+    let last = histories.last().unwrap();
+    let system: Vec<&ChatMessage> = last.iter().filter(|m| m.role == ChatRole::System).collect();
+    assert_eq!(system.len(), 1);
+    assert!(system[0].content.contains("Schema `Task`") && system[0].content.contains("Schema `Estimate`"));
+    assert!(last.iter().filter(|m| m.role != ChatRole::System).all(|m| !m.content.contains(SCHEMA_MARKER)));
+    assert_eq!(last.last().unwrap().role, ChatRole::User);
+}
+
+#[tokio::test]
+async fn single_query_moves_schema_to_system_role() {
+    let (client, handle) = mock(true, r#"{"title": "a"}"#, 1);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_schema_placement(SchemaPlacement::System);
+    resolver.query::<Task>("Plan".into()).await.unwrap();
+
+    let histories: Vec<Vec<ChatMessage>> = handle.calls().into_iter().filter_map(|call| call.messages).collect();
+    assert_eq!(histories[0][0].role, ChatRole::System);
+    assert!(histories[0][0].content.contains(SCHEMA_MARKER));
+    assert_eq!(histories[0][1], ChatMessage::user("Plan"));
+    assert_eq!(handle.calls().len(), histories.len());
+}
+
+#[tokio::test]
+async fn system_placement_falls_back_to_prompt() {
+    let (client, handle) = mock(false, r#"{"title": "a"}"#, 1);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_schema_placement(SchemaPlacement::System);
+    assert!(!resolver.schema_in_system_role());
+    resolver.query::<Task>("Plan".into()).await.unwrap();
+
+    let prompts = handle.prompts();
+    assert!(prompts[0].starts_with("Plan\n\n") && prompts[0].contains(SCHEMA_MARKER));
+}
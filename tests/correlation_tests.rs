@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex};
+
+use semantic_query::clients::mock::{MockClient, MockHandle, MockResponse};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::correlation::{self, CorrelationId};
+use semantic_query::error::{AIError, OpenAIError, ProviderError, QueryResolverError};
+use semantic_query::stats::QueryStats;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Answer {
+    value: u32,
+}
+
+/// A mock answering each of `calls` queries with a fixed answer
+fn mock(calls: usize) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![r#"{"value": 1}"#; calls]);
+    (client, handle)
+}
+
+#[tokio::test]
+async fn each_query_gets_a_fresh_id() {
+    let (client, handle) = mock(2);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    resolver.query::<Answer>("one".into()).await.unwrap();
+    resolver.query::<Answer>("two".into()).await.unwrap();
+
+    let seen: Vec<CorrelationId> = handle.calls().into_iter().map(|call| call.correlation_id.unwrap()).collect();
+    let (first, second) = (&seen[0], &seen[1]);
+    assert_ne!(first, second);
+    assert!(first.as_str().starts_with("sq-"));
+    assert!(correlation::current().is_none());
+}
+
+#[tokio::test]
+async fn caller_id_is_propagated_to_client_and_stats() {
+    let (client, handle) = mock(2);
+    let stats: Arc<Mutex<Vec<QueryStats>>> = Arc::default();
+    let sink = stats.clone();
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_stats_callback(move |s| sink.lock().unwrap().push(s.clone()));
+
+    let id = CorrelationId::from("req-7");
+    correlation::scope(id.clone(), async {
+        resolver.query::<Answer>("one".into()).await.unwrap();
+        resolver.query_typed::<Answer>("two".into()).await.unwrap();
+    }).await;
+
+    assert!(handle.calls().iter().all(|call| call.correlation_id.as_ref() == Some(&id)));
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats.len(), 2);
+    assert!(stats.iter().all(|s| s.correlation_id.as_deref() == Some("req-7")));
+}
+
+#[tokio::test]
+async fn provider_errors_record_the_id() {
+    let (client, handle) = MockClient::new();
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let err = correlation::scope(CorrelationId::from("req-9"), async {
+        // Built while the query runs, as a provider would
+        handle.add_response(MockResponse::Error(AIError::OpenAI(ProviderError::new(500, "boom").into())));
+        resolver.query::<Answer>("one".into()).await.unwrap_err()
+    }).await;
+
+    let QueryResolverError::Ai(err) = err else { panic!("expected an AI error, got {err:?}") };
+    assert!(matches!(err, AIError::OpenAI(OpenAIError::Status(_))));
+    assert_eq!(err.correlation_id(), Some("req-9"));
+    assert!(err.to_string().contains("correlation id req-9"));
+}
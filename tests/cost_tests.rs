@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use semantic_query::audit::RecordUsage;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::cost::{self, CostBreakdown, ModelPrice, PriceTable, Session, UNKNOWN_MODEL};
+use semantic_query::schema::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Invoice {
+    amount: u32,
+}
+
+/// A mock that answers `replies` prompts with one invoice each and reports the usage
+/// like a provider would
+fn billed(replies: usize, prompt_tokens: u64, cached_tokens: u64, completion_tokens: u64) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![r#"{"amount": 12}"#; replies]);
+    handle.report_usage(RecordUsage { prompt_tokens, cached_tokens, completion_tokens });
+    (client, handle)
+}
+
+fn prices() -> PriceTable {
+    PriceTable::new().with_default(ModelPrice::new(0.01, 0.03).with_cached(0.001))
+}
+
+#[tokio::test]
+async fn responses_carry_the_cost_of_reported_usage() {
+    let (client, _handle) = billed(1, 1500, 500, 200);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_pricing(prices());
+    let response = resolver.query::<Invoice>("Extract the invoice".into()).await.unwrap();
+    let cost = response.cost.clone().unwrap();
+    assert_eq!((cost.model.as_deref(), cost.input_tokens, cost.cached_tokens, cost.output_tokens), (None, 1000, 500, 200));
+    assert_eq!((cost.input_per_1k, cost.cached_per_1k, cost.output_per_1k), (0.01, 0.001, 0.03));
+    // 1000 * 0.01 + 500 * 0.001 + 200 * 0.03, per 1000 tokens
+    assert!((cost.total - 0.0165).abs() < 1e-12, "{}", cost.total);
+    assert!(serde_json::to_value(&response).unwrap()["cost"]["total"].is_number());
+
+    // Without a price for the model nothing is priced
+    let (client, _handle) = billed(1, 10, 0, 10);
+    let unpriced = QueryResolver::new(client, RetryConfig::default()).with_pricing(PriceTable::new());
+    assert!(unpriced.query::<Invoice>("x".into()).await.unwrap().cost.is_none());
+}
+
+#[tokio::test]
+async fn sessions_and_the_global_report_roll_costs_up() {
+    let session = Session::new("cost-tests-rollup");
+    let (client, _handle) = billed(2, 1000, 0, 100);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_pricing(prices())
+        .with_cost_session(session.clone());
+    resolver.query::<Invoice>("a".into()).await.unwrap();
+    resolver.query::<Invoice>("b".into()).await.unwrap();
+    // Without reported usage the estimate is priced
+    let estimated = QueryResolver::new(ScriptedClient::Reply(r#"{"amount": 3}"#.into()), RetryConfig::default())
+        .with_pricing(prices())
+        .with_cost_session(session.clone());
+    let cost = estimated.query::<Invoice>("c".into()).await.unwrap().cost.unwrap();
+    assert!(cost.input_tokens > 0 && cost.output_tokens > 0);
+
+    let summary = session.cost_summary();
+    let total = summary.total();
+    assert_eq!((total.queries, total.input_tokens - cost.input_tokens, total.output_tokens - cost.output_tokens), (3, 2000, 200));
+    assert!((total.total - 0.026 - cost.total).abs() < 1e-12);
+    assert_eq!(summary.by_model.keys().collect::<Vec<_>>(), [UNKNOWN_MODEL]);
+    assert_eq!(cost::global_cost_report().sessions["cost-tests-rollup"], summary);
+
+    let csv = summary.to_csv();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("model,queries,input_tokens,cached_tokens,output_tokens,total"));
+    assert!(lines.next().unwrap().starts_with("unknown,3,"));
+    assert!(cost::global_cost_report().to_csv().contains("\ncost-tests-rollup,unknown,3,"));
+    let json: serde_json::Value = serde_json::from_str(&summary.to_json()).unwrap();
+    assert_eq!(json["by_model"][UNKNOWN_MODEL]["queries"], 3);
+}
+
+#[test]
+fn models_are_priced_by_their_longest_prefix() {
+    let mini = ModelPrice::new(0.00015, 0.0006);
+    let table = PriceTable::new()
+        .with_model("gpt-4o", ModelPrice::new(0.0025, 0.01))
+        .with_model("gpt-4o-mini", mini);
+    assert_eq!(table.price_for(Some("gpt-4o-mini-2024-07-18")), Some(mini));
+    assert_eq!(table.price_for(Some("gpt-4o-2024-08-06")).unwrap().input_per_1k, 0.0025);
+    assert_eq!(table.price_for(Some("claude-3-5-haiku")), None);
+    assert_eq!(table.price_for(None), None);
+
+    // Cached tokens never exceed the prompt they are part of
+    let usage = RecordUsage { prompt_tokens: 10, cached_tokens: 50, completion_tokens: 0 };
+    let cost = CostBreakdown::new(Some("gpt-4o-mini".into()), usage, mini.with_cached(0.0));
+    assert_eq!((cost.input_tokens, cost.cached_tokens, cost.total), (0, 10, 0.0));
+}
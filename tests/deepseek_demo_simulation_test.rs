@@ -79,6 +79,9 @@ async fn test_deepseek_cutoff_pattern() {
             Ok(StreamItem::Data(tc)) => {
                 println!("\n[Got Tool Call]: {}", tc.name);
             },
+            Ok(StreamItem::Finished { reason }) => {
+                println!("\n[Finished]: {:?}", reason);
+            },
             Err(e) => panic!("Stream error: {}", e),
         }
     }
@@ -0,0 +1,91 @@
+use bytes::Bytes;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{ParsedResponse, QueryResolver, RawByteStream, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::json_utils::{find_json_structures_in, DetectionScope, JsonStreamParser};
+use semantic_query::streaming::{stream_from_bytes_with, stream_from_sse_bytes_with, StreamItem, StreamOptions, TextFidelity};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Row {
+    id: u32,
+}
+
+const FENCED: &str = "Rows look like {\"id\": 0}, so:\n```json\n{\"id\": 1}\n```\nand {\"id\": 9} is not one.\n  ```\n[{\"id\": 2}]\n```";
+
+fn sentinel() -> DetectionScope {
+    DetectionScope::AfterSentinel("ANSWER:".into())
+}
+
+fn ids(items: &[StreamItem<Row>]) -> Vec<u32> {
+    items.iter().filter_map(|item| match item {
+        StreamItem::Data(row) => Some(row.id),
+        _ => None,
+    }).collect()
+}
+
+fn sse(tokens: &[&str]) -> RawByteStream {
+    let mut body = String::new();
+    for token in tokens {
+        body.push_str(&format!("data: {}\n\n", serde_json::json!({ "choices": [{ "delta": { "content": token } }] })));
+    }
+    body.push_str("data: [DONE]\n\n");
+    Box::pin(futures_util::stream::iter(vec![Ok::<_, AIError>(Bytes::from(body))]))
+}
+
+#[tokio::test]
+async fn queries_only_take_json_within_the_scope() {
+    let fenced = QueryResolver::new(ScriptedClient::Reply(FENCED.into()), RetryConfig::default())
+        .with_detection_scope(DetectionScope::FencedOnly);
+    let response = fenced.query::<Row>("List the rows".into()).await.unwrap();
+    assert_eq!(response.data_only().iter().map(|row| row.id).collect::<Vec<_>>(), [1, 2]);
+    assert!(response.text_content().contains("and {\"id\": 9} is not one."));
+
+    let reply = "Thinking: {\"id\": 7} looks wrong.\nANSWER: {\"id\": 3} (not {\"id\": 4}, ANSWER: {\"id\": 5})";
+    let answered = QueryResolver::new(ScriptedClient::Reply(reply.into()), RetryConfig::default())
+        .with_detection_scope(sentinel());
+    let response = answered.query::<Row>("Which row?".into()).await.unwrap();
+    // Everything after the first sentinel counts
+    assert_eq!(response.data_only().iter().map(|row| row.id).collect::<Vec<_>>(), [3, 4, 5]);
+
+    // JSON before the sentinel is kept as written
+    let exact = ParsedResponse::<Row>::from_raw_scoped(reply, TextFidelity::Exact, &sentinel());
+    assert!(exact.text_content().starts_with("Thinking: {\"id\": 7} looks wrong.\nANSWER:  {\"id\": 3}"));
+    assert_eq!(ParsedResponse::<Row>::from_raw(FENCED).data_only().len(), 4);
+}
+
+#[tokio::test]
+async fn streams_track_the_scope_across_chunks() {
+    let chunks = ["Use {\"id\": 0}.\n`", "``json\n{\"id\"", ": 1}\n`", "`", "`\n{\"id\": 9}"];
+    let bytes = futures_util::stream::iter(chunks.map(|chunk| Ok::<_, AIError>(Bytes::from_static(chunk.as_bytes()))));
+    let options = StreamOptions { detection_scope: DetectionScope::FencedOnly, ..StreamOptions::default() };
+    let items: Vec<StreamItem<Row>> = stream_from_bytes_with(Box::pin(bytes), options).map(Result::unwrap).collect().await;
+    assert_eq!(ids(&items), [1]);
+
+    // SSE text is flushed by paragraph, sentinel included, before the answer arrives
+    let tokens = ["Draft: {\"id\": 7}\n\n", "ANS", "WER:\n\n", "{\"id\": 2}"];
+    let options = StreamOptions { detection_scope: sentinel(), ..StreamOptions::default() };
+    let items: Vec<StreamItem<Row>> = stream_from_sse_bytes_with(sse(&tokens), options)
+        .filter_map(|item| async move { item.ok() })
+        .collect()
+        .await;
+    assert_eq!(ids(&items), [2]);
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Text(t) if t.text.contains("{\"id\": 7}"))));
+}
+
+#[test]
+fn fences_drop_structures_they_cut_off() {
+    let text = "```\n{\"id\": [1,\n```\n{\"id\": 2}\n```\n{\"id\": 3}\n```";
+    let roots = find_json_structures_in(text, &DetectionScope::FencedOnly);
+    assert_eq!(roots.iter().map(|node| &text[node.start..=node.end]).collect::<Vec<_>>(), ["{\"id\": 3}"]);
+    // Unscoped, the cut-off structure swallows everything after it
+    assert!(find_json_structures_in(text, &DetectionScope::Anywhere).is_empty());
+
+    // A sentinel split between feeds is still found
+    let mut parser = JsonStreamParser::new().with_scope(sentinel());
+    assert!(parser.feed("[0] ANSW").is_empty());
+    assert_eq!(parser.feed("ER: [1]").len(), 1);
+    assert_eq!(find_json_structures_in("no marker {}", &DetectionScope::AfterSentinel(String::new())).len(), 1);
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::{stream, StreamExt};
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RawByteStream, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::streaming::{stream_from_bytes_with, FinishReason, StreamItem, StreamOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+fn early_stop() -> StreamOptions {
+    StreamOptions { stop_after_data: true, ..StreamOptions::default() }
+}
+
+fn counted(chunks: &[&'static str], polled: Arc<AtomicUsize>) -> RawByteStream {
+    Box::pin(stream::iter(chunks.to_vec()).map(move |chunk| {
+        polled.fetch_add(1, Ordering::SeqCst);
+        Ok::<_, AIError>(Bytes::from_static(chunk.as_bytes()))
+    }))
+}
+
+#[tokio::test]
+async fn stream_ends_after_first_data_without_reading_further() {
+    let polled = Arc::new(AtomicUsize::new(0));
+    let chunks = ["Sure: ", r#"{"name": "Ada"}"#, " Let me also explain", " at great length."];
+    let items: Vec<StreamItem<Contact>> = stream_from_bytes_with(counted(&chunks, polled.clone()), early_stop())
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    assert!(matches!(items[0], StreamItem::Text(_)));
+    assert!(matches!(items[1], StreamItem::Data(ref c) if c.name == "Ada"));
+    assert!(matches!(items[2], StreamItem::Finished { reason: FinishReason::EarlyStopAfterData }));
+    assert_eq!(items.len(), 3);
+    // The trailing prose was never pulled from the connection
+    assert_eq!(polled.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn streams_run_to_the_end_by_default() {
+    let polled = Arc::new(AtomicUsize::new(0));
+    let chunks = [r#"{"name": "Ada"}"#, " and more."];
+    let items: Vec<StreamItem<Contact>> = stream_from_bytes_with(counted(&chunks, polled.clone()), StreamOptions::default())
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    assert!(!items.iter().any(|i| matches!(i, StreamItem::Finished { .. })));
+    assert_eq!(polled.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn resolver_streams_honour_stop_after_data() {
+    let client = ScriptedClient::Stream(
+        [r#"{"name": "#, r#""Ada"}"#, "\n\nAnything else?"].iter().map(|c| c.as_bytes().to_vec()).collect(),
+    );
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_stream_options(early_stop());
+
+    let items: Vec<StreamItem<Contact>> = resolver.stream_query::<Contact>("who?".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let last = items.last().unwrap();
+    assert!(matches!(last, StreamItem::Finished { reason: FinishReason::EarlyStopAfterData }));
+    assert!(matches!(items[items.len() - 2], StreamItem::Data(ref c) if c.name == "Ada"));
+    assert!(!items.iter().any(|i| matches!(i, StreamItem::Token(t) if t.contains("Anything"))));
+}
+
+#[test]
+fn finished_items_serialize_with_their_reason() {
+    let item: StreamItem<Contact> = StreamItem::Finished { reason: FinishReason::EarlyStopAfterData };
+    let value = serde_json::to_value(&item).unwrap();
+    assert_eq!(value, json!({"kind": "Finished", "content": {"reason": "EarlyStopAfterData"}}));
+    let back: StreamItem<Contact> = serde_json::from_value(value).unwrap();
+    assert!(matches!(back, StreamItem::Finished { reason: FinishReason::EarlyStopAfterData }));
+}
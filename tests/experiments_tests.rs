@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::{AIError, QueryResolverError};
+use semantic_query::experiments::{Experiment, Variant};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Ticket {
+    priority: u8,
+}
+
+fn resolver(client: ScriptedClient) -> QueryResolver<ScriptedClient> {
+    QueryResolver::new(client, RetryConfig::default())
+}
+
+#[test]
+fn assignment_is_deterministic_and_weighted() {
+    let experiment = Experiment::new("triage")
+        .with_variant(Variant::new("control", "{input}", resolver(ScriptedClient::Reply(String::new()))).with_weight(3))
+        .with_variant(Variant::new("terse", "{input}", resolver(ScriptedClient::Reply(String::new()))))
+        .with_variant(Variant::new("off", "{input}", resolver(ScriptedClient::Reply(String::new()))).with_weight(0));
+
+    let mut counts = std::collections::HashMap::new();
+    for i in 0..2000 {
+        let key = format!("user-{i}");
+        let name = experiment.assign(&key).unwrap().name.clone();
+        assert_eq!(experiment.assign(&key).unwrap().name, name);
+        *counts.entry(name).or_insert(0) += 1;
+    }
+    assert!(!counts.contains_key("off"));
+    let control = counts["control"] as f64 / 2000.0;
+    assert!((0.68..0.82).contains(&control), "control share {control}");
+
+    let empty: Experiment<ScriptedClient> = Experiment::new("empty");
+    assert!(empty.assign("user-1").is_none());
+}
+
+#[test]
+fn templates_place_the_input() {
+    let variant = Variant::new("v", "Triage:\n{input}\nAnswer in JSON.", resolver(ScriptedClient::Reply(String::new())));
+    assert_eq!(variant.render("printer on fire"), "Triage:\nprinter on fire\nAnswer in JSON.");
+    let variant = Variant::new("v", "Triage this ticket.", resolver(ScriptedClient::Reply(String::new())));
+    assert_eq!(variant.render("printer on fire"), "Triage this ticket.\n\nprinter on fire");
+}
+
+#[tokio::test]
+async fn outcomes_are_reported_and_summarized() {
+    let outcomes = Arc::new(Mutex::new(Vec::new()));
+    let sink = outcomes.clone();
+    let experiment = Experiment::new("triage")
+        .with_variant(Variant::new("json", "{input}", resolver(ScriptedClient::Reply(r#"{"priority": 2}"#.into()))).with_cost_per_1k_tokens(1.0))
+        .with_outcome_callback(move |o| sink.lock().unwrap().push(o.clone()));
+
+    let run = experiment.query::<Ticket>("user-1", "printer on fire").await.unwrap();
+    assert_eq!(run.outcome.variant, "json");
+    assert!(run.outcome.compliant);
+    assert!(run.outcome.cost.unwrap() > 0.0);
+    assert_eq!(run.response.first().unwrap().priority, 2);
+
+    let failing = Experiment::new("triage")
+        .with_variant(Variant::new("broken", "{input}", resolver(ScriptedClient::Fail(AIError::Mock("down".into())))));
+    assert!(matches!(failing.query::<Ticket>("user-1", "x").await, Err(QueryResolverError::Ai(_))));
+    let report = failing.report();
+    assert_eq!((report.variants[0].runs, report.variants[0].errors, report.variants[0].compliant), (1, 1, 0));
+
+    experiment.query::<Ticket>("user-2", "printer still on fire").await.unwrap();
+    let report = experiment.report();
+    assert_eq!(report.variants[0].runs, 2);
+    assert_eq!(report.variants[0].compliance_rate(), 1.0);
+    assert_eq!(report.leader().unwrap().variant, "json");
+    assert_eq!(outcomes.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,66 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::core::{ParsedResponse, ResponseItem};
+use semantic_query::streaming::{build_parsed_stream, stream_from_bytes, StreamItem};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Item {
+    id: u32,
+}
+
+fn texts<T: JsonSchema>(items: &[StreamItem<T>]) -> Vec<&str> {
+    items.iter().filter_map(|item| match item {
+        StreamItem::Text(t) => Some(t.text.as_str()),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn original_text_is_the_source_slice() {
+    let raw = "Found:\n{ \"id\" : 1 }\nand {\"id\": 2}";
+    let response = ParsedResponse::<Item>::from_raw(raw);
+    let originals: Vec<&str> = response.items.iter().filter_map(|item| match item {
+        ResponseItem::Data { original_text, .. } => Some(original_text.as_str()),
+        ResponseItem::Text(_) => None,
+    }).collect();
+    assert_eq!(originals, vec!["{ \"id\" : 1 }", "{\"id\": 2}"]);
+}
+
+#[test]
+fn nested_matches_keep_their_own_offsets() {
+    let raw = r#"pre {"results": [{"id": 1}, {"other": true}, {"id": 2}]} post"#;
+    let items: Vec<StreamItem<Item>> = build_parsed_stream(raw);
+    let data: Vec<u32> = items.iter().filter_map(|i| match i {
+        StreamItem::Data(d) => Some(d.id),
+        _ => None,
+    }).collect();
+    assert_eq!(data, vec![1, 2]);
+    assert_eq!(texts(&items), vec!["pre ", r#"{"other": true}"#, " post"]);
+
+    let response = ParsedResponse::<Item>::from_raw(raw);
+    assert_eq!(response.items.len(), items.len());
+}
+
+#[test]
+fn unmatched_root_is_kept_whole() {
+    let raw = r#"{"a": {"b": 1}}"#;
+    let items: Vec<StreamItem<Item>> = build_parsed_stream(raw);
+    assert_eq!(texts(&items), vec![r#"{"b": 1}"#, raw]);
+}
+
+#[tokio::test]
+async fn byte_stream_matches_one_shot_segmentation() {
+    let raw = r#"a {"wrap": [{"id": 7}, {"id": 8}]} b {"x": 1} c"#;
+    let chunks: Vec<Result<bytes::Bytes, semantic_query::error::AIError>> = raw
+        .as_bytes()
+        .chunks(4)
+        .map(|c| Ok(bytes::Bytes::copy_from_slice(c)))
+        .collect();
+    let streamed: Vec<StreamItem<Item>> = stream_from_bytes::<Item>(Box::pin(futures_util::stream::iter(chunks)))
+        .filter_map(|item| async move { item.ok() })
+        .collect()
+        .await;
+    let direct: Vec<StreamItem<Item>> = build_parsed_stream(raw);
+    assert_eq!(serde_json::to_value(&streamed).unwrap(), serde_json::to_value(&direct).unwrap());
+}
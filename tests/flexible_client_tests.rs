@@ -0,0 +1,73 @@
+#![cfg(all(feature = "openai", feature = "deepseek"))]
+
+use semantic_query::clients::flexible::FlexibleClient;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::LowLevelClient;
+
+#[tokio::test]
+async fn swap_with_replaces_client_for_all_clones() {
+    let (client, first) = FlexibleClient::mock();
+    let shared = client.clone();
+    first.add_json_response(r#"{"from":"first"}"#);
+    assert_eq!(shared.ask_raw("p".to_string()).await.unwrap(), r#"{"from":"first"}"#);
+
+    let (replacement, second) = MockClient::new();
+    second.add_json_response(r#"{"from":"second"}"#);
+    client.swap_with(Box::new(replacement));
+
+    // The clone observes the swap without being rebuilt
+    assert_eq!(shared.ask_raw("p".to_string()).await.unwrap(), r#"{"from":"second"}"#);
+}
+
+#[tokio::test]
+async fn lazy_client_defers_construction_until_first_call() {
+    use semantic_query::clients::flexible::{ClientType, LazyClient};
+
+    let lazy = LazyClient::new(ClientType::Mock);
+    assert!(!lazy.is_initialized());
+    // The mock built from a ClientType has no handle, so the call errors, but the
+    // client itself is now constructed
+    assert!(lazy.ask_raw("p".to_string()).await.is_err());
+    assert!(lazy.is_initialized());
+}
+
+#[test]
+fn missing_key_is_a_configuration_error() {
+    use semantic_query::clients::deepseek::DeepSeekClient;
+    use semantic_query::error::AIError;
+
+    std::env::remove_var("DEEPSEEK_API_KEY");
+    if std::path::Path::new(".env").exists() {
+        return; // a local .env may legitimately provide the key
+    }
+    assert!(matches!(DeepSeekClient::try_default(), Err(AIError::Configuration(_))));
+}
+
+#[test]
+fn configure_tweaks_the_boxed_client_for_all_clones() {
+    use semantic_query::clients::{OpenAIClient, OpenAIConfig, OpenAIModel};
+
+    let client = FlexibleClient::new(Box::new(OpenAIClient::new(OpenAIConfig::default())));
+    let shared = client.clone();
+    assert!(client.as_any().is::<OpenAIClient>());
+    assert!(client.as_claude().is_none());
+
+    assert!(client.configure(|openai: &mut OpenAIClient| {
+        openai.set_model(OpenAIModel::Gpt4o);
+        openai.set_temperature(0.9);
+    }));
+
+    let openai = shared.as_openai().expect("still an OpenAI client");
+    assert_eq!(openai.config().model, OpenAIModel::Gpt4o);
+    assert!((openai.config().temperature - 0.9).abs() < f32::EPSILON);
+}
+
+#[test]
+fn configure_is_a_no_op_for_other_client_types() {
+    use semantic_query::clients::OpenAIClient;
+
+    let (client, _handle) = FlexibleClient::mock();
+    assert!(client.as_any().is::<MockClient>());
+    assert!(!client.configure(|_: &mut OpenAIClient| panic!("not an OpenAI client")));
+    assert!(client.as_openai().is_none());
+}
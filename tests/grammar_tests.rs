@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::grammar::{schema_to_gbnf, OutputConstraint};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Finding {
+    title: String,
+    severity: u8,
+    tags: Vec<String>,
+    note: Option<String>,
+}
+
+#[test]
+fn object_schema_lists_required_then_optional_members() {
+    let gbnf = schema_to_gbnf(&json!({
+        "type": "object",
+        "properties": {
+            "a": { "type": "integer" },
+            "b": { "type": "string" }
+        },
+        "required": ["a"]
+    }))
+    .unwrap();
+
+    let root = gbnf.lines().next().unwrap();
+    assert!(root.starts_with("root ::= \"{\" ws"), "{root}");
+    assert!(root.contains(r#""\"a\"" ":" ws integer"#), "{root}");
+    assert!(root.contains(r#"( "," ws "\"b\"" ":" ws string )?"#), "{root}");
+    assert!(gbnf.contains("integer ::= "));
+    assert!(gbnf.contains("ws ::= "));
+}
+
+#[test]
+fn enums_become_literal_alternatives() {
+    let gbnf = schema_to_gbnf(&json!({ "enum": ["low", "high", 3] })).unwrap();
+    assert!(gbnf.starts_with(r#"root ::= ( "\"low\"" | "\"high\"" | "3" ) ws"#), "{gbnf}");
+}
+
+#[test]
+fn recursive_refs_terminate() {
+    let gbnf = schema_to_gbnf(&json!({
+        "$ref": "#/$defs/Node",
+        "$defs": {
+            "Node": {
+                "type": "object",
+                "properties": {
+                    "children": { "type": "array", "items": { "$ref": "#/$defs/Node" } }
+                },
+                "required": ["children"]
+            }
+        }
+    }))
+    .unwrap();
+    assert!(gbnf.contains("root ::= node"), "{gbnf}");
+    assert!(gbnf.contains("node ::= "), "{gbnf}");
+}
+
+#[test]
+fn unknown_refs_are_rejected() {
+    assert!(schema_to_gbnf(&json!({ "$ref": "http://example.com/schema" })).is_err());
+}
+
+#[test]
+fn derived_schemas_convert() {
+    let constraint = OutputConstraint::for_type::<Finding>().unwrap();
+    assert!(constraint.gbnf.contains(r#""\"severity\"""#));
+    assert_eq!(constraint.json_schema["type"], "object");
+}
+
+/// A mock enforcing grammars, answering `reply`
+fn grammar_client(reply: &str) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::Grammar]);
+    handle.add_json_response(reply);
+    (client, handle)
+}
+
+#[tokio::test]
+async fn grammar_constrained_queries_parse_strictly() {
+    let body = r#"{"title":"leak","severity":3,"tags":["mem"],"note":null}"#;
+    let (client, handle) = grammar_client(body);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_grammar_constraints(true);
+    assert!(resolver.grammar_enforced());
+
+    let response = resolver.query::<Finding>("find issues".to_string()).await.unwrap();
+    assert_eq!(response.data_count(), 1);
+    assert_eq!(response.first_required().unwrap().title, "leak");
+    assert_eq!(handle.calls()[0].constraint, Some(OutputConstraint::for_type::<Finding>().unwrap()));
+}
+
+#[tokio::test]
+async fn grammar_violations_surface_as_deserialization_errors() {
+    let (client, _handle) = grammar_client(r#"{"title":"leak"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_grammar_constraints(true);
+    let err = resolver.query::<Finding>("find issues".to_string()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::JsonDeserialization(_, _)));
+}
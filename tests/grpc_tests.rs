@@ -0,0 +1,167 @@
+#![cfg(feature = "grpc")]
+
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::clients::grpc::{GenerateReply, GenerateRequest, DEFAULT_SERVICE};
+use semantic_query::clients::{GrpcClient, GrpcConfig};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::error::{AIError, GrpcError};
+use semantic_query::streaming::StreamItem;
+use serde::{Deserialize, Serialize};
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, Body, BoxFuture, Service, StdError};
+use tonic::server::{NamedService, ServerStreamingService, UnaryService};
+use tonic::{Request, Response, Status};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+/// What the test server answers: a reply split into chunks, or a failure status
+#[derive(Clone)]
+enum Answer {
+    Chunks(Vec<&'static str>),
+    Fail(tonic::Code),
+}
+
+/// Hand-written `TextGeneration` server recording the requests it gets
+#[derive(Clone)]
+struct TestServer {
+    answer: Answer,
+    seen: Arc<Mutex<Vec<(GenerateRequest, Option<String>)>>>,
+}
+
+impl NamedService for TestServer {
+    const NAME: &'static str = DEFAULT_SERVICE;
+}
+
+impl TestServer {
+    fn record(&self, request: &Request<GenerateRequest>) -> Result<Vec<&'static str>, Status> {
+        let auth = request.metadata().get("authorization").and_then(|v| v.to_str().ok()).map(str::to_string);
+        self.seen.lock().unwrap().push((request.get_ref().clone(), auth));
+        match &self.answer {
+            Answer::Chunks(chunks) => Ok(chunks.clone()),
+            Answer::Fail(code) => Err(Status::new(*code, "server says no")),
+        }
+    }
+}
+
+impl UnaryService<GenerateRequest> for TestServer {
+    type Response = GenerateReply;
+    type Future = BoxFuture<Response<GenerateReply>, Status>;
+
+    fn call(&mut self, request: Request<GenerateRequest>) -> Self::Future {
+        let result = self.record(&request).map(|chunks| Response::new(GenerateReply { text: chunks.concat(), finish_reason: "stop".into() }));
+        Box::pin(async move { result })
+    }
+}
+
+impl ServerStreamingService<GenerateRequest> for TestServer {
+    type Response = GenerateReply;
+    type ResponseStream = futures_util::stream::BoxStream<'static, Result<GenerateReply, Status>>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<GenerateRequest>) -> Self::Future {
+        let result = self.record(&request).map(|chunks| {
+            let replies = chunks.into_iter().map(|text| Ok(GenerateReply { text: text.into(), finish_reason: String::new() }));
+            Response::new(futures_util::stream::iter(replies).boxed())
+        });
+        Box::pin(async move { result })
+    }
+}
+
+impl<B> Service<http::Request<B>> for TestServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let server = self.clone();
+        let streaming = req.uri().path().ends_with("/GenerateStream");
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(ProstCodec::<GenerateReply, GenerateRequest>::default());
+            Ok(if streaming { grpc.server_streaming(server, req).await } else { grpc.unary(server, req).await })
+        })
+    }
+}
+
+async fn serve(answer: Answer) -> (String, Arc<Mutex<Vec<(GenerateRequest, Option<String>)>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let server = TestServer { answer, seen: seen.clone() };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let incoming = async_stream::stream! {
+        loop {
+            yield listener.accept().await.map(|(stream, _)| stream);
+        }
+    };
+    tokio::spawn(tonic::transport::Server::builder().add_service(server).serve_with_incoming(incoming));
+    (url, seen)
+}
+
+#[tokio::test]
+async fn unary_generate_answers_queries() {
+    let (url, seen) = serve(Answer::Chunks(vec![r#"{"name": "#, r#""Ada"}"#])).await;
+    let config = GrpcConfig { temperature: Some(0.3), ..GrpcConfig::new(url, "house-7b").with_bearer_token("secret") };
+    let resolver = QueryResolver::new(GrpcClient::new(config), RetryConfig::default());
+
+    let response = resolver.query::<Contact>("Who?".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Contact { name: "Ada".into() }));
+
+    let seen = seen.lock().unwrap();
+    let (request, auth) = &seen[0];
+    assert!(request.prompt.starts_with("Who?"));
+    assert_eq!(request.model, "house-7b");
+    assert_eq!(request.temperature, Some(0.3));
+    assert_eq!(auth.as_deref(), Some("Bearer secret"));
+}
+
+#[tokio::test]
+async fn generate_stream_feeds_the_stream_pipeline() {
+    let (url, _) = serve(Answer::Chunks(vec!["Found ", r#"{"name""#, r#": "Lin"}"#, " here"])).await;
+    let resolver = QueryResolver::new(GrpcClient::new(GrpcConfig::new(url, "house-7b")), RetryConfig::default());
+
+    let items: Vec<_> = resolver.stream_query::<Contact>("Who?".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let tokens: Vec<&str> = items.iter().filter_map(|item| match item {
+        StreamItem::Token(token) => Some(token.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(tokens, ["Found ", r#"{"name""#, r#": "Lin"}"#, " here"]);
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Data(c) if c.name == "Lin")));
+}
+
+#[tokio::test]
+async fn statuses_map_to_retryable_errors() {
+    let (url, _) = serve(Answer::Fail(tonic::Code::Unavailable)).await;
+    let client = GrpcClient::new(GrpcConfig::new(url, "m"));
+    let error = client.ask_raw("x".into()).await.unwrap_err();
+    assert!(matches!(&error, AIError::Grpc(GrpcError::Status { code, message }) if code == "Unavailable" && message == "server says no"));
+    assert!(error.is_retryable());
+
+    let (url, _) = serve(Answer::Fail(tonic::Code::InvalidArgument)).await;
+    let chunks: Vec<_> = GrpcClient::new(GrpcConfig::new(url, "m")).stream_raw("x".into()).unwrap().collect().await;
+    assert!(matches!(chunks.last(), Some(Err(e)) if !e.is_retryable()));
+
+    let unreachable = GrpcClient::new(GrpcConfig::new("http://127.0.0.1:1", "m"));
+    let error = unreachable.ask_raw("x".into()).await.unwrap_err();
+    assert!(matches!(error, AIError::Grpc(_)) && error.is_retryable(), "{error:?}");
+
+    let invalid = GrpcClient::new(GrpcConfig::new("not a uri", "m"));
+    assert!(matches!(invalid.ask_raw("x".into()).await, Err(AIError::Configuration(_))));
+}
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use semantic_query::clients::{FlexibleClient, MockClient, MockHandle, MockResponse};
+use semantic_query::core::LowLevelClient;
+use semantic_query::correlation::{self, CorrelationId};
+use semantic_query::error::AIError;
+use semantic_query::interceptors::{GuardedInterceptor, Interceptor, InterceptorErrorPolicy, InterceptorRetry};
+
+/// Fails its first `failures` saves, then records the prompt and correlation id
+#[derive(Debug, Default)]
+struct FlakySink {
+    failures: AtomicUsize,
+    saved: Mutex<Vec<(String, Option<CorrelationId>)>>,
+}
+
+impl FlakySink {
+    fn failing(failures: usize) -> Arc<Self> {
+        Arc::new(Self { failures: AtomicUsize::new(failures), ..Self::default() })
+    }
+}
+
+#[async_trait]
+impl Interceptor for FlakySink {
+    async fn save(&self, prompt: &str, _response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+            return Err("audit store unavailable".into());
+        }
+        self.saved.lock().unwrap().push((prompt.to_string(), correlation::current()));
+        Ok(())
+    }
+}
+
+fn mock(replies: usize) -> (FlexibleClient, Arc<MockHandle>) {
+    let (mock, handle) = MockClient::new();
+    for _ in 0..replies {
+        handle.add_response(MockResponse::Success("ok".into()));
+    }
+    (FlexibleClient::new(Box::new(mock)), handle)
+}
+
+fn retry(capacity: usize, max_attempts: u32) -> InterceptorRetry {
+    InterceptorRetry { capacity, max_attempts, backoff: Duration::from_millis(30) }
+}
+
+#[tokio::test]
+async fn policies_decide_whether_failed_saves_fail_the_request() {
+    let (client, _handle) = mock(3);
+    let sink = FlakySink::failing(usize::MAX);
+
+    let ignore = client.with_guarded_interceptor(GuardedInterceptor::new(sink.clone()).on_error(InterceptorErrorPolicy::Ignore));
+    assert_eq!(ignore.ask_raw("a".into()).await.unwrap(), "ok");
+    let warn = client.with_interceptor(sink.clone());
+    assert_eq!(warn.ask_raw("b".into()).await.unwrap(), "ok");
+
+    let strict = client.with_guarded_interceptor(GuardedInterceptor::new(sink).on_error(InterceptorErrorPolicy::FailRequest));
+    match strict.ask_raw("c".into()).await {
+        Err(AIError::Interceptor(message)) => assert_eq!(message, "audit store unavailable"),
+        other => panic!("expected an interceptor error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried_in_the_background() {
+    let (client, _handle) = mock(1);
+    let sink = FlakySink::failing(2);
+    let guard = GuardedInterceptor::new(sink.clone()).on_error(InterceptorErrorPolicy::FailRequest).with_retry(retry(8, 5));
+    let client = client.with_guarded_interceptor(guard.clone());
+
+    let id = CorrelationId::from("req-7");
+    correlation::scope(id.clone(), client.ask_raw("audited".into())).await.unwrap();
+    assert_eq!(guard.pending(), 1);
+    assert!(sink.saved.lock().unwrap().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(guard.pending(), 0);
+    assert_eq!(guard.dropped(), 0);
+    // Delivered once, under the query's correlation id
+    assert_eq!(*sink.saved.lock().unwrap(), [("audited".to_string(), Some(id))]);
+    assert!(client.flush().await.is_ok());
+}
+
+#[tokio::test]
+async fn exhausted_retries_are_reported_not_lost() {
+    let (client, _handle) = mock(2);
+    let guard = GuardedInterceptor::new(FlakySink::failing(usize::MAX))
+        .on_error(InterceptorErrorPolicy::FailRequest)
+        .with_retry(retry(1, 2));
+    let client = client.with_guarded_interceptor(guard.clone());
+
+    client.ask_raw("first".into()).await.unwrap();
+    // The queue is full, so the policy applies
+    assert!(matches!(client.ask_raw("second".into()).await, Err(AIError::Interceptor(m)) if m.contains("retry queue full")));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!((guard.pending(), guard.dropped()), (0, 1));
+    let flushed = client.flush().await.unwrap_err();
+    assert!(flushed.contains("1 dropped"), "{flushed}");
+    // Each drop is reported once
+    assert!(client.flush().await.is_ok());
+}
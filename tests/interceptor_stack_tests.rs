@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use semantic_query::audit::QueryRecord;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::{FlexibleClient, MockClient, MockResponse};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::interceptors::{Interceptor, InterceptorFilter, InterceptorStack, Redactor};
+use semantic_query::schema::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Answer {
+    value: i32,
+}
+
+/// Records what it was handed, tagged with its name, into a log shared with other sinks
+#[derive(Debug)]
+struct Sink {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Sink {
+    fn new(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Arc<Self> {
+        Arc::new(Self { name, log: log.clone() })
+    }
+
+    fn push(&self, entry: String) {
+        self.log.lock().unwrap().push(format!("{}: {entry}", self.name));
+    }
+}
+
+#[async_trait]
+impl Interceptor for Sink {
+    async fn save(&self, prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.push(format!("{prompt} -> {response}"));
+        Ok(())
+    }
+
+    async fn save_record(&self, record: &QueryRecord) -> Result<(), Box<dyn std::error::Error>> {
+        self.push(format!("record {}", record.operation));
+        Ok(())
+    }
+
+    async fn save_error(&self, prompt: &str, error: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.push(format!("{prompt} failed: {error}"));
+        Ok(())
+    }
+}
+
+fn entries(log: &Arc<Mutex<Vec<String>>>) -> Vec<String> {
+    std::mem::take(&mut *log.lock().unwrap())
+}
+
+#[tokio::test]
+async fn stacks_run_in_order_and_rewrite_for_later_layers() {
+    let log = Arc::default();
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success("token sk-live-123 issued".into()));
+    let stack = InterceptorStack::new()
+        .push(Sink::new("raw", &log))
+        .push(Redactor::new(|text| *text = text.replace("sk-live-123", "[key]")))
+        .push(Sink::new("audit", &log));
+    assert_eq!(stack.len(), 3);
+
+    let client = FlexibleClient::new(Box::new(mock)).with_interceptors(stack);
+    assert_eq!(client.ask_raw("issue sk-live-123".into()).await.unwrap(), "token sk-live-123 issued");
+    assert_eq!(entries(&log), [
+        "raw: issue sk-live-123 -> token sk-live-123 issued",
+        "audit: issue [key] -> token [key] issued",
+    ]);
+}
+
+#[tokio::test]
+async fn filters_pick_errors_streams_and_providers() {
+    let log = Arc::default();
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success("plain".into()));
+    handle.add_response(MockResponse::Error(AIError::Mock("overloaded".into())));
+    let stack = InterceptorStack::new()
+        .push_filtered(Sink::new("errors", &log), InterceptorFilter::default().only_errors())
+        .push_filtered(Sink::new("streams", &log), InterceptorFilter::default().only_streaming())
+        .push_filtered(Sink::new("claude", &log), InterceptorFilter::default().provider("claude"))
+        .push_filtered(Sink::new("mock", &log), InterceptorFilter::default().provider("mock"));
+    let client = FlexibleClient::new(Box::new(mock)).with_interceptors(stack);
+    assert_eq!(client.provider(), Some("mock"));
+
+    client.ask_raw("a".into()).await.unwrap();
+    assert!(client.ask_raw("b".into()).await.is_err());
+    assert_eq!(entries(&log), ["mock: a -> plain", "errors: b failed: Mock error: overloaded", "mock: b failed: Mock error: overloaded"]);
+
+    handle.add_response(MockResponse::Success("streamed reply".into()));
+    handle.stream_in_chunks(5);
+    let chunks: Vec<_> = client.stream_raw("c".into()).unwrap().collect().await;
+    assert!(chunks.iter().all(Result::is_ok));
+    assert_eq!(entries(&log), ["streams: c -> streamed reply", "mock: c -> streamed reply"]);
+}
+
+#[tokio::test]
+async fn intercepted_resolvers_see_every_query() {
+    let log = Arc::default();
+    let resolver = QueryResolver::new(ScriptedClient::Reply(r#"{"value": 4}"#.into()), RetryConfig::default())
+        .intercepted(InterceptorStack::new().push(Sink::new("all", &log)));
+
+    assert_eq!(resolver.query::<Answer>("four".into()).await.unwrap().first().unwrap().value, 4);
+    let log = entries(&log);
+    assert_eq!(log.len(), 2, "{log:?}");
+    assert!(log[0].starts_with("all: four") && log[0].ends_with(r#"-> {"value": 4}"#), "{log:?}");
+    assert_eq!(log[1], "all: record query");
+}
@@ -0,0 +1,59 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::core::ParsedResponse;
+use semantic_query::journal::Journal;
+use semantic_query::streaming::{build_parsed_stream, StreamItem, TextContent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Step {
+    n: u32,
+}
+
+#[test]
+fn tokens_rebuild_the_non_streaming_response() {
+    let raw = r#"Plan: {"n": 1} then {"n": 2} done"#;
+    let tokens: Vec<StreamItem<Step>> = raw
+        .as_bytes()
+        .chunks(5)
+        .map(|c| StreamItem::Token(String::from_utf8(c.to_vec()).unwrap()))
+        .collect();
+
+    let replayed = ParsedResponse::from_journal(tokens);
+    let direct: Vec<StreamItem<Step>> = build_parsed_stream(raw);
+    assert_eq!(replayed.data_count(), 2);
+    assert_eq!(replayed.items.len(), direct.len());
+    assert_eq!(replayed.text_content(), ParsedResponse::<Step>::from_raw(raw).text_content());
+}
+
+#[test]
+fn items_without_tokens_are_kept_in_order() {
+    let items = vec![
+        StreamItem::Text(TextContent { text: "hi".into(), block: None }),
+        StreamItem::Data(Step { n: 3 }),
+    ];
+    let response = ParsedResponse::from_journal(items);
+    assert_eq!(response.first().unwrap().n, 3);
+    assert_eq!(response.items.len(), 2);
+}
+
+#[tokio::test]
+async fn records_to_disk_and_replays() {
+    let path = std::env::temp_dir().join(format!("semantic-query-journal-{}.jsonl", std::process::id()));
+    let source = futures_util::stream::iter(vec![
+        Ok::<_, std::io::Error>(StreamItem::Token(r#"{"n":"#.to_string())),
+        Ok(StreamItem::Token("7}".to_string())),
+        Ok(StreamItem::Data(Step { n: 7 })),
+    ]);
+
+    let passed: Vec<_> = Journal::<Step>::record(source, &path).collect().await;
+    assert_eq!(passed.len(), 3);
+
+    let journal = Journal::<Step>::load(&path).await.unwrap();
+    assert_eq!(journal.entries.len(), 3);
+    assert_eq!(journal.to_parsed_response().first().unwrap().n, 7);
+
+    let replayed: Vec<StreamItem<Step>> = journal.replay(0.0).collect().await;
+    assert!(matches!(replayed[0], StreamItem::Token(ref t) if t == r#"{"n":"#));
+    let _ = std::fs::remove_file(path);
+}
@@ -80,3 +80,22 @@ tail {"id":3,"name":"gamma","meta":{"tags":[],"flags":{"kind":"A","fast":false}}
     assert_eq!(items[1].meta.tags[0], "z");
     match items[2].meta.flags { Flags::A { fast } => assert!(!fast), _ => panic!("expected Flags::A") }
 }
+
+#[cfg(feature = "json5")]
+#[test]
+fn relaxed_json_is_recovered_as_data() {
+    use semantic_query::json_utils::{deserialize_stream_map, ParsedOrUnknown};
+
+    let s = "Result: {x: 4, /* the answer */} and {'x': 5,}";
+    let items = deserialize_stream_map::<Item>(s);
+    assert!(matches!(items.as_slice(), [ParsedOrUnknown::Parsed(Item { x: 4 }), ParsedOrUnknown::Parsed(Item { x: 5 })]));
+}
+
+#[cfg(not(feature = "json5"))]
+#[test]
+fn relaxed_json_stays_unknown_without_json5() {
+    use semantic_query::json_utils::{deserialize_stream_map, ParsedOrUnknown};
+
+    let items = deserialize_stream_map::<Item>("{x: 4,}");
+    assert!(matches!(items.as_slice(), [ParsedOrUnknown::Unknown(_)]));
+}
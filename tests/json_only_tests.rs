@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting};
+use semantic_query::core::{ProsePolicy, QueryResolver, ResponseItem, ResponseMode, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Verdict {
+    approved: bool,
+}
+
+/// A mock with a JSON mode, answering `reply`
+fn mock(reply: &str) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::JsonMode]);
+    handle.add_json_response(reply);
+    (client, handle)
+}
+
+#[tokio::test]
+async fn query_typed_returns_data_in_json_mode() {
+    let (client, handle) = mock(r#"{"approved": true}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let verdict: Verdict = resolver.query_typed("Approve?".into()).await.unwrap();
+    assert_eq!(verdict, Verdict { approved: true });
+
+    let call = &handle.calls()[0];
+    assert!(call.settings.json_mode);
+    assert!(call.prompt.starts_with("Approve?"));
+    assert!(call.prompt.contains("Respond with JSON only"));
+}
+
+#[tokio::test]
+async fn code_fences_are_not_prose() {
+    let (client, _handle) = mock("```json\n{\"approved\": false}\n```");
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let verdict: Verdict = resolver.query_typed("Approve?".into()).await.unwrap();
+    assert!(!verdict.approved);
+}
+
+#[tokio::test]
+async fn prose_is_rejected_by_default() {
+    let (client, _handle) = mock(r#"Sure! {"approved": true} Hope that helps."#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    match resolver.query_typed::<Verdict>("Approve?".into()).await {
+        Err(QueryResolverError::UnexpectedProse(prose)) => {
+            assert!(prose.contains("Sure!"));
+            assert!(prose.contains("Hope that helps."));
+        }
+        other => panic!("expected UnexpectedProse, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn prose_can_be_ignored() {
+    let (client, _handle) = mock(r#"Sure! {"approved": true}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_response_mode(ResponseMode::JsonOnly)
+        .with_prose_policy(ProsePolicy::Ignore);
+    let response = resolver.query::<Verdict>("Approve?".into()).await.unwrap();
+    assert_eq!(response.items.len(), 1);
+    assert_eq!(response.first(), Some(&Verdict { approved: true }));
+}
+
+#[tokio::test]
+async fn mixed_mode_keeps_prose_and_json_mode_off() {
+    let (client, handle) = mock(r#"Sure! {"approved": true}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let response = resolver.query::<Verdict>("Approve?".into()).await.unwrap();
+    assert!(matches!(&response.items[0], ResponseItem::Text(text) if text.text.trim() == "Sure!"));
+    assert!(!handle.calls()[0].settings.json_mode);
+}
@@ -0,0 +1,99 @@
+use std::sync::{Arc, Mutex};
+
+use schemars::JsonSchema;
+use semantic_query::clients::mock::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::language::{Language, LanguageDetector, NgramDetector};
+use semantic_query::retry::{self, FailedAttempt, RetryPlan, RetryStrategy};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Summary {
+    title: String,
+    text: String,
+}
+
+/// A mock answering with `replies` in order
+fn scripted(replies: Vec<&str>) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(replies);
+    (client, handle)
+}
+
+const ENGLISH: &str = r#"{"title": "Quarterly review", "text": "Sales grew in every region, and the team expects the same next quarter."}"#;
+const GERMAN: &str = r#"{"title": "Quartalsbericht", "text": "Der Umsatz ist in allen Regionen gewachsen, und das Team erwartet im nächsten Quartal dasselbe."}"#;
+
+#[test]
+fn detector_tells_languages_apart() {
+    let detector = NgramDetector::default();
+    let cases = [
+        ("The weather was cold, so we stayed inside and read books all afternoon.", Language::English),
+        ("El tiempo estaba frío, así que nos quedamos en casa y leímos libros toda la tarde.", Language::Spanish),
+        ("Il faisait froid, alors nous sommes restés à la maison et avons lu des livres tout l'après-midi.", Language::French),
+        ("Das Wetter war kalt, also sind wir drinnen geblieben und haben den ganzen Nachmittag gelesen.", Language::German),
+        ("O tempo estava frio, então ficamos em casa e lemos livros durante toda a tarde.", Language::Portuguese),
+        ("Faceva freddo, quindi siamo rimasti in casa e abbiamo letto libri per tutto il pomeriggio.", Language::Italian),
+        ("Het weer was koud, dus we bleven binnen en lazen de hele middag boeken.", Language::Dutch),
+        ("Погода была холодной, поэтому мы остались дома и читали книги весь день.", Language::Russian),
+        ("Погода була холодною, тому ми залишилися вдома і читали книжки.", Language::Ukrainian),
+        ("天気が寒かったので、私たちは一日中家で本を読みました。", Language::Japanese),
+        ("天气很冷，所以我们整个下午都待在家里看书和喝茶。", Language::Chinese),
+        ("날씨가 추워서 우리는 오후 내내 집에서 책을 읽었습니다.", Language::Korean),
+    ];
+    for (text, language) in cases {
+        assert_eq!(detector.detect(text), Some(language), "{text}");
+    }
+    assert_eq!(detector.detect("OK, done."), None);
+    assert_eq!(NgramDetector::default().with_min_letters(4).detect("Vielen Dank für die Antwort"), Some(Language::German));
+}
+
+#[tokio::test]
+async fn wrong_language_is_corrected() {
+    let (client, handle) = scripted(vec![ENGLISH, GERMAN]);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_expected_language(Language::German);
+    assert_eq!(resolver.expected_language(), Some(Language::German));
+
+    let summary = resolver.query::<Summary>("Fasse den Quartalsbericht zusammen".into()).await.unwrap();
+    assert_eq!(summary.first().unwrap().title, "Quartalsbericht");
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("Answer again in German"), "{}", prompts[1]);
+
+    // Once retries run out, the last answer is returned as it is
+    let (client, handle) = scripted(vec![ENGLISH, ENGLISH]);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_expected_language(Language::German);
+    let summary = resolver.query::<Summary>("Fasse zusammen".into()).await.unwrap();
+    assert_eq!(summary.first().unwrap().title, "Quarterly review");
+    assert_eq!(handle.prompts().len(), 2);
+
+    // Answers in the expected language are not retried
+    let (client, handle) = scripted(vec![GERMAN]);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_expected_language(Language::German);
+    resolver.query::<Summary>("Fasse zusammen".into()).await.unwrap();
+    assert_eq!(handle.prompts().len(), 1);
+}
+
+/// Restates the prompt with the language error, recording it
+#[derive(Debug, Default)]
+struct Restate {
+    errors: Mutex<Vec<String>>,
+}
+
+impl RetryStrategy for Restate {
+    fn next_attempt(&self, failed: &FailedAttempt<'_>) -> Option<RetryPlan> {
+        self.errors.lock().unwrap().push(failed.error.to_string());
+        Some(RetryPlan::prompt(format!("{} ({})", failed.prompt, failed.error)))
+    }
+}
+
+#[tokio::test]
+async fn language_class_takes_strategies() {
+    let strategy = Arc::new(Restate::default());
+    let (client, handle) = scripted(vec![ENGLISH, GERMAN]);
+    let config = RetryConfig::default().with_strategy(retry::LANGUAGE, strategy.clone());
+    let resolver = QueryResolver::new(client, config).with_expected_language(Language::German);
+
+    resolver.query::<Summary>("Fasse zusammen".into()).await.unwrap();
+    assert_eq!(*strategy.errors.lock().unwrap(), ["the answer is in English, not German"]);
+    assert!(handle.prompts()[1].contains("the answer is in English, not German"));
+}
@@ -0,0 +1,43 @@
+#![allow(deprecated)]
+
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::{DataExtractionError, QueryResolverError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Analysis {
+    topic: String,
+    confidence: f64,
+}
+
+#[tokio::test]
+async fn query_with_schema_returns_first_item() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_response(r#"Here you go: {"topic": "async", "confidence": 0.8} and {"topic": "sync", "confidence": 0.1}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let analysis: Analysis = resolver.query_with_schema("Analyze".into()).await.unwrap();
+    assert_eq!(analysis, Analysis { topic: "async".into(), confidence: 0.8 });
+}
+
+#[tokio::test]
+async fn query_deserialized_returns_first_item() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_response(r#"{"topic": "async", "confidence": 0.8}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let analysis: Analysis = resolver.query_deserialized("Analyze".into()).await.unwrap();
+    assert_eq!(analysis.topic, "async");
+}
+
+#[tokio::test]
+async fn legacy_methods_report_missing_data() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_response("No structured answer, sorry.");
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let err = resolver.query_with_schema::<Analysis>("Analyze".into()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::DataExtraction(DataExtractionError::NoDataFound)));
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting, MockSettings};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::retry::{LengthRecovery, CONTINUE_INSTRUCTION, LENGTH};
+use semantic_query::streaming::FinishReason;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Person {
+    name: String,
+    age: u32,
+}
+
+/// A mock requesting up to `max_tokens`, raising the limit on request if `resizable`,
+/// that answers with `replies`, each reported as cut off (`Length`) or complete
+fn truncating(max_tokens: u32, resizable: bool, replies: Vec<(&str, bool)>) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    if resizable {
+        handle.accept(&[MockSetting::MaxTokens]);
+    }
+    for (reply, cut_off) in replies {
+        if cut_off {
+            handle.add_truncated_response(reply);
+        } else {
+            handle.add_json_response(reply);
+        }
+    }
+    (client.with_settings(MockSettings { max_tokens: Some(max_tokens), ..MockSettings::default() }), handle)
+}
+
+const CUT_OFF: &str = r#"Here you go: {"name": "Ada", "ag"#;
+const COMPLETE: &str = r#"Here you go: {"name": "Ada", "age": 36}"#;
+
+#[tokio::test]
+async fn cut_off_replies_are_retried_with_a_higher_limit() {
+    let (client, handle) = truncating(100, true, vec![(CUT_OFF, true), (CUT_OFF, true), (COMPLETE, false)]);
+    let mut config = RetryConfig::default().with_length_recovery(LengthRecovery::GrowMaxTokens { factor: 4, cap: 1000 });
+    config.max_retries.insert(LENGTH.to_string(), 2);
+    let resolver = QueryResolver::new(client, config);
+
+    let response = resolver.query_mixed::<Person>("who".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Person { name: "Ada".into(), age: 36 }));
+
+    let limits: Vec<Option<u32>> = handle.calls().into_iter().map(|call| call.settings.max_tokens).collect();
+    assert_eq!(limits, vec![Some(100), Some(400), Some(1000)]);
+}
+
+#[tokio::test]
+async fn continuations_are_stitched_before_parsing() {
+    let (client, handle) = truncating(100, true, vec![(CUT_OFF, true), (r#"e": 36}"#, false)]);
+    let config = RetryConfig::default().with_length_recovery(LengthRecovery::Continue);
+    let resolver = QueryResolver::new(client, config);
+
+    let response = resolver.query_mixed::<Person>("who".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Person { name: "Ada".into(), age: 36 }));
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains(CUT_OFF) && prompts[1].contains(CONTINUE_INSTRUCTION));
+}
+
+#[tokio::test]
+async fn fixed_limits_fall_back_to_continuing() {
+    let (client, handle) = truncating(1000, false, vec![(CUT_OFF, true), (r#"e": 36}"#, false)]);
+    let config = RetryConfig::default().with_length_recovery(LengthRecovery::GrowMaxTokens { factor: 2, cap: 4000 });
+    let resolver = QueryResolver::new(client, config);
+
+    let response = resolver.query_mixed::<Person>("who".into()).await.unwrap();
+    assert_eq!(response.data_count(), 1);
+    assert!(handle.prompts()[1].contains(CONTINUE_INSTRUCTION));
+}
+
+#[tokio::test]
+async fn recovery_is_opt_in_and_keyed_off_the_finish_reason() {
+    let (client, handle) = truncating(100, true, vec![(CUT_OFF, true)]);
+    let response = QueryResolver::new(client, RetryConfig::default())
+        .query_mixed::<Person>("who".into()).await.unwrap();
+    assert!(!response.has_data());
+    assert_eq!(handle.prompts().len(), 1);
+
+    // Complete replies without data are not length failures
+    let (client, handle) = truncating(100, true, vec![("I don't know.", false)]);
+    let config = RetryConfig::default().with_length_recovery(LengthRecovery::Continue);
+    let response = QueryResolver::new(client, config).query_mixed::<Person>("who".into()).await.unwrap();
+    assert!(!response.has_data());
+    assert_eq!(handle.prompts().len(), 1);
+}
+
+#[test]
+fn provider_reasons_map_to_finish_reasons() {
+    assert_eq!(FinishReason::from_provider("length"), Some(FinishReason::Length));
+    assert_eq!(FinishReason::from_provider("max_tokens"), Some(FinishReason::Length));
+    assert_eq!(FinishReason::from_provider("end_turn"), Some(FinishReason::Stop));
+    assert_eq!(FinishReason::from_provider("tool_use"), Some(FinishReason::ToolCalls));
+    assert_eq!(FinishReason::from_provider("mystery"), None);
+}
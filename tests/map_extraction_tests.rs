@@ -0,0 +1,65 @@
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{ExtractionPolicy, KeyConflict, QueryResolver, RetryConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Price {
+    amount: f64,
+    currency: String,
+}
+
+#[tokio::test]
+async fn extracts_hashmap_targets() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"Here are the prices: {"apple": {"amount": 1.5, "currency": "USD"}, "pear": {"amount": 2.0, "currency": "EUR"}}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let response = resolver.query::<HashMap<String, Price>>("prices".to_string()).await.unwrap();
+    let prices = response.first_required().unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices["pear"].currency, "EUR");
+}
+
+#[tokio::test]
+async fn scalar_valued_btreemaps_are_supported() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"a": 1, "b": 2}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let response = resolver.query::<BTreeMap<String, u32>>("counts".to_string()).await.unwrap();
+    assert_eq!(response.first_required().unwrap(), BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]));
+}
+
+#[tokio::test]
+async fn keep_all_returns_each_emitted_map() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"First {"a": 1} then {"b": 2, "a": 3}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let response = resolver.query::<HashMap<String, u32>>("counts".to_string()).await.unwrap();
+    assert_eq!(response.data_count(), 2);
+}
+
+#[tokio::test]
+async fn merge_maps_policy_unions_emitted_maps() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![
+        r#"First {"a": 1} then {"b": 2, "a": 3}"#,
+        r#"First {"a": 1} then {"b": 2, "a": 3}"#,
+    ]);
+
+    let last_wins = QueryResolver::new(client.clone(), RetryConfig::default())
+        .with_extraction_policy(ExtractionPolicy::MergeMaps { on_conflict: KeyConflict::KeepLast });
+    let response = last_wins.query::<HashMap<String, u32>>("counts".to_string()).await.unwrap();
+    assert_eq!(response.data_count(), 1);
+    assert_eq!(response.first_required().unwrap(), HashMap::from([("a".to_string(), 3), ("b".to_string(), 2)]));
+    // Text around the maps is preserved
+    assert!(response.text_content().contains("First"));
+
+    let first_wins = QueryResolver::new(client, RetryConfig::default())
+        .with_extraction_policy(ExtractionPolicy::MergeMaps { on_conflict: KeyConflict::KeepFirst });
+    let response = first_wins.query::<HashMap<String, u32>>("counts".to_string()).await.unwrap();
+    assert_eq!(response.first_required().unwrap()["a"], 1);
+}
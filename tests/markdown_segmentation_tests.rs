@@ -0,0 +1,103 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, ResponseItem, RetryConfig};
+use semantic_query::streaming::markdown::{segment_markdown, MarkdownBlock, MarkdownSegmenter};
+use semantic_query::streaming::{StreamItem, TextContent, TextFidelity};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Note {
+    text: String,
+}
+
+const ANSWER: &str = "## Findings\nTwo issues stand out,\nboth minor.\n\n- first\n  still first\n- second\n1. numbered\n\n```rust\nfn main() {}\n\n// done\n```\n> quoted\n> twice\n\n";
+
+fn kinds(blocks: &[TextContent]) -> Vec<Option<MarkdownBlock>> {
+    blocks.iter().map(|b| b.block.clone()).collect()
+}
+
+#[test]
+fn text_splits_into_tagged_blocks() {
+    let blocks = segment_markdown(ANSWER, TextFidelity::Trimmed);
+    assert_eq!(kinds(&blocks), [
+        Some(MarkdownBlock::Heading { level: 2 }),
+        Some(MarkdownBlock::Paragraph),
+        Some(MarkdownBlock::ListItem { ordered: false }),
+        Some(MarkdownBlock::ListItem { ordered: false }),
+        Some(MarkdownBlock::ListItem { ordered: true }),
+        Some(MarkdownBlock::CodeBlock { language: Some("rust".into()) }),
+        Some(MarkdownBlock::Quote),
+    ]);
+    assert_eq!(blocks[1].text, "Two issues stand out,\nboth minor.");
+    assert_eq!(blocks[2].text, "- first\n  still first");
+    assert_eq!(blocks[5].text, "```rust\nfn main() {}\n\n// done\n```");
+
+    let exact = segment_markdown(ANSWER, TextFidelity::Exact);
+    assert_eq!(kinds(&exact), kinds(&blocks));
+    assert_eq!(exact.iter().map(|b| b.text.as_str()).collect::<String>(), ANSWER);
+
+    assert_eq!(serde_json::to_value(&blocks[0]).unwrap(), json!({"text": "## Findings", "block": {"kind": "heading", "level": 2}}));
+    assert_eq!(serde_json::to_value(TextContent { text: "hi".into(), block: None }).unwrap(), json!({"text": "hi"}));
+}
+
+#[test]
+fn segmenter_holds_blocks_until_complete() {
+    let mut segmenter = MarkdownSegmenter::new(TextFidelity::Exact);
+    let mut out = Vec::new();
+    for piece in ANSWER.as_bytes().chunks(3) {
+        let blocks = segmenter.push(std::str::from_utf8(piece).unwrap());
+        // A block only comes out once it can no longer grow
+        for block in &blocks {
+            assert!(ANSWER[out.iter().map(|b: &TextContent| b.text.len()).sum::<usize>()..].starts_with(&block.text));
+            out.push(block.clone());
+        }
+    }
+    out.extend(segmenter.flush());
+    assert_eq!(out, segment_markdown(ANSWER, TextFidelity::Exact));
+
+    let mut open = MarkdownSegmenter::new(TextFidelity::Trimmed);
+    assert!(open.push("```json\n{\"a\": 1}\n\n").is_empty());
+    assert_eq!(kinds(&open.flush()), [Some(MarkdownBlock::CodeBlock { language: Some("json".into()) })]);
+    // Text after a flush continues the open code block up to its fence
+    let after = open.push("```\nDone.\n\n");
+    assert_eq!(kinds(&after), [Some(MarkdownBlock::CodeBlock { language: Some("json".into()) }), Some(MarkdownBlock::Paragraph)]);
+}
+
+#[tokio::test]
+async fn resolver_tags_blocks_in_queries_and_streams() {
+    let raw = "# Notes\nHere is one:\n{\"text\": \"a\"}\n- and a list\n- of two";
+    let resolver = QueryResolver::new(ScriptedClient::Reply(raw.into()), RetryConfig::default()).with_markdown_blocks(true);
+    let response = resolver.query_mixed::<Note>("notes".into()).await.unwrap();
+    let shape: Vec<_> = response.items.iter().map(|item| match item {
+        ResponseItem::Text(t) => (t.text.as_str(), t.block.clone()),
+        ResponseItem::Data { .. } => ("<data>", None),
+    }).collect();
+    assert_eq!(shape, [
+        ("# Notes", Some(MarkdownBlock::Heading { level: 1 })),
+        ("Here is one:", Some(MarkdownBlock::Paragraph)),
+        ("<data>", None),
+        ("- and a list", Some(MarkdownBlock::ListItem { ordered: false })),
+        ("- of two", Some(MarkdownBlock::ListItem { ordered: false })),
+    ]);
+
+    let chunks: Vec<Vec<u8>> = ["## Plan\n\nStep one", " is short.\n- a", "\n- b\n\n", "{\"text\": \"b\"}", "\n\nThat's all."]
+        .iter().map(|c| c.as_bytes().to_vec()).collect();
+    let resolver = QueryResolver::new(ScriptedClient::Stream(chunks), RetryConfig::default()).with_markdown_blocks(true);
+    let items: Vec<_> = resolver.stream_query::<Note>("plan".into()).await.unwrap().map(Result::unwrap).collect().await;
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Token(_))));
+    let shape: Vec<_> = items.iter().filter_map(|item| match item {
+        StreamItem::Text(t) => Some((t.text.as_str(), t.block.clone())),
+        StreamItem::Data(_) => Some(("<data>", None)),
+        _ => None,
+    }).collect();
+    assert_eq!(shape, [
+        ("## Plan", Some(MarkdownBlock::Heading { level: 2 })),
+        ("Step one is short.", Some(MarkdownBlock::Paragraph)),
+        ("- a", Some(MarkdownBlock::ListItem { ordered: false })),
+        ("- b", Some(MarkdownBlock::ListItem { ordered: false })),
+        ("<data>", None),
+        ("That's all.", Some(MarkdownBlock::Paragraph)),
+    ]);
+}
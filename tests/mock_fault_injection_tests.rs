@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use semantic_query::clients::{FaultConfig, FaultStats, Latency, MockClient, MockSetting, MockSettings};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::error::{AIError, QueryResolverError};
+use semantic_query::schema::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Answer {
+    value: i32,
+}
+
+const REPLY: &str = r#"{"value": 42, "note": "the answer"}"#;
+
+/// Outcome of each of `calls` asks, with `REPLY` queued for every one
+async fn outcomes(faults: FaultConfig, calls: usize) -> (Vec<bool>, FaultStats) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![REPLY; calls]);
+    handle.inject_faults(faults);
+    let mut results = Vec::new();
+    for _ in 0..calls {
+        results.push(client.ask_raw("q".to_string()).await.is_ok());
+    }
+    (results, handle.fault_stats())
+}
+
+#[tokio::test]
+async fn seeded_faults_replay_exactly() {
+    let faults = FaultConfig::new().with_error_rate(0.5).with_seed(4);
+    let (first, stats) = outcomes(faults.clone(), 16).await;
+    assert_eq!(outcomes(faults, 16).await.0, first);
+    assert!(first.contains(&true) && first.contains(&false));
+    assert_eq!(stats.calls, 16);
+    assert_eq!(stats.errors, first.iter().filter(|ok| !**ok).count());
+}
+
+#[tokio::test]
+async fn injected_errors_keep_the_queue_for_retries() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(REPLY);
+    handle.inject_faults(FaultConfig::new().with_error_rate(1.0));
+
+    let err = client.ask_raw("q".to_string()).await.unwrap_err();
+    assert!(err.is_retryable());
+    assert_eq!(handle.remaining_count(), 1);
+
+    handle.clear_faults();
+    assert_eq!(client.ask_raw("q".to_string()).await.unwrap(), REPLY);
+}
+
+#[tokio::test]
+async fn resolver_surfaces_the_injected_error() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(REPLY);
+    handle.inject_faults(FaultConfig::new().with_error_rate(1.0).with_error(AIError::Mock("down".to_string())));
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let err = resolver.query::<Answer>("q".to_string()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::Ai(AIError::Mock(m)) if m == "down"));
+    assert_eq!(handle.fault_stats(), FaultStats { calls: 1, errors: 1, ..FaultStats::default() });
+
+    handle.clear_faults();
+    assert_eq!(resolver.query::<Answer>("q".to_string()).await.unwrap().first_required().unwrap().value, 42);
+}
+
+#[tokio::test]
+async fn truncated_and_malformed_replies_break_the_json() {
+    for seed in 0..6 {
+        let (client, handle) = MockClient::new();
+        handle.add_json_responses(vec![REPLY, REPLY]);
+
+        handle.inject_faults(FaultConfig::new().with_truncation(1.0).with_seed(seed));
+        let truncated = client.ask_raw("q".to_string()).await.unwrap();
+        assert!(truncated.len() < REPLY.len() && REPLY.starts_with(&truncated));
+
+        handle.inject_faults(FaultConfig::new().with_malformed_json(1.0).with_seed(seed));
+        let malformed = client.ask_raw("q".to_string()).await.unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&malformed).is_err(), "{malformed}");
+        assert_eq!(handle.fault_stats().malformed, 1);
+    }
+}
+
+#[tokio::test]
+async fn latency_delays_each_call() {
+    let faults = FaultConfig::new().with_latency(Latency::Fixed(Duration::from_millis(30)));
+    let started = Instant::now();
+    outcomes(faults, 2).await;
+    assert!(started.elapsed() >= Duration::from_millis(60));
+}
+
+#[tokio::test]
+async fn streaming_is_opt_in_and_can_disconnect() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![REPLY, REPLY]);
+    assert!(client.stream_raw("q".to_string()).is_none());
+
+    handle.stream_in_chunks(8);
+    let chunks: Vec<_> = client.stream_raw("q".to_string()).unwrap().collect().await;
+    let body: String = chunks.iter().map(|c| String::from_utf8(c.as_ref().unwrap().to_vec()).unwrap()).collect();
+    assert_eq!(chunks.len(), REPLY.len().div_ceil(8) + 1);
+    assert!(body.ends_with("data: [DONE]\n\n"));
+
+    handle.inject_faults(FaultConfig::new().with_disconnects(1.0));
+    let chunks: Vec<_> = client.stream_raw("q".to_string()).unwrap().collect().await;
+    assert!(chunks.len() < REPLY.len().div_ceil(8) + 1);
+    assert!(chunks.last().unwrap().is_err());
+    assert_eq!(handle.fault_stats().disconnects, 1);
+}
+
+#[tokio::test]
+async fn calls_are_recorded_with_the_settings_they_were_made_with() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![REPLY, REPLY]);
+    assert!(client.with_user("u-1").is_none());
+
+    handle.accept(&[MockSetting::User, MockSetting::StopSequences, MockSetting::Prefill]);
+    let configured = client.with_user("u-1").unwrap().with_stop_sequences(&[", \"note\"".to_string()]).unwrap();
+    assert_eq!(configured.ask_raw("first".to_string()).await.unwrap(), r#"{"value": 42"#);
+    let prefilled = client.with_prefill("Answer: ").unwrap();
+    assert_eq!(prefilled.ask_raw("second".to_string()).await.unwrap(), format!("Answer: {REPLY}"));
+    assert!(client.capabilities().supports_prefill && !client.capabilities().supports_json_mode);
+
+    let calls = handle.calls();
+    assert_eq!(handle.prompts(), ["first", "second"]);
+    assert_eq!(calls[0].settings.user.as_deref(), Some("u-1"));
+    assert_eq!(calls[1].settings, MockSettings { prefill: Some("Answer: ".into()), ..MockSettings::default() });
+}
@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use semantic_query::clients::{FlexibleClient, MockClient, MockHandle, MockSetting, MockSettings};
+use semantic_query::core::{ChatMessage, LowLevelClient};
+use semantic_query::interceptors::Interceptor;
+use semantic_query::request::{ModelRequest, ResponseFormat, ToolSpec};
+use serde_json::json;
+
+/// A mock supporting temperature and JSON mode (but not seeds or max tokens)
+fn client() -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::Temperature, MockSetting::JsonMode]);
+    handle.add_json_response("{\"ok\": true}");
+    (client, handle)
+}
+
+/// Records the requests passed to `save_request`
+#[derive(Debug, Default)]
+struct RequestLog(Mutex<Vec<(ModelRequest, String)>>);
+
+#[async_trait]
+impl Interceptor for RequestLog {
+    async fn save(&self, _prompt: &str, _response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        unreachable!("save_request is overridden")
+    }
+
+    async fn save_request(&self, request: &ModelRequest, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push((request.clone(), response.to_string()));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn default_mapping_applies_supported_params() {
+    let (client, handle) = client();
+    let request = ModelRequest::new(vec![ChatMessage::system("Be terse"), ChatMessage::user("Hi")])
+        .with_temperature(0.3)
+        .with_seed(7)
+        .with_response_format(ResponseFormat::JsonObject);
+    client.ask_request(request).await.unwrap();
+
+    let call = &handle.calls()[0];
+    assert_eq!(call.prompt, "Be terse\n\nUser: Hi");
+    assert_eq!(call.settings, MockSettings { temperature: Some(0.3), json_mode: true, ..MockSettings::default() });
+}
+
+#[tokio::test]
+async fn text_requests_leave_the_client_unchanged() {
+    let (client, handle) = client();
+    client.ask_request(ModelRequest::user("Hi")).await.unwrap();
+    let call = &handle.calls()[0];
+    assert_eq!((call.prompt.as_str(), &call.settings), ("Hi", &MockSettings::default()));
+}
+
+#[tokio::test]
+async fn flexible_client_hands_the_request_to_interceptors() {
+    let (inner, handle) = client();
+    let log = Arc::new(RequestLog::default());
+    let client = FlexibleClient::new(Box::new(inner)).with_interceptor(log.clone());
+    let request = ModelRequest::user("Hi").with_temperature(0.0);
+    client.ask_request(request.clone()).await.unwrap();
+
+    assert_eq!(handle.calls()[0].settings.temperature, Some(0.0));
+    assert_eq!(log.0.lock().unwrap().as_slice(), [(request, "{\"ok\": true}".to_string())]);
+}
+
+#[test]
+fn requests_round_trip_through_json() {
+    let request = ModelRequest::user("Weather?")
+        .with_max_tokens(256)
+        .with_tool(ToolSpec::new("get_weather", "Current weather", json!({ "type": "object" })))
+        .with_response_format(ResponseFormat::JsonSchema { name: "weather".into(), schema: json!({ "type": "object" }) });
+    let value = serde_json::to_value(&request).unwrap();
+
+    assert_eq!(value["params"], json!({ "max_tokens": 256 }));
+    assert_eq!(value["response_format"]["type"], "json_schema");
+    assert_eq!(serde_json::from_value::<ModelRequest>(value).unwrap(), request);
+
+    let minimal: ModelRequest = serde_json::from_value(json!({ "messages": [{ "role": "user", "content": "Hi" }] })).unwrap();
+    assert_eq!(minimal, ModelRequest::user("Hi"));
+}
@@ -0,0 +1,65 @@
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::moderation::{KeywordModerator, ModerationAction};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct Contact {
+    name: String,
+    phone: String,
+}
+
+#[tokio::test]
+async fn redacts_flagged_spans_before_parsing() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"name":"Ada","phone":"555-0100"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_moderator(Arc::new(KeywordModerator::new(["555-0100"])));
+
+    let response = resolver.query_mixed::<Contact>("who?".to_string()).await.unwrap();
+    assert_eq!(response.first_required().unwrap().phone, "[REDACTED]");
+
+    let safety = response.safety.expect("moderator attaches a report");
+    assert!(safety.flagged());
+    assert!(safety.redacted());
+    assert_eq!(safety.response.unwrap().action, ModerationAction::Redact(vec!["555-0100".to_string()]));
+}
+
+#[tokio::test]
+async fn blocking_moderator_fails_the_query() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"name":"Ada","phone":"forbidden"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_moderator(Arc::new(KeywordModerator::new(["FORBIDDEN"]).blocking()));
+
+    let err = resolver.query_mixed::<Contact>("who?".to_string()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::ModerationBlocked(v) if v.categories == ["FORBIDDEN"]));
+}
+
+#[tokio::test]
+async fn prompt_moderation_blocks_before_sending() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"name":"Ada","phone":"1"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_moderator(Arc::new(KeywordModerator::new(["secret"]).blocking()))
+        .with_prompt_moderation(true);
+
+    let err = resolver.query_mixed::<Contact>("tell me the secret".to_string()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::ModerationBlocked(_)));
+    // The response was never consumed
+    assert_eq!(handle.remaining_count(), 1);
+}
+
+#[tokio::test]
+async fn clean_responses_carry_an_unflagged_report() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"name":"Ada","phone":"1"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_moderator(Arc::new(KeywordModerator::new(["secret"]).annotating()));
+
+    let response = resolver.query_mixed::<Contact>("who?".to_string()).await.unwrap();
+    assert!(!response.safety.unwrap().flagged());
+}
@@ -0,0 +1,60 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::normalize::{Normalizers, StraightenQuotes, StripAnsi, StripBom, TextNormalizer};
+use semantic_query::streaming::StreamItem;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+#[test]
+fn ansi_sequences_are_stripped() {
+    let colored = "\x1b[1;32m{\"name\": \"Ada\"}\x1b[0m\x1b]0;title\x07 done";
+    assert_eq!(StripAnsi.normalize(colored), "{\"name\": \"Ada\"} done");
+    assert!(matches!(StripAnsi.normalize("plain"), std::borrow::Cow::Borrowed(_)));
+}
+
+#[test]
+fn curly_quotes_are_straightened_only_when_every_quote_is_curly() {
+    assert_eq!(StraightenQuotes.normalize("{“name”: “Ada”}"), r#"{"name": "Ada"}"#);
+    let content = r#"{"quote": "She said “hi”"}"#;
+    assert_eq!(StraightenQuotes.normalize(content), content);
+}
+
+#[test]
+fn normalizers_run_in_order() {
+    let normalizers = Normalizers::new().with(StripBom).with(StripAnsi);
+    assert_eq!(normalizers.apply("\u{feff}\x1b[31m{}\x1b[0m"), "{}");
+    assert!(Normalizers::new().is_empty());
+}
+
+#[tokio::test]
+async fn replies_are_normalized_before_extraction() {
+    let client = ScriptedClient::Reply("\u{feff}\x1b[36m{“name”: “Ada”}\x1b[0m".into());
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_normalizers(Normalizers::standard());
+
+    let response = resolver.query::<Contact>("Who?".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Contact { name: "Ada".into() }));
+}
+
+#[tokio::test]
+async fn streamed_chunks_are_normalized() {
+    let chunks = vec![b"\x1b[36m{".to_vec(), b"\"name\": \"Ada\"}".to_vec(), b"\x1b[0m".to_vec()];
+    let resolver = QueryResolver::new(ScriptedClient::Stream(chunks), RetryConfig::default())
+        .with_normalizers(Normalizers::new().with(StripAnsi));
+
+    let items: Vec<_> = resolver.stream_query::<Contact>("Who?".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let tokens: String = items.iter().filter_map(|item| match item {
+        StreamItem::Token(token) => Some(token.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(tokens, r#"{"name": "Ada"}"#);
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Data(c) if c.name == "Ada")));
+}
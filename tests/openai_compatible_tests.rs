@@ -0,0 +1,185 @@
+#![cfg(feature = "openai-compatible")]
+
+use futures_util::StreamExt;
+use semantic_query::clients::flexible::ClientType;
+use semantic_query::clients::{CompatClient, CompatConfig};
+use semantic_query::config::load_from_path;
+use semantic_query::core::{LowLevelClient, QueryResolver, ResponseItem, RetryConfig};
+use semantic_query::streaming::StreamItem;
+use std::str::FromStr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Answer {
+    value: u32,
+}
+
+/// Serve one request with `body` as the response, returning the raw request text
+async fn serve_once(content_type: &'static str, body: String) -> (String, tokio::task::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let length = text.lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if request.len() >= header_end + 4 + length { break; }
+            }
+            if n == 0 { break; }
+        }
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    });
+    (base_url, handle)
+}
+
+#[tokio::test]
+async fn one_shot_uses_custom_path_and_auth_header() {
+    let body = serde_json::json!({ "choices": [{ "message": { "content": "{\"value\": 42}" } }] }).to_string();
+    let (base_url, server) = serve_once("application/json", body).await;
+    let config = CompatConfig::new(base_url, "llama-3.1-8b")
+        .with_path("/openai/v1/chat/completions")
+        .with_auth_header("api-key")
+        .with_api_key("secret");
+
+    let resolver = QueryResolver::new(CompatClient::new(config), RetryConfig::default());
+    let answer = resolver.query::<Answer>("answer".into()).await.unwrap();
+    assert_eq!(answer.first().unwrap().value, 42);
+
+    let request = server.await.unwrap();
+    assert!(request.starts_with("POST /openai/v1/chat/completions "));
+    assert!(request.to_ascii_lowercase().contains("api-key: secret"));
+    assert!(request.contains("\"model\":\"llama-3.1-8b\""));
+}
+
+#[tokio::test]
+async fn streams_sse_deltas() {
+    let body = [r#"{"value": "#, "7}"]
+        .iter()
+        .map(|t| format!("data: {}\n\n", serde_json::json!({ "choices": [{ "delta": { "content": t } }] })))
+        .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+        .collect::<String>();
+    let (base_url, server) = serve_once("text/event-stream", body).await;
+    let client = CompatClient::new(CompatConfig::new(base_url, "local").with_api_key("key"));
+
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let items: Vec<_> = resolver.stream_query::<Answer>("answer".into()).await.unwrap().collect().await;
+    assert!(items.iter().any(|i| matches!(i, Ok(StreamItem::Data(a)) if a.value == 7)));
+
+    let request = server.await.unwrap();
+    assert!(request.to_ascii_lowercase().contains("authorization: bearer key"));
+    assert!(request.contains("\"stream\":true"));
+}
+
+#[tokio::test]
+async fn field_confidence_requests_and_reads_logprobs() {
+    let tokens = [("{\"value\": ", -0.01), ("4", -0.5), ("2", -0.3), ("}", 0.0)];
+    let body = serde_json::json!({ "choices": [{
+        "message": { "content": "{\"value\": 42}" },
+        "logprobs": { "content": tokens.iter().map(|(token, logprob)| serde_json::json!({ "token": token, "logprob": logprob, "top_logprobs": [] })).collect::<Vec<_>>() }
+    }] }).to_string();
+    let (base_url, server) = serve_once("application/json", body).await;
+
+    let resolver = QueryResolver::new(CompatClient::new(CompatConfig::new(base_url, "local")), RetryConfig::default())
+        .with_field_confidence(true);
+    let answer = resolver.query::<Answer>("answer".into()).await.unwrap();
+    let Some(ResponseItem::Data { confidence_map: Some(confidence), .. }) = answer.items.first() else { panic!("{answer:?}") };
+    assert!((confidence["/value"] - (-0.4f64).exp()).abs() < 1e-9);
+
+    assert!(server.await.unwrap().contains("\"logprobs\":true"));
+}
+
+#[test]
+fn config_file_selects_compat_client() {
+    let path = std::env::temp_dir().join(format!("sq_{}_compat.toml", std::process::id()));
+    std::fs::write(&path, r#"
+[openai_compatible]
+endpoint = "https://api.groq.com/openai"
+model = "llama-3.1-70b-versatile"
+"#).unwrap();
+    let config = load_from_path(&path).unwrap();
+    assert!(matches!(ClientType::from_config(&config), ClientType::OpenAICompatible));
+
+    let compat = config.compat_config().unwrap();
+    assert_eq!(compat.base_url, "https://api.groq.com/openai");
+    assert_eq!(compat.model, "llama-3.1-70b-versatile");
+    assert!(ClientType::OpenAICompatible.build_with(&config).is_ok());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn parses_client_type_names() {
+    assert!(matches!(ClientType::from_str("compat"), Ok(ClientType::OpenAICompatible)));
+    let display = ClientType::OpenAICompatible.to_string().to_lowercase();
+    assert!(matches!(ClientType::from_str(&display), Ok(ClientType::OpenAICompatible)));
+}
+
+#[test]
+fn clones_keep_configuration() {
+    let client = CompatClient::new(CompatConfig::new("http://localhost:1234/", "m"));
+    assert!(client.with_temperature(0.9).is_some());
+    let _boxed = client.clone_box();
+    assert_eq!(client.config().base_url, "http://localhost:1234/");
+}
+
+#[tokio::test]
+async fn requests_carry_the_correlation_id() {
+    use semantic_query::correlation::{self, CorrelationId};
+
+    let body = serde_json::json!({ "choices": [{ "message": { "content": "{\"value\": 1}" } }] }).to_string();
+    let (base_url, server) = serve_once("application/json", body).await;
+    let resolver = QueryResolver::new(CompatClient::new(CompatConfig::new(base_url, "llama-3.1-8b")), RetryConfig::default());
+
+    correlation::scope(CorrelationId::from("trace-42"), resolver.query::<Answer>("answer".into())).await.unwrap();
+
+    let request = server.await.unwrap().to_ascii_lowercase();
+    assert!(request.contains("x-correlation-id: trace-42"));
+}
+
+#[tokio::test]
+async fn content_filter_is_a_refusal() {
+    use semantic_query::error::AIError;
+
+    let body = serde_json::json!({ "choices": [{ "message": { "content": null }, "finish_reason": "content_filter" }] }).to_string();
+    let (base_url, _server) = serve_once("application/json", body).await;
+    let client = CompatClient::new(CompatConfig::new(base_url, "llama-3.1-8b"));
+    let err = client.ask_raw("answer".into()).await.unwrap_err();
+    assert!(matches!(err, AIError::Refused(_)));
+}
+
+#[tokio::test]
+async fn reproducible_pins_sampling_and_records_fingerprint() {
+    let body = serde_json::json!({
+        "model": "llama-3.1-8b-0724",
+        "system_fingerprint": "fp_44709d6fcb",
+        "choices": [{ "message": { "content": "{\"value\": 3}" } }]
+    }).to_string();
+    let (base_url, server) = serve_once("application/json", body).await;
+    let stats = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = stats.clone();
+    let resolver = QueryResolver::new(CompatClient::new(CompatConfig::new(base_url, "llama-3.1-8b")), RetryConfig::default())
+        .with_stats_callback(move |s| sink.lock().unwrap().push(s.clone()))
+        .reproducible(true);
+
+    resolver.query::<Answer>("answer".into()).await.unwrap();
+
+    let request = server.await.unwrap();
+    assert!(request.contains("\"seed\":42"));
+    assert!(request.contains("\"temperature\":0.0"));
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats[0].model.as_deref(), Some("llama-3.1-8b-0724"));
+    assert_eq!(stats[0].system_fingerprint.as_deref(), Some("fp_44709d6fcb"));
+    assert_eq!(stats[0].seed, Some(42));
+}
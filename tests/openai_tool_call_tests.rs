@@ -0,0 +1,98 @@
+#![cfg(feature = "openai")]
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::clients::{FunctionTool, OpenAIConfig};
+use semantic_query::core::RawByteStream;
+use semantic_query::error::AIError;
+use semantic_query::streaming::{stream_from_sse_bytes, stream_from_sse_bytes_timed, StreamItem, StreamOptions, ToolCall};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Weather {
+    location: String,
+}
+
+fn sse(chunks: &[Value], done: bool) -> RawByteStream {
+    let mut body: String = chunks.iter().map(|chunk| format!("data: {chunk}\n\n")).collect();
+    if done {
+        body.push_str("data: [DONE]\n\n");
+    }
+    Box::pin(futures_util::stream::iter(vec![Ok::<_, AIError>(Bytes::from(body))]))
+}
+
+fn delta(tool_calls: Value) -> Value {
+    json!({ "id": "chatcmpl-1", "choices": [{ "delta": { "tool_calls": tool_calls }, "finish_reason": null }] })
+}
+
+/// Two parallel calls whose argument fragments interleave, as OpenAI streams them
+fn parallel_calls() -> Vec<Value> {
+    vec![
+        delta(json!([{ "index": 0, "id": "call_a", "type": "function", "function": { "name": "get_weather", "arguments": "" } }])),
+        delta(json!([{ "index": 0, "function": { "arguments": "{\"locat" } }])),
+        delta(json!([{ "index": 1, "id": "call_b", "type": "function", "function": { "name": "get_weather", "arguments": "{\"location\":" } }])),
+        delta(json!([{ "index": 0, "function": { "arguments": "ion\": \"Paris\"}" } }])),
+        delta(json!([{ "index": 1, "function": { "arguments": " \"Oslo\"}" } }])),
+        json!({ "id": "chatcmpl-1", "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }),
+    ]
+}
+
+#[tokio::test]
+async fn fragmented_arguments_are_reassembled_per_index() {
+    let items: Vec<_> = stream_from_sse_bytes::<Weather>(sse(&parallel_calls(), true)).collect().await;
+    let data: Vec<Weather> = items.into_iter().map(Result::unwrap).filter_map(|i| match i {
+        StreamItem::Data(w) => Some(w),
+        _ => None,
+    }).collect();
+    assert_eq!(data, vec![Weather { location: "Paris".into() }, Weather { location: "Oslo".into() }]);
+}
+
+#[tokio::test]
+async fn calls_keep_their_ids() {
+    let items: Vec<_> = stream_from_sse_bytes::<ToolCall>(sse(&parallel_calls(), false)).collect().await;
+    let ids: Vec<String> = items.into_iter().filter_map(|i| match i {
+        Ok(StreamItem::Data(call)) => Some(call.id),
+        _ => None,
+    }).collect();
+    assert_eq!(ids, vec!["call_a", "call_b"]);
+
+    let timed: Vec<_> = stream_from_sse_bytes_timed::<Weather>(sse(&parallel_calls(), true), StreamOptions::default()).collect().await;
+    let ids: Vec<_> = timed.into_iter().map(Result::unwrap)
+        .filter(|t| matches!(t.item, StreamItem::Data(_)))
+        .map(|t| t.event_id.unwrap())
+        .collect();
+    assert_eq!(ids, vec!["call_a", "call_b"]);
+}
+
+#[tokio::test]
+async fn calls_without_finish_reason_complete_at_done() {
+    let mut chunks = parallel_calls();
+    chunks.pop();
+    let items: Vec<_> = stream_from_sse_bytes::<Weather>(sse(&chunks, true)).collect().await;
+    assert_eq!(items.iter().filter(|i| matches!(i, Ok(StreamItem::Data(_)))).count(), 2);
+}
+
+#[tokio::test]
+async fn text_and_mismatched_calls_are_kept_as_text() {
+    let chunks = vec![
+        json!({ "choices": [{ "delta": { "content": "Let me check." }, "finish_reason": null }] }),
+        delta(json!([{ "index": 0, "id": "call_a", "function": { "name": "get_time", "arguments": "{\"zone\": \"CET\"}" } }])),
+        json!({ "choices": [{ "delta": {}, "finish_reason": "tool_calls" }] }),
+    ];
+    let items: Vec<_> = stream_from_sse_bytes::<Weather>(sse(&chunks, true)).collect().await;
+    let texts: Vec<String> = items.into_iter().filter_map(|i| match i {
+        Ok(StreamItem::Text(t)) => Some(t.text),
+        _ => None,
+    }).collect();
+    assert_eq!(texts, vec!["Let me check.".to_string(), r#"{"zone":"CET"}"#.to_string()]);
+}
+
+#[test]
+fn configured_tools_use_the_function_format() {
+    let config = OpenAIConfig::default().with_tool(FunctionTool::for_type::<Weather>("get_weather", "Current weather for a city"));
+    let tool = serde_json::to_value(&config.tools[0]).unwrap();
+    assert_eq!(tool["name"], "get_weather");
+    assert_eq!(tool["parameters"]["required"], json!(["location"]));
+}
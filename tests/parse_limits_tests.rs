@@ -0,0 +1,64 @@
+use bytes::Bytes;
+use futures_util::StreamExt;
+use semantic_query::core::RawByteStream;
+use semantic_query::error::{AIError, DataExtractionError, ParseLimitError, QueryResolverError};
+use semantic_query::json_utils::{find_json_structures, try_find_json_structures, JsonStreamParser, ParseLimits};
+use semantic_query::streaming::{stream_from_bytes_with, StreamItem, StreamOptions};
+
+fn nested(depth: usize) -> String {
+    format!("{}{}", "[".repeat(depth), "]".repeat(depth))
+}
+
+#[test]
+fn depth_limit_is_reported() {
+    let limits = ParseLimits { max_depth: 8, ..ParseLimits::default() };
+    assert!(try_find_json_structures(&nested(8), limits).is_ok());
+    let err = try_find_json_structures(&nested(9), limits).unwrap_err();
+    assert_eq!(err, ParseLimitError::Depth { limit: 8, offset: 8 });
+}
+
+#[test]
+fn node_and_size_limits_are_reported() {
+    let many = format!("[{}]", vec!["{}"; 10].join(","));
+    let limits = ParseLimits { max_nodes: 5, ..ParseLimits::default() };
+    assert!(matches!(try_find_json_structures(&many, limits), Err(ParseLimitError::Nodes { limit: 5, .. })));
+
+    let limits = ParseLimits { max_structure_bytes: 16, ..ParseLimits::default() };
+    let big = r#"x {"text": "this string is far too long"}"#;
+    assert_eq!(try_find_json_structures(big, limits).unwrap_err(), ParseLimitError::Size { limit: 16, start: 2 });
+    assert_eq!(try_find_json_structures(r#"{"a":1}"#, limits).unwrap().len(), 1);
+}
+
+#[test]
+fn lenient_scan_drops_offending_structure_and_continues() {
+    let text = format!("{} then {{\"ok\":true}}", nested(10_000));
+    let roots = find_json_structures(&text);
+    let last = roots.last().unwrap();
+    assert_eq!(&text[last.start..=last.end], "{\"ok\":true}");
+}
+
+#[test]
+fn parser_memory_stays_bounded_across_chunks() {
+    let mut parser = JsonStreamParser::with_limits(ParseLimits { max_depth: 4, ..ParseLimits::default() });
+    for _ in 0..1000 {
+        parser.feed("[[[[[[");
+    }
+    assert!(matches!(parser.take_error(), Some(ParseLimitError::Depth { limit: 4, .. })));
+    assert!(parser.take_error().is_none());
+}
+
+#[tokio::test]
+async fn streams_end_with_a_structured_error() {
+    let chunks: Vec<Result<Bytes, AIError>> = vec![
+        Ok(Bytes::from_static(b"{\"a\":1} ")),
+        Ok(Bytes::from(nested(500))),
+    ];
+    let bytes: RawByteStream = Box::pin(futures_util::stream::iter(chunks));
+    let items: Vec<_> = stream_from_bytes_with::<serde_json::Value>(bytes, StreamOptions::default()).collect().await;
+
+    assert!(matches!(&items[0], Ok(StreamItem::Data(_))));
+    assert!(matches!(
+        items.last(),
+        Some(Err(QueryResolverError::DataExtraction(DataExtractionError::LimitExceeded(ParseLimitError::Depth { limit: 128, .. }))))
+    ));
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::clients::mock::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::PipelineError;
+use semantic_query::pipeline::{Pipeline, PipelineEvent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Topic {
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Summary {
+    text: String,
+}
+
+/// The handle must outlive the queries: the mock errors once it is dropped
+fn resolver(responses: Vec<&str>) -> (QueryResolver<MockClient>, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(responses);
+    (QueryResolver::new(client, RetryConfig { default_max_retries: 0, ..RetryConfig::default() }), handle)
+}
+
+#[tokio::test]
+async fn steps_feed_the_next_prompt() {
+    let (resolver, _handle) = resolver(vec![r#"{"name":"rust"}"#, r#"{"text":"fast"}"#]);
+
+    let (summary, context) = Pipeline::start::<Topic>("pick a topic")
+        .named("topic")
+        .then::<Summary, _>(|topic| format!("summarize {}", topic.name))
+        .run_with_context(&resolver)
+        .await
+        .unwrap();
+
+    assert_eq!(summary, Summary { text: "fast".into() });
+    assert_eq!(context.outputs().len(), 2);
+    assert_eq!(context.output("topic").unwrap().output["name"], "rust");
+    assert!(context.outputs()[1].prompt.starts_with("summarize rust"));
+}
+
+#[tokio::test]
+async fn steps_retry_when_no_data_is_found() {
+    let (resolver, _handle) = resolver(vec![r#"{"name":"rust"}"#, "no json here", r#"{"text":"ok"}"#]);
+
+    let (_, context) = Pipeline::start::<Topic>("pick")
+        .then::<Summary, _>(|topic| topic.name.clone())
+        .with_retries(1)
+        .run_with_context(&resolver)
+        .await
+        .unwrap();
+    assert_eq!(context.outputs()[1].attempts, 2);
+}
+
+#[tokio::test]
+async fn failures_report_the_step() {
+    let (resolver, _handle) = resolver(vec!["nothing"]);
+
+    let err = Pipeline::start::<Topic>("pick").named("topic").run(&resolver).await.unwrap_err();
+    assert!(matches!(err, PipelineError::Step { index: 0, ref name, .. } if name == "topic"));
+}
+
+#[tokio::test]
+async fn stream_yields_intermediate_results() {
+    let (resolver, _handle) = resolver(vec![r#"{"name":"rust"}"#, r#"{"text":"fast"}"#]);
+
+    let events: Vec<_> = Pipeline::start::<Topic>("pick")
+        .with_shared_context("You are terse.")
+        .then_with_context::<Summary, _>(|topic, ctx| format!("{} ({} done)", topic.name, ctx.outputs().len()))
+        .stream(&resolver)
+        .collect()
+        .await;
+
+    assert_eq!(events.len(), 3);
+    assert!(matches!(&events[0], Ok(PipelineEvent::Step(s)) if s.prompt.starts_with("You are terse.")));
+    assert!(matches!(&events[2], Ok(PipelineEvent::Done(s)) if s.text == "fast"));
+}
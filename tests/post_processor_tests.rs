@@ -0,0 +1,79 @@
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::postprocess::{CanonicalEnum, Clamp, TrimStrings};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+enum Severity {
+    Low,
+    High,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Finding {
+    title: String,
+    severity: Severity,
+    confidence: f64,
+    hours: u32,
+}
+
+#[tokio::test]
+async fn json_is_normalized_before_deserialization() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_response(r#"Found: {"title": "  Leaky pipe ", "severity": "HIGH", "confidence": 1.7, "hours": 3}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_post_processor::<Finding>(TrimStrings)
+        .with_post_processor::<Finding>(CanonicalEnum::new("/severity", ["Low", "High"]))
+        .with_post_processor::<Finding>(Clamp::new("/confidence", 0.0, 1.0));
+
+    let finding = resolver.query_mixed::<Finding>("inspect".into()).await.unwrap().first_required().unwrap();
+    assert_eq!(finding, Finding { title: "Leaky pipe".into(), severity: Severity::High, confidence: 1.0, hours: 3 });
+}
+
+#[tokio::test]
+async fn rejections_feed_a_correction_request() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_responses(vec![
+        r#"{"title": "a", "severity": "Low", "confidence": 0.5, "hours": 0}"#,
+        r#"{"title": "a", "severity": "Low", "confidence": 0.5, "hours": 2}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_post_processor(|f: Finding| if f.hours == 0 { Err("hours must be positive".to_string()) } else { Ok(f) });
+
+    let response = resolver.query::<Finding>("estimate".into()).await.unwrap();
+    assert_eq!(response.first().unwrap().hours, 2);
+    assert!(mock.is_empty());
+}
+
+#[tokio::test]
+async fn rejections_fail_once_retries_run_out() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_responses(vec![
+        r#"{"title": "a", "severity": "Low", "confidence": 0.5, "hours": 0}"#,
+        r#"{"title": "a", "severity": "Low", "confidence": 0.5, "hours": 0}"#,
+    ]);
+    let mut config = RetryConfig::default();
+    config.max_retries.insert("post_process".into(), 1);
+    let resolver = QueryResolver::new(client, config)
+        .with_post_processor(|f: Finding| if f.hours == 0 { Err("hours must be positive".to_string()) } else { Ok(f) });
+
+    let err = resolver.query::<Finding>("estimate".into()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::PostProcessing(ref m) if m.contains("hours must be positive")), "{err}");
+}
+
+#[tokio::test]
+async fn processors_only_apply_to_their_type() {
+    #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+    struct Other {
+        title: String,
+    }
+
+    let (client, mock) = MockClient::new();
+    mock.add_json_response(r#"{"title": "  spaced  "}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_post_processor::<Finding>(TrimStrings);
+
+    let other = resolver.query_mixed::<Other>("x".into()).await.unwrap();
+    assert_eq!(other.first().unwrap().title, "  spaced  ");
+}
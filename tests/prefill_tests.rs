@@ -0,0 +1,63 @@
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockSetting};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Task {
+    title: String,
+}
+
+#[tokio::test]
+async fn conversations_send_the_prefill_and_keep_the_full_reply() {
+    // Like Claude, the model continues the prefill and the client adds it back
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::Prefill]);
+    handle.add_json_response(r#""title": "Ship it"}"#);
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
+    let mut conversation = resolver.conversation().with_prefill("{");
+
+    let task = conversation.query::<Task>("Plan the release".to_string()).await.unwrap();
+    assert_eq!(task.first_required().unwrap().title, "Ship it");
+    assert_eq!(handle.calls()[0].settings.prefill.as_deref(), Some("{"));
+    assert_eq!(conversation.history()[1].content, r#"{"title": "Ship it"}"#);
+    assert!(client.capabilities().supports_prefill);
+}
+
+#[tokio::test]
+async fn conversations_without_prefill_support_send_the_prompt_as_is() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"title": "Ship it"}"#);
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
+    let mut conversation = resolver.conversation().with_prefill("{");
+    assert_eq!(conversation.ask("Plan the release".to_string()).await.unwrap(), r#"{"title": "Ship it"}"#);
+    assert!(!client.capabilities().supports_prefill);
+}
+
+#[cfg(feature = "anthropic")]
+mod claude {
+    use semantic_query::clients::claude::{ClaudeConfig, ClaudeModel, ClaudeRequest};
+    use semantic_query::core::ChatMessage;
+    use serde_json::json;
+
+    fn config() -> ClaudeConfig {
+        ClaudeConfig::anthropic("k".into(), ClaudeModel::Haiku35)
+    }
+
+    #[test]
+    fn configured_prefill_ends_the_messages() {
+        let request = ClaudeRequest::new("Plan the release".into(), &config().with_prefill("{ "));
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["messages"][1], json!({ "role": "assistant", "content": "{" }));
+        assert_eq!(request.prefill(), Some("{"));
+        assert_eq!(ClaudeRequest::new("Hi".into(), &config()).prefill(), None);
+    }
+
+    #[test]
+    fn a_final_assistant_turn_is_the_prefill() {
+        let messages = vec![ChatMessage::user("Plan the release"), ChatMessage::assistant("Here is the plan:\n")];
+        let request = ClaudeRequest::from_messages(messages, &config().with_prefill("{"));
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.prefill(), Some("Here is the plan:"));
+    }
+}
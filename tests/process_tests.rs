@@ -0,0 +1,108 @@
+#![cfg(all(unix, feature = "rt-tokio"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::clients::{ProcessClient, ProcessConfig, PromptInput};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::error::{AIError, ProcessError};
+use semantic_query::streaming::StreamItem;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+fn shell(script: &str) -> ProcessConfig {
+    ProcessConfig::new("/bin/sh").with_args(["-c", script])
+}
+
+#[tokio::test]
+async fn prompt_goes_to_stdin_and_reply_comes_from_stdout() {
+    let client = ProcessClient::new(shell("tr a-z A-Z"));
+    assert_eq!(client.ask_raw("hello".into()).await.unwrap(), "HELLO");
+
+    let resolver = QueryResolver::new(ProcessClient::new(shell(r#"cat >/dev/null; echo '{"name": "Ada"}'"#)), RetryConfig::default());
+    let response = resolver.query::<Contact>("Who?".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Contact { name: "Ada".into() }));
+}
+
+#[tokio::test]
+async fn prompt_can_be_passed_as_an_argument() {
+    let placeholder = shell(r#"printf '[%s]' "$1""#).with_args(["sh", "{prompt}"]).with_prompt_input(PromptInput::Argument);
+    assert_eq!(ProcessClient::new(placeholder).ask_raw("two words".into()).await.unwrap(), "[two words]");
+
+    let appended = ProcessConfig::new("echo").with_arg("-n").with_prompt_input(PromptInput::Argument);
+    assert_eq!(ProcessClient::new(appended).ask_raw("last".into()).await.unwrap(), "last");
+}
+
+#[tokio::test]
+async fn environment_is_controlled() {
+    std::env::set_var("PROCESS_TESTS_INHERITED", "yes");
+    let config = shell(r#"printf '%s|%s|%s' "$GREETING" "$PROCESS_TESTS_INHERITED" "$PROCESS_TESTS_REMOVED""#)
+        .with_env("GREETING", "hi");
+    assert_eq!(ProcessClient::new(config.clone()).ask_raw(String::new()).await.unwrap(), "hi|yes|");
+
+    let cleared = config.clone().with_env_clear();
+    assert_eq!(ProcessClient::new(cleared).ask_raw(String::new()).await.unwrap(), "hi||");
+
+    let removed = config.without_env("PROCESS_TESTS_INHERITED");
+    assert_eq!(ProcessClient::new(removed).ask_raw(String::new()).await.unwrap(), "hi||");
+
+    let dir = ProcessConfig::new("pwd").with_current_dir("/");
+    assert_eq!(ProcessClient::new(dir).ask_raw(String::new()).await.unwrap().trim(), "/");
+}
+
+#[tokio::test]
+async fn failures_are_reported() {
+    let failing = ProcessClient::new(shell("echo boom >&2; exit 3"));
+    match failing.ask_raw("x".into()).await {
+        Err(AIError::Process(ProcessError::Exit { code: Some(3), stderr })) => assert_eq!(stderr, "boom"),
+        other => panic!("expected an exit error, got {other:?}"),
+    }
+
+    let missing = ProcessClient::new(ProcessConfig::new("semantic-query-no-such-command"));
+    assert!(matches!(missing.ask_raw("x".into()).await, Err(AIError::Process(ProcessError::Spawn { .. }))));
+
+    let slow = ProcessClient::new(shell("sleep 5").with_timeout(Duration::from_millis(100)));
+    let started = std::time::Instant::now();
+    let error = slow.ask_raw("x".into()).await.unwrap_err();
+    assert!(matches!(error, AIError::Process(ProcessError::Timeout(_))));
+    assert!(error.is_retryable());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn stdout_streams_line_by_line() {
+    let script = r#"cat >/dev/null; echo 'Found:'; echo '{"name": "Lin"}'; echo done"#;
+    let resolver = QueryResolver::new(ProcessClient::new(shell(script)), RetryConfig::default());
+    let items: Vec<_> = resolver.stream_query::<Contact>("Who?".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let tokens: Vec<&str> = items.iter().filter_map(|item| match item {
+        StreamItem::Token(token) => Some(token.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(tokens, ["Found:\n", "{\"name\": \"Lin\"}\n", "done\n"]);
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Data(c) if c.name == "Lin")));
+}
+
+#[tokio::test]
+async fn streaming_fails_with_the_command_and_can_be_disabled() {
+    let client = ProcessClient::new(shell("echo partial; echo broken >&2; exit 1"));
+    let chunks: Vec<_> = client.stream_raw("x".into()).unwrap().collect().await;
+    assert!(chunks[0].is_ok());
+    assert!(matches!(chunks.last(), Some(Err(AIError::Process(ProcessError::Exit { code: Some(1), .. })))));
+
+    let slow = ProcessClient::new(shell("echo first; sleep 5").with_timeout(Duration::from_millis(200)));
+    let chunks: Vec<_> = slow.stream_raw("x".into()).unwrap().collect().await;
+    assert!(matches!(chunks.last(), Some(Err(AIError::Process(ProcessError::Timeout(_))))));
+
+    let quiet = ProcessClient::new(shell("cat").with_streaming(false));
+    assert!(quiet.stream_raw("x".into()).is_none());
+    assert!(!quiet.capabilities().supports_streaming);
+}
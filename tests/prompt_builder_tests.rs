@@ -0,0 +1,99 @@
+use semantic_query::core::ChatRole;
+use semantic_query::prompt::{estimate_tokens, CutAction, PartKind, PromptBuilder, PromptPart};
+
+fn doc(words: usize) -> String {
+    "lorem ipsum ".repeat(words)
+}
+
+#[test]
+fn renders_all_parts_in_order_when_within_budget() {
+    let rendered = PromptBuilder::new()
+        .system("Be terse.")
+        .instructions("Answer from the documents only.")
+        .context("a.txt", "alpha")
+        .question("What is in a.txt?")
+        .max_tokens(1000)
+        .render();
+
+    assert_eq!(rendered.system.as_deref(), Some("Be terse."));
+    assert_eq!(rendered.prompt, "Answer from the documents only.\n\n### a.txt\nalpha\n\nWhat is in a.txt?");
+    assert!(rendered.cuts.is_empty());
+    assert!(rendered.fits());
+
+    let messages = rendered.messages();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].role, ChatRole::System);
+    assert!(rendered.clone().into_text().starts_with("Be terse.\n\nAnswer"));
+}
+
+#[test]
+fn lowest_priority_and_latest_parts_are_cut_first() {
+    let builder = PromptBuilder::new()
+        .instructions("Answer from the documents only.")
+        .context_with_priority("important.txt", doc(100), 70)
+        .context("first.txt", doc(100))
+        .context("second.txt", doc(100))
+        .question("Summarize.");
+
+    let full = builder.render().tokens;
+    let second = estimate_tokens(&format!("### second.txt\n{}", doc(100)));
+    let rendered = builder.clone().max_tokens(full - second).render();
+
+    // Only `second.txt` (priority 50, added after `first.txt`) is touched
+    assert_eq!(rendered.cuts.len(), 1);
+    assert_eq!(rendered.cuts[0].name, "second.txt");
+    assert!(rendered.fits(), "{} > {:?}", rendered.tokens, rendered.max_tokens);
+    assert!(rendered.prompt.contains("### first.txt"));
+    assert!(rendered.prompt.contains("### important.txt"));
+
+    // Rendering is deterministic
+    assert_eq!(builder.clone().max_tokens(full - second).render(), rendered);
+}
+
+#[test]
+fn truncates_documents_and_reports_kept_tokens() {
+    let rendered = PromptBuilder::new()
+        .context("long.txt", doc(200))
+        .question("Summarize.")
+        .max_tokens(300)
+        .render();
+
+    assert!(rendered.fits());
+    assert_eq!(rendered.cuts.len(), 1);
+    match rendered.cuts[0].action {
+        CutAction::Truncated { original_tokens, kept_tokens } => assert!(kept_tokens < original_tokens),
+        other => panic!("expected truncation, got {other:?}"),
+    }
+    assert!(rendered.prompt.contains("[...truncated]"));
+    assert!(rendered.prompt.ends_with("Summarize."));
+}
+
+#[test]
+fn non_truncatable_parts_are_dropped_whole_and_pinned_parts_kept() {
+    let rendered = PromptBuilder::new()
+        .system(doc(50))
+        .instructions(doc(50))
+        .part(PromptPart::new(PartKind::Instructions, "style", doc(50)).priority(10))
+        .question(doc(50))
+        .max_tokens(10)
+        .render();
+
+    // Everything cuttable is gone, but the pinned system text and question alone exceed the budget
+    assert!(!rendered.fits());
+    assert_eq!(rendered.cuts.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["style", "instructions"]);
+    assert!(rendered.cuts.iter().all(|c| matches!(c.action, CutAction::Dropped { .. })));
+    assert_eq!(rendered.system.as_deref(), Some(doc(50).as_str()));
+    assert_eq!(rendered.prompt, doc(50));
+}
+
+#[test]
+fn schema_part_contains_the_json_schema() {
+    #[derive(schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct Invoice {
+        total: f64,
+    }
+
+    let rendered = PromptBuilder::new().schema::<Invoice>().question("Extract the invoice.").render();
+    assert!(rendered.prompt.contains("\"total\""));
+}
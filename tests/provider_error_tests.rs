@@ -0,0 +1,81 @@
+#![cfg(feature = "http")]
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderValue};
+use semantic_query::core::RetryConfig;
+use semantic_query::error::{AIError, ClaudeError, OllamaError, OpenAIError, ProviderError};
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(*name, HeaderValue::from_static(value));
+    }
+    map
+}
+
+#[test]
+fn parses_anthropic_error_body_and_headers() {
+    let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+    let err = ProviderError::from_parts(529, &headers(&[("request-id", "req_011"), ("retry-after", "12")]), body);
+    assert_eq!(err.status, 529);
+    assert_eq!(err.code.as_deref(), Some("overloaded_error"));
+    assert_eq!(err.message, "Overloaded");
+    assert_eq!(err.retry_after, Some(Duration::from_secs(12)));
+    assert_eq!(err.request_id.as_deref(), Some("req_011"));
+}
+
+#[test]
+fn prefers_openai_code_over_type_and_retry_after_ms() {
+    let body = r#"{"error":{"message":"Too long","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+    let err = ProviderError::from_parts(400, &headers(&[("x-request-id", "abc"), ("retry-after-ms", "1500"), ("retry-after", "9")]), body);
+    assert_eq!(err.code.as_deref(), Some("context_length_exceeded"));
+    assert_eq!(err.retry_after, Some(Duration::from_millis(1500)));
+    assert_eq!(err.request_id.as_deref(), Some("abc"));
+}
+
+#[test]
+fn falls_back_to_raw_body() {
+    let err = ProviderError::from_parts(502, &HeaderMap::new(), "<html>Bad Gateway</html>");
+    assert_eq!(err.code, None);
+    assert_eq!(err.message, "<html>Bad Gateway</html>");
+    assert_eq!(ProviderError::from_parts(500, &HeaderMap::new(), " ").message, "Unknown error");
+    assert_eq!(ProviderError::from_parts(404, &HeaderMap::new(), r#"{"error":"model not found"}"#).message, "model not found");
+}
+
+#[test]
+fn status_selects_variant() {
+    assert!(matches!(ClaudeError::from(ProviderError::new(401, "bad key")), ClaudeError::Authentication(_)));
+    assert!(matches!(OpenAIError::from(ProviderError::new(403, "forbidden")), OpenAIError::Authentication(_)));
+    assert!(matches!(OpenAIError::from(ProviderError::new(429, "slow down")), OpenAIError::RateLimit(_)));
+    assert!(matches!(OpenAIError::from(ProviderError::new(400, "bad request")), OpenAIError::Status(_)));
+}
+
+#[test]
+fn accessors_and_retry_classification() {
+    let mut limited = ProviderError::new(429, "slow down");
+    limited.retry_after = Some(Duration::from_secs(3));
+    limited.request_id = Some("req_1".into());
+    let err = AIError::Claude(limited.into());
+    assert_eq!(err.status(), Some(429));
+    assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+    assert_eq!(err.request_id(), Some("req_1"));
+    assert_eq!(err.retry_key(), "rate_limit");
+    assert!(err.to_string().contains("req_1"));
+
+    let overloaded = AIError::Ollama(OllamaError::Status(ProviderError::new(503, "busy").into()));
+    assert_eq!(overloaded.retry_key(), "http_error");
+    assert!(overloaded.is_retryable());
+
+    let transport = AIError::OpenAI(OpenAIError::Http("connection reset".into()));
+    assert_eq!(transport.status(), None);
+    assert!(transport.is_retryable());
+
+    let bad_request = AIError::OpenAI(ProviderError::new(400, "bad request").into());
+    assert_eq!(bad_request.retry_key(), "api_error");
+    assert!(!bad_request.is_retryable());
+
+    let config = RetryConfig::default();
+    assert_eq!(config.max_retries_for(&err), 1);
+    assert_eq!(config.max_retries_for(&bad_request), 0);
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use semantic_query::audit::{content_hash, QueryRecord, RecordOutcome};
+use semantic_query::clients::{FlexibleClient, MockClient, MockResponse};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::interceptors::Interceptor;
+use semantic_query::schema::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Answer {
+    value: i32,
+}
+
+/// Resolver over `client` collecting every record
+fn recording<C: LowLevelClient>(client: C) -> (QueryResolver<C>, Arc<Mutex<Vec<QueryRecord>>>) {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let sink = records.clone();
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_record_callback(move |record| sink.lock().unwrap().push(record.clone()));
+    (resolver, records)
+}
+
+/// Keeps the records passed to `save_record`
+#[derive(Debug, Default)]
+struct RecordLog(Mutex<Vec<QueryRecord>>);
+
+#[async_trait]
+impl Interceptor for RecordLog {
+    async fn save(&self, _prompt: &str, _response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn save_record(&self, record: &QueryRecord) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(record.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn successful_queries_record_hashes_not_content() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"value": 42}"#);
+    let (resolver, records) = recording(client);
+    resolver.query::<Answer>("What is the answer?".to_string()).await.unwrap();
+
+    let record = records.lock().unwrap()[0].clone();
+    assert_eq!(record.operation, "query");
+    assert_eq!(record.prompt_hash, content_hash(b"What is the answer?"));
+    assert_eq!(record.prompt, None);
+    assert!(record.schema_id.as_deref().is_some_and(|id| id.starts_with("Answer:")));
+    assert_eq!(record.attempts, 1);
+    assert_eq!(record.retries, 0);
+    assert_eq!(record.outcome, RecordOutcome::Succeeded { items: 1 });
+    assert_eq!(record.data_hash, Some(content_hash(br#"[{"value":42}]"#)));
+    assert!(record.correlation_id.is_some());
+    assert!(record.usage.prompt_tokens > 0 && record.usage.completion_tokens > 0);
+}
+
+#[tokio::test]
+async fn failed_queries_record_the_error() {
+    let (client, handle) = MockClient::new();
+    handle.add_response(MockResponse::Error(AIError::Mock("down".to_string())));
+    let (resolver, records) = recording(client);
+    let resolver = resolver.with_prompts_in_records(true);
+    assert!(resolver.query::<Answer>("q".to_string()).await.is_err());
+
+    let record = records.lock().unwrap()[0].clone();
+    assert!(!record.succeeded());
+    assert!(matches!(&record.outcome, RecordOutcome::Failed { error } if error.contains("down")));
+    assert_eq!(record.data_hash, None);
+    assert_eq!(record.prompt.as_deref(), Some("q"));
+}
+
+#[tokio::test]
+async fn records_reach_interceptors() {
+    let (mock, handle) = MockClient::new();
+    handle.add_json_responses(vec![r#"{"value": 1}"#, r#"{"value": 2}"#]);
+    let log = Arc::new(RecordLog::default());
+    let client = FlexibleClient::new(Box::new(mock)).with_interceptor(log.clone());
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    resolver.query_mixed::<Answer>("first".to_string()).await.unwrap();
+    resolver.conversation().query::<Answer>("second".to_string()).await.unwrap();
+
+    let records = log.0.lock().unwrap();
+    let operations: Vec<_> = records.iter().map(|r| r.operation.as_str()).collect();
+    assert_eq!(operations, ["query_mixed", "conversation"]);
+    assert_eq!(records[0].schema_id, None);
+    assert_eq!(records[1].prompt_hash, content_hash(b"second"));
+}
+
+#[test]
+fn records_round_trip_through_json() {
+    let json = serde_json::json!({
+        "operation": "query",
+        "correlation_id": "sq-1",
+        "tenant": null,
+        "started_at": "2024-01-15T09:30:00Z",
+        "duration": { "secs": 1, "nanos": 0 },
+        "prompt_hash": content_hash(b"q"),
+        "schema_id": null,
+        "model": "gpt-4o",
+        "usage": { "prompt_tokens": 1, "completion_tokens": 4 },
+        "attempts": 1,
+        "retries": 0,
+        "outcome": { "status": "succeeded", "items": 1 },
+        "data_hash": null
+    });
+    let record: QueryRecord = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&record).unwrap(), json);
+}
@@ -0,0 +1,51 @@
+use futures_util::StreamExt;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::streaming::StreamItem;
+use serde_json::json;
+
+fn contact_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": { "name": { "type": "string" }, "email": { "type": "string" } },
+        "required": ["name", "email"]
+    })
+}
+
+#[tokio::test]
+async fn runtime_schema_guides_the_prompt_and_json_is_extracted() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"Found it: {"name": "Ada", "email": "ada@example.com"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let response = resolver.query_schema("Extract the contact".into(), &contact_schema()).await.unwrap();
+    assert_eq!(response.first_required().unwrap(), json!({"name": "Ada", "email": "ada@example.com"}));
+
+    let prompt = handle.prompts()[0].clone();
+    assert!(prompt.starts_with("Extract the contact"));
+    assert!(prompt.contains(r#""required""#) && prompt.contains(r#""email""#));
+}
+
+#[tokio::test]
+async fn reply_without_json_has_no_data() {
+    let resolver = QueryResolver::new(ScriptedClient::Reply("No contact details here.".into()), RetryConfig::default());
+    let response = resolver.query_schema("Extract the contact".into(), &contact_schema()).await.unwrap();
+    assert!(!response.has_data());
+}
+
+#[tokio::test]
+async fn streamed_values_are_parsed() {
+    let chunks = ["Sure: ", r#"{"name": "Ada", "#, r#""email": "ada@example.com"}"#];
+    let client = ScriptedClient::Stream(chunks.iter().map(|c| c.as_bytes().to_vec()).collect());
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let mut stream = resolver.stream_query_schema("Extract the contact".into(), &contact_schema()).await.unwrap();
+    let mut data = Vec::new();
+    while let Some(item) = stream.next().await {
+        if let StreamItem::Data(value) = item.unwrap() {
+            data.push(value);
+        }
+    }
+    assert_eq!(data, vec![json!({"name": "Ada", "email": "ada@example.com"})]);
+}
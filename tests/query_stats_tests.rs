@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::stats::QueryStats;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Estimate {
+    hours: u32,
+}
+
+fn recording<C: LowLevelClient>(client: C) -> (QueryResolver<C>, Arc<Mutex<Vec<QueryStats>>>) {
+    let stats = Arc::new(Mutex::new(Vec::new()));
+    let sink = stats.clone();
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_stats_callback(move |s| sink.lock().unwrap().push(s.clone()));
+    (resolver, stats)
+}
+
+#[tokio::test]
+async fn non_streaming_query_counts_retries_and_bytes() {
+    let (client, mock) = MockClient::new();
+    let first = r#"{"hours": 0}"#;
+    let second = r#"Fixed: {"hours": 4}"#;
+    mock.add_json_responses(vec![first, second]);
+    let (resolver, stats) = recording(client);
+    let resolver = resolver.with_post_processor(|e: Estimate| if e.hours == 0 { Err("hours must be positive".to_string()) } else { Ok(e) });
+
+    resolver.query::<Estimate>("estimate".into()).await.unwrap();
+
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats.len(), 1);
+    let s = &stats[0];
+    assert_eq!(s.operation, "query");
+    assert!(s.succeeded);
+    assert_eq!(s.retries, 1);
+    assert_eq!(s.bytes_received, first.len() + second.len());
+    assert_eq!(s.items, 1);
+    assert!(s.time_to_first_token <= s.time_to_first_data);
+    assert!(s.time_to_first_data <= Some(s.total));
+}
+
+#[tokio::test]
+async fn failures_are_reported() {
+    let (client, _mock) = MockClient::new();
+    let (resolver, stats) = recording(client);
+
+    assert!(resolver.query_mixed::<Estimate>("estimate".into()).await.is_err());
+
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats[0].operation, "query_mixed");
+    assert!(!stats[0].succeeded);
+    assert_eq!(stats[0].time_to_first_token, None);
+}
+
+#[tokio::test]
+async fn streams_report_first_token_and_first_data() {
+    let client = ScriptedClient::Stream(vec![b"Estimate: ".to_vec(), br#"{"hours": 2}"#.to_vec()]);
+    let (resolver, stats) = recording(client);
+
+    let items: Vec<_> = resolver.stream_query::<Estimate>("estimate".into()).await.unwrap().collect().await;
+    assert!(items.iter().all(Result::is_ok));
+
+    let stats = stats.lock().unwrap();
+    let s = &stats[0];
+    assert_eq!(s.operation, "stream_query");
+    assert!(s.succeeded);
+    assert_eq!(s.items, 1);
+    assert_eq!(s.bytes_received, "Estimate: ".len() + r#"{"hours": 2}"#.len());
+    assert!(s.time_to_first_token.is_some());
+    assert!(s.time_to_first_data >= s.time_to_first_token);
+}
+
+#[tokio::test]
+async fn dropped_streams_still_report() {
+    let client = ScriptedClient::Stream(vec![b"Estimate: ".to_vec(), br#"{"hours": 2}"#.to_vec()]);
+    let (resolver, stats) = recording(client);
+
+    let mut stream = resolver.stream_query::<Estimate>("estimate".into()).await.unwrap();
+    stream.next().await;
+    drop(stream);
+
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats.len(), 1);
+    assert!(!stats[0].succeeded);
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use semantic_query::clients::mock::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::{JsonPathError, QueryResolverError};
+use semantic_query::jsonpath::JsonPath;
+use serde_json::json;
+
+const REPLY: &str = r#"Two orders: {"id": 1, "lines": [{"sku": "A", "qty": 2}]} and {"id": 2, "lines": [{"sku": "B", "qty": 1}, {"sku": "C", "qty": 4}]}."#;
+
+fn resolver() -> (QueryResolver<MockClient>, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(REPLY);
+    (QueryResolver::new(client, RetryConfig::default()), handle)
+}
+
+#[tokio::test]
+async fn without_a_path_every_structure_is_returned() {
+    let (resolver, handle) = resolver();
+    let values = resolver.query_values("List the orders".to_string(), None).await.unwrap();
+
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0]["id"], 1);
+    assert_eq!(values[1]["lines"][1]["sku"], "C");
+    assert_eq!(handle.prompts(), ["List the orders"]);
+}
+
+#[tokio::test]
+async fn a_path_selects_within_each_structure() {
+    let (resolver, handle) = resolver();
+    handle.add_json_response(REPLY);
+    let skus = resolver.query_values("List the orders".to_string(), Some("$.lines[*].sku")).await.unwrap();
+    assert_eq!(skus, vec![json!("A"), json!("B"), json!("C")]);
+
+    let last = resolver.query_values("List the orders".to_string(), Some("$..qty")).await.unwrap();
+    assert_eq!(last, vec![json!(2), json!(1), json!(4)]);
+}
+
+#[tokio::test]
+async fn an_invalid_path_fails_before_sending() {
+    let (resolver, handle) = resolver();
+    let err = resolver.query_values("List the orders".to_string(), Some("lines")).await.unwrap_err();
+
+    assert!(matches!(err, QueryResolverError::JsonPath(JsonPathError::Syntax { offset: 0, .. })));
+    assert!(handle.prompts().is_empty());
+}
+
+#[test]
+fn paths_support_names_indexes_wildcards_and_descent() {
+    let value = json!({
+        "store": {
+            "book": [
+                { "title": "A", "price": 8 },
+                { "title": "B", "price": 12, "tags": { "first edition": true } }
+            ],
+            "bicycle": { "price": 20 }
+        }
+    });
+    let select = |path: &str| path.parse::<JsonPath>().unwrap().select(&value).into_iter().cloned().collect::<Vec<_>>();
+
+    assert_eq!(select("$"), vec![value.clone()]);
+    assert_eq!(select("$.store.book[0].title"), vec![json!("A")]);
+    assert_eq!(select("$['store'][\"book\"][-1].title"), vec![json!("B")]);
+    assert_eq!(select("$.store.book[*].price"), vec![json!(8), json!(12)]);
+    assert_eq!(select("$.store.*.price"), vec![json!(20)]);
+    assert_eq!(select("$..price").len(), 3);
+    assert_eq!(select("$..tags['first edition']"), vec![json!(true)]);
+    assert!(select("$.store.book[5]").is_empty());
+    assert!(select("$.missing..price").is_empty());
+}
+
+#[test]
+fn malformed_paths_report_where_they_fail() {
+    let offset = |path: &str| match JsonPath::parse(path).unwrap_err() {
+        JsonPathError::Syntax { offset, .. } => offset,
+    };
+    assert_eq!(offset("store"), 0);
+    assert_eq!(offset("$.store["), 7);
+    assert_eq!(offset("$.a[x]"), 4);
+    assert_eq!(offset("$.a['b'"), 7);
+    assert_eq!(offset("$."), 2);
+    assert_eq!("$.a[0]".parse::<JsonPath>().unwrap().to_string(), "$.a[0]");
+}
@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use semantic_query::audit::{QueryRecord, RecordOutcome};
+use semantic_query::clients::{FaultConfig, Latency, MockClient, MockHandle};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::error::{AIError, QueryResolverError};
+use semantic_query::tenancy::{Tenancy, TenantLimits};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Quote {
+    price: f64,
+}
+
+/// A mock that answers `reply` after `millis`
+fn entrant(millis: u64, reply: Result<&str, &str>) -> (Box<dyn LowLevelClient>, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.inject_faults(FaultConfig::new().with_latency(Latency::Fixed(Duration::from_millis(millis))));
+    match reply {
+        Ok(text) => handle.add_json_response(text),
+        Err(error) => handle.add_error(AIError::Mock(error.to_string())),
+    }
+    (Box::new(client), handle)
+}
+
+fn entrants(replies: Vec<(u64, Result<&str, &str>)>) -> (Vec<Box<dyn LowLevelClient>>, Vec<Arc<MockHandle>>) {
+    replies.into_iter().map(|(millis, reply)| entrant(millis, reply)).unzip()
+}
+
+fn resolver() -> QueryResolver<MockClient> {
+    // Only the entrants are asked
+    let (default, _) = MockClient::new();
+    QueryResolver::new(default, RetryConfig { default_max_retries: 0, ..RetryConfig::default() })
+}
+
+#[tokio::test]
+async fn the_first_client_with_data_wins() {
+    let (clients, _handles) = entrants(vec![
+        (5, Err("provider down")),
+        (10, Ok("no data here")),
+        (20, Ok(r#"{"price": 9.5}"#)),
+        (5_000, Ok(r#"{"price": 1.0}"#)),
+    ]);
+
+    let race = resolver().query_race::<Quote>("Quote it".into(), clients).await.unwrap();
+    assert_eq!(race.winner, 2);
+    assert_eq!(race.response.first(), Some(&Quote { price: 9.5 }));
+    assert_eq!(race.failed.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(race.cancelled, vec![3]);
+}
+
+#[tokio::test]
+async fn the_last_error_is_returned_when_nobody_wins() {
+    let (clients, _handles) = entrants(vec![(5, Ok("nothing")), (10, Err("provider down"))]);
+    let err = resolver().query_race::<Quote>("Quote it".into(), clients).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::Ai(AIError::Mock(ref e)) if e == "provider down"));
+}
+
+#[tokio::test]
+async fn cancelled_entrants_are_still_accounted() {
+    let records: Arc<Mutex<Vec<QueryRecord>>> = Arc::default();
+    let sink = records.clone();
+    let (slow, _slow_handle) = entrant(5_000, Ok(r#"{"price": 1.0}"#));
+    let tenancy = Tenancy::new().with_tenant("acme", slow, TenantLimits::default());
+    let slow = Box::new(tenancy.client("acme").unwrap());
+    let (fast, _fast_handle) = entrant(5, Ok(r#"{"price": 9.5}"#));
+
+    let race = resolver()
+        .with_record_callback(move |record| sink.lock().unwrap().push(record.clone()))
+        .query_race::<Quote>("Quote it".into(), vec![fast, slow])
+        .await
+        .unwrap();
+    assert_eq!(race.cancelled, vec![1]);
+
+    let records = records.lock().unwrap();
+    let cancelled = records.iter().find(|r| r.outcome == RecordOutcome::Cancelled).unwrap();
+    assert_eq!(cancelled.tenant.as_deref(), Some("acme"));
+    assert!(cancelled.usage.prompt_tokens > 0);
+    assert_eq!(records.iter().map(|r| &r.correlation_id).collect::<std::collections::HashSet<_>>().len(), 1);
+    assert_eq!(tenancy.usage("acme").unwrap().requests, 1);
+}
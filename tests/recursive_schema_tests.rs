@@ -0,0 +1,111 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::MockClient;
+use semantic_query::core::{QueryResolver, ResponseMode, RetryConfig};
+use semantic_query::grammar::schema_to_gbnf;
+use semantic_query::schema::{is_recursive, schema_value};
+use semantic_query::streaming::StreamItem;
+use serde::{Deserialize, Serialize};
+
+/// Self-referencing: the schema points at `#`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Comment {
+    author: String,
+    replies: Vec<Comment>,
+}
+
+/// Wraps a recursive type, which then lives under `$defs`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Thread {
+    title: String,
+    root: Comment,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Unit {
+    name: String,
+    parent: Option<Box<Unit>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Flat {
+    name: String,
+    tags: Vec<String>,
+}
+
+const THREAD: &str = r#"Here is the discussion I found:
+
+{"title": "Release plan", "root": {"author": "ana", "replies": [
+  {"author": "bo", "replies": [{"author": "cy", "replies": []}]},
+  {"author": "di", "replies": []}
+]}}
+
+Let me know if you need more."#;
+
+fn comment(author: &str, replies: Vec<Comment>) -> Comment {
+    Comment { author: author.into(), replies }
+}
+
+#[test]
+fn recursion_is_detected_through_root_and_definition_refs() {
+    assert!(is_recursive(&schema_value::<Comment>()));
+    assert!(is_recursive(&schema_value::<Thread>()));
+    assert!(is_recursive(&schema_value::<Unit>()));
+    assert!(!is_recursive(&schema_value::<Flat>()));
+}
+
+#[tokio::test]
+async fn a_tree_is_extracted_from_mixed_text() {
+    let resolver = QueryResolver::new(ScriptedClient::Reply(THREAD.into()), RetryConfig::default());
+    let response = resolver.query::<Thread>("Summarize".into()).await.unwrap();
+
+    let expected = Thread {
+        title: "Release plan".into(),
+        root: comment("ana", vec![comment("bo", vec![comment("cy", vec![])]), comment("di", vec![])]),
+    };
+    assert_eq!(response.data_only(), vec![&expected]);
+    assert!(response.text_content().starts_with("Here is the discussion"));
+}
+
+#[tokio::test]
+async fn a_tree_is_extracted_from_a_stream() {
+    let chunks = THREAD.as_bytes().chunks(7).map(<[u8]>::to_vec).collect();
+    let resolver = QueryResolver::new(ScriptedClient::Stream(chunks), RetryConfig::default());
+
+    let items: Vec<_> = resolver.stream_query::<Thread>("Summarize".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let thread = items.iter().find_map(|item| match item {
+        StreamItem::Data(thread) => Some(thread),
+        _ => None,
+    }).unwrap();
+    assert_eq!(thread.root.replies[0].replies[0].author, "cy");
+}
+
+#[test]
+fn self_references_compile_to_the_root_rule() {
+    let gbnf = schema_to_gbnf(&schema_value::<Comment>()).unwrap();
+    let items = gbnf.lines().find(|line| line.starts_with("root-replies ::=")).unwrap();
+    assert!(items.contains("root ( \",\" ws root )*"), "{gbnf}");
+
+    assert!(schema_to_gbnf(&schema_value::<Unit>()).is_ok());
+    assert!(schema_to_gbnf(&schema_value::<Thread>()).is_ok());
+}
+
+#[tokio::test]
+async fn guidance_explains_recursive_schemas() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![r#"{"author": "ana", "replies": []}"#; 3]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    resolver.query::<Comment>("Extract".into()).await.unwrap();
+    resolver.clone().with_response_mode(ResponseMode::JsonOnly).query::<Comment>("Extract".into()).await.unwrap();
+    resolver.query::<Flat>("Extract".into()).await.unwrap();
+
+    let prompts = handle.prompts();
+    assert!(prompts[0].contains("The schema is recursive"));
+    assert!(prompts[1].contains("The schema is recursive"));
+    assert!(!prompts[2].contains("The schema is recursive"));
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use semantic_query::clients::mock::{MockClient, MockHandle, MockResponse};
+use semantic_query::core::{ProsePolicy, QueryResolver, ResponseMode, RetryConfig};
+use semantic_query::error::{AIError, QueryResolverError};
+use semantic_query::refusal::{PhraseRefusalDetector, QueryOutcome, RefusalDetector};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Summary {
+    text: String,
+}
+
+fn resolver_with(response: MockResponse) -> (QueryResolver<MockClient>, Arc<MockHandle>) {
+    let (client, handle) = MockClient::with_responses(vec![response]);
+    (QueryResolver::new(client, RetryConfig::default()), handle)
+}
+
+#[tokio::test]
+async fn heuristic_refusal_is_reported() {
+    let (resolver, _handle) = resolver_with(MockResponse::Success("I\u{2019}m sorry, but I can\u{2019}t help with that request.".into()));
+    match resolver.query_outcome::<Summary>("Summarize".into()).await.unwrap() {
+        QueryOutcome::Refused { message } => assert!(message.starts_with("I\u{2019}m sorry")),
+        other => panic!("expected a refusal, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn provider_refusal_is_reported() {
+    let (resolver, _handle) = resolver_with(MockResponse::Error(AIError::Refused("content filtered".into())));
+    let outcome = resolver.query_outcome::<Summary>("Summarize".into()).await.unwrap();
+    assert!(outcome.is_refused());
+    assert!(matches!(outcome.into_response(), Err(QueryResolverError::Refused(m)) if m == "content filtered"));
+}
+
+#[tokio::test]
+async fn data_and_plain_text_are_extracted() {
+    let (resolver, _handle) = resolver_with(MockResponse::Success(r#"{"text": "I can't help noticing the fees are high."}"#.into()));
+    let outcome = resolver.query_outcome::<Summary>("Summarize".into()).await.unwrap();
+    let QueryOutcome::Extracted(response) = outcome else { panic!("data is not a refusal") };
+    assert!(response.has_data());
+
+    let (resolver, _handle) = resolver_with(MockResponse::Success("The document is empty.".into()));
+    let outcome = resolver.query_outcome::<Summary>("Summarize".into()).await.unwrap();
+    assert!(matches!(outcome, QueryOutcome::Extracted(r) if !r.has_data()));
+}
+
+#[tokio::test]
+async fn rejected_prose_is_checked_for_refusals() {
+    let (resolver, _handle) = resolver_with(MockResponse::Success("I cannot assist with that.".into()));
+    let resolver = resolver.with_response_mode(ResponseMode::JsonOnly).with_prose_policy(ProsePolicy::Reject);
+    assert!(resolver.query_outcome::<Summary>("Summarize".into()).await.unwrap().is_refused());
+}
+
+#[tokio::test]
+async fn custom_detector() {
+    let detector = PhraseRefusalDetector::default().with_phrase("Outside my Scope");
+    assert!(detector.detect("That is outside my scope.").is_some());
+    assert!(detector.detect("Here is the summary.").is_none());
+    assert!(PhraseRefusalDetector::default().with_window(10).detect("Sure! But first: I can't help it").is_none());
+
+    let (resolver, _handle) = resolver_with(MockResponse::Success("That is outside my scope.".into()));
+    let resolver = resolver.with_refusal_detector(Arc::new(detector));
+    assert!(resolver.query_outcome::<Summary>("Summarize".into()).await.unwrap().is_refused());
+}
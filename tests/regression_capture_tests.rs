@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::interceptors::Redactor;
+use semantic_query::schema::JsonSchema;
+use semantic_query::testing::{CaptureMode, RegressionCase, RegressionSuite, CAPTURE_ENV};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Invoice {
+    number: String,
+    total: f64,
+}
+
+/// A mock standing in for a real provider: numbers its `replies` and leaks an account id
+fn provider(replies: usize) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    for call in 1..=replies {
+        handle.add_json_response(&format!(r#"Billed to acct-9931: {{"number": "INV-{call}", "total": 10}}"#));
+    }
+    (client, handle)
+}
+
+fn redactor() -> Option<Redactor> {
+    Some(Redactor::new(|text| *text = text.replace("acct-9931", "[account]")))
+}
+
+#[tokio::test]
+async fn captured_replies_are_redacted_saved_and_replayed() {
+    let mut suite = RegressionSuite::new(vec![
+        RegressionCase::new("first", "Extract the invoice").expect_matches(json!({"total": 10})),
+    ]);
+    let (client, handle) = provider(1);
+    let live = QueryResolver::new(client, RetryConfig::default());
+    assert_eq!(suite.capture::<Invoice, _>(&live, CaptureMode::Missing, redactor()).await.unwrap(), 1);
+    assert_eq!(suite.cases[0].responses, [r#"Billed to [account]: {"number": "INV-1", "total": 10}"#]);
+    assert_eq!(suite.cases[0].provider.as_deref(), Some("mock"));
+
+    let path = std::env::temp_dir().join(format!("semantic-query-golden-{}.json", std::process::id()));
+    suite.save(&path).unwrap();
+    let golden = RegressionSuite::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(golden, suite);
+
+    // Replays never reach the client of the resolver under test
+    let offline = QueryResolver::new(ScriptedClient::Fail(AIError::Mock("offline".into())), RetryConfig::default());
+    golden.run::<Invoice, _>(&offline).await.assert_passed();
+    assert_eq!(handle.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn modes_pick_which_cases_are_captured() {
+    let mut suite = RegressionSuite::new(vec![
+        RegressionCase::new("kept", "a").replying(r#"{"number": "hand-written", "total": 1}"#),
+        RegressionCase::new("missing", "b"),
+    ]);
+    let (client, _handle) = provider(3);
+    let live = QueryResolver::new(client, RetryConfig::default());
+
+    assert_eq!(suite.capture::<Invoice, _>(&live, CaptureMode::Missing, None).await.unwrap(), 1);
+    assert!(suite.cases[0].responses[0].contains("hand-written"));
+    assert!(suite.cases[1].responses[0].contains("INV-1"));
+    assert_eq!(suite.capture::<Invoice, _>(&live, CaptureMode::All, None).await.unwrap(), 2);
+    assert!(suite.cases[0].responses[0].contains("INV-2"));
+    assert!(suite.cases[1].responses[0].contains("INV-3"));
+
+    // A failing provider stops the capture and names the case
+    let mut pending = RegressionSuite::new(vec![RegressionCase::new("down", "c")]);
+    let failing = QueryResolver::new(ScriptedClient::Fail(AIError::Mock("503".into())), RetryConfig::default());
+    let error = pending.capture::<Invoice, _>(&failing, CaptureMode::Missing, None).await.unwrap_err();
+    assert!(error.starts_with("capturing 'down' failed"), "{error}");
+    assert!(pending.cases[0].responses.is_empty());
+}
+
+#[tokio::test]
+async fn cases_without_replies_ask_for_a_capture() {
+    let suite = RegressionSuite::new(vec![RegressionCase::new("uncaptured", "q")]);
+    let (client, _handle) = provider(0);
+    let report = suite.run::<Invoice, _>(&QueryResolver::new(client, RetryConfig::default())).await;
+    assert_eq!(report.failures()[0].detail, format!("no replies to replay; capture them with {CAPTURE_ENV}=missing"));
+
+    // No `regression_suite!` test runs in this binary, so the variable can be set here
+    for (value, mode) in [("all", Some(CaptureMode::All)), ("1", Some(CaptureMode::Missing)), ("0", None)] {
+        std::env::set_var(CAPTURE_ENV, value);
+        assert_eq!(CaptureMode::from_env(), mode);
+    }
+    std::env::remove_var(CAPTURE_ENV);
+    assert_eq!(CaptureMode::from_env(), None);
+}
@@ -0,0 +1,59 @@
+use semantic_query::clients::mock::MockVoid;
+use semantic_query::core::{ProsePolicy, QueryResolver, ResponseMode, RetryConfig};
+use semantic_query::schema::JsonSchema;
+use semantic_query::testing::{RegressionCase, RegressionSuite};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Invoice {
+    number: String,
+    total: f64,
+}
+
+semantic_query::regression_suite!(invoice_cases_pass, Invoice, "tests/cases/invoices.json");
+// Captures (with SEMANTIC_QUERY_CAPTURE set) would come from `resolver()`, redacted
+semantic_query::regression_suite!(invoice_cases_pass_with_settings, Invoice, "tests/cases/invoices.json", resolver(),
+    redact = |text: &mut String| *text = text.replace("INV", "[id]"));
+
+fn resolver() -> QueryResolver<MockVoid> {
+    QueryResolver::new(MockVoid, RetryConfig::default())
+}
+
+#[tokio::test]
+async fn failing_cases_say_what_did_not_hold() {
+    let reply = r#"{"number": "A-1", "total": 12.5, "lines": [{"sku": "x"}]}"#;
+    let suite = RegressionSuite::new(vec![
+        RegressionCase::new("exact", "q").replying(reply).expect_equals(json!({"number": "A-9", "total": 12.5})),
+        RegressionCase::new("subset", "q").replying(reply).expect_matches(json!({"number": "A-1", "total": 12})),
+        RegressionCase::new("count", "q").replying(reply).expect_items(2),
+        RegressionCase::new("error", "q").replying(reply).expect_error("rate limit"),
+        RegressionCase::new("passes", "q").replying(reply).expect_matches(json!({"total": 12.5})),
+    ]);
+    let report = suite.run::<Invoice, _>(&resolver()).await;
+    let failures: Vec<_> = report.failures().iter().map(|case| (case.name.as_str(), case.detail.clone())).collect();
+    assert_eq!(failures.len(), 4, "{report}");
+    assert!(failures[0].1.starts_with(r#"expected {"number":"A-9""#), "{}", failures[0].1);
+    assert!(failures[1].1.starts_with(".total differs"), "{}", failures[1].1);
+    assert!(failures[2].1.starts_with("expected 2 data items, got 1"), "{}", failures[2].1);
+    assert!(failures[3].1.contains("but the query succeeded"), "{}", failures[3].1);
+    assert!(!report.is_success());
+    assert!(report.to_string().contains("  FAILED  subset: .total differs"));
+    assert!(report.to_string().contains("      ok  passes\n"));
+}
+
+#[tokio::test]
+async fn cases_run_with_the_resolver_settings_under_test() {
+    let suite = RegressionSuite::from_json(r#"[
+        {"name": "prose", "prompt": "q", "response": "Here: {\"number\": \"A\", \"total\": 1}", "expect": {"error": "contains prose"}}
+    ]"#).unwrap();
+    let strict = resolver().with_response_mode(ResponseMode::JsonOnly).with_prose_policy(ProsePolicy::Reject);
+    suite.run::<Invoice, _>(&strict).await.assert_passed();
+    // The default resolver extracts the item instead
+    assert!(!suite.run::<Invoice, _>(&resolver()).await.is_success());
+
+    // Typos in a case are rejected rather than silently expecting nothing
+    let typo = RegressionSuite::from_json(r#"[{"name": "t", "prompt": "q", "response": "{}", "expect": {"equal": {}}}]"#);
+    assert!(typo.unwrap_err().to_string().contains("unknown field `equal`"));
+}
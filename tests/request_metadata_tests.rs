@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use schemars::JsonSchema;
+use semantic_query::audit::QueryRecord;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig};
+use semantic_query::request::ModelRequest;
+use semantic_query::stats::QueryStats;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct Answer {
+    value: i32,
+}
+
+/// A mock that answers `{"value": 1}` `replies` times and records the end user of each call
+fn mock(replies: usize) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::User]);
+    handle.add_json_responses(vec![r#"{"value": 1}"#; replies]);
+    (client, handle)
+}
+
+#[tokio::test]
+async fn end_users_reach_the_client() {
+    let (client, handle) = mock(3);
+    let resolver = QueryResolver::new(client.clone(), RetryConfig::default());
+    resolver.query::<Answer>("q".into()).await.unwrap();
+    resolver.for_user("user-7f3a").query::<Answer>("q".into()).await.unwrap();
+    client.ask_request(ModelRequest::user("q").with_user("user-19c2")).await.unwrap();
+    let users: Vec<_> = handle.calls().into_iter().map(|call| call.settings.user).collect();
+    assert_eq!(users, [None, Some("user-7f3a".into()), Some("user-19c2".into())]);
+
+    let request = serde_json::to_value(ModelRequest::user("q").with_user("u1")).unwrap();
+    assert_eq!(request["params"]["user"], "u1");
+}
+
+#[tokio::test]
+async fn tags_reach_stats_and_records() {
+    let stats: Arc<Mutex<Vec<QueryStats>>> = Arc::default();
+    let records: Arc<Mutex<Vec<QueryRecord>>> = Arc::default();
+    let (stats_sink, record_sink) = (stats.clone(), records.clone());
+    let (client, _handle) = mock(1);
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_tag("feature", "invoice-import")
+        .with_tags([("team", "billing")])
+        .with_stats_callback(move |s| stats_sink.lock().unwrap().push(s.clone()))
+        .with_record_callback(move |r| record_sink.lock().unwrap().push(r.clone()));
+    resolver.query::<Answer>("q".into()).await.unwrap();
+
+    let expected = HashMap::from([("feature".to_string(), "invoice-import".to_string()), ("team".to_string(), "billing".to_string())]);
+    assert_eq!(resolver.tags(), &expected);
+    assert_eq!(stats.lock().unwrap()[0].tags, expected);
+    let record = records.lock().unwrap()[0].clone();
+    assert_eq!(record.tags, expected);
+    assert_eq!(serde_json::to_value(&record).unwrap()["tags"]["team"], "billing");
+
+    // Untagged queries leave the field out of the record
+    let untagged = Arc::new(Mutex::new(Vec::new()));
+    let sink = untagged.clone();
+    let (client, _handle) = mock(1);
+    QueryResolver::new(client, RetryConfig::default())
+        .with_record_callback(move |r| sink.lock().unwrap().push(r.clone()))
+        .query::<Answer>("q".into()).await.unwrap();
+    let json = serde_json::to_value(&untagged.lock().unwrap()[0]).unwrap();
+    assert!(json.get("tags").is_none());
+}
+
+#[cfg(feature = "anthropic")]
+mod claude {
+    use semantic_query::clients::claude::{ClaudeConfig, ClaudeModel, ClaudeRequest};
+    use semantic_query::request::ModelRequest;
+
+    #[test]
+    fn user_ids_are_sent_as_metadata() {
+        let config = ClaudeConfig::anthropic("k".into(), ClaudeModel::Haiku35);
+        let body = serde_json::to_value(ClaudeRequest::new("Hi".into(), &config)).unwrap();
+        assert!(body.get("metadata").is_none());
+
+        let config = ClaudeConfig { user_id: Some("user-7f3a".into()), ..config };
+        let body = serde_json::to_value(ClaudeRequest::new("Hi".into(), &config)).unwrap();
+        assert_eq!(body["metadata"], serde_json::json!({"user_id": "user-7f3a"}));
+
+        let request = ClaudeRequest::from_model_request(ModelRequest::user("Hi").with_user("user-19c2"), &config);
+        assert_eq!(request.metadata.unwrap().user_id, "user-19c2");
+    }
+}
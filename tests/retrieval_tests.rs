@@ -0,0 +1,93 @@
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::prompt::PromptBuilder;
+use semantic_query::retrieval::{map_citations, stuff_context, ContextProvider, GroundingOptions, InMemoryContext, Snippet};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Answer {
+    days: u32,
+    explanation: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Claims {
+    claims: Vec<String>,
+}
+
+fn handbook() -> InMemoryContext {
+    InMemoryContext::new([
+        ("leave", "Employees get 25 days of annual leave."),
+        ("parking", "Parking permits are issued by the front desk."),
+        ("sick", "Sick leave requires a doctor's note after three days."),
+    ])
+}
+
+#[tokio::test]
+async fn in_memory_context_scores_by_shared_words() {
+    let snippets = handbook().retrieve("How many days of annual leave?", 10).await.unwrap();
+    let ids: Vec<&str> = snippets.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["leave", "sick"]);
+    assert!(snippets[0].score > snippets[1].score);
+}
+
+#[test]
+fn stuffing_orders_snippets_by_score_and_drops_the_weakest_over_budget() {
+    let snippets = vec![
+        Snippet::new("weak", "w ".repeat(200), 0.1),
+        Snippet::new("strong", "s ".repeat(200), 0.9).with_source("guide.md"),
+    ];
+
+    let roomy = stuff_context(PromptBuilder::new(), &snippets, "Question?");
+    assert_eq!(roomy.included.len(), 2);
+    assert_eq!(roomy.included[0].marker, "[S1]");
+    assert_eq!(roomy.included[0].snippet.id, "strong");
+    assert!(roomy.rendered.prompt.starts_with("### [S1] guide.md\n"));
+    assert!(roomy.rendered.prompt.ends_with("Question?"));
+
+    // Room for the strong snippet but not both: the weak one goes first
+    let tight = stuff_context(PromptBuilder::new().max_tokens(110), &snippets, "Question?");
+    assert!(tight.rendered.fits());
+    let ids: Vec<&str> = tight.included.iter().map(|c| c.snippet.id.as_str()).collect();
+    assert_eq!(ids, ["strong"]);
+    assert!(!tight.included[0].truncated);
+    assert!(tight.snippet("[S2]").is_none());
+}
+
+#[test]
+fn citations_map_markers_to_snippet_ids() {
+    let snippets = vec![Snippet::new("a", "alpha", 0.9), Snippet::new("b", "beta", 0.5)];
+    let stuffed = stuff_context(PromptBuilder::new(), &snippets, "Question?");
+    let data = Claims { claims: vec!["Alpha holds [S1].".into(), "Both agree [S2][S1], unlike [S7].".into()] };
+
+    let citations = map_citations(&data, &stuffed);
+    let mapped: Vec<(&str, &str, Option<&str>)> = citations.iter()
+        .map(|c| (c.path.as_str(), c.marker.as_str(), c.snippet_id.as_deref()))
+        .collect();
+    assert_eq!(mapped, [
+        ("/claims/0", "[S1]", Some("a")),
+        ("/claims/1", "[S2]", Some("b")),
+        ("/claims/1", "[S1]", Some("a")),
+        ("/claims/1", "[S7]", None),
+    ]);
+}
+
+#[tokio::test]
+async fn query_grounded_returns_data_with_resolved_citations() {
+    let (client, mock) = MockClient::new();
+    mock.add_json_response(r#"{"days": 25, "explanation": "Annual leave is 25 days [S1]."}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let grounded = resolver
+        .query_grounded::<Answer>("How many days of annual leave?".into(), &handbook(), &GroundingOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(grounded.data.days, 25);
+    assert_eq!(grounded.snippets[0].snippet.id, "leave");
+    assert_eq!(grounded.citations.len(), 1);
+    assert_eq!(grounded.citations[0].path, "/explanation");
+    assert_eq!(grounded.citations[0].snippet_id.as_deref(), Some("leave"));
+    assert!(grounded.cuts.is_empty());
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::PipelineError;
+use semantic_query::pipeline::Pipeline;
+use semantic_query::retry::{self, RetryBudget, StrictJson};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Estimate {
+    hours: u32,
+}
+
+/// A mock that never answers with JSON, with more replies queued than any test asks for
+fn vague() -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec!["Hard to say, a few hours maybe."; 20]);
+    (client, handle)
+}
+
+/// Retries replies without data up to ten times
+fn generous() -> RetryConfig {
+    let mut config = RetryConfig::default().with_strategy(retry::NO_DATA, Arc::new(StrictJson));
+    config.max_retries.insert(retry::NO_DATA.to_string(), 10);
+    config
+}
+
+#[tokio::test]
+async fn resolver_budget_caps_retries_across_queries() {
+    let (client, handle) = vague();
+    let budget = RetryBudget::attempts(3);
+    let resolver = QueryResolver::new(client, generous()).with_retry_budget(budget.clone());
+
+    let response = resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert!(!response.has_data());
+    assert_eq!(handle.calls().len(), 4);
+    assert_eq!((budget.spent(), budget.remaining()), (3, Some(0)));
+    assert!(budget.is_exhausted());
+
+    // Clones share the allowance, so the next query gets no retries at all
+    resolver.clone().query::<Estimate>("estimate again".into()).await.unwrap();
+    assert_eq!(handle.calls().len(), 5);
+}
+
+#[tokio::test]
+async fn scoped_budget_is_shared_by_everything_inside() {
+    let (client, handle) = vague();
+    let resolver = QueryResolver::new(client, generous());
+    let budget = RetryBudget::attempts(2);
+
+    retry::budget_scope(budget.clone(), async {
+        resolver.query::<Estimate>("first".into()).await.unwrap();
+        resolver.query::<Estimate>("second".into()).await.unwrap();
+    }).await;
+    assert_eq!(handle.calls().len(), 4);
+    assert_eq!(budget.spent(), 2);
+
+    // Outside the scope the per-class limit applies again
+    resolver.query::<Estimate>("third".into()).await.unwrap();
+    assert_eq!(handle.calls().len(), 15);
+}
+
+#[tokio::test]
+async fn nested_scopes_spend_from_every_budget() {
+    let (client, handle) = vague();
+    let resolver = QueryResolver::new(client, generous());
+    let outer = RetryBudget::attempts(5);
+    let inner = RetryBudget::attempts(1);
+
+    retry::budget_scope(outer.clone(), async {
+        retry::budget_scope(inner.clone(), resolver.query::<Estimate>("inner".into())).await.unwrap();
+        resolver.query::<Estimate>("outer".into()).await.unwrap();
+    }).await;
+    assert_eq!((inner.spent(), outer.spent()), (1, 5));
+    assert_eq!(handle.calls().len(), 7);
+}
+
+#[tokio::test]
+async fn pipeline_budget_covers_step_and_query_retries() {
+    let (client, handle) = vague();
+    let resolver = QueryResolver::new(client, generous());
+    let budget = RetryBudget::attempts(4);
+
+    let err = Pipeline::start::<Estimate>("estimate")
+        .with_retries(10)
+        .with_retry_budget(budget.clone())
+        .run(&resolver)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PipelineError::Step { index: 0, .. }));
+    assert_eq!(handle.calls().len(), 5);
+    assert_eq!(budget.spent(), 4);
+}
+
+#[tokio::test]
+async fn deadline_stops_retries() {
+    let (client, handle) = vague();
+    let budget = RetryBudget::within(Duration::ZERO);
+    assert!(budget.is_exhausted());
+    assert!(!budget.try_spend());
+
+    let resolver = QueryResolver::new(client, generous()).with_retry_budget(budget.clone());
+    resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert_eq!(handle.calls().len(), 1);
+    assert_eq!(budget.spent(), 0);
+
+    let open = RetryBudget::attempts(2).with_deadline(Duration::from_secs(60));
+    assert!(open.try_spend() && open.try_spend() && !open.try_spend());
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::clients::mock::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::retry::{self, AddExample, AppendFailure, Escalate, Sequence, StrictJson, STRICT_JSON_INSTRUCTION};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Estimate {
+    hours: u32,
+}
+
+/// A mock answering with `replies` in order
+fn scripted(replies: Vec<&str>) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(replies);
+    (client, handle)
+}
+
+fn positive(e: Estimate) -> Result<Estimate, String> {
+    if e.hours == 0 { Err("hours must be positive".to_string()) } else { Ok(e) }
+}
+
+#[tokio::test]
+async fn strict_json_retries_replies_without_data() {
+    let (client, handle) = scripted(vec!["I think about three hours.", r#"{"hours": 3}"#]);
+    let config = RetryConfig::default().with_strategy(retry::NO_DATA, Arc::new(StrictJson));
+    let resolver = QueryResolver::new(client, config);
+
+    let response = resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Estimate { hours: 3 }));
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].starts_with(&prompts[0]));
+    assert!(prompts[1].ends_with(STRICT_JSON_INSTRUCTION));
+}
+
+#[tokio::test]
+async fn replies_without_data_are_kept_without_a_strategy() {
+    let (client, handle) = scripted(vec!["I think about three hours."]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let response = resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert!(!response.has_data());
+    assert_eq!(handle.prompts().len(), 1);
+}
+
+#[tokio::test]
+async fn append_failure_repeats_the_prompt_with_the_error() {
+    let (client, handle) = scripted(vec![r#"{"hours": 0}"#, r#"{"hours": 2}"#]);
+    let config = RetryConfig::default().with_strategy(retry::POST_PROCESS, Arc::new(AppendFailure));
+    let resolver = QueryResolver::new(client, config).with_post_processor(positive);
+
+    let response = resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Estimate { hours: 2 }));
+
+    let prompts = handle.prompts();
+    assert!(prompts[1].starts_with(&prompts[0]));
+    assert!(prompts[1].contains(r#"{"hours": 0}"#));
+    assert!(prompts[1].contains("hours must be positive"));
+}
+
+#[tokio::test]
+async fn strategies_fail_once_retries_run_out() {
+    let (client, handle) = scripted(vec![r#"{"hours": 0}"#, r#"{"hours": 0}"#]);
+    let mut config = RetryConfig::default().with_strategy(retry::POST_PROCESS, Arc::new(AppendFailure));
+    config.max_retries.insert(retry::POST_PROCESS.into(), 1);
+    let resolver = QueryResolver::new(client, config).with_post_processor(positive);
+
+    let result = resolver.query::<Estimate>("estimate".into()).await;
+    assert!(matches!(result, Err(QueryResolverError::PostProcessing(e)) if e.contains("hours must be positive")));
+    assert_eq!(handle.prompts().len(), 2);
+}
+
+#[tokio::test]
+async fn escalate_sends_the_original_prompt_to_another_client() {
+    let (weak, weak_handle) = scripted(vec!["no idea"]);
+    let (strong, strong_handle) = scripted(vec![r#"{"hours": 5}"#]);
+    let config = RetryConfig::default().with_strategy(retry::NO_DATA, Arc::new(Escalate::new(Box::new(strong))));
+    let resolver = QueryResolver::new(weak, config);
+
+    let response = resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Estimate { hours: 5 }));
+    assert_eq!(strong_handle.prompts(), weak_handle.prompts());
+}
+
+#[tokio::test]
+async fn sequence_uses_one_strategy_per_retry() {
+    let (client, handle) = scripted(vec!["no idea", "still no idea", "nope", r#"{"hours": 1}"#]);
+    let sequence = Sequence::new(vec![Arc::new(StrictJson), Arc::new(AddExample::of(&Estimate { hours: 4 }))]);
+    let mut config = RetryConfig::default().with_strategy(retry::NO_DATA, Arc::new(sequence));
+    config.max_retries.insert(retry::NO_DATA.into(), 3);
+    let resolver = QueryResolver::new(client, config);
+
+    let response = resolver.query::<Estimate>("estimate".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Estimate { hours: 1 }));
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 4);
+    assert!(prompts[1].ends_with(STRICT_JSON_INSTRUCTION));
+    assert!(prompts[2].contains("example of a valid answer"));
+    assert!(prompts[3].contains("example of a valid answer"));
+}
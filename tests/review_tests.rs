@@ -0,0 +1,75 @@
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::review::{InMemoryReviewQueue, ReviewOutcome, ReviewResolution};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Invoice {
+    total: f64,
+    confidence: f64,
+}
+
+fn low_confidence(invoice: &Invoice) -> Option<String> {
+    (invoice.confidence < 0.8).then(|| format!("confidence {}", invoice.confidence))
+}
+
+fn resolver(reply: &str) -> QueryResolver<ScriptedClient> {
+    QueryResolver::new(ScriptedClient::Reply(reply.into()), RetryConfig::default())
+}
+
+#[tokio::test]
+async fn confident_extractions_are_accepted() {
+    let queue = InMemoryReviewQueue::new();
+    let outcome = resolver(r#"{"total": 12.0, "confidence": 0.95}"#)
+        .query_reviewed::<Invoice, _>("Extract".into(), &low_confidence, &queue)
+        .await
+        .unwrap();
+    assert!(matches!(outcome, ReviewOutcome::Accepted(r) if r.first().unwrap().total == 12.0));
+    assert!(queue.pending().is_empty());
+}
+
+#[tokio::test]
+async fn flagged_extractions_wait_for_a_reviewer() {
+    let queue = InMemoryReviewQueue::new();
+    let reply = r#"Best guess: {"total": 12.0, "confidence": 0.4}"#;
+    let outcome = resolver(reply)
+        .query_reviewed::<Invoice, _>("Extract the invoice".into(), &low_confidence, &queue)
+        .await
+        .unwrap();
+    let ReviewOutcome::Pending { id, reason } = outcome else { panic!("expected a pending review") };
+    assert_eq!(reason, "confidence 0.4");
+
+    let pending = queue.pending();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, id);
+    assert_eq!(pending[0].prompt, "Extract the invoice");
+    assert!(pending[0].raw_response.contains("Best guess"));
+    assert_eq!(pending[0].candidate, json!({"total": 12.0, "confidence": 0.4}));
+    assert!(queue.resolved::<Invoice>(&id).is_none());
+
+    queue.resolve(&id, ReviewResolution::Correct(json!({"total": 21.0, "confidence": 1.0}))).unwrap();
+    assert!(queue.pending().is_empty());
+    assert_eq!(queue.resolved::<Invoice>(&id).unwrap().unwrap(), Invoice { total: 21.0, confidence: 1.0 });
+}
+
+#[test]
+fn unknown_reviews_cannot_be_resolved() {
+    let queue = InMemoryReviewQueue::new();
+    assert!(matches!(queue.resolve("missing", ReviewResolution::Approve), Err(QueryResolverError::Review(_))));
+}
+
+#[tokio::test]
+async fn approve_and_reject() {
+    let queue = InMemoryReviewQueue::new();
+    let resolver = resolver(r#"{"total": 5.0, "confidence": 0.1}"#);
+    let ReviewOutcome::Pending { id, .. } = resolver.query_reviewed::<Invoice, _>("a".into(), &low_confidence, &queue).await.unwrap() else {
+        panic!("expected a pending review")
+    };
+    let request = queue.get(&id).unwrap();
+    assert_eq!(request.resolve::<Invoice>(&ReviewResolution::Approve).unwrap().total, 5.0);
+    assert!(matches!(
+        request.resolve::<Invoice>(&ReviewResolution::Reject("not an invoice".into())),
+        Err(QueryResolverError::ReviewRejected(r)) if r == "not an invoice"
+    ));
+}
@@ -0,0 +1,85 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use semantic_query::runtime::{self, compat};
+use semantic_query::schema::JsonSchema;
+use semantic_query::streaming::{stream_from_async_read, stream_from_futures_read, StreamItem};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Answer {
+    value: i32,
+}
+
+const REPLY: &str = "Sure: {\"value\": 1} and {\"value\": 2}.";
+
+/// A futures-io reader handing out a few bytes per read
+struct Trickle(&'static [u8]);
+
+impl futures_io::AsyncRead for Trickle {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let n = buf.len().min(self.0.len()).min(5);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Poll::Ready(Ok(n))
+    }
+}
+
+fn data(items: Vec<StreamItem<Answer>>) -> Vec<Answer> {
+    items.into_iter().filter_map(|item| match item {
+        StreamItem::Data(answer) => Some(answer),
+        _ => None,
+    }).collect()
+}
+
+#[tokio::test]
+async fn futures_io_readers_stream_like_tokio_ones() {
+    let from_futures: Vec<StreamItem<Answer>> = stream_from_futures_read(Trickle(REPLY.as_bytes()), 1024).collect().await;
+    let from_tokio: Vec<StreamItem<Answer>> = stream_from_async_read(std::io::Cursor::new(REPLY), 1024).collect().await;
+    assert_eq!(data(from_futures), [Answer { value: 1 }, Answer { value: 2 }]);
+    assert_eq!(data(from_tokio), [Answer { value: 1 }, Answer { value: 2 }]);
+
+    // And back: a tokio reader read through futures-io
+    let mut reader = compat::into_futures_read(std::io::Cursor::new(REPLY));
+    let mut buf = [0; 64];
+    let n = std::future::poll_fn(|cx| futures_io::AsyncRead::poll_read(Pin::new(&mut reader), cx, &mut buf)).await.unwrap();
+    assert_eq!(&buf[..n], REPLY.as_bytes());
+}
+
+#[tokio::test]
+async fn runtime_touchpoints_run_on_the_selected_runtime() {
+    assert_eq!(runtime::timeout(Duration::from_secs(1), async { 7 }).await, Ok(7));
+    let slow = runtime::timeout(Duration::from_millis(10), runtime::sleep(Duration::from_secs(5))).await;
+    assert_eq!(slow, Err(runtime::Elapsed));
+    assert_eq!(runtime::spawn_blocking(|| 2 + 2).await, 4);
+
+    let dir = std::env::temp_dir().join(format!("semantic-query-runtime-{}", std::process::id()));
+    runtime::fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join("state.json");
+    runtime::fs::write(&path, "{}").await.unwrap();
+    assert!(runtime::fs::try_exists(&path).await.unwrap());
+    runtime::fs::rename(&path, dir.join("moved.json")).await.unwrap();
+    assert_eq!(runtime::fs::read_to_string(dir.join("moved.json")).await.unwrap(), "{}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Run with `--no-default-features --features schemars-1,rt-async-std`
+#[cfg(feature = "rt-async-std")]
+#[test]
+fn queries_run_without_a_tokio_runtime() {
+    use semantic_query::client_testkit::ScriptedClient;
+    use semantic_query::core::{QueryResolver, RetryConfig};
+    use semantic_query::shutdown::ShutdownHandle;
+
+    async_std::task::block_on(async {
+        let shutdown = ShutdownHandle::new();
+        let resolver = QueryResolver::new(ScriptedClient::Reply(REPLY.into()), RetryConfig::default())
+            .with_shutdown(shutdown.clone());
+        assert_eq!(resolver.query::<Answer>("q".into()).await.unwrap().data_count(), 2);
+        assert!(shutdown.shutdown(Duration::from_millis(10)).await.is_clean());
+        runtime::sleep(Duration::from_millis(1)).await;
+    });
+}
@@ -0,0 +1,109 @@
+use std::any::TypeId;
+use std::sync::{Arc, Mutex};
+
+use schemars::JsonSchema;
+use semantic_query::clients::mock::MockClient;
+use semantic_query::core::{QueryResolver, ResponseMode, RetryConfig};
+use semantic_query::schema::{cached_schema, schema_from_json, Schema, SchemaCache, SchemaGenerator};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Invoice {
+    total: f64,
+}
+
+/// The guidance appended after the prompt `marker`
+fn guidance(prompts: &[String], marker: &str) -> String {
+    let prompt = prompts.iter().find(|p| p.starts_with(marker)).unwrap();
+    prompt[marker.len()..].to_string()
+}
+
+#[tokio::test]
+async fn rendered_guidance_is_kept_apart_per_type_and_mode() {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec!["nothing here"; 8]);
+    let mixed = QueryResolver::new(client, RetryConfig::default());
+    let json_only = mixed.clone().with_response_mode(ResponseMode::JsonOnly);
+
+    for round in 0..2 {
+        mixed.query::<Contact>(format!("contact {round}")).await.unwrap();
+        mixed.query::<Vec<Contact>>(format!("contacts {round}")).await.unwrap();
+        mixed.query::<Invoice>(format!("invoice {round}")).await.unwrap();
+        let _ = json_only.query::<Contact>(format!("strict {round}")).await;
+    }
+    let prompts = handle.prompts();
+
+    let contact = guidance(&prompts, "contact 0");
+    assert!(contact.contains("\"name\"") && !contact.contains("\"total\""));
+    assert!(guidance(&prompts, "invoice 0").contains("\"total\""));
+    assert!(guidance(&prompts, "contacts 0").contains("\"array\""));
+    assert!(guidance(&prompts, "strict 0").contains("Respond with JSON only"));
+    assert!(!contact.contains("Respond with JSON only"));
+
+    // Later queries reuse the same text
+    for marker in ["contact", "contacts", "invoice", "strict"] {
+        assert_eq!(guidance(&prompts, &format!("{marker} 0")), guidance(&prompts, &format!("{marker} 1")));
+    }
+}
+
+/// Statuses the `Ticket` schema offers; changes at runtime
+static STATUSES: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+fn status_schema(_generator: &mut SchemaGenerator) -> Schema {
+    schema_from_json(json!({ "type": "string", "enum": *STATUSES.lock().unwrap() }))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Ticket {
+    #[schemars(schema_with = "status_schema")]
+    status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Warmed {
+    id: u32,
+}
+
+#[test]
+fn schemas_are_generated_once_until_invalidated() {
+    let cache = SchemaCache::global();
+    *STATUSES.lock().unwrap() = vec!["open", "closed"];
+    let first = cached_schema::<Ticket>();
+    assert!(Arc::ptr_eq(&first, &cache.get::<Ticket>()));
+
+    // The cached schema outlives the state it was generated from...
+    STATUSES.lock().unwrap().push("escalated");
+    assert_eq!(cache.get::<Ticket>()["properties"]["status"]["enum"], json!(["open", "closed"]));
+
+    // ...until the type is invalidated
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let hook_seen = seen.clone();
+    cache.on_invalidate(move |id| hook_seen.lock().unwrap().push(id));
+    cache.invalidate::<Ticket>();
+    assert!(!cache.contains::<Ticket>());
+    assert!(seen.lock().unwrap().contains(&Some(TypeId::of::<Ticket>())));
+    assert_eq!(cache.get::<Ticket>()["properties"]["status"]["enum"], json!(["open", "closed", "escalated"]));
+}
+
+#[test]
+fn warming_fills_the_cache_and_clear_empties_it() {
+    assert!(!SchemaCache::global().contains::<Warmed>());
+    SchemaCache::global().warm::<Warmed>();
+    assert!(SchemaCache::global().contains::<Warmed>());
+
+    let cache = SchemaCache::default();
+    let cleared = Arc::new(Mutex::new(Vec::new()));
+    let hook_cleared = cleared.clone();
+    cache.on_invalidate(move |id| hook_cleared.lock().unwrap().push(id));
+    cache.warm::<Warmed>().warm::<Contact>();
+    assert_eq!(cache.len(), 2);
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(*cleared.lock().unwrap(), [None]);
+}
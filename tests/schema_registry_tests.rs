@@ -0,0 +1,85 @@
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::{QueryResolverError, SchemaRegistryError};
+use semantic_query::schema_registry::{SchemaRef, SchemaRegistry};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct InvoiceV1 {
+    amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Invoice {
+    total: f64,
+    currency: String,
+}
+
+fn registry() -> SchemaRegistry {
+    SchemaRegistry::new()
+        .with_type::<InvoiceV1>("invoice", 1)
+        .with_type::<Invoice>("invoice", 2)
+        .with_migration("invoice", 1, |v| {
+            let amount = v.get("amount").cloned().ok_or("missing amount")?;
+            Ok(json!({ "total": amount, "currency": "EUR" }))
+        })
+}
+
+#[test]
+fn references_parse_and_resolve() {
+    assert_eq!("invoice@v2".parse::<SchemaRef>().unwrap(), SchemaRef { name: "invoice".into(), version: Some(2) });
+    assert_eq!("invoice@2".parse::<SchemaRef>().unwrap().version, Some(2));
+    assert!(matches!("invoice@latest".parse::<SchemaRef>(), Err(SchemaRegistryError::InvalidReference(_))));
+
+    let registry = registry();
+    assert_eq!(registry.versions("invoice"), vec![1, 2]);
+    assert_eq!(registry.resolve("invoice").unwrap().0, 2);
+    assert!(registry.resolve("invoice@v1").unwrap().1.to_string().contains("amount"));
+    assert_eq!(
+        registry.resolve("invoice@v7").unwrap_err(),
+        SchemaRegistryError::UnknownVersion { name: "invoice".into(), version: 7 }
+    );
+    assert_eq!(registry.resolve("receipt").unwrap_err(), SchemaRegistryError::UnknownSchema("receipt".into()));
+}
+
+#[test]
+fn migrations_chain_to_the_latest_version() {
+    let registry = registry()
+        .with_schema("invoice", 3, json!({ "type": "object" }))
+        .with_migration("invoice", 2, |mut v| {
+            v["paid"] = json!(false);
+            Ok(v)
+        });
+    let migrated = registry.migrate("invoice", 1, json!({ "amount": 12.5 })).unwrap();
+    assert_eq!(migrated, json!({ "total": 12.5, "currency": "EUR", "paid": false }));
+
+    let gap = registry.with_schema("invoice", 4, json!({}));
+    assert_eq!(
+        gap.migrate("invoice", 3, json!({})).unwrap_err(),
+        SchemaRegistryError::MissingMigration { name: "invoice".into(), from: 3 }
+    );
+}
+
+#[test]
+fn records_round_trip() {
+    let registry = registry();
+    let records = registry.records();
+    let restored = SchemaRegistry::from_records(serde_json::from_value::<Vec<_>>(serde_json::to_value(&records).unwrap()).unwrap());
+    assert_eq!(restored.records(), records);
+}
+
+#[tokio::test]
+async fn old_version_queries_are_migrated_into_the_current_type() {
+    let client = ScriptedClient::Reply(r#"Here it is: {"amount": 40.0}"#.into());
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let response = resolver.query_versioned::<Invoice>(&registry(), "invoice@v1", "Extract".into()).await.unwrap();
+    assert_eq!(response.first_required().unwrap(), Invoice { total: 40.0, currency: "EUR".into() });
+}
+
+#[tokio::test]
+async fn failing_migration_is_an_error() {
+    let client = ScriptedClient::Reply(r#"{"sum": 40.0}"#.into());
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let err = resolver.query_versioned::<Invoice>(&registry(), "invoice@v1", "Extract".into()).await.unwrap_err();
+    assert!(matches!(err, QueryResolverError::SchemaRegistry(SchemaRegistryError::Migration { from: 1, .. })));
+}
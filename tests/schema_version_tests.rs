@@ -0,0 +1,50 @@
+#[cfg(feature = "anthropic")]
+use semantic_query::clients::claude::ClaudeTool;
+use semantic_query::grammar::OutputConstraint;
+use semantic_query::schema::{schema_from_json, schema_value, JsonSchema, Schema, SchemaGenerator};
+use serde_json::json;
+
+#[derive(Debug, Clone, serde::Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Line {
+    sku: String,
+    #[schemars(schema_with = "quantity_schema")]
+    quantity: u32,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Order {
+    lines: Vec<Line>,
+}
+
+fn quantity_schema(_generator: &mut SchemaGenerator) -> Schema {
+    schema_from_json(json!({ "type": "integer", "minimum": 1 }))
+}
+
+/// Shared definitions live under `$defs` (1.x) or `definitions` (0.8)
+fn definition<'a>(schema: &'a serde_json::Value, name: &str) -> &'a serde_json::Value {
+    schema.get("$defs").or_else(|| schema.get("definitions")).and_then(|defs| defs.get(name)).unwrap()
+}
+
+#[test]
+fn types_derived_through_the_re_export_satisfy_the_bounds() {
+    let schema = schema_value::<Order>();
+    assert_eq!(schema["properties"]["lines"]["type"], "array");
+    assert_eq!(definition(&schema, "Line")["properties"]["quantity"], json!({ "type": "integer", "minimum": 1 }));
+
+    let order: Order = serde_json::from_value(json!({ "lines": [{ "sku": "A-1", "quantity": 2 }] })).unwrap();
+    assert_eq!((order.lines[0].sku.as_str(), order.lines[0].quantity), ("A-1", 2));
+}
+
+#[test]
+fn generated_schemas_feed_grammars() {
+    let constraint = OutputConstraint::for_type::<Order>().unwrap();
+    assert!(constraint.gbnf.contains("root"));
+}
+
+#[cfg(feature = "anthropic")]
+#[test]
+fn generated_schemas_feed_tools() {
+    assert_eq!(ClaudeTool::for_type::<Order>("order", "Record an order").input_schema, schema_value::<Order>());
+}
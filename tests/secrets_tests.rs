@@ -0,0 +1,31 @@
+use semantic_query::config::SemanticQueryConfig;
+use semantic_query::secrets::{ChainedSecrets, DotenvSecrets, SecretProvider, StaticSecrets};
+
+#[tokio::test]
+async fn chained_secrets_prefers_earlier_providers() {
+    let secrets = ChainedSecrets::new()
+        .with(StaticSecrets::new().with("DEEPSEEK_API_KEY", "from-first"))
+        .with(StaticSecrets::new().with("DEEPSEEK_API_KEY", "from-second").with("OPENAI_API_KEY", "sk-openai"));
+
+    assert_eq!(secrets.get_secret("DEEPSEEK_API_KEY").await.unwrap().as_deref(), Some("from-first"));
+    assert_eq!(secrets.get_secret("OPENAI_API_KEY").await.unwrap().as_deref(), Some("sk-openai"));
+    assert_eq!(secrets.get_secret("MISSING").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn resolve_secrets_fills_provider_keys() {
+    let mut config = SemanticQueryConfig::default();
+    config.resolve_secrets(&StaticSecrets::new().with("ANTHROPIC_API_KEY", "sk-ant")).await.unwrap();
+    assert_eq!(config.anthropic.api_key.as_deref(), Some("sk-ant"));
+    assert!(config.deepseek.api_key.is_none());
+}
+
+#[tokio::test]
+async fn dotenv_secrets_reads_without_touching_env() {
+    let path = std::env::temp_dir().join(format!("sq_secrets_{}.env", std::process::id()));
+    std::fs::write(&path, "SQ_TEST_ONLY_SECRET=abc123\n").unwrap();
+    let secrets = DotenvSecrets::new(&path);
+    assert_eq!(secrets.get_secret("SQ_TEST_ONLY_SECRET").await.unwrap().as_deref(), Some("abc123"));
+    assert!(std::env::var("SQ_TEST_ONLY_SECRET").is_err());
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use schemars::{schema_for, JsonSchema};
+use semantic_query::serde_helpers::{parse_date, parse_datetime, parse_enum, parse_number};
+use semantic_query::streaming::{build_parsed_stream, StreamItem};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Invoice {
+    #[serde(with = "semantic_query::serde_helpers::date")]
+    #[schemars(schema_with = "semantic_query::serde_helpers::date::schema")]
+    due: NaiveDate,
+    #[serde(with = "semantic_query::serde_helpers::datetime")]
+    #[schemars(schema_with = "semantic_query::serde_helpers::datetime::schema")]
+    issued: DateTime<Utc>,
+    #[serde(with = "semantic_query::serde_helpers::number")]
+    #[schemars(with = "f64")]
+    total: f64,
+    #[serde(with = "semantic_query::serde_helpers::number")]
+    #[schemars(with = "u32")]
+    quantity: u32,
+    #[serde(default, with = "semantic_query::serde_helpers::date::option")]
+    #[schemars(schema_with = "semantic_query::serde_helpers::date::option::schema")]
+    paid: Option<NaiveDate>,
+}
+
+fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+#[test]
+fn dates_in_common_layouts() {
+    for text in ["2024-01-03", "Jan 3, 2024", "January 3rd, 2024", "3 January 2024", "01/03/2024", "2024/01/03", "03.01.2024", "2024-01-03T18:30:00Z"] {
+        assert_eq!(parse_date(text), Some(ymd(2024, 1, 3)), "{text}");
+    }
+    assert_eq!(parse_date("sometime soon"), None);
+}
+
+#[test]
+fn timestamps_in_common_layouts() {
+    let expected = ymd(2024, 1, 3).and_hms_opt(10, 0, 0).unwrap().and_utc();
+    for text in ["2024-01-03T10:00:00Z", "2024-01-03T12:00:00+02:00", "2024-01-03 10:00", "2024-01-03T10:00:00.000"] {
+        assert_eq!(parse_datetime(text), Some(expected), "{text}");
+    }
+    assert_eq!(parse_datetime("Jan 3, 2024"), Some(ymd(2024, 1, 3).and_hms_opt(0, 0, 0).unwrap().and_utc()));
+}
+
+#[test]
+fn numbers_from_strings() {
+    assert_eq!(parse_number::<f64>("$1,234.50"), Some(1234.5));
+    assert_eq!(parse_number::<u32>(" 3.0 "), Some(3));
+    assert_eq!(parse_number::<i64>("1_000"), Some(1000));
+    assert_eq!(parse_number::<u32>("3.5"), None);
+}
+
+#[test]
+fn lenient_fields_survive_extraction() {
+    let raw = r#"Invoice: {"due": "Jan 3, 2024", "issued": 1704276000, "total": "$1,234.50", "quantity": "2", "paid": ""}"#;
+    let items: Vec<StreamItem<Invoice>> = build_parsed_stream(raw);
+    let invoice = items.into_iter().find_map(|i| match i { StreamItem::Data(d) => Some(d), _ => None }).unwrap();
+    assert_eq!(invoice.due, ymd(2024, 1, 3));
+    assert_eq!(invoice.issued, DateTime::from_timestamp(1704276000, 0).unwrap());
+    assert_eq!(invoice.total, 1234.5);
+    assert_eq!(invoice.quantity, 2);
+    assert_eq!(invoice.paid, None);
+
+    let json = serde_json::to_value(&invoice).unwrap();
+    assert_eq!(json["due"], "2024-01-03");
+    assert_eq!(json["total"], 1234.5);
+}
+
+#[test]
+fn schemas_ask_for_canonical_formats() {
+    let schema = serde_json::to_value(schema_for!(Invoice)).unwrap();
+    assert_eq!(schema["properties"]["due"]["format"], "date");
+    assert_eq!(schema["properties"]["issued"]["format"], "date-time");
+    assert_eq!(schema["properties"]["quantity"]["type"], "integer");
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Priority {
+    Low,
+    /// Needs attention today
+    #[serde(alias = "urgent")]
+    High,
+    InProgress,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Ticket {
+    #[serde(with = "semantic_query::lenient_enum")]
+    #[schemars(schema_with = "semantic_query::lenient_enum::schema::<Priority>")]
+    priority: Priority,
+    #[serde(default, with = "semantic_query::lenient_enum::option")]
+    #[schemars(schema_with = "semantic_query::lenient_enum::option::schema::<Priority>")]
+    previous: Option<Priority>,
+}
+
+#[test]
+fn enum_variants_in_any_case_or_alias() {
+    for text in ["high", "HIGH", "High", " high ", "urgent", "URGENT", "Urgent"] {
+        assert_eq!(parse_enum::<Priority>(text), Some(Priority::High), "{text}");
+    }
+    for text in ["in_progress", "IN_PROGRESS", "In Progress", "in-progress", "InProgress"] {
+        assert_eq!(parse_enum::<Priority>(text), Some(Priority::InProgress), "{text}");
+    }
+    assert_eq!(parse_enum::<Priority>("medium"), None);
+}
+
+#[test]
+fn lenient_enums_survive_extraction() {
+    let raw = r#"Triage: {"priority": "URGENT", "previous": "In Progress"} and {"priority": "Low", "previous": ""}"#;
+    let tickets: Vec<Ticket> = build_parsed_stream(raw).into_iter()
+        .filter_map(|i| match i { StreamItem::Data(d) => Some(d), _ => None })
+        .collect();
+    assert_eq!(tickets, vec![
+        Ticket { priority: Priority::High, previous: Some(Priority::InProgress) },
+        Ticket { priority: Priority::Low, previous: None },
+    ]);
+    assert_eq!(serde_json::to_value(&tickets[0]).unwrap()["priority"], "high");
+
+    let err = serde_json::from_str::<Ticket>(r#"{"priority": "medium"}"#).unwrap_err();
+    assert!(err.to_string().contains("expected one of the enum variants"), "{err}");
+
+    let schema = serde_json::to_value(schema_for!(Ticket)).unwrap();
+    assert_eq!(schema["properties"]["priority"]["$ref"], "#/$defs/Priority");
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuids_in_any_form() {
+    #[derive(Deserialize)]
+    struct Row {
+        #[serde(with = "semantic_query::serde_helpers::uuid")]
+        id: uuid::Uuid,
+    }
+    let expected = uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+    for text in ["67e55044-10b1-426f-9247-bb680e5fe0c8", " {67E55044-10B1-426F-9247-BB680E5FE0C8} ", "urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8", "67e5504410b1426f9247bb680e5fe0c8"] {
+        let row: Row = serde_json::from_value(serde_json::json!({ "id": text })).unwrap();
+        assert_eq!(row.id, expected, "{text}");
+    }
+}
@@ -0,0 +1,53 @@
+use schemars::JsonSchema;
+use semantic_query::core::{ParsedResponse, ResponseItem};
+use semantic_query::streaming::{StreamItem, TextContent};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Point {
+    x: i32,
+}
+
+#[test]
+fn stream_items_use_the_documented_layout() {
+    let items: Vec<StreamItem<Point>> = vec![
+        StreamItem::Text(TextContent { text: "hi".into(), block: None }),
+        StreamItem::Data(Point { x: 1 }),
+        StreamItem::Token("t".into()),
+    ];
+    assert_eq!(
+        serde_json::to_value(&items).unwrap(),
+        json!([
+            {"kind": "Text", "content": {"text": "hi"}},
+            {"kind": "Data", "content": {"x": 1}},
+            {"kind": "Token", "content": "t"},
+        ])
+    );
+
+    // Tokens are transient and are not accepted back
+    let back: Result<StreamItem<Point>, _> = serde_json::from_value(json!({"kind": "Token", "content": "t"}));
+    assert!(back.is_err());
+    let back: StreamItem<Point> = serde_json::from_value(json!({"kind": "Data", "content": {"x": 1}})).unwrap();
+    assert!(matches!(back, StreamItem::Data(Point { x: 1 })));
+}
+
+#[test]
+fn parsed_responses_round_trip() {
+    let response = ParsedResponse {
+        items: vec![
+            ResponseItem::Text(TextContent { text: "intro".into(), block: None }),
+            ResponseItem::Data { data: Point { x: 2 }, original_text: r#"{"x":2}"#.into(), confidence_map: None },
+        ],
+        safety: None,
+        truncated: false,
+        error: None,
+        cost: None,
+    };
+    let value = serde_json::to_value(&response).unwrap();
+    assert_eq!(value["items"][1], json!({"kind": "Data", "content": {"data": {"x": 2}, "original_text": "{\"x\":2}"}}));
+    assert!(value.get("safety").is_none());
+
+    let back: ParsedResponse<Point> = serde_json::from_value(value).unwrap();
+    assert_eq!(back.items, response.items);
+}
@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use semantic_query::clients::{FaultConfig, FlexibleClient, Latency, MockClient, MockHandle, MockResponse};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::interceptors::Interceptor;
+use semantic_query::schema::JsonSchema;
+use semantic_query::shutdown::ShutdownHandle;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Answer {
+    value: i32,
+}
+
+/// A mock that answers `{"value": 1}` after `delay`
+fn slow(delay: Duration) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"value": 1}"#);
+    handle.inject_faults(FaultConfig::new().with_latency(Latency::Fixed(delay)));
+    (client, handle)
+}
+
+/// Counts its flushes
+#[derive(Debug, Default)]
+struct BufferedLog {
+    flushes: AtomicUsize,
+}
+
+#[async_trait]
+impl Interceptor for BufferedLog {
+    async fn save(&self, _prompt: &str, _response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn shutdown_drains_queries_in_flight() {
+    let shutdown = ShutdownHandle::new();
+    let (client, _handle) = slow(Duration::from_millis(100));
+    let resolver = Arc::new(QueryResolver::new(client, RetryConfig::default())
+        .with_shutdown(shutdown.clone()));
+    let running = {
+        let resolver = resolver.clone();
+        tokio::spawn(async move { resolver.query::<Answer>("q".into()).await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(shutdown.in_flight(), 1);
+
+    let report = shutdown.shutdown(Duration::from_secs(5)).await;
+    assert!(report.is_clean(), "{report:?}");
+    assert_eq!(running.await.unwrap().unwrap().first().unwrap().value, 1);
+    assert_eq!(shutdown.in_flight(), 0);
+
+    // Resolvers derived from a tracked one are refused too
+    assert!(matches!(resolver.query::<Answer>("q".into()).await, Err(QueryResolverError::ShuttingDown)));
+    assert!(matches!(resolver.for_user("u1").query_typed::<Answer>("q".into()).await, Err(QueryResolverError::ShuttingDown)));
+}
+
+#[tokio::test]
+async fn queries_and_streams_are_cancelled_at_the_deadline() {
+    let shutdown = ShutdownHandle::new();
+    let (client, _slow_handle) = slow(Duration::from_secs(30));
+    let slow = Arc::new(QueryResolver::new(client, RetryConfig::default())
+        .with_shutdown(shutdown.clone()));
+    let running = {
+        let slow = slow.clone();
+        tokio::spawn(async move { slow.query::<Answer>("q".into()).await })
+    };
+
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success(r#"{"value": 2}"#.into()));
+    handle.stream_in_chunks(4);
+    let streaming = QueryResolver::new(mock, RetryConfig::default()).with_shutdown(shutdown.clone());
+    let mut items = streaming.stream_query::<Answer>("q".into()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(shutdown.in_flight(), 2);
+
+    let report = shutdown.shutdown(Duration::from_millis(50)).await;
+    assert_eq!(report.cancelled, 2);
+    assert!(!report.is_clean());
+    assert!(matches!(running.await.unwrap(), Err(QueryResolverError::Cancelled)));
+    assert!(matches!(items.next().await, Some(Err(QueryResolverError::Cancelled))));
+    assert!(items.next().await.is_none());
+    drop(items);
+    assert_eq!(shutdown.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn shutdown_flushes_interceptors_and_hooks() {
+    let log = Arc::new(BufferedLog::default());
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success(r#"{"value": 3}"#.into()));
+    let client = FlexibleClient::new(Box::new(mock)).with_interceptor(log.clone());
+
+    let shutdown = ShutdownHandle::new();
+    let hooks = Arc::new(AtomicUsize::new(0));
+    let counter = hooks.clone();
+    shutdown.on_flush(move || {
+        let counter = counter.clone();
+        async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    });
+    shutdown.on_flush(|| async { Err("metrics exporter unreachable".to_string()) });
+
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_shutdown(shutdown.clone());
+    resolver.query::<Answer>("q".into()).await.unwrap();
+    let report = shutdown.shutdown(Duration::from_secs(1)).await;
+    assert_eq!(report.cancelled, 0);
+    assert_eq!(report.flush_errors, ["metrics exporter unreachable"]);
+    assert_eq!(log.flushes.load(Ordering::SeqCst), 1);
+    assert_eq!(hooks.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use semantic_query::clients::{MockClient, MockHandle, MockResponse};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::schema::JsonSchema;
+use semantic_query::spawn::SpawnOptions;
+use semantic_query::streaming::StreamItem;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Step {
+    n: i32,
+}
+
+const REPLY: &str = r#"First {"n": 1}, then {"n": 2}, and finally {"n": 3}."#;
+
+/// Streams `REPLY` in small chunks; keep the handle alive while it runs
+fn streaming_resolver() -> (QueryResolver<MockClient>, Arc<MockHandle>) {
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success(REPLY.into()));
+    handle.stream_in_chunks(4);
+    (QueryResolver::new(mock, RetryConfig::default()), handle)
+}
+
+#[tokio::test]
+async fn spawned_queries_send_items_and_the_response() {
+    let (resolver, _mock) = streaming_resolver();
+    let (mut items, handle) = resolver.spawn_query::<Step>("plan".into());
+    let mut steps = Vec::new();
+    while let Some(item) = items.recv().await {
+        if let StreamItem::Data(step) = item {
+            steps.push(step);
+        }
+    }
+    assert_eq!(steps, [Step { n: 1 }, Step { n: 2 }, Step { n: 3 }]);
+    assert!(handle.is_finished());
+    let response = handle.await.unwrap();
+    assert_eq!(response.data_count(), 3);
+    assert!(!response.truncated);
+}
+
+#[tokio::test]
+async fn full_channels_hold_the_query_back() {
+    let (resolver, _mock) = streaming_resolver();
+    let (mut items, handle) = resolver.spawn_query_with::<Step>("plan".into(), SpawnOptions::default().with_capacity(1));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!handle.is_finished());
+    assert!(items.recv().await.is_some());
+
+    // Dropping the receiver lets the query finish for the handle
+    drop(items);
+    assert_eq!(handle.await.unwrap().data_count(), 3);
+}
+
+#[tokio::test]
+async fn spawned_queries_can_be_cancelled() {
+    let (resolver, _mock) = streaming_resolver();
+    let (_items, handle) = resolver.spawn_query_with::<Step>("plan".into(), SpawnOptions::default().with_capacity(1));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    handle.cancel();
+    assert!(matches!(handle.await, Err(QueryResolverError::Cancelled)));
+
+    // An actor's token cancels every query spawned with it
+    let actor = CancellationToken::new();
+    let options = SpawnOptions::default().with_capacity(1).with_cancellation(actor.clone());
+    let (resolver, mock) = streaming_resolver();
+    mock.add_response(MockResponse::Success(REPLY.into()));
+    let (_a, first) = resolver.spawn_query_with::<Step>("plan".into(), options.clone());
+    let (_b, second) = resolver.spawn_query_with::<Step>("plan".into(), options);
+    actor.cancel();
+    assert!(matches!(first.await, Err(QueryResolverError::Cancelled)));
+    assert!(matches!(second.await, Err(QueryResolverError::Cancelled)));
+}
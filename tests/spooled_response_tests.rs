@@ -0,0 +1,113 @@
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::{MockClient, MockResponse};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::json_utils::{DetectionScope, ParseLimits};
+use semantic_query::schema::JsonSchema;
+use semantic_query::spool::{SpoolConfig, SpooledText};
+use semantic_query::stats::QueryStats;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Row {
+    id: u64,
+    label: String,
+}
+
+/// A fresh directory for one test's spool files
+fn spool_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("semantic-query-spool-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// `count` rows as a JSON array after some prose; labels are multi-byte so scan
+/// chunks split characters
+fn export(count: u64) -> String {
+    let rows: Vec<_> = (0..count).map(|id| Row { id, label: format!("ligne-été-{id}") }).collect();
+    format!("Here is the export:\n{}\nDone.", serde_json::to_string_pretty(&rows).unwrap())
+}
+
+fn files_in(dir: &PathBuf) -> usize {
+    std::fs::read_dir(dir).unwrap().count()
+}
+
+#[test]
+fn spooled_text_reads_back_from_disk() {
+    let dir = spool_dir("text");
+    let config = SpoolConfig::new(1024).in_dir(&dir);
+    let text = export(5_000);
+    assert!(text.len() > 200_000);
+
+    let spooled = SpooledText::from_string(text.clone(), &config).unwrap();
+    assert!(spooled.is_spooled());
+    assert_eq!(spooled.len(), text.len());
+    assert!(spooled.path().unwrap().starts_with(&dir));
+    assert_eq!(spooled.read_range(0..19).unwrap(), "Here is the export:");
+    assert_eq!(spooled.reader().unwrap().lines().last().unwrap().unwrap(), "Done.");
+    assert_eq!(spooled.read_to_string().unwrap(), text);
+
+    let rows = spooled.extract_all::<Row>(ParseLimits::default(), &DetectionScope::Anywhere).unwrap();
+    assert_eq!(rows.len(), 5_000);
+    assert_eq!(rows[4_999], Row { id: 4_999, label: "ligne-été-4999".into() });
+
+    // The file goes away with the handle
+    drop(spooled);
+    assert_eq!(files_in(&dir), 0);
+
+    let small = SpooledText::from_string("[]".into(), &config).unwrap();
+    assert!(!small.is_spooled() && small.path().is_none());
+    assert!(small.read_range(1..5).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn large_replies_are_spooled() {
+    let dir = spool_dir("ask");
+    let stats: Arc<Mutex<Vec<QueryStats>>> = Arc::default();
+    let sink = stats.clone();
+    let resolver = QueryResolver::new(ScriptedClient::Reply(export(2_000)), RetryConfig::default())
+        .with_spool(SpoolConfig::new(64 * 1024).in_dir(&dir))
+        .with_stats_callback(move |s| sink.lock().unwrap().push(s.clone()));
+
+    let response = resolver.query_spooled::<Row>("export".into()).await.unwrap();
+    assert!(response.raw.is_spooled());
+    assert_eq!(files_in(&dir), 1);
+    assert_eq!(response.data.len(), 2_000);
+    assert_eq!(response.data[7].id, 7);
+    assert_eq!(stats.lock().unwrap()[0].items, 2_000);
+
+    drop(response);
+    assert_eq!(files_in(&dir), 0);
+
+    // Under the threshold the reply stays in memory
+    let resolver = QueryResolver::new(ScriptedClient::Reply(export(2)), RetryConfig::default())
+        .with_spool(SpoolConfig::new(64 * 1024).in_dir(&dir));
+    let response = resolver.query_spooled::<Row>("export".into()).await.unwrap();
+    assert!(!response.raw.is_spooled());
+    assert_eq!(response.data.len(), 2);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn streamed_replies_are_spooled_as_they_arrive() {
+    let dir = spool_dir("stream");
+    let reply = export(500);
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success(reply.clone()));
+    handle.stream_in_chunks(256);
+
+    let resolver = QueryResolver::new(mock, RetryConfig::default()).with_spool(SpoolConfig::new(4 * 1024).in_dir(&dir));
+    let response = resolver.query_spooled::<Row>("export".into()).await.unwrap();
+    assert!(response.raw.is_spooled());
+    assert_eq!(response.raw.read_to_string().unwrap(), reply);
+    assert_eq!(response.data.len(), 500);
+    assert_eq!(response.data[499].label, "ligne-été-499");
+    drop(response);
+    std::fs::remove_dir_all(dir).unwrap();
+}
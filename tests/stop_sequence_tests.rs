@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use semantic_query::clients::{MockClient, MockHandle, MockSetting};
+use semantic_query::core::{LowLevelClient, QueryResolver, RetryConfig, StopSequences, JSON_ONLY_STOPS};
+use semantic_query::request::ModelRequest;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Task {
+    title: String,
+}
+
+const REPLY: &str = "```json\n{\"title\": \"Ship it\"}\n```\n\n## Notes\nShipping on Friday.";
+
+/// A mock that answers in a fenced block and cuts the reply where a stop sequence begins,
+/// as providers do
+fn stopping() -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.accept(&[MockSetting::StopSequences]);
+    handle.add_json_response(REPLY);
+    (client, handle)
+}
+
+#[tokio::test]
+async fn auto_stops_json_only_queries_after_the_block() {
+    let (client, handle) = stopping();
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_stop_sequences(StopSequences::Auto);
+
+    let task: Task = resolver.query_typed("Plan the release".to_string()).await.unwrap();
+    assert_eq!(task.title, "Ship it");
+    assert_eq!(handle.calls()[0].settings.stop_sequences, JSON_ONLY_STOPS.map(String::from).to_vec());
+}
+
+#[tokio::test]
+async fn auto_leaves_mixed_queries_alone() {
+    let (client, handle) = stopping();
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_stop_sequences(StopSequences::Auto);
+
+    let response = resolver.query::<Task>("Plan the release".to_string()).await.unwrap();
+    assert!(response.text_content().contains("Shipping on Friday"));
+    assert!(handle.calls()[0].settings.stop_sequences.is_empty());
+}
+
+#[tokio::test]
+async fn custom_stops_apply_to_every_query() {
+    let (client, handle) = stopping();
+    let resolver = QueryResolver::new(client, RetryConfig::default())
+        .with_stop_sequences(StopSequences::Custom(vec!["## ".into()]));
+
+    let response = resolver.query::<Task>("Plan the release".to_string()).await.unwrap();
+    assert_eq!(response.first_required().unwrap().title, "Ship it");
+    assert!(!response.text_content().contains("Notes"));
+    assert_eq!(handle.calls()[0].settings.stop_sequences, vec!["## ".to_string()]);
+}
+
+#[tokio::test]
+async fn model_requests_send_their_stop_sequences() {
+    let (client, _handle) = stopping();
+    let reply = client.ask_request(ModelRequest::user("Plan the release").with_stop(["\n```"])).await.unwrap();
+    assert_eq!(reply, "```json\n{\"title\": \"Ship it\"}");
+}
+
+#[cfg(feature = "anthropic")]
+mod claude {
+    use semantic_query::clients::claude::{ClaudeConfig, ClaudeModel, ClaudeRequest};
+    use semantic_query::request::ModelRequest;
+
+    #[test]
+    fn request_stops_replace_the_configured_ones() {
+        let config = ClaudeConfig { stop_sequences: vec!["END".into()], ..ClaudeConfig::anthropic("k".into(), ClaudeModel::Haiku35) };
+        let body = serde_json::to_value(ClaudeRequest::new("Hi".into(), &config)).unwrap();
+        assert_eq!(body["stop_sequences"], serde_json::json!(["END"]));
+
+        let request = ModelRequest::user("Hi").with_stop(["\n```\n"]);
+        let claude = ClaudeRequest::from_model_request(request, &config);
+        assert_eq!(claude.stop_sequences, vec!["\n```\n".to_string()]);
+    }
+}
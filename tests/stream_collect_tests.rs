@@ -0,0 +1,52 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, ResponseItem, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::streaming::StreamItem;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Step {
+    name: String,
+}
+
+fn scripted(chunks: &[&str]) -> ScriptedClient {
+    ScriptedClient::Stream(chunks.iter().map(|c| c.as_bytes().to_vec()).collect())
+}
+
+#[tokio::test]
+async fn stream_and_response_come_from_one_pass() {
+    let client = scripted(&["Plan:\n\n", r#"{"name": "#, r#""pack"}"#, "\n\nThen go."]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let mut collect = resolver.query_stream_collect::<Step>("plan".into()).await.unwrap();
+    let mut tokens = String::new();
+    let mut live_data = Vec::new();
+    while let Some(item) = collect.stream.next().await {
+        match item.unwrap() {
+            StreamItem::Token(t) => tokens.push_str(&t),
+            StreamItem::Data(d) => live_data.push(d),
+            StreamItem::Text(_) | StreamItem::Finished { .. } => {}
+        }
+    }
+    let response = collect.response.await.unwrap();
+
+    assert!(tokens.contains("pack"));
+    assert_eq!(live_data, vec![Step { name: "pack".into() }]);
+    assert_eq!(response.data_only(), vec![&Step { name: "pack".into() }]);
+    assert!(response.text_content().contains("Then go."));
+    let original = response.items.iter().find_map(|i| match i {
+        ResponseItem::Data { original_text, .. } => Some(original_text.clone()),
+        ResponseItem::Text(_) => None,
+    });
+    assert_eq!(original.as_deref(), Some(r#"{"name":"pack"}"#));
+}
+
+#[tokio::test]
+async fn dropping_the_stream_interrupts_the_response() {
+    let resolver = QueryResolver::new(scripted(&[r#"{"name": "pack"}"#]), RetryConfig::default());
+    let collect = resolver.query_stream_collect::<Step>("plan".into()).await.unwrap();
+    drop(collect.stream);
+    assert!(matches!(collect.response.await, Err(QueryResolverError::StreamInterrupted(_))));
+}
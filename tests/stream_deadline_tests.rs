@@ -0,0 +1,85 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::clients::{FaultConfig, MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::streaming::{StreamItem, StreamOptions};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+/// A mock streaming `reply`, then stalling without closing the connection
+fn stalling(reply: &str) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(reply);
+    handle.stream_in_chunks(16);
+    handle.inject_faults(FaultConfig::new().with_stalls(1.0));
+    (client, handle)
+}
+
+fn resolver(partial_on_deadline: bool) -> (QueryResolver<MockClient>, Arc<MockHandle>) {
+    let (client, handle) = stalling(r#"[{"name": "Ada"}, {"name": "Bob"}, {"name": "Cy"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_stream_options(StreamOptions {
+        deadline: Some(Duration::from_millis(100)),
+        partial_on_deadline,
+        ..StreamOptions::default()
+    });
+    (resolver, handle)
+}
+
+#[tokio::test]
+async fn stalled_streams_end_at_the_deadline() {
+    let (resolver, _handle) = resolver(false);
+    let items: Vec<_> = resolver.stream_query::<Contact>("contacts".into()).await.unwrap().collect().await;
+
+    let names: Vec<&str> = items.iter().filter_map(|i| match i {
+        Ok(StreamItem::Data(c)) => Some(c.name.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(names, vec!["Ada", "Bob"]);
+    assert!(matches!(items.last(), Some(Err(QueryResolverError::DeadlineExceeded(d))) if *d == Duration::from_millis(100)));
+}
+
+#[tokio::test]
+async fn collected_responses_fail_at_the_deadline_by_default() {
+    let (resolver, _handle) = resolver(false);
+    let collect = resolver.query_stream_collect::<Contact>("contacts".into()).await.unwrap();
+    assert!(matches!(collect.finish().await, Err(QueryResolverError::StreamInterrupted(_))));
+}
+
+#[tokio::test]
+async fn partial_responses_keep_completed_items() {
+    let (resolver, _handle) = resolver(true);
+    let collect = resolver.query_stream_collect::<Contact>("contacts".into()).await.unwrap();
+    let response = collect.finish().await.unwrap();
+
+    assert!(response.truncated);
+    assert!(response.error.as_deref().is_some_and(|e| e.contains("deadline")));
+    assert_eq!(response.data_only(), vec![&Contact { name: "Ada".into() }, &Contact { name: "Bob".into() }]);
+
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["truncated"], true);
+}
+
+#[tokio::test]
+async fn complete_responses_are_not_truncated() {
+    let (client, _handle) = stalling(r#"{"name": "Ada"}"#);
+    let resolver = QueryResolver::new(client, RetryConfig::default()).with_stream_options(StreamOptions {
+        deadline: Some(Duration::from_secs(5)),
+        partial_on_deadline: true,
+        stop_after_data: true,
+        ..StreamOptions::default()
+    });
+    let response = resolver.query_stream_collect::<Contact>("contact".into()).await.unwrap().finish().await.unwrap();
+    assert!(!response.truncated);
+    assert_eq!(response.error, None);
+    assert!(serde_json::to_value(&response).unwrap().get("truncated").is_none());
+}
@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use futures_util::{stream, StreamExt};
+use schemars::JsonSchema;
+use semantic_query::journal::Journal;
+use semantic_query::stream_ext::{SemanticStreamExt, TextBuffer};
+use semantic_query::streaming::{FinishReason, StreamItem, TextContent};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Fact {
+    text: String,
+}
+
+type Item = Result<StreamItem<Fact>, String>;
+
+fn text(text: &str) -> Item {
+    Ok(StreamItem::Text(TextContent { text: text.into(), block: None }))
+}
+
+fn fact(text: &str) -> Item {
+    Ok(StreamItem::Data(Fact { text: text.into() }))
+}
+
+fn texts(items: &[Item]) -> Vec<String> {
+    items.iter().filter_map(|item| match item {
+        Ok(StreamItem::Text(t)) => Some(t.text.clone()),
+        _ => None,
+    }).collect()
+}
+
+fn sample() -> Vec<Item> {
+    vec![
+        Ok(StreamItem::Token("Here".into())),
+        text("Here are "),
+        text("the facts."),
+        fact("water is wet"),
+        Err("connection reset".into()),
+        text("Done."),
+        fact("fire is hot"),
+    ]
+}
+
+#[tokio::test]
+async fn data_only_keeps_data_and_errors() {
+    let items: Vec<Result<Fact, String>> = stream::iter(sample()).data_only().collect().await;
+    assert_eq!(items, vec![
+        Ok(Fact { text: "water is wet".into() }),
+        Err("connection reset".into()),
+        Ok(Fact { text: "fire is hot".into() }),
+    ]);
+}
+
+#[tokio::test]
+async fn text_only_keeps_text_and_errors() {
+    let items: Vec<Result<String, String>> = stream::iter(sample()).text_only().collect().await;
+    assert_eq!(items, vec![Ok("Here are ".into()), Ok("the facts.".into()), Err("connection reset".into()), Ok("Done.".into())]);
+}
+
+#[tokio::test]
+async fn text_is_buffered_into_sentences() {
+    let input = vec![text("One. Tw"), text("o! Three"), text(" and"), fact("x"), text("Four? "), text("tail")];
+    let items: Vec<Item> = stream::iter(input).buffer_text(TextBuffer::Sentences).collect().await;
+    assert_eq!(texts(&items), vec!["One. ", "Two! ", "Three and", "Four? ", "tail"]);
+    assert!(matches!(items[3], Ok(StreamItem::Data(_))), "data keeps its place after the flushed text");
+}
+
+#[tokio::test]
+async fn text_is_buffered_by_lines_chars_or_whole() {
+    let input = || vec![text("ab\ncd"), text("ef\n"), text("g")];
+    let lines: Vec<Item> = stream::iter(input()).buffer_text(TextBuffer::Lines).collect().await;
+    assert_eq!(texts(&lines), vec!["ab\n", "cdef\n", "g"]);
+
+    let chars: Vec<Item> = stream::iter(input()).buffer_text(TextBuffer::Chars(4)).collect().await;
+    assert_eq!(texts(&chars), vec!["ab\nc", "def\n", "g"]);
+
+    let whole: Vec<Item> = stream::iter(input()).buffer_text(TextBuffer::Whole).collect().await;
+    assert_eq!(texts(&whole), vec!["ab\ncdef\ng"]);
+}
+
+#[tokio::test]
+async fn data_can_be_mapped() {
+    let items: Vec<Result<StreamItem<usize>, String>> = stream::iter(sample()).map_data(|f| f.text.len()).collect().await;
+    let lengths: Vec<usize> = items.iter().filter_map(|i| match i {
+        Ok(StreamItem::Data(n)) => Some(*n),
+        _ => None,
+    }).collect();
+    assert_eq!(lengths, vec![12, 11]);
+    assert_eq!(items.len(), sample().len());
+}
+
+#[tokio::test]
+async fn streams_end_after_the_first_data() {
+    let items: Vec<Item> = stream::iter(sample()).take_until_first_data().collect().await;
+    assert_eq!(items.len(), 4);
+    assert!(matches!(items.last(), Some(Ok(StreamItem::Data(f))) if f.text == "water is wet"));
+}
+
+#[tokio::test]
+async fn tee_records_successful_items() {
+    let journal = Arc::new(Mutex::new(Journal::new()));
+    let mut input = sample();
+    input.push(Ok(StreamItem::Finished { reason: FinishReason::EarlyStopAfterData }));
+    let items: Vec<Item> = stream::iter(input).tee_to(journal.clone()).collect().await;
+    assert_eq!(items.len(), 8);
+
+    let journal = journal.lock().unwrap();
+    assert_eq!(journal.entries.len(), 7);
+    assert!(matches!(journal.items().last(), Some(StreamItem::Finished { .. })));
+}
@@ -0,0 +1,66 @@
+use futures_util::{stream, StreamExt};
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::clients::mock::MockVoid;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::streaming::{stream_merge, Sourced, StreamItem};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Fact {
+    text: String,
+}
+
+#[tokio::test]
+async fn merged_items_keep_per_source_order() {
+    let merged: Vec<Sourced<&str, u32>> = stream_merge([
+        ("a", stream::iter(vec![1, 2, 3])),
+        ("b", stream::iter(vec![10, 20])),
+    ])
+    .collect()
+    .await;
+
+    assert_eq!(merged.len(), 5);
+    for source in ["a", "b"] {
+        let items: Vec<(usize, u32)> = merged.iter().filter(|s| s.source == source).map(|s| (s.seq, s.item)).collect();
+        let expected: Vec<(usize, u32)> = match source {
+            "a" => vec![(0, 1), (1, 2), (2, 3)],
+            _ => vec![(0, 10), (1, 20)],
+        };
+        assert_eq!(items, expected);
+    }
+}
+
+#[tokio::test]
+async fn resolver_merges_streams_for_several_prompts() {
+    let client = ScriptedClient::Stream(vec![br#"{"text": "#.to_vec(), br#""fact"}"#.to_vec()]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let merged: Vec<_> = resolver
+        .stream_query_merged::<Fact, usize>((0..3).map(|shard| (shard, format!("shard {shard}"))))
+        .await
+        .collect()
+        .await;
+
+    for shard in 0..3 {
+        let data: Vec<&Fact> = merged.iter()
+            .filter(|s| s.source == shard)
+            .filter_map(|s| match &s.item { Ok(StreamItem::Data(fact)) => Some(fact), _ => None })
+            .collect();
+        assert_eq!(data, [&Fact { text: "fact".into() }], "shard {shard}");
+    }
+}
+
+#[tokio::test]
+async fn a_source_that_cannot_stream_yields_one_error() {
+    // MockVoid has no streaming support
+    let resolver = QueryResolver::new(MockVoid, RetryConfig::default());
+    let merged: Vec<_> = resolver
+        .stream_query_merged::<Fact, &str>([("left", "p".to_string()), ("right", "p".to_string())])
+        .await
+        .collect()
+        .await;
+
+    assert_eq!(merged.len(), 2);
+    assert!(merged.iter().all(|s| s.seq == 0 && s.item.is_err()));
+}
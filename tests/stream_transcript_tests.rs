@@ -0,0 +1,153 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use semantic_query::clients::{FaultConfig, FlexibleClient, MockClient, MockHandle, MockResponse};
+use semantic_query::core::LowLevelClient;
+use semantic_query::error::AIError;
+use semantic_query::interceptors::{
+    GuardedInterceptor, Interceptor, InterceptorErrorPolicy, InterceptorStack, Redactor, StreamTranscript,
+};
+
+const REPLY: &str = "The deploy key is sk-live-42, rotate it weekly.";
+
+fn streaming_mock() -> (FlexibleClient, Arc<MockHandle>) {
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success(REPLY.into()));
+    handle.stream_in_chunks(6);
+    (FlexibleClient::new(Box::new(mock)), handle)
+}
+
+/// Streams the first chunk of its reply, then disconnects (where seed 6 cuts a two-chunk stream)
+fn broken_stream() -> (FlexibleClient, Arc<MockHandle>) {
+    let (mock, handle) = MockClient::new();
+    handle.add_response(MockResponse::Success("Half an hour".into()));
+    handle.stream_in_chunks(7);
+    handle.inject_faults(FaultConfig::new().with_disconnects(1.0).with_seed(6));
+    (FlexibleClient::new(Box::new(mock)), handle)
+}
+
+/// Logs chunks, stream ends and whole-reply saves; fails chunks containing `fail_on`
+#[derive(Debug, Default)]
+struct Recorder {
+    log: Arc<Mutex<Vec<String>>>,
+    fail_on: Option<&'static str>,
+}
+
+struct RecorderTranscript {
+    log: Arc<Mutex<Vec<String>>>,
+    fail_on: Option<&'static str>,
+}
+
+#[async_trait]
+impl Interceptor for Recorder {
+    async fn save(&self, _prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.log.lock().unwrap().push(format!("save {response}"));
+        Ok(())
+    }
+
+    async fn save_error(&self, _prompt: &str, error: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.log.lock().unwrap().push(format!("error {error}"));
+        Ok(())
+    }
+
+    async fn start_stream(&self, prompt: &str) -> Result<Option<Box<dyn StreamTranscript>>, Box<dyn std::error::Error>> {
+        self.log.lock().unwrap().push(format!("start {prompt}"));
+        Ok(Some(Box::new(RecorderTranscript { log: self.log.clone(), fail_on: self.fail_on })))
+    }
+}
+
+#[async_trait]
+impl StreamTranscript for RecorderTranscript {
+    async fn on_chunk(&mut self, chunk: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.fail_on.is_some_and(|needle| chunk.contains(needle)) {
+            return Err("transcript store full".into());
+        }
+        self.log.lock().unwrap().push(chunk.to_string());
+        Ok(())
+    }
+
+    async fn on_stream_end(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.log.lock().unwrap().push("end".into());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn file_transcripts_grow_while_the_reply_streams() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("semantic-query-transcripts-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let (client, _handle) = streaming_mock();
+    let client = client.with_file_interceptor(dir.clone());
+
+    let mut stream = client.stream_raw("Where is the key?".into()).unwrap();
+    for _ in 0..3 {
+        stream.next().await.unwrap().unwrap();
+    }
+    let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(files.len(), 1);
+    let partial = std::fs::read_to_string(&files[0]).unwrap();
+    assert!(partial.starts_with("# Prompt\n\nWhere is the key?\n\n# Response\n\nThe de"), "{partial:?}");
+    assert!(partial.len() < REPLY.len() + 40);
+
+    while stream.next().await.is_some() {}
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), format!("# Prompt\n\nWhere is the key?\n\n# Response\n\n{REPLY}\n"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn transcripts_get_chunks_and_other_layers_the_whole_reply() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let saves = Arc::new(Mutex::new(Vec::new()));
+    let stack = InterceptorStack::new()
+        .push(Redactor::new(|text| *text = text.replace("sk-", "**-")))
+        .push(Recorder { log: log.clone(), fail_on: None })
+        .push(WholeReplies(saves.clone()));
+    let (client, _handle) = streaming_mock();
+    let client = client.with_interceptors(stack);
+
+    let chunks: Vec<_> = client.stream_raw("key?".into()).unwrap().collect().await;
+    assert!(chunks.iter().all(Result::is_ok));
+    let log = log.lock().unwrap().clone();
+    assert_eq!(log.first().unwrap(), "start key?");
+    assert_eq!(log.last().unwrap(), "end");
+    assert!(log.len() > 4, "{log:?}");
+    assert_eq!(log[1..log.len() - 1].concat(), REPLY.replace("sk-", "**-"));
+    // Layers without a transcript still get one save, and no layer gets both
+    assert!(!log.iter().any(|entry| entry.starts_with("save")));
+    assert_eq!(*saves.lock().unwrap(), [REPLY.replace("sk-", "**-")]);
+}
+
+#[tokio::test]
+async fn failed_streams_and_transcripts_end_the_transcript() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (client, _handle) = broken_stream();
+    let client = client.with_interceptors(InterceptorStack::new().push(Recorder { log: log.clone(), fail_on: None }));
+    let items: Vec<_> = client.stream_raw("q".into()).unwrap().collect().await;
+    assert!(matches!(items.last(), Some(Err(AIError::OpenAI(_)))));
+    assert_eq!(*log.lock().unwrap(), ["start q", "Half an", "end", "error OpenAI API error: HTTP error: mock: stream disconnected"]);
+
+    // A transcript failing under `FailRequest` ends the stream with its error
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let strict = GuardedInterceptor::new(Arc::new(Recorder { log: log.clone(), fail_on: Some("sk-") }))
+        .on_error(InterceptorErrorPolicy::FailRequest);
+    let (client, _handle) = streaming_mock();
+    let client = client.with_guarded_interceptor(strict);
+    let items: Vec<_> = client.stream_raw("q".into()).unwrap().collect().await;
+    assert!(matches!(items.last(), Some(Err(AIError::Interceptor(e))) if e == "transcript store full"));
+    assert_eq!(log.lock().unwrap().last().unwrap(), "end");
+}
+
+/// Keeps whole replies saved through `save`
+#[derive(Debug)]
+struct WholeReplies(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl Interceptor for WholeReplies {
+    async fn save(&self, _prompt: &str, response: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().push(response.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{ParsedResponse, QueryResolver, ResponseItem, RetryConfig};
+use semantic_query::streaming::TextFidelity;
+use semantic_query::tables::find_tables;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Item {
+    name: String,
+    quantity: u32,
+    unit_price: f64,
+    #[serde(default)]
+    in_stock: Option<bool>,
+}
+
+const MARKDOWN: &str = "Here is the inventory:\n\n| Product Name | Qty | Unit Price ($) | In Stock? |\n|:--|--:|--:|:-:|\n| **Bolt** | 1,200 | 0.15 | yes |\n| Nut \\| M6 | 40 | 0.05 | — |\n\nLet me know if you need more.";
+
+fn item(name: &str, quantity: u32, unit_price: f64, in_stock: Option<bool>) -> Item {
+    Item { name: name.into(), quantity, unit_price, in_stock }
+}
+
+#[test]
+fn tables_are_found_and_mapped_by_header() {
+    let tables = find_tables(MARKDOWN);
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].headers, ["Product Name", "Qty", "Unit Price ($)", "In Stock?"]);
+    assert_eq!(tables[0].rows[1], ["Nut | M6", "40", "0.05", "—"]);
+    assert!(MARKDOWN[tables[0].range.clone()].starts_with("| Product Name"));
+    assert_eq!(tables[0].extract::<Item>(), [item("Bolt", 1200, 0.15, Some(true)), item("Nut | M6", 40, 0.05, None)]);
+    assert_eq!(tables[0].extract::<Vec<Item>>().len(), 1);
+
+    let csv = "```csv\nname,quantity,unit price\n\"Washer, flat\",500,0.02\nSpring,12,1.5\n```\nSure, that works, I think.";
+    let tables = find_tables(csv);
+    assert_eq!(tables.len(), 1, "prose with commas is not a table");
+    assert_eq!(&csv[tables[0].range.clone()], "```csv\nname,quantity,unit price\n\"Washer, flat\",500,0.02\nSpring,12,1.5\n```\n");
+    assert_eq!(tables[0].extract::<Item>(), [item("Washer, flat", 500, 0.02, None), item("Spring", 12, 1.5, None)]);
+    assert_eq!(tables[0].extract::<serde_json::Value>()[1], json!({"name": "Spring", "quantity": 12, "unit price": 1.5}));
+
+    // Headers that match no field leave nothing to extract
+    assert!(find_tables("| a | b |\n|---|---|\n| 1 | 2 |").first().unwrap().extract::<Item>().is_empty());
+}
+
+#[tokio::test]
+async fn resolver_falls_back_to_tables() {
+    let resolver = QueryResolver::new(ScriptedClient::Reply(MARKDOWN.into()), RetryConfig::default());
+    assert!(!resolver.query_mixed::<Item>("inventory".into()).await.unwrap().has_data());
+
+    let resolver = resolver.with_table_fallback(true);
+    let response = resolver.query_mixed::<Item>("inventory".into()).await.unwrap();
+    assert_eq!(response.data_only(), [&item("Bolt", 1200, 0.15, Some(true)), &item("Nut | M6", 40, 0.05, None)]);
+    assert_eq!(response.items.len(), 4, "text, two rows, text");
+    let ResponseItem::Data { original_text, .. } = &response.items[1] else { panic!("{:?}", response.items) };
+    assert_eq!(original_text, "| **Bolt** | 1,200 | 0.15 | yes |\n");
+
+    let all = resolver.query_mixed::<Vec<Item>>("inventory".into()).await.unwrap();
+    assert_eq!(all.first().map(Vec::len), Some(2));
+
+    // JSON in the reply wins over tables
+    let reply = format!("{MARKDOWN}\n{}", json!({"name": "Gear", "quantity": 1, "unit_price": 9.5}));
+    let resolver = QueryResolver::new(ScriptedClient::Reply(reply), RetryConfig::default()).with_table_fallback(true);
+    let response = resolver.query_mixed::<Item>("inventory".into()).await.unwrap();
+    assert_eq!(response.data_only(), [&item("Gear", 1, 9.5, None)]);
+}
+
+#[tokio::test]
+async fn exact_text_keeps_the_table_around_rows() {
+    let resolver = QueryResolver::new(ScriptedClient::Reply(MARKDOWN.into()), RetryConfig::default())
+        .with_table_fallback(true)
+        .with_text_fidelity(TextFidelity::Exact);
+    let response: ParsedResponse<Item> = resolver.query_mixed("inventory".into()).await.unwrap();
+    let rebuilt: String = response.items.iter().map(|item| match item {
+        ResponseItem::Data { original_text, .. } => original_text.as_str(),
+        ResponseItem::Text(text) => text.text.as_str(),
+    }).collect();
+    assert_eq!(rebuilt, MARKDOWN);
+    assert_eq!(response.data_count(), 2);
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use semantic_query::clients::mock::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::QueryResolverError;
+use semantic_query::tasks::{chunk_text, locate_span, ChangeKind, ChangeReport, Entity, EntitySpan};
+
+/// A mock answering with `replies` in order
+fn scripted(replies: Vec<&str>) -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(replies);
+    (client, handle)
+}
+
+fn paragraph(n: usize) -> String {
+    format!("Clause {n}: the supplier delivers batch {n} within thirty days of the order.")
+}
+
+#[test]
+fn chunks_respect_the_budget_and_paragraphs() {
+    let text = (1..=6).map(paragraph).collect::<Vec<_>>().join("\n\n");
+    let chunks = chunk_text(&text, 40);
+    assert!(chunks.len() > 1);
+    for chunk in &chunks {
+        assert!(chunk.len() <= 160, "{chunk}");
+        assert!(chunk.starts_with("Clause"));
+    }
+    assert_eq!(chunks.join("\n\n"), text);
+
+    let long = "word ".repeat(100);
+    let chunks = chunk_text(&long, 10);
+    assert!(chunks.iter().all(|c| c.len() <= 40 && !c.is_empty()));
+    assert_eq!(chunks.join(" ").split_whitespace().count(), 100);
+}
+
+#[tokio::test]
+async fn short_texts_are_compared_in_one_query() {
+    let (client, handle) = scripted(vec![
+        r#"{"summary": "The fee went up.", "changes": [{"kind": "Modified", "description": "Fee raised from 10 to 12 EUR", "before": "10 EUR", "after": "12 EUR"}]}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let report = resolver.diff::<ChangeReport>("The fee is 10 EUR.", "The fee is 12 EUR.").await.unwrap();
+    assert_eq!(report.changes.len(), 1);
+    assert_eq!(report.changes[0].kind, ChangeKind::Modified);
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].contains("=== OLD VERSION ===\nThe fee is 10 EUR."));
+    assert!(prompts[0].contains("=== NEW VERSION ===\nThe fee is 12 EUR."));
+}
+
+#[tokio::test]
+async fn identical_texts_skip_the_model() {
+    let (client, handle) = scripted(vec![]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let report = resolver.diff::<ChangeReport>("Same.", "Same.").await.unwrap();
+    assert_eq!(report, ChangeReport::default());
+    assert!(handle.prompts().is_empty());
+}
+
+#[tokio::test]
+async fn long_texts_send_only_changed_passages() {
+    let old = (1..=40).map(paragraph).collect::<Vec<_>>().join("\n\n");
+    let new = old.replace("batch 3 within thirty", "batch 3 within sixty").replace(&format!("\n\n{}", paragraph(30)), "");
+    let (client, handle) = scripted(vec![
+        r#"{"summary": "Delivery of batch 3 takes longer.", "changes": [{"kind": "Modified", "description": "Batch 3 deadline doubled", "before": null, "after": null}]}"#,
+        r#"{"summary": "Clause 30 was dropped.", "changes": [{"kind": "Removed", "description": "Clause 30 removed", "before": null, "after": null}]}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let report: ChangeReport = resolver.tasks().with_chunk_tokens(100).diff(&old, &new).await.unwrap();
+    assert_eq!(report.changes.iter().map(|c| c.kind).collect::<Vec<_>>(), vec![ChangeKind::Modified, ChangeKind::Removed]);
+    assert_eq!(report.summary, "Delivery of batch 3 takes longer. Clause 30 was dropped.");
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].contains("batch 3 within sixty"));
+    assert!(prompts[0].contains(&paragraph(2)), "leading context is included");
+    assert!(!prompts[0].contains(&paragraph(20)));
+    assert!(prompts[1].contains(&paragraph(30)));
+}
+
+#[tokio::test]
+async fn long_texts_are_summarized_in_parts() {
+    let text = (1..=6).map(paragraph).collect::<Vec<_>>().join("\n\n");
+    let (client, handle) = scripted(vec![
+        r#"{"summary": "Batches 1-3.", "key_points": ["thirty days"]}"#,
+        r#"{"summary": "Batches 4-6.", "key_points": []}"#,
+        r#"{"summary": "Six batches, each within thirty days.", "key_points": ["thirty days"]}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let summary = resolver.tasks().with_chunk_tokens(70).summarize(&text).await.unwrap();
+    assert_eq!(summary.summary, "Six batches, each within thirty days.");
+
+    let prompts = handle.prompts();
+    assert_eq!(prompts.len(), 3);
+    assert!(prompts[2].contains("Part 1: Batches 1-3."));
+    assert!(prompts[2].contains("Part 2: Batches 4-6."));
+}
+
+#[tokio::test]
+async fn classification_is_limited_to_the_labels() {
+    let (client, handle) = scripted(vec![
+        r#"{"label": "Invoice", "confidence": 0.9, "rationale": "Has a total."}"#,
+        r#"{"label": "recipe", "confidence": 0.4, "rationale": "Unsure."}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let classification = resolver.tasks().classify("Total: 12 EUR", &["contract", "invoice"]).await.unwrap();
+    assert_eq!(classification.label, "invoice");
+
+    let result = resolver.tasks().classify("Total: 12 EUR", &["contract", "invoice"]).await;
+    assert!(matches!(result, Err(QueryResolverError::Task(e)) if e.contains("recipe")));
+    assert!(handle.prompts()[0].contains("contract, invoice"));
+}
+
+#[tokio::test]
+async fn entities_are_deduplicated_across_chunks() {
+    let text = (1..=6).map(paragraph).collect::<Vec<_>>().join("\n\n");
+    let (client, handle) = scripted(vec![
+        r#"{"entities": [{"text": "thirty days", "kind": "date"}, {"text": "batch 1", "kind": "product"}]}"#,
+        r#"{"entities": [{"text": "thirty days", "kind": "date"}]}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let entities = resolver.tasks().with_chunk_tokens(70).extract_entities(&text).await.unwrap();
+    assert_eq!(entities, vec![
+        Entity { text: "thirty days".into(), kind: "date".into() },
+        Entity { text: "batch 1".into(), kind: "product".into() },
+    ]);
+    assert_eq!(handle.prompts().len(), 2);
+}
+
+#[test]
+fn spans_are_located_near_the_claimed_offset() {
+    let source = "Anna met Bob. Later, Bob met ANNA in Zürich.";
+    assert_eq!(locate_span(source, "Bob", Some(9)), Some((9, 12)));
+    assert_eq!(locate_span(source, "Bob", Some(19)), Some((21, 24)));
+    assert_eq!(locate_span(source, "Bob", None), Some((9, 12)));
+    assert_eq!(locate_span(source, "anna", Some(30)), Some((29, 33)));
+    assert_eq!(locate_span(source, "Zürich", Some(0)), Some((37, 43)));
+    assert_eq!(locate_span(source, "Carol", Some(0)), None);
+}
+
+#[tokio::test]
+async fn entity_offsets_are_verified_against_the_source() {
+    let source = "Anna paid 12 EUR to Acme in Zürich.";
+    let (client, _handle) = scripted(vec![r#"{"mentions": [
+        {"text": "Anna", "kind": "person", "start": 0, "end": 4},
+        {"text": "Acme", "kind": "organization", "start": 18, "end": 22},
+        {"text": "Zürich", "kind": "location", "start": 28, "end": 34},
+        {"text": "Berlin", "kind": "location", "start": 0, "end": 6},
+        {"text": "anna", "kind": "person", "start": 0, "end": 4}
+    ]}"#]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let spans = resolver.tasks().extract_entity_spans(source).await.unwrap();
+    assert_eq!(spans, vec![
+        EntitySpan { text: "Anna".into(), kind: "person".into(), start: 0, end: 4, repaired: false },
+        EntitySpan { text: "Acme".into(), kind: "organization".into(), start: 20, end: 24, repaired: true },
+        EntitySpan { text: "Zürich".into(), kind: "location".into(), start: 28, end: 34, repaired: false },
+    ]);
+    let chars: Vec<char> = source.chars().collect();
+    for span in &spans {
+        assert_eq!(chars[span.start..span.end].iter().collect::<String>(), span.text);
+    }
+}
+
+#[tokio::test]
+async fn entity_offsets_in_later_chunks_are_relative_to_the_source() {
+    let text = (1..=6).map(paragraph).collect::<Vec<_>>().join("\n\n");
+    let (client, _handle) = scripted(vec![
+        r#"{"mentions": [{"text": "batch 2", "kind": "product", "start": 106, "end": 113}]}"#,
+        r#"{"mentions": [{"text": "batch 5", "kind": "product", "start": 106, "end": 113}]}"#,
+    ]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+
+    let spans = resolver.tasks().with_chunk_tokens(70).extract_entity_spans(&text).await.unwrap();
+    assert_eq!(spans.len(), 2);
+    for span in &spans {
+        assert!(!span.repaired, "{span:?}");
+        assert_eq!(&text[span.start..span.end], span.text);
+    }
+    assert_eq!(spans[1].text, "batch 5");
+}
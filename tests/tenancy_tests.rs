@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+
+use schemars::JsonSchema;
+use semantic_query::clients::mock::{MockClient, MockHandle, MockVoid};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::{AIError, QueryResolverError};
+use semantic_query::stats::QueryStats;
+use semantic_query::tenancy::{Tenancy, TenantLimits};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Invoice {
+    total: f64,
+}
+
+/// A mock answering one query with a fixed invoice
+fn invoice_mock() -> (MockClient, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(r#"{"total": 1.0}"#);
+    (client, handle)
+}
+
+#[tokio::test]
+async fn each_tenant_queries_with_its_own_client() {
+    let (acme, acme_mock) = MockClient::new();
+    acme_mock.add_json_response(r#"{"total": 12.5}"#);
+    let (globex, globex_mock) = MockClient::new();
+    globex_mock.add_json_response(r#"{"total": 99.0}"#);
+    let tenancy = Tenancy::new()
+        .with_tenant("acme", Box::new(acme), TenantLimits::default())
+        .with_tenant("globex", Box::new(globex), TenantLimits::default());
+    let resolver = QueryResolver::new(MockVoid, RetryConfig::default()).with_tenancy(tenancy.clone());
+
+    let invoice = resolver.for_tenant("globex").unwrap().query::<Invoice>("Extract".into()).await.unwrap();
+    assert_eq!(invoice.first(), Some(&Invoice { total: 99.0 }));
+    assert!(globex_mock.is_empty());
+    assert_eq!(acme_mock.remaining_count(), 1);
+
+    let usage = tenancy.usage("globex").unwrap();
+    assert_eq!(usage.requests, 1);
+    assert!(usage.prompt_tokens > 0 && usage.completion_tokens > 0);
+    assert_eq!(tenancy.usage("acme").unwrap().requests, 0);
+    assert_eq!(tenancy.tenants(), vec!["acme", "globex"]);
+}
+
+#[tokio::test]
+async fn requests_over_the_rate_limit_are_refused() {
+    let (client, _handle) = invoice_mock();
+    let tenancy = Tenancy::new().with_tenant("acme", Box::new(client), TenantLimits::default().requests_per_minute(1));
+    let resolver = QueryResolver::new(MockVoid, RetryConfig::default()).with_tenancy(tenancy.clone());
+    let acme = resolver.for_tenant("acme").unwrap();
+
+    acme.query::<Invoice>("Extract".into()).await.unwrap();
+    let refused = acme.query::<Invoice>("Extract".into()).await;
+    assert!(matches!(
+        refused,
+        Err(QueryResolverError::Ai(AIError::TenantLimit { ref tenant, ref limit })) if tenant == "acme" && limit == "requests_per_minute"
+    ), "{refused:?}");
+
+    let usage = tenancy.usage("acme").unwrap();
+    assert_eq!((usage.requests, usage.rejected), (1, 1));
+}
+
+#[tokio::test]
+async fn budgets_cap_total_tokens() {
+    let (client, _handle) = invoice_mock();
+    let tenancy = Tenancy::new().with_tenant("acme", Box::new(client), TenantLimits::default().token_budget(5));
+    let resolver = QueryResolver::new(MockVoid, RetryConfig::default()).with_tenancy(tenancy.clone());
+
+    let refused = resolver.for_tenant("acme").unwrap().query::<Invoice>("Extract the invoice".into()).await;
+    assert!(matches!(refused, Err(QueryResolverError::Ai(AIError::TenantLimit { ref limit, .. })) if limit == "token_budget"));
+    assert_eq!(tenancy.usage_report()["acme"].rejected, 1);
+}
+
+#[tokio::test]
+async fn unknown_tenants_are_an_error() {
+    let resolver = QueryResolver::new(MockVoid, RetryConfig::default());
+    assert!(matches!(resolver.for_tenant("acme"), Err(QueryResolverError::Tenancy(_))));
+
+    let resolver = resolver.with_tenancy(Tenancy::new());
+    assert!(matches!(resolver.for_tenant("acme"), Err(QueryResolverError::Tenancy(e)) if e.contains("acme")));
+}
+
+#[tokio::test]
+async fn queries_are_attributed_to_the_tenant() {
+    let (client, handle) = invoice_mock();
+    let stats: Arc<Mutex<Vec<QueryStats>>> = Arc::default();
+    let sink = stats.clone();
+    let resolver = QueryResolver::new(MockVoid, RetryConfig::default())
+        .with_tenancy(Tenancy::new().with_tenant("acme", Box::new(client), TenantLimits::default()))
+        .with_stats_callback(move |s| sink.lock().unwrap().push(s.clone()));
+
+    resolver.for_tenant("acme").unwrap().query::<Invoice>("Extract".into()).await.unwrap();
+    assert_eq!(handle.calls()[0].tenant.as_deref(), Some("acme"));
+    assert_eq!(stats.lock().unwrap()[0].tenant.as_deref(), Some("acme"));
+}
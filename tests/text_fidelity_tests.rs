@@ -0,0 +1,74 @@
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, ResponseItem, RetryConfig};
+use semantic_query::streaming::{build_parsed_stream, build_parsed_stream_with, StreamItem, TextFidelity};
+use semantic_query::ParsedResponse;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Note {
+    text: String,
+}
+
+const RAW: &str = "Intro:\n\n{\"text\": \"a\"}\n  \n{\"wrap\": {\"text\": \"b\"}} {\"z\": 1}\n\nbye\n";
+
+fn rebuild(response: &ParsedResponse<Note>) -> String {
+    response.items.iter().map(|item| match item {
+        ResponseItem::Text(t) => t.text.as_str(),
+        ResponseItem::Data { original_text, .. } => original_text.as_str(),
+    }).collect()
+}
+
+#[test]
+fn exact_responses_rebuild_the_raw_text() {
+    let exact = ParsedResponse::<Note>::from_raw_with(RAW, TextFidelity::Exact);
+    assert_eq!(rebuild(&exact), RAW);
+    assert_eq!(exact.data_count(), 2);
+
+    let trimmed = ParsedResponse::<Note>::from_raw(RAW);
+    assert_eq!(trimmed.data_count(), 2);
+    assert!(trimmed.items.iter().all(|item| match item {
+        ResponseItem::Text(t) => !t.text.trim().is_empty(),
+        ResponseItem::Data { .. } => true,
+    }));
+}
+
+#[test]
+fn exact_parsed_streams_keep_whitespace_only_text() {
+    let texts = |items: Vec<StreamItem<Note>>| -> Vec<String> {
+        items.into_iter().filter_map(|item| match item {
+            StreamItem::Text(t) => Some(t.text),
+            _ => None,
+        }).collect()
+    };
+    let raw = "{\"text\": \"a\"}\n\n{\"text\": \"b\"}";
+    assert_eq!(texts(build_parsed_stream_with(raw, TextFidelity::Exact)), ["\n\n"]);
+    assert!(texts(build_parsed_stream(raw)).is_empty());
+}
+
+#[tokio::test]
+async fn resolver_applies_fidelity_to_queries_and_streams() {
+    let resolver = QueryResolver::new(ScriptedClient::Reply(RAW.into()), RetryConfig::default())
+        .with_text_fidelity(TextFidelity::Exact);
+    let response = resolver.query_mixed::<Note>("notes".into()).await.unwrap();
+    assert_eq!(rebuild(&response), RAW);
+
+    let chunks: Vec<Vec<u8>> = ["  Here", " they are:\n\n", "  {\"text\": ", "\"c\"}", "\n  done  "]
+        .iter().map(|c| c.as_bytes().to_vec()).collect();
+    let stream_text = |fidelity| {
+        let resolver = QueryResolver::new(ScriptedClient::Stream(chunks.clone()), RetryConfig::default())
+            .with_text_fidelity(fidelity);
+        async move {
+            resolver.stream_query::<Note>("notes".into()).await.unwrap()
+                .filter_map(|item| async move { match item.unwrap() {
+                    StreamItem::Text(t) => Some(t.text),
+                    _ => None,
+                }})
+                .collect::<Vec<_>>()
+                .await
+        }
+    };
+    assert_eq!(stream_text(TextFidelity::Exact).await, ["  Here they are:\n\n", "  ", "\n  done  "]);
+    assert_eq!(stream_text(TextFidelity::Trimmed).await, ["Here they are:", "done"]);
+}
@@ -0,0 +1,65 @@
+use bytes::Bytes;
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RawByteStream, RetryConfig};
+use semantic_query::streaming::{stream_from_sse_bytes_timed, StreamItem, StreamLatency, StreamOptions, Timed};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Answer {
+    value: u32,
+}
+
+fn sse(events: &[&str]) -> RawByteStream {
+    let body: Vec<Result<Bytes, _>> = events.iter().map(|e| Ok(Bytes::from(format!("{e}\n\n")))).collect();
+    Box::pin(futures_util::stream::iter(body))
+}
+
+fn delta(id: &str, content: &str) -> String {
+    format!("data: {}", serde_json::json!({ "id": id, "choices": [{ "delta": { "content": content } }] }))
+}
+
+#[tokio::test]
+async fn items_carry_token_counts_and_event_ids() {
+    let first = format!("id: evt-1\n{}", delta("chatcmpl-1", "Sure: "));
+    let second = delta("chatcmpl-1", r#"{"value": "#);
+    let third = format!("id: evt-3\n{}", delta("chatcmpl-1", "7}"));
+    let stream = sse(&[&first, &second, &third, "data: [DONE]"]);
+
+    let items: Vec<Timed<StreamItem<Answer>>> = stream_from_sse_bytes_timed(stream, StreamOptions::default())
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let tokens: Vec<_> = items.iter().filter(|t| matches!(t.item, StreamItem::Token(_))).collect();
+    assert_eq!(tokens.iter().map(|t| t.tokens).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(tokens[0].event_id.as_deref(), Some("evt-1"));
+    assert_eq!(tokens[1].event_id.as_deref(), Some("chatcmpl-1"));
+    assert_eq!(tokens[2].event_id.as_deref(), Some("evt-3"));
+
+    let data = items.iter().find(|t| matches!(t.item, StreamItem::Data(_))).unwrap();
+    assert!(matches!(data.item, StreamItem::Data(Answer { value: 7 })));
+    assert_eq!(data.tokens, 3);
+    assert!(items.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+}
+
+#[tokio::test]
+async fn latency_summary_from_resolver_stream() {
+    let client = ScriptedClient::Stream(vec![b"ok ".to_vec(), br#"{"value": 1}"#.to_vec()]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    let items: Vec<_> = resolver.stream_query_timed::<Answer>("go".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let latency = StreamLatency::measure(&items);
+    assert!(latency.first_token.is_some());
+    assert!(latency.first_data >= latency.first_token);
+    assert_eq!(latency.tokens, items.last().unwrap().tokens);
+
+    let replay: Vec<Timed<StreamItem<Answer>>> = serde_json::from_value(serde_json::to_value(
+        items.iter().filter(|t| !matches!(t.item, StreamItem::Token(_))).collect::<Vec<_>>(),
+    ).unwrap()).unwrap();
+    assert!(replay.iter().any(|t| matches!(t.item, StreamItem::Data(Answer { value: 1 }))));
+}
@@ -0,0 +1,84 @@
+use semantic_query::error::ToolError;
+use semantic_query::request::ToolSpec;
+use semantic_query::streaming::ToolCall;
+use semantic_query::tools::{convert, evaluate, ToolRegistry};
+use serde_json::{json, Value};
+
+fn call(registry: &ToolRegistry, name: &str, args: Value) -> Result<Value, ToolError> {
+    registry.call(name, &args)
+}
+
+#[test]
+fn built_ins_are_registered_by_default() {
+    let registry = ToolRegistry::default();
+    assert_eq!(registry.names(), ["calculator", "date_math", "unit_convert"]);
+    let specs = registry.specs();
+    assert!(specs.iter().all(|spec| !spec.description.is_empty() && spec.schema.is_object()));
+    assert!(specs[0].schema.to_string().contains("expression"));
+}
+
+#[test]
+fn calculator_follows_precedence() {
+    assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7.0);
+    assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9.0);
+    assert_eq!(evaluate("2 ^ 3 ^ 2").unwrap(), 512.0);
+    assert_eq!(evaluate("-2 ** 2").unwrap(), -4.0);
+    assert_eq!(evaluate("10 % 4 - -1").unwrap(), 3.0);
+    assert_eq!(evaluate("1.5e3 / 3").unwrap(), 500.0);
+    assert_eq!(evaluate("max(1, sqrt(16), min(9, 7)) + round(pi, 2)").unwrap(), 10.14);
+    assert_eq!(evaluate("log(1000) + log(8, 2)").unwrap(), 6.0);
+}
+
+#[test]
+fn calculator_rejects_bad_input() {
+    assert!(matches!(evaluate("1 / 0"), Err(ToolError::Failed { .. })));
+    assert!(matches!(evaluate("sqrt(-1)"), Err(ToolError::Failed { .. })));
+    assert!(matches!(evaluate("2 +"), Err(ToolError::InvalidArguments { .. })));
+    assert!(matches!(evaluate("system(\"ls\")"), Err(ToolError::InvalidArguments { .. })));
+    assert!(matches!(evaluate(&"(".repeat(500)), Err(ToolError::InvalidArguments { .. })));
+    assert!(matches!(evaluate(&"1+".repeat(1000)), Err(ToolError::InvalidArguments { .. })));
+}
+
+#[test]
+fn date_math_adds_diffs_and_names_weekdays() {
+    let registry = ToolRegistry::new();
+    let added = call(&registry, "date_math", json!({"op": "add", "date": "2024-01-31", "months": 1, "days": 1})).unwrap();
+    assert_eq!(added, json!({"date": "2024-03-01", "weekday": "Friday"}));
+
+    let diff = call(&registry, "date_math", json!({"op": "diff", "from": "2024-03-01", "to": "March 15, 2024"})).unwrap();
+    assert_eq!(diff, json!({"days": 14}));
+
+    let weekday = call(&registry, "date_math", json!({"op": "weekday", "date": "2000-01-01"})).unwrap();
+    assert_eq!(weekday["weekday"], "Saturday");
+
+    let bad = call(&registry, "date_math", json!({"op": "add", "date": "someday"}));
+    assert!(matches!(bad, Err(ToolError::InvalidArguments { .. })));
+}
+
+#[test]
+fn units_convert_within_a_dimension() {
+    assert!((convert(1.0, "mile", "km").unwrap() - 1.609344).abs() < 1e-9);
+    assert!((convert(2.0, "lbs", "kg").unwrap() - 0.90718474).abs() < 1e-9);
+    assert!((convert(100.0, "°C", "F").unwrap() - 212.0).abs() < 1e-9);
+    assert_eq!(convert(1.0, "GiB", "MiB").unwrap(), 1024.0);
+    assert_eq!(convert(90.0, "minutes", "hours").unwrap(), 1.5);
+    assert!(matches!(convert(1.0, "kg", "m"), Err(ToolError::InvalidArguments { .. })));
+    assert!(matches!(convert(1.0, "furlong", "m"), Err(ToolError::InvalidArguments { .. })));
+
+    let converted = call(&ToolRegistry::new(), "unit_convert", json!({"value": 3, "from": "ft", "to": "in"})).unwrap();
+    assert!((converted["value"].as_f64().unwrap() - 36.0).abs() < 1e-9);
+    assert_eq!(converted["unit"], "in");
+}
+
+#[test]
+fn registry_dispatches_custom_tools_and_unknown_names() {
+    let spec = ToolSpec::new("echo", "Repeat the input", json!({"type": "object"}));
+    let registry = ToolRegistry::empty().with_fn(spec, |args| Ok(args.clone())).with_tool(semantic_query::tools::Calculator);
+    assert_eq!(call(&registry, "echo", json!({"x": 1})).unwrap(), json!({"x": 1}));
+    assert_eq!(call(&registry, "calculator", json!({"expression": "6 * 7"})).unwrap(), json!({"result": 42.0}));
+    assert_eq!(call(&registry, "date_math", json!({})), Err(ToolError::UnknownTool("date_math".into())));
+    assert_eq!(registry.without("echo").names(), ["calculator"]);
+
+    let native = ToolCall { id: "toolu_1".into(), name: "unit_convert".into(), input: json!({"value": 1, "from": "km", "to": "m"}) };
+    assert_eq!(ToolRegistry::new().run(&native).unwrap(), json!({"value": 1000.0, "unit": "m"}));
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "cli")]
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use semantic_query::clients::{MockClient, MockResponse};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::error::AIError;
+use semantic_query::schema::JsonSchema;
+use semantic_query::telemetry;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(crate = "semantic_query::schemars")]
+struct Answer {
+    value: i32,
+}
+
+/// A span as it looked when closed: name, target, parent name and recorded fields
+#[derive(Debug, Clone, Default)]
+struct Captured {
+    name: &'static str,
+    target: String,
+    parent: Option<&'static str>,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for Captured {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.fields.insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+/// Collects every span in creation order
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<(u64, Captured)>>>);
+
+impl Capture {
+    fn named(&self, name: &str) -> Vec<Captured> {
+        self.0.lock().unwrap().iter().filter(|(_, span)| span.name == name).map(|(_, span)| span.clone()).collect()
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut span = Captured {
+            name: attrs.metadata().name(),
+            target: attrs.metadata().target().to_string(),
+            parent: ctx.span(id).and_then(|span| span.parent()).map(|parent| parent.name()),
+            ..Captured::default()
+        };
+        attrs.record(&mut span);
+        self.0.lock().unwrap().push((id.into_u64(), span));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        let mut spans = self.0.lock().unwrap();
+        if let Some((_, span)) = spans.iter_mut().rev().find(|(span_id, _)| *span_id == id.into_u64()) {
+            values.record(span);
+        }
+    }
+}
+
+#[tokio::test]
+async fn each_call_and_parse_gets_its_own_span() {
+    let capture = Capture::default();
+    let _guard = tracing::subscriber::set_default(Registry::default().with(capture.clone()));
+
+    let (client, handle) = MockClient::new();
+    handle.add_json_responses(vec![r#"{"value": 1}"#, r#"{"value": 2}"#]);
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    resolver.query::<Answer>("first".to_string()).await.unwrap();
+    resolver.query::<Answer>("second".to_string()).await.unwrap();
+
+    let attempts = capture.named("attempt");
+    assert_eq!(attempts.len(), 2);
+    for attempt in &attempts {
+        assert_eq!(attempt.target, telemetry::ATTEMPT);
+        assert_eq!(attempt.parent, Some("query"));
+        assert_eq!(attempt.fields["attempt"], "1", "attempts are numbered per query");
+        assert_eq!(attempt.fields["status"], "ok");
+        assert_eq!(attempt.fields["reply_len"], "12");
+    }
+
+    let extracts = capture.named("extract");
+    assert_eq!(extracts.len(), 2);
+    assert_eq!(extracts[0].target, telemetry::EXTRACT);
+    assert_eq!(extracts[0].fields["pass"], "1");
+    assert_eq!(extracts[0].fields["items_emitted"], "1");
+}
+
+#[tokio::test]
+async fn failed_attempts_record_the_retry_key() {
+    let capture = Capture::default();
+    let _guard = tracing::subscriber::set_default(Registry::default().with(capture.clone()));
+
+    let (client, handle) = MockClient::new();
+    handle.add_response(MockResponse::Error(AIError::Mock("down".to_string())));
+    let resolver = QueryResolver::new(client, RetryConfig::default());
+    assert!(resolver.query::<Answer>("q".to_string()).await.is_err());
+
+    let attempts = capture.named("attempt");
+    assert_eq!(attempts.len(), 1);
+    assert_eq!(attempts[0].fields["status"], "api_error");
+    assert!(!attempts[0].fields.contains_key("reply_len"));
+    assert!(capture.named("extract").is_empty());
+}
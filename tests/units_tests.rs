@@ -0,0 +1,71 @@
+use schemars::JsonSchema;
+use semantic_query::client_testkit::ScriptedClient;
+use semantic_query::core::{QueryResolver, RetryConfig};
+use semantic_query::postprocess::NormalizeUnits;
+use semantic_query::units::{convert, parse_quantity, unit_symbol, Quantity};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq)]
+struct Task {
+    title: String,
+    #[serde(with = "semantic_query::units::hours")]
+    #[schemars(schema_with = "semantic_query::units::hours::schema")]
+    effort: f64,
+    #[serde(default, with = "semantic_query::units::milliseconds::option")]
+    #[schemars(schema_with = "semantic_query::units::milliseconds::option::schema")]
+    timeout: Option<f64>,
+    size: Quantity,
+}
+
+#[test]
+fn quantities_parse_in_common_spellings() {
+    let cases = [
+        ("3.5 hours", 3.5, "h"),
+        ("2,5 Stunden", 2.5, "h"),
+        ("150ms", 150.0, "ms"),
+        ("1.234,5 km", 1234.5, "km"),
+        ("1,500 MB", 1500.0, "MB"),
+        ("1 500 kg", 1500.0, "kg"),
+        ("1h 30min", 1.5, "h"),
+        ("12 widgets", 12.0, "widgets"),
+        ("42", 42.0, ""),
+    ];
+    for (text, value, unit) in cases {
+        assert_eq!(parse_quantity(text), Some(Quantity { value, unit: unit.into() }), "{text}");
+    }
+    assert_eq!(parse_quantity("about three hours"), None);
+    assert_eq!(parse_quantity("1 h 2 kg"), None);
+
+    assert_eq!(unit_symbol("Minuten"), Some("min"));
+    assert_eq!(convert(90.0, "min", "h"), Some(1.5));
+    assert_eq!(convert(1.0, "h", "kg"), None);
+    assert_eq!(Quantity::new(2.0, "GiB").to("MiB"), Some(2048.0));
+}
+
+#[test]
+fn annotated_fields_convert_to_their_unit() {
+    let task: Task = serde_json::from_value(json!({
+        "title": "Migrate", "effort": "90 min", "timeout": "2 s", "size": {"value": "1,5", "unit": "gigabytes"},
+    })).unwrap();
+    assert_eq!(task, Task { title: "Migrate".into(), effort: 1.5, timeout: Some(2000.0), size: Quantity::new(1.5, "GB") });
+    assert_eq!(serde_json::to_value(&task).unwrap()["size"], json!({"value": 1.5, "unit": "GB"}));
+
+    let bare: Task = serde_json::from_value(json!({"title": "x", "effort": 3, "size": "10 KB"})).unwrap();
+    assert_eq!((bare.effort, bare.timeout), (3.0, None));
+
+    let error = serde_json::from_value::<Task>(json!({"title": "x", "effort": "2 kg", "size": 1})).unwrap_err();
+    assert!(error.to_string().contains("a quantity in hours"), "{error}");
+
+    let schema = serde_json::to_value(schemars::schema_for!(Task)).unwrap();
+    assert_eq!(schema["properties"]["effort"]["description"], "In hours");
+}
+
+#[tokio::test]
+async fn post_processor_normalizes_pointers() {
+    let reply = r#"{"name": "Backup", "duration": "1,5 Stunden", "retention": 30}"#;
+    let resolver = QueryResolver::new(ScriptedClient::Reply(reply.into()), RetryConfig::default())
+        .with_post_processor::<serde_json::Value>(NormalizeUnits::new().with_field("/duration", "min").with_field("/retention", "d"));
+    let response = resolver.query::<serde_json::Value>("jobs".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&json!({"name": "Backup", "duration": 90.0, "retention": 30})));
+}
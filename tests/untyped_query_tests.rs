@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use semantic_query::clients::mock::{MockClient, MockHandle};
+use semantic_query::core::{QueryResolver, RetryConfig};
+use serde_json::json;
+
+/// Stands in for a type from another crate: serde only, no `JsonSchema`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+fn resolver(reply: &str) -> (QueryResolver<MockClient>, Arc<MockHandle>) {
+    let (client, handle) = MockClient::new();
+    handle.add_json_response(reply);
+    (QueryResolver::new(client, RetryConfig::default()), handle)
+}
+
+#[tokio::test]
+async fn untyped_query_sends_the_prompt_as_is() {
+    let (resolver, handle) = resolver("The point is {\"x\": 1, \"y\": 2}.");
+    let response = resolver.query_untyped::<Point>("Where is it?".to_string()).await.unwrap();
+
+    assert_eq!(response.data_only(), vec![&Point { x: 1, y: 2 }]);
+    assert_eq!(handle.prompts(), ["Where is it?"]);
+}
+
+#[tokio::test]
+async fn untyped_query_extracts_arbitrary_json_values() {
+    let (resolver, _handle) = resolver("First {\"a\": 1} then {\"b\": [true]}");
+    let response = resolver.query_untyped::<serde_json::Value>("Anything".to_string()).await.unwrap();
+    assert_eq!(response.data_only(), vec![&json!({ "a": 1 }), &json!({ "b": [true] })]);
+}
+
+#[tokio::test]
+async fn free_text_schema_is_passed_on_verbatim() {
+    let (resolver, handle) = resolver("{\"x\": 3, \"y\": 4}");
+    let response = resolver
+        .query_mixed_unschema::<Point>("Where is it?".to_string(), "{ x: number, y: number }")
+        .await
+        .unwrap();
+
+    assert_eq!(response.first_required().unwrap(), Point { x: 3, y: 4 });
+    let prompt = handle.prompts()[0].clone();
+    assert!(prompt.starts_with("Where is it?\n\n## Response Format"));
+    assert!(prompt.contains("```\n{ x: number, y: number }\n```"));
+}
+
+#[tokio::test]
+async fn json_schema_text_gets_schema_wording() {
+    let (resolver, handle) = resolver("{\"x\": 3, \"y\": 4}");
+    let schema = r#"{"type": "object", "properties": {"x": {"type": "integer"}, "y": {"type": "integer"}}}"#;
+    resolver.query_mixed_unschema::<Point>("Where is it?".to_string(), schema).await.unwrap();
+
+    let prompt = handle.prompts()[0].clone();
+    assert!(prompt.contains("matching this schema"));
+    assert!(prompt.contains("\"properties\""));
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "web")]
+
+use std::time::Duration;
+
+use futures_util::{stream, StreamExt};
+use semantic_query::streaming::{FinishReason, StreamItem, TextContent};
+use semantic_query::web::{sse_body, web_events, websocket_messages, WebEvent, WebOptions};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+struct Answer {
+    value: u32,
+}
+
+fn items() -> Vec<Result<StreamItem<Answer>, String>> {
+    vec![
+        Ok(StreamItem::Token("{".into())),
+        Ok(StreamItem::Text(TextContent { text: "Here you go".into(), block: None })),
+        Ok(StreamItem::Data(Answer { value: 7 })),
+        Err("connection reset".into()),
+        Ok(StreamItem::Finished { reason: FinishReason::EarlyStopAfterData }),
+    ]
+}
+
+#[tokio::test]
+async fn events_have_stable_names_and_end() {
+    let events: Vec<WebEvent> = web_events(stream::iter(items()), WebOptions::default()).collect().await;
+    let names: Vec<&str> = events.iter().map(WebEvent::name).collect();
+    assert_eq!(names, ["token", "text", "data", "error", "finished", "end"]);
+    assert_eq!(events[2].data(), serde_json::json!({ "value": 7 }));
+    assert_eq!(events[3].data(), serde_json::json!({ "message": "connection reset" }));
+    assert_eq!(events[4].data(), serde_json::json!({ "reason": "EarlyStopAfterData" }));
+}
+
+#[tokio::test]
+async fn sse_frames_are_numbered() {
+    let options = WebOptions { end_event: false, ..WebOptions::default() };
+    let frames: Vec<String> = sse_body(stream::iter(items()), options)
+        .map(|frame| String::from_utf8(frame.unwrap().to_vec()).unwrap())
+        .collect()
+        .await;
+    assert_eq!(frames.len(), 5);
+    assert_eq!(frames[0], "id: 0\nevent: token\ndata: {\"token\":\"{\"}\n\n");
+    assert_eq!(frames[2], "id: 2\nevent: data\ndata: {\"value\":7}\n\n");
+
+    let options = WebOptions { event_ids: false, ..options };
+    let first = sse_body(stream::iter(items()), options).next().await.unwrap().unwrap();
+    assert!(first.starts_with(b"event: token\n"));
+}
+
+#[tokio::test]
+async fn websocket_frames_wrap_name_and_data() {
+    let frames: Vec<String> = websocket_messages(stream::iter(items()), WebOptions::default()).collect().await;
+    let first: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+    assert_eq!(first, serde_json::json!({ "event": "token", "data": { "token": "{" } }));
+    let last: serde_json::Value = serde_json::from_str(frames.last().unwrap()).unwrap();
+    assert_eq!(last, serde_json::json!({ "event": "end", "data": {} }));
+}
+
+#[tokio::test]
+async fn pings_fill_quiet_periods() {
+    let slow = stream::once(async {
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        Ok::<_, String>(StreamItem::<Answer>::Token("late".into()))
+    });
+    let options = WebOptions { keep_alive: Some(Duration::from_millis(25)), ..WebOptions::default() };
+    let events: Vec<WebEvent> = web_events(slow, options).collect().await;
+    let pings = events.iter().filter(|e| **e == WebEvent::Ping).count();
+    assert!(pings >= 2, "expected pings before the token, got {events:?}");
+    assert_eq!(events[events.len() - 2], WebEvent::Token("late".into()));
+    assert_eq!(events.last(), Some(&WebEvent::End));
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use schemars::JsonSchema;
+use semantic_query::error::{AIError, OpenAIError};
+use semantic_query::streaming::ws::{sse_from_ws_frames, OpenAIRealtimeDecoder, WsDecoder, WsEvent};
+use semantic_query::streaming::{stream_from_sse_bytes, StreamItem};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+struct Contact {
+    name: String,
+}
+
+/// Realtime server events spelling out `text` in small deltas, then `response.done`
+fn realtime_frames(text: &str) -> Vec<String> {
+    let mut frames = vec![json!({"type": "session.created", "session": {}}).to_string()];
+    let chars: Vec<char> = text.chars().collect();
+    for chunk in chars.chunks(4) {
+        let delta: String = chunk.iter().collect();
+        frames.push(json!({"type": "response.text.delta", "delta": delta}).to_string());
+    }
+    frames.push(json!({"type": "response.done", "response": {"status": "completed"}}).to_string());
+    frames
+}
+
+#[test]
+fn realtime_decoder_opens_a_text_response() {
+    let frames = OpenAIRealtimeDecoder::default().with_instructions("Be brief").open("Who wrote it?");
+    let frames: Vec<Value> = frames.iter().map(|f| serde_json::from_str(f).unwrap()).collect();
+    let types: Vec<&str> = frames.iter().map(|f| f["type"].as_str().unwrap()).collect();
+    assert_eq!(types, ["session.update", "conversation.item.create", "response.create"]);
+    assert_eq!(frames[0]["session"]["instructions"], "Be brief");
+    assert_eq!(frames[1]["item"]["content"][0]["text"], "Who wrote it?");
+    assert_eq!(frames[2]["response"]["modalities"], json!(["text"]));
+}
+
+#[test]
+fn realtime_decoder_reads_deltas_done_and_errors() {
+    let decoder = OpenAIRealtimeDecoder::default();
+    assert!(matches!(decoder.decode(r#"{"type":"response.text.delta","delta":"Hi"}"#), WsEvent::Delta(d) if d == "Hi"));
+    assert!(matches!(decoder.decode(r#"{"type":"response.output_text.delta","delta":"!"}"#), WsEvent::Delta(d) if d == "!"));
+    assert!(matches!(decoder.decode(r#"{"type":"response.done","response":{"status":"completed"}}"#), WsEvent::Done));
+    assert!(matches!(decoder.decode(r#"{"type":"rate_limits.updated"}"#), WsEvent::Skip));
+    assert!(matches!(decoder.decode("not json"), WsEvent::Skip));
+    assert!(matches!(
+        decoder.decode(r#"{"type":"error","error":{"message":"bad key"}}"#),
+        WsEvent::Error(AIError::OpenAI(OpenAIError::Api(m))) if m == "bad key"
+    ));
+}
+
+#[tokio::test]
+async fn frames_feed_the_sse_pipeline() {
+    let frames = realtime_frames(r#"Here: {"name": "Ada"}"#).into_iter().map(Ok);
+    let bytes = sse_from_ws_frames(futures_util::stream::iter(frames), Arc::new(OpenAIRealtimeDecoder::default()));
+    let items: Vec<_> = stream_from_sse_bytes::<Contact>(bytes).map(Result::unwrap).collect().await;
+
+    let tokens: String = items.iter().filter_map(|item| match item {
+        StreamItem::Token(token) => Some(token.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(tokens, r#"Here: {"name": "Ada"}"#);
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Data(c) if c.name == "Ada")));
+}
+
+#[tokio::test]
+async fn server_errors_end_the_stream() {
+    let frames = vec![
+        Ok(json!({"type": "response.text.delta", "delta": "{\"na"}).to_string()),
+        Ok(json!({"type": "error", "error": {"message": "session expired"}}).to_string()),
+    ];
+    let bytes = sse_from_ws_frames(futures_util::stream::iter(frames), Arc::new(OpenAIRealtimeDecoder::default()));
+    let chunks: Vec<_> = bytes.collect().await;
+    assert!(chunks[0].is_ok());
+    assert!(matches!(chunks.last(), Some(Err(AIError::OpenAI(OpenAIError::Api(m)))) if m == "session expired"));
+}
+
+/// A local WebSocket server that replays `realtime_frames(reply)` once the client has
+/// sent its three opening frames, returning what it received
+#[cfg(feature = "websocket")]
+async fn serve_realtime(reply: &'static str) -> (String, tokio::task::JoinHandle<Vec<Value>>) {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            if let Some(Ok(Message::Text(text))) = socket.next().await {
+                received.push(serde_json::from_str(&text).unwrap());
+            }
+        }
+        for frame in realtime_frames(reply) {
+            socket.send(Message::Text(frame)).await.unwrap();
+        }
+        let _ = socket.close(None).await;
+        received
+    });
+    (url, server)
+}
+
+#[cfg(feature = "websocket")]
+#[tokio::test]
+async fn ws_client_answers_queries() {
+    use semantic_query::clients::WsClient;
+    use semantic_query::core::{QueryResolver, RetryConfig};
+
+    let (url, server) = serve_realtime(r#"{"name": "Grace"}"#).await;
+    let client = WsClient::new(url, OpenAIRealtimeDecoder::default());
+    let resolver = QueryResolver::new(client, RetryConfig { default_max_retries: 0, ..RetryConfig::default() });
+
+    let response = resolver.query::<Contact>("Who?".into()).await.unwrap();
+    assert_eq!(response.first(), Some(&Contact { name: "Grace".into() }));
+    let received = server.await.unwrap();
+    assert!(received[1]["item"]["content"][0]["text"].as_str().unwrap().starts_with("Who?"));
+}
+
+#[cfg(feature = "websocket")]
+#[tokio::test]
+async fn ws_client_streams_queries() {
+    use semantic_query::clients::WsClient;
+    use semantic_query::core::{QueryResolver, RetryConfig};
+
+    let (url, server) = serve_realtime(r#"Found {"name": "Lin"} in the file"#).await;
+    let resolver = QueryResolver::new(WsClient::new(url, OpenAIRealtimeDecoder::default()), RetryConfig::default());
+
+    let items: Vec<_> = resolver.stream_query::<Contact>("Who?".into()).await.unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert!(items.iter().any(|item| matches!(item, StreamItem::Data(c) if c.name == "Lin")));
+    server.await.unwrap();
+}